@@ -0,0 +1,352 @@
+//! Answering messages without a terminal, for the chat bots: the same
+//! provider calls, hooks and tool calls as the chat, run until the model
+//! gives its final answer.
+//!
+//! Nobody is there to approve anything, and the people writing may not be
+//! the owner of the machine, so tools that read local files, the clipboard
+//! or long-term memory are not offered, `http_request` only reaches hosts
+//! on the allowlist, and no tool reaches this machine or its network unless
+//! the egress allowlist names the address.
+
+use crate::config::FetchConfig;
+use crate::conversation::{self, ConversationManager};
+use crate::diagnostics;
+use crate::fetch::Fetcher;
+use crate::hooks::CommandHooks;
+use crate::http::{Allowlist, Egress};
+use crate::memory::MemoryStore;
+use crate::message::{Message, ToolCall, ToolChoice, ToolDefinition};
+use crate::moderation::{Direction, Moderator, Verdict};
+use crate::ocr::Ocr;
+use crate::pii::Scrubber;
+use crate::plugins::PluginHost;
+use crate::provider::{ChatOptions, Provider};
+use crate::redact::Redactor;
+use crate::scripting::{HookOutcome, ScriptHooks};
+use crate::search::BraveSearchClient;
+use crate::tools::{self, Policy, RepeatGuard, ToolContext, ToolRegistry};
+
+/// Built-in tools that reach into the machine or the owner's data.
+const LOCAL_TOOLS: &[&str] = &[
+    "remember",
+    "search_docs",
+    "read_pdf",
+    "read_image",
+    "read_clipboard",
+];
+
+/// Model requests per message, so a model that keeps calling tools still
+/// comes to an end.
+const MAX_STEPS: usize = 10;
+
+pub struct Agent {
+    provider: Provider,
+    brave: BraveSearchClient,
+    tools: ToolRegistry,
+    hooks: ScriptHooks,
+    command_hooks: CommandHooks,
+    memory: MemoryStore,
+    ocr: Ocr,
+    fetcher: Fetcher,
+    http_allowlist: Allowlist,
+    /// Hosts any tool may contact at all.
+    egress: Egress,
+    /// Masks secrets in messages before they are sent.
+    redactor: Redactor,
+    /// Replaces personal data with placeholders in what the model sees,
+    /// and the placeholders with it again in replies.
+    pii: Option<Scrubber>,
+    moderator: Moderator,
+    /// Tool calls that may change something are refused.
+    read_only: bool,
+    /// Whether the workspace is trusted to run programs in, as decided in
+    /// `[trust]`; nobody is there to ask.
+    trusted: bool,
+}
+
+impl Agent {
+    pub fn new(
+        provider: Provider,
+        brave: BraveSearchClient,
+        plugins: PluginHost,
+        hooks: ScriptHooks,
+        command_hooks: CommandHooks,
+        ocr: Ocr,
+        http_allowlist: Allowlist,
+    ) -> Self {
+        Self {
+            provider,
+            brave,
+            tools: ToolRegistry::new(&plugins),
+            hooks,
+            command_hooks,
+            memory: MemoryStore::disabled(),
+            ocr,
+            fetcher: Fetcher::from_config(&FetchConfig::default(), &Egress::default().unattended()),
+            http_allowlist,
+            egress: Egress::default().unattended(),
+            redactor: Redactor::default(),
+            pii: None,
+            moderator: Moderator::default(),
+            read_only: false,
+            trusted: false,
+        }
+    }
+
+    /// The fetcher should check redirects against the same
+    /// [`Egress::unattended`] allowlist as [`Agent::set_egress`] sets.
+    pub fn set_fetcher(&mut self, fetcher: Fetcher) {
+        self.fetcher = fetcher;
+    }
+
+    /// Sets the egress allowlist, refusing local and private addresses it
+    /// does not name.
+    pub fn set_egress(&mut self, egress: Egress) {
+        self.egress = egress.unattended();
+    }
+
+    pub fn set_redactor(&mut self, redactor: Redactor) {
+        self.redactor = redactor;
+    }
+
+    pub fn set_pii(&mut self, pii: Scrubber) {
+        self.pii = Some(pii);
+    }
+
+    pub fn set_moderator(&mut self, moderator: Moderator) {
+        self.moderator = moderator;
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn set_trusted(&mut self, trusted: bool) {
+        self.trusted = trusted;
+    }
+
+    fn policy(&self) -> Policy<'_> {
+        Policy {
+            read_only: self.read_only,
+            trusted: Some(self.trusted),
+            egress: &self.egress,
+            allowlist: &self.http_allowlist,
+        }
+    }
+
+    /// The built-in tools that are safe to run for anyone, and the plugin
+    /// tools, but in read-only mode none that always change something, and
+    /// none that run programs in a workspace that is not trusted.
+    pub fn tools(&self) -> Vec<ToolDefinition> {
+        self.tools
+            .definitions()
+            .into_iter()
+            .filter(|tool| {
+                let name = tool.function.name.as_str();
+                !LOCAL_TOOLS.contains(&name)
+                    && self
+                        .tools
+                        .get(name)
+                        .is_some_and(|tool| self.policy().offers(tool.as_ref()))
+            })
+            .collect()
+    }
+
+    /// Adds `text` to `conversation` and returns the model's final answer,
+    /// calling tools along the way; `on_tool` hears of each call before it
+    /// runs. When a request fails, the last message is taken back, as in
+    /// the chat.
+    pub async fn reply(
+        &mut self,
+        conversation: &mut ConversationManager,
+        model: &str,
+        context_window: usize,
+        text: &str,
+        on_tool: &mut dyn FnMut(&ToolCall),
+    ) -> Result<String, String> {
+        let text = match self.hooks.on_user_message(text)? {
+            HookOutcome::Keep => text.to_string(),
+            HookOutcome::Replace(text) => text,
+            HookOutcome::Block(reason) => return Err(format!("Blocked by hook: {}", reason)),
+        };
+        let report = self.command_hooks.pre_submit(&text, model).await;
+        if let Some(reason) = report.blocked {
+            return Err(format!("Blocked by hook: {}", reason));
+        }
+        let (text, secrets) = self.redactor.redact(&text);
+        if !secrets.is_empty() {
+            diagnostics::log(&format!("masked {} in a message", secrets.join(", ")));
+        }
+        let text = match &mut self.pii {
+            Some(pii) => pii.scrub(&text),
+            None => text,
+        };
+        // Moderation sees the masked text, so a moderation service is not
+        // sent what the model would not be. Warnings are put before the
+        // reply, for everyone in the chat to see.
+        let mut warnings = Vec::new();
+        match self.moderator.check(&text, Direction::Input).await {
+            Verdict::Block(reason) => return Err(format!("Blocked by moderation: {}", reason)),
+            Verdict::Warn(reason) => warnings.push(reason),
+            Verdict::Allow => {}
+        }
+        conversation.add_user_message(&text);
+
+        let mut repeats = RepeatGuard::default();
+        for _ in 0..MAX_STEPS {
+            let tools = self.tools();
+            let overhead = conversation::estimate_tool_tokens(&tools);
+            let messages = match conversation.fit(context_window, overhead) {
+                Ok(messages) => messages,
+                Err(e) => {
+                    conversation.rewind_last_user_message();
+                    return Err(e);
+                }
+            };
+            // A model stuck calling the same tool is made to answer.
+            let options = ChatOptions {
+                tool_choice: repeats.exhausted().then_some(ToolChoice::None),
+                ..ChatOptions::default()
+            };
+            let mut response = match self
+                .provider
+                .chat_completion_with(model, &messages, Some(tools), &options)
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    conversation.rewind_last_user_message();
+                    return Err(e.to_string());
+                }
+            };
+
+            let Some(tool_calls) = response.tool_calls.clone() else {
+                let answer = response.content.clone().unwrap_or_default();
+                let answer = match self.hooks.on_response(&answer)? {
+                    HookOutcome::Keep => answer,
+                    HookOutcome::Replace(answer) => answer,
+                    HookOutcome::Block(reason) => {
+                        return Err(format!("Response blocked by hook: {}", reason))
+                    }
+                };
+                match self.moderator.check(&answer, Direction::Output).await {
+                    Verdict::Block(reason) => {
+                        return Err(format!("Answer blocked by moderation: {}", reason))
+                    }
+                    Verdict::Warn(reason) => warnings.push(reason),
+                    Verdict::Allow => {}
+                }
+                response.content = Some(answer.clone());
+                conversation.add_message(response);
+                self.command_hooks.post_response(&answer, model).await;
+                let answer = match &self.pii {
+                    Some(pii) => pii.restore(&answer),
+                    None => answer,
+                };
+                if warnings.is_empty() {
+                    return Ok(answer);
+                }
+                return Ok(format!(
+                    "Moderation warning: {}\n\n{}",
+                    warnings.join("; "),
+                    answer
+                ));
+            };
+
+            conversation.add_message(response);
+            for tool_call in &tool_calls {
+                on_tool(tool_call);
+                let output = self.run_tool(tool_call, &mut repeats).await;
+                let (output, secrets) = self.redactor.redact(&output);
+                if !secrets.is_empty() {
+                    diagnostics::log(&format!("masked {} in a tool result", secrets.join(", ")));
+                }
+                let output = match &mut self.pii {
+                    Some(pii) => pii.scrub(&output),
+                    None => output,
+                };
+                conversation.add_message(Message::tool(&output, &tool_call.id));
+            }
+        }
+        Err(format!(
+            "Gave up after {} requests without a final answer",
+            MAX_STEPS
+        ))
+    }
+
+    /// Runs one tool call and returns what the model is told about it.
+    async fn run_tool(&mut self, tool_call: &ToolCall, repeats: &mut RepeatGuard) -> String {
+        let tool_call = match self.hooks.on_tool_call(tool_call) {
+            Ok(HookOutcome::Keep) | Err(_) => tool_call.clone(),
+            Ok(HookOutcome::Replace(call)) => call,
+            Ok(HookOutcome::Block(reason)) => {
+                return format!("Error: This tool call was blocked: {}", reason)
+            }
+        };
+        let report = self.command_hooks.pre_tool_exec(&tool_call).await;
+        if let Some(reason) = report.blocked {
+            return format!("Error: This tool call was blocked: {}", reason);
+        }
+
+        let name = &tool_call.function.name;
+        let tool = match self.tools.get(name) {
+            Some(tool) if !LOCAL_TOOLS.contains(&name.as_str()) => tool,
+            _ => return format!("Error: The tool '{}' is not available here.", name),
+        };
+        let arguments = match tools::read_arguments(tool.as_ref(), &tool_call.function.arguments) {
+            Ok(arguments) => arguments,
+            Err(e) => return e.tool_message(name),
+        };
+        if repeats.is_repeat(name, &arguments) {
+            return repeats.tool_message(name);
+        }
+        if let Err(refusal) = self.policy().check(tool.as_ref(), &arguments).unattended() {
+            return refusal;
+        }
+
+        let context = ToolContext {
+            brave: &self.brave,
+            memory: &mut self.memory,
+            documents: None,
+            ocr: &self.ocr,
+            fetcher: &self.fetcher,
+        };
+        match tool.execute(&arguments, context).await {
+            Ok(output) => output,
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+}
+
+/// Splits an answer into messages of at most `limit` characters, at line
+/// ends where it can, for chat services that cap message length.
+pub(crate) fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut length = 0;
+    for mut line in text.split_inclusive('\n') {
+        loop {
+            let count = line.chars().count();
+            if length + count <= limit {
+                current.push_str(line);
+                length += count;
+                break;
+            }
+            if length > 0 {
+                pieces.push(std::mem::take(&mut current));
+                length = 0;
+                continue;
+            }
+            // A single line longer than a message.
+            let cut = line
+                .char_indices()
+                .nth(limit)
+                .map_or(line.len(), |(i, _)| i);
+            pieces.push(line[..cut].to_string());
+            line = &line[cut..];
+        }
+    }
+    pieces.push(current);
+    pieces.retain(|piece| !piece.trim().is_empty());
+    pieces
+}
@@ -0,0 +1,227 @@
+//! Paper search and abstracts from the arXiv API, for the `arxiv` tool.
+
+use roxmltree::{Document, Node};
+
+const API_URL: &str = "https://export.arxiv.org/api/query";
+
+/// Papers listed per search.
+const SEARCH_RESULTS: usize = 5;
+
+/// Characters of each abstract shown in search results.
+const SNIPPET_CHARS: usize = 300;
+
+/// Authors named in search results before "et al.".
+const LISTED_AUTHORS: usize = 3;
+
+/// One paper as listed in the API's Atom feed.
+struct Paper {
+    id: String,
+    title: String,
+    authors: Vec<String>,
+    summary: String,
+    published: String,
+    updated: String,
+    categories: Vec<String>,
+    comment: Option<String>,
+    journal: Option<String>,
+    doi: Option<String>,
+    pdf: Option<String>,
+}
+
+/// Searches titles, abstracts, authors or everything (`field`) for all the
+/// words in `query`. `newest` sorts by submission date instead of relevance.
+pub async fn search(query: &str, field: Option<&str>, newest: bool) -> Result<String, String> {
+    let prefix = match field.unwrap_or("all") {
+        "all" => "all",
+        "title" => "ti",
+        "abstract" => "abs",
+        "author" => "au",
+        other => {
+            return Err(format!(
+                "Unknown arXiv field '{}'; use all, title, abstract or author",
+                other
+            ))
+        }
+    };
+    let terms: Vec<String> = query
+        .split(|c: char| c.is_whitespace() || "\"():".contains(c))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:{}", prefix, word))
+        .collect();
+    if terms.is_empty() {
+        return Err("The arXiv search query is empty".to_string());
+    }
+
+    let search_query = terms.join(" AND ");
+    let max_results = SEARCH_RESULTS.to_string();
+    let sort = if newest { "submittedDate" } else { "relevance" };
+    let papers = fetch(&[
+        ("search_query", search_query.as_str()),
+        ("max_results", &max_results),
+        ("sortBy", sort),
+        ("sortOrder", "descending"),
+    ])
+    .await?;
+
+    let mut output = String::from("### arXiv Results\n\n");
+    if papers.is_empty() {
+        output.push_str("No papers found.\n");
+    }
+    for (i, paper) in papers.iter().enumerate() {
+        let mut authors = paper.authors[..paper.authors.len().min(LISTED_AUTHORS)].join(", ");
+        if paper.authors.len() > LISTED_AUTHORS {
+            authors.push_str(" et al.");
+        }
+        let mut snippet: String = paper.summary.chars().take(SNIPPET_CHARS).collect();
+        if snippet.len() < paper.summary.len() {
+            snippet.push('…');
+        }
+        output.push_str(&format!("{}. **{}**\n", i + 1, paper.title));
+        output.push_str(&format!("   - ID: {}\n", paper.id));
+        output.push_str(&format!("   - Authors: {}\n", authors));
+        output.push_str(&format!(
+            "   - Published: {} ({})\n",
+            paper.published,
+            paper.categories.first().map(String::as_str).unwrap_or("")
+        ));
+        output.push_str(&format!("   - Abstract: {}\n", snippet));
+        output.push_str(&format!("   - URL: https://arxiv.org/abs/{}\n\n", paper.id));
+    }
+    Ok(output)
+}
+
+/// The metadata and full abstract of one paper, by ID or arXiv URL.
+pub async fn paper(id: &str) -> Result<String, String> {
+    let id = normalize_id(id);
+    let papers = fetch(&[("id_list", id.as_str())]).await?;
+    let paper = papers
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("No arXiv paper with ID {}", id))?;
+
+    let mut output = format!("# {}\n\n", paper.title);
+    output.push_str(&format!("- ID: {}\n", paper.id));
+    output.push_str(&format!("- Authors: {}\n", paper.authors.join(", ")));
+    output.push_str(&format!("- Published: {}\n", paper.published));
+    if paper.updated != paper.published {
+        output.push_str(&format!("- Updated: {}\n", paper.updated));
+    }
+    output.push_str(&format!("- Categories: {}\n", paper.categories.join(", ")));
+    for (label, value) in [
+        ("Comment", &paper.comment),
+        ("Journal", &paper.journal),
+        ("DOI", &paper.doi),
+        ("PDF", &paper.pdf),
+    ] {
+        if let Some(value) = value {
+            output.push_str(&format!("- {}: {}\n", label, value));
+        }
+    }
+    output.push_str(&format!("- URL: https://arxiv.org/abs/{}\n\n", paper.id));
+    output.push_str(&format!("## Abstract\n\n{}\n", paper.summary));
+    Ok(output)
+}
+
+/// `2401.12345` from forms like `arXiv:2401.12345` or
+/// `https://arxiv.org/pdf/2401.12345.pdf`. Old-style IDs such as
+/// `hep-th/9901001` keep their archive.
+fn normalize_id(id: &str) -> String {
+    let id = id.trim();
+    let id = id
+        .strip_prefix("arXiv:")
+        .or_else(|| id.strip_prefix("arxiv:"))
+        .unwrap_or(id);
+    let id = match id.find("arxiv.org/") {
+        Some(start) => {
+            let path = &id[start + "arxiv.org/".len()..];
+            path.split_once('/').map_or(path, |(_, rest)| rest)
+        }
+        None => id,
+    };
+    id.trim_end_matches(".pdf")
+        .trim_end_matches('/')
+        .to_string()
+}
+
+async fn fetch(query: &[(&str, &str)]) -> Result<Vec<Paper>, String> {
+    let response = reqwest::Client::new()
+        .get(API_URL)
+        .query(query)
+        .send()
+        .await
+        .map_err(|e| format!("arXiv request failed: {}", e))?;
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("arXiv request failed: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("arXiv returned {}", status));
+    }
+    parse_feed(&text)
+}
+
+fn parse_feed(xml: &str) -> Result<Vec<Paper>, String> {
+    let document = Document::parse(xml).map_err(|e| format!("Invalid arXiv response: {}", e))?;
+    let mut papers = Vec::new();
+    for entry in document
+        .root_element()
+        .children()
+        .filter(|n| n.tag_name().name() == "entry")
+    {
+        let id_url = text(entry, "id").unwrap_or_default();
+        // Bad queries come back as a single entry describing the error.
+        if id_url.contains("/api/errors") {
+            return Err(format!(
+                "arXiv rejected the request: {}",
+                text(entry, "summary").unwrap_or_default()
+            ));
+        }
+        let link = |title: &str| {
+            entry
+                .children()
+                .find(|n| n.tag_name().name() == "link" && n.attribute("title") == Some(title))
+                .and_then(|n| n.attribute("href"))
+                .map(str::to_string)
+        };
+        papers.push(Paper {
+            id: id_url.rsplit("/abs/").next().unwrap_or(&id_url).to_string(),
+            title: text(entry, "title").unwrap_or_default(),
+            authors: entry
+                .children()
+                .filter(|n| n.tag_name().name() == "author")
+                .filter_map(|author| text(author, "name"))
+                .collect(),
+            summary: text(entry, "summary").unwrap_or_default(),
+            published: date(text(entry, "published")),
+            updated: date(text(entry, "updated")),
+            categories: entry
+                .children()
+                .filter(|n| n.tag_name().name() == "category")
+                .filter_map(|n| n.attribute("term").map(str::to_string))
+                .collect(),
+            comment: text(entry, "comment"),
+            journal: text(entry, "journal_ref"),
+            doi: text(entry, "doi"),
+            pdf: link("pdf"),
+        });
+    }
+    Ok(papers)
+}
+
+/// The text of the first child named `name`, with whitespace collapsed.
+fn text(node: Node, name: &str) -> Option<String> {
+    let child = node.children().find(|n| n.tag_name().name() == name)?;
+    let text = child
+        .text()?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// `2024-01-22` from `2024-01-22T18:59:59Z`.
+fn date(timestamp: Option<String>) -> String {
+    let timestamp = timestamp.unwrap_or_default();
+    timestamp.split('T').next().unwrap_or_default().to_string()
+}
@@ -0,0 +1,29 @@
+//! Opening web pages in the default browser, through `open` on macOS,
+//! `start` on Windows and `xdg-open` elsewhere.
+
+use std::process::{Command, Stdio};
+
+/// Opens `url` in the default browser, without waiting for it.
+pub fn open(url: &str) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        // The empty title keeps `start` from taking the URL for one.
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+        .map_err(|e| {
+            let program = command.get_program().to_string_lossy().into_owned();
+            format!("Could not open the browser with {}: {}", program, e)
+        })
+}
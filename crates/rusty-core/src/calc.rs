@@ -0,0 +1,1139 @@
+//! Exact arithmetic and unit conversion for the `calculate` tool.
+//!
+//! Numbers are big rationals, so `+ - * /`, integer powers, factorials and
+//! unit conversions are exact. Roots that are not exact, powers too large
+//! to compute, logarithms and trigonometry fall back to double precision,
+//! and results that went through them are marked with `≈`.
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+/// Significant digits shown for results that are not exact decimals.
+const DIGITS: usize = 30;
+
+/// Digits after the point up to which a terminating result is shown exactly.
+const MAX_EXACT_DECIMALS: u32 = 40;
+
+/// Largest exponent applied exactly; bigger powers are approximated.
+const MAX_EXACT_EXPONENT: i64 = 10_000;
+
+/// Largest power computed exactly, in bits of numerator and denominator
+/// (about 315,000 digits); bigger ones are approximated.
+const MAX_EXACT_BITS: u64 = 1 << 20;
+
+const MAX_FACTORIAL: u64 = 5_000;
+
+/// Base dimensions, in this order: metre, kilogram, second, kelvin, ampere,
+/// mole, byte.
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "K", "A", "mol", "B"];
+
+type Dims = [i8; 7];
+
+/// Units defined in terms of base units or each other. Names are tried
+/// before SI prefixes, so `min` is a minute and `mi` a mile.
+const UNITS: &[(&[&str], &str)] = &[
+    (&["meter", "meters", "metre", "metres"], "m"),
+    (
+        &["kilometer", "kilometers", "kilometre", "kilometres"],
+        "1000 m",
+    ),
+    (
+        &["centimeter", "centimeters", "centimetre", "centimetres"],
+        "0.01 m",
+    ),
+    (
+        &["millimeter", "millimeters", "millimetre", "millimetres"],
+        "0.001 m",
+    ),
+    (&["mi", "mile", "miles"], "1609.344 m"),
+    (&["yd", "yard", "yards"], "0.9144 m"),
+    (&["ft", "foot", "feet"], "0.3048 m"),
+    (&["in", "inch", "inches"], "0.0254 m"),
+    (&["nmi"], "1852 m"),
+    (&["au"], "149597870700 m"),
+    (&["ly", "lightyear", "lightyears"], "9460730472580800 m"),
+    (&["kilogram", "kilograms"], "kg"),
+    (&["g", "gram", "grams"], "0.001 kg"),
+    (&["t", "tonne", "tonnes"], "1000 kg"),
+    (&["lb", "lbs", "pound", "pounds"], "0.45359237 kg"),
+    (&["oz", "ounce", "ounces"], "lb/16"),
+    (&["st", "stone"], "14 lb"),
+    (&["sec", "secs", "second", "seconds"], "s"),
+    (&["min", "mins", "minute", "minutes"], "60 s"),
+    (&["h", "hr", "hrs", "hour", "hours"], "3600 s"),
+    (&["d", "day", "days"], "86400 s"),
+    (&["wk", "week", "weeks"], "7 d"),
+    (&["yr", "year", "years"], "365.25 d"),
+    (&["month", "months"], "yr/12"),
+    (&["kelvin"], "K"),
+    (&["amp", "amps", "ampere", "amperes"], "A"),
+    (&["Ah"], "A h"),
+    (&["V", "volt", "volts"], "W/A"),
+    (&["ohm", "ohms", "Ω"], "V/A"),
+    (&["byte", "bytes"], "B"),
+    (&["b", "bit", "bits"], "B/8"),
+    (&["KB"], "1000 B"),
+    (&["ha", "hectare", "hectares"], "10000 m^2"),
+    (&["acre", "acres"], "4046.8564224 m^2"),
+    (
+        &["L", "l", "liter", "liters", "litre", "litres"],
+        "0.001 m^3",
+    ),
+    (&["gal", "gallon", "gallons"], "3.785411784 L"),
+    (&["qt", "quart", "quarts"], "gal/4"),
+    (&["pt", "pint", "pints"], "gal/8"),
+    (&["cup", "cups"], "gal/16"),
+    (&["floz"], "gal/128"),
+    (&["tbsp"], "floz/2"),
+    (&["tsp"], "tbsp/3"),
+    (&["mph"], "mi/h"),
+    (&["kph", "kmh"], "km/h"),
+    (&["kn", "knot", "knots"], "nmi/h"),
+    (&["N", "newton", "newtons"], "kg m/s^2"),
+    (&["lbf"], "lb 9.80665 m/s^2"),
+    (&["J", "joule", "joules"], "N m"),
+    (&["cal", "calorie", "calories"], "4.184 J"),
+    (&["kcal", "Cal"], "1000 cal"),
+    (&["Wh"], "W h"),
+    (&["eV"], "1.602176634e-19 J"),
+    (&["BTU"], "1055.05585262 J"),
+    (&["W", "watt", "watts"], "J/s"),
+    (&["hp", "horsepower"], "550 ft lbf/s"),
+    (&["Pa", "pascal", "pascals"], "N/m^2"),
+    (&["bar"], "100000 Pa"),
+    (&["atm"], "101325 Pa"),
+    (&["psi"], "lbf/in^2"),
+    (&["mmHg"], "133.322387415 Pa"),
+    (&["Hz", "hertz"], "1/s"),
+    (&["rad", "radian", "radians"], "1"),
+    (&["deg", "degree", "degrees", "°"], "pi/180"),
+];
+
+/// Units that take SI prefixes (and, for data, binary ones).
+const PREFIXABLE: &[&str] = &[
+    "m", "g", "s", "L", "l", "W", "Wh", "J", "eV", "Hz", "Pa", "N", "V", "A", "Ah", "mol", "B",
+    "b", "bit",
+];
+
+const PREFIXES: &[(&str, &str)] = &[
+    ("Ki", "1024"),
+    ("Mi", "1048576"),
+    ("Gi", "1073741824"),
+    ("Ti", "1099511627776"),
+    ("Pi", "1125899906842624"),
+    ("P", "1e15"),
+    ("T", "1e12"),
+    ("G", "1e9"),
+    ("M", "1e6"),
+    ("k", "1e3"),
+    ("c", "1e-2"),
+    ("d", "1e-1"),
+    ("m", "1e-3"),
+    ("µ", "1e-6"),
+    ("u", "1e-6"),
+    ("n", "1e-9"),
+    ("p", "1e-12"),
+];
+
+/// Evaluates `expression`, converting the result when it ends in
+/// `to <unit>` (or `in`, `as`), and returns `expression = result`.
+pub fn evaluate(expression: &str) -> Result<String, String> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Err("The expression is empty".to_string());
+    }
+    let (source, target) = split_conversion(&tokens);
+
+    let mut parser = Parser::new(source);
+    let value = parser.expression()?;
+    parser.finish()?;
+
+    let result = match target {
+        Some(target) => {
+            let mut unit_parser = Parser::new(target);
+            let unit = unit_parser.expression()?;
+            unit_parser.finish()?;
+            let name = unit_name(target);
+            convert(&value, &unit, unit_parser.affine.as_ref(), &name)?
+        }
+        None => match parser.display_unit(&value) {
+            Some((name, unit, affine)) => convert(&value, &unit, affine.as_ref(), &name)?,
+            None => format_quantity(&value)?,
+        },
+    };
+    Ok(format!("{} = {}", expression.trim(), result))
+}
+
+#[derive(Clone, Debug)]
+enum Num {
+    Exact(BigRational),
+    Approx(f64),
+}
+
+impl Num {
+    fn integer(n: i64) -> Self {
+        Num::Exact(BigRational::from_integer(n.into()))
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            Num::Exact(r) => r.to_f64().unwrap_or(f64::NAN),
+            Num::Approx(f) => *f,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Num::Exact(r) => r.is_zero(),
+            Num::Approx(f) => *f == 0.0,
+        }
+    }
+
+    fn combine(
+        &self,
+        other: &Num,
+        exact: impl Fn(&BigRational, &BigRational) -> BigRational,
+        approx: impl Fn(f64, f64) -> f64,
+    ) -> Num {
+        match (self, other) {
+            (Num::Exact(a), Num::Exact(b)) => Num::Exact(exact(a, b)),
+            _ => Num::Approx(approx(self.to_f64(), other.to_f64())),
+        }
+    }
+
+    fn add(&self, other: &Num) -> Num {
+        self.combine(other, |a, b| a + b, |a, b| a + b)
+    }
+
+    fn sub(&self, other: &Num) -> Num {
+        self.combine(other, |a, b| a - b, |a, b| a - b)
+    }
+
+    fn mul(&self, other: &Num) -> Num {
+        self.combine(other, |a, b| a * b, |a, b| a * b)
+    }
+
+    fn div(&self, other: &Num) -> Result<Num, String> {
+        if other.is_zero() {
+            return Err("Division by zero".to_string());
+        }
+        Ok(self.combine(other, |a, b| a / b, |a, b| a / b))
+    }
+
+    /// The exact integer this is, if it is one.
+    fn as_integer(&self) -> Option<BigInt> {
+        match self {
+            Num::Exact(r) if r.is_integer() => Some(r.to_integer()),
+            _ => None,
+        }
+    }
+}
+
+/// A number with a dimension, kept in base units.
+#[derive(Clone, Debug)]
+struct Quantity {
+    value: Num,
+    dims: Dims,
+}
+
+impl Quantity {
+    fn number(value: Num) -> Self {
+        Self {
+            value,
+            dims: [0; 7],
+        }
+    }
+
+    fn is_dimensionless(&self) -> bool {
+        self.dims.iter().all(|&d| d == 0)
+    }
+}
+
+/// A temperature scale with a zero point other than absolute zero, as
+/// `kelvin = value * factor + offset`.
+#[derive(Clone, Debug)]
+struct Affine {
+    factor: BigRational,
+    offset: BigRational,
+}
+
+fn affine(name: &str) -> Option<Affine> {
+    let ratio = |n: i64, d: i64| BigRational::new(n.into(), d.into());
+    match name {
+        "°C" | "C" | "degC" | "celsius" | "Celsius" => Some(Affine {
+            factor: ratio(1, 1),
+            offset: ratio(27315, 100),
+        }),
+        "°F" | "F" | "degF" | "fahrenheit" | "Fahrenheit" => Some(Affine {
+            factor: ratio(5, 9),
+            offset: ratio(45967, 180),
+        }),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(String),
+    Name(String),
+    Op(char),
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match self {
+            Token::Number(s) | Token::Name(s) => s.clone(),
+            Token::Op(c) => c.to_string(),
+        }
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        matches!(self, Token::Name(n) if n == name)
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let mut number = String::new();
+            while i < chars.len() {
+                let c = chars[i];
+                let thousands = c == ','
+                    && chars[i + 1..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_digit())
+                        .count()
+                        == 3;
+                if c.is_ascii_digit() || c == '.' {
+                    number.push(c);
+                } else if c == '_' || thousands {
+                    // Digit separators.
+                } else if (c == 'e' || c == 'E')
+                    && (chars.get(i + 1).is_some_and(char::is_ascii_digit)
+                        || (matches!(chars.get(i + 1), Some('+' | '-'))
+                            && chars.get(i + 2).is_some_and(char::is_ascii_digit)))
+                {
+                    number.push('e');
+                    number.push(chars[i + 1]);
+                    i += 1;
+                } else {
+                    break;
+                }
+                i += 1;
+            }
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '°' || c == '_' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else {
+            let op = match c {
+                '×' | '·' => '*',
+                '÷' => '/',
+                '−' => '-',
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    i += 1;
+                    '^'
+                }
+                '-' if chars.get(i + 1) == Some(&'>') => {
+                    i += 1;
+                    '→'
+                }
+                '²' | '³' => {
+                    tokens.push(Token::Op('^'));
+                    tokens.push(Token::Number(if c == '²' { "2" } else { "3" }.to_string()));
+                    i += 1;
+                    continue;
+                }
+                '+' | '-' | '*' | '/' | '^' | '!' | '%' | '(' | ')' | '→' => c,
+                _ => return Err(format!("Unexpected character '{}'", c)),
+            };
+            tokens.push(Token::Op(op));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/// Splits `<expression> to <unit>` at its last `to`, `as`, `into` or `→`,
+/// or failing those at the last `in` with something on both sides (so
+/// `12 in` alone stays inches).
+fn split_conversion(tokens: &[Token]) -> (&[Token], Option<&[Token]>) {
+    let keyword = tokens.iter().rposition(|t| {
+        t.is_name("to") || t.is_name("as") || t.is_name("into") || *t == Token::Op('→')
+    });
+    let keyword = keyword.or_else(|| {
+        tokens
+            .iter()
+            .rposition(|t| t.is_name("in"))
+            .filter(|&i| i > 0 && i + 1 < tokens.len())
+    });
+    match keyword {
+        Some(i) if i > 0 && i + 1 < tokens.len() => (&tokens[..i], Some(&tokens[i + 1..])),
+        _ => (tokens, None),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    /// Units named in the expression, in order, for showing the result in
+    /// the user's own units.
+    seen: Vec<(String, Quantity)>,
+    /// Set when the expression is a bare affine temperature unit, such as a
+    /// conversion target of `°F`.
+    affine: Option<Affine>,
+    /// How many unit definitions deep this parser is, to catch loops.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            seen: Vec::new(),
+            affine: None,
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(format!("Unexpected '{}'", token.text())),
+        }
+    }
+
+    /// `term (('+' | '-') term)*`
+    fn expression(&mut self) -> Result<Quantity, String> {
+        let mut left = self.term()?;
+        loop {
+            let subtract = if self.eat('+') {
+                false
+            } else if self.eat('-') {
+                true
+            } else {
+                return Ok(left);
+            };
+            let right = self.term()?;
+            if left.dims != right.dims {
+                return Err(format!(
+                    "Cannot {} {} and {}",
+                    if subtract { "subtract" } else { "add" },
+                    dims_name(&left.dims),
+                    dims_name(&right.dims)
+                ));
+            }
+            left.value = if subtract {
+                left.value.sub(&right.value)
+            } else {
+                left.value.add(&right.value)
+            };
+        }
+    }
+
+    /// `product (('*' | '/' | 'mod') product)*`
+    fn term(&mut self) -> Result<Quantity, String> {
+        let mut left = self.product()?;
+        loop {
+            if self.eat('*') {
+                let right = self.product()?;
+                left = multiply(&left, &right, 1)?;
+            } else if self.eat('/') {
+                let right = self.product()?;
+                left = multiply(&left, &right, -1)?;
+            } else if self.peek().is_some_and(|t| t.is_name("mod")) {
+                self.position += 1;
+                let right = self.product()?;
+                left = modulo(&left, &right)?;
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    /// Juxtaposition, as in `5 km` or `2 pi`, binding tighter than `*` and
+    /// `/` so that `60 mi / 2 h` reads as expected.
+    fn product(&mut self) -> Result<Quantity, String> {
+        let mut left = self.unary()?;
+        while let Some(token) = self.peek() {
+            let starts_factor = match token {
+                Token::Number(_) | Token::Op('(') => true,
+                Token::Name(name) => !matches!(name.as_str(), "to" | "as" | "into" | "mod"),
+                _ => false,
+            };
+            if !starts_factor {
+                break;
+            }
+            // A number followed by a temperature scale is a temperature.
+            if let (Some(Token::Name(name)), true) = (self.peek(), left.is_dimensionless()) {
+                if let Some(scale) = affine(name) {
+                    let name = name.clone();
+                    self.position += 1;
+                    self.seen.push((name, kelvin(&scale.factor)));
+                    left = Quantity {
+                        value: left
+                            .value
+                            .mul(&Num::Exact(scale.factor))
+                            .add(&Num::Exact(scale.offset)),
+                        dims: kelvin_dims(),
+                    };
+                    continue;
+                }
+            }
+            // `5 ft 2 in` is a sum, as measurements are written.
+            if !left.is_dimensionless() && matches!(self.peek(), Some(Token::Number(_))) {
+                let right = self.product()?;
+                if right.dims == left.dims {
+                    left.value = left.value.add(&right.value);
+                } else {
+                    left = multiply(&left, &right, 1)?;
+                }
+                continue;
+            }
+            let right = self.power()?;
+            left = multiply(&left, &right, 1)?;
+        }
+        Ok(left)
+    }
+
+    /// `'-' unary | power`
+    fn unary(&mut self) -> Result<Quantity, String> {
+        if self.eat('-') {
+            let mut value = self.unary()?;
+            value.value = Num::integer(0).sub(&value.value);
+            Ok(value)
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    /// `postfix ('^' unary)?`, right-associative.
+    fn power(&mut self) -> Result<Quantity, String> {
+        let base = self.postfix()?;
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            return power(&base, &exponent);
+        }
+        Ok(base)
+    }
+
+    /// `primary ('!' | '%')*`
+    fn postfix(&mut self) -> Result<Quantity, String> {
+        let mut value = self.primary()?;
+        loop {
+            if self.eat('!') {
+                value = factorial(&value)?;
+            } else if self.eat('%') {
+                value.value = value.value.div(&Num::integer(100))?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Quantity, String> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or("The expression ends too early")?;
+        self.position += 1;
+        match token {
+            Token::Number(text) => Ok(Quantity::number(Num::Exact(parse_decimal(&text)?))),
+            Token::Op('(') => {
+                let value = self.expression()?;
+                if !self.eat(')') {
+                    return Err("Missing ')'".to_string());
+                }
+                Ok(value)
+            }
+            Token::Name(name) => {
+                if self.peek() == Some(&Token::Op('(')) {
+                    self.position += 1;
+                    let argument = self.expression()?;
+                    if !self.eat(')') {
+                        return Err("Missing ')'".to_string());
+                    }
+                    return function(&name, &argument);
+                }
+                match name.as_str() {
+                    "pi" | "π" => return Ok(Quantity::number(Num::Approx(std::f64::consts::PI))),
+                    "tau" | "τ" => {
+                        return Ok(Quantity::number(Num::Approx(std::f64::consts::TAU)))
+                    }
+                    "e" => return Ok(Quantity::number(Num::Approx(std::f64::consts::E))),
+                    _ => {}
+                }
+                if let Some(scale) = affine(&name) {
+                    // A bare scale, as in `to °F`: its size, remembered for the conversion.
+                    self.affine = Some(scale.clone());
+                    let unit = kelvin(&scale.factor);
+                    self.seen.push((name, unit.clone()));
+                    return Ok(unit);
+                }
+                let unit = unit(&name, self.depth)?;
+                self.seen.push((name, unit.clone()));
+                Ok(unit)
+            }
+            token => Err(format!("Unexpected '{}'", token.text())),
+        }
+    }
+
+    /// The first unit the user wrote with the result's dimension, if the
+    /// result has one.
+    fn display_unit(&self, value: &Quantity) -> Option<(String, Quantity, Option<Affine>)> {
+        if value.is_dimensionless() {
+            return None;
+        }
+        self.seen
+            .iter()
+            .find(|(_, unit)| unit.dims == value.dims)
+            .map(|(name, unit)| (name.clone(), unit.clone(), affine(name)))
+    }
+}
+
+fn kelvin_dims() -> Dims {
+    let mut dims = [0; 7];
+    dims[3] = 1;
+    dims
+}
+
+fn kelvin(factor: &BigRational) -> Quantity {
+    Quantity {
+        value: Num::Exact(factor.clone()),
+        dims: kelvin_dims(),
+    }
+}
+
+/// The size of the unit `name` in base units.
+fn unit(name: &str, depth: usize) -> Result<Quantity, String> {
+    if depth > 8 {
+        return Err(format!("Unit '{}' is defined in a loop", name));
+    }
+    if let Some(index) = BASE_UNITS.iter().position(|&base| base == name) {
+        let mut dims = [0; 7];
+        dims[index] = 1;
+        return Ok(Quantity {
+            value: Num::integer(1),
+            dims,
+        });
+    }
+    if let Some((_, definition)) = UNITS.iter().find(|(names, _)| names.contains(&name)) {
+        return define(definition, depth);
+    }
+    for (prefix, factor) in PREFIXES {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            let binary = prefix.ends_with('i');
+            let allowed = if binary {
+                matches!(rest, "B" | "b" | "bit")
+            } else {
+                PREFIXABLE.contains(&rest)
+            };
+            if allowed {
+                let base = unit(rest, depth + 1)?;
+                let factor = Num::Exact(parse_decimal(factor)?);
+                return Ok(Quantity {
+                    value: base.value.mul(&factor),
+                    dims: base.dims,
+                });
+            }
+        }
+    }
+    Err(format!("Unknown unit or name '{}'", name))
+}
+
+fn define(definition: &str, depth: usize) -> Result<Quantity, String> {
+    let tokens = tokenize(definition)?;
+    let mut parser = Parser::new(&tokens);
+    parser.depth = depth + 1;
+    let value = parser.expression()?;
+    parser.finish()?;
+    Ok(value)
+}
+
+fn multiply(left: &Quantity, right: &Quantity, sign: i8) -> Result<Quantity, String> {
+    let mut dims = left.dims;
+    for (d, r) in dims.iter_mut().zip(right.dims) {
+        *d = d
+            .checked_add(r * sign)
+            .ok_or("Unit exponent out of range")?;
+    }
+    let value = if sign > 0 {
+        left.value.mul(&right.value)
+    } else {
+        left.value.div(&right.value)?
+    };
+    Ok(Quantity { value, dims })
+}
+
+fn modulo(left: &Quantity, right: &Quantity) -> Result<Quantity, String> {
+    if left.dims != right.dims {
+        return Err("mod needs both sides in the same unit".to_string());
+    }
+    if right.value.is_zero() {
+        return Err("Division by zero".to_string());
+    }
+    let value = match (&left.value, &right.value) {
+        (Num::Exact(a), Num::Exact(b)) => Num::Exact(a - b * (a / b).floor()),
+        (a, b) => Num::Approx(a.to_f64().rem_euclid(b.to_f64())),
+    };
+    Ok(Quantity {
+        value,
+        dims: left.dims,
+    })
+}
+
+fn power(base: &Quantity, exponent: &Quantity) -> Result<Quantity, String> {
+    if !exponent.is_dimensionless() {
+        return Err("An exponent cannot have a unit".to_string());
+    }
+    if let Some(n) = exponent.value.as_integer() {
+        let n = n.to_i64().filter(|n| n.abs() <= MAX_EXACT_EXPONENT);
+        if let Some(n) = n {
+            let mut dims = base.dims;
+            for d in dims.iter_mut() {
+                *d = i8::try_from(*d as i64 * n).map_err(|_| "Unit exponent out of range")?;
+            }
+            let value = match &base.value {
+                Num::Exact(r) if r.is_zero() && n < 0 => return Err("Division by zero".to_string()),
+                Num::Exact(r) if fits(r, n) => Num::Exact(r.pow(n as i32)),
+                Num::Exact(r) => Num::Approx(r.to_f64().unwrap_or(f64::NAN).powi(n as i32)),
+                Num::Approx(f) => Num::Approx(f.powi(n as i32)),
+            };
+            return Ok(Quantity { value, dims });
+        }
+    }
+
+    // A fractional power of a unit only makes sense when it divides evenly,
+    // as in (9 m^2)^0.5.
+    let dims = if base.is_dimensionless() {
+        base.dims
+    } else {
+        let Num::Exact(exponent) = &exponent.value else {
+            return Err("A unit cannot be raised to an inexact power".to_string());
+        };
+        let mut dims = base.dims;
+        for d in dims.iter_mut() {
+            let scaled = exponent * BigRational::from_integer((*d).into());
+            if !scaled.is_integer() {
+                return Err(format!(
+                    "Cannot raise {} to this power",
+                    dims_name(&base.dims)
+                ));
+            }
+            *d = scaled
+                .to_integer()
+                .to_i8()
+                .ok_or("Unit exponent out of range")?;
+        }
+        dims
+    };
+    if let (Num::Exact(b), Num::Exact(e)) = (&base.value, &exponent.value) {
+        if let Some(root) = exact_root(b, e) {
+            return Ok(Quantity {
+                value: Num::Exact(root),
+                dims,
+            });
+        }
+    }
+    Ok(Quantity {
+        value: Num::Approx(base.value.to_f64().powf(exponent.value.to_f64())),
+        dims,
+    })
+}
+
+/// `base^exponent` when the exponent is `p/q` and `base` has an exact
+/// rational `q`th root.
+fn exact_root(base: &BigRational, exponent: &BigRational) -> Option<BigRational> {
+    let q = exponent.denom().to_u32().filter(|&q| q <= 64)?;
+    let p = exponent
+        .numer()
+        .to_i32()
+        .filter(|p| p.abs() as i64 <= MAX_EXACT_EXPONENT)?;
+    if base.is_negative() || (base.is_zero() && p < 0) {
+        return None;
+    }
+    let root = |n: &BigInt| {
+        let r = n.nth_root(q);
+        (r.pow(q) == *n).then_some(r)
+    };
+    let root = BigRational::new(root(base.numer())?, root(base.denom())?);
+    fits(&root, p as i64).then(|| root.pow(p))
+}
+
+/// Whether `r^n` is small enough to compute exactly.
+fn fits(r: &BigRational, n: i64) -> bool {
+    (r.numer().bits() + r.denom().bits()).saturating_mul(n.unsigned_abs()) <= MAX_EXACT_BITS
+}
+
+fn factorial(value: &Quantity) -> Result<Quantity, String> {
+    let n = value
+        .value
+        .as_integer()
+        .filter(|n| !n.is_negative() && value.is_dimensionless())
+        .ok_or("Factorial needs a whole number without a unit")?;
+    let n = n
+        .to_u64()
+        .filter(|&n| n <= MAX_FACTORIAL)
+        .ok_or_else(|| format!("Factorials above {}! are too large", MAX_FACTORIAL))?;
+    let mut result = BigInt::one();
+    for i in 2..=n {
+        result *= i;
+    }
+    Ok(Quantity::number(Num::Exact(BigRational::from_integer(
+        result,
+    ))))
+}
+
+fn function(name: &str, argument: &Quantity) -> Result<Quantity, String> {
+    let value = &argument.value;
+    match name {
+        "sqrt" => {
+            let half = Quantity::number(Num::Exact(BigRational::new(1.into(), 2.into())));
+            return power(argument, &half);
+        }
+        "cbrt" => {
+            let third = Quantity::number(Num::Exact(BigRational::new(1.into(), 3.into())));
+            return power(argument, &third);
+        }
+        "abs" | "floor" | "ceil" | "round" => {
+            let value = match value {
+                Num::Exact(r) => Num::Exact(match name {
+                    "abs" => r.abs(),
+                    "floor" => r.floor(),
+                    "ceil" => r.ceil(),
+                    _ => r.round(),
+                }),
+                Num::Approx(f) => Num::Approx(match name {
+                    "abs" => f.abs(),
+                    "floor" => f.floor(),
+                    "ceil" => f.ceil(),
+                    _ => f.round(),
+                }),
+            };
+            return Ok(Quantity {
+                value,
+                dims: argument.dims,
+            });
+        }
+        _ => {}
+    }
+
+    if !argument.is_dimensionless() {
+        return Err(format!("{}() needs a number without a unit", name));
+    }
+    let x = value.to_f64();
+    let result = match name {
+        "ln" => x.ln(),
+        "log" | "log10" => x.log10(),
+        "log2" => x.log2(),
+        "exp" => x.exp(),
+        "sin" => x.sin(),
+        "cos" => x.cos(),
+        "tan" => x.tan(),
+        "asin" => x.asin(),
+        "acos" => x.acos(),
+        "atan" => x.atan(),
+        "sinh" => x.sinh(),
+        "cosh" => x.cosh(),
+        "tanh" => x.tanh(),
+        _ => return Err(format!("Unknown function '{}'", name)),
+    };
+    Ok(Quantity::number(Num::Approx(result)))
+}
+
+/// `value` expressed in `unit`, such as `3.106855961186669 mi`.
+fn convert(
+    value: &Quantity,
+    unit: &Quantity,
+    affine: Option<&Affine>,
+    name: &str,
+) -> Result<String, String> {
+    if value.dims != unit.dims {
+        return Err(format!(
+            "Cannot convert {} to {}",
+            dims_name(&value.dims),
+            dims_name(&unit.dims)
+        ));
+    }
+    let converted = match affine {
+        Some(scale) => value
+            .value
+            .sub(&Num::Exact(scale.offset.clone()))
+            .div(&Num::Exact(scale.factor.clone()))?,
+        None => value.value.div(&unit.value)?,
+    };
+    with_unit(&converted, name)
+}
+
+fn format_quantity(value: &Quantity) -> Result<String, String> {
+    if value.is_dimensionless() {
+        with_unit(&value.value, "")
+    } else {
+        with_unit(&value.value, &dims_name(&value.dims))
+    }
+}
+
+/// `≈ 3.10685… mi (exactly 78125/25146 mi)`, the fraction given when it is
+/// short enough to be useful.
+fn with_unit(value: &Num, unit: &str) -> Result<String, String> {
+    let unit = if unit.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", unit)
+    };
+    let (number, fraction) = format_num(value)?;
+    Ok(match fraction {
+        Some(fraction) => format!("{}{} (exactly {}{})", number, unit, fraction, unit),
+        None => format!("{}{}", number, unit),
+    })
+}
+
+/// The unit as written, without spaces around operators: `km/h`, `kg m/s^2`.
+fn unit_name(tokens: &[Token]) -> String {
+    let mut name = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let spaced =
+            i > 0 && !matches!(token, Token::Op(_)) && !matches!(tokens[i - 1], Token::Op(_));
+        if spaced {
+            name.push(' ');
+        }
+        name.push_str(&token.text());
+    }
+    name
+}
+
+/// A dimension in base units, such as `m/s^2`.
+fn dims_name(dims: &Dims) -> String {
+    if dims.iter().all(|&d| d == 0) {
+        return "a plain number".to_string();
+    }
+    let part = |unit: &str, power: i8| match power {
+        1 => unit.to_string(),
+        _ => format!("{}^{}", unit, power),
+    };
+    let above: Vec<String> = BASE_UNITS
+        .iter()
+        .zip(dims)
+        .filter(|(_, &d)| d > 0)
+        .map(|(unit, &d)| part(unit, d))
+        .collect();
+    let below: Vec<String> = BASE_UNITS
+        .iter()
+        .zip(dims)
+        .filter(|(_, &d)| d < 0)
+        .map(|(unit, &d)| part(unit, -d))
+        .collect();
+    match (above.is_empty(), below.is_empty()) {
+        (_, true) => above.join("·"),
+        (true, false) => format!("1/{}", below.join("·")),
+        (false, false) => format!("{}/{}", above.join("·"), below.join("·")),
+    }
+}
+
+/// The number as text, and as a fraction when it is exact but has no short
+/// decimal form.
+fn format_num(value: &Num) -> Result<(String, Option<String>), String> {
+    match value {
+        Num::Exact(r) => Ok(format_exact(r)),
+        Num::Approx(f) if f.is_finite() => {
+            let r = BigRational::from_float(*f).ok_or("The result is not a number")?;
+            Ok((format!("≈ {}", to_decimal(&r, 15)), None))
+        }
+        Num::Approx(_) => Err("The result is not a finite number".to_string()),
+    }
+}
+
+/// Integers and terminating decimals in full; anything else as a rounded
+/// decimal, with the fraction when it is short.
+fn format_exact(r: &BigRational) -> (String, Option<String>) {
+    if r.is_integer() {
+        return (r.to_integer().to_string(), None);
+    }
+    // A fraction terminates when its denominator is 2^a·5^b, after max(a, b)
+    // decimals.
+    let mut denom = r.denom().clone();
+    let mut decimals = [0u32; 2];
+    for (count, factor) in decimals.iter_mut().zip([2, 5]) {
+        let factor = BigInt::from(factor);
+        while denom.is_multiple_of(&factor) {
+            denom /= &factor;
+            *count += 1;
+        }
+    }
+    let decimals = decimals[0].max(decimals[1]);
+    if denom.is_one() && decimals <= MAX_EXACT_DECIMALS {
+        let scaled = (r * BigRational::from_integer(BigInt::from(10).pow(decimals))).to_integer();
+        return (place_point(&scaled, decimals as i64), None);
+    }
+    let approx = format!("≈ {}", to_decimal(r, DIGITS));
+    let short = r.numer().to_string().len() <= 15 && r.denom().to_string().len() <= 15;
+    (approx, short.then(|| r.to_string()))
+}
+
+/// `r` rounded to `digits` significant digits, in scientific notation when
+/// very large or small.
+fn to_decimal(r: &BigRational, digits: usize) -> String {
+    if r.is_zero() {
+        return "0".to_string();
+    }
+    let ten = BigRational::from_integer(10.into());
+    let magnitude = r.abs();
+    let mut exponent =
+        magnitude.numer().to_string().len() as i64 - magnitude.denom().to_string().len() as i64;
+    while pow10(exponent) > magnitude {
+        exponent -= 1;
+    }
+    while pow10(exponent + 1) <= magnitude {
+        exponent += 1;
+    }
+    let scale = digits as i64 - 1 - exponent;
+    let mut scaled = (r * pow10(scale)).round().to_integer();
+    // Rounding up can add a digit, as 9.99… becomes 10.0….
+    if scaled.abs().to_string().len() > digits {
+        scaled = (BigRational::from_integer(scaled) / &ten)
+            .round()
+            .to_integer();
+        exponent += 1;
+    }
+    let scale = digits as i64 - 1 - exponent;
+
+    if !(-10..=40).contains(&exponent) {
+        let sign = if scaled.is_negative() { "-" } else { "" };
+        let mantissa = place_point(&scaled.abs(), digits as i64 - 1);
+        return format!("{}{}e{}", sign, mantissa, exponent);
+    }
+    if scale <= 0 {
+        return (scaled * BigInt::from(10).pow((-scale) as u32)).to_string();
+    }
+    place_point(&scaled, scale)
+}
+
+fn pow10(exponent: i64) -> BigRational {
+    let power = BigRational::from_integer(BigInt::from(10).pow(exponent.unsigned_abs() as u32));
+    if exponent < 0 {
+        power.recip()
+    } else {
+        power
+    }
+}
+
+/// `scaled / 10^decimals` written out, without trailing zeros.
+fn place_point(scaled: &BigInt, decimals: i64) -> String {
+    let sign = if scaled.is_negative() { "-" } else { "" };
+    let digits = scaled.abs().to_string();
+    let decimals = decimals as usize;
+    let padded = if digits.len() <= decimals {
+        format!("{}{}", "0".repeat(decimals + 1 - digits.len()), digits)
+    } else {
+        digits
+    };
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// An exact rational from decimal text such as `1.5e-3`.
+fn parse_decimal(text: &str) -> Result<BigRational, String> {
+    let invalid = || format!("Invalid number '{}'", text);
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().map_err(|_| invalid())?),
+        None => (text, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if fraction.contains('.') || (whole.is_empty() && fraction.is_empty()) {
+        return Err(invalid());
+    }
+    let digits: BigInt = format!("{}{}", whole, fraction)
+        .parse()
+        .map_err(|_| invalid())?;
+    let exponent = exponent - fraction.len() as i64;
+    if exponent.abs() > 10_000 {
+        return Err(invalid());
+    }
+    Ok(BigRational::from_integer(digits) * pow10(exponent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(expression: &str) -> String {
+        let answer = evaluate(expression).unwrap();
+        answer
+            .split_once(" = ")
+            .map_or(answer.clone(), |(_, result)| result.to_string())
+    }
+
+    #[test]
+    fn keeps_operator_precedence() {
+        assert_eq!(result("2 + 3 * 4"), "14");
+        assert_eq!(result("(2 + 3) * 4"), "20");
+        assert_eq!(result("2 ^ 3 ^ 2"), "512");
+    }
+
+    #[test]
+    fn is_exact_with_fractions_and_big_numbers() {
+        assert_eq!(result("0.1 + 0.2"), "0.3");
+        assert_eq!(result("2 ^ 100"), "1267650600228229401496703205376");
+        assert_eq!(result("20!"), "2432902008176640000");
+    }
+
+    #[test]
+    fn converts_units() {
+        assert_eq!(result("1 km to m"), "1000 m");
+        assert_eq!(result("2 h in min"), "120 min");
+        assert_eq!(result("100 degC to degF"), "212 degF");
+    }
+
+    #[test]
+    fn refuses_what_it_cannot_compute() {
+        assert!(evaluate("").is_err());
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("2 +").is_err());
+        assert!(evaluate("1 kg to m").is_err());
+    }
+
+    #[test]
+    fn approximates_powers_too_large_to_compute_exactly() {
+        assert_eq!(result("(10^100)^100"), format!("1{}", "0".repeat(10_000)));
+        assert_eq!(
+            evaluate("(10^10000)^1000").unwrap_err(),
+            "The result is not a finite number"
+        );
+        assert_eq!(result("(10^10000)^-1000"), "≈ 0");
+    }
+}
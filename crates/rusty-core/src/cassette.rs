@@ -0,0 +1,124 @@
+//! Cassettes: the requests made to the model and to Brave Search and the
+//! responses they got, written to a JSON file as they happen so that the
+//! same session can be played back later without the network. Playing one
+//! back answers each request with the recorded response for the same kind
+//! of request and key, in the order they were recorded, which makes runs
+//! of the whole agent loop repeatable.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// One request and its response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    /// What was asked: `chat`, `search` or `summary`.
+    pub kind: String,
+    /// What a played-back request is matched on: the model and the last
+    /// prompt for a chat, the query for a search.
+    pub key: String,
+    /// The request as it was sent, for reading; it is not matched on.
+    #[serde(default)]
+    pub request: Value,
+    pub response: Value,
+    #[serde(skip)]
+    played: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Tape {
+    interactions: Vec<Interaction>,
+}
+
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl Cassette {
+    /// A cassette to record to `path`, which is written over on the first
+    /// interaction.
+    pub fn record(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            mode: Mode::Record,
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The cassette recorded at `path`, to play back.
+    pub fn replay(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read cassette {}: {}", path.display(), e))?;
+        let tape: Tape = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid cassette {}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            mode: Mode::Replay,
+            interactions: Mutex::new(tape.interactions),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    /// The response recorded for the next request of `kind` with `key`.
+    /// Each interaction is played once, so the same request asked twice
+    /// gets the two responses it got when recorded.
+    pub fn play(&self, kind: &str, key: &str) -> Result<Value, String> {
+        let mut interactions = self.interactions.lock().unwrap();
+        let interaction = interactions
+            .iter_mut()
+            .find(|i| !i.played && i.kind == kind && i.key == key)
+            .ok_or_else(|| {
+                format!(
+                    "Cassette {} has no {} left for {:?}",
+                    self.path.display(),
+                    kind,
+                    key.chars().take(80).collect::<String>()
+                )
+            })?;
+        interaction.played = true;
+        Ok(interaction.response.clone())
+    }
+
+    /// Records an interaction, writing the whole cassette again so that it
+    /// is complete even when the session ends abruptly.
+    pub fn add(
+        &self,
+        kind: &str,
+        key: &str,
+        request: Value,
+        response: Value,
+    ) -> Result<(), String> {
+        let mut interactions = self.interactions.lock().unwrap();
+        interactions.push(Interaction {
+            kind: kind.to_string(),
+            key: key.to_string(),
+            request,
+            response,
+            played: false,
+        });
+        let tape = Tape {
+            interactions: interactions.clone(),
+        };
+        let text = serde_json::to_string_pretty(&tape).map_err(|e| e.to_string())?;
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&self.path, text)
+            .map_err(|e| format!("Failed to write cassette {}: {}", self.path.display(), e))
+    }
+}
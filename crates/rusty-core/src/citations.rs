@@ -0,0 +1,147 @@
+//! Numbered citations for answers that drew on the web: links to the pages
+//! the search and `open` tools returned become `[1]`-style references,
+//! listed with their titles under "Sources" at the end of the answer.
+
+use regex::{Captures, Regex};
+
+/// A page a tool returned.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub title: String,
+    pub url: String,
+    /// Read whole by `open`, rather than found in search results.
+    pub opened: bool,
+}
+
+/// The pages the tools of a turn returned.
+#[derive(Debug, Default)]
+pub struct Sources {
+    sources: Vec<Source>,
+}
+
+/// An answer with its links numbered, and the sources footer for it.
+pub struct Cited {
+    pub text: String,
+    pub footer: String,
+}
+
+impl Sources {
+    /// Reads the pages named in a tool's output: each `URL:` line, titled by
+    /// the result or page heading above it.
+    pub fn add(&mut self, output: &str) {
+        let heading = Regex::new(r"^(?:\d+\. \*\*(.+)\*\*|# (.+))$").unwrap();
+        let mut title = None;
+        for line in output.lines() {
+            if let Some(c) = heading.captures(line) {
+                title = c.get(1).or(c.get(2)).map(|m| m.as_str().to_string());
+                continue;
+            }
+            let trimmed = line.trim_start();
+            let (url, opened) = match trimmed.strip_prefix("- URL: ") {
+                Some(url) => (url, false),
+                None => match line.strip_prefix("URL: ") {
+                    Some(url) => (url, true),
+                    None => continue,
+                },
+            };
+            let url = url.trim();
+            if !url.starts_with("http") {
+                continue;
+            }
+            match self.find(url) {
+                Some(i) => self.sources[i].opened |= opened,
+                None => self.sources.push(Source {
+                    title: title.take().unwrap_or_else(|| url.to_string()),
+                    url: url.to_string(),
+                    opened,
+                }),
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Numbers the sources `answer` links to, in the order it first does,
+    /// replacing the links with `[n]`. When it links to none, the pages
+    /// that were opened are listed instead. `None` when there is nothing to
+    /// list.
+    pub fn cite(&self, answer: &str) -> Option<Cited> {
+        let link = Regex::new(r"\[([^\]\n]+)\]\((https?://[^)\s]+)\)").unwrap();
+        let bare = Regex::new(r"<?(https?://[^\s<>()\[\]]+[^\s<>()\[\].,;:!?])>?").unwrap();
+        let mut cited: Vec<usize> = Vec::new();
+        let mut number = |url: &str| {
+            let i = self.find(url)?;
+            let n = match cited.iter().position(|&c| c == i) {
+                Some(n) => n,
+                None => {
+                    cited.push(i);
+                    cited.len() - 1
+                }
+            };
+            Some(n + 1)
+        };
+
+        let mut text = String::new();
+        let mut code = false;
+        for line in answer.split_inclusive('\n') {
+            if line.trim_start().starts_with("```") {
+                code = !code;
+            }
+            if code || line.trim_start().starts_with("```") {
+                text.push_str(line);
+                continue;
+            }
+            let line = link.replace_all(line, |c: &Captures| match number(&c[2]) {
+                Some(n) => format!("{} [{}]", &c[1], n),
+                None => c[0].to_string(),
+            });
+            let line = bare.replace_all(&line, |c: &Captures| match number(&c[1]) {
+                Some(n) => format!("[{}]", n),
+                None => c[0].to_string(),
+            });
+            text.push_str(&line);
+        }
+
+        let listed: Vec<&Source> = if cited.is_empty() {
+            self.sources.iter().filter(|source| source.opened).collect()
+        } else {
+            cited.iter().map(|&i| &self.sources[i]).collect()
+        };
+        if listed.is_empty() {
+            return None;
+        }
+        let mut footer = String::from("**Sources**\n\n");
+        for (i, source) in listed.iter().enumerate() {
+            footer.push_str(&format!("{}. {} — {}\n", i + 1, source.title, source.url));
+        }
+        Some(Cited { text, footer })
+    }
+
+    fn find(&self, url: &str) -> Option<usize> {
+        let url = same_page(url);
+        self.sources
+            .iter()
+            .position(|source| same_page(&source.url) == url)
+    }
+}
+
+/// The URLs in the sources footer of an answer, in their numbered order.
+pub fn cited_urls(answer: &str) -> Vec<&str> {
+    let Some((_, footer)) = answer.rsplit_once("**Sources**\n") else {
+        return Vec::new();
+    };
+    let item = Regex::new(r"^\d+\. .* — (\S+)$").unwrap();
+    footer
+        .lines()
+        .filter_map(|line| Some(item.captures(line)?.get(1)?.as_str()))
+        .collect()
+}
+
+/// A URL without its fragment or a trailing slash, to tell whether two
+/// name the same page.
+fn same_page(url: &str) -> &str {
+    let url = url.split('#').next().unwrap_or_default();
+    url.trim_end_matches('/')
+}
@@ -0,0 +1,182 @@
+//! The system clipboard, through the platform's own command-line tools:
+//! `pbpaste`/`pbcopy` on macOS, PowerShell and `clip` on Windows, and
+//! `wl-paste`/`wl-copy`, `xclip` or `xsel` elsewhere. Inside tmux, text can
+//! also go to tmux's own paste buffer.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd};
+
+/// Commands that print the clipboard, tried in order.
+fn read_commands() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbpaste"]]
+    } else if cfg!(windows) {
+        &[&["powershell", "-NoProfile", "-Command", "Get-Clipboard -Raw"]]
+    } else {
+        &[
+            &["wl-paste", "--no-newline"],
+            &["xclip", "-selection", "clipboard", "-out"],
+            &["xsel", "--clipboard", "--output"],
+        ]
+    }
+}
+
+/// Commands that take new clipboard text on stdin, tried in order.
+fn write_commands() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&["clip"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard", "-in"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    }
+}
+
+/// The text on the clipboard.
+pub fn read() -> Result<String, String> {
+    let mut failures = Vec::new();
+    for command in read_commands() {
+        match Command::new(command[0]).args(&command[1..]).output() {
+            Ok(output) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+            Ok(output) => failures.push(format!(
+                "{}: {}",
+                command[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            // Not installed; try the next one.
+            Err(_) => {}
+        }
+    }
+    if failures.is_empty() {
+        Err(format!(
+            "No clipboard tool found; install one of: {}",
+            read_commands()
+                .iter()
+                .map(|command| command[0])
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    } else {
+        Err(format!(
+            "Could not read the clipboard ({})",
+            failures.join("; ")
+        ))
+    }
+}
+
+/// Puts `text` on the clipboard.
+pub fn write(text: &str) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for command in write_commands() {
+        let child = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn();
+        // Not installed; try the next one.
+        let Ok(mut child) = child else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| format!("Could not write to {}: {}", command[0], e))?;
+        }
+        match child.wait_with_output() {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => failures.push(format!(
+                "{}: {}",
+                command[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) => failures.push(format!("{}: {}", command[0], e)),
+        }
+    }
+    if failures.is_empty() {
+        Err(format!(
+            "No clipboard tool found; install one of: {}",
+            write_commands()
+                .iter()
+                .map(|command| command[0])
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    } else {
+        Err(format!(
+            "Could not write the clipboard ({})",
+            failures.join("; ")
+        ))
+    }
+}
+
+/// Whether Rusty runs inside tmux.
+pub fn in_tmux() -> bool {
+    std::env::var_os("TMUX").is_some_and(|tmux| !tmux.is_empty())
+}
+
+/// Puts `text` in tmux's paste buffer, for `prefix ]` to paste.
+pub fn write_tmux_buffer(text: &str) -> Result<(), String> {
+    let mut child = Command::new("tmux")
+        .args(["load-buffer", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not run tmux: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("Could not write to tmux: {}", e))?;
+    }
+    match child.wait_with_output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(format!(
+            "tmux load-buffer failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Err(e) => Err(format!("tmux load-buffer failed: {}", e)),
+    }
+}
+
+/// The contents of the fenced and indented code blocks in `markdown`,
+/// without their fences.
+pub fn code_blocks(markdown: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<String> = None;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_) | CodeBlockKind::Indented)) => {
+                current = Some(String::new())
+            }
+            Event::Text(text) => {
+                if let Some(block) = current.as_mut() {
+                    block.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(block) = current.take() {
+                    blocks.push(block.trim_end_matches('\n').to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// `text` as a fenced code block, with a fence longer than any run of
+/// backticks inside it.
+pub fn fenced(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}\n{}\n{}", fence, text.trim_end_matches('\n'), fence)
+}
@@ -0,0 +1,929 @@
+//! The user configuration file, `config.toml` in the configuration directory.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::paths;
+use crate::search::Location;
+use crate::settings::Settings;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub chat: ChatConfig,
+    pub styles: StylesConfig,
+    pub editor: EditorConfig,
+    pub history: HistoryConfig,
+    pub storage: StorageConfig,
+    pub context: ContextConfig,
+    pub environment: EnvironmentConfig,
+    pub budget: BudgetConfig,
+    pub embeddings: EmbeddingsConfig,
+    pub index: IndexConfig,
+    pub hooks: HooksConfig,
+    pub http: HttpConfig,
+    pub trust: TrustConfig,
+    pub redact: RedactConfig,
+    pub pii: PiiConfig,
+    pub moderation: ModerationConfig,
+    pub ocr: OcrConfig,
+    pub clipboard: ClipboardConfig,
+    pub notifications: NotificationsConfig,
+    pub groq: GroqConfig,
+    pub discord: DiscordConfig,
+    pub telegram: TelegramConfig,
+    pub slack: SlackConfig,
+    pub webhook: WebhookConfig,
+    pub schedule: ScheduleConfig,
+    pub watch: WatchConfig,
+    pub git: GitConfig,
+    pub telemetry: TelemetryConfig,
+    pub search: SearchConfig,
+    pub fetch: FetchConfig,
+    /// The defaults of what `/set` changes.
+    pub settings: Settings,
+    /// User-defined slash commands, keyed by name without the leading `/`.
+    pub aliases: BTreeMap<String, Alias>,
+    /// Named system prompts with their settings and tools, keyed by the
+    /// name `/persona` and `--persona` take.
+    pub personas: BTreeMap<String, Persona>,
+    /// What models can do and cost, keyed by model id, for models Rusty
+    /// does not know or to correct what it knows.
+    pub models: BTreeMap<String, ModelConfig>,
+}
+
+/// The chat's defaults, as chosen in `rusty setup`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ChatConfig {
+    /// The model offered first at startup; unset for the first of the list.
+    pub model: Option<String>,
+    /// The syntax highlighting theme of code blocks, such as
+    /// `base16-ocean.dark` or `InspiredGitHub`.
+    pub theme: Option<String>,
+    /// The language of the interface, such as `de`; unset to follow the
+    /// system locale.
+    pub locale: Option<String>,
+    /// Linear, labeled output for screen readers, without boxes, colors or
+    /// cursor movement.
+    pub accessible: bool,
+    /// Leave LaTeX math in answers as the model wrote it rather than
+    /// converting it to Unicode.
+    pub raw_math: bool,
+    /// How patches in answers are laid out.
+    pub diff_view: DiffView,
+    /// Show the Git repository and branch of the working directory under
+    /// the prompt.
+    pub git_prompt: bool,
+    /// Leave the terminal's window title alone instead of naming the
+    /// session and model in it.
+    pub keep_title: bool,
+    /// Follow each answer with prompts to send next, picked by number.
+    pub follow_ups: bool,
+}
+
+/// The layout of patches in answers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffView {
+    /// Side by side when the terminal is wide enough, unified otherwise.
+    #[default]
+    Auto,
+    Unified,
+    /// Old lines on the left and new lines on the right.
+    SideBySide,
+}
+
+/// What a model can do and costs. Unset keys keep what Rusty knows of the
+/// model, or the defaults for models it does not know.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelConfig {
+    /// In tokens.
+    pub context_window: Option<usize>,
+    /// Whether the model can call tools; without, none are offered.
+    pub tools: Option<bool>,
+    /// Whether the model can see images.
+    pub vision: Option<bool>,
+    /// US dollars per million input tokens.
+    pub input_price: Option<f64>,
+    /// US dollars per million output tokens.
+    pub output_price: Option<f64>,
+}
+
+/// A way of answering to switch to at once: a system prompt, settings and
+/// the tools to offer, and a model to use, each optional.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Persona {
+    /// Shown next to the name in `/persona`'s list.
+    pub description: Option<String>,
+    pub system: Option<String>,
+    pub model: Option<String>,
+    /// As under `[settings]`, over the configured ones.
+    pub settings: Settings,
+    /// The only tools offered; unset for the usual ones.
+    pub tools: Option<Vec<String>>,
+}
+
+/// What a user-defined slash command expands to: one line, or several lines
+/// run in order. Each line may be a built-in command or a message, and
+/// `{input}` is replaced by the text typed after the alias.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    Line(String),
+    Steps(Vec<String>),
+}
+
+impl Alias {
+    pub fn expand(&self, input: &str) -> Vec<String> {
+        let steps = match self {
+            Alias::Line(line) => vec![line.clone()],
+            Alias::Steps(steps) => steps.clone(),
+        };
+
+        if steps.iter().any(|s| s.contains("{input}")) {
+            return steps
+                .into_iter()
+                .map(|s| s.replace("{input}", input).trim().to_string())
+                .collect();
+        }
+
+        let mut steps = steps;
+        if let Some(last) = steps.last_mut() {
+            if !input.is_empty() {
+                last.push(' ');
+                last.push_str(input);
+            }
+        }
+        steps
+    }
+}
+
+/// How the prompt line is edited.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    /// Use vi keybindings (insert and normal modes) instead of the default
+    /// emacs-style ones.
+    pub vi_mode: bool,
+}
+
+/// How prompt history is kept between sessions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Entries kept in the history file; 0 turns history off.
+    pub max_entries: usize,
+    /// Drop earlier copies of a prompt when it is entered again.
+    pub dedupe: bool,
+    /// Regular expressions; matching prompts are never written to history.
+    pub ignore: Vec<String>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 1000,
+            dedupe: true,
+            ignore: Vec::new(),
+        }
+    }
+}
+
+/// How saved sessions and history are stored.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Encrypt sessions and history with a key derived from a passphrase,
+    /// read from `RUSTY_PASSPHRASE` or asked for at startup.
+    pub encrypt: bool,
+}
+
+/// How the conversation is trimmed when it no longer fits the model's
+/// context window.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ContextConfig {
+    pub strategy: PruneStrategy,
+    /// Messages `keep-last` keeps, rounded up to whole turns.
+    pub keep_last: usize,
+    /// Overrides the model's context window, in tokens.
+    pub max_tokens: Option<usize>,
+    /// Tokens left free for the model's reply.
+    pub reserve_tokens: usize,
+    /// Before pruning, ask the model to summarize the oldest half of the
+    /// history into a note that replaces it.
+    pub summarize: bool,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            strategy: PruneStrategy::default(),
+            keep_last: 20,
+            max_tokens: None,
+            reserve_tokens: 4096,
+            summarize: true,
+        }
+    }
+}
+
+/// What the chat tells the model about the user's machine, in a system
+/// note, so that questions about "my setup" need not spell it out. Each
+/// is sent unless turned off.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentConfig {
+    /// The operating system and architecture.
+    pub os: bool,
+    pub shell: bool,
+    /// The working directory, with the home directory written as `~`.
+    pub cwd: bool,
+    /// The repository's branch, changed files and commits not pushed.
+    pub git: bool,
+    /// Today's date and the local time.
+    pub date: bool,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        Self {
+            os: true,
+            shell: true,
+            cwd: true,
+            git: true,
+            date: true,
+        }
+    }
+}
+
+/// Limits on what a session, a day or a month may spend.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Tokens a session may use, prompts and answers together, before
+    /// each request past them must be confirmed.
+    pub session_tokens: Option<u64>,
+    /// Tokens a single request may take, as estimated before it is sent,
+    /// above which its size and cost are shown and it must be confirmed.
+    pub request_tokens: Option<u64>,
+    /// Dollars that may be spent on priced models in a day, and in a
+    /// month, after which such requests are refused.
+    pub daily: Option<f64>,
+    pub monthly: Option<f64>,
+}
+
+/// How the Markdown of answers is styled, each in termimad's notation: a
+/// foreground color, then optionally a background color, and attributes,
+/// such as `"#ff8700 bold"` or `"white ansi(236)"`. Colors are names,
+/// `ansi(n)`, `grey(n)`, `rgb(r, g, b)` or `#rrggbb`. `quote` and `bullet`
+/// may also give the character drawn, as in `"• yellow"`. Unset keys keep
+/// Rusty's own style.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct StylesConfig {
+    pub headers: Option<String>,
+    pub bold: Option<String>,
+    pub italic: Option<String>,
+    pub inline_code: Option<String>,
+    pub code_block: Option<String>,
+    pub quote: Option<String>,
+    pub bullet: Option<String>,
+}
+
+/// Which turns are dropped first when the history is too long.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PruneStrategy {
+    /// The oldest turns go first.
+    #[default]
+    DropOldest,
+    /// Only the system prompt and the last `keep_last` messages are kept.
+    KeepLast,
+    /// Turns scored least important go first; recent turns, the opening
+    /// request and turns with code or explicit instructions score higher.
+    Importance,
+}
+
+/// Where document embeddings come from. Without a model, Rusty embeds text
+/// locally.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingsConfig {
+    /// Embedding model served by an OpenAI-compatible `/embeddings` API.
+    pub model: Option<String>,
+    pub base_url: String,
+    /// Environment variable holding the API key.
+    pub api_key_env: String,
+    pub retry: RetryConfig,
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            model: None,
+            base_url: "https://api.openai.com/v1".to_string(),
+            api_key_env: "OPENAI_API_KEY".to_string(),
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// Where the document index is kept.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    pub store: StoreKind,
+}
+
+/// Backends of the document index.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StoreKind {
+    /// `index.sqlite`, searched with the sqlite-vec extension.
+    #[default]
+    Sqlite,
+    /// `index.json`, loaded whole and searched in memory.
+    Json,
+}
+
+/// Shell commands run at points in the chat lifecycle.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub pre_submit: Vec<String>,
+    pub post_response: Vec<String>,
+    pub pre_tool_exec: Vec<String>,
+    /// Seconds a hook command may run before it is killed.
+    pub timeout_secs: Option<u64>,
+    /// A command whose first line of output is shown under the prompt,
+    /// run again before each prompt.
+    pub statusline: Option<String>,
+}
+
+/// Which hosts the `http_request` tool may call without asking.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Domains, each covering its subdomains too. Requests anywhere else
+    /// are shown to the user for approval first.
+    pub allow: Vec<String>,
+    /// Glob patterns of the hosts the tools that take a URL, `open`,
+    /// `http_request`, `read_feed` and `youtube_transcript`, may contact at
+    /// all; empty for any.
+    pub egress: Vec<String>,
+}
+
+/// Masking of secrets in messages before they are sent.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedactConfig {
+    pub enabled: bool,
+    /// Regular expressions of secrets of your own, masked along with API
+    /// keys, AWS credentials and private keys.
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// Pseudonyms for personal data in prompts and tool outputs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PiiConfig {
+    /// Off by default.
+    pub enabled: bool,
+    pub emails: bool,
+    pub phones: bool,
+    /// Names of people to replace, matched as whole words in any case.
+    pub names: Vec<String>,
+}
+
+impl Default for PiiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            emails: true,
+            phones: true,
+            names: Vec::new(),
+        }
+    }
+}
+
+/// Moderation of user messages and answers.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModerationConfig {
+    pub rules: Vec<ModerationRule>,
+    /// A URL asked about every message with a POST of
+    /// `{"direction": "input" | "output", "text": ...}`, which answers
+    /// `{"action": "allow" | "warn" | "block", "reason": ...}`.
+    pub endpoint: Option<String>,
+    /// Block messages when the endpoint cannot be asked, rather than let
+    /// them through with a warning.
+    pub fail_closed: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModerationRule {
+    /// A regular expression; add `(?i)` to ignore case.
+    pub pattern: String,
+    pub action: ModerationAction,
+    #[serde(default, rename = "applies_to")]
+    pub scope: ModerationScope,
+    /// Shown when the rule matches, instead of the pattern.
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModerationAction {
+    Allow,
+    Warn,
+    Block,
+}
+
+/// Which messages a moderation rule looks at.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModerationScope {
+    /// What users send.
+    Input,
+    /// What the model answers.
+    Output,
+    #[default]
+    Both,
+}
+
+/// The workspaces, each a Git repository or else a directory, the user
+/// was asked about when tools first ran there. Tools that run programs on
+/// this machine are only allowed in trusted ones.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct TrustConfig {
+    pub trusted: Vec<String>,
+    pub untrusted: Vec<String>,
+    /// Refuse every tool call that may change something, in any
+    /// workspace, whatever the tool settings say.
+    pub read_only: bool,
+}
+
+impl TrustConfig {
+    /// Whether `workspace` is trusted, as decided for it or for the
+    /// nearest directory above it; `None` when neither was asked about.
+    pub fn decision(&self, workspace: &Path) -> Option<bool> {
+        let nearest = |dirs: &[String]| {
+            dirs.iter()
+                .map(Path::new)
+                .filter(|dir| workspace.starts_with(dir))
+                .map(|dir| dir.components().count())
+                .max()
+        };
+        match (nearest(&self.trusted), nearest(&self.untrusted)) {
+            (Some(trusted), Some(untrusted)) => Some(trusted > untrusted),
+            (trusted, untrusted) => trusted.map(|_| true).or(untrusted.map(|_| false)),
+        }
+    }
+
+    /// Writes down the decision for `workspace` in the configuration file.
+    pub fn remember(workspace: &Path, trusted: bool) -> Result<(), String> {
+        let key = if trusted { "trusted" } else { "untrusted" };
+        let workspace = workspace.display().to_string();
+        Config::update("trust", |table| {
+            let list = table
+                .entry(key)
+                .or_insert_with(|| toml_edit::value(toml_edit::Array::new()));
+            if let Some(list) = list.as_array_mut() {
+                list.push(workspace);
+            }
+        })
+    }
+}
+
+/// How text is read from images. Tesseract is used when installed; the
+/// vision model reads images it cannot.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OcrConfig {
+    /// Tesseract languages, such as `eng` or `eng+deu`.
+    pub languages: String,
+    /// Model served by an OpenAI-compatible chat API that accepts images;
+    /// empty turns the fallback off.
+    pub vision_model: Option<String>,
+    pub base_url: String,
+    /// Environment variable holding the API key.
+    pub api_key_env: String,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            languages: "eng".to_string(),
+            vision_model: Some("meta-llama/llama-4-scout-17b-16e-instruct".to_string()),
+            base_url: "https://api.groq.com/openai/v1".to_string(),
+            api_key_env: "GROQ_API_KEY".to_string(),
+        }
+    }
+}
+
+/// Access to the system clipboard.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    /// Offer the model the `read_clipboard` tool. Off by default, since the
+    /// clipboard often holds things not meant for the model.
+    pub tool: bool,
+}
+
+/// Desktop notifications for slow turns, and a sound for every turn.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    pub enabled: bool,
+    /// Seconds a turn, tool calls included, must take before it notifies.
+    pub after_secs: u64,
+    /// Played when the assistant finishes responding: `"bell"` rings the
+    /// terminal bell, anything else is the path of a sound file.
+    pub sound: Option<String>,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            after_secs: 30,
+            sound: None,
+        }
+    }
+}
+
+/// How requests to Groq are sent.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GroqConfig {
+    /// With several keys in `GROQ_API_KEY`, use each in turn for every
+    /// request instead of moving on only when one is rate-limited.
+    pub round_robin: bool,
+    /// Requests in flight at once; more wait for one to finish.
+    pub max_concurrent: usize,
+    /// Requests started per minute, retries included; unset for no limit
+    /// beyond Groq's own.
+    pub requests_per_minute: Option<usize>,
+    /// When refused requests are sent again.
+    pub retry: RetryConfig,
+}
+
+impl Default for GroqConfig {
+    fn default() -> Self {
+        Self {
+            round_robin: false,
+            max_concurrent: 4,
+            requests_per_minute: None,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// How a provider retries a request refused with a status that may pass on
+/// a second try, as `[groq.retry]` or `[embeddings.retry]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Retries after the first attempt; 0 to fail at once.
+    pub max_retries: u32,
+    /// Seconds before the first retry, doubled for each one after.
+    pub base_delay: f64,
+    /// The longest wait between attempts, in seconds, also capping the
+    /// delay a `Retry-After` header asks for.
+    pub max_delay: f64,
+    /// HTTP statuses worth retrying.
+    pub statuses: Vec<u16>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: 2.0,
+            max_delay: 60.0,
+            statuses: vec![429, 500, 502, 503],
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn retries(&self, status: u16) -> bool {
+        self.statuses.contains(&status)
+    }
+
+    /// The wait before retry number `attempt`, counting from 1: what the
+    /// server asked for when it said, the backoff otherwise.
+    pub fn delay(&self, attempt: u32, asked: Option<Duration>) -> Duration {
+        let backoff = self.base_delay.max(0.0) * 2f64.powi(attempt.saturating_sub(1) as i32);
+        let max = Duration::from_secs_f64(self.max_delay.max(0.0));
+        asked
+            .unwrap_or_else(|| Duration::from_secs_f64(backoff.min(self.max_delay.max(0.0))))
+            .min(max)
+    }
+}
+
+/// `rusty discord`, the Discord bot.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct DiscordConfig {
+    /// The model that answers; unset for the default model.
+    pub model: Option<String>,
+}
+
+/// `rusty telegram`, the Telegram bot.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct TelegramConfig {
+    /// The model each chat starts with; unset for the default model.
+    pub model: Option<String>,
+    /// Usernames or numeric user ids allowed to use the bot. Anyone can
+    /// find a Telegram bot, so leaving this empty lets everyone spend your
+    /// API quota.
+    pub users: Vec<String>,
+}
+
+/// `rusty slack`, the Slack app.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct SlackConfig {
+    /// The model that answers; unset for the default model.
+    pub model: Option<String>,
+    /// Tokens of each thread's history sent with a request, when fewer
+    /// than the model's context window; older messages are pruned.
+    pub context_tokens: Option<usize>,
+}
+
+/// `rusty webhook`, the HTTP listener that runs prompts on POSTed payloads.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// The address to listen on. The default only accepts requests from
+    /// this machine; put a reverse proxy in front to take outside ones.
+    pub listen: String,
+    /// The model that answers; unset for the default model.
+    pub model: Option<String>,
+    /// The routes, keyed by the path they are POSTed to, without the `/`.
+    pub routes: BTreeMap<String, WebhookRoute>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            listen: "127.0.0.1:8787".to_string(),
+            model: None,
+            routes: BTreeMap::new(),
+        }
+    }
+}
+
+/// A webhook route: which saved prompt a payload is given to, and where the
+/// answer goes.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookRoute {
+    /// The name of a prompt template; the payload, as pretty-printed JSON,
+    /// takes the place of `{input}`.
+    pub prompt: String,
+    /// The URL the result is POSTed to. Without one, it is only printed.
+    pub callback: Option<String>,
+}
+
+/// `rusty schedule`, prompts run on cron schedules.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// The model that answers; unset for the default model.
+    pub model: Option<String>,
+    pub jobs: Vec<ScheduledPrompt>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduledPrompt {
+    /// When to run, as a five-field cron pattern in local time, such as
+    /// `0 9 * * 1-5`.
+    pub cron: String,
+    pub prompt: String,
+    /// The title of the saved session the runs are added to, which is
+    /// created if needed; unset to append the answers to the schedule log.
+    pub session: Option<String>,
+}
+
+/// `rusty watch`, reviews of files as they are saved.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// The model that reviews; unset for the default model.
+    pub model: Option<String>,
+    /// The prompt sent with each saved file when `--prompt` is not given.
+    pub prompt: Option<String>,
+}
+
+/// `rusty hook`, the helpers called from Git hooks.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    /// The model that writes commit messages and reviews; unset for the
+    /// default model.
+    pub model: Option<String>,
+}
+
+/// Where anonymous usage statistics go once `/telemetry on` allows them.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// The URL the counts are posted to as JSON when Rusty exits; unset,
+    /// they are only kept locally.
+    pub endpoint: Option<String>,
+}
+
+/// Web searches.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// Send your location with searches. Off by default; when on without
+    /// anything below, only the system's time zone is sent.
+    pub location: bool,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    /// A two-letter country code, such as `FR`.
+    pub country: Option<String>,
+    pub postal_code: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// How the `open` tool fetches web pages.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FetchConfig {
+    /// Sent with every request, so sites can tell who is fetching.
+    pub user_agent: String,
+    /// Ask each site's `robots.txt` before fetching from it.
+    pub robots: bool,
+    /// The least time between two requests to one site, in milliseconds.
+    pub delay_ms: u64,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: format!(
+                "rusty-cli/{} (+https://github.com/AlphsX/rusty-cli)",
+                env!("CARGO_PKG_VERSION")
+            ),
+            robots: true,
+            delay_ms: 1000,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// The location to send, or `None` while `location` is off.
+    pub fn location(&self) -> Option<Location> {
+        self.location.then(|| Location {
+            city: self.city.clone(),
+            state: self.state.clone(),
+            country: self.country.clone(),
+            postal_code: self.postal_code.clone(),
+            latitude: self.latitude,
+            longitude: self.longitude,
+            timezone: iana_time_zone::get_timezone().ok(),
+        })
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        paths::config_dir().join("config.toml")
+    }
+
+    /// Reads the configuration file. A missing file yields the defaults.
+    pub fn load() -> Result<Self, String> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Sets `key` in the `[section]` table of the configuration file,
+    /// creating the file or the table when needed. Everything else in the
+    /// file, comments included, is kept as it was.
+    pub fn set_value(section: &str, key: &str, value: &str) -> Result<(), String> {
+        Self::update(section, |table| table[key] = toml_edit::value(value))
+    }
+
+    /// Like `set_value`, with `value` written as in the file, such as `0.2`
+    /// or `["END"]`, and a dotted `key` such as `tools.brave_search` going
+    /// into a table of its own.
+    pub fn set_toml(section: &str, key: &str, value: &str) -> Result<(), String> {
+        let value: toml_edit::Value = value
+            .parse()
+            .map_err(|e| format!("Invalid value '{}': {}", value, e))?;
+        Self::update(section, |table| match key.split_once('.') {
+            Some((group, name)) => table[group][name] = toml_edit::Item::Value(value),
+            None => table[key] = toml_edit::Item::Value(value),
+        })
+    }
+
+    /// What the file sets for `key`, such as `chat.model`, as written in
+    /// the file; `None` when it leaves it to the default.
+    pub fn get_key(key: &str) -> Result<Option<String>, String> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let table: toml::Table = text
+            .parse()
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        let mut parts = key.split('.');
+        let Some(mut value) = parts.next().and_then(|part| table.get(part)) else {
+            return Ok(None);
+        };
+        for part in parts {
+            match value.get(part) {
+                Some(inner) => value = inner,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(match value {
+            toml::Value::String(text) => text.clone(),
+            toml::Value::Table(table) => toml::to_string(table).map_err(|e| e.to_string())?,
+            value => value.to_string(),
+        }))
+    }
+
+    /// Sets `key`, such as `chat.model` or `settings.tools.brave_search`,
+    /// to `value`: as written in the file when it reads as a TOML value,
+    /// else as text. A change that leaves the file invalid is undone.
+    pub fn set_key(key: &str, value: &str) -> Result<(), String> {
+        let Some((section, name)) = key.split_once('.') else {
+            return Err(format!(
+                "'{}' is not a key such as chat.model; give the section too",
+                key
+            ));
+        };
+        let value = match value.parse::<toml_edit::Value>() {
+            Ok(_) => value.to_string(),
+            Err(_) => toml_edit::Value::from(value).to_string(),
+        };
+        let path = Self::path();
+        let before = fs::read_to_string(&path).ok();
+        Self::set_toml(section, name, &value)?;
+        if let Err(e) = Self::load() {
+            let _ = match before {
+                Some(text) => fs::write(&path, text),
+                None => fs::remove_file(&path),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Changes the `[section]` table of the file with `change`.
+    fn update(section: &str, change: impl FnOnce(&mut toml_edit::Table)) -> Result<(), String> {
+        let path = Self::path();
+        let text = if path.exists() {
+            fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        } else {
+            String::new()
+        };
+        let mut document: toml_edit::DocumentMut = text
+            .parse()
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        let table = document
+            .entry(section)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| format!("[{}] in {} is not a table", section, path.display()))?;
+        change(table);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        fs::write(&path, document.to_string())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
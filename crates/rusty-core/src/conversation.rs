@@ -0,0 +1,552 @@
+use std::ops::Range;
+
+use crate::config::{ContextConfig, PruneStrategy};
+use crate::datetime;
+use crate::message::{Message, ToolDefinition, Usage};
+use crate::provider::Provider;
+
+const SUMMARY_PROMPT: &str = "Summarize the conversation below so it can be continued without it. \
+Keep decisions, facts about the user and their project, names, file paths, code that is still \
+relevant and open questions; leave out pleasantries. Reply with the summary only.";
+
+const TRANSLATION_PROMPT: &str = "You are a professional translator. Translate the user's \
+message into {language}, detecting the source language yourself. Keep the meaning, tone and \
+register, and keep Markdown, line breaks, code, URLs, placeholders and proper names as they are. \
+If the text is already in {language}, translate it into English instead. Reply with the \
+translation only, without notes, quotes or explanations.";
+
+const FOLLOW_UP_PROMPT: &str = "Below is the end of a conversation between a user and an \
+assistant. Suggest {count} questions the user might ask next to dig deeper, check a claim or \
+take the topic somewhere useful, each different from the others and answerable by the \
+assistant. Write them as the user would, one per line, each under 15 words, without numbers, \
+bullets, quotes or anything else.";
+
+/// Heading of the system note that stands in for summarized messages.
+const SUMMARY_HEADER: &str = "Summary of the earlier conversation:";
+
+/// Characters of each message passed on for summarizing.
+const MAX_SUMMARY_INPUT_CHARS: usize = 2000;
+
+/// Appended to a tool result cut short so the request fits the window.
+const CUT_NOTE: &str = "[cut to fit the context window]";
+
+/// Words that mark a message as an instruction worth keeping around.
+const IMPORTANT_WORDS: &[&str] = &["remember", "important", "always", "never", "must"];
+
+/// A point in the conversation to go back to, named with `/checkpoint`.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub name: String,
+    /// The number of messages there were when it was marked.
+    pub len: usize,
+}
+
+pub struct ConversationManager {
+    messages: Vec<Message>,
+    checkpoints: Vec<Checkpoint>,
+    system_prompt: Option<String>,
+    instructions: Option<String>,
+    memory: Option<String>,
+    environment: Option<String>,
+    /// Whether today's date is sent; bots leave it on.
+    date: bool,
+    stream_mode: bool,
+    context: ContextConfig,
+}
+
+impl ConversationManager {
+    pub fn new() -> Self {
+        Self {
+            messages: Vec::new(),
+            checkpoints: Vec::new(),
+            system_prompt: None,
+            instructions: None,
+            memory: None,
+            environment: None,
+            date: true,
+            stream_mode: false,
+            context: ContextConfig::default(),
+        }
+    }
+
+    /// How the history is pruned when it outgrows the context window.
+    pub fn set_context(&mut self, context: ContextConfig) {
+        self.context = context;
+    }
+
+    pub fn add_user_message(&mut self, content: &str) {
+        self.messages.push(Message::user(content));
+    }
+
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    pub fn remove_last_message(&mut self) {
+        self.messages.pop();
+        self.forget_lost_checkpoints();
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.checkpoints.clear();
+    }
+
+    /// The content of the latest user message, if there is one.
+    pub fn last_user_message(&self) -> Option<&str> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .and_then(|m| m.content.as_deref())
+    }
+
+    /// Drops the latest user message and everything after it, so the
+    /// conversation is back to where that message was sent.
+    pub fn rewind_last_user_message(&mut self) {
+        if let Some(index) = self.messages.iter().rposition(|m| m.role == "user") {
+            self.messages.truncate(index);
+        }
+        self.forget_lost_checkpoints();
+    }
+
+    /// Marks the conversation as it is now as `name`, moving the checkpoint
+    /// of that name if there was one. Returns whether there was.
+    pub fn checkpoint(&mut self, name: &str) -> bool {
+        let moved = self.checkpoints.iter().any(|c| c.name == name);
+        self.checkpoints.retain(|c| c.name != name);
+        self.checkpoints.push(Checkpoint {
+            name: name.to_string(),
+            len: self.messages.len(),
+        });
+        moved
+    }
+
+    /// The checkpoints, oldest first.
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Drops every message after the checkpoint `name`, and the checkpoints
+    /// marked after it. Returns how many messages were dropped.
+    pub fn rewind_to(&mut self, name: &str) -> Result<usize, String> {
+        let index = self
+            .checkpoints
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| format!("No checkpoint named '{}'", name))?;
+        let len = self.checkpoints[index].len;
+        let dropped = self.messages.len().saturating_sub(len);
+        self.messages.truncate(len);
+        // Those marked later go too, even at the same point, as they came
+        // after.
+        self.checkpoints.truncate(index + 1);
+        Ok(dropped)
+    }
+
+    /// Checkpoints past the end of a conversation that was cut back cannot
+    /// be gone back to any more.
+    fn forget_lost_checkpoints(&mut self) {
+        let len = self.messages.len();
+        self.checkpoints.retain(|c| c.len <= len);
+    }
+
+    pub fn get_messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// A user-chosen system prompt that survives `clear`.
+    pub fn set_system_prompt(&mut self, prompt: Option<String>) {
+        self.system_prompt = prompt;
+    }
+
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.system_prompt.as_deref()
+    }
+
+    /// The instructions of the `RUSTY.md` files, sent as a system note
+    /// after the system prompt.
+    pub fn set_instructions(&mut self, instructions: Option<String>) {
+        self.instructions = instructions;
+    }
+
+    /// Remembered facts, sent as a system note after the instructions.
+    pub fn set_memory(&mut self, memory: Option<String>) {
+        self.memory = memory;
+    }
+
+    /// The note on the user's machine from `environment::note`, sent after
+    /// the memory, and whether today's date follows it.
+    pub fn set_environment(&mut self, environment: Option<String>, date: bool) {
+        self.environment = environment;
+        self.date = date;
+    }
+
+    /// The system prompt, the instructions, the memory note, the
+    /// environment and today's date that precede the history.
+    fn preamble(&self) -> Vec<Message> {
+        // The date note changes every minute, so it comes last, after the
+        // parts that stay the same and can be read from a prompt cache.
+        let today = self.date.then(datetime::today_note);
+        self.system_prompt
+            .iter()
+            .chain(&self.instructions)
+            .chain(&self.memory)
+            .chain(&self.environment)
+            .chain(&today)
+            .map(|text| Message::system(text))
+            .collect()
+    }
+
+    /// The history as it should be sent: the custom system prompt and
+    /// remembered facts, if any, first, then as many turns as fit in `context_window` tokens under the
+    /// configured pruning strategy, with `overhead` tokens set aside for the
+    /// tool definitions. The latest turn is always kept; when it alone is
+    /// too large, its tool results are cut, the oldest first.
+    pub fn request_messages(&self, context_window: usize, overhead: usize) -> Vec<Message> {
+        let budget = self.budget(context_window).saturating_sub(overhead);
+        let mut messages = self.preamble();
+        let start = messages.len();
+        for turn in self.prune(budget) {
+            messages.extend_from_slice(&self.messages[turn]);
+        }
+        let mut excess = estimate_tokens(&messages[start..]).saturating_sub(budget);
+        for message in &mut messages[start..] {
+            if excess == 0 {
+                break;
+            }
+            if message.role == "tool" {
+                excess = excess.saturating_sub(cut(message, excess));
+            }
+        }
+        messages
+    }
+
+    /// [`Self::request_messages`], or why the request cannot be sent when
+    /// even the latest message, its tool results cut, does not fit the
+    /// window, which the API would only answer with an error.
+    pub fn fit(&self, context_window: usize, overhead: usize) -> Result<Vec<Message>, String> {
+        let messages = self.request_messages(context_window, overhead);
+        let budget = self.budget(context_window).saturating_sub(overhead);
+        let history = estimate_tokens(&messages[self.preamble().len()..]);
+        if history <= budget {
+            return Ok(messages);
+        }
+        let window = self
+            .context
+            .max_tokens
+            .unwrap_or(context_window)
+            .saturating_sub(self.context.reserve_tokens);
+        Err(format!(
+            "This request needs about {} tokens, but the model's context window leaves room for \
+             about {} once {} are reserved for the reply; shorten the message, or use /model to \
+             switch to a model with a larger window",
+            estimate_tokens(&messages) + overhead,
+            window,
+            self.context.reserve_tokens
+        ))
+    }
+
+    /// How many history messages pruning left out of `request`.
+    pub fn omitted(&self, request: &[Message]) -> usize {
+        (self.preamble().len() + self.messages.len()).saturating_sub(request.len())
+    }
+
+    /// Whether the history has outgrown the window and summarizing is on.
+    pub fn should_summarize(&self, context_window: usize) -> bool {
+        self.context.summarize && estimate_tokens(&self.messages) > self.budget(context_window)
+    }
+
+    /// The oldest turns holding about half of the history's tokens. The
+    /// latest turn is never part of it.
+    pub fn oldest_chunk(&self) -> Option<Range<usize>> {
+        let turns = self.turns();
+        let half = estimate_tokens(&self.messages) / 2;
+        let mut end = 0;
+        for turn in &turns[..turns.len().saturating_sub(1)] {
+            end = turn.end;
+            if estimate_tokens(&self.messages[..end]) >= half {
+                break;
+            }
+        }
+        // A lone summary note is not worth summarizing again.
+        (end > 1 || end == 1 && !is_summary(&self.messages[0])).then_some(0..end)
+    }
+
+    /// Replaces the messages in `range` with a system note holding `summary`.
+    pub fn replace_with_summary(&mut self, range: Range<usize>, summary: &str) {
+        let note = Message::system(&format!("{}\n\n{}", SUMMARY_HEADER, summary.trim()));
+        // Checkpoints inside the summarized messages end up just after the
+        // summary, which is as close as can be gone back to now.
+        for checkpoint in &mut self.checkpoints {
+            if checkpoint.len >= range.end {
+                checkpoint.len -= range.len() - 1;
+            } else if checkpoint.len > range.start {
+                checkpoint.len = range.start + 1;
+            }
+        }
+        self.messages.splice(range, [note]);
+    }
+
+    /// Tokens left for the history once the system prompt, memory and the
+    /// reply's reserve are accounted for.
+    fn budget(&self, context_window: usize) -> usize {
+        let prompt = estimate_tokens(&self.preamble());
+        self.context
+            .max_tokens
+            .unwrap_or(context_window)
+            .saturating_sub(self.context.reserve_tokens)
+            .saturating_sub(prompt)
+    }
+
+    /// The turns to send, oldest first.
+    fn prune(&self, budget: usize) -> Vec<Range<usize>> {
+        let mut turns = self.turns();
+        let cost = |turn: &Range<usize>| estimate_tokens(&self.messages[turn.clone()]);
+        let mut total: usize = turns.iter().map(cost).sum();
+        if total <= budget {
+            return turns;
+        }
+
+        match self.context.strategy {
+            PruneStrategy::DropOldest => {}
+            PruneStrategy::KeepLast => {
+                let len = self.messages.len();
+                while turns.len() > 1 && len - turns[1].start >= self.context.keep_last {
+                    total -= cost(&turns.remove(0));
+                }
+            }
+            PruneStrategy::Importance => {
+                let count = turns.len();
+                let mut ranked: Vec<(f64, usize)> = turns[..count - 1]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, turn)| (self.importance(turn, i, count), i))
+                    .collect();
+                ranked.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+                let mut dropped = vec![false; count];
+                for (_, i) in ranked {
+                    if total <= budget {
+                        break;
+                    }
+                    total -= cost(&turns[i]);
+                    dropped[i] = true;
+                }
+                let mut index = 0;
+                turns.retain(|_| {
+                    index += 1;
+                    !dropped[index - 1]
+                });
+            }
+        }
+
+        while turns.len() > 1 && total > budget {
+            total -= cost(&turns.remove(0));
+        }
+        turns
+    }
+
+    /// Splits the history into turns, each starting at a user message, so a
+    /// tool call is never separated from its result.
+    fn turns(&self) -> Vec<Range<usize>> {
+        let mut turns: Vec<Range<usize>> = Vec::new();
+        for (i, message) in self.messages.iter().enumerate() {
+            match turns.last_mut() {
+                Some(turn) if message.role != "user" => turn.end = i + 1,
+                _ => turns.push(i..i + 1),
+            }
+        }
+        turns
+    }
+
+    /// A rough score of how much a turn matters to what comes next.
+    fn importance(&self, turn: &Range<usize>, index: usize, count: usize) -> f64 {
+        let mut score = 2.0 * index as f64 / count as f64;
+        if index == 0 {
+            score += 1.0;
+        }
+        for message in &self.messages[turn.clone()] {
+            let text = message.content.as_deref().unwrap_or("").to_lowercase();
+            if text.contains("```") {
+                score += 0.5;
+            }
+            if message.role == "user" && IMPORTANT_WORDS.iter().any(|w| text.contains(w)) {
+                score += 1.0;
+            }
+            if message.role == "tool" {
+                score -= 0.25;
+            }
+        }
+        score
+    }
+
+    pub fn toggle_stream_mode(&mut self) {
+        self.stream_mode = !self.stream_mode;
+    }
+
+    pub fn is_stream_mode(&self) -> bool {
+        self.stream_mode
+    }
+}
+
+/// A rough token count for `messages`: about four characters per token,
+/// plus a little per message for the role and framing.
+pub fn estimate_tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .map(|m| {
+            let mut chars = m.content.as_deref().map_or(0, |c| c.chars().count());
+            for call in m.tool_calls.iter().flatten() {
+                chars += call.function.name.len() + call.function.arguments.chars().count();
+            }
+            chars / 4 + 4
+        })
+        .sum()
+}
+
+/// Asks the model for a summary of `messages`, for use with
+/// [`ConversationManager::replace_with_summary`].
+pub async fn summarize(
+    provider: &Provider,
+    model: &str,
+    messages: &[Message],
+) -> Result<String, String> {
+    let mut transcript = String::new();
+    for message in messages {
+        let content = message.content.as_deref().unwrap_or("");
+        let content = content
+            .strip_prefix(SUMMARY_HEADER)
+            .unwrap_or(content)
+            .trim();
+        if !content.is_empty() {
+            let content: String = content.chars().take(MAX_SUMMARY_INPUT_CHARS).collect();
+            transcript.push_str(&format!("{}: {}\n\n", message.role, content));
+        }
+    }
+
+    let request = [Message::system(SUMMARY_PROMPT), Message::user(&transcript)];
+    let reply = provider
+        .chat_completion(model, &request, None)
+        .await
+        .map_err(|e| format!("Failed to summarize the conversation: {}", e))?;
+    reply
+        .content
+        .filter(|s| !s.trim().is_empty())
+        .ok_or_else(|| "Failed to summarize the conversation: empty reply".to_string())
+}
+
+/// The request asking for `text` in `language`, on its own so the
+/// conversation does not color the translation.
+pub fn translation_request(language: &str, text: &str) -> [Message; 2] {
+    [
+        Message::system(&TRANSLATION_PROMPT.replace("{language}", language)),
+        Message::user(text),
+    ]
+}
+
+/// `text` translated into `language`, and the tokens it took.
+pub async fn translate(
+    provider: &Provider,
+    model: &str,
+    language: &str,
+    text: &str,
+) -> Result<(String, Option<Usage>), String> {
+    let reply = provider
+        .chat_completion(model, &translation_request(language, text), None)
+        .await
+        .map_err(|e| format!("Failed to translate: {}", e))?;
+    let translation = reply
+        .content
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| "Failed to translate: empty reply".to_string())?;
+    Ok((translation, reply.usage))
+}
+
+/// Up to `count` prompts the user might send next, asked for in a request
+/// of its own with only the last exchange of `messages`, to keep it cheap.
+pub async fn follow_ups(
+    provider: &Provider,
+    model: &str,
+    messages: &[Message],
+    count: usize,
+) -> Result<Vec<String>, String> {
+    let start = messages
+        .iter()
+        .rposition(|m| m.role == "user")
+        .ok_or_else(|| "No prompt to follow up on".to_string())?;
+    let mut transcript = String::new();
+    for message in messages[start..]
+        .iter()
+        .filter(|m| m.role == "user" || m.role == "assistant")
+    {
+        let content = message.content.as_deref().unwrap_or("").trim();
+        if !content.is_empty() {
+            let content: String = content.chars().take(MAX_SUMMARY_INPUT_CHARS).collect();
+            transcript.push_str(&format!("{}: {}\n\n", message.role, content));
+        }
+    }
+
+    let request = [
+        Message::system(&FOLLOW_UP_PROMPT.replace("{count}", &count.to_string())),
+        Message::user(&transcript),
+    ];
+    let reply = provider
+        .chat_completion(model, &request, None)
+        .await
+        .map_err(|e| format!("Failed to suggest follow-ups: {}", e))?;
+    Ok(reply
+        .content
+        .unwrap_or_default()
+        .lines()
+        .map(|line| {
+            // Numbers and bullets the model added anyway.
+            line.trim()
+                .trim_start_matches(|c: char| c.is_ascii_digit() || "-*•.)".contains(c))
+                .trim()
+                .trim_matches('"')
+                .to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .take(count)
+        .collect())
+}
+
+/// A rough token count for the tool definitions sent with a request.
+pub fn estimate_tool_tokens(tools: &[ToolDefinition]) -> usize {
+    serde_json::to_string(tools).map_or(0, |json| json.chars().count() / 4)
+}
+
+/// Cuts the end off a tool result to save about `tokens` tokens, keeping
+/// a note that it was cut. Returns the tokens saved.
+fn cut(message: &mut Message, tokens: usize) -> usize {
+    let Some(content) = &message.content else {
+        return 0;
+    };
+    let chars = content.chars().count();
+    if chars <= CUT_NOTE.len() + 1 {
+        return 0;
+    }
+    let keep = chars.saturating_sub(tokens * 4 + CUT_NOTE.len() + 1);
+    let mut text: String = content.chars().take(keep).collect();
+    text.push('\n');
+    text.push_str(CUT_NOTE);
+    let saved = (chars - text.chars().count()) / 4;
+    message.content = Some(text);
+    saved
+}
+
+fn is_summary(message: &Message) -> bool {
+    message.role == "system"
+        && message
+            .content
+            .as_deref()
+            .is_some_and(|c| c.starts_with(SUMMARY_HEADER))
+}
+
+impl Default for ConversationManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,376 @@
+//! Dates and times for the `datetime` tool: the time in any time zone,
+//! conversion between zones, and date arithmetic. Also writes the note
+//! that tells the model what day it is.
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, Months, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use regex::Regex;
+
+/// What the model asked of the `datetime` tool.
+pub enum Query<'a> {
+    /// The current time in each of `zones`, comma-separated.
+    Now { zones: Option<&'a str> },
+    /// `time` as read in `from`, shown in `to`.
+    Convert {
+        time: Option<&'a str>,
+        from: Option<&'a str>,
+        to: Option<&'a str>,
+    },
+    /// `time` moved by `duration`, such as `3 weeks` or `-2 months 4 days`.
+    Add {
+        time: Option<&'a str>,
+        zone: Option<&'a str>,
+        duration: &'a str,
+    },
+    /// The time from `time` until `end`.
+    Between {
+        time: Option<&'a str>,
+        end: &'a str,
+        zone: Option<&'a str>,
+    },
+}
+
+pub fn query(query: Query) -> Result<String, String> {
+    match query {
+        Query::Now { zones } => {
+            let zones: Vec<&str> = zones
+                .unwrap_or("")
+                .split(',')
+                .map(str::trim)
+                .filter(|zone| !zone.is_empty())
+                .collect();
+            let now = Utc::now();
+            if zones.is_empty() {
+                return Ok(with_calendar(&Zone::Local, now));
+            }
+            let lines = zones
+                .into_iter()
+                .map(|name| Ok(with_calendar(&Zone::parse(Some(name))?, now)))
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(lines.join("\n\n"))
+        }
+        Query::Convert { time, from, to } => {
+            let from = Zone::parse(from)?;
+            let to = Zone::parse(to)?;
+            let instant = from.read(time)?;
+            Ok(format!(
+                "{} ({}) is {} ({})",
+                from.show(instant),
+                from.name(),
+                to.show(instant),
+                to.name()
+            ))
+        }
+        Query::Add {
+            time,
+            zone,
+            duration,
+        } => {
+            let zone = Zone::parse(zone)?;
+            let start = zone.read(time)?;
+            let end = add(&zone, start, duration)?;
+            Ok(format!(
+                "{} {} {} is {} ({})",
+                zone.show(start),
+                if duration.trim_start().starts_with('-') {
+                    "minus"
+                } else {
+                    "plus"
+                },
+                duration.trim().trim_start_matches(['+', '-']).trim(),
+                zone.show(end),
+                zone.name()
+            ))
+        }
+        Query::Between { time, end, zone } => {
+            let zone = Zone::parse(zone)?;
+            let start = zone.read(time)?;
+            let end = zone.read(Some(end))?;
+            Ok(format!(
+                "From {} to {} ({}): {}",
+                zone.show(start),
+                zone.show(end),
+                zone.name(),
+                span(&zone, start, end)
+            ))
+        }
+    }
+}
+
+/// The system note giving today's date and the local time, so the model
+/// does not fall back on its training cutoff for "today".
+pub fn today_note() -> String {
+    let now = Local::now();
+    format!(
+        "Today is {}. The user's local time is {} (UTC{}).",
+        now.format("%A, %-d %B %Y"),
+        now.format("%H:%M"),
+        now.format("%:z")
+    )
+}
+
+/// A time zone from the tz database, or the system's own.
+enum Zone {
+    Local,
+    Named(Tz),
+}
+
+impl Zone {
+    /// Accepts IANA names in any case (`europe/paris`), bare city names
+    /// (`Tokyo`, `new york`), `UTC`, and `local` or nothing for the
+    /// system zone.
+    fn parse(name: Option<&str>) -> Result<Self, String> {
+        let name = name.unwrap_or("").trim();
+        if name.is_empty() || name.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+        if let Ok(tz) = name.parse::<Tz>() {
+            return Ok(Zone::Named(tz));
+        }
+        let wanted = name.replace(' ', "_");
+        chrono_tz::TZ_VARIANTS
+            .iter()
+            .find(|tz| tz.name().eq_ignore_ascii_case(&wanted))
+            .or_else(|| {
+                chrono_tz::TZ_VARIANTS.iter().find(|tz| {
+                    tz.name()
+                        .rsplit('/')
+                        .next()
+                        .is_some_and(|city| city.eq_ignore_ascii_case(&wanted))
+                })
+            })
+            .map(|tz| Zone::Named(*tz))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown time zone '{}'; use an IANA name such as Europe/Paris",
+                    name
+                )
+            })
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Zone::Local => "local time".to_string(),
+            Zone::Named(tz) => tz.name().to_string(),
+        }
+    }
+
+    fn at(&self, instant: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Local => instant.with_timezone(&Local).fixed_offset(),
+            Zone::Named(tz) => instant.with_timezone(tz).fixed_offset(),
+        }
+    }
+
+    /// The instant a wall-clock time in this zone names. Times repeated
+    /// when clocks go back read as the earlier one.
+    fn resolve(&self, naive: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+        let resolved = match self {
+            Zone::Local => Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|t| t.to_utc()),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|t| t.to_utc()),
+        };
+        resolved.ok_or_else(|| {
+            format!(
+                "{} does not exist in {}; the clocks skip it",
+                naive.format("%Y-%m-%d %H:%M"),
+                self.name()
+            )
+        })
+    }
+
+    /// `time` read in this zone: `now`, an RFC 3339 timestamp, a date
+    /// with an optional time, or a time of day today.
+    fn read(&self, time: Option<&str>) -> Result<DateTime<Utc>, String> {
+        let time = time.unwrap_or("now").trim();
+        if time.is_empty() || time.eq_ignore_ascii_case("now") {
+            return Ok(Utc::now());
+        }
+        if time.eq_ignore_ascii_case("today") {
+            let today = self.at(Utc::now()).date_naive();
+            return self.resolve(today.and_time(NaiveTime::MIN));
+        }
+        if let Ok(instant) = DateTime::parse_from_rfc3339(time) {
+            return Ok(instant.to_utc());
+        }
+        for format in [
+            "%Y-%m-%d %H:%M:%S",
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%dT%H:%M:%S",
+            "%Y-%m-%dT%H:%M",
+        ] {
+            if let Ok(naive) = NaiveDateTime::parse_from_str(time, format) {
+                return self.resolve(naive);
+            }
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(time, "%Y-%m-%d") {
+            return self.resolve(date.and_time(NaiveTime::MIN));
+        }
+        for format in ["%H:%M:%S", "%H:%M"] {
+            if let Ok(clock) = NaiveTime::parse_from_str(time, format) {
+                let today = self.at(Utc::now()).date_naive();
+                return self.resolve(today.and_time(clock));
+            }
+        }
+        Err(format!(
+            "Could not read the time '{}'; use YYYY-MM-DD, YYYY-MM-DD HH:MM, HH:MM or now",
+            time
+        ))
+    }
+
+    /// `Wednesday, 1 May 2024, 14:00:00 CEST (UTC+02:00)`.
+    fn show(&self, instant: DateTime<Utc>) -> String {
+        let (text, abbreviation) = match self {
+            Zone::Local => (self.at(instant), String::new()),
+            Zone::Named(tz) => {
+                let time = instant.with_timezone(tz);
+                let abbreviation = time.format("%Z").to_string();
+                // Zones without a letter abbreviation print their offset.
+                let abbreviation = if abbreviation.starts_with(['+', '-']) {
+                    String::new()
+                } else {
+                    format!(" {}", abbreviation)
+                };
+                (time.fixed_offset(), abbreviation)
+            }
+        };
+        format!(
+            "{}{} (UTC{})",
+            text.format("%A, %-d %B %Y, %H:%M:%S"),
+            abbreviation,
+            text.format("%:z")
+        )
+    }
+}
+
+/// The time in `zone` with its place in the calendar.
+fn with_calendar(zone: &Zone, now: DateTime<Utc>) -> String {
+    let local = zone.at(now);
+    format!(
+        "{}: {}\nISO 8601: {}\nWeek {} of {}, day {} of the year",
+        zone.name(),
+        zone.show(now),
+        local.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        local.iso_week().week(),
+        local.iso_week().year(),
+        local.ordinal()
+    )
+}
+
+/// Adds a duration such as `3 weeks 2 days` or `-1 month`. Years, months,
+/// weeks and days move the calendar date and keep the wall-clock time;
+/// hours, minutes and seconds move the instant.
+fn add(zone: &Zone, start: DateTime<Utc>, duration: &str) -> Result<DateTime<Utc>, String> {
+    let term = Regex::new(r"(?i)([+-]?\d+)\s*([a-z]+)").unwrap();
+    let negate = duration.trim_start().starts_with('-');
+    let mut months: i64 = 0;
+    let mut days: i64 = 0;
+    let mut seconds: i64 = 0;
+    let mut terms = 0;
+    for capture in term.captures_iter(duration) {
+        terms += 1;
+        let mut amount: i64 = capture[1]
+            .parse()
+            .map_err(|_| format!("Number too large in '{}'", duration))?;
+        // A leading minus applies to every term: "-1 day 2 hours".
+        if negate && !capture[1].starts_with('-') {
+            amount = -amount;
+        }
+        let unit = capture[2].to_lowercase();
+        match unit.as_str() {
+            "y" | "yr" | "yrs" | "year" | "years" => months += amount * 12,
+            "mo" | "mon" | "month" | "months" => months += amount,
+            "w" | "wk" | "wks" | "week" | "weeks" => days += amount * 7,
+            "d" | "day" | "days" => days += amount,
+            "h" | "hr" | "hrs" | "hour" | "hours" => seconds += amount * 3600,
+            "m" | "min" | "mins" | "minute" | "minutes" => seconds += amount * 60,
+            "s" | "sec" | "secs" | "second" | "seconds" => seconds += amount,
+            other => return Err(format!("Unknown unit '{}' in '{}'", other, duration)),
+        }
+    }
+    let rest = term.replace_all(duration, "");
+    let unread = rest
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .any(|word| !matches!(word, "" | "and" | "+" | "-"));
+    if terms == 0 || unread {
+        return Err(format!(
+            "Could not read the duration '{}'; use terms such as 3 days, -2 weeks or 1 year 6 months",
+            duration
+        ));
+    }
+
+    let overflow = || format!("'{}' moves the date out of range", duration);
+    let mut date = zone.at(start).naive_local();
+    let shift = Months::new(months.unsigned_abs().try_into().map_err(|_| overflow())?);
+    date = if months < 0 {
+        date.checked_sub_months(shift)
+    } else {
+        date.checked_add_months(shift)
+    }
+    .ok_or_else(overflow)?;
+    date = date
+        .checked_add_signed(Duration::try_days(days).ok_or_else(overflow)?)
+        .ok_or_else(overflow)?;
+    zone.resolve(date)?
+        .checked_add_signed(Duration::try_seconds(seconds).ok_or_else(overflow)?)
+        .ok_or_else(overflow)
+}
+
+/// The time between two instants, in days and as a calendar span.
+fn span(zone: &Zone, start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    let (earlier, later, before) = if end < start {
+        (end, start, " (the second time is earlier)")
+    } else {
+        (start, end, "")
+    };
+    let total = later - earlier;
+    let mut parts = vec![plural(total.num_days(), "day")];
+    let hours = total.num_hours() % 24;
+    let minutes = total.num_minutes() % 60;
+    if hours > 0 || minutes > 0 {
+        parts.push(plural(hours, "hour"));
+        parts.push(plural(minutes, "minute"));
+    }
+    let mut text = parts.join(", ");
+    if total.num_days() >= 7 {
+        text.push_str(&format!(
+            "; {} and {}",
+            plural(total.num_days() / 7, "week"),
+            plural(total.num_days() % 7, "day")
+        ));
+    }
+
+    // Whole months counted on the calendar, then the days left over.
+    let (from, to) = (zone.at(earlier).naive_local(), zone.at(later).naive_local());
+    let mut months = (to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32;
+    let shifted = |months: i32| from.checked_add_months(Months::new(months.max(0) as u32));
+    if shifted(months).is_some_and(|date| date > to) {
+        months -= 1;
+    }
+    if months > 0 {
+        let rest = shifted(months).map_or(0, |date| (to - date).num_days());
+        let mut calendar = Vec::new();
+        if months >= 12 {
+            calendar.push(plural(i64::from(months / 12), "year"));
+        }
+        if months % 12 > 0 {
+            calendar.push(plural(i64::from(months % 12), "month"));
+        }
+        calendar.push(plural(rest, "day"));
+        text.push_str(&format!("; {}", calendar.join(", ")));
+    }
+    text.push_str(before);
+    text
+}
+
+fn plural(count: i64, unit: &str) -> String {
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
@@ -0,0 +1,134 @@
+//! Diagnostic reports to attach to a bug report after a crash: Rusty's
+//! version and platform, the configuration with its secrets removed, and
+//! a log of the requests made lately, kept in memory with their model and
+//! size but not their content.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Local;
+use regex::Regex;
+
+use crate::config::Config;
+use crate::paths;
+
+/// Lines of the log kept for a report.
+const MAX_LOG_LINES: usize = 100;
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// The last request sent, described without its content.
+static LAST_REQUEST: Mutex<Option<String>> = Mutex::new(None);
+
+/// Where bugs are reported.
+pub const ISSUES_URL: &str = "https://github.com/AlphsX/rusty-cli/issues/new";
+
+/// Adds `line` to the log, with the time.
+pub fn log(line: &str) {
+    let Ok(mut log) = LOG.lock() else {
+        return;
+    };
+    if log.len() == MAX_LOG_LINES {
+        log.pop_front();
+    }
+    log.push_back(format!("{} {}", Local::now().format("%H:%M:%S"), line));
+}
+
+/// Notes the request about to be sent, `what` saying what it is without
+/// its content, such as the model and the number of messages.
+pub fn request(what: &str) {
+    log(&format!("request {}", what));
+    if let Ok(mut last) = LAST_REQUEST.lock() {
+        *last = Some(what.to_string());
+    }
+}
+
+/// Writes a report on `failure`, a panic or a fatal error, to the reports
+/// directory and returns its path.
+pub fn write_report(failure: &str) -> Result<PathBuf, String> {
+    let dir = paths::config_dir().join("reports");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!(
+        "report-{}.md",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    fs::write(&path, report(failure))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// The report as Markdown, ready to paste into an issue.
+pub fn report(failure: &str) -> String {
+    let mut out = String::from("# Rusty diagnostic report\n\n");
+    out.push_str(&format!("- Version: {}\n", env!("CARGO_PKG_VERSION")));
+    out.push_str(&format!(
+        "- Platform: {} {}\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    ));
+    if let Ok(term) = std::env::var("TERM") {
+        out.push_str(&format!("- Terminal: {}\n", term));
+    }
+    out.push_str(&format!(
+        "- Time: {}\n",
+        Local::now().format("%Y-%m-%d %H:%M:%S %:z")
+    ));
+
+    out.push_str(&format!(
+        "\n## What happened\n\n```text\n{}\n```\n",
+        redact(failure.trim())
+    ));
+
+    let last = LAST_REQUEST.lock().ok().and_then(|last| last.clone());
+    out.push_str("\n## Last request\n\n");
+    out.push_str(&match last {
+        Some(request) => format!("```text\n{}\n```\n", request),
+        None => "No request was sent.\n".to_string(),
+    });
+
+    let log: Vec<String> = LOG
+        .lock()
+        .map(|log| log.iter().cloned().collect())
+        .unwrap_or_default();
+    if !log.is_empty() {
+        out.push_str(&format!(
+            "\n## Recent log\n\n```text\n{}\n```\n",
+            redact(&log.join("\n"))
+        ));
+    }
+
+    out.push_str("\n## Configuration, secrets removed\n\n");
+    out.push_str(&match fs::read_to_string(Config::path()) {
+        Ok(text) => format!("```toml\n{}\n```\n", redact_config(text.trim_end())),
+        Err(_) => "No config.toml.\n".to_string(),
+    });
+    out
+}
+
+/// `toml` with the values of keys that hold secrets, such as API keys,
+/// tokens and passwords, replaced.
+pub fn redact_config(toml: &str) -> String {
+    let secret = Regex::new(
+        r#"(?i)^(\s*"?[\w.-]*(?:key|token|secret|password|passphrase|webhook_url)[\w.-]*"?\s*=\s*).*$"#,
+    )
+    .unwrap();
+    let lines: Vec<String> = toml
+        .lines()
+        .map(|line| secret.replace(line, r#"${1}"<redacted>""#).to_string())
+        .collect();
+    redact(&lines.join("\n"))
+}
+
+/// `text` with API keys, tokens and passwords in URLs replaced, wherever
+/// they appear.
+pub fn redact(text: &str) -> String {
+    let keys = Regex::new(
+        r"\b(?:gsk_|sk-|xox[abpr]-|ghp_|gho_|github_pat_|BSA)[A-Za-z0-9_\-]{8,}|\bBearer\s+\S+",
+    )
+    .unwrap();
+    let userinfo = Regex::new(r"://[^/\s:@]+:[^/\s@]+@").unwrap();
+    let text = keys.replace_all(text, "<redacted>");
+    userinfo.replace_all(&text, "://<redacted>@").to_string()
+}
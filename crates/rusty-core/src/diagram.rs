@@ -0,0 +1,311 @@
+//! Diagrams in answers: code blocks tagged `mermaid`, or `dot` for
+//! Graphviz, drawn to an image with `mmdc` (the Mermaid CLI) or `dot`, or
+//! approximated in text as the list of their edges when neither is at hand.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd};
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Mermaid,
+    Dot,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mermaid => "Mermaid",
+            Self::Dot => "Graphviz",
+        }
+    }
+
+    /// The program that draws it, and where to get it.
+    fn tool(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::Mermaid => ("mmdc", "npm install -g @mermaid-js/mermaid-cli"),
+            Self::Dot => (
+                "dot",
+                "install Graphviz from https://graphviz.org/download/",
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagram {
+    pub kind: Kind,
+    pub source: String,
+}
+
+/// The Mermaid and Graphviz code blocks of an answer, in order.
+pub fn find(markdown: &str) -> Vec<Diagram> {
+    let mut diagrams = Vec::new();
+    let mut current: Option<Diagram> = None;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let language = info.split_whitespace().next().unwrap_or_default();
+                let kind = match language.to_lowercase().as_str() {
+                    "mermaid" => Some(Kind::Mermaid),
+                    "dot" | "graphviz" => Some(Kind::Dot),
+                    _ => None,
+                };
+                current = kind.map(|kind| Diagram {
+                    kind,
+                    source: String::new(),
+                });
+            }
+            Event::Text(text) => {
+                if let Some(diagram) = current.as_mut() {
+                    diagram.source.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => diagrams.extend(current.take()),
+            _ => {}
+        }
+    }
+    diagrams
+}
+
+impl Diagram {
+    /// Draws the diagram as a PNG at `path`.
+    pub fn render(&self, path: &Path) -> Result<(), String> {
+        let (program, install) = self.kind.tool();
+        let mut command = Command::new(program);
+        match self.kind {
+            // `mmdc` reads the diagram from stdin with `-i -`.
+            Kind::Mermaid => command.args(["-i", "-", "-o"]).arg(path),
+            Kind::Dot => command.arg("-Tpng").arg("-o").arg(path),
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|_| format!("{} is not installed; {}", program, install))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(self.source.as_bytes())
+                .map_err(|e| format!("Could not run {}: {}", program, e))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Could not run {}: {}", program, e))?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            let error = error.lines().find(|line| !line.trim().is_empty());
+            return Err(format!(
+                "{} could not draw the diagram: {}",
+                program,
+                error.unwrap_or("it gave no reason").trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The diagram as text: each edge on a line, as `A ──▶ B`, with the
+    /// labels of nodes where they have one, and the nodes without edges
+    /// after.
+    pub fn text(&self) -> String {
+        let graph = match self.kind {
+            Kind::Mermaid => mermaid(&self.source),
+            Kind::Dot => dot(&self.source),
+        };
+        let mut lines: Vec<String> = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                let arrow = match &edge.label {
+                    Some(label) => format!("──{}──▶", label),
+                    None => "──▶".to_string(),
+                };
+                format!(
+                    "{} {} {}",
+                    graph.label(&edge.from),
+                    arrow,
+                    graph.label(&edge.to)
+                )
+            })
+            .collect();
+        for (id, _) in &graph.nodes {
+            if !graph.edges.iter().any(|e| e.from == *id || e.to == *id) {
+                lines.push(graph.label(id));
+            }
+        }
+        if lines.is_empty() {
+            return "(nothing to draw in text)".to_string();
+        }
+        lines.join("\n")
+    }
+}
+
+struct Edge {
+    from: String,
+    to: String,
+    label: Option<String>,
+}
+
+#[derive(Default)]
+struct Graph {
+    /// Node ids and their labels, in the order they appear.
+    nodes: Vec<(String, Option<String>)>,
+    edges: Vec<Edge>,
+}
+
+impl Graph {
+    fn node(&mut self, id: &str, label: Option<String>) {
+        match self.nodes.iter_mut().find(|(node, _)| node == id) {
+            Some((_, known)) => {
+                if label.is_some() {
+                    *known = label;
+                }
+            }
+            None => self.nodes.push((id.to_string(), label)),
+        }
+    }
+
+    fn label(&self, id: &str) -> String {
+        self.nodes
+            .iter()
+            .find(|(node, _)| node == id)
+            .and_then(|(_, label)| label.clone())
+            .unwrap_or_else(|| id.to_string())
+    }
+}
+
+/// The nodes and edges of a Mermaid flowchart, or the messages of a
+/// sequence diagram as edges.
+fn mermaid(source: &str) -> Graph {
+    let mut graph = Graph::default();
+    let message = Regex::new(r"^([^-:>]+?)\s*-{1,2}(?:>>|>|x|\))\s*([^:]+?)\s*:\s*(.*)$").unwrap();
+    // `A -->|label| B`, `A -- label --> B`, `A ==> B`, `A -.-> B`, `A --- B`.
+    let arrow = Regex::new(
+        r"^\s*(?:--\s+([^-|>]+?)\s+-->|==\s+([^=|>]+?)\s+==>|<?(?:-{2,}|={2,}|-\.+-)[>ox]?)\s*(?:\|([^|]*)\|)?\s*",
+    )
+    .unwrap();
+    let node = Regex::new(
+        r#"^([\w.:]+)\s*(?:\(\(([^)]*)\)\)|\[\[([^\]]*)\]\]|\[\(([^)]*)\)\]|\(\[([^\]]*)\]\)|\[([^\]]*)\]|\(([^)]*)\)|\{\{([^}]*)\}\}|\{([^}]*)\}|>([^\]]*)\])?"#,
+    )
+    .unwrap();
+
+    let mut lines = source.lines().flat_map(|line| line.split(';'));
+    let sequence = lines
+        .next()
+        .is_some_and(|first| first.trim().starts_with("sequenceDiagram"));
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("%%") || line.starts_with("style ") {
+            continue;
+        }
+        if sequence {
+            if let Some(c) = message.captures(line) {
+                graph.node(c[1].trim(), None);
+                graph.node(c[2].trim(), None);
+                graph.edges.push(Edge {
+                    from: c[1].trim().to_string(),
+                    to: c[2].trim().to_string(),
+                    label: Some(c[3].trim().to_string()).filter(|label| !label.is_empty()),
+                });
+            }
+            continue;
+        }
+        if line == "end"
+            || [
+                "subgraph",
+                "classDef",
+                "class ",
+                "click ",
+                "linkStyle",
+                "direction",
+            ]
+            .iter()
+            .any(|keyword| line.starts_with(keyword))
+        {
+            continue;
+        }
+
+        let mut previous: Option<String> = None;
+        let mut label = None;
+        let mut rest = line;
+        while let Some(c) = node.captures(rest) {
+            let id = c[1].to_string();
+            let text = (2..=10)
+                .find_map(|i| c.get(i))
+                .map(|m| m.as_str().trim().trim_matches('"').to_string());
+            graph.node(&id, text);
+            if let Some(from) = previous.take() {
+                graph.edges.push(Edge {
+                    from,
+                    to: id.clone(),
+                    label: label.take(),
+                });
+            }
+            rest = &rest[c[0].len()..];
+            let Some(a) = arrow.captures(rest) else {
+                break;
+            };
+            label = [1, 2, 3]
+                .iter()
+                .find_map(|&i| a.get(i))
+                .map(|m| m.as_str().trim().trim_matches('"').to_string())
+                .filter(|label| !label.is_empty());
+            previous = Some(id);
+            rest = &rest[a[0].len()..];
+        }
+    }
+    graph
+}
+
+/// The nodes and edges of a Graphviz graph.
+fn dot(source: &str) -> Graph {
+    let mut graph = Graph::default();
+    let attribute = |attributes: &str, name: &str| {
+        let pattern = format!(r#"\b{}\s*=\s*(?:"((?:[^"\\]|\\.)*)"|([\w.]+))"#, name);
+        let c = Regex::new(&pattern).unwrap().captures(attributes)?;
+        Some(c.get(1).or(c.get(2))?.as_str().replace("\\n", " "))
+    };
+    let statement = Regex::new(r"^(.*?)\s*(?:\[(.*)\])?\s*$").unwrap();
+    for line in source.lines().flat_map(|line| line.split(';')) {
+        // Statements after `digraph G {` or before `}` on the same line.
+        let line = line.rsplit('{').next().unwrap_or_default();
+        let line = line.split('}').next().unwrap_or_default().trim();
+        if line.is_empty()
+            || line.starts_with("//")
+            || line.starts_with('#')
+            || line.contains('=') && !line.contains('[')
+        {
+            continue;
+        }
+        let Some(c) = statement.captures(line) else {
+            continue;
+        };
+        let attributes = c.get(2).map_or("", |m| m.as_str());
+        let ids: Vec<String> = c[1]
+            .split("->")
+            .flat_map(|part| part.split("--"))
+            .map(|id| id.trim().trim_matches('"').to_string())
+            .filter(|id| !id.is_empty())
+            .collect();
+        match ids.as_slice() {
+            [] => {}
+            [id] if ["node", "edge", "graph"].contains(&id.as_str()) => {}
+            [id] => graph.node(id, attribute(attributes, "label")),
+            ids => {
+                for pair in ids.windows(2) {
+                    graph.node(&pair[0], None);
+                    graph.node(&pair[1], None);
+                    graph.edges.push(Edge {
+                        from: pair[0].clone(),
+                        to: pair[1].clone(),
+                        label: attribute(attributes, "label"),
+                    });
+                }
+            }
+        }
+    }
+    graph
+}
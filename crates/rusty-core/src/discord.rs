@@ -0,0 +1,380 @@
+//! A Discord bot: the gateway WebSocket delivers the messages addressed to
+//! the bot, and replies go out through the REST API.
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use reqwest::header::{AUTHORIZATION, USER_AGENT};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::Message as Frame;
+
+use crate::agent::split_message;
+
+const API_URL: &str = "https://discord.com/api/v10";
+const GATEWAY_URL: &str = "wss://gateway.discord.gg";
+const GATEWAY_QUERY: &str = "/?v=10&encoding=json";
+
+/// Guild messages and direct messages. Without the privileged message
+/// content intent, Discord still sends the text of messages that mention
+/// the bot and of direct messages, which is all the bot answers.
+const INTENTS: u64 = (1 << 9) | (1 << 12);
+
+/// The longest message Discord accepts, in characters.
+const MESSAGE_LIMIT: usize = 2000;
+
+/// Failed connection attempts in a row before giving up.
+const MAX_RECONNECTS: u32 = 5;
+
+/// How often the typing indicator is renewed; Discord shows it for ten
+/// seconds.
+const TYPING_INTERVAL: Duration = Duration::from_secs(8);
+
+/// Sets the scene for the model, since it sees who wrote each message.
+pub const SYSTEM_PROMPT: &str = "You are Rusty, an assistant answering messages in a Discord \
+channel. Each message starts with the name of the person who wrote it. Keep answers short, use \
+Discord Markdown (no tables or headings), and answer the latest message.";
+
+/// What the gateway reports.
+pub enum Event {
+    /// Connected, as the bot user with this name.
+    Ready(String),
+    Message(IncomingMessage),
+}
+
+/// A message that mentions the bot or was sent to it directly.
+#[derive(Debug, Clone)]
+pub struct IncomingMessage {
+    pub id: String,
+    pub channel_id: String,
+    /// The sender's nickname on the server, or their display name.
+    pub author: String,
+    /// The text, without the mention of the bot.
+    pub content: String,
+}
+
+#[derive(Clone)]
+pub struct DiscordBot {
+    token: String,
+    client: reqwest::Client,
+}
+
+impl DiscordBot {
+    pub fn new(token: String) -> Self {
+        Self {
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Connects to the gateway in the background, reconnecting when the
+    /// connection drops. Events arrive on the returned channel, which ends
+    /// with an `Err` once the token is rejected or Discord cannot be reached.
+    pub fn listen(&self) -> mpsc::UnboundedReceiver<Result<Event, String>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run_gateway(self.token.clone(), sender));
+        receiver
+    }
+
+    /// Shows the bot typing in `channel_id` until the task is aborted.
+    pub fn keep_typing(&self, channel_id: &str) -> JoinHandle<()> {
+        let bot = self.clone();
+        let path = format!("/channels/{}/typing", channel_id);
+        tokio::spawn(async move {
+            loop {
+                let _ = bot.post(&path, &json!({})).await;
+                tokio::time::sleep(TYPING_INTERVAL).await;
+            }
+        })
+    }
+
+    /// Answers `message` in its channel, in several messages when the text
+    /// is longer than Discord allows. Mentions in the text ping nobody.
+    pub async fn reply(&self, message: &IncomingMessage, text: &str) -> Result<(), String> {
+        let path = format!("/channels/{}/messages", message.channel_id);
+        for (i, piece) in split_message(text, MESSAGE_LIMIT).iter().enumerate() {
+            let mut body = json!({
+                "content": piece,
+                "allowed_mentions": { "parse": [] },
+            });
+            if i == 0 {
+                body["message_reference"] = json!({
+                    "message_id": message.id,
+                    "fail_if_not_exists": false,
+                });
+            }
+            self.post(&path, &body).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends `body` to an API endpoint, waiting out rate limits a few
+    /// times.
+    async fn post(&self, path: &str, body: &Value) -> Result<(), String> {
+        for _ in 0..3 {
+            let response = self
+                .client
+                .post(format!("{}{}", API_URL, path))
+                .header(AUTHORIZATION, format!("Bot {}", self.token))
+                .header(
+                    USER_AGENT,
+                    format!(
+                        "DiscordBot (https://github.com/AlphsX/Rusty, {})",
+                        env!("CARGO_PKG_VERSION")
+                    ),
+                )
+                .json(body)
+                .send()
+                .await
+                .map_err(|e| format!("Discord request failed: {}", e))?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            let json: Value = response.json().await.unwrap_or_default();
+            if let (429, Some(secs)) = (status.as_u16(), json["retry_after"].as_f64()) {
+                tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+                continue;
+            }
+            let message = json["message"].as_str().unwrap_or("unknown error");
+            return Err(format!("Discord returned {}: {}", status, message));
+        }
+        Err("Discord kept rate-limiting the bot".to_string())
+    }
+}
+
+/// What a dropped connection needs to pick up where it left off.
+#[derive(Default)]
+struct Session {
+    bot_id: String,
+    id: Option<String>,
+    resume_url: Option<String>,
+    sequence: Option<u64>,
+    /// Set once the connection is identified or resumed.
+    healthy: bool,
+}
+
+impl Session {
+    fn forget(&mut self) {
+        self.id = None;
+        self.resume_url = None;
+        self.sequence = None;
+    }
+}
+
+/// Why a connection ended.
+enum Disconnect {
+    /// Connect again, resuming the session if Discord still knows it.
+    Reconnect(String),
+    /// The token or the settings were rejected; connecting again will not
+    /// help.
+    Fatal(String),
+    /// Nobody is listening for events any more.
+    Closed,
+}
+
+async fn run_gateway(token: String, events: mpsc::UnboundedSender<Result<Event, String>>) {
+    let mut session = Session::default();
+    let mut failures = 0;
+    loop {
+        let reason = match connect(&token, &mut session, &events).await {
+            Disconnect::Closed => return,
+            Disconnect::Fatal(reason) => reason,
+            Disconnect::Reconnect(reason) => {
+                if std::mem::take(&mut session.healthy) {
+                    failures = 0;
+                }
+                failures += 1;
+                if failures <= MAX_RECONNECTS {
+                    tokio::time::sleep(Duration::from_secs(1 << failures)).await;
+                    continue;
+                }
+                reason
+            }
+        };
+        let _ = events.send(Err(reason));
+        return;
+    }
+}
+
+/// One connection to the gateway, from hello to disconnect.
+async fn connect(
+    token: &str,
+    session: &mut Session,
+    events: &mpsc::UnboundedSender<Result<Event, String>>,
+) -> Disconnect {
+    let url = format!(
+        "{}{}",
+        session.resume_url.as_deref().unwrap_or(GATEWAY_URL),
+        GATEWAY_QUERY
+    );
+    let mut socket = match tokio_tungstenite::connect_async(url).await {
+        Ok((socket, _)) => socket,
+        Err(e) => return Disconnect::Reconnect(format!("Could not connect to Discord: {}", e)),
+    };
+
+    // The gateway opens with Hello, which sets the heartbeat interval.
+    let interval = match socket.next().await {
+        Some(Ok(Frame::Text(text))) => serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|hello| hello["d"]["heartbeat_interval"].as_u64()),
+        _ => None,
+    };
+    let Some(interval) = interval else {
+        return Disconnect::Reconnect("Discord did not say hello".to_string());
+    };
+    let period = Duration::from_millis(interval);
+    let mut heartbeat = tokio::time::interval_at(Instant::now() + period / 2, period);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut acknowledged = true;
+
+    let greeting = match (&session.id, session.sequence) {
+        (Some(id), Some(sequence)) => json!({
+            "op": 6,
+            "d": { "token": token, "session_id": id, "seq": sequence },
+        }),
+        _ => json!({
+            "op": 2,
+            "d": {
+                "token": token,
+                "intents": INTENTS,
+                "properties": {
+                    "os": std::env::consts::OS,
+                    "browser": "rusty",
+                    "device": "rusty",
+                },
+            },
+        }),
+    };
+    if let Err(e) = socket.send(Frame::text(greeting.to_string())).await {
+        return Disconnect::Reconnect(format!("Discord connection failed: {}", e));
+    }
+
+    loop {
+        let frame = tokio::select! {
+            _ = heartbeat.tick() => {
+                if !acknowledged {
+                    return Disconnect::Reconnect("Discord stopped answering heartbeats".to_string());
+                }
+                acknowledged = false;
+                let beat = json!({ "op": 1, "d": session.sequence });
+                if let Err(e) = socket.send(Frame::text(beat.to_string())).await {
+                    return Disconnect::Reconnect(format!("Discord connection failed: {}", e));
+                }
+                continue;
+            }
+            frame = socket.next() => frame,
+        };
+
+        let payload = match frame {
+            Some(Ok(Frame::Text(text))) => match serde_json::from_str::<Value>(&text) {
+                Ok(payload) => payload,
+                Err(_) => continue,
+            },
+            Some(Ok(Frame::Close(frame))) => {
+                return match frame.map(|frame| u16::from(frame.code)) {
+                    Some(4004) => Disconnect::Fatal("Discord rejected the bot token".to_string()),
+                    Some(code @ (4010..=4014)) => Disconnect::Fatal(format!(
+                        "Discord refused the connection (close code {})",
+                        code
+                    )),
+                    // The session is gone; identify afresh.
+                    Some(4007 | 4009) => {
+                        session.forget();
+                        Disconnect::Reconnect("Discord session expired".to_string())
+                    }
+                    _ => Disconnect::Reconnect("Discord closed the connection".to_string()),
+                };
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => {
+                return Disconnect::Reconnect(format!("Discord connection failed: {}", e))
+            }
+            None => return Disconnect::Reconnect("Discord closed the connection".to_string()),
+        };
+
+        if let Some(sequence) = payload["s"].as_u64() {
+            session.sequence = Some(sequence);
+        }
+        let data = &payload["d"];
+        match payload["op"].as_u64() {
+            Some(0) => {
+                let event = match payload["t"].as_str() {
+                    Some("READY") => {
+                        session.bot_id =
+                            data["user"]["id"].as_str().unwrap_or_default().to_string();
+                        session.id = data["session_id"].as_str().map(str::to_string);
+                        session.resume_url =
+                            data["resume_gateway_url"].as_str().map(str::to_string);
+                        session.healthy = true;
+                        let name = data["user"]["username"].as_str().unwrap_or_default();
+                        Some(Event::Ready(name.to_string()))
+                    }
+                    Some("RESUMED") => {
+                        session.healthy = true;
+                        None
+                    }
+                    Some("MESSAGE_CREATE") => addressed(data, &session.bot_id).map(Event::Message),
+                    _ => None,
+                };
+                if let Some(event) = event {
+                    if events.send(Ok(event)).is_err() {
+                        return Disconnect::Closed;
+                    }
+                }
+            }
+            // Discord asks for a heartbeat right away.
+            Some(1) => heartbeat.reset_immediately(),
+            Some(7) => return Disconnect::Reconnect("Discord asked to reconnect".to_string()),
+            // Invalid session; `d` says whether it can still be resumed.
+            Some(9) => {
+                if data.as_bool() != Some(true) {
+                    session.forget();
+                }
+                return Disconnect::Reconnect("Discord invalidated the session".to_string());
+            }
+            Some(11) => acknowledged = true,
+            _ => {}
+        }
+    }
+}
+
+/// The message in a `MESSAGE_CREATE` event, if it mentions the bot or was
+/// sent to it directly. Messages from bots, the bot itself included, are
+/// ignored.
+fn addressed(data: &Value, bot_id: &str) -> Option<IncomingMessage> {
+    let author = &data["author"];
+    if author["bot"].as_bool() == Some(true) || author["id"] == bot_id {
+        return None;
+    }
+    let direct = data["guild_id"].is_null();
+    let mentioned = data["mentions"]
+        .as_array()
+        .is_some_and(|users| users.iter().any(|user| user["id"] == bot_id));
+    if !direct && !mentioned {
+        return None;
+    }
+
+    let content = data["content"]
+        .as_str()
+        .unwrap_or_default()
+        .replace(&format!("<@{}>", bot_id), "")
+        .replace(&format!("<@!{}>", bot_id), "");
+    let content = content.trim();
+    if content.is_empty() {
+        return None;
+    }
+    let author_name = data["member"]["nick"]
+        .as_str()
+        .or(author["global_name"].as_str())
+        .or(author["username"].as_str())
+        .unwrap_or("someone");
+    Some(IncomingMessage {
+        id: data["id"].as_str()?.to_string(),
+        channel_id: data["channel_id"].as_str()?.to_string(),
+        author: author_name.to_string(),
+        content: content.to_string(),
+    })
+}
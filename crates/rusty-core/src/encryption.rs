@@ -0,0 +1,197 @@
+//! Optional at-rest encryption for saved sessions and prompt history.
+//!
+//! A 256-bit key is derived from the user's passphrase with Argon2id and used
+//! with ChaCha20-Poly1305. The salt lives in a small key file next to the
+//! data, together with an encrypted marker that lets a wrong passphrase be
+//! rejected up front instead of surfacing as unreadable sessions.
+
+use std::fs;
+use std::path::Path;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+/// Prefix of every encrypted file, followed by a version byte.
+const MAGIC: &[u8] = b"RUSTYENC";
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+/// Plaintext of the marker stored in the key file.
+const MARKER: &[u8] = b"rusty";
+
+#[derive(Clone)]
+pub struct Cipher {
+    key: Key,
+}
+
+impl Cipher {
+    /// Derives the key from `passphrase`. The first call creates the key file
+    /// at `key_file` with a fresh salt; later calls fail with "Wrong
+    /// passphrase" if the passphrase does not match.
+    pub fn unlock(key_file: &Path, passphrase: &str) -> Result<Self, String> {
+        if let Ok(data) = fs::read(key_file) {
+            if data.len() < SALT_LEN {
+                return Err(format!("Corrupt key file {}", key_file.display()));
+            }
+            let (salt, marker) = data.split_at(SALT_LEN);
+            let cipher = Self::derive(passphrase, salt)?;
+            return match cipher.decrypt(marker) {
+                Ok(plain) if plain == MARKER => Ok(cipher),
+                _ => Err("Wrong passphrase".to_string()),
+            };
+        }
+
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = Self::derive(passphrase, &salt)?;
+        let mut data = salt.to_vec();
+        data.extend(cipher.encrypt(MARKER)?);
+        if let Some(parent) = key_file.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(key_file, data)
+            .map_err(|e| format!("Failed to write {}: {}", key_file.display(), e))?;
+        Ok(cipher)
+    }
+
+    fn derive(passphrase: &str, salt: &[u8]) -> Result<Self, String> {
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| format!("Key derivation failed: {}", e))?;
+        Ok(Self { key })
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Encryption failed".to_string())?;
+        let mut out = Vec::with_capacity(MAGIC.len() + 1 + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&nonce);
+        out.extend(ciphertext);
+        Ok(out)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let body = data
+            .strip_prefix(MAGIC)
+            .and_then(|rest| rest.strip_prefix(&[VERSION]))
+            .filter(|body| body.len() >= NONCE_LEN)
+            .ok_or("Not an encrypted file")?;
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        ChaCha20Poly1305::new(&self.key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Decryption failed (wrong passphrase or damaged file)".to_string())
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Reads a file that may or may not be encrypted, returning its text.
+pub fn read_text(path: &Path, cipher: Option<&Cipher>) -> Result<String, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let plain = match (is_encrypted(&data), cipher) {
+        (false, _) => data,
+        (true, Some(cipher)) => cipher
+            .decrypt(&data)
+            .map_err(|e| format!("{}: {}", path.display(), e))?,
+        (true, None) => {
+            return Err(format!(
+                "{} is encrypted; enable [storage] encrypt to read it",
+                path.display()
+            ))
+        }
+    };
+    String::from_utf8(plain).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Writes `text`, encrypting it when a cipher is given.
+pub fn write_text(path: &Path, text: &str, cipher: Option<&Cipher>) -> Result<(), String> {
+    let data = match cipher {
+        Some(cipher) => cipher.encrypt(text.as_bytes())?,
+        None => text.as_bytes().to_vec(),
+    };
+    fs::write(path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A key file of its own for each test, removed when it ends.
+    struct KeyFile(std::path::PathBuf);
+
+    impl KeyFile {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "rusty-encryption-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            Self(dir.join("key"))
+        }
+    }
+
+    impl Drop for KeyFile {
+        fn drop(&mut self) {
+            if let Some(dir) = self.0.parent() {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_through_encryption() {
+        let key_file = KeyFile::new("round-trip");
+        let cipher = Cipher::unlock(&key_file.0, "correct horse").unwrap();
+        let data = cipher.encrypt(b"a saved session").unwrap();
+        assert!(is_encrypted(&data));
+        assert!(!data.windows(5).any(|w| w == b"saved"));
+        assert_eq!(cipher.decrypt(&data).unwrap(), b"a saved session");
+
+        let again = Cipher::unlock(&key_file.0, "correct horse").unwrap();
+        assert_eq!(again.decrypt(&data).unwrap(), b"a saved session");
+    }
+
+    #[test]
+    fn refuses_a_wrong_passphrase() {
+        let key_file = KeyFile::new("wrong-passphrase");
+        let cipher = Cipher::unlock(&key_file.0, "correct horse").unwrap();
+        let data = cipher.encrypt(b"a saved session").unwrap();
+
+        assert_eq!(
+            Cipher::unlock(&key_file.0, "battery staple")
+                .err()
+                .as_deref(),
+            Some("Wrong passphrase")
+        );
+        // A key from another passphrase cannot read the data either.
+        let other = KeyFile::new("other-passphrase");
+        let stranger = Cipher::unlock(&other.0, "battery staple").unwrap();
+        assert!(stranger.decrypt(&data).is_err());
+    }
+
+    #[test]
+    fn reads_plain_files_and_refuses_encrypted_ones_without_a_key() {
+        let key_file = KeyFile::new("read-text");
+        let cipher = Cipher::unlock(&key_file.0, "correct horse").unwrap();
+        let dir = key_file.0.parent().unwrap();
+        let plain = dir.join("plain.json");
+        let secret = dir.join("secret.json");
+        write_text(&plain, "{}", None).unwrap();
+        write_text(&secret, "{}", Some(&cipher)).unwrap();
+
+        assert_eq!(read_text(&plain, Some(&cipher)).unwrap(), "{}");
+        assert_eq!(read_text(&secret, Some(&cipher)).unwrap(), "{}");
+        assert!(read_text(&secret, None).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! The note telling the model about the user's machine: the operating
+//! system, the shell, the working directory and its Git repository, as
+//! `[environment]` allows. Today's date is sent apart from it, as it
+//! changes more often.
+
+use std::fs;
+
+use crate::config::EnvironmentConfig;
+use crate::repo::{self, Repo};
+
+/// The note, or `None` when everything is turned off.
+pub fn note(config: &EnvironmentConfig) -> Option<String> {
+    let mut facts = Vec::new();
+    if config.os {
+        facts.push(format!(
+            "Operating system: {} ({})",
+            os_name(),
+            std::env::consts::ARCH
+        ));
+    }
+    if config.shell {
+        if let Some(shell) = shell() {
+            facts.push(format!("Shell: {}", shell));
+        }
+    }
+    if config.cwd {
+        facts.push(format!("Working directory: {}", repo::working_dir()));
+    }
+    if config.git {
+        facts.push(match Repo::current() {
+            Some(repo) => format!("Git: {}", describe(&repo)),
+            None => "Git: not in a repository".to_string(),
+        });
+    }
+    if facts.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "The user's environment, for questions about their setup:\n- {}",
+        facts.join("\n- ")
+    ))
+}
+
+/// "branch main of rusty, 3 changed files, 1 commit not pushed".
+fn describe(repo: &Repo) -> String {
+    let mut text = format!("branch {} of {}", repo.branch, repo.name);
+    text.push_str(&match repo.changed {
+        0 => ", no changes".to_string(),
+        1 => ", 1 changed file".to_string(),
+        n => format!(", {} changed files", n),
+    });
+    for (count, what) in [(repo.ahead, "not pushed"), (repo.behind, "not pulled")] {
+        match count {
+            0 => {}
+            1 => text.push_str(&format!(", 1 commit {}", what)),
+            n => text.push_str(&format!(", {} commits {}", n, what)),
+        }
+    }
+    text
+}
+
+/// The distribution's name on Linux, such as "Ubuntu 24.04.1 LTS", else
+/// the name Rust knows the system by.
+fn os_name() -> String {
+    let pretty = fs::read_to_string("/etc/os-release").ok().and_then(|text| {
+        text.lines()
+            .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+            .map(|name| name.trim_matches('"').to_string())
+    });
+    match std::env::consts::OS {
+        "linux" => pretty.unwrap_or_else(|| "Linux".to_string()),
+        "macos" => "macOS".to_string(),
+        "windows" => "Windows".to_string(),
+        os => os.to_string(),
+    }
+}
+
+/// `$SHELL`, which Windows only has under shells such as Git Bash.
+fn shell() -> Option<String> {
+    std::env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+}
@@ -0,0 +1,258 @@
+//! Renders sessions for reading outside the terminal.
+
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+use crate::message::Message;
+use crate::sessions::Session;
+
+const HTML_STYLE: &str = r#"
+body { background: #1e1e2e; color: #cdd6f4; font: 15px/1.6 -apple-system, "Segoe UI", sans-serif; margin: 0; }
+main { max-width: 860px; margin: 0 auto; padding: 32px 20px; }
+h1 { color: #ff8c00; margin-bottom: 4px; }
+.meta { color: #7f849c; margin-bottom: 24px; }
+.system { border-left: 3px solid #7f849c; padding: 4px 12px; color: #a6adc8; white-space: pre-wrap; }
+.turn { margin: 20px 0; }
+.role { font-weight: 600; font-size: 13px; text-transform: uppercase; letter-spacing: .05em; }
+.user .role { color: #7aa2f7; }
+.assistant .role { color: #ff8c00; }
+.user .body { background: #313244; border-radius: 8px; padding: 10px 14px; white-space: pre-wrap; }
+pre { border-radius: 6px; padding: 12px; overflow-x: auto; font-size: 13px; }
+code { font-family: "JetBrains Mono", Menlo, Consolas, monospace; }
+:not(pre) > code { background: #313244; border-radius: 4px; padding: 1px 5px; }
+details { background: #181825; border: 1px solid #313244; border-radius: 6px; margin: 8px 0; padding: 6px 12px; }
+summary { cursor: pointer; color: #a6adc8; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #45475a; padding: 4px 10px; }
+a { color: #89b4fa; }
+"#;
+
+/// The session as Markdown: a title, the custom system prompt if any, then
+/// each turn. Tool calls and their results are folded into `<details>` blocks
+/// so the conversation itself stays readable.
+pub fn to_markdown(session: &Session) -> String {
+    let mut out = format!("# {}\n\n", session.display_title());
+    out.push_str(&format!("_Model: `{}`_", session.model));
+    if !session.tags.is_empty() {
+        let tags: Vec<String> = session.tags.iter().map(|t| format!("#{}", t)).collect();
+        out.push_str(&format!(" · _{}_", tags.join(" ")));
+    }
+    out.push_str("\n\n");
+    if let Some(prompt) = &session.system_prompt {
+        for line in prompt.lines() {
+            out.push_str(&format!("> {}\n", line));
+        }
+        out.push('\n');
+    }
+
+    for message in &session.messages {
+        push_message(&mut out, message);
+    }
+    out
+}
+
+fn push_message(out: &mut String, message: &Message) {
+    let content = message.content.as_deref().unwrap_or("").trim();
+    match message.role.as_str() {
+        "user" => out.push_str(&format!("## User\n\n{}\n\n", content)),
+        "assistant" => {
+            if !content.is_empty() {
+                out.push_str(&format!("## Assistant\n\n{}\n\n", content));
+            }
+            for call in message.tool_calls.iter().flatten() {
+                let arguments = serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+                    .and_then(|v| serde_json::to_string_pretty(&v))
+                    .unwrap_or_else(|_| call.function.arguments.clone());
+                out.push_str(&format!(
+                    "<details>\n<summary>Tool call: {}</summary>\n\n```json\n{}\n```\n\n</details>\n\n",
+                    call.function.name, arguments
+                ));
+            }
+        }
+        "tool" => {
+            // Longer than any run of backticks in the result, so pages
+            // fetched as Markdown cannot end the fence early.
+            let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+            let fence = "`".repeat(longest.max(2) + 1);
+            out.push_str(&format!(
+                "<details>\n<summary>Tool result</summary>\n\n{}\n{}\n{}\n\n</details>\n\n",
+                fence, content, fence
+            ))
+        }
+        _ => {}
+    }
+}
+
+/// Sessions as OpenAI-style chat JSONL, for fine-tuning or evaluation
+/// datasets: one line per session with its `messages`, the custom system
+/// prompt first. With `flatten_tools`, tool calls and their results are left
+/// out and the assistant text around them joined into one answer, for
+/// models trained without tools. Sessions without an answer are skipped.
+pub fn to_jsonl(sessions: &[Session], flatten_tools: bool) -> String {
+    let mut out = String::new();
+    for session in sessions {
+        let mut messages: Vec<Message> = session
+            .system_prompt
+            .iter()
+            .map(|prompt| Message::system(prompt))
+            .collect();
+        for message in &session.messages {
+            if !flatten_tools {
+                messages.push(message.clone());
+                continue;
+            }
+            let content = message.content.as_deref().unwrap_or("").trim();
+            match message.role.as_str() {
+                "tool" => {}
+                _ if content.is_empty() => {}
+                "assistant" => match messages.last_mut() {
+                    Some(last) if last.role == "assistant" => {
+                        let joined =
+                            format!("{}\n\n{}", last.content.as_deref().unwrap_or(""), content);
+                        last.content = Some(joined);
+                    }
+                    _ => messages.push(Message::assistant(content)),
+                },
+                _ => messages.push(message.clone()),
+            }
+        }
+        // An example ends with the answer it teaches.
+        while messages.last().is_some_and(|m| m.role != "assistant") {
+            messages.pop();
+        }
+        if messages.is_empty() {
+            continue;
+        }
+        let line = serde_json::json!({ "messages": messages });
+        out.push_str(&line.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+/// The session as a standalone HTML page: Markdown rendered, code blocks
+/// highlighted with syntect, and tool calls in collapsible `<details>`.
+pub fn to_html(session: &Session) -> String {
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+    let themes = ThemeSet::load_defaults();
+    let highlighter = Highlighter {
+        syntaxes: &syntaxes,
+        theme: &themes.themes["base16-mocha.dark"],
+    };
+
+    let title = escape_html(&session.display_title());
+    let mut body = format!(
+        "<h1>{}</h1>\n<div class=\"meta\">Model: <code>{}</code>",
+        title,
+        escape_html(&session.model)
+    );
+    for tag in &session.tags {
+        body.push_str(&format!(" · #{}", escape_html(tag)));
+    }
+    body.push_str("</div>\n");
+    if let Some(prompt) = &session.system_prompt {
+        body.push_str(&format!(
+            "<div class=\"system\">{}</div>\n",
+            escape_html(prompt)
+        ));
+    }
+
+    for message in &session.messages {
+        let content = message.content.as_deref().unwrap_or("").trim();
+        match message.role.as_str() {
+            "user" => body.push_str(&format!(
+                "<section class=\"turn user\"><div class=\"role\">User</div><div class=\"body\">{}</div></section>\n",
+                escape_html(content)
+            )),
+            "assistant" => {
+                body.push_str("<section class=\"turn assistant\">");
+                if !content.is_empty() {
+                    body.push_str("<div class=\"role\">Assistant</div>");
+                    body.push_str(&highlighter.markdown(content));
+                }
+                for call in message.tool_calls.iter().flatten() {
+                    let arguments = serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+                        .and_then(|v| serde_json::to_string_pretty(&v))
+                        .unwrap_or_else(|_| call.function.arguments.clone());
+                    body.push_str(&format!(
+                        "<details><summary>Tool call: {}</summary>{}</details>",
+                        escape_html(&call.function.name),
+                        highlighter.code(&arguments, "json")
+                    ));
+                }
+                body.push_str("</section>\n");
+            }
+            "tool" => body.push_str(&format!(
+                "<details><summary>Tool result</summary>{}</details>\n",
+                highlighter.markdown(content)
+            )),
+            _ => {}
+        }
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body><main>\n{}</main></body>\n</html>\n",
+        title, HTML_STYLE, body
+    )
+}
+
+struct Highlighter<'a> {
+    syntaxes: &'a SyntaxSet,
+    theme: &'a syntect::highlighting::Theme,
+}
+
+impl Highlighter<'_> {
+    /// A highlighted `<pre>` block, falling back to plain text for unknown languages.
+    fn code(&self, code: &str, language: &str) -> String {
+        let syntax = self
+            .syntaxes
+            .find_syntax_by_token(language)
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+        highlighted_html_for_string(code, self.syntaxes, syntax, self.theme)
+            .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>", escape_html(code)))
+    }
+
+    /// Markdown to HTML, with fenced code blocks swapped for highlighted ones.
+    fn markdown(&self, text: &str) -> String {
+        let mut events = Vec::new();
+        let mut code_block: Option<(String, String)> = None;
+        for event in Parser::new_ext(text, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH) {
+            match event {
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(info) => {
+                            info.split_whitespace().next().unwrap_or("").to_string()
+                        }
+                        CodeBlockKind::Indented => String::new(),
+                    };
+                    code_block = Some((language, String::new()));
+                }
+                Event::Text(text) if code_block.is_some() => {
+                    if let Some((_, code)) = code_block.as_mut() {
+                        code.push_str(&text);
+                    }
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    if let Some((language, code)) = code_block.take() {
+                        events.push(Event::Html(self.code(&code, &language).into()));
+                    }
+                }
+                // Raw HTML in model output is shown as text, not injected into the page.
+                Event::Html(raw) | Event::InlineHtml(raw) => events.push(Event::Text(raw)),
+                other => events.push(other),
+            }
+        }
+        let mut out = String::new();
+        html::push_html(&mut out, events.into_iter());
+        out
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
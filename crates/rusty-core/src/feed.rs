@@ -0,0 +1,236 @@
+//! Recent entries from RSS and Atom feeds, for the `read_feed` tool. A page
+//! that is not a feed is searched for the feed it advertises.
+
+use regex::Regex;
+use reqwest::Url;
+use roxmltree::{Document, Node, ParsingOptions};
+
+/// Entries listed when the model does not ask for a number.
+const DEFAULT_ENTRIES: usize = 10;
+
+/// Most entries listed at once.
+const MAX_ENTRIES: usize = 30;
+
+/// Characters of each entry's summary shown.
+const SUMMARY_CHARS: usize = 400;
+
+/// One item of an RSS feed or entry of an Atom feed.
+struct Entry {
+    title: String,
+    link: Option<String>,
+    date: Option<String>,
+    summary: String,
+}
+
+/// The newest `limit` entries of the feed at `url`, or of the feed linked
+/// from the page at `url`.
+pub async fn read(url: &str, limit: Option<usize>) -> Result<String, String> {
+    let limit = limit.unwrap_or(DEFAULT_ENTRIES).clamp(1, MAX_ENTRIES);
+    let client = reqwest::Client::new();
+    let url = Url::parse(url.trim()).map_err(|e| format!("Invalid feed URL: {}", e))?;
+
+    let mut body = fetch(&client, &url).await?;
+    let mut source = url.to_string();
+    if !looks_like_feed(&body) {
+        let feed_url = discover(&body, &url)
+            .ok_or_else(|| format!("{} is not a feed and links to none", url))?;
+        body = fetch(&client, &feed_url).await?;
+        source = feed_url.to_string();
+    }
+
+    let (title, entries) = parse_feed(&body)?;
+    let mut output = format!("### {}\n", title.as_deref().unwrap_or("Feed"));
+    output.push_str(&format!("{}\n\n", source));
+    if entries.is_empty() {
+        output.push_str("The feed has no entries.\n");
+    }
+    for (i, entry) in entries.iter().take(limit).enumerate() {
+        output.push_str(&format!("{}. **{}**\n", i + 1, entry.title));
+        if let Some(date) = &entry.date {
+            output.push_str(&format!("   - Date: {}\n", date));
+        }
+        if !entry.summary.is_empty() {
+            let mut snippet: String = entry.summary.chars().take(SUMMARY_CHARS).collect();
+            if snippet.len() < entry.summary.len() {
+                snippet.push('…');
+            }
+            output.push_str(&format!("   - Summary: {}\n", snippet));
+        }
+        if let Some(link) = &entry.link {
+            output.push_str(&format!("   - URL: {}\n", link));
+        }
+        output.push('\n');
+    }
+    if entries.len() > limit {
+        output.push_str(&format!(
+            "({} older entries not shown.)\n",
+            entries.len() - limit
+        ));
+    }
+    Ok(output)
+}
+
+async fn fetch(client: &reqwest::Client, url: &Url) -> Result<String, String> {
+    let response = client
+        .get(url.clone())
+        .header("User-Agent", "rusty-cli")
+        .send()
+        .await
+        .map_err(|e| format!("Feed request failed: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("{} returned {}", url, status));
+    }
+    response
+        .text()
+        .await
+        .map_err(|e| format!("Feed request failed: {}", e))
+}
+
+fn looks_like_feed(body: &str) -> bool {
+    let head: String = body.chars().take(1_000).collect::<String>().to_lowercase();
+    ["<rss", "<feed", "<rdf:rdf"]
+        .iter()
+        .any(|tag| head.contains(tag))
+}
+
+/// The first RSS or Atom feed a web page announces with
+/// `<link rel="alternate">`, resolved against the page's URL.
+fn discover(html: &str, base: &Url) -> Option<Url> {
+    let link = Regex::new(r"(?is)<link\b[^>]*>").unwrap();
+    let attribute = |tag: &str, name: &str| {
+        Regex::new(&format!(r#"(?i)\b{}\s*=\s*["']([^"']*)["']"#, name))
+            .unwrap()
+            .captures(tag)
+            .map(|c| c[1].to_string())
+    };
+    let feed = link.find_iter(html).find_map(|tag| {
+        let tag = tag.as_str();
+        let rel = attribute(tag, "rel")?.to_lowercase();
+        let kind = attribute(tag, "type")?.to_lowercase();
+        if rel.split_whitespace().any(|r| r == "alternate")
+            && (kind.contains("rss") || kind.contains("atom"))
+        {
+            base.join(&unescape(&attribute(tag, "href")?)).ok()
+        } else {
+            None
+        }
+    });
+    feed
+}
+
+/// The feed's title and its entries in document order, which feeds keep
+/// newest first.
+fn parse_feed(xml: &str) -> Result<(Option<String>, Vec<Entry>), String> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let document = Document::parse_with_options(xml.trim_start_matches('\u{feff}'), options)
+        .map_err(|e| format!("Invalid feed: {}", e))?;
+    let root = document.root_element();
+    // RSS 2.0 nests everything in <channel>; Atom and RSS 1.0 do not.
+    let channel = child(root, "channel").unwrap_or(root);
+    let title = text(channel, &["title"]);
+
+    let entries = root
+        .descendants()
+        .filter(|n| matches!(n.tag_name().name(), "item" | "entry"))
+        .map(|entry| Entry {
+            title: text(entry, &["title"]).unwrap_or_else(|| "Untitled".to_string()),
+            link: link(entry),
+            date: text(entry, &["pubDate", "published", "updated", "date"]).map(|d| date(&d)),
+            summary: text(entry, &["description", "summary", "encoded", "content"])
+                .map(|html| html_to_text(&html))
+                .unwrap_or_default(),
+        })
+        .collect();
+    Ok((title, entries))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.tag_name().name() == name)
+}
+
+/// The text of the first child with one of `names`, tried in order, with
+/// whitespace collapsed. Markup inside the child (Atom's XHTML content) is
+/// flattened to its text.
+fn text(node: Node, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        let text: String = child(node, name)?
+            .descendants()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect();
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+/// RSS gives the link as text; Atom as the `href` of a `<link>`, preferring
+/// the `alternate` one.
+fn link(entry: Node) -> Option<String> {
+    let links: Vec<Node> = entry
+        .children()
+        .filter(|n| n.tag_name().name() == "link")
+        .collect();
+    links
+        .iter()
+        .find(|n| matches!(n.attribute("rel"), None | Some("alternate")) && n.has_attribute("href"))
+        .or_else(|| links.iter().find(|n| n.has_attribute("href")))
+        .and_then(|n| n.attribute("href"))
+        .map(str::to_string)
+        .or_else(|| text(entry, &["link", "guid"]).filter(|link| link.starts_with("http")))
+}
+
+/// `2024-01-22` from an Atom timestamp such as `2024-01-22T18:59:59Z` or an
+/// RSS one such as `Mon, 22 Jan 2024 18:59:59 GMT`. Anything else is kept
+/// as written.
+fn date(raw: &str) -> String {
+    if raw.len() >= 10 && raw.as_bytes()[4] == b'-' && raw.is_char_boundary(10) {
+        return raw[..10].to_string();
+    }
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let parts: Vec<&str> = raw
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .collect();
+    let start = if parts.first().is_some_and(|p| p.parse::<u32>().is_err()) {
+        1
+    } else {
+        0
+    };
+    let day = parts.get(start).and_then(|d| d.parse::<u32>().ok());
+    let month = parts.get(start + 1).and_then(|m| {
+        let m = m.to_lowercase();
+        MONTHS.iter().position(|name| m.starts_with(name))
+    });
+    let year = parts.get(start + 2).and_then(|y| y.parse::<u32>().ok());
+    match (day, month, year) {
+        (Some(day), Some(month), Some(year)) => {
+            format!("{:04}-{:02}-{:02}", year, month + 1, day)
+        }
+        _ => raw.to_string(),
+    }
+}
+
+/// Plain text from an entry's HTML summary.
+fn html_to_text(html: &str) -> String {
+    let tags = Regex::new(r"<[^>]+>").unwrap();
+    let text = tags.replace_all(html, " ");
+    unescape(&text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,122 @@
+//! Ratings given to answers with `/good` and `/bad`, kept in a SQLite
+//! database as a personal record of which models handle which tasks.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::Local;
+use rusqlite::{params, Connection};
+
+use crate::spending::csv_field;
+
+pub struct FeedbackLog {
+    conn: Connection,
+}
+
+/// An exchange and what was thought of its answer.
+#[derive(Debug, Clone)]
+pub struct Rating {
+    /// Local time, as `2025-01-31 14:05`.
+    pub time: String,
+    pub model: String,
+    pub good: bool,
+    pub prompt: String,
+    pub answer: String,
+    pub note: Option<String>,
+}
+
+impl Rating {
+    pub fn new(model: &str, good: bool, prompt: &str, answer: &str, note: Option<&str>) -> Self {
+        Self {
+            time: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            model: model.to_string(),
+            good,
+            prompt: prompt.to_string(),
+            answer: answer.to_string(),
+            note: note.map(str::to_string),
+        }
+    }
+}
+
+impl FeedbackLog {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| sql_error(path, e))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS feedback (
+                 id INTEGER PRIMARY KEY,
+                 time TEXT NOT NULL,
+                 model TEXT NOT NULL,
+                 good INTEGER NOT NULL,
+                 prompt TEXT NOT NULL,
+                 answer TEXT NOT NULL,
+                 note TEXT
+             );",
+        )
+        .map_err(|e| sql_error(path, e))?;
+        Ok(Self { conn })
+    }
+
+    pub fn record(&self, rating: &Rating) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO feedback (time, model, good, prompt, answer, note)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    rating.time,
+                    rating.model,
+                    rating.good,
+                    rating.prompt,
+                    rating.answer,
+                    rating.note
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Every rating, oldest first.
+    pub fn all(&self) -> Result<Vec<Rating>, String> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT time, model, good, prompt, answer, note FROM feedback ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok(Rating {
+                    time: row.get(0)?,
+                    model: row.get(1)?,
+                    good: row.get(2)?,
+                    prompt: row.get(3)?,
+                    answer: row.get(4)?,
+                    note: row.get(5)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+}
+
+/// `ratings` as CSV with a header row.
+pub fn to_csv(ratings: &[Rating]) -> String {
+    let mut csv = String::from("time,model,rating,prompt,answer,note\n");
+    for rating in ratings {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            rating.time,
+            csv_field(&rating.model),
+            if rating.good { "good" } else { "bad" },
+            csv_field(&rating.prompt),
+            csv_field(&rating.answer),
+            csv_field(rating.note.as_deref().unwrap_or_default())
+        ));
+    }
+    csv
+}
+
+fn sql_error(path: &Path, e: rusqlite::Error) -> String {
+    format!("Failed to open {}: {}", path.display(), e)
+}
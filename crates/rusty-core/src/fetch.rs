@@ -0,0 +1,604 @@
+//! Reading web pages for the `open` tool. A page is fetched, its text taken
+//! out of the HTML, and, when it is long, cut into chunks of which only the
+//! ones closest to the question are returned, after an outline of the page
+//! built from its headings.
+//!
+//! Fetching behaves like a polite crawler: it names itself, asks each site's
+//! `robots.txt` first, and leaves some time between requests to one site.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use regex::Regex;
+use reqwest::Url;
+use tokio::time::Instant;
+
+use crate::config::FetchConfig;
+use crate::html;
+use crate::http::Egress;
+use crate::rag::{chunk_text, dot, embed_local};
+
+/// Characters of page text returned whole; longer pages are chunked.
+const MAX_WHOLE_CHARS: usize = 8_000;
+
+/// Chunks of a long page returned.
+const TOP_CHUNKS: usize = 4;
+
+/// Headings listed in the outline.
+const MAX_OUTLINE: usize = 40;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+const MAX_REDIRECTS: usize = 10;
+
+/// The longest `Crawl-delay` honored, so one site cannot stall a turn.
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(10);
+
+/// Fetches web pages, keeping what it learned of each site, for as long as
+/// it lives, in state its clones share.
+#[derive(Clone)]
+pub struct Fetcher {
+    client: reqwest::Client,
+    user_agent: String,
+    robots: bool,
+    delay: Duration,
+    sites: Arc<Mutex<HashMap<String, Site>>>,
+}
+
+/// A fetched page: where it was found, after redirects, its title, its
+/// text, and the links on it.
+pub struct Page {
+    pub url: Url,
+    pub title: Option<String>,
+    pub text: String,
+    pub links: Vec<Url>,
+}
+
+/// The pages a crawl may go to: those on the site it started from, under
+/// the directory of its first page.
+struct Scope {
+    origin: String,
+    path: String,
+}
+
+impl Scope {
+    fn new(start: &Url) -> Self {
+        let path = start.path();
+        // `/book/intro.html` covers `/book/`; `/foo` covers `/foo/...`.
+        let last = path.rsplit('/').next().unwrap_or_default();
+        let path = if last.contains('.') {
+            &path[..path.len() - last.len()]
+        } else {
+            path
+        };
+        Self {
+            origin: start.origin().ascii_serialization(),
+            path: path.to_string(),
+        }
+    }
+
+    fn contains(&self, url: &Url) -> bool {
+        let extension = url
+            .path()
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_lowercase());
+        let page = extension.as_deref().is_none_or(|extension| {
+            matches!(
+                extension,
+                "html" | "htm" | "md" | "txt" | "php" | "asp" | "aspx"
+            )
+        });
+        page && url.origin().ascii_serialization() == self.origin
+            && url.path().starts_with(&self.path)
+    }
+}
+
+fn without_fragment(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url
+}
+
+/// What is known of a site: its `robots.txt` rules for Rusty, once read,
+/// and when the next request to it may go.
+#[derive(Default)]
+struct Site {
+    rules: Option<Arc<Robots>>,
+    next: Option<Instant>,
+}
+
+impl Default for Fetcher {
+    fn default() -> Self {
+        Self::from_config(&FetchConfig::default(), &Egress::default())
+    }
+}
+
+impl Fetcher {
+    /// A fetcher whose redirects, like the first request, stay on the
+    /// hosts `egress` allows.
+    pub fn from_config(config: &FetchConfig, egress: &Egress) -> Self {
+        let egress = egress.clone();
+        let redirects = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            match egress.check("redirect", attempt.url().as_str()) {
+                Ok(()) => attempt.follow(),
+                Err(message) => attempt.error(message),
+            }
+        });
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(TIMEOUT)
+                .redirect(redirects)
+                .build()
+                .unwrap_or_default(),
+            user_agent: config.user_agent.clone(),
+            robots: config.robots,
+            delay: Duration::from_millis(config.delay_ms),
+            sites: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Fetches `url` and returns its text: all of it for a short page, and
+    /// for a long one the outline and the chunks most relevant to
+    /// `question`, or its first chunks without one.
+    pub async fn open(&self, url: &str, question: Option<&str>) -> Result<String, String> {
+        let page = self.page(url).await?;
+        Ok(extract(url, page.title.as_deref(), &page.text, question))
+    }
+
+    /// Fetches a page and reads its text, in Markdown for HTML, and the
+    /// links on it.
+    pub async fn page(&self, url: &str) -> Result<Page, String> {
+        let parsed = Url::parse(url.trim()).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+        let site = parsed.origin().ascii_serialization();
+        let rules = if self.robots {
+            Some(self.rules(&site).await)
+        } else {
+            None
+        };
+        if let Some(rules) = &rules {
+            let path = match parsed.query() {
+                Some(query) => format!("{}?{}", parsed.path(), query),
+                None => parsed.path().to_string(),
+            };
+            if !rules.allows(&path) {
+                return Err(format!(
+                    "{}'s robots.txt asks crawlers not to fetch {}; answer from search results instead",
+                    site, path
+                ));
+            }
+        }
+        let crawl_delay = rules.and_then(|rules| rules.crawl_delay);
+        self.wait_turn(&site, crawl_delay).await;
+
+        let response = self
+            .client
+            .get(parsed)
+            .header("User-Agent", &self.user_agent)
+            .header("Accept", "text/html, text/plain;q=0.9, */*;q=0.5")
+            .send()
+            .await
+            .map_err(|e| format!("Could not fetch {}: {}", url, e))?;
+        if !response.status().is_success() {
+            return Err(format!("{} answered {}", url, response.status()));
+        }
+        // Links are relative to where any redirects led.
+        let url = response.url().clone();
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_lowercase();
+        if content_type.contains("pdf") {
+            return Err(format!("{} is a PDF; read it with read_pdf instead", url));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("Could not read {}: {}", url, e))?;
+
+        if content_type.contains("html") || body.trim_start().starts_with('<') {
+            let (title, text) = page_text(&body, &url);
+            let links = html::links(&body, &url);
+            Ok(Page {
+                url,
+                title,
+                text,
+                links,
+            })
+        } else {
+            Ok(Page {
+                url,
+                title: None,
+                text: body,
+                links: Vec::new(),
+            })
+        }
+    }
+
+    /// Fetches `start` and the pages it links to on the same site and under
+    /// the same path, following links up to `depth` steps away, until `max`
+    /// pages are read. `progress` is told of each page read, or why one
+    /// could not be. Only the first page failing is an error.
+    pub async fn crawl(
+        &self,
+        start: &str,
+        depth: usize,
+        max: usize,
+        mut progress: impl FnMut(&str, Result<&Page, &str>),
+    ) -> Result<Vec<Page>, String> {
+        let first = self.page(start).await?;
+        progress(first.url.as_str(), Ok(&first));
+        let scope = Scope::new(&first.url);
+
+        let mut seen: HashSet<Url> = HashSet::new();
+        seen.insert(without_fragment(&first.url));
+        let mut queue: VecDeque<(Url, usize)> = VecDeque::new();
+        let mut enqueue = |page: &Page, level: usize, queue: &mut VecDeque<(Url, usize)>| {
+            if level >= depth {
+                return;
+            }
+            for link in &page.links {
+                let link = without_fragment(link);
+                if scope.contains(&link) && seen.insert(link.clone()) {
+                    queue.push_back((link, level + 1));
+                }
+            }
+        };
+        enqueue(&first, 0, &mut queue);
+        let mut pages = vec![first];
+
+        while pages.len() < max {
+            let Some((url, level)) = queue.pop_front() else {
+                break;
+            };
+            match self.page(url.as_str()).await {
+                Ok(page) => {
+                    progress(url.as_str(), Ok(&page));
+                    enqueue(&page, level, &mut queue);
+                    pages.push(page);
+                }
+                Err(e) => progress(url.as_str(), Err(&e)),
+            }
+        }
+        Ok(pages)
+    }
+
+    /// The `robots.txt` rules of `site` for Rusty, read on the first visit.
+    /// A missing or unreadable file allows everything; one the site refuses
+    /// to show, with 401 or 403, allows nothing.
+    async fn rules(&self, site: &str) -> Arc<Robots> {
+        if let Some(rules) = self.site(site, |site| site.rules.clone()) {
+            return rules;
+        }
+        self.wait_turn(site, None).await;
+        let response = self
+            .client
+            .get(format!("{}/robots.txt", site))
+            .header("User-Agent", &self.user_agent)
+            .send()
+            .await;
+        let rules = match response {
+            Ok(response) if response.status().is_success() => {
+                let text = response.text().await.unwrap_or_default();
+                Robots::parse(&text, &self.user_agent)
+            }
+            Ok(response) if matches!(response.status().as_u16(), 401 | 403) => {
+                Robots::disallow_all()
+            }
+            _ => Robots::default(),
+        };
+        let rules = Arc::new(rules);
+        self.site(site, |site| site.rules = Some(rules.clone()));
+        rules
+    }
+
+    /// Waits until the last request to `site` is far enough behind, by the
+    /// configured delay or the site's `Crawl-delay` when longer, and books
+    /// the next slot.
+    async fn wait_turn(&self, site: &str, crawl_delay: Option<Duration>) {
+        let delay = crawl_delay
+            .map_or(self.delay, |crawl| crawl.min(MAX_CRAWL_DELAY))
+            .max(self.delay);
+        let now = Instant::now();
+        let start = self.site(site, |site| {
+            let start = site.next.map_or(now, |next| next.max(now));
+            site.next = Some(start + delay);
+            start
+        });
+        tokio::time::sleep_until(start).await;
+    }
+
+    fn site<T>(&self, site: &str, f: impl FnOnce(&mut Site) -> T) -> T {
+        let mut sites = self.sites.lock().unwrap_or_else(|e| e.into_inner());
+        f(sites.entry(site.to_string()).or_default())
+    }
+}
+
+/// The `robots.txt` rules that apply to one crawler.
+#[derive(Debug, Default)]
+struct Robots {
+    /// Path patterns, each allowing or disallowing what it matches.
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    fn disallow_all() -> Self {
+        Self {
+            rules: vec![(false, "/".to_string())],
+            crawl_delay: None,
+        }
+    }
+
+    /// The rules of the group naming the crawler `user_agent` is, or of
+    /// the `*` group when none does.
+    fn parse(text: &str, user_agent: &str) -> Self {
+        let product = user_agent
+            .split(['/', ' '])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let mut named = None;
+        let mut any = None;
+        let mut agents: Vec<String> = Vec::new();
+        let mut group = Robots::default();
+        let mut in_rules = false;
+        let mut finish = |agents: &[String], group: Robots| {
+            if agents
+                .iter()
+                .any(|a| !a.is_empty() && a != "*" && product.contains(a.as_str()))
+            {
+                named.get_or_insert(group);
+            } else if agents.iter().any(|a| a == "*") {
+                any.get_or_insert(group);
+            }
+        };
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match field.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        finish(&agents, std::mem::take(&mut group));
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    // An empty Disallow allows everything.
+                    if !value.is_empty() {
+                        group.rules.push((
+                            field.trim().eq_ignore_ascii_case("allow"),
+                            value.to_string(),
+                        ));
+                    }
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    group.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|d| d.is_finite() && *d >= 0.0)
+                        .map(Duration::from_secs_f64);
+                }
+                _ => {}
+            }
+        }
+        finish(&agents, group);
+        named.or(any).unwrap_or_default()
+    }
+
+    /// Whether `path` may be fetched: the longest matching rule decides,
+    /// an Allow winning a tie.
+    fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Whether a `robots.txt` path pattern matches `path`: a prefix match, with
+/// `*` standing for any characters and a final `$` for the end.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        // The last piece of an anchored pattern must end the path.
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// The page's title and its text in Markdown. Only the `<main>` element is
+/// read when there is one.
+fn page_text(html: &str, url: &Url) -> (Option<String>, String) {
+    let title = Regex::new(r"(?is)<title[^>]*>(.*?)</title>")
+        .unwrap()
+        .captures(html)
+        .map(|c| html::unescape(c[1].trim()))
+        .filter(|title| !title.is_empty());
+    let main = Regex::new(r"(?is)<main\b[^>]*>(.*)</main>").unwrap();
+    let html = match main.captures(html) {
+        Some(c) => c.get(1).map_or(html, |m| m.as_str()),
+        None => html,
+    };
+    (title, html::to_markdown(html, Some(url)))
+}
+
+/// The text to return for a page: all of it when short, otherwise the
+/// outline and the chunks that score best against the question, in the
+/// order they come on the page.
+fn extract(url: &str, title: Option<&str>, text: &str, question: Option<&str>) -> String {
+    let question = question.map(str::trim).filter(|q| !q.is_empty());
+    let mut output = format!("# {}\nURL: {}\n\n", title.unwrap_or(url), url);
+    let total = text.chars().count();
+    if total <= MAX_WHOLE_CHARS {
+        output.push_str(text);
+        return output;
+    }
+
+    let chunks = chunk_text(text);
+    let mut picked: Vec<usize> = match question {
+        Some(question) => {
+            let query = embed_local(question);
+            let mut scored: Vec<(usize, f32)> = chunks
+                .iter()
+                .enumerate()
+                .map(|(i, (_, chunk))| (i, dot(&query, &embed_local(chunk))))
+                .collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+            // Chunks far behind the best are only there to fill the count.
+            let best = scored.first().map_or(0.0, |(_, score)| *score);
+            scored
+                .into_iter()
+                .take(TOP_CHUNKS)
+                .filter(|(_, score)| *score >= best / 2.0)
+                .map(|(i, _)| i)
+                .collect()
+        }
+        None => (0..chunks.len().min(TOP_CHUNKS)).collect(),
+    };
+    picked.sort_unstable();
+
+    let mut code = false;
+    let outline: Vec<String> = text
+        .lines()
+        .filter_map(|line| {
+            // Comments in code blocks are not headings.
+            if line.starts_with("```") {
+                code = !code;
+            }
+            if code {
+                return None;
+            }
+            let level = line.chars().take_while(|c| *c == '#').count();
+            let title = line[level..].strip_prefix(' ')?;
+            (level > 0).then(|| format!("{}- {}", "  ".repeat(level - 1), title))
+        })
+        .take(MAX_OUTLINE)
+        .collect();
+    if !outline.is_empty() {
+        output.push_str("## Outline\n\n");
+        output.push_str(&outline.join("\n"));
+        output.push_str("\n\n");
+    }
+
+    output.push_str(&match question {
+        Some(question) => format!(
+            "## Parts most relevant to \"{}\" ({} of {}, {} characters in all)\n\n",
+            question,
+            picked.len(),
+            chunks.len(),
+            total
+        ),
+        None => format!(
+            "## First parts ({} of {}, {} characters in all)\n\n",
+            picked.len(),
+            chunks.len(),
+            total
+        ),
+    });
+    for i in picked {
+        output.push_str(&format!(
+            "[Part {} of {}]\n{}\n",
+            i + 1,
+            chunks.len(),
+            chunks[i].1.trim()
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "\
+# Everyone else
+User-agent: *
+Disallow: /private/
+Crawl-delay: 10
+
+User-agent: OtherBot
+User-agent: rusty # us
+Disallow: /drafts/
+Allow: /drafts/public
+Crawl-delay: 0.5
+";
+
+    #[test]
+    fn picks_the_group_naming_the_crawler() {
+        let ours = Robots::parse(ROBOTS, "Rusty/0.1 (+https://example.com)");
+        assert!(!ours.allows("/drafts/today"));
+        assert!(ours.allows("/private/notes"));
+        assert_eq!(ours.crawl_delay, Some(Duration::from_millis(500)));
+
+        let other = Robots::parse(ROBOTS, "SomeCrawler/2.0");
+        assert!(!other.allows("/private/notes"));
+        assert!(other.allows("/drafts/today"));
+        assert_eq!(other.crawl_delay, Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn allows_everything_without_a_matching_group() {
+        let robots = Robots::parse("User-agent: OtherBot\nDisallow: /", "Rusty/0.1");
+        assert!(robots.allows("/anything"));
+        assert_eq!(robots.crawl_delay, None);
+
+        let robots = Robots::parse("User-agent: *\nDisallow:", "Rusty/0.1");
+        assert!(robots.allows("/anything"));
+    }
+
+    #[test]
+    fn lets_the_longest_rule_decide_with_allow_winning_a_tie() {
+        let robots = Robots::parse(ROBOTS, "Rusty/0.1");
+        assert!(robots.allows("/drafts/public/page"));
+        assert!(!robots.allows("/drafts/publish"));
+
+        let tie = Robots::parse("User-agent: *\nDisallow: /page\nAllow: /page", "Rusty/0.1");
+        assert!(tie.allows("/page"));
+        assert!(!Robots::disallow_all().allows("/"));
+    }
+
+    #[test]
+    fn matches_wildcards_and_anchors() {
+        assert!(matches("/docs", "/docs/intro"));
+        assert!(!matches("/docs", "/doc"));
+        assert!(matches("/*.pdf", "/files/report.pdf"));
+        assert!(matches("/*.pdf", "/files/report.pdf?download=1"));
+        assert!(matches("/*.pdf$", "/files/report.pdf"));
+        assert!(!matches("/*.pdf$", "/files/report.pdf?download=1"));
+        assert!(matches("/a*b*c", "/axxbyyc/more"));
+        assert!(!matches("/a*b*c", "/axxcyyb"));
+        assert!(matches("/exact$", "/exact"));
+        assert!(!matches("/exact$", "/exactly"));
+    }
+}
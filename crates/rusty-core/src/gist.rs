@@ -0,0 +1,45 @@
+//! Publishing text as a GitHub Gist.
+
+use serde_json::json;
+
+const GISTS_URL: &str = "https://api.github.com/gists";
+
+/// Creates a gist holding one file and returns its web URL. Secret gists are
+/// unlisted but readable by anyone with the link.
+pub async fn create_gist(
+    token: &str,
+    description: &str,
+    filename: &str,
+    content: &str,
+    public: bool,
+) -> Result<String, String> {
+    let body = json!({
+        "description": description,
+        "public": public,
+        "files": { filename: { "content": content } },
+    });
+
+    let response = reqwest::Client::new()
+        .post(GISTS_URL)
+        .bearer_auth(token)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "rusty-cli")
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("GitHub request failed: {}", e))?;
+
+    let status = response.status();
+    let json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid GitHub response: {}", e))?;
+    if !status.is_success() {
+        let message = json["message"].as_str().unwrap_or("unknown error");
+        return Err(format!("GitHub returned {}: {}", status, message));
+    }
+    json["html_url"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "GitHub response had no gist URL".to_string())
+}
@@ -0,0 +1,192 @@
+//! `rusty hook`: helpers meant to be called from Git hooks. They read the
+//! changes from Git, ask the model once, without tools, and leave the result
+//! where Git or the person pushing will find it. The changes are masked and
+//! checked as a prompt in the chat is.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tokio::process::Command;
+
+use crate::config::BudgetConfig;
+use crate::message::Message;
+use crate::moderation::{Direction, Moderator, Verdict};
+use crate::notice;
+use crate::pii::Scrubber;
+use crate::provider::Provider;
+use crate::redact::Redactor;
+use crate::spending::{self, Ledger};
+
+/// Characters of diff sent to the model; the rest is cut off with a note.
+const MAX_DIFF_CHARS: usize = 60_000;
+
+/// Stands for a ref that does not exist in `pre-push` input.
+const NO_COMMIT: &str = "0000000000000000000000000000000000000000";
+
+const COMMIT_PROMPT: &str = "Write a Git commit message for the staged changes below. Use a \
+summary line of at most 72 characters in the imperative mood, then, only if the change needs \
+explaining, a blank line and a short body wrapped at 72 characters saying what changed and why. \
+Reply with the message only, without code fences.";
+
+const REVIEW_PROMPT: &str = "Review the commits below, which are about to be pushed. List \
+bugs, security problems, leftover debugging code and anything else worth fixing before others \
+see it, most important first, naming the file for each. If nothing stands out, say so in one \
+sentence.";
+
+/// What the changes go through before they are sent: secrets and personal
+/// data are masked, moderation may block them, and a model with a price is
+/// not asked once a spending cap is reached, and has what it used recorded.
+pub struct Checks {
+    pub redactor: Redactor,
+    pub pii: Scrubber,
+    pub moderator: Moderator,
+    pub ledger: Option<Ledger>,
+    /// The caps to keep to, or `None` with `--ignore-caps`.
+    pub budget: Option<BudgetConfig>,
+    /// The model's price in dollars per million input and output tokens.
+    pub price: Option<(f64, f64)>,
+}
+
+/// For `prepare-commit-msg`: writes a message for the staged changes above
+/// what Git put in `message_file`. Nothing is done when Git already has a
+/// message, as with `-m`, merges, squashes and amends (`source` is set then),
+/// or when nothing is staged.
+pub async fn prepare_commit_msg(
+    provider: &Provider,
+    model: &str,
+    checks: &mut Checks,
+    message_file: &Path,
+    source: Option<&str>,
+) -> Result<(), String> {
+    if source.is_some_and(|source| !source.is_empty()) {
+        return Ok(());
+    }
+    let diff = git(&["diff", "--cached", "--no-color", "--no-ext-diff"]).await?;
+    if diff.trim().is_empty() {
+        return Ok(());
+    }
+    let message = ask(provider, model, checks, COMMIT_PROMPT, &diff).await?;
+    let message = message
+        .trim()
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    let existing = fs::read_to_string(message_file).unwrap_or_default();
+    fs::write(message_file, format!("{}\n{}", message, existing))
+        .map_err(|e| format!("Failed to write {}: {}", message_file.display(), e))
+}
+
+/// For `pre-push`: reviews the commits being pushed to `remote`, as listed
+/// by Git on the hook's standard input in `updates`, and saves the review
+/// to `rusty-review.md` in the Git directory. Returns the review and where
+/// it was saved, or `None` when nothing new is pushed.
+pub async fn pre_push_review(
+    provider: &Provider,
+    model: &str,
+    checks: &mut Checks,
+    remote: &str,
+    updates: &str,
+) -> Result<Option<(String, PathBuf)>, String> {
+    let mut log = String::new();
+    for line in updates.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [_, local, _, pushed] = fields[..] else {
+            continue;
+        };
+        // A deleted branch brings nothing to review.
+        if local == NO_COMMIT {
+            continue;
+        }
+        let known = pushed != NO_COMMIT
+            && git(&["cat-file", "-e", &format!("{}^{{commit}}", pushed)])
+                .await
+                .is_ok();
+        // Without the remote's commit, everything not on the remote yet.
+        let remotes = format!("--remotes={}", remote);
+        let range = format!("{}..{}", pushed, local);
+        let mut args = vec!["log", "-p", "--reverse", "--no-color", "--no-ext-diff"];
+        if known {
+            args.push(&range);
+        } else {
+            args.extend([local, "--not", &remotes]);
+        }
+        log.push_str(&git(&args).await?);
+    }
+    if log.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let review = ask(provider, model, checks, REVIEW_PROMPT, &log).await?;
+    let path =
+        PathBuf::from(git(&["rev-parse", "--git-dir"]).await?.trim()).join("rusty-review.md");
+    fs::write(&path, format!("{}\n", review.trim()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(Some((review, path)))
+}
+
+async fn ask(
+    provider: &Provider,
+    model: &str,
+    checks: &mut Checks,
+    prompt: &str,
+    diff: &str,
+) -> Result<String, String> {
+    let diff = match diff.char_indices().nth(MAX_DIFF_CHARS) {
+        Some((cut, _)) => format!("{}\n[The rest of the diff was cut off.]", &diff[..cut]),
+        None => diff.to_string(),
+    };
+    let (diff, secrets) = checks.redactor.redact(&diff);
+    if !secrets.is_empty() {
+        notice::warn(&format!("Masked before sending: {}", secrets.join(", ")));
+    }
+    let diff = checks.pii.scrub(&diff);
+    match checks.moderator.check(&diff, Direction::Input).await {
+        Verdict::Block(reason) => return Err(format!("Blocked by moderation: {}", reason)),
+        Verdict::Warn(reason) => notice::warn(&format!("Moderation warning: {}", reason)),
+        Verdict::Allow => {}
+    }
+    if let (Some(ledger), Some(budget), Some(_)) = (&checks.ledger, &checks.budget, checks.price) {
+        match ledger.cap_reached(budget) {
+            Ok(Some(cap)) => return Err(format!("{}; skipping the hook", cap)),
+            Ok(None) => {}
+            Err(e) => notice::warn(&format!("Cannot check the spending caps: {}", e)),
+        }
+    }
+
+    let request = [Message::system(prompt), Message::user(&diff)];
+    let reply = provider
+        .chat_completion(model, &request, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let (Some(ledger), Some(usage)) = (&checks.ledger, &reply.usage) {
+        let cost = checks
+            .price
+            .map_or(0.0, |price| spending::cost(usage, price));
+        if let Err(e) = ledger.record(model, usage, cost) {
+            notice::warn(&format!("Cannot record usage: {}", e));
+        }
+    }
+    reply
+        .content
+        .filter(|content| !content.trim().is_empty())
+        .map(|content| checks.pii.restore(&content))
+        .ok_or_else(|| "The model gave an empty answer".to_string())
+}
+
+/// Runs Git and returns what it printed.
+async fn git(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Cannot run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
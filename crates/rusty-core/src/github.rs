@@ -0,0 +1,388 @@
+//! Read-only queries against the GitHub REST API, for the `github` tool.
+//! Works without a token at GitHub's anonymous rate limit; a
+//! `GITHUB_TOKEN` raises the limit, reaches private repositories, and is
+//! required for code search.
+
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::Value;
+
+const API_URL: &str = "https://api.github.com";
+
+/// Issues or code results listed per search.
+const LISTED: usize = 10;
+
+/// Characters of file content returned at once.
+const MAX_CHARS: usize = 30_000;
+
+/// Characters of an issue body returned.
+const BODY_CHARS: usize = 4_000;
+
+/// What the model asked GitHub for.
+pub enum Query<'a> {
+    /// Metadata of `repo`.
+    Repo { repo: &'a str },
+    /// Issues (and, with `is:pr`, pull requests) in `repo` matching `query`.
+    Issues {
+        repo: &'a str,
+        query: Option<&'a str>,
+        state: Option<&'a str>,
+    },
+    /// One issue or pull request with its description.
+    Issue { repo: &'a str, number: u64 },
+    /// A file's content, or a directory's listing.
+    File {
+        repo: &'a str,
+        path: &'a str,
+        reference: Option<&'a str>,
+    },
+    /// Code matching `query`, optionally within `repo`.
+    SearchCode {
+        query: &'a str,
+        repo: Option<&'a str>,
+    },
+}
+
+pub async fn query(query: Query<'_>) -> Result<String, String> {
+    let github = GitHub {
+        client: Client::new(),
+        token: std::env::var("GITHUB_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty()),
+    };
+    match query {
+        Query::Repo { repo } => github.repo(&repo_name(repo)?).await,
+        Query::Issues { repo, query, state } => {
+            github.issues(&repo_name(repo)?, query, state).await
+        }
+        Query::Issue { repo, number } => github.issue(&repo_name(repo)?, number).await,
+        Query::File {
+            repo,
+            path,
+            reference,
+        } => github.file(&repo_name(repo)?, path, reference).await,
+        Query::SearchCode { query, repo } => {
+            let repo = repo.map(repo_name).transpose()?;
+            github.search_code(query, repo.as_deref()).await
+        }
+    }
+}
+
+/// `owner/name` from itself or a `github.com` URL.
+fn repo_name(repo: &str) -> Result<String, String> {
+    let trimmed = repo.trim().trim_end_matches('/').trim_end_matches(".git");
+    let trimmed = match trimmed.find("github.com/") {
+        Some(start) => &trimmed[start + "github.com/".len()..],
+        None => trimmed,
+    };
+    let mut parts = trimmed.split('/');
+    match (parts.next(), parts.next()) {
+        (Some(owner), Some(name)) if !owner.is_empty() && !name.is_empty() => {
+            Ok(format!("{}/{}", owner, name))
+        }
+        _ => Err(format!(
+            "Expected a repository as owner/name, got '{}'",
+            repo
+        )),
+    }
+}
+
+struct GitHub {
+    client: Client,
+    token: Option<String>,
+}
+
+impl GitHub {
+    fn get(&self, url: &str) -> RequestBuilder {
+        self.get_as(url, "application/vnd.github+json")
+    }
+
+    /// A GET asking for the `accept` media type.
+    fn get_as(&self, url: &str, accept: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .get(url)
+            .header("Accept", accept)
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("User-Agent", "rusty-cli");
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Value, String> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("GitHub request failed: {}", e))?;
+        let status = response.status();
+        let json: Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid GitHub response: {}", e))?;
+        if status.is_success() {
+            return Ok(json);
+        }
+        let message = json["message"].as_str().unwrap_or("unknown error");
+        let hint = match status {
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS if self.token.is_none() => {
+                " (set GITHUB_TOKEN for a higher rate limit)"
+            }
+            StatusCode::UNAUTHORIZED if self.token.is_none() => " (set GITHUB_TOKEN)",
+            _ => "",
+        };
+        Err(format!("GitHub returned {}: {}{}", status, message, hint))
+    }
+
+    async fn repo(&self, repo: &str) -> Result<String, String> {
+        let json = self
+            .send(self.get(&format!("{}/repos/{}", API_URL, repo)))
+            .await?;
+        let mut output = format!("# {}\n", json["full_name"].as_str().unwrap_or(repo));
+        if let Some(description) = json["description"].as_str() {
+            output.push_str(&format!("{}\n", description));
+        }
+        output.push('\n');
+        let count = |key: &str| json[key].as_u64().unwrap_or(0);
+        output.push_str(&format!("- Stars: {}\n", count("stargazers_count")));
+        output.push_str(&format!("- Forks: {}\n", count("forks_count")));
+        output.push_str(&format!(
+            "- Open issues and pull requests: {}\n",
+            count("open_issues_count")
+        ));
+        for (label, key) in [
+            ("Language", "language"),
+            ("Default branch", "default_branch"),
+            ("Homepage", "homepage"),
+            ("Created", "created_at"),
+            ("Last push", "pushed_at"),
+        ] {
+            if let Some(value) = json[key].as_str().filter(|v| !v.is_empty()) {
+                output.push_str(&format!("- {}: {}\n", label, value));
+            }
+        }
+        if let Some(license) = json["license"]["spdx_id"].as_str() {
+            output.push_str(&format!("- License: {}\n", license));
+        }
+        if let Some(topics) = json["topics"].as_array().filter(|t| !t.is_empty()) {
+            let topics: Vec<&str> = topics.iter().filter_map(Value::as_str).collect();
+            output.push_str(&format!("- Topics: {}\n", topics.join(", ")));
+        }
+        if json["archived"].as_bool() == Some(true) {
+            output.push_str("- Archived\n");
+        }
+        output.push_str(&format!(
+            "- URL: {}\n",
+            json["html_url"].as_str().unwrap_or_default()
+        ));
+        Ok(output)
+    }
+
+    async fn issues(
+        &self,
+        repo: &str,
+        query: Option<&str>,
+        state: Option<&str>,
+    ) -> Result<String, String> {
+        let query = query.unwrap_or("");
+        let mut q = format!("repo:{}", repo);
+        if !query.contains("is:pr") && !query.contains("is:issue") {
+            q.push_str(" is:issue");
+        }
+        match state.unwrap_or("open") {
+            "all" => {}
+            state => q.push_str(&format!(" state:{}", state)),
+        }
+        if !query.is_empty() {
+            q.push(' ');
+            q.push_str(query);
+        }
+
+        let per_page = LISTED.to_string();
+        let json = self
+            .send(
+                self.get(&format!("{}/search/issues", API_URL))
+                    .query(&[("q", q.as_str()), ("per_page", &per_page)]),
+            )
+            .await?;
+        let total = json["total_count"].as_u64().unwrap_or(0);
+        let mut output = format!(
+            "### GitHub Issues\n\nQuery: `{}`\nTotal matches: {}\n\n",
+            q, total
+        );
+        for (i, item) in json["items"].as_array().into_iter().flatten().enumerate() {
+            let labels: Vec<&str> = item["labels"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|label| label["name"].as_str())
+                .collect();
+            output.push_str(&format!(
+                "{}. **#{} {}**\n",
+                i + 1,
+                item["number"].as_u64().unwrap_or(0),
+                item["title"].as_str().unwrap_or("No Title")
+            ));
+            output.push_str(&format!(
+                "   - State: {}, {} comments, opened {}\n",
+                item["state"].as_str().unwrap_or(""),
+                item["comments"].as_u64().unwrap_or(0),
+                date(&item["created_at"])
+            ));
+            if !labels.is_empty() {
+                output.push_str(&format!("   - Labels: {}\n", labels.join(", ")));
+            }
+            output.push_str(&format!(
+                "   - URL: {}\n\n",
+                item["html_url"].as_str().unwrap_or("")
+            ));
+        }
+        Ok(output)
+    }
+
+    async fn issue(&self, repo: &str, number: u64) -> Result<String, String> {
+        let json = self
+            .send(self.get(&format!("{}/repos/{}/issues/{}", API_URL, repo, number)))
+            .await?;
+        let kind = if json["pull_request"].is_object() {
+            "Pull request"
+        } else {
+            "Issue"
+        };
+        let mut output = format!(
+            "# {} #{}: {}\n\n",
+            kind,
+            number,
+            json["title"].as_str().unwrap_or("No Title")
+        );
+        output.push_str(&format!(
+            "- State: {}\n",
+            json["state"].as_str().unwrap_or("")
+        ));
+        output.push_str(&format!(
+            "- Author: {}\n",
+            json["user"]["login"].as_str().unwrap_or("")
+        ));
+        output.push_str(&format!("- Opened: {}\n", date(&json["created_at"])));
+        if json["closed_at"].is_string() {
+            output.push_str(&format!("- Closed: {}\n", date(&json["closed_at"])));
+        }
+        output.push_str(&format!(
+            "- Comments: {}\n",
+            json["comments"].as_u64().unwrap_or(0)
+        ));
+        output.push_str(&format!(
+            "- URL: {}\n\n",
+            json["html_url"].as_str().unwrap_or("")
+        ));
+        let body = json["body"].as_str().unwrap_or("(no description)");
+        output.push_str(&truncate(body, BODY_CHARS));
+        output.push('\n');
+        Ok(output)
+    }
+
+    async fn file(
+        &self,
+        repo: &str,
+        path: &str,
+        reference: Option<&str>,
+    ) -> Result<String, String> {
+        let path = path.trim_matches('/');
+        let mut request = self.get(&format!("{}/repos/{}/contents/{}", API_URL, repo, path));
+        if let Some(reference) = reference {
+            request = request.query(&[("ref", reference)]);
+        }
+        let json = self.send(request).await?;
+
+        if let Some(entries) = json.as_array() {
+            let mut output = format!("Contents of {}/{}:\n\n", repo, path);
+            for entry in entries {
+                let name = entry["name"].as_str().unwrap_or("");
+                match entry["type"].as_str() {
+                    Some("dir") => output.push_str(&format!("- {}/\n", name)),
+                    _ => output.push_str(&format!("- {}\n", name)),
+                }
+            }
+            return Ok(output);
+        }
+
+        let url = json["download_url"]
+            .as_str()
+            .ok_or_else(|| format!("{} is not a file", path))?;
+        let response = self
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| format!("GitHub request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("GitHub returned {}", response.status()));
+        }
+        let content = response
+            .text()
+            .await
+            .map_err(|e| format!("GitHub request failed: {}", e))?;
+        Ok(format!(
+            "{}/{}\n\n```\n{}\n```\n",
+            repo,
+            path,
+            truncate(&content, MAX_CHARS)
+        ))
+    }
+
+    async fn search_code(&self, query: &str, repo: Option<&str>) -> Result<String, String> {
+        if self.token.is_none() {
+            return Err("GitHub code search needs a GITHUB_TOKEN".to_string());
+        }
+        let q = match repo {
+            Some(repo) => format!("{} repo:{}", query, repo),
+            None => query.to_string(),
+        };
+        let per_page = LISTED.to_string();
+        let json = self
+            .send(
+                self.get_as(
+                    &format!("{}/search/code", API_URL),
+                    "application/vnd.github.text-match+json",
+                )
+                .query(&[("q", q.as_str()), ("per_page", &per_page)]),
+            )
+            .await?;
+
+        let mut output = format!(
+            "### GitHub Code Search\n\nQuery: `{}`\nTotal matches: {}\n\n",
+            q,
+            json["total_count"].as_u64().unwrap_or(0)
+        );
+        for (i, item) in json["items"].as_array().into_iter().flatten().enumerate() {
+            output.push_str(&format!(
+                "{}. **{}** in {}\n",
+                i + 1,
+                item["path"].as_str().unwrap_or(""),
+                item["repository"]["full_name"].as_str().unwrap_or("")
+            ));
+            if let Some(fragment) = item["text_matches"][0]["fragment"].as_str() {
+                output.push_str(&format!("   - Snippet: {}\n", fragment.trim()));
+            }
+            output.push_str(&format!(
+                "   - URL: {}\n\n",
+                item["html_url"].as_str().unwrap_or("")
+            ));
+        }
+        Ok(output)
+    }
+}
+
+/// `2024-01-22` from an ISO 8601 timestamp.
+fn date(value: &Value) -> &str {
+    let timestamp = value.as_str().unwrap_or("");
+    timestamp.split('T').next().unwrap_or(timestamp)
+}
+
+fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(limit).collect();
+    truncated.push_str("\n[Truncated.]");
+    truncated
+}
@@ -0,0 +1,147 @@
+//! Prompt history persisted across sessions, one entry per line like a shell
+//! history file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::config::HistoryConfig;
+use crate::encryption::{self, Cipher};
+
+pub struct History {
+    entries: Vec<String>,
+    path: PathBuf,
+    max_entries: usize,
+    dedupe: bool,
+    ignore: Vec<Regex>,
+    cipher: Option<Cipher>,
+}
+
+impl History {
+    /// Reads the history file at `path`, which need not exist yet. Invalid
+    /// ignore patterns are skipped with a warning. With a cipher, the file is
+    /// encrypted from the next write on.
+    pub fn load(
+        path: &Path,
+        config: &HistoryConfig,
+        cipher: Option<Cipher>,
+    ) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let ignore = config
+            .ignore
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warnings.push(format!(
+                        "Invalid history ignore pattern {:?}: {}",
+                        pattern, e
+                    ));
+                    None
+                }
+            })
+            .collect();
+
+        let entries = if path.exists() {
+            match encryption::read_text(path, cipher.as_ref()) {
+                Ok(text) => text.lines().map(unescape).collect(),
+                Err(e) => {
+                    warnings.push(e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        let mut history = Self {
+            entries,
+            path: path.to_path_buf(),
+            max_entries: config.max_entries,
+            dedupe: config.dedupe,
+            ignore,
+            cipher,
+        };
+        history.truncate();
+        (history, warnings)
+    }
+
+    /// Entries, oldest first.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Whether `line` matches one of the ignore patterns and so is never recorded.
+    pub fn is_ignored(&self, line: &str) -> bool {
+        self.ignore.iter().any(|regex| regex.is_match(line))
+    }
+
+    /// Records `line` and rewrites the history file. Empty and ignored lines,
+    /// and repeats of the latest entry, are dropped.
+    pub fn add(&mut self, line: &str) -> Result<(), String> {
+        if line.trim().is_empty()
+            || self.is_ignored(line)
+            || self.entries.last().is_some_and(|last| last == line)
+        {
+            return Ok(());
+        }
+        if self.dedupe {
+            self.entries.retain(|entry| entry != line);
+        }
+        self.entries.push(line.to_string());
+        self.truncate();
+        self.save()
+    }
+
+    fn truncate(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.max_entries);
+        self.entries.drain(..excess);
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if self.max_entries == 0 {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(&escape(entry));
+            text.push('\n');
+        }
+        encryption::write_text(&self.path, &text, self.cipher.as_ref())?;
+
+        // Prompts can contain secrets; keep the file private like shell history.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600));
+        }
+        Ok(())
+    }
+}
+
+/// Keeps multi-line entries on one line of the file.
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
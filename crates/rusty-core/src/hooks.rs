@@ -0,0 +1,180 @@
+//! Lifecycle hooks that run external shell commands.
+//!
+//! Each hook command receives a JSON payload describing the event on stdin.
+//! `pre_submit` and `pre_tool_exec` commands can veto the action by exiting
+//! with a non-zero status; whatever they printed becomes the reason.
+//! `post_response` commands cannot veto anything, but their output is shown
+//! to the user, which makes them a good place to lint code the model proposed.
+//! The `statusline` command runs before each prompt, and the first line it
+//! prints is shown under the prompt.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+
+use crate::config::HooksConfig;
+use crate::message::ToolCall;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Seconds the status line command may take, as it holds up the prompt.
+const STATUSLINE_TIMEOUT_SECS: u64 = 2;
+
+/// What a hook command printed, and whether it asked to stop.
+#[derive(Debug, Default)]
+pub struct HookReport {
+    /// Set when a command exited non-zero, with its output as the reason.
+    pub blocked: Option<String>,
+    /// Non-empty stdout of every command that ran.
+    pub output: Vec<String>,
+}
+
+pub struct CommandHooks {
+    config: HooksConfig,
+}
+
+impl CommandHooks {
+    pub fn new(config: HooksConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn pre_submit(&self, message: &str, model: &str) -> HookReport {
+        let payload = serde_json::json!({
+            "event": "pre_submit",
+            "message": message,
+            "model": model,
+        });
+        self.run_all(&self.config.pre_submit, &payload, true).await
+    }
+
+    pub async fn post_response(&self, response: &str, model: &str) -> HookReport {
+        let payload = serde_json::json!({
+            "event": "post_response",
+            "response": response,
+            "model": model,
+        });
+        self.run_all(&self.config.post_response, &payload, false)
+            .await
+    }
+
+    pub async fn pre_tool_exec(&self, call: &ToolCall) -> HookReport {
+        let arguments = serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+            .unwrap_or_else(|_| serde_json::Value::String(call.function.arguments.clone()));
+        let payload = serde_json::json!({
+            "event": "pre_tool_exec",
+            "tool": call.function.name,
+            "arguments": arguments,
+        });
+        self.run_all(&self.config.pre_tool_exec, &payload, true)
+            .await
+    }
+
+    /// The first line the `statusline` command prints; `None` when there
+    /// is none, or the command fails or prints nothing.
+    pub async fn statusline(&self, model: &str) -> Option<String> {
+        let command = self.config.statusline.as_deref()?;
+        let payload = serde_json::json!({
+            "event": "statusline",
+            "model": model,
+        });
+        let timeout = Duration::from_secs(STATUSLINE_TIMEOUT_SECS);
+        let stdout = run_shell(command, &payload.to_string(), timeout)
+            .await
+            .ok()?;
+        let line = stdout.lines().next()?.trim_end();
+        (!line.trim().is_empty()).then(|| line.to_string())
+    }
+
+    /// Runs `commands` in order. When `can_block` is set, the first failing
+    /// command stops the rest and marks the report as blocked.
+    async fn run_all(
+        &self,
+        commands: &[String],
+        payload: &serde_json::Value,
+        can_block: bool,
+    ) -> HookReport {
+        let mut report = HookReport::default();
+        let timeout = Duration::from_secs(self.config.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+
+        for command in commands {
+            match run_shell(command, &payload.to_string(), timeout).await {
+                Ok(stdout) => {
+                    if !stdout.trim().is_empty() {
+                        report.output.push(stdout.trim_end().to_string());
+                    }
+                }
+                Err(reason) => {
+                    let reason = format!("`{}` {}", command, reason);
+                    if can_block {
+                        report.blocked = Some(reason);
+                        break;
+                    }
+                    report.output.push(reason);
+                }
+            }
+        }
+
+        report
+    }
+}
+
+/// Runs `command` through the platform shell with `input` on stdin. A
+/// non-zero exit becomes an error carrying the command's combined output.
+async fn run_shell(command: &str, input: &str, timeout: Duration) -> Result<String, String> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    let run = async {
+        if let Some(mut stdin) = child.stdin.take() {
+            // A hook that ignores its input may exit before reading it.
+            let _ = stdin.write_all(input.as_bytes()).await;
+        }
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        let (out, err) = (child.stdout.take(), child.stderr.take());
+        tokio::join!(
+            async {
+                if let Some(mut out) = out {
+                    let _ = out.read_to_string(&mut stdout).await;
+                }
+            },
+            async {
+                if let Some(mut err) = err {
+                    let _ = err.read_to_string(&mut stderr).await;
+                }
+            }
+        );
+        let status = child.wait().await.map_err(|e| e.to_string())?;
+        if status.success() {
+            Ok(stdout)
+        } else {
+            let detail = format!("{}{}", stdout, stderr);
+            let detail = detail.trim();
+            if detail.is_empty() {
+                Err(format!("exited with {}", status))
+            } else {
+                Err(format!("exited with {}: {}", status, detail))
+            }
+        }
+    };
+
+    tokio::time::timeout(timeout, run)
+        .await
+        .map_err(|_| format!("timed out after {}s", timeout.as_secs()))?
+}
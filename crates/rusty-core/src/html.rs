@@ -0,0 +1,499 @@
+//! HTML to Markdown for fetched pages: headings, lists, tables, links,
+//! emphasis and code blocks are kept, so the model gets the page's
+//! structure and the links it can cite, and transcripts render it.
+//! Scripts, styles, navigation and footers are left out.
+
+use regex::{Captures, Regex};
+use reqwest::Url;
+
+/// Converts `html` to Markdown, making links relative to `base` absolute.
+pub fn to_markdown(html: &str, base: Option<&Url>) -> String {
+    let hidden = Regex::new(
+        r"(?is)<script\b.*?</script>|<style\b.*?</style>|<noscript\b.*?</noscript>|<svg\b.*?</svg>|<template\b.*?</template>|<title\b.*?</title>|<nav\b.*?</nav>|<footer\b.*?</footer>|<!--.*?-->|<![^>]*>|<\?[^>]*>",
+    )
+    .unwrap();
+    let html = hidden.replace_all(html, "");
+    let tag = Regex::new(r"<(/?)([a-zA-Z][a-zA-Z0-9]*)([^>]*)>").unwrap();
+
+    let mut writer = Writer::new(base);
+    let mut last = 0;
+    for captures in tag.captures_iter(&html) {
+        let whole = captures.get(0).unwrap();
+        writer.text(&html[last..whole.start()]);
+        last = whole.end();
+        let name = captures[2].to_lowercase();
+        if &captures[1] == "/" {
+            writer.end(&name);
+        } else {
+            writer.start(&name, &captures[3]);
+        }
+    }
+    writer.text(&html[last..]);
+    writer.finish()
+}
+
+/// The addresses of the links in `html`, made absolute against `base`,
+/// without anchors on the page.
+pub fn links(html: &str, base: &Url) -> Vec<Url> {
+    let anchor = Regex::new(r"(?i)<a\s([^>]*)>").unwrap();
+    anchor
+        .captures_iter(html)
+        .filter_map(|c| attribute(&c[1], "href"))
+        .filter(|href| !href.starts_with('#'))
+        .filter_map(|href| base.join(&unescape(href.trim())).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .collect()
+}
+
+/// A `<table>` being read: its rows of cells, and whether a cell is open.
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    cell_open: bool,
+}
+
+struct Writer<'a> {
+    base: Option<&'a Url>,
+    /// The output, with a buffer on top for each blockquote, table cell or
+    /// code block being read, which is only written out once it ends.
+    buffers: Vec<String>,
+    /// A space was seen and is written before the next text.
+    space: bool,
+    /// Open lists, whether each is numbered, and the count of its items.
+    lists: Vec<(bool, usize)>,
+    /// Open links: where the `[` was written, at which buffer depth, and
+    /// the address.
+    links: Vec<(usize, usize, Option<String>)>,
+    tables: Vec<Table>,
+    /// Inside `<pre>`, whitespace is kept and tags are ignored, and the
+    /// language is taken from a `language-` class.
+    pre: bool,
+    language: String,
+}
+
+impl<'a> Writer<'a> {
+    fn new(base: Option<&'a Url>) -> Self {
+        Self {
+            base,
+            buffers: vec![String::new()],
+            space: false,
+            lists: Vec::new(),
+            links: Vec::new(),
+            tables: Vec::new(),
+            pre: false,
+            language: String::new(),
+        }
+    }
+
+    fn buffer(&mut self) -> &mut String {
+        self.buffers
+            .last_mut()
+            .expect("the output buffer is never popped")
+    }
+
+    fn push(&mut self, text: &str) {
+        self.buffer().push_str(text);
+    }
+
+    fn text(&mut self, raw: &str) {
+        if raw.is_empty() {
+            return;
+        }
+        let text = unescape(raw);
+        if self.pre {
+            self.push(&text);
+            return;
+        }
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if text.starts_with(char::is_whitespace) {
+            self.space = true;
+        }
+        if words.is_empty() {
+            return;
+        }
+        let after_break = self
+            .buffer()
+            .chars()
+            .last()
+            .is_none_or(|c| c.is_whitespace() || c == '[' || c == '(');
+        if self.space && !after_break {
+            self.push(" ");
+        }
+        self.push(&words.join(" "));
+        self.space = text.ends_with(char::is_whitespace);
+    }
+
+    /// Ends the current line, unless nothing has been written to it.
+    fn newline(&mut self) {
+        self.space = false;
+        let buffer = self.buffer();
+        while buffer.ends_with(' ') {
+            buffer.pop();
+        }
+        if !buffer.is_empty() && !buffer.ends_with('\n') {
+            buffer.push('\n');
+        }
+    }
+
+    /// Leaves an empty line before what comes next.
+    fn blank(&mut self) {
+        self.newline();
+        let buffer = self.buffer();
+        if !buffer.is_empty() && !buffer.ends_with("\n\n") {
+            buffer.push('\n');
+        }
+    }
+
+    fn start(&mut self, name: &str, attributes: &str) {
+        if self.pre {
+            if let Some(language) = language(attributes).filter(|_| name == "code") {
+                self.language = language;
+            }
+            return;
+        }
+        match name {
+            "p" | "div" | "section" | "article" | "header" | "main" | "aside" | "figure"
+            | "form" | "dl" | "dt" | "dd" | "address" | "details" | "summary" => {
+                if name == "p" {
+                    self.blank();
+                } else {
+                    self.newline();
+                }
+            }
+            "br" => self.newline(),
+            "hr" => {
+                self.blank();
+                self.push("---");
+                self.blank();
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.blank();
+                let level = name[1..].parse().unwrap_or(1);
+                self.push(&format!("{} ", "#".repeat(level)));
+            }
+            "ul" | "ol" => {
+                if self.lists.is_empty() {
+                    self.blank();
+                } else {
+                    self.newline();
+                }
+                let start = attribute(attributes, "start")
+                    .and_then(|start| start.parse::<usize>().ok())
+                    .unwrap_or(1);
+                self.lists.push((name == "ol", start.saturating_sub(1)));
+            }
+            "li" => {
+                self.newline();
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some((true, count)) => {
+                        *count += 1;
+                        format!("{}. ", count)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.push(&format!("{}{}", "  ".repeat(depth), marker));
+            }
+            "blockquote" => {
+                self.blank();
+                self.buffers.push(String::new());
+            }
+            "pre" => {
+                self.blank();
+                self.pre = true;
+                self.language = language(attributes).unwrap_or_default();
+                self.buffers.push(String::new());
+            }
+            "code" | "kbd" | "samp" => self.inline("`", true),
+            "strong" | "b" => self.inline("**", true),
+            "em" | "i" => self.inline("*", true),
+            "a" => {
+                let href = attribute(attributes, "href").and_then(|href| self.resolve(&href));
+                self.inline("[", true);
+                let at = self.buffer().len() - 1;
+                self.links.push((at, self.buffers.len(), href));
+            }
+            "img" => {
+                let alt = attribute(attributes, "alt").unwrap_or_default();
+                let src = attribute(attributes, "src").and_then(|src| self.resolve(&src));
+                if let (false, Some(src)) = (alt.trim().is_empty(), src) {
+                    self.inline(&format!("![{}]({})", alt.trim(), src), true);
+                }
+            }
+            "table" => {
+                self.blank();
+                self.tables.push(Table::default());
+            }
+            "tr" => {
+                self.close_cell();
+                if let Some(table) = self.tables.last_mut() {
+                    table.rows.push(Vec::new());
+                }
+            }
+            "td" | "th" => {
+                self.close_cell();
+                if let Some(table) = self.tables.last_mut() {
+                    if table.rows.is_empty() {
+                        table.rows.push(Vec::new());
+                    }
+                    table.cell_open = true;
+                    self.buffers.push(String::new());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if self.pre {
+            if name == "pre" {
+                self.pre = false;
+                let code = self.buffers.pop().unwrap_or_default();
+                let fence = format!(
+                    "```{}\n{}\n```",
+                    self.language,
+                    code.trim_matches('\n').trim_end()
+                );
+                self.push(&fence);
+                self.blank();
+            }
+            return;
+        }
+        match name {
+            "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.blank(),
+            "div" | "section" | "article" | "header" | "main" | "aside" | "figure" | "form"
+            | "dl" | "dt" | "dd" | "address" | "details" | "summary" | "li" => self.newline(),
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank();
+                } else {
+                    self.newline();
+                }
+            }
+            "blockquote" if self.buffers.len() > 1 => {
+                let quote = self.buffers.pop().unwrap_or_default();
+                let quote: Vec<String> = quote
+                    .trim()
+                    .lines()
+                    .map(|line| format!("> {}", line).trim_end().to_string())
+                    .collect();
+                self.push(&quote.join("\n"));
+                self.blank();
+            }
+            "code" | "kbd" | "samp" => self.inline("`", false),
+            "strong" | "b" => self.inline("**", false),
+            "em" | "i" => self.inline("*", false),
+            "a" => self.close_link(),
+            "td" | "th" => self.close_cell(),
+            "table" => {
+                self.close_cell();
+                if let Some(table) = self.tables.pop() {
+                    let table = render_table(table);
+                    self.push(&table);
+                    self.blank();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes an inline marker, such as `**`. A space seen before an
+    /// opening one goes before it, and one seen before a closing one after
+    /// it, so the marker stays against the text.
+    fn inline(&mut self, marker: &str, opening: bool) {
+        if opening && self.space {
+            let after_break = self
+                .buffer()
+                .chars()
+                .last()
+                .is_none_or(|c| c.is_whitespace() || c == '[' || c == '(');
+            if !after_break {
+                self.push(" ");
+            }
+            self.space = false;
+        }
+        self.push(marker);
+    }
+
+    fn close_link(&mut self) {
+        let Some((at, depth, href)) = self.links.pop() else {
+            return;
+        };
+        if depth != self.buffers.len() {
+            return;
+        }
+        let buffer = self.buffer();
+        let text = buffer[at + 1..].trim().to_string();
+        match href {
+            Some(href) if !text.is_empty() => {
+                buffer.truncate(at);
+                buffer.push_str(&format!("[{}]({})", text, href));
+            }
+            _ => {
+                buffer.remove(at);
+            }
+        }
+    }
+
+    fn close_cell(&mut self) {
+        let Some(table) = self.tables.last_mut() else {
+            return;
+        };
+        if !table.cell_open {
+            return;
+        }
+        table.cell_open = false;
+        let cell = self.buffers.pop().unwrap_or_default();
+        let cell = cell.split_whitespace().collect::<Vec<_>>().join(" ");
+        if let Some(row) = self.tables.last_mut().and_then(|t| t.rows.last_mut()) {
+            row.push(cell.replace('|', "\\|"));
+        }
+    }
+
+    /// `href` as an absolute address, or `None` for anchors on the page
+    /// and scripts.
+    fn resolve(&self, href: &str) -> Option<String> {
+        let href = unescape(href.trim());
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+            return None;
+        }
+        match self.base {
+            Some(base) => base.join(&href).ok().map(|url| url.to_string()),
+            None => Some(href),
+        }
+    }
+
+    fn finish(mut self) -> String {
+        while self.buffers.len() > 1 {
+            let inner = self.buffers.pop().unwrap_or_default();
+            self.push(&inner);
+        }
+        let output = self.buffers.pop().unwrap_or_default();
+        let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+        let blank_lines = Regex::new(r"\n{3,}").unwrap();
+        blank_lines
+            .replace_all(lines.join("\n").trim(), "\n\n")
+            .into_owned()
+    }
+}
+
+/// A table in Markdown, its first row as the header. Rows are padded to
+/// the same number of cells, and empty rows left out.
+fn render_table(table: Table) -> String {
+    let rows: Vec<Vec<String>> = table
+        .rows
+        .into_iter()
+        .filter(|row| row.iter().any(|cell| !cell.is_empty()))
+        .collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    // A layout table of one cell is just its text.
+    if rows.len() == 1 && columns == 1 {
+        return rows[0][0].clone();
+    }
+    let line = |row: &[String]| {
+        let cells: Vec<&str> = (0..columns)
+            .map(|i| row.get(i).map_or("", String::as_str))
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        lines.push(line(row));
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(columns)));
+        }
+    }
+    lines.join("\n")
+}
+
+/// The value of an attribute in a tag's attribute text.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let pattern = format!(
+        r#"(?i)(?:^|\s){}\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#,
+        regex::escape(name)
+    );
+    let captures = Regex::new(&pattern).ok()?.captures(attributes)?;
+    captures
+        .get(1)
+        .or(captures.get(2))
+        .or(captures.get(3))
+        .map(|value| value.as_str().to_string())
+}
+
+/// The language of a code block, from a `language-rust` or `lang-rust`
+/// class.
+fn language(attributes: &str) -> Option<String> {
+    attribute(attributes, "class")?
+        .split_whitespace()
+        .find_map(|class| {
+            class
+                .strip_prefix("language-")
+                .or_else(|| class.strip_prefix("lang-"))
+                .map(str::to_string)
+        })
+}
+
+pub fn unescape(text: &str) -> String {
+    let numeric = Regex::new(r"&#(x[0-9a-fA-F]+|\d+);").unwrap();
+    let text = numeric.replace_all(text, |c: &Captures| {
+        let code = match c[1].strip_prefix('x') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => c[1].parse().ok(),
+        };
+        code.and_then(char::from_u32)
+            .map(String::from)
+            .unwrap_or_default()
+    });
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_headings_and_paragraphs() {
+        let html = "<h1>Title</h1><p>First   paragraph.</p><h2>Part <em>two</em></h2><p>More.</p>";
+        assert_eq!(
+            to_markdown(html, None),
+            "# Title\n\nFirst paragraph.\n\n## Part *two*\n\nMore."
+        );
+    }
+
+    #[test]
+    fn makes_links_absolute() {
+        let base = Url::parse("https://example.com/docs/page").unwrap();
+        let html = r#"<p>See <a href="../guide">the guide</a> and <a href='https://other.org/'>this</a>.</p>"#;
+        assert_eq!(
+            to_markdown(html, Some(&base)),
+            "See [the guide](https://example.com/guide) and [this](https://other.org/)."
+        );
+    }
+
+    #[test]
+    fn writes_bulleted_and_numbered_lists() {
+        let html = "<ul><li>one</li><li>two<ol><li>nested</li></ol></li></ul>";
+        assert_eq!(to_markdown(html, None), "- one\n- two\n  1. nested");
+    }
+
+    #[test]
+    fn leaves_out_scripts_styles_and_navigation() {
+        let html = "<style>p { color: red }</style><nav><a href=\"/\">Home</a></nav>\
+                    <p>Kept &amp; shown</p><script>alert('hidden')</script><footer>Footer</footer>";
+        assert_eq!(to_markdown(html, None), "Kept & shown");
+    }
+
+    #[test]
+    fn unescapes_named_and_numeric_entities() {
+        assert_eq!(
+            unescape("&lt;a&gt; &#39;b&#x27; &amp;amp;"),
+            "<a> 'b' &amp;"
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Plain HTTP calls for the `http_request` tool. Hosts on the user's
+//! allowlist are called straight away; the application asks before any
+//! other host is contacted. Apart from that, an egress allowlist may keep
+//! every tool that takes a URL to some hosts, with no asking.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr};
+
+use chrono::Local;
+use reqwest::{Method, Url};
+
+use crate::{diagnostics, paths};
+
+/// Characters of a response body returned to the model.
+const MAX_CHARS: usize = 20_000;
+
+/// Response headers worth showing the model.
+const SHOWN_HEADERS: [&str; 4] = ["content-type", "location", "retry-after", "link"];
+
+/// Domains the model may call without asking. An entry covers the domain
+/// and all of its subdomains, so `example.com` also allows
+/// `api.example.com`.
+#[derive(Debug, Default, Clone)]
+pub struct Allowlist {
+    domains: Vec<String>,
+}
+
+impl Allowlist {
+    pub fn new(domains: &[String]) -> Self {
+        Self {
+            domains: domains
+                .iter()
+                .map(|domain| normalize(domain))
+                .filter(|domain| !domain.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn allows(&self, host: &str) -> bool {
+        let host = normalize(host);
+        self.domains.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        })
+    }
+
+    /// Allows `host` for the rest of the session.
+    pub fn add(&mut self, host: &str) {
+        if !self.allows(host) {
+            self.domains.push(normalize(host));
+        }
+    }
+}
+
+/// The hosts tools that take a URL may contact at all, as glob patterns
+/// such as `*.github.com`, where `*` stands for any characters and `?` for
+/// one. Empty, it lets them contact any host.
+#[derive(Debug, Default, Clone)]
+pub struct Egress {
+    patterns: Vec<String>,
+    /// Local and private addresses are refused unless a pattern names
+    /// them, as when nobody watches what the tools reach.
+    unattended: bool,
+}
+
+impl Egress {
+    pub fn new(patterns: &[String]) -> Self {
+        Self {
+            patterns: patterns
+                .iter()
+                .map(|pattern| pattern.trim().trim_end_matches('.').to_lowercase())
+                .filter(|pattern| !pattern.is_empty())
+                .collect(),
+            unattended: false,
+        }
+    }
+
+    /// The same allowlist for the bots, where a model answering strangers
+    /// must not reach this machine or its network: local and private
+    /// addresses are refused unless a pattern other than `*` matches them.
+    pub fn unattended(mut self) -> Self {
+        self.unattended = true;
+        self
+    }
+
+    /// Whether `tool` may contact `url`. A refusal is written to the egress
+    /// log, and the error is what the model is told. URLs that are not
+    /// valid pass, for the tool to refuse with the reason.
+    pub fn check(&self, tool: &str, url: &str) -> Result<(), String> {
+        if self.patterns.is_empty() && !self.unattended {
+            return Ok(());
+        }
+        let Ok(host) = host(url) else {
+            return Ok(());
+        };
+        let lower = host.to_lowercase();
+        let reason = if self.unattended
+            && is_local(&lower)
+            && !self
+                .patterns
+                .iter()
+                .any(|pattern| pattern != "*" && glob(pattern, &lower))
+        {
+            format!("{} is a local or private address", host)
+        } else if self.patterns.is_empty()
+            || self.patterns.iter().any(|pattern| glob(pattern, &lower))
+        {
+            return Ok(());
+        } else {
+            format!("{} is not on the egress allowlist", host)
+        };
+        diagnostics::log(&format!("egress denied {} {}", tool, host));
+        let path = paths::egress_log();
+        let entry = format!(
+            "{} {} {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            tool,
+            url.trim()
+        );
+        let _ = fs::create_dir_all(paths::config_dir());
+        let _ = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(entry.as_bytes()));
+        Err(format!(
+            "Error: Refused by the user's network policy: {}. \
+Do not try it again; use another source, or answer without it.",
+            reason
+        ))
+    }
+}
+
+/// Whether `host` is this machine or on a private network: `localhost`,
+/// and loopback, private, link-local, shared and unspecified addresses.
+/// Names are not resolved.
+pub fn is_local(host: &str) -> bool {
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_lowercase();
+    if host == "localhost" || host.ends_with(".localhost") {
+        return true;
+    }
+    let v4 = |ip: Ipv4Addr| {
+        let [first, second, ..] = ip.octets();
+        ip.is_loopback()
+            || ip.is_private()
+            || ip.is_link_local()
+            || ip.is_unspecified()
+            || (first == 100 && second & 0xc0 == 64)
+    };
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => v4(ip),
+        Ok(IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(v4)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` is any run of characters
+/// and `?` any one.
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // The last `*` seen and where in `text` it started matching, to go
+    // back to when the rest fails.
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn normalize(domain: &str) -> String {
+    domain
+        .trim()
+        .trim_start_matches("*.")
+        .trim_end_matches('.')
+        .to_lowercase()
+}
+
+/// The host `url` points at, refusing anything but http and https.
+pub fn host(url: &str) -> Result<String, String> {
+    let url = Url::parse(url.trim()).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Only http and https URLs are allowed, not {}",
+            url.scheme()
+        ));
+    }
+    url.host_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("The URL '{}' has no host", url))
+}
+
+/// Sends a GET or POST request and returns the status, a few headers and
+/// the body as text.
+pub async fn send(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: Option<&str>,
+) -> Result<String, String> {
+    host(url)?;
+    let method = match method.to_uppercase().as_str() {
+        "" | "GET" => Method::GET,
+        "POST" => Method::POST,
+        other => return Err(format!("Unsupported method {}; use GET or POST", other)),
+    };
+    if method == Method::GET && body.is_some() {
+        return Err("A GET request cannot have a body".to_string());
+    }
+
+    // Redirects are not followed, since they could lead off the allowlist;
+    // the model sees the `location` header and can ask for it instead.
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| format!("Could not set up the client: {}", e))?;
+    let mut request = client
+        .request(method, url.trim())
+        .header("User-Agent", "rusty-cli");
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    if let Some(body) = body {
+        request = request.body(body.to_string());
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Could not send the request: {}", e))?;
+
+    let mut output = format!("HTTP {}\n", response.status());
+    for name in SHOWN_HEADERS {
+        if let Some(value) = response.headers().get(name).and_then(|v| v.to_str().ok()) {
+            output.push_str(&format!("{}: {}\n", name, value));
+        }
+    }
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("Could not read the response: {}", e))?;
+    output.push('\n');
+    output.extend(text.chars().take(MAX_CHARS));
+    let total = text.chars().count();
+    if total > MAX_CHARS {
+        output.push_str(&format!(
+            "\n\n[Body truncated: {} of {} characters shown.]",
+            MAX_CHARS, total
+        ));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_any_run_or_any_one_character() {
+        assert!(glob("*.github.com", "api.github.com"));
+        assert!(glob("*.github.com", "raw.objects.github.com"));
+        assert!(!glob("*.github.com", "github.com"));
+        assert!(!glob("*.github.com", "github.com.evil.net"));
+        assert!(glob("docs.rs", "docs.rs"));
+        assert!(!glob("docs.rs", "docs.rsx"));
+        assert!(glob("api?.example.com", "api2.example.com"));
+        assert!(!glob("api?.example.com", "api.example.com"));
+        assert!(glob("*", "anything"));
+        assert!(glob("a*b*c", "axxbyyc"));
+        assert!(!glob("a*b*c", "axxbyy"));
+    }
+
+    #[test]
+    fn egress_lets_matching_hosts_through() {
+        assert!(Egress::new(&[])
+            .check("open", "https://anywhere.org/")
+            .is_ok());
+        let egress = Egress::new(&["*.GitHub.com.".to_string(), "docs.rs".to_string()]);
+        assert!(egress.check("open", "https://API.github.com/repos").is_ok());
+        assert!(egress.check("open", "https://docs.rs/regex").is_ok());
+        assert!(egress.check("open", "not a url").is_ok());
+    }
+
+    #[test]
+    fn tells_local_and_private_hosts() {
+        for host in [
+            "localhost",
+            "api.localhost",
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "[::1]",
+            "[fd00::1]",
+            "[fe80::1]",
+            "[::ffff:127.0.0.1]",
+        ] {
+            assert!(is_local(host), "{}", host);
+        }
+        for host in ["example.com", "8.8.8.8", "172.32.0.1", "[2606:4700::1111]"] {
+            assert!(!is_local(host), "{}", host);
+        }
+    }
+
+    #[test]
+    fn unattended_egress_lets_listed_local_hosts_through() {
+        let egress =
+            Egress::new(&["127.0.0.1".to_string(), "192.168.1.*".to_string()]).unattended();
+        assert!(egress.check("open", "http://127.0.0.1:8080/").is_ok());
+        assert!(egress.check("open", "http://192.168.1.20/").is_ok());
+        assert!(Egress::default()
+            .unattended()
+            .check("open", "https://example.com/")
+            .is_ok());
+    }
+
+    #[test]
+    fn allowlist_covers_subdomains() {
+        let mut allowlist = Allowlist::new(&["*.Example.com".to_string()]);
+        assert!(allowlist.allows("example.com"));
+        assert!(allowlist.allows("api.example.com."));
+        assert!(!allowlist.allows("badexample.com"));
+        allowlist.add("other.org");
+        assert!(allowlist.allows("other.org"));
+    }
+
+    #[test]
+    fn only_http_and_https_urls_have_a_host() {
+        assert_eq!(host("https://example.com/x").unwrap(), "example.com");
+        assert!(host("file:///etc/passwd").is_err());
+        assert!(host("ftp://example.com/").is_err());
+    }
+}
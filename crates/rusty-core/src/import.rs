@@ -0,0 +1,227 @@
+//! `rusty import`: conversations exported from ChatGPT or Claude, read
+//! into sessions so they can be searched and resumed like Rusty's own.
+//!
+//! Both services export a `conversations.json` inside the archive they
+//! send: ChatGPT's keeps each conversation as a tree of messages, with the
+//! branch last shown in `current_node`; Claude's keeps a flat list of
+//! `chat_messages`. Only the text of prompts and answers is kept; system
+//! notes, tool output and attachments are left out.
+
+use chrono::DateTime;
+use serde_json::Value;
+
+use crate::message::Message;
+use crate::sessions::Session;
+
+/// Where an export came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    ChatGpt,
+    Claude,
+}
+
+impl Source {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ChatGpt => "ChatGPT",
+            Self::Claude => "Claude",
+        }
+    }
+
+    /// The tag imported sessions get, and the start of their ids.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Self::ChatGpt => "chatgpt",
+            Self::Claude => "claude",
+        }
+    }
+}
+
+/// The sessions in an export, which conversations without any text were
+/// left out of, and where the export came from.
+pub struct Import {
+    pub source: Source,
+    pub sessions: Vec<Session>,
+    pub skipped: usize,
+}
+
+/// Reads the `conversations.json` of a ChatGPT or Claude export. Session
+/// ids are made from the conversations' own, so importing the same export
+/// again replaces the sessions rather than adding copies.
+pub fn read_export(text: &str) -> Result<Import, String> {
+    let export: Value =
+        serde_json::from_str(text).map_err(|e| format!("The export is not valid JSON: {}", e))?;
+    let conversations = export
+        .as_array()
+        .ok_or("The export is not a list of conversations")?;
+    let source = match conversations.first() {
+        None => return Err("The export has no conversations".to_string()),
+        Some(first) if first.get("mapping").is_some() => Source::ChatGpt,
+        Some(first) if first.get("chat_messages").is_some() => Source::Claude,
+        Some(_) => {
+            return Err(
+                "Not a ChatGPT or Claude export; use the conversations.json from the archive"
+                    .to_string(),
+            )
+        }
+    };
+
+    let mut sessions = Vec::new();
+    let mut skipped = 0;
+    for conversation in conversations {
+        let session = match source {
+            Source::ChatGpt => chatgpt_session(conversation),
+            Source::Claude => claude_session(conversation),
+        };
+        match session {
+            Some(mut session) if !session.messages.is_empty() => {
+                session.add_tag(source.tag());
+                sessions.push(session);
+            }
+            _ => skipped += 1,
+        }
+    }
+    Ok(Import {
+        source,
+        sessions,
+        skipped,
+    })
+}
+
+fn chatgpt_session(conversation: &Value) -> Option<Session> {
+    let id = conversation["id"]
+        .as_str()
+        .or_else(|| conversation["conversation_id"].as_str())?;
+    let mapping = conversation["mapping"].as_object()?;
+
+    // The branch shown last, walked back from its end.
+    let mut nodes = Vec::new();
+    let mut current = conversation["current_node"].as_str();
+    while let Some(node) = current.and_then(|id| mapping.get(id)) {
+        nodes.push(node);
+        current = node["parent"].as_str();
+        // A broken export could loop.
+        if nodes.len() > mapping.len() {
+            break;
+        }
+    }
+    nodes.reverse();
+
+    let messages = nodes
+        .iter()
+        .filter_map(|node| {
+            let message = &node["message"];
+            if message["metadata"]["is_visually_hidden_from_conversation"] == true {
+                return None;
+            }
+            let content = &message["content"];
+            let text = match content["content_type"].as_str() {
+                Some("text" | "multimodal_text") => content["parts"]
+                    .as_array()?
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+                Some("code") => format!("```\n{}\n```", content["text"].as_str()?),
+                _ => return None,
+            };
+            match message["author"]["role"].as_str()? {
+                "user" => message_of("user", &text),
+                "assistant" => message_of("assistant", &text),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let created_at = conversation["create_time"].as_f64().unwrap_or_default() as u64;
+    let updated_at = conversation["update_time"]
+        .as_f64()
+        .map_or(created_at, |t| t as u64);
+    let model = conversation["default_model_slug"]
+        .as_str()
+        .unwrap_or("chatgpt");
+    Some(session(
+        Source::ChatGpt,
+        id,
+        conversation["title"].as_str(),
+        created_at,
+        updated_at,
+        model,
+        messages,
+    ))
+}
+
+fn claude_session(conversation: &Value) -> Option<Session> {
+    let id = conversation["uuid"].as_str()?;
+    let messages = conversation["chat_messages"]
+        .as_array()?
+        .iter()
+        .filter_map(|message| {
+            // Newer exports keep the text in content blocks only.
+            let text = match message["text"].as_str() {
+                Some(text) if !text.trim().is_empty() => text.to_string(),
+                _ => message["content"]
+                    .as_array()?
+                    .iter()
+                    .filter(|block| block["type"] == "text")
+                    .filter_map(|block| block["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+            };
+            match message["sender"].as_str()? {
+                "human" => message_of("user", &text),
+                "assistant" => message_of("assistant", &text),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let time = |key: &str| {
+        conversation[key]
+            .as_str()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.timestamp().max(0) as u64)
+    };
+    let created_at = time("created_at").unwrap_or_default();
+    Some(session(
+        Source::Claude,
+        id,
+        conversation["name"].as_str(),
+        created_at,
+        time("updated_at").unwrap_or(created_at),
+        "claude",
+        messages,
+    ))
+}
+
+fn session(
+    source: Source,
+    id: &str,
+    title: Option<&str>,
+    created_at: u64,
+    updated_at: u64,
+    model: &str,
+    messages: Vec<Message>,
+) -> Session {
+    let mut session = Session::new(model);
+    session.id = format!("{}-{}", source.tag(), id);
+    session.title = title
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(str::to_string);
+    session.created_at = created_at;
+    session.updated_at = updated_at;
+    session.messages = messages;
+    session
+}
+
+fn message_of(role: &str, text: &str) -> Option<Message> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(match role {
+        "user" => Message::user(text),
+        _ => Message::assistant(text),
+    })
+}
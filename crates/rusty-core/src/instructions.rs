@@ -0,0 +1,64 @@
+//! Standing instructions written in `RUSTY.md` files: a global one in the
+//! configuration directory, and one in any directory from the filesystem
+//! root down to the working directory, the project root among them. They
+//! are all sent, the nearest last, so that its instructions win where
+//! they disagree.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+
+pub const FILE_NAME: &str = "RUSTY.md";
+
+#[derive(Debug, Clone)]
+pub struct InstructionFile {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+/// The instruction files that apply in the working directory, from the
+/// one that gives way to all the others to the nearest. Files that are
+/// empty or cannot be read are skipped.
+pub fn load() -> Vec<InstructionFile> {
+    let mut paths = vec![paths::instructions_file()];
+    if let Ok(dir) = std::env::current_dir() {
+        let mut dirs: Vec<&Path> = dir.ancestors().collect();
+        dirs.reverse();
+        paths.extend(dirs.into_iter().map(|dir| dir.join(FILE_NAME)));
+    }
+
+    let mut files: Vec<InstructionFile> = Vec::new();
+    for path in paths {
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        // The global file is also found on the way down when the
+        // configuration directory is an ancestor.
+        let path = path.canonicalize().unwrap_or(path);
+        if text.trim().is_empty() || files.iter().any(|file| file.path == path) {
+            continue;
+        }
+        files.push(InstructionFile { path, text });
+    }
+    files
+}
+
+/// The system note holding every file's instructions, or `None` when there
+/// are none.
+pub fn system_prompt(files: &[InstructionFile]) -> Option<String> {
+    if files.is_empty() {
+        return None;
+    }
+    let mut prompt = String::from(
+        "Instructions from the user's RUSTY.md files, the most general first; where they disagree, follow the later ones.\n",
+    );
+    for file in files {
+        prompt.push_str(&format!(
+            "\n## {}\n\n{}\n",
+            file.path.display(),
+            file.text.trim()
+        ));
+    }
+    Some(prompt)
+}
@@ -0,0 +1,99 @@
+//! The chat engine behind the Rusty CLI.
+//!
+//! `rusty-core` holds everything that does not touch the terminal: the message
+//! types exchanged with the provider, the provider clients themselves, the
+//! Brave Search client, the tools the model may call (built in or provided by
+//! plugin executables), the conversation history, the models on offer, and
+//! the services that answer bots, webhooks and schedules. Front ends (the
+//! REPL, or a future GUI) drive these pieces and decide how to render them.
+
+pub mod agent;
+pub mod arxiv;
+pub mod browser;
+pub mod calc;
+pub mod cassette;
+pub mod citations;
+pub mod clipboard;
+pub mod config;
+pub mod conversation;
+pub mod datetime;
+pub mod diagnostics;
+pub mod diagram;
+pub mod discord;
+pub mod encryption;
+pub mod environment;
+pub mod export;
+pub mod feed;
+pub mod feedback;
+pub mod fetch;
+pub mod gist;
+pub mod githooks;
+pub mod github;
+pub mod history;
+pub mod hooks;
+pub mod html;
+pub mod http;
+pub mod import;
+pub mod instructions;
+pub mod locale;
+pub mod market;
+pub mod mcp;
+pub mod memory;
+pub mod message;
+pub mod models;
+pub mod moderation;
+pub mod notice;
+pub mod notify;
+pub mod ocr;
+pub mod paths;
+pub mod pdf;
+pub mod pii;
+pub mod plugins;
+pub mod projects;
+pub mod prompts;
+pub mod provider;
+pub mod rag;
+pub mod redact;
+pub mod repo;
+pub mod schedule;
+pub mod scripting;
+pub mod search;
+pub mod services;
+pub mod sessions;
+pub mod settings;
+pub mod shell;
+pub mod slack;
+pub mod spending;
+pub mod stackexchange;
+pub mod structured;
+pub mod telegram;
+pub mod telemetry;
+pub mod tools;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watch;
+pub mod webhook;
+pub mod wikipedia;
+pub mod youtube;
+
+pub use agent::Agent;
+pub use config::Config;
+pub use conversation::ConversationManager;
+pub use history::History;
+pub use hooks::CommandHooks;
+pub use memory::MemoryStore;
+pub use message::{
+    FunctionCall, Generation, Message, TokenLogprob, ToolCall, ToolChoice, ToolDefinition,
+    ToolFunction, Usage,
+};
+pub use plugins::PluginHost;
+pub use prompts::PromptLibrary;
+pub use provider::{
+    ChatOptions, Embedder, GroqApiClient, MockProvider, MockReply, Provider, ProviderError,
+    Recorder, Scheduler,
+};
+pub use scripting::{HookOutcome, ScriptHooks};
+pub use search::BraveSearchClient;
+pub use sessions::{Session, SessionStore};
+pub use settings::Settings;
+pub use structured::ResponseFormat;
@@ -0,0 +1,89 @@
+//! Message catalogs for the interface text. A catalog is a TOML file of
+//! strings and lists of strings, grouped in tables; keys are looked up by
+//! their dotted path, such as `dashboard.tips`. Catalogs merged later
+//! replace the entries of earlier ones, so a translation only needs the keys
+//! it translates.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Text(String),
+    List(Vec<String>),
+}
+
+#[derive(Debug, Default)]
+pub struct Catalog {
+    entries: HashMap<String, Entry>,
+}
+
+impl Catalog {
+    /// Adds the entries of the catalog in `source`, replacing any already
+    /// there.
+    pub fn merge(&mut self, source: &str) -> Result<(), String> {
+        let table: toml::Table = toml::from_str(source).map_err(|e| e.to_string())?;
+        self.flatten("", table)
+    }
+
+    fn flatten(&mut self, prefix: &str, table: toml::Table) -> Result<(), String> {
+        for (key, value) in table {
+            let key = format!("{}{}", prefix, key);
+            match value {
+                toml::Value::String(text) => {
+                    self.entries.insert(key, Entry::Text(text));
+                }
+                toml::Value::Array(items) => {
+                    let list = items
+                        .into_iter()
+                        .map(|item| match item {
+                            toml::Value::String(text) => Ok(text),
+                            _ => Err(format!("'{}' must only hold strings", key)),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    self.entries.insert(key, Entry::List(list));
+                }
+                toml::Value::Table(table) => self.flatten(&format!("{}.", key), table)?,
+                _ => return Err(format!("'{}' must be a string or a list of strings", key)),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn text(&self, key: &str) -> Option<&str> {
+        match self.entries.get(key) {
+            Some(Entry::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn list(&self, key: &str) -> Option<&[String]> {
+        match self.entries.get(key) {
+            Some(Entry::List(list)) => Some(list),
+            _ => None,
+        }
+    }
+}
+
+/// The language to show: `configured` if set, otherwise the one of the
+/// system locale (`LC_ALL`, `LC_MESSAGES`, then `LANG`), as a lowercase
+/// code such as `de`. English when neither says.
+pub fn language(configured: Option<&str>) -> String {
+    let system = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    let tag = configured
+        .map(str::to_string)
+        .or(system)
+        .unwrap_or_default();
+    // `pt_BR.UTF-8` and `pt-BR` are both Portuguese.
+    let code = tag
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match code.as_str() {
+        "" | "c" | "posix" => "en".to_string(),
+        _ => code,
+    }
+}
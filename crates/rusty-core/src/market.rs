@@ -0,0 +1,438 @@
+//! Cryptocurrency spot prices and 24-hour statistics, for the
+//! `crypto_price` tool, and stock quotes for the `stock_quote` tool.
+//! CoinGecko is asked first for coins since it knows every coin and fiat
+//! currency; Binance answers when CoinGecko is rate limited. Stock quotes
+//! need a key for Alpha Vantage or Twelve Data in `STOCK_API_KEY`, with
+//! `STOCK_PROVIDER` naming which.
+
+use serde_json::Value;
+
+const COINGECKO_URL: &str = "https://api.coingecko.com/api/v3";
+const BINANCE_URL: &str = "https://api.binance.com/api/v3";
+const ALPHA_VANTAGE_URL: &str = "https://www.alphavantage.co/query";
+const TWELVE_DATA_URL: &str = "https://api.twelvedata.com/quote";
+
+/// One coin's market snapshot, whichever exchange it came from.
+struct Snapshot {
+    name: String,
+    symbol: String,
+    price: f64,
+    change: Option<f64>,
+    change_percent: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    volume: Option<f64>,
+    market_cap: Option<f64>,
+    rank: Option<u64>,
+    updated: Option<String>,
+    source: &'static str,
+}
+
+/// The price of `coin` (a ticker such as `BTC` or a name such as
+/// `ethereum`) in `currency`, by default US dollars.
+pub async fn price(coin: &str, currency: Option<&str>) -> Result<String, String> {
+    let coin = coin.trim();
+    if coin.is_empty() {
+        return Err("No coin given".to_string());
+    }
+    let currency = currency.unwrap_or("usd").trim().to_lowercase();
+    let client = reqwest::Client::new();
+
+    let snapshot = match coingecko(&client, coin, &currency).await {
+        Ok(snapshot) => snapshot,
+        Err(gecko) => binance(&client, coin, &currency)
+            .await
+            .map_err(|binance| format!("{}; {}", gecko, binance))?,
+    };
+    Ok(format_snapshot(&snapshot, &currency.to_uppercase()))
+}
+
+async fn get(
+    client: &reqwest::Client,
+    url: &str,
+    query: &[(&str, &str)],
+    source: &str,
+) -> Result<Value, String> {
+    let response = client
+        .get(url)
+        .query(query)
+        .header("User-Agent", "rusty-cli")
+        .send()
+        .await
+        .map_err(|e| format!("{} request failed: {}", source, e))?;
+    let status = response.status();
+    let json: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid {} response: {}", source, e))?;
+    if !status.is_success() {
+        let message = json["msg"]
+            .as_str()
+            .or(json["status"]["error_message"].as_str())
+            .or(json["error"].as_str())
+            .unwrap_or("unknown error");
+        return Err(format!("{} returned {}: {}", source, status, message));
+    }
+    Ok(json)
+}
+
+async fn coingecko(
+    client: &reqwest::Client,
+    coin: &str,
+    currency: &str,
+) -> Result<Snapshot, String> {
+    // Tickers are ambiguous, so resolve to the largest coin using it.
+    let search = get(
+        client,
+        &format!("{}/search", COINGECKO_URL),
+        &[("query", coin)],
+        "CoinGecko",
+    )
+    .await?;
+    let coins = search["coins"].as_array().cloned().unwrap_or_default();
+    let wanted = coin.to_lowercase();
+    let matches = |c: &&Value| {
+        [&c["symbol"], &c["id"], &c["name"]]
+            .iter()
+            .any(|v| v.as_str().is_some_and(|v| v.to_lowercase() == wanted))
+    };
+    let id = coins
+        .iter()
+        .filter(matches)
+        .min_by_key(|c| c["market_cap_rank"].as_u64().unwrap_or(u64::MAX))
+        .or(coins.first())
+        .and_then(|c| c["id"].as_str())
+        .ok_or_else(|| format!("CoinGecko knows no coin called '{}'", coin))?;
+
+    let markets = get(
+        client,
+        &format!("{}/coins/markets", COINGECKO_URL),
+        &[("vs_currency", currency), ("ids", id)],
+        "CoinGecko",
+    )
+    .await?;
+    let market = markets.get(0).ok_or_else(|| {
+        format!(
+            "CoinGecko has no {} price for {}",
+            currency.to_uppercase(),
+            id
+        )
+    })?;
+    Ok(Snapshot {
+        name: market["name"].as_str().unwrap_or(id).to_string(),
+        symbol: market["symbol"].as_str().unwrap_or(coin).to_uppercase(),
+        price: market["current_price"]
+            .as_f64()
+            .ok_or("CoinGecko returned no price")?,
+        change: market["price_change_24h"].as_f64(),
+        change_percent: market["price_change_percentage_24h"].as_f64(),
+        high: market["high_24h"].as_f64(),
+        low: market["low_24h"].as_f64(),
+        volume: market["total_volume"].as_f64(),
+        market_cap: market["market_cap"].as_f64(),
+        rank: market["market_cap_rank"].as_u64(),
+        updated: market["last_updated"].as_str().map(str::to_string),
+        source: "CoinGecko",
+    })
+}
+
+async fn binance(client: &reqwest::Client, coin: &str, currency: &str) -> Result<Snapshot, String> {
+    // Binance quotes dollars in the USDT stablecoin.
+    let quote = match currency {
+        "usd" => "USDT".to_string(),
+        other => other.to_uppercase(),
+    };
+    let base = coin.to_uppercase();
+    let pair = format!("{}{}", base, quote);
+    let ticker = get(
+        client,
+        &format!("{}/ticker/24hr", BINANCE_URL),
+        &[("symbol", pair.as_str())],
+        "Binance",
+    )
+    .await?;
+    let number = |key: &str| ticker[key].as_str().and_then(|v| v.parse::<f64>().ok());
+    Ok(Snapshot {
+        name: base.clone(),
+        symbol: base,
+        price: number("lastPrice").ok_or("Binance returned no price")?,
+        change: number("priceChange"),
+        change_percent: number("priceChangePercent"),
+        high: number("highPrice"),
+        low: number("lowPrice"),
+        volume: number("quoteVolume"),
+        market_cap: None,
+        rank: None,
+        updated: None,
+        source: "Binance",
+    })
+}
+
+/// One stock's latest quote.
+struct Quote {
+    symbol: String,
+    name: Option<String>,
+    exchange: Option<String>,
+    currency: Option<String>,
+    price: f64,
+    change: Option<f64>,
+    change_percent: Option<f64>,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    previous_close: Option<f64>,
+    volume: Option<f64>,
+    date: Option<String>,
+    source: &'static str,
+}
+
+/// The latest quote for the stock `symbol` (such as `AAPL`) from the
+/// provider configured in the environment.
+pub async fn stock_quote(symbol: &str) -> Result<String, String> {
+    let symbol = symbol.trim().to_uppercase();
+    if symbol.is_empty() {
+        return Err("No stock symbol given".to_string());
+    }
+    let key = std::env::var("STOCK_API_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .ok_or("Stock quotes need a data provider key; set STOCK_API_KEY in .env")?;
+    let provider = std::env::var("STOCK_PROVIDER").unwrap_or_default();
+    let client = reqwest::Client::new();
+
+    let quote = match provider.trim().to_lowercase().as_str() {
+        "" | "alphavantage" | "alpha_vantage" => alpha_vantage(&client, &symbol, &key).await?,
+        "twelvedata" | "twelve_data" => twelve_data(&client, &symbol, &key).await?,
+        other => {
+            return Err(format!(
+                "Unknown STOCK_PROVIDER '{}'; use alphavantage or twelvedata",
+                other
+            ))
+        }
+    };
+    Ok(format_quote(&quote))
+}
+
+async fn alpha_vantage(client: &reqwest::Client, symbol: &str, key: &str) -> Result<Quote, String> {
+    let json = get(
+        client,
+        ALPHA_VANTAGE_URL,
+        &[
+            ("function", "GLOBAL_QUOTE"),
+            ("symbol", symbol),
+            ("apikey", key),
+        ],
+        "Alpha Vantage",
+    )
+    .await?;
+    // Errors and rate limits arrive with a 200 status.
+    if let Some(message) = ["Error Message", "Note", "Information"]
+        .iter()
+        .find_map(|field| json[field].as_str())
+    {
+        return Err(format!("Alpha Vantage returned an error: {}", message));
+    }
+    let quote = &json["Global Quote"];
+    let number = |key: &str| {
+        quote[key]
+            .as_str()
+            .and_then(|v| v.trim_end_matches('%').parse::<f64>().ok())
+    };
+    Ok(Quote {
+        symbol: quote["01. symbol"].as_str().unwrap_or(symbol).to_string(),
+        name: None,
+        exchange: None,
+        currency: None,
+        price: number("05. price")
+            .ok_or_else(|| format!("Alpha Vantage has no quote for {}", symbol))?,
+        change: number("09. change"),
+        change_percent: number("10. change percent"),
+        open: number("02. open"),
+        high: number("03. high"),
+        low: number("04. low"),
+        previous_close: number("08. previous close"),
+        volume: number("06. volume"),
+        date: quote["07. latest trading day"].as_str().map(str::to_string),
+        source: "Alpha Vantage",
+    })
+}
+
+async fn twelve_data(client: &reqwest::Client, symbol: &str, key: &str) -> Result<Quote, String> {
+    let json = get(
+        client,
+        TWELVE_DATA_URL,
+        &[("symbol", symbol), ("apikey", key)],
+        "Twelve Data",
+    )
+    .await?;
+    if json["status"].as_str() == Some("error") {
+        return Err(format!(
+            "Twelve Data returned an error: {}",
+            json["message"].as_str().unwrap_or("unknown error")
+        ));
+    }
+    let number = |key: &str| json[key].as_str().and_then(|v| v.parse::<f64>().ok());
+    let text = |key: &str| json[key].as_str().map(str::to_string);
+    Ok(Quote {
+        symbol: json["symbol"].as_str().unwrap_or(symbol).to_string(),
+        name: text("name"),
+        exchange: text("exchange"),
+        currency: text("currency"),
+        price: number("close").ok_or_else(|| format!("Twelve Data has no quote for {}", symbol))?,
+        change: number("change"),
+        change_percent: number("percent_change"),
+        open: number("open"),
+        high: number("high"),
+        low: number("low"),
+        previous_close: number("previous_close"),
+        volume: number("volume"),
+        date: text("datetime"),
+        source: "Twelve Data",
+    })
+}
+
+fn format_quote(quote: &Quote) -> String {
+    let mut output = match &quote.name {
+        Some(name) => format!("### {} ({}) quote\n\n", name, quote.symbol),
+        None => format!("### {} quote\n\n", quote.symbol),
+    };
+    let currency = quote
+        .currency
+        .as_deref()
+        .map(|currency| format!(" {}", currency))
+        .unwrap_or_default();
+    output.push_str(&format!("- Price: {}{}\n", amount(quote.price), currency));
+    if let Some(change) = quote.change {
+        let percent = quote
+            .change_percent
+            .map(|percent| format!(" ({}{:.2}%)", sign(percent), percent.abs()))
+            .unwrap_or_default();
+        output.push_str(&format!(
+            "- Change: {}{}{}\n",
+            sign(change),
+            amount(change.abs()),
+            percent
+        ));
+    }
+    for (label, value) in [
+        ("Open", quote.open),
+        ("Previous close", quote.previous_close),
+    ] {
+        if let Some(value) = value {
+            output.push_str(&format!("- {}: {}\n", label, amount(value)));
+        }
+    }
+    if let (Some(high), Some(low)) = (quote.high, quote.low) {
+        output.push_str(&format!(
+            "- Day high / low: {} / {}\n",
+            amount(high),
+            amount(low)
+        ));
+    }
+    if let Some(volume) = quote.volume {
+        let volume = amount(volume.round());
+        output.push_str(&format!(
+            "- Volume: {} shares\n",
+            volume.trim_end_matches(".00")
+        ));
+    }
+    if let Some(exchange) = &quote.exchange {
+        output.push_str(&format!("- Exchange: {}\n", exchange));
+    }
+    if let Some(date) = &quote.date {
+        output.push_str(&format!("- As of: {}\n", date));
+    }
+    output.push_str(&format!("- Source: {}\n", quote.source));
+    output
+}
+
+fn format_snapshot(snapshot: &Snapshot, currency: &str) -> String {
+    let mut output = format!("### {} ({}) price\n\n", snapshot.name, snapshot.symbol);
+    output.push_str(&format!(
+        "- Price: {} {}\n",
+        amount(snapshot.price),
+        currency
+    ));
+    if let Some(percent) = snapshot.change_percent {
+        let change = snapshot
+            .change
+            .map(|change| format!(" ({}{} {})", sign(change), amount(change.abs()), currency))
+            .unwrap_or_default();
+        output.push_str(&format!(
+            "- 24h change: {}{:.2}%{}\n",
+            sign(percent),
+            percent.abs(),
+            change
+        ));
+    }
+    if let (Some(high), Some(low)) = (snapshot.high, snapshot.low) {
+        output.push_str(&format!(
+            "- 24h high / low: {} / {} {}\n",
+            amount(high),
+            amount(low),
+            currency
+        ));
+    }
+    if let Some(volume) = snapshot.volume {
+        output.push_str(&format!(
+            "- 24h volume: {} {}\n",
+            amount(volume.round()),
+            currency
+        ));
+    }
+    if let Some(cap) = snapshot.market_cap.filter(|&cap| cap > 0.0) {
+        let rank = snapshot
+            .rank
+            .map(|rank| format!(" (rank #{})", rank))
+            .unwrap_or_default();
+        output.push_str(&format!(
+            "- Market cap: {} {}{}\n",
+            amount(cap.round()),
+            currency,
+            rank
+        ));
+    }
+    if let Some(updated) = &snapshot.updated {
+        output.push_str(&format!("- Updated: {}\n", updated));
+    }
+    output.push_str(&format!("- Source: {}\n", snapshot.source));
+    output
+}
+
+fn sign(value: f64) -> &'static str {
+    if value < 0.0 {
+        "-"
+    } else {
+        "+"
+    }
+}
+
+/// Two decimals with thousands separators, or six significant digits for
+/// prices below one.
+fn amount(value: f64) -> String {
+    if value.abs() < 1.0 && value != 0.0 {
+        let decimals = (5 - value.abs().log10().floor() as i32).clamp(2, 12) as usize;
+        return format!("{:.*}", decimals, value)
+            .trim_end_matches('0')
+            .to_string();
+    }
+    let text = format!("{:.2}", value);
+    let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+    let (sign, digits) = match whole.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", whole),
+    };
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    let fraction = if fraction == "00" && value.abs() >= 1e6 {
+        String::new()
+    } else {
+        format!(".{}", fraction)
+    };
+    format!("{}{}{}", sign, grouped, fraction)
+}
@@ -0,0 +1,250 @@
+//! Rusty's tools as a Model Context Protocol server, so MCP clients such as
+//! Claude Desktop can call them. Requests and responses are JSON-RPC 2.0
+//! messages, one per line, over the reader and writer given to
+//! [`McpServer::serve`] (stdin and stdout for `rusty mcp-serve`).
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::fetch::Fetcher;
+use crate::http::{Allowlist, Egress};
+use crate::memory::MemoryStore;
+use crate::message::ToolDefinition;
+use crate::ocr::Ocr;
+use crate::plugins::PluginHost;
+use crate::rag::Retriever;
+use crate::search::BraveSearchClient;
+use crate::tools::{self, Policy, ToolContext, ToolRegistry};
+
+/// Protocol revisions the server speaks, newest first.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub struct McpServer {
+    brave: BraveSearchClient,
+    tools: ToolRegistry,
+    memory: MemoryStore,
+    documents: Option<Retriever>,
+    ocr: Ocr,
+    fetcher: Fetcher,
+    /// Hosts `http_request` may call. Nobody is there to approve others,
+    /// so requests anywhere else are refused.
+    http_allowlist: Allowlist,
+    /// Hosts any tool may contact at all.
+    egress: Egress,
+    /// Whether `read_clipboard` is offered.
+    clipboard_tool: bool,
+    /// Tool calls that may change something are refused.
+    read_only: bool,
+    /// Whether the workspace is trusted to run programs in, as decided in
+    /// `[trust]`; stdin carries the protocol, so nobody can be asked.
+    trusted: bool,
+}
+
+impl McpServer {
+    pub fn new(
+        brave: BraveSearchClient,
+        plugins: PluginHost,
+        memory: MemoryStore,
+        documents: Option<Retriever>,
+        ocr: Ocr,
+        http_allowlist: Allowlist,
+        clipboard_tool: bool,
+    ) -> Self {
+        Self {
+            brave,
+            tools: ToolRegistry::new(&plugins),
+            memory,
+            documents,
+            ocr,
+            fetcher: Fetcher::default(),
+            http_allowlist,
+            egress: Egress::default(),
+            clipboard_tool,
+            read_only: false,
+            trusted: false,
+        }
+    }
+
+    pub fn set_fetcher(&mut self, fetcher: Fetcher) {
+        self.fetcher = fetcher;
+    }
+
+    pub fn set_egress(&mut self, egress: Egress) {
+        self.egress = egress;
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn set_trusted(&mut self, trusted: bool) {
+        self.trusted = trusted;
+    }
+
+    fn policy(&self) -> Policy<'_> {
+        Policy {
+            read_only: self.read_only,
+            trusted: Some(self.trusted),
+            egress: &self.egress,
+            allowlist: &self.http_allowlist,
+        }
+    }
+
+    /// Answers requests from `input` on `output` until `input` ends.
+    pub async fn serve<R, W>(&mut self, input: R, mut output: W) -> Result<(), String>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = input.lines();
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| format!("Could not read a request: {}", e))?
+        {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle(message).await,
+                Err(e) => Some(error(Value::Null, PARSE_ERROR, &e.to_string())),
+            };
+            if let Some(response) = response {
+                let mut text = response.to_string();
+                text.push('\n');
+                output
+                    .write_all(text.as_bytes())
+                    .await
+                    .map_err(|e| format!("Could not write a response: {}", e))?;
+                output
+                    .flush()
+                    .await
+                    .map_err(|e| format!("Could not write a response: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The response to one message, or `None` for a notification.
+    async fn handle(&mut self, message: Value) -> Option<Value> {
+        let Some(method) = message["method"].as_str() else {
+            // A response to a request of ours; the server makes none.
+            return message
+                .get("id")
+                .is_none()
+                .then(|| error(Value::Null, INVALID_REQUEST, "Not a request"));
+        };
+        let id = message.get("id").cloned()?;
+        let params = &message["params"];
+        let result = match method {
+            "initialize" => Ok(initialize(params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({
+                "tools": self
+                    .tools()
+                    .iter()
+                    .map(|tool| json!({
+                        "name": tool.function.name,
+                        "description": tool.function.description,
+                        "inputSchema": tool.function.parameters,
+                    }))
+                    .collect::<Vec<_>>()
+            })),
+            "tools/call" => self.call(params).await,
+            other => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", other))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error(id, code, &message),
+        })
+    }
+
+    /// The tools offered, as in the chat: built-in and plugin tools, plus
+    /// the optional ones that are turned on, but none that run programs in
+    /// a workspace that is not trusted, or that always change something in
+    /// read-only mode.
+    fn tools(&self) -> Vec<ToolDefinition> {
+        self.tools
+            .definitions()
+            .into_iter()
+            .filter(|tool| match tool.function.name.as_str() {
+                "search_docs" => self.documents.is_some(),
+                "read_clipboard" => self.clipboard_tool,
+                name => self
+                    .tools
+                    .get(name)
+                    .is_some_and(|tool| self.policy().offers(tool.as_ref())),
+            })
+            .collect()
+    }
+
+    async fn call(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params["name"]
+            .as_str()
+            .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+        let tool = match self.tools.get(name) {
+            Some(tool) if self.tools().iter().any(|t| t.function.name == name) => tool,
+            _ => return Err((INVALID_PARAMS, format!("Unknown tool '{}'", name))),
+        };
+        let arguments = match &params["arguments"] {
+            Value::Null => json!({}),
+            arguments => arguments.clone(),
+        };
+        let arguments = tools::read_arguments(tool.as_ref(), &arguments.to_string())
+            .map_err(|e| (INVALID_PARAMS, format!("The arguments {}", e)))?;
+
+        if let Err(refusal) = self.policy().check(tool.as_ref(), &arguments).unattended() {
+            return Ok(tool_result(&refusal, true));
+        }
+
+        let context = ToolContext {
+            brave: &self.brave,
+            memory: &mut self.memory,
+            documents: self.documents.as_ref(),
+            ocr: &self.ocr,
+            fetcher: &self.fetcher,
+        };
+        let result = tool.execute(&arguments, context).await;
+        // Tool failures are results for the client's model to read, not
+        // protocol errors.
+        Ok(match result {
+            Ok(text) => tool_result(&text, false),
+            Err(e) => tool_result(&e, true),
+        })
+    }
+}
+
+fn tool_result(text: &str, is_error: bool) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    })
+}
+
+/// Agrees on the client's protocol revision when the server speaks it, and
+/// otherwise offers the newest one.
+fn initialize(params: &Value) -> Value {
+    let requested = params["protocolVersion"].as_str().unwrap_or_default();
+    let version = PROTOCOL_VERSIONS
+        .iter()
+        .find(|&&version| version == requested)
+        .unwrap_or(&PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": { "listChanged": false } },
+        "serverInfo": { "name": "rusty", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
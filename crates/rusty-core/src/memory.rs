@@ -0,0 +1,125 @@
+//! Long-term memory: facts about the user kept across sessions, one per
+//! line, and shown to the model at the start of every conversation.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::encryption::{self, Cipher};
+
+pub struct MemoryStore {
+    facts: Vec<String>,
+    path: PathBuf,
+    cipher: Option<Cipher>,
+}
+
+impl MemoryStore {
+    /// Reads the memory file at `path`, which need not exist yet.
+    pub fn load(path: &Path, cipher: Option<Cipher>) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let facts = if path.exists() {
+            match encryption::read_text(path, cipher.as_ref()) {
+                Ok(text) => text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                Err(e) => {
+                    warnings.push(e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        let store = Self {
+            facts,
+            path: path.to_path_buf(),
+            cipher,
+        };
+        (store, warnings)
+    }
+
+    /// A memory that starts empty and cannot be saved to, for callers that
+    /// do not offer the `remember` tool.
+    pub fn disabled() -> Self {
+        Self {
+            facts: Vec::new(),
+            path: PathBuf::new(),
+            cipher: None,
+        }
+    }
+
+    /// Facts in the order they were remembered.
+    pub fn facts(&self) -> &[String] {
+        &self.facts
+    }
+
+    /// Remembers `fact` and rewrites the file. Returns `false` when the fact
+    /// was already known.
+    pub fn add(&mut self, fact: &str) -> Result<bool, String> {
+        let fact = fact.split_whitespace().collect::<Vec<_>>().join(" ");
+        if fact.is_empty() {
+            return Err("Nothing to remember".to_string());
+        }
+        if self.facts.iter().any(|f| f.eq_ignore_ascii_case(&fact)) {
+            return Ok(false);
+        }
+        self.facts.push(fact);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Forgets the fact at `index` (0-based) and returns it.
+    pub fn remove(&mut self, index: usize) -> Result<String, String> {
+        if index >= self.facts.len() {
+            return Err(format!("No memory #{}", index + 1));
+        }
+        let fact = self.facts.remove(index);
+        self.save()?;
+        Ok(fact)
+    }
+
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.facts.clear();
+        self.save()
+    }
+
+    /// The system note listing every fact, or `None` when there are none.
+    pub fn system_prompt(&self) -> Option<String> {
+        if self.facts.is_empty() {
+            return None;
+        }
+        let mut prompt = String::from(
+            "Things you were asked to remember about the user, from earlier sessions:\n",
+        );
+        for fact in &self.facts {
+            prompt.push_str(&format!("- {}\n", fact));
+        }
+        Some(prompt)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if self.path.as_os_str().is_empty() {
+            return Err("Long-term memory is not available here".to_string());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut text = String::new();
+        for fact in &self.facts {
+            text.push_str(fact);
+            text.push('\n');
+        }
+        encryption::write_text(&self.path, &text, self.cipher.as_ref())?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600));
+        }
+        Ok(())
+    }
+}
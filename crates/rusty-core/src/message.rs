@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Message {
+    pub role: String,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// What a reasoning model thought before answering. It is read from
+    /// responses but never written, so it is not sent back to the model.
+    #[serde(default, skip_serializing, alias = "reasoning_content")]
+    pub reasoning: Option<String>,
+    /// The other answers written when several were asked for with `n`.
+    /// They are shown but not kept in the conversation.
+    #[serde(skip)]
+    pub alternatives: Vec<String>,
+    /// How sure the model was of each token of the answer, when asked.
+    #[serde(skip)]
+    pub logprobs: Vec<TokenLogprob>,
+    /// The tokens the request for this answer took, when the provider says.
+    #[serde(skip)]
+    pub usage: Option<Usage>,
+}
+
+/// The tokens a request took: `prompt` in and `completion` out, and how
+/// many of the prompt's the provider read from its prompt cache.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(from = "UsageBody")]
+pub struct Usage {
+    pub prompt: u64,
+    pub completion: u64,
+    pub cached: u64,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        self.prompt += other.prompt;
+        self.completion += other.completion;
+        self.cached += other.cached;
+    }
+
+    /// The share of prompt tokens read from the cache, from 0 to 1.
+    pub fn hit_rate(&self) -> f64 {
+        if self.prompt == 0 {
+            0.0
+        } else {
+            self.cached as f64 / self.prompt as f64
+        }
+    }
+}
+
+/// `usage` as the API returns it.
+#[derive(Deserialize)]
+struct UsageBody {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    #[serde(default)]
+    prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Deserialize)]
+struct PromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: u64,
+}
+
+impl From<UsageBody> for Usage {
+    fn from(body: UsageBody) -> Self {
+        Self {
+            prompt: body.prompt_tokens,
+            completion: body.completion_tokens,
+            cached: body
+                .prompt_tokens_details
+                .map_or(0, |details| details.cached_tokens),
+        }
+    }
+}
+
+/// A token of an answer and the log probability the model gave it, with
+/// the likeliest tokens it could have written instead.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f64,
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f64,
+}
+
+impl TokenLogprob {
+    /// The probability, from 0 to 1.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub r#type: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    pub arguments: String,
+}
+
+impl Message {
+    pub fn new(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: Some(content.to_string()),
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning: None,
+            alternatives: Vec::new(),
+            logprobs: Vec::new(),
+            usage: None,
+        }
+    }
+
+    pub fn user(content: &str) -> Self {
+        Self::new("user", content)
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        Self::new("assistant", content)
+    }
+
+    pub fn system(content: &str) -> Self {
+        Self::new("system", content)
+    }
+
+    pub fn tool(content: &str, id: &str) -> Self {
+        Self {
+            role: "tool".to_string(),
+            content: Some(content.to_string()),
+            tool_calls: None,
+            tool_call_id: Some(id.to_string()),
+            reasoning: None,
+            alternatives: Vec::new(),
+            logprobs: Vec::new(),
+            usage: None,
+        }
+    }
+
+    /// Moves reasoning the model wrote into its answer, between `<think>`
+    /// and `</think>` at the start, to `reasoning`, as some models do
+    /// instead of returning it apart.
+    pub fn split_reasoning(&mut self) {
+        let Some(content) = &self.content else {
+            return;
+        };
+        let Some(rest) = content.trim_start().strip_prefix("<think>") else {
+            return;
+        };
+        let Some((thought, answer)) = rest.split_once("</think>") else {
+            return;
+        };
+        let thought = thought.trim().to_string();
+        self.content = Some(answer.trim_start().to_string());
+        if !thought.is_empty() {
+            self.reasoning = Some(match self.reasoning.take() {
+                Some(reasoning) => format!("{}\n\n{}", reasoning, thought),
+                None => thought,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ToolDefinition {
+    pub r#type: String,
+    pub function: ToolFunction,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ToolFunction {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// How the model writes its answers, part of the [settings](crate::settings).
+/// Unset values are left to the API.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Generation {
+    /// Text that ends the answer where the model writes it; it is left out
+    /// of the answer.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// How many answers to write; the first one continues the conversation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// A bias from -100 to 100 for each token id, -100 ruling the token
+    /// out and 100 making it all but certain.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub logit_bias: BTreeMap<String, i32>,
+    /// From 0 to 2; higher makes the answers more varied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Only the most likely tokens adding up to this probability are
+    /// considered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    /// The longest answer, in tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Makes the same request give the same answer, as far as the provider
+    /// manages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// Whether the model must call a tool, sent as `tool_choice`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolChoice {
+    /// The model decides.
+    Auto,
+    /// No tool may be called.
+    None,
+    /// Some tool must be called.
+    Required,
+    /// The tool of this name must be called.
+    Tool(String),
+}
+
+impl ToolChoice {
+    /// `auto`, `none`, `required`, or the name of a tool.
+    pub fn parse(text: &str) -> Self {
+        match text {
+            "auto" => Self::Auto,
+            "none" => Self::None,
+            "required" => Self::Required,
+            name => Self::Tool(name.to_string()),
+        }
+    }
+
+    /// Whether a tool must be called, which can only hold for the first
+    /// request of a turn, or the model would never get to answer.
+    pub fn forces_call(&self) -> bool {
+        matches!(self, Self::Required | Self::Tool(_))
+    }
+
+    pub fn body(&self) -> serde_json::Value {
+        match self {
+            Self::Auto => "auto".into(),
+            Self::None => "none".into(),
+            Self::Required => "required".into(),
+            Self::Tool(name) => serde_json::json!({
+                "type": "function",
+                "function": { "name": name },
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub generation: Generation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    /// Alternatives returned for each token along with `logprobs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatResponse {
+    pub choices: Vec<Choice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Choice {
+    pub message: Message,
+    #[serde(default)]
+    pub logprobs: Option<ChoiceLogprobs>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChoiceLogprobs {
+    #[serde(default)]
+    pub content: Option<Vec<TokenLogprob>>,
+}
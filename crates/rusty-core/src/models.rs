@@ -0,0 +1,74 @@
+//! The models Rusty offers and what each can do and costs, with anything
+//! `[models]` in the configuration says about them over that.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::config::ModelConfig;
+
+/// Offered in this order; the first is the default.
+pub const MODELS: &[&str] = &[
+    "openai/gpt-oss-120b",
+    "meta-llama/llama-4-maverick-17b-128e-instruct",
+    "moonshotai/kimi-k2-instruct-0905",
+];
+
+/// What a model can do and costs.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelInfo {
+    /// In tokens.
+    pub context_window: usize,
+    /// Whether it can call tools.
+    pub tools: bool,
+    /// Whether it can see images.
+    pub vision: bool,
+    /// GroqCloud's price in US dollars per million input and output tokens.
+    pub price: Option<(f64, f64)>,
+}
+
+static OVERRIDES: OnceLock<BTreeMap<String, ModelConfig>> = OnceLock::new();
+
+/// Sets what `[models]` says, once for the process.
+pub fn set_overrides(models: BTreeMap<String, ModelConfig>) {
+    let _ = OVERRIDES.set(models);
+}
+
+/// What `model` can do and costs: what is known of it, with anything
+/// `[models]` says about it over that.
+pub fn model_info(model: &str) -> ModelInfo {
+    let known = |context_window, vision, price| ModelInfo {
+        context_window,
+        tools: true,
+        vision,
+        price: Some(price),
+    };
+    let mut info = match model {
+        "openai/gpt-oss-120b" => known(131_072, false, (0.15, 0.60)),
+        "meta-llama/llama-4-maverick-17b-128e-instruct" => known(131_072, true, (0.20, 0.60)),
+        "moonshotai/kimi-k2-instruct-0905" => known(262_144, false, (1.00, 3.00)),
+        _ => ModelInfo {
+            context_window: 131_072,
+            tools: true,
+            vision: false,
+            price: None,
+        },
+    };
+    if let Some(custom) = OVERRIDES.get().and_then(|models| models.get(model)) {
+        info.context_window = custom.context_window.unwrap_or(info.context_window);
+        info.tools = custom.tools.unwrap_or(info.tools);
+        info.vision = custom.vision.unwrap_or(info.vision);
+        if custom.input_price.is_some() || custom.output_price.is_some() {
+            let (input, output) = info.price.unwrap_or_default();
+            info.price = Some((
+                custom.input_price.unwrap_or(input),
+                custom.output_price.unwrap_or(output),
+            ));
+        }
+    }
+    info
+}
+
+/// Context window of `model` in tokens, before any `[context]` override.
+pub fn context_window(model: &str) -> usize {
+    model_info(model).context_window
+}
@@ -0,0 +1,208 @@
+//! Moderation of what users send and what the model answers, for Rusty
+//! run as a bot that a whole team talks to. Local rules are regular
+//! expressions; an endpoint, when configured, is asked about everything
+//! as well. Each can let a message through with a warning or block it.
+
+use std::time::Duration;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::config::{ModerationAction, ModerationConfig, ModerationScope};
+use crate::diagnostics;
+
+const ENDPOINT_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// What a user sent.
+    Input,
+    /// What the model answered.
+    Output,
+}
+
+impl Direction {
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Input => "input",
+            Direction::Output => "output",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    /// Let through, with the reasons to point out.
+    Warn(String),
+    Block(String),
+}
+
+struct Rule {
+    regex: Regex,
+    action: ModerationAction,
+    scope: ModerationScope,
+    reason: String,
+}
+
+/// What the endpoint answers.
+#[derive(Deserialize)]
+struct EndpointVerdict {
+    action: ModerationAction,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Moderator {
+    rules: Vec<Rule>,
+    endpoint: Option<String>,
+    fail_closed: bool,
+    client: reqwest::Client,
+}
+
+impl Moderator {
+    /// Invalid rule patterns are skipped with a warning.
+    pub fn new(config: &ModerationConfig) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let rules = config
+            .rules
+            .iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(regex) => Some(Rule {
+                    regex,
+                    action: rule.action,
+                    scope: rule.scope,
+                    reason: rule
+                        .reason
+                        .clone()
+                        .unwrap_or_else(|| format!("matches {:?}", rule.pattern)),
+                }),
+                Err(e) => {
+                    warnings.push(format!(
+                        "Invalid moderation pattern {:?}: {}",
+                        rule.pattern, e
+                    ));
+                    None
+                }
+            })
+            .collect();
+        let moderator = Self {
+            rules,
+            endpoint: config.endpoint.clone().filter(|url| !url.is_empty()),
+            fail_closed: config.fail_closed,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(ENDPOINT_TIMEOUT_SECS))
+                .build()
+                .unwrap_or_default(),
+        };
+        (moderator, warnings)
+    }
+
+    /// Whether messages going `direction` are checked at all. Answers that
+    /// are cannot be streamed, as they are checked before they are shown.
+    pub fn checks(&self, direction: Direction) -> bool {
+        self.endpoint.is_some() || self.rules.iter().any(|rule| rule.scope.covers(direction))
+    }
+
+    /// The verdict on `text`: blocked when any rule or the endpoint blocks
+    /// it, else with every warning given.
+    pub async fn check(&self, text: &str, direction: Direction) -> Verdict {
+        let mut warnings = Vec::new();
+        for rule in &self.rules {
+            if !rule.scope.covers(direction) || !rule.regex.is_match(text) {
+                continue;
+            }
+            match rule.action {
+                ModerationAction::Block => {
+                    return self.verdict(direction, Verdict::Block(rule.reason.clone()))
+                }
+                ModerationAction::Warn => warnings.push(rule.reason.clone()),
+                ModerationAction::Allow => {}
+            }
+        }
+        if let Some(endpoint) = &self.endpoint {
+            match self.ask(endpoint, text, direction).await {
+                Ok(EndpointVerdict {
+                    action: ModerationAction::Block,
+                    reason,
+                }) => {
+                    let reason =
+                        reason.unwrap_or_else(|| "blocked by the moderation service".to_string());
+                    return self.verdict(direction, Verdict::Block(reason));
+                }
+                Ok(EndpointVerdict {
+                    action: ModerationAction::Warn,
+                    reason,
+                }) => warnings.push(
+                    reason.unwrap_or_else(|| "flagged by the moderation service".to_string()),
+                ),
+                Ok(_) => {}
+                Err(e) if self.fail_closed => {
+                    return self.verdict(
+                        direction,
+                        Verdict::Block(format!("moderation unavailable ({})", e)),
+                    )
+                }
+                Err(e) => warnings.push(format!("not moderated: {}", e)),
+            }
+        }
+        if warnings.is_empty() {
+            Verdict::Allow
+        } else {
+            self.verdict(direction, Verdict::Warn(warnings.join("; ")))
+        }
+    }
+
+    async fn ask(
+        &self,
+        endpoint: &str,
+        text: &str,
+        direction: Direction,
+    ) -> Result<EndpointVerdict, String> {
+        let response = self
+            .client
+            .post(endpoint)
+            .json(&json!({ "direction": direction.name(), "text": text }))
+            .send()
+            .await
+            .map_err(|e| format!("the moderation request failed: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("the moderation service returned {}", status));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| format!("invalid moderation response: {}", e))
+    }
+
+    /// Logs a verdict that is not `Allow` before it is acted on.
+    fn verdict(&self, direction: Direction, verdict: Verdict) -> Verdict {
+        match &verdict {
+            Verdict::Block(reason) => diagnostics::log(&format!(
+                "moderation blocked {}: {}",
+                direction.name(),
+                reason
+            )),
+            Verdict::Warn(reason) => diagnostics::log(&format!(
+                "moderation warned on {}: {}",
+                direction.name(),
+                reason
+            )),
+            Verdict::Allow => {}
+        }
+        verdict
+    }
+}
+
+impl ModerationScope {
+    fn covers(self, direction: Direction) -> bool {
+        match self {
+            ModerationScope::Both => true,
+            ModerationScope::Input => direction == Direction::Input,
+            ModerationScope::Output => direction == Direction::Output,
+        }
+    }
+}
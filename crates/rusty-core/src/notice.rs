@@ -0,0 +1,48 @@
+//! What the engine has to tell the user while it works, deep in a request
+//! or a tool call, with no caller to return it to: a retry after a rate
+//! limit, a cassette that cannot be written, a line a plugin logged, or
+//! `--debug` output. The front end sets a handler that shows them as it
+//! sees fit; without one, warnings only go to the diagnostic log.
+
+use std::sync::OnceLock;
+
+use crate::diagnostics;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Notice<'a> {
+    Warning(&'a str),
+    /// A line a WASM plugin logged.
+    PluginLog {
+        plugin: &'a str,
+        line: &'a str,
+    },
+    /// A labelled block of debug output, such as a request body.
+    Debug {
+        label: &'a str,
+        body: &'a str,
+    },
+}
+
+static HANDLER: OnceLock<fn(Notice<'_>)> = OnceLock::new();
+
+/// Sets what is done with notices, once for the process.
+pub fn set_handler(handler: fn(Notice<'_>)) {
+    let _ = HANDLER.set(handler);
+}
+
+pub fn send(notice: Notice<'_>) {
+    if let Notice::Warning(message) = notice {
+        diagnostics::log(&format!("warning {}", message));
+    }
+    if let Some(handler) = HANDLER.get() {
+        handler(notice);
+    }
+}
+
+pub fn warn(message: &str) {
+    send(Notice::Warning(message));
+}
+
+pub fn debug(label: &str, body: &str) {
+    send(Notice::Debug { label, body });
+}
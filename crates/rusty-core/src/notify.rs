@@ -0,0 +1,145 @@
+//! Desktop notifications, sent when a slow turn finishes while the user is
+//! looking at another window, and sounds played when any turn finishes.
+
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Shows a desktop notification: `osascript` on macOS, a PowerShell toast
+/// on Windows, and `notify-send` elsewhere.
+pub fn send(title: &str, body: &str) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command
+            .args(["-e", "on run argv"])
+            .args([
+                "-e",
+                "display notification (item 2 of argv) with title (item 1 of argv)",
+            ])
+            .args(["-e", "end run", title, body]);
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("powershell");
+        command
+            .args(["-NoProfile", "-Command", WINDOWS_TOAST])
+            .env("RUSTY_NOTIFY_TITLE", title)
+            .env("RUSTY_NOTIFY_BODY", body);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=Rusty", title, body]);
+        command
+    };
+    let output = command
+        .output()
+        .map_err(|e| format!("Could not send a notification: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Could not send a notification: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Builds a two-line toast from the title and body in the environment, so
+/// neither needs quoting for PowerShell.
+const WINDOWS_TOAST: &str = "\
+[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+$toast = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+$text = $toast.GetElementsByTagName('text'); \
+$text.Item(0).AppendChild($toast.CreateTextNode($env:RUSTY_NOTIFY_TITLE)) > $null; \
+$text.Item(1).AppendChild($toast.CreateTextNode($env:RUSTY_NOTIFY_BODY)) > $null; \
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('Rusty').Show([Windows.UI.Notifications.ToastNotification]::new($toast))";
+
+/// Starts playing the sound file at `path` and returns without waiting for
+/// it: `afplay` on macOS, PowerShell's `SoundPlayer` (WAV only) on Windows,
+/// and `paplay`, `pw-play` or `aplay` elsewhere.
+pub fn play_sound(path: &str) -> Result<(), String> {
+    let path = match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    };
+    if !path.is_file() {
+        return Err(format!("Sound file {} not found", path.display()));
+    }
+    let players: &[&[&str]] = if cfg!(target_os = "macos") {
+        &[&["afplay"]]
+    } else if cfg!(windows) {
+        &[&[
+            "powershell",
+            "-NoProfile",
+            "-Command",
+            "(New-Object Media.SoundPlayer $env:RUSTY_SOUND).PlaySync()",
+        ]]
+    } else {
+        &[&["paplay"], &["pw-play"], &["aplay", "-q"]]
+    };
+    for player in players {
+        let mut command = Command::new(player[0]);
+        command
+            .args(&player[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if cfg!(windows) {
+            command.env("RUSTY_SOUND", &path);
+        } else {
+            command.arg(&path);
+        }
+        // Not installed; try the next one.
+        let Ok(mut child) = command.spawn() else {
+            continue;
+        };
+        // Reap the player once it is done, without holding up the chat.
+        std::thread::spawn(move || child.wait());
+        return Ok(());
+    }
+    Err(format!(
+        "No sound player found; install one of: {}",
+        players
+            .iter()
+            .map(|player| player[0])
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+/// Whether the terminal running Rusty is the focused window, or `None` when
+/// that cannot be told. macOS compares the frontmost application with the
+/// terminal named by `TERM_PROGRAM`; X11 compares the active window with
+/// the `WINDOWID` the terminal exports.
+pub fn terminal_focused() -> Option<bool> {
+    if cfg!(target_os = "macos") {
+        let app = match std::env::var("TERM_PROGRAM").ok()?.as_str() {
+            "Apple_Terminal" => "Terminal",
+            "iTerm.app" => "iTerm2",
+            "vscode" => "Code",
+            "WezTerm" => "wezterm-gui",
+            "ghostty" => "ghostty",
+            _ => return None,
+        };
+        let frontmost = output(
+            "osascript",
+            &[
+                "-e",
+                "tell application \"System Events\" to get name of first application process whose frontmost is true",
+            ],
+        )?;
+        Some(frontmost.eq_ignore_ascii_case(app))
+    } else if cfg!(windows) {
+        None
+    } else {
+        let window: u64 = std::env::var("WINDOWID").ok()?.trim().parse().ok()?;
+        let active: u64 = output("xdotool", &["getactivewindow"])?.parse().ok()?;
+        Some(window == active)
+    }
+}
+
+/// The trimmed standard output of a command that succeeded.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,188 @@
+//! Text from images, for the `read_image` tool and `@image` attachments.
+//! The local `tesseract` command is tried first; when it is missing or
+//! finds nothing, a vision model served by an OpenAI-compatible API reads
+//! the image instead.
+
+use std::path::Path;
+
+use base64::Engine;
+
+use crate::config::OcrConfig;
+
+/// Largest image sent to the vision model, before base64 encoding.
+const MAX_VISION_BYTES: u64 = 3 * 1024 * 1024;
+
+const VISION_PROMPT: &str = "Transcribe all the text in this image exactly as written, \
+keeping line breaks and the reading order. For code or tables, keep their layout. Reply with \
+the text only; if there is no text, reply with a one-sentence description of the image instead.";
+
+/// Reads text out of images.
+#[derive(Clone)]
+pub struct Ocr {
+    languages: String,
+    vision: Option<VisionModel>,
+}
+
+/// A chat model that accepts images, used when tesseract cannot help.
+#[derive(Clone)]
+struct VisionModel {
+    url: String,
+    api_key: String,
+    model: String,
+    client: reqwest::Client,
+}
+
+impl Default for Ocr {
+    fn default() -> Self {
+        Self::from_config(&OcrConfig::default())
+    }
+}
+
+impl Ocr {
+    pub fn from_config(config: &OcrConfig) -> Self {
+        let vision = config
+            .vision_model
+            .as_deref()
+            .filter(|model| !model.is_empty())
+            .map(|model| VisionModel {
+                url: format!("{}/chat/completions", config.base_url.trim_end_matches('/')),
+                // `GROQ_API_KEY` may list several keys; any one will do.
+                api_key: std::env::var(&config.api_key_env)
+                    .unwrap_or_default()
+                    .split(',')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+                model: model.to_string(),
+                client: reqwest::Client::new(),
+            });
+        Self {
+            languages: config.languages.clone(),
+            vision,
+        }
+    }
+
+    /// The text in the image at `path`.
+    pub async fn image_text(&self, path: &Path) -> Result<String, String> {
+        if !path.is_file() {
+            return Err(format!("Cannot read {}: no such file", path.display()));
+        }
+        let local = self.tesseract(path).await;
+        if let Ok(text) = &local {
+            if !text.trim().is_empty() {
+                return Ok(text.trim().to_string());
+            }
+        }
+        match (&self.vision, local) {
+            (Some(vision), local) => vision.read(path).await.map_err(|e| match local {
+                Err(tesseract) => format!("{}; {}", tesseract, e),
+                Ok(_) => e,
+            }),
+            (None, Ok(_)) => Err(format!("No text found in {}", path.display())),
+            (None, Err(e)) => Err(e),
+        }
+    }
+
+    async fn tesseract(&self, path: &Path) -> Result<String, String> {
+        let output = tokio::process::Command::new("tesseract")
+            .arg(path)
+            .arg("stdout")
+            .args(["-l", &self.languages])
+            .output()
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => "tesseract is not installed".to_string(),
+                _ => format!("Failed to run tesseract: {}", e),
+            })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("tesseract failed: {}", stderr.trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl VisionModel {
+    async fn read(&self, path: &Path) -> Result<String, String> {
+        let size = std::fs::metadata(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?
+            .len();
+        if size > MAX_VISION_BYTES {
+            return Err(format!(
+                "{} is too large for the vision model ({} MB at most)",
+                path.display(),
+                MAX_VISION_BYTES / 1024 / 1024
+            ));
+        }
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let data_url = format!(
+            "data:{};base64,{}",
+            mime_type(path),
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        );
+        let body = serde_json::json!({
+            "model": self.model,
+            "temperature": 0,
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": VISION_PROMPT },
+                    { "type": "image_url", "image_url": { "url": data_url } }
+                ]
+            }]
+        });
+
+        let mut request = self.client.post(&self.url).json(&body);
+        if !self.api_key.is_empty() {
+            request = request.bearer_auth(&self.api_key);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Vision request failed: {}", e))?;
+        let status = response.status();
+        let json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid vision response: {}", e))?;
+        if !status.is_success() {
+            return Err(format!(
+                "Vision model returned {}: {}",
+                status,
+                json["error"]["message"].as_str().unwrap_or("unknown error")
+            ));
+        }
+        json["choices"][0]["message"]["content"]
+            .as_str()
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+            .ok_or_else(|| "The vision model returned no text".to_string())
+    }
+}
+
+/// Whether `path` names an image, judging by its extension.
+pub fn is_image(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+        ["png", "jpg", "jpeg", "gif", "bmp", "tif", "tiff", "webp"]
+            .iter()
+            .any(|ext| e.eq_ignore_ascii_case(ext))
+    })
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "webp" => "image/webp",
+        _ => "image/png",
+    }
+}
@@ -0,0 +1,126 @@
+//! Well-known locations for Rusty's configuration and data files.
+
+use std::path::PathBuf;
+
+/// The configuration directory, `~/.config/rusty` by default.
+///
+/// `RUSTY_CONFIG_DIR` overrides the location outright; otherwise
+/// `XDG_CONFIG_HOME` is honored, falling back to `%APPDATA%` on Windows.
+pub fn config_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("RUSTY_CONFIG_DIR") {
+        return PathBuf::from(dir);
+    }
+    if let Ok(dir) = std::env::var("XDG_CONFIG_HOME") {
+        return PathBuf::from(dir).join("rusty");
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        return PathBuf::from(home).join(".config").join("rusty");
+    }
+    if let Ok(appdata) = std::env::var("APPDATA") {
+        return PathBuf::from(appdata).join("rusty");
+    }
+    PathBuf::from(".rusty")
+}
+
+/// Directory scanned for external tool plugins.
+pub fn plugins_dir() -> PathBuf {
+    config_dir().join("plugins")
+}
+
+/// Directory scanned for Rhai hook scripts.
+pub fn scripts_dir() -> PathBuf {
+    config_dir().join("scripts")
+}
+
+/// Directory holding prompt templates.
+pub fn prompts_dir() -> PathBuf {
+    config_dir().join("prompts")
+}
+
+/// Directory holding message catalogs that add to or replace the built-in
+/// ones, named by language, such as `fr.toml`.
+pub fn locales_dir() -> PathBuf {
+    config_dir().join("locales")
+}
+
+/// File holding prompt history across sessions.
+pub fn history_file() -> PathBuf {
+    config_dir().join("history")
+}
+
+/// Instructions sent with every conversation, before those of the
+/// project's `RUSTY.md` files.
+pub fn instructions_file() -> PathBuf {
+    config_dir().join("RUSTY.md")
+}
+
+/// Facts remembered across sessions.
+pub fn memory_file() -> PathBuf {
+    config_dir().join("memory")
+}
+
+/// The values behind the placeholders `[pii]` gave out.
+pub fn pii_file() -> PathBuf {
+    config_dir().join("pii")
+}
+
+/// Chunks and embeddings of indexed local documents, for the JSON store.
+pub fn index_file() -> PathBuf {
+    config_dir().join("index.json")
+}
+
+/// The same for the SQLite store.
+pub fn index_db() -> PathBuf {
+    config_dir().join("index.sqlite")
+}
+
+/// Pages read by `/crawl`, a directory per site, kept for the index.
+pub fn crawls_dir() -> PathBuf {
+    config_dir().join("crawls")
+}
+
+/// What is remembered about each project, such as its model.
+pub fn projects_file() -> PathBuf {
+    config_dir().join("projects.json")
+}
+
+/// Tokens and dollars spent, by day and model.
+pub fn usage_db() -> PathBuf {
+    config_dir().join("usage.sqlite")
+}
+
+/// Answers rated with `/good` and `/bad`.
+pub fn feedback_db() -> PathBuf {
+    config_dir().join("feedback.sqlite")
+}
+
+/// Whether usage statistics are collected, and those not sent yet.
+pub fn telemetry_file() -> PathBuf {
+    config_dir().join("telemetry.json")
+}
+
+/// Directory holding saved sessions.
+pub fn sessions_dir() -> PathBuf {
+    config_dir().join("sessions")
+}
+
+/// API keys kept by `rusty setup`, read wherever Rusty is started. A
+/// `.env` in the current directory takes precedence.
+pub fn env_file() -> PathBuf {
+    config_dir().join(".env")
+}
+
+/// Answers of scheduled prompts that are not kept in a session.
+pub fn schedule_log() -> PathBuf {
+    config_dir().join("schedule.md")
+}
+
+/// Requests of tools refused by the `[http] egress` allowlist.
+pub fn egress_log() -> PathBuf {
+    config_dir().join("egress.log")
+}
+
+/// Salt and passphrase check for encrypted storage.
+pub fn key_file() -> PathBuf {
+    config_dir().join("storage.key")
+}
@@ -0,0 +1,89 @@
+//! Local text extraction from PDF files, for the `read_pdf` tool and `@file`
+//! attachments.
+
+use std::path::Path;
+
+/// Characters of extracted text returned at once; longer documents are cut
+/// at a page boundary with a note on how to read on.
+const MAX_CHARS: usize = 60_000;
+
+/// The text of `path`, page by page, optionally limited to a 1-based
+/// inclusive page range such as `"3-5"` or `"7"`.
+pub fn extract_text(path: &Path, pages: Option<&str>) -> Result<String, String> {
+    let all = read_pages(path)?;
+    let total = all.len();
+    let (first, last) = match pages {
+        Some(range) => parse_range(range, total)?,
+        None => (1, total),
+    };
+
+    let mut text = String::new();
+    let mut shown = first;
+    for (number, page) in all.iter().enumerate().take(last).skip(first - 1) {
+        let page = format!("--- Page {} ---\n{}\n\n", number + 1, page.trim());
+        if !text.is_empty() && text.chars().count() + page.chars().count() > MAX_CHARS {
+            text.push_str(&format!(
+                "[Truncated after page {} of {}. Request pages {}-{} to read on.]\n",
+                shown,
+                total,
+                shown + 1,
+                last
+            ));
+            return Ok(text);
+        }
+        text.push_str(&page);
+        shown = number + 1;
+    }
+    if text.trim().is_empty() {
+        return Err(format!(
+            "{} has no extractable text (it may be a scanned image)",
+            path.display()
+        ));
+    }
+    Ok(text)
+}
+
+fn read_pages(path: &Path) -> Result<Vec<String>, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    // The extractor panics on some malformed files; report those as errors.
+    let result = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(&bytes));
+    match result {
+        Ok(Ok(pages)) => Ok(pages),
+        Ok(Err(e)) => Err(format!("Cannot read PDF {}: {}", path.display(), e)),
+        Err(_) => Err(format!(
+            "Cannot read PDF {}: unsupported file",
+            path.display()
+        )),
+    }
+}
+
+fn parse_range(range: &str, total: usize) -> Result<(usize, usize), String> {
+    let invalid = || {
+        format!(
+            "Invalid page range {:?}; the document has {} pages",
+            range, total
+        )
+    };
+    let (first, last) = match range.split_once('-') {
+        Some((first, last)) => (first.trim(), last.trim()),
+        None => (range.trim(), range.trim()),
+    };
+    let first: usize = first.parse().map_err(|_| invalid())?;
+    let last: usize = if last.is_empty() {
+        total
+    } else {
+        last.parse().map_err(|_| invalid())?
+    };
+    if first == 0 || first > last || first > total {
+        return Err(invalid());
+    }
+    Ok((first, last.min(total)))
+}
+
+/// Whether `path` names a PDF, judging by its extension.
+pub fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"))
+}
@@ -0,0 +1,275 @@
+//! Pseudonyms for personal data. With `[pii]` on, email addresses, phone
+//! numbers and the names listed in the configuration are replaced by
+//! placeholders such as `[EMAIL_1]` in prompts and tool outputs before the
+//! model sees them. Which placeholder stands for what stays on this
+//! machine, so that answers can be shown with the real values again.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::{Captures, Regex};
+
+use crate::config::PiiConfig;
+use crate::encryption::{self, Cipher};
+use crate::notice;
+
+/// A placeholder that may still be cut off at the end of a streamed
+/// piece is held back while it is shorter than this.
+const LONGEST_PLACEHOLDER: usize = 16;
+
+pub struct Scrubber {
+    enabled: bool,
+    email: Option<Regex>,
+    phone: Option<Regex>,
+    names: Option<Regex>,
+    placeholder: Regex,
+    /// Each placeholder given out, with the value it stands for.
+    mapping: Vec<(String, String)>,
+    /// Where the mapping is kept; `None` keeps it for this run only.
+    path: Option<PathBuf>,
+    cipher: Option<Cipher>,
+}
+
+impl Scrubber {
+    /// A scrubber whose mapping lasts for this run only.
+    pub fn new(config: &PiiConfig) -> Self {
+        let mut names: Vec<&str> = config
+            .names
+            .iter()
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .collect();
+        // "Ann Lee" is replaced whole before "Ann" alone is looked for.
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+        let names = (!names.is_empty()).then(|| {
+            let names: Vec<String> = names.iter().map(|name| regex::escape(name)).collect();
+            Regex::new(&format!(r"(?i)\b(?:{})\b", names.join("|"))).unwrap()
+        });
+        Self {
+            enabled: config.enabled,
+            email: config.emails.then(|| {
+                Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b").unwrap()
+            }),
+            phone: config.phones.then(|| {
+                Regex::new(
+                    r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{1,4}\)[ .-]?)?\d{2,4}(?:[ .-]?\d{2,4}){2,4}",
+                )
+                .unwrap()
+            }),
+            names,
+            placeholder: Regex::new(r"\[(?:EMAIL|PHONE|NAME)_\d+\]").unwrap(),
+            mapping: Vec::new(),
+            path: None,
+            cipher: None,
+        }
+    }
+
+    /// A scrubber that keeps its mapping in the file at `path`, which need
+    /// not exist yet, so that placeholders in saved sessions can still be
+    /// shown with their values.
+    pub fn load(path: &Path, config: &PiiConfig, cipher: Option<Cipher>) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let mut scrubber = Self::new(config);
+        if path.exists() {
+            match encryption::read_text(path, cipher.as_ref()) {
+                Ok(text) => {
+                    scrubber.mapping = text
+                        .lines()
+                        .filter_map(|line| line.split_once('\t'))
+                        .map(|(placeholder, value)| (placeholder.to_string(), value.to_string()))
+                        .collect()
+                }
+                Err(e) => warnings.push(e),
+            }
+        }
+        scrubber.path = Some(path.to_path_buf());
+        scrubber.cipher = cipher;
+        (scrubber, warnings)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// `text` with its personal data replaced by placeholders, the same
+    /// value always by the same one. Off, it is `text` as it is.
+    pub fn scrub(&mut self, text: &str) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+        let known = self.mapping.len();
+        let mut text = text.to_string();
+        let kinds = [
+            ("EMAIL", &self.email),
+            ("PHONE", &self.phone),
+            ("NAME", &self.names),
+        ];
+        for (kind, regex) in kinds {
+            let Some(regex) = regex else {
+                continue;
+            };
+            text = regex
+                .replace_all(&text, |captures: &Captures| {
+                    let value = &captures[0];
+                    if kind == "PHONE" && !is_phone(value) {
+                        return value.to_string();
+                    }
+                    pseudonym(&mut self.mapping, kind, value)
+                })
+                .into_owned();
+        }
+        // Kept for this run even when it cannot be saved.
+        if self.mapping.len() > known {
+            if let Err(e) = self.save() {
+                notice::warn(&e);
+            }
+        }
+        text
+    }
+
+    /// `text` with every placeholder given out replaced by its value again.
+    pub fn restore(&self, text: &str) -> String {
+        if self.mapping.is_empty() {
+            return text.to_string();
+        }
+        self.placeholder
+            .replace_all(text, |captures: &Captures| {
+                let placeholder = &captures[0];
+                self.mapping
+                    .iter()
+                    .find(|(known, _)| known == placeholder)
+                    .map_or(placeholder, |(_, value)| value.as_str())
+                    .to_string()
+            })
+            .into_owned()
+    }
+
+    /// Restores a streamed `delta`, holding back in `pending` the end of a
+    /// placeholder that the next piece finishes. What is left in `pending`
+    /// when the stream ends is shown with `restore`.
+    pub fn restore_streamed(&self, pending: &mut String, delta: &str) -> String {
+        if self.mapping.is_empty() {
+            return delta.to_string();
+        }
+        pending.push_str(delta);
+        let cut = match pending.rfind('[') {
+            Some(start)
+                if !pending[start..].contains(']')
+                    && pending.len() - start < LONGEST_PLACEHOLDER =>
+            {
+                start
+            }
+            _ => pending.len(),
+        };
+        let ready = self.restore(&pending[..cut]);
+        pending.replace_range(..cut, "");
+        ready
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let text: String = self
+            .mapping
+            .iter()
+            .map(|(placeholder, value)| format!("{}\t{}\n", placeholder, value))
+            .collect();
+        encryption::write_text(path, &text, self.cipher.as_ref())
+    }
+}
+
+/// The placeholder for `value`, given out now when it has none yet.
+fn pseudonym(mapping: &mut Vec<(String, String)>, kind: &str, value: &str) -> String {
+    let prefix = format!("[{}_", kind);
+    let same = |known: &str| match kind {
+        "PHONE" => digits(known) == digits(value),
+        _ => known.eq_ignore_ascii_case(value),
+    };
+    if let Some((placeholder, _)) = mapping
+        .iter()
+        .find(|(placeholder, known)| placeholder.starts_with(&prefix) && same(known))
+    {
+        return placeholder.clone();
+    }
+    let number = mapping
+        .iter()
+        .filter(|(placeholder, _)| placeholder.starts_with(&prefix))
+        .count()
+        + 1;
+    let placeholder = format!("{}{}]", prefix, number);
+    mapping.push((placeholder.clone(), value.to_string()));
+    placeholder
+}
+
+fn digits(text: &str) -> String {
+    text.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// Phone numbers have 9 to 15 digits, which leaves out times and most
+/// other numbers, and do not start with an ISO date.
+fn is_phone(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let date = bytes.len() >= 10 && bytes[4] == b'-' && bytes[7] == b'-';
+    !date && (9..=15).contains(&digits(text).len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrubber(names: &[&str]) -> Scrubber {
+        Scrubber::new(&PiiConfig {
+            enabled: true,
+            names: names.iter().map(|name| name.to_string()).collect(),
+            ..PiiConfig::default()
+        })
+    }
+
+    #[test]
+    fn replaces_emails_phones_and_names_and_restores_them() {
+        let mut pii = scrubber(&["Ann Lee"]);
+        let text = "Ann Lee (ann@example.com, +1 555 123 4567) wrote on 2024-05-01 at 10:30.";
+        let scrubbed = pii.scrub(text);
+        assert_eq!(
+            scrubbed,
+            "[NAME_1] ([EMAIL_1], [PHONE_1]) wrote on 2024-05-01 at 10:30."
+        );
+        assert_eq!(pii.restore(&scrubbed), text);
+    }
+
+    #[test]
+    fn gives_the_same_value_the_same_placeholder() {
+        let mut pii = scrubber(&["Ann", "Bob"]);
+        assert_eq!(pii.scrub("Ann met Bob"), "[NAME_1] met [NAME_2]");
+        assert_eq!(pii.scrub("Bob and ANN"), "[NAME_2] and [NAME_1]");
+        assert_eq!(
+            pii.scrub("call 555-123-4567"),
+            pii.scrub("call 555 123 4567")
+        );
+    }
+
+    #[test]
+    fn restores_placeholders_split_across_streamed_pieces() {
+        let mut pii = scrubber(&[]);
+        pii.scrub("mail ann@example.com");
+        let mut pending = String::new();
+        let mut shown = String::new();
+        for delta in ["Write to [EM", "AIL_1] today", " [unrelated]"] {
+            shown.push_str(&pii.restore_streamed(&mut pending, delta));
+        }
+        shown.push_str(&pii.restore(&pending));
+        assert_eq!(shown, "Write to ann@example.com today [unrelated]");
+    }
+
+    #[test]
+    fn leaves_text_alone_when_off() {
+        let mut pii = Scrubber::new(&PiiConfig::default());
+        let text = "ann@example.com";
+        assert_eq!(pii.scrub(text), text);
+    }
+}
@@ -0,0 +1,322 @@
+//! Tools provided by external executables.
+//!
+//! Every executable in the plugins directory speaks a small JSON protocol over
+//! stdio. Rusty starts the process, writes one request object to its stdin,
+//! closes stdin, and reads one response object from its stdout:
+//!
+//! - `{"type": "describe"}` must be answered with
+//!   `{"tools": [{"name": ..., "description": ..., "parameters": {...}}]}`,
+//!   where `parameters` is a JSON schema like the built-in tools use.
+//! - `{"type": "call", "tool": "<name>", "arguments": {...}}` must be answered
+//!   with `{"content": "..."}` on success or `{"error": "..."}` on failure.
+//!
+//! `.wasm` files in the same directory are loaded as sandboxed plugins speaking
+//! the same messages through exported functions instead of stdio (see the
+//! `wasm` module); they require the `wasm` feature.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+#[cfg(feature = "wasm")]
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+
+use crate::message::{ToolDefinition, ToolFunction};
+use crate::tools::{Tool, ToolContext, ToolFuture, ToolSteps};
+#[cfg(feature = "wasm")]
+use crate::wasm::WasmPlugin;
+
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(5);
+const CALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+struct DescribeResponse {
+    tools: Vec<PluginToolSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginToolSpec {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default = "empty_schema")]
+    parameters: serde_json::Value,
+}
+
+fn empty_schema() -> serde_json::Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+#[derive(Debug, Deserialize)]
+struct CallResponse {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Backend {
+    Executable,
+    #[cfg(feature = "wasm")]
+    Wasm(Arc<WasmPlugin>),
+}
+
+/// One tool registered by a plugin.
+#[derive(Debug, Clone)]
+pub struct PluginTool {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+    /// The executable or `.wasm` file providing the tool.
+    pub source: PathBuf,
+    backend: Backend,
+}
+
+impl PluginTool {
+    pub fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            r#type: "function".to_string(),
+            function: ToolFunction {
+                name: self.name.clone(),
+                description: self.description.clone(),
+                parameters: self.parameters.clone(),
+            },
+        }
+    }
+
+    pub async fn call(&self, arguments: &serde_json::Value) -> Result<String, String> {
+        let request = serde_json::json!({
+            "type": "call",
+            "tool": self.name,
+            "arguments": arguments,
+        });
+        let output = match &self.backend {
+            Backend::Executable => exchange(&self.source, &request, CALL_TIMEOUT).await?,
+            #[cfg(feature = "wasm")]
+            Backend::Wasm(plugin) => {
+                let plugin = Arc::clone(plugin);
+                let request = request.to_string();
+                tokio::task::spawn_blocking(move || plugin.call(&request))
+                    .await
+                    .map_err(|e| e.to_string())??
+            }
+        };
+        let response: CallResponse = serde_json::from_str(&output)
+            .map_err(|e| format!("Invalid response from plugin {}: {}", self.name, e))?;
+
+        match (response.content, response.error) {
+            (_, Some(error)) => Err(error),
+            (Some(content), None) => Ok(content),
+            (None, None) => Ok(String::new()),
+        }
+    }
+}
+
+impl Tool for PluginTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        PluginTool::definition(self)
+    }
+
+    /// Executables do; `.wasm` plugins run sandboxed.
+    fn runs_programs(&self) -> bool {
+        matches!(self.backend, Backend::Executable)
+    }
+
+    fn steps(&self, _arguments: &serde_json::Value) -> ToolSteps {
+        ToolSteps {
+            step: format!("Running plugin tool '{}'", self.name),
+            done: Some("Reasoning with tool output"),
+            failure: "Plugin tool failed",
+            fallback: Some("Error: The tool failed. Please answer without it."),
+        }
+    }
+
+    fn execute<'a>(
+        &'a self,
+        arguments: &'a serde_json::Value,
+        _context: ToolContext<'a>,
+    ) -> ToolFuture<'a> {
+        Box::pin(self.call(arguments))
+    }
+}
+
+/// The set of plugin tools discovered at startup.
+#[derive(Debug, Default)]
+pub struct PluginHost {
+    tools: Vec<PluginTool>,
+}
+
+impl PluginHost {
+    /// Asks every plugin in `dir` to describe its tools. Plugins that fail
+    /// to answer, or that reuse a name in `reserved`, are skipped and reported
+    /// in the returned warnings. Describing an executable runs it, so those
+    /// are skipped unless `run_executables` is set, as in a trusted
+    /// workspace; `.wasm` plugins are sandboxed and always loaded.
+    pub async fn load(dir: &Path, reserved: &[&str], run_executables: bool) -> (Self, Vec<String>) {
+        let mut host = Self::default();
+        let mut warnings = Vec::new();
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return (host, warnings);
+        };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        paths.sort();
+
+        let describe = serde_json::json!({ "type": "describe" });
+        for path in paths {
+            let (backend, described) = if path.extension().is_some_and(|e| e == "wasm") {
+                match describe_wasm(&path).await {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        warnings.push(format!("Plugin {} failed to load: {}", path.display(), e));
+                        continue;
+                    }
+                }
+            } else if is_executable(&path) {
+                if !run_executables {
+                    continue;
+                }
+                (
+                    Backend::Executable,
+                    exchange(&path, &describe, DESCRIBE_TIMEOUT).await,
+                )
+            } else {
+                continue;
+            };
+
+            let described = described.and_then(|out| {
+                serde_json::from_str::<DescribeResponse>(&out).map_err(|e| e.to_string())
+            });
+
+            match described {
+                Ok(response) => {
+                    for spec in response.tools {
+                        if reserved.contains(&spec.name.as_str()) || host.find(&spec.name).is_some()
+                        {
+                            warnings.push(format!(
+                                "Plugin {} redefines tool `{}`; skipping it.",
+                                path.display(),
+                                spec.name
+                            ));
+                            continue;
+                        }
+                        host.tools.push(PluginTool {
+                            name: spec.name,
+                            description: spec.description,
+                            parameters: spec.parameters,
+                            source: path.clone(),
+                            backend: backend.clone(),
+                        });
+                    }
+                }
+                Err(e) => warnings.push(format!(
+                    "Plugin {} failed to describe itself: {}",
+                    path.display(),
+                    e
+                )),
+            }
+        }
+
+        (host, warnings)
+    }
+
+    /// Whether `dir` holds executable plugins, which are only loaded once
+    /// the workspace is trusted.
+    pub fn has_executables(dir: &Path) -> bool {
+        std::fs::read_dir(dir).is_ok_and(|entries| {
+            entries.filter_map(|e| e.ok()).any(|e| {
+                is_executable(&e.path()) && e.path().extension().is_none_or(|e| e != "wasm")
+            })
+        })
+    }
+
+    pub fn tools(&self) -> &[PluginTool] {
+        &self.tools
+    }
+
+    pub fn find(&self, name: &str) -> Option<&PluginTool> {
+        self.tools.iter().find(|t| t.name == name)
+    }
+}
+
+#[cfg(feature = "wasm")]
+async fn describe_wasm(path: &Path) -> Result<(Backend, Result<String, String>), String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let plugin = Arc::new(WasmPlugin::load(&path)?);
+        let described = plugin.describe();
+        Ok((Backend::Wasm(plugin), described))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(not(feature = "wasm"))]
+async fn describe_wasm(_path: &Path) -> Result<(Backend, Result<String, String>), String> {
+    Err("Rusty was built without the `wasm` feature".to_string())
+}
+
+/// Writes `request` to the executable's stdin and returns what it printed on stdout.
+async fn exchange(
+    executable: &Path,
+    request: &serde_json::Value,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut child = Command::new(executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    let run = async {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(request.to_string().as_bytes())
+                .await
+                .map_err(|e| format!("failed to write request: {}", e))?;
+        }
+        let mut stdout = String::new();
+        if let Some(mut out) = child.stdout.take() {
+            out.read_to_string(&mut stdout)
+                .await
+                .map_err(|e| format!("failed to read response: {}", e))?;
+        }
+        let status = child.wait().await.map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("exited with {}", status));
+        }
+        Ok(stdout)
+    };
+
+    tokio::time::timeout(timeout, run)
+        .await
+        .map_err(|_| format!("timed out after {}s", timeout.as_secs()))?
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| matches!(e.to_ascii_lowercase().as_str(), "exe" | "bat" | "cmd"))
+            .unwrap_or(false)
+}
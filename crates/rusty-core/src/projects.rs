@@ -0,0 +1,73 @@
+//! What is remembered about each project, the Git repository Rusty is
+//! started in or else the directory: for now, the model last chosen there.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::repo;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Project {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+pub struct Projects {
+    /// Projects by their directory.
+    projects: BTreeMap<String, Project>,
+    path: PathBuf,
+    /// The project of the working directory.
+    current: Option<String>,
+}
+
+impl Projects {
+    /// Reads the projects file at `path`, which need not exist yet; one
+    /// that cannot be read is started afresh.
+    pub fn load(path: &Path) -> Self {
+        let projects = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        let current = repo::top_level()
+            .or_else(|| std::env::current_dir().ok())
+            .map(|dir| dir.display().to_string());
+        Self {
+            projects,
+            path: path.to_path_buf(),
+            current,
+        }
+    }
+
+    /// The model last chosen in this project.
+    pub fn model(&self) -> Option<&str> {
+        let current = self.current.as_ref()?;
+        self.projects.get(current)?.model.as_deref()
+    }
+
+    /// Remembers `model` for this project, rewriting the file when it
+    /// changes.
+    pub fn set_model(&mut self, model: &str) -> Result<(), String> {
+        let Some(current) = &self.current else {
+            return Ok(());
+        };
+        let project = self.projects.entry(current.clone()).or_default();
+        if project.model.as_deref() == Some(model) {
+            return Ok(());
+        }
+        project.model = Some(model.to_string());
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(&self.projects).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
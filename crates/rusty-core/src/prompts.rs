@@ -0,0 +1,259 @@
+//! Named prompt templates stored as `*.md` files in the prompts directory.
+//!
+//! A template's name is its file stem. The body may use these placeholders:
+//!
+//! - `{input}`: free text given after the template name
+//! - `{files}`: every `@path` attachment, rendered as fenced code blocks
+//!   (PDFs and images are converted to text)
+//!
+//! Input or attachments the template has no placeholder for are appended. An
+//! optional first line starting with `# ` describes the template in listings
+//! and is not sent.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ocr::{self, Ocr};
+use crate::pdf;
+
+/// How many files of the workspace the `@` finder offers, so that starting
+/// Rusty in a huge tree does not hold up the prompt.
+const MAX_WORKSPACE_FILES: usize = 20_000;
+
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub body: String,
+    pub path: PathBuf,
+}
+
+/// A file pulled into a prompt with `@path`.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub path: String,
+    pub content: String,
+}
+
+impl Attachment {
+    /// Renders the file as a labelled fenced block, tagged with its extension.
+    pub fn to_markdown(&self) -> String {
+        let path = Path::new(&self.path);
+        let lang = match path.extension().and_then(|e| e.to_str()) {
+            _ if pdf::is_pdf(path) || ocr::is_image(path) => "",
+            Some(ext) => ext,
+            None => "",
+        };
+        let mut content = self.content.clone();
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        format!("File: {}\n```{}\n{}```", self.path, lang, content)
+    }
+}
+
+impl PromptTemplate {
+    /// The `# ` header line, or the first line of the prompt when there is none.
+    pub fn description(&self) -> &str {
+        match self.header() {
+            Some(header) => header,
+            None => self.prompt().lines().next().unwrap_or("").trim(),
+        }
+    }
+
+    fn header(&self) -> Option<&str> {
+        self.body.lines().next()?.strip_prefix("# ").map(str::trim)
+    }
+
+    /// The template body without its header line.
+    pub fn prompt(&self) -> &str {
+        match self.header() {
+            Some(_) => self
+                .body
+                .split_once('\n')
+                .map(|(_, rest)| rest)
+                .unwrap_or(""),
+            None => &self.body,
+        }
+        .trim()
+    }
+
+    pub fn expand(&self, input: &str, attachments: &[Attachment]) -> String {
+        let files = attachments
+            .iter()
+            .map(|a| a.to_markdown())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let mut text = self.prompt().to_string();
+        if text.contains("{input}") {
+            text = text.replace("{input}", input);
+        } else if !input.is_empty() {
+            text = format!("{}\n\n{}", text, input);
+        }
+        if text.contains("{files}") {
+            text = text.replace("{files}", &files);
+        } else if !files.is_empty() {
+            text = format!("{}\n\n{}", text, files);
+        }
+        text
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PromptLibrary {
+    templates: Vec<PromptTemplate>,
+}
+
+impl PromptLibrary {
+    pub fn load(dir: &Path) -> Self {
+        let mut library = Self::default();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return library;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.extension().is_none_or(|e| e != "md") {
+                continue;
+            }
+            let (Some(name), Ok(body)) = (path.file_stem(), fs::read_to_string(&path)) else {
+                continue;
+            };
+            library.templates.push(PromptTemplate {
+                name: name.to_string_lossy().to_string(),
+                body,
+                path: path.clone(),
+            });
+        }
+        library.templates.sort_by(|a, b| a.name.cmp(&b.name));
+        library
+    }
+
+    pub fn templates(&self) -> &[PromptTemplate] {
+        &self.templates
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PromptTemplate> {
+        self.templates.iter().find(|t| t.name == name)
+    }
+}
+
+/// Splits `@path` mentions out of `text`, reading each file (and extracting
+/// the text of PDFs and images). Relative paths are looked for in the
+/// working directory, then in each of `dirs`. Returns the
+/// remaining text and the attachments, or an error naming an unreadable file.
+pub async fn extract_attachments(
+    text: &str,
+    ocr: &Ocr,
+    dirs: &[PathBuf],
+) -> Result<(String, Vec<Attachment>), String> {
+    let mut words = Vec::new();
+    let mut attachments = Vec::new();
+
+    for word in text.split_whitespace() {
+        match word.strip_prefix('@') {
+            Some(path) if !path.is_empty() => {
+                let resolved = resolve(path, dirs);
+                let path = resolved.to_str().unwrap_or(path);
+                let content = if pdf::is_pdf(Path::new(path)) {
+                    pdf::extract_text(Path::new(path), None)
+                        .map_err(|e| format!("Cannot attach {}: {}", path, e))?
+                } else if ocr::is_image(Path::new(path)) {
+                    ocr.image_text(Path::new(path))
+                        .await
+                        .map_err(|e| format!("Cannot attach {}: {}", path, e))?
+                } else {
+                    fs::read_to_string(path)
+                        .map_err(|e| format!("Cannot attach {}: {}", path, e))?
+                };
+                attachments.push(Attachment {
+                    path: path.to_string(),
+                    content,
+                });
+            }
+            _ => words.push(word),
+        }
+    }
+
+    Ok((words.join(" "), attachments))
+}
+
+/// Appends the files that `@path` mentions in a chat message name, as
+/// `/use` does, leaving the mentions in place. Mentions of anything that is
+/// not a file, such as `@alice`, are left alone. Returns the message and
+/// the paths attached.
+pub async fn attach_mentions(
+    text: &str,
+    ocr: &Ocr,
+    dirs: &[PathBuf],
+) -> Result<(String, Vec<String>), String> {
+    let mut paths = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(path) = word.strip_prefix('@') else {
+            continue;
+        };
+        // "Look at @src/main.rs, please": the comma is not part of it.
+        let path = [
+            path,
+            path.trim_end_matches([',', '.', ';', ':', '!', '?', ')']),
+        ]
+        .into_iter()
+        .find(|path| !path.is_empty() && resolve(path, dirs).is_file());
+        if let Some(path) = path.filter(|path| !paths.iter().any(|p: &String| p == path)) {
+            paths.push(path.to_string());
+        }
+    }
+    if paths.is_empty() {
+        return Ok((text.to_string(), paths));
+    }
+    let mentions = paths
+        .iter()
+        .map(|path| format!("@{}", path))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (_, attachments) = extract_attachments(&mentions, ocr, dirs).await?;
+    let files = attachments
+        .iter()
+        .map(Attachment::to_markdown)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok((format!("{}\n\n{}", text, files), paths))
+}
+
+/// The files `@` can attach: those under the working directory, then those
+/// under each of `dirs` as paths relative to it, leaving out hidden files
+/// and what `.gitignore` leaves out of Git.
+pub fn workspace_files(dirs: &[PathBuf]) -> Vec<String> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for root in std::iter::once(&cwd).chain(dirs) {
+        for entry in ignore::WalkBuilder::new(root).build().flatten() {
+            if files.len() >= MAX_WORKSPACE_FILES {
+                return files;
+            }
+            if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+                continue;
+            }
+            let path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            // Mentions end at whitespace, so such paths could not be sent.
+            let path = path.to_string_lossy().replace('\\', "/");
+            if !path.contains(char::is_whitespace) && seen.insert(path.clone()) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// Where a mentioned `path` is: as given when it exists from the working
+/// directory or is absolute, or else in the first of `dirs` that has it.
+fn resolve(path: &str, dirs: &[PathBuf]) -> PathBuf {
+    let given = PathBuf::from(path);
+    if given.is_absolute() || given.exists() {
+        return given;
+    }
+    dirs.iter()
+        .map(|dir| dir.join(path))
+        .find(|candidate| candidate.exists())
+        .unwrap_or(given)
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::redact_key;
+use crate::config::{EmbeddingsConfig, RetryConfig};
+use crate::notice;
+use crate::rag;
+
+/// Inputs sent in one request; larger batches are split.
+const BATCH_SIZE: usize = 64;
+
+/// Turns text into vectors, either locally or through an embeddings API.
+#[derive(Clone)]
+pub enum Embedder {
+    /// Hashed word features computed in-process; see [`rag::embed_local`].
+    Local,
+    Api(EmbeddingsClient),
+}
+
+impl Embedder {
+    /// The API client when `[embeddings] model` is set, the local embedder
+    /// otherwise.
+    pub fn from_config(config: &EmbeddingsConfig) -> Self {
+        match &config.model {
+            Some(model) => {
+                let api_key = std::env::var(&config.api_key_env).unwrap_or_default();
+                let mut client = EmbeddingsClient::new(&config.base_url, &api_key, model);
+                client.retry = config.retry.clone();
+                Embedder::Api(client)
+            }
+            None => Embedder::Local,
+        }
+    }
+
+    /// Recorded in the document index, so vectors from different embedders
+    /// are never compared.
+    pub fn name(&self) -> String {
+        match self {
+            Embedder::Local => rag::LOCAL_EMBEDDER.to_string(),
+            Embedder::Api(client) => client.model.clone(),
+        }
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        if let Embedder::Api(client) = self {
+            client.debug = debug;
+        }
+    }
+
+    /// One vector per input, in order.
+    pub async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        match self {
+            Embedder::Local => Ok(inputs.iter().map(|text| rag::embed_local(text)).collect()),
+            Embedder::Api(client) => {
+                let mut vectors = Vec::with_capacity(inputs.len());
+                for batch in inputs.chunks(BATCH_SIZE) {
+                    vectors.extend(client.embed(batch).await?);
+                }
+                Ok(vectors)
+            }
+        }
+    }
+}
+
+/// A client for the OpenAI-compatible `/embeddings` endpoint offered by
+/// OpenAI, Groq, Ollama, LM Studio and others.
+#[derive(Clone)]
+pub struct EmbeddingsClient {
+    base_url: String,
+    api_key: String,
+    model: String,
+    client: reqwest::Client,
+    debug: bool,
+    retry: RetryConfig,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl EmbeddingsClient {
+    /// `base_url` is the API root, e.g. `https://api.openai.com/v1`. An empty
+    /// key sends no `Authorization` header, for local servers.
+    pub fn new(base_url: &str, api_key: &str, model: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            client: reqwest::Client::new(),
+            debug: false,
+            retry: RetryConfig::default(),
+        }
+    }
+
+    pub async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let url = format!("{}/embeddings", self.base_url);
+        let body = serde_json::json!({ "model": self.model, "input": inputs });
+        if self.debug {
+            notice::debug(
+                &format!(
+                    "POST {} (Authorization: Bearer {})",
+                    url,
+                    redact_key(&self.api_key)
+                ),
+                &serde_json::to_string_pretty(&body).unwrap_or_default(),
+            );
+        }
+
+        let mut retries = 0;
+        let response = loop {
+            let mut request = self.client.post(&url).json(&body);
+            if !self.api_key.is_empty() {
+                request = request.bearer_auth(&self.api_key);
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Embeddings request failed: {}", e))?;
+            if !self.retry.retries(response.status().as_u16()) || retries >= self.retry.max_retries
+            {
+                break response;
+            }
+            retries += 1;
+            let asked = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs);
+            tokio::time::sleep(self.retry.delay(retries, asked)).await;
+        };
+
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Embeddings request failed: {}", e))?;
+        if self.debug {
+            let preview: String = text.chars().take(1000).collect();
+            notice::debug(&format!("Response {}", status), &preview);
+        }
+        if !status.is_success() {
+            let message = serde_json::from_str::<serde_json::Value>(&text)
+                .ok()
+                .and_then(|v| v["error"]["message"].as_str().map(str::to_string))
+                .unwrap_or(text);
+            return Err(format!("Embeddings API returned {}: {}", status, message));
+        }
+
+        let mut parsed: EmbeddingsResponse = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid embeddings response: {}", e))?;
+        if parsed.data.len() != inputs.len() {
+            return Err(format!(
+                "Embeddings API returned {} vectors for {} inputs",
+                parsed.data.len(),
+                inputs.len()
+            ));
+        }
+        parsed.data.sort_by_key(|d| d.index);
+        Ok(parsed
+            .data
+            .into_iter()
+            .map(|d| {
+                let mut vector = d.embedding;
+                rag::normalize(&mut vector);
+                vector
+            })
+            .collect())
+    }
+}
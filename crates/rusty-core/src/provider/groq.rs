@@ -0,0 +1,489 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+
+use super::{redact_key, ChatOptions, Probe, ProviderError, Scheduler};
+use crate::config::RetryConfig;
+use crate::conversation::estimate_tokens;
+use crate::message::{ChatRequest, ChatResponse, Message, ToolChoice, ToolDefinition};
+use crate::notice;
+use crate::structured::ResponseFormat;
+
+pub const GROQ_API_URL: &str = "https://api.groq.com/openai/v1/chat/completions";
+const GROQ_MODELS_URL: &str = "https://api.groq.com/openai/v1/models";
+/// The host of the API, for the connectivity probe.
+pub(super) const GROQ_HOST: &str = "api.groq.com";
+
+/// The system prompt prepended to every request sent to Groq.
+pub const SYSTEM_PROMPT: &str = "You are a helpful AI assistant. Use the tools offered with each \
+request when they help: `brave_search` finds up-to-date information and `open` reads a page in full, \
+and others may look up papers, code, prices, feeds or documents, or calculate. Which tools are \
+offered changes from request to request; only call the ones offered with this one.";
+
+/// The longest a request is held back to stay under the rate limits. Past
+/// this, the request is sent anyway with a warning.
+const MAX_THROTTLE: Duration = Duration::from_secs(60);
+
+/// Alternatives asked for each token when log probabilities are.
+const TOP_LOGPROBS: u32 = 3;
+
+/// Remaining requests below which each request warns how many are left.
+const LOW_REQUESTS: u64 = 10;
+
+pub struct GroqApiClient {
+    /// One or more keys; requests move to the next key when one is
+    /// rate-limited.
+    api_keys: Vec<String>,
+    client: reqwest::Client,
+    debug: bool,
+    /// Spread requests over every key in turn, rather than staying on one
+    /// until it runs out.
+    round_robin: bool,
+    /// The key in use, or the next one in round-robin order.
+    current: AtomicUsize,
+    /// The rate limits reported by the last response for each key.
+    limits: Mutex<Vec<Option<RateLimits>>>,
+    /// The configured limits on concurrent and per-minute requests.
+    scheduler: Scheduler,
+    /// Which refused requests are sent again, how often and after how long.
+    retry: RetryConfig,
+}
+
+/// What Groq's `x-ratelimit-*` headers said about the account's limits:
+/// requests per day and tokens per minute.
+#[derive(Debug, Clone, Copy)]
+struct RateLimits {
+    remaining_requests: Option<u64>,
+    remaining_tokens: Option<u64>,
+    requests_reset: Option<Duration>,
+    tokens_reset: Option<Duration>,
+    seen: Instant,
+}
+
+impl RateLimits {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let limits = Self {
+            remaining_requests: header("x-ratelimit-remaining-requests")
+                .and_then(|v| v.parse().ok()),
+            remaining_tokens: header("x-ratelimit-remaining-tokens").and_then(|v| v.parse().ok()),
+            requests_reset: header("x-ratelimit-reset-requests").and_then(parse_reset),
+            tokens_reset: header("x-ratelimit-reset-tokens").and_then(parse_reset),
+            seen: Instant::now(),
+        };
+        (limits.remaining_requests.is_some() || limits.remaining_tokens.is_some()).then_some(limits)
+    }
+
+    /// How long until a request of about `tokens` tokens fits within the
+    /// limits, when it does not fit now.
+    fn wait_for(&self, tokens: u64) -> Option<Duration> {
+        let elapsed = self.seen.elapsed();
+        let mut wait = Duration::ZERO;
+        if self.remaining_requests == Some(0) {
+            if let Some(reset) = self.requests_reset {
+                wait = wait.max(reset.saturating_sub(elapsed));
+            }
+        }
+        if self
+            .remaining_tokens
+            .is_some_and(|remaining| remaining < tokens)
+        {
+            if let Some(reset) = self.tokens_reset {
+                wait = wait.max(reset.saturating_sub(elapsed));
+            }
+        }
+        (!wait.is_zero()).then_some(wait)
+    }
+}
+
+/// A reset time as Groq writes it, such as `7.66s`, `2m59.56s` or `120ms`.
+fn parse_reset(text: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let value: f64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        total += value
+            * match &rest[..unit_len] {
+                "h" => 3600.0,
+                "m" => 60.0,
+                "s" => 1.0,
+                "ms" => 0.001,
+                _ => return None,
+            };
+        rest = &rest[unit_len..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+impl GroqApiClient {
+    /// `api_key` may list several keys separated by commas.
+    pub fn new(api_key: String) -> Self {
+        let api_keys: Vec<String> = api_key
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self {
+            limits: Mutex::new(vec![None; api_keys.len()]),
+            api_keys,
+            client: reqwest::Client::new(),
+            debug: false,
+            round_robin: false,
+            current: AtomicUsize::new(0),
+            scheduler: Scheduler::default(),
+            retry: RetryConfig::default(),
+        }
+    }
+
+    pub fn set_retry(&mut self, retry: RetryConfig) {
+        self.retry = retry;
+    }
+
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) {
+        self.scheduler = scheduler;
+    }
+
+    pub fn set_round_robin(&mut self, round_robin: bool) {
+        self.round_robin = round_robin;
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
+
+    /// Masks every occurrence of the API keys so debug output is safe to paste into bug reports.
+    fn redact(&self, text: &str) -> String {
+        self.api_keys.iter().fold(text.to_string(), |text, key| {
+            text.replace(key, &redact_key(key))
+        })
+    }
+
+    /// ` (key 2 of 3)` when there are several keys, to tell them apart.
+    fn key_label(&self, key: usize) -> String {
+        if self.api_keys.len() > 1 {
+            format!(" (key {} of {})", key + 1, self.api_keys.len())
+        } else {
+            String::new()
+        }
+    }
+
+    /// Probes the models endpoint, which answers without a key.
+    pub fn probe(&self) -> Probe {
+        Probe {
+            client: self.client.clone(),
+            url: GROQ_MODELS_URL,
+        }
+    }
+
+    /// Whether GroqCloud accepts every key, found by listing the models.
+    /// An error means GroqCloud could not be asked.
+    pub async fn check_keys(&self) -> Result<bool, String> {
+        for key in &self.api_keys {
+            let response = self
+                .client
+                .get(GROQ_MODELS_URL)
+                .bearer_auth(key)
+                .send()
+                .await
+                .map_err(|e| format!("GroqCloud could not be reached: {}", e))?;
+            if matches!(response.status().as_u16(), 401 | 403) {
+                return Ok(false);
+            }
+        }
+        Ok(!self.api_keys.is_empty())
+    }
+
+    pub async fn chat_completion(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<Vec<ToolDefinition>>,
+        options: &ChatOptions,
+    ) -> Result<Message, ProviderError> {
+        self.chat_completion_non_stream(model, messages, tools, options)
+            .await
+    }
+
+    async fn chat_completion_non_stream(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<Vec<ToolDefinition>>,
+        options: &ChatOptions,
+    ) -> Result<Message, ProviderError> {
+        let request = chat_request(model, messages, tools, options);
+
+        let _slot = self.scheduler.slot().await;
+        let mut key = self
+            .pick_key(estimate_tokens(&request.messages) as u64)
+            .await;
+
+        if self.debug {
+            let body = serde_json::to_string_pretty(&request).unwrap_or_default();
+            notice::debug(
+                &format!(
+                    "POST {} (Authorization: Bearer {})",
+                    GROQ_API_URL,
+                    redact_key(self.api_keys.get(key).map_or("", String::as_str))
+                ),
+                &self.redact(&body),
+            );
+        }
+
+        let mut retries = 0;
+        let mut keys_tried = 1;
+        loop {
+            self.scheduler.pace().await;
+            let response = self
+                .client
+                .post(GROQ_API_URL)
+                .header(
+                    AUTHORIZATION,
+                    format!(
+                        "Bearer {}",
+                        self.api_keys.get(key).map_or("", String::as_str)
+                    ),
+                )
+                .header(CONTENT_TYPE, "application/json")
+                .json(&request)
+                .send()
+                .await?;
+            if let Some(limits) = RateLimits::from_headers(response.headers()) {
+                if let Ok(mut last) = self.limits.lock() {
+                    if let Some(last) = last.get_mut(key) {
+                        *last = Some(limits);
+                    }
+                }
+            }
+
+            if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                // Another key may still have quota; try each once before waiting.
+                if keys_tried < self.api_keys.len() {
+                    let next = (key + 1) % self.api_keys.len();
+                    notice::warn(&format!(
+                        "Rate limit hit on key {}; switching to key {}...",
+                        key + 1,
+                        next + 1
+                    ));
+                    key = next;
+                    keys_tried += 1;
+                    self.current.store(key, Ordering::Relaxed);
+                    continue;
+                }
+            }
+
+            let status = response.status();
+            if self.retry.retries(status.as_u16()) && retries < self.retry.max_retries {
+                retries += 1;
+                let asked = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .map(Duration::from_secs);
+                let delay = self.retry.delay(retries, asked);
+                let reason = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    "Rate limit hit".to_string()
+                } else {
+                    format!("Groq returned {}", status)
+                };
+                notice::warn(&format!(
+                    "{}, retrying in {}... (Attempt {}/{})",
+                    reason,
+                    format_wait(delay),
+                    retries,
+                    self.retry.max_retries
+                ));
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            let body_text = response.text().await?;
+            if self.debug {
+                notice::debug(
+                    &format!("Response {}", status),
+                    &self.redact(&Self::pretty_json(&body_text)),
+                );
+            }
+            if matches!(status.as_u16(), 401 | 403) {
+                return Err(ProviderError::Auth(error_message(&body_text)));
+            }
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Err(ProviderError::RateLimited(error_message(&body_text)));
+            }
+            if !status.is_success() {
+                return Err(ProviderError::Api(format!(
+                    "{} {}",
+                    status,
+                    error_message(&body_text)
+                )));
+            }
+            let chat_response: ChatResponse = serde_json::from_str(&body_text).map_err(|e| {
+                ProviderError::Api(format!("Unreadable response ({}): {}", e, body_text))
+            })?;
+            let mut choices = chat_response.choices.into_iter().map(|choice| {
+                let mut message = choice.message;
+                message.logprobs = choice
+                    .logprobs
+                    .and_then(|logprobs| logprobs.content)
+                    .unwrap_or_default();
+                message
+            });
+            let mut reply = choices.next().unwrap_or_else(|| Message::assistant(""));
+            reply.alternatives = choices.filter_map(|m| m.content).collect();
+            reply.usage = chat_response.usage;
+            return Ok(reply);
+        }
+    }
+
+    /// The key for a request of about `tokens` tokens: the current one, or
+    /// the next in round-robin order, unless its last response said the
+    /// request would be refused and another key has room. When no key has
+    /// room, waits for the one that frees up first, as long as the wait is
+    /// short; otherwise warns and lets the request go.
+    async fn pick_key(&self, tokens: u64) -> usize {
+        let count = self.api_keys.len().max(1);
+        let start = if self.round_robin {
+            self.current.fetch_add(1, Ordering::Relaxed) % count
+        } else {
+            self.current.load(Ordering::Relaxed) % count
+        };
+        let limits = self
+            .limits
+            .lock()
+            .map(|limits| limits.clone())
+            .unwrap_or_default();
+        let waits: Vec<(usize, Option<Duration>)> = (0..count)
+            .map(|i| (start + i) % count)
+            .map(|key| {
+                let wait = limits
+                    .get(key)
+                    .copied()
+                    .flatten()
+                    .and_then(|l| l.wait_for(tokens));
+                (key, wait)
+            })
+            .collect();
+        let (key, wait) = waits
+            .iter()
+            .find(|(_, wait)| wait.is_none())
+            .or_else(|| waits.iter().min_by_key(|(_, wait)| *wait))
+            .copied()
+            .unwrap_or((start, None));
+        if key != start {
+            notice::warn(&format!(
+                "Key {} is close to Groq's rate limit; switching to key {}",
+                start + 1,
+                key + 1
+            ));
+        }
+        if !self.round_robin {
+            self.current.store(key, Ordering::Relaxed);
+        }
+
+        let label = self.key_label(key);
+        match wait {
+            Some(wait) if wait <= MAX_THROTTLE => {
+                notice::warn(&format!(
+                    "Close to Groq's rate limit{}; waiting {}s for it to reset...",
+                    label,
+                    wait.as_secs_f64().ceil()
+                ));
+                tokio::time::sleep(wait).await;
+            }
+            Some(wait) => notice::warn(&format!(
+                "Groq's rate limit{} resets in {}; this request will likely be refused",
+                label,
+                format_wait(wait)
+            )),
+            None => {
+                let Some(limits) = limits.get(key).copied().flatten() else {
+                    return key;
+                };
+                if let Some(remaining) = limits
+                    .remaining_requests
+                    .filter(|&remaining| remaining < LOW_REQUESTS)
+                {
+                    notice::warn(&format!(
+                        "{} requests left{} before Groq's limit resets{}",
+                        remaining,
+                        label,
+                        limits
+                            .requests_reset
+                            .map(|reset| format!(
+                                " in {}",
+                                format_wait(reset.saturating_sub(limits.seen.elapsed()))
+                            ))
+                            .unwrap_or_default()
+                    ));
+                }
+            }
+        }
+        key
+    }
+
+    fn pretty_json(body: &str) -> String {
+        serde_json::from_str::<serde_json::Value>(body)
+            .and_then(|v| serde_json::to_string_pretty(&v))
+            .unwrap_or_else(|_| body.to_string())
+    }
+
+    // Stream mode is trickier with tool calls, for now let's focus on non-stream for search
+    // or handle it by disabling stream when tool calls are expected.
+}
+
+/// The body of a chat completion request: the conversation after the
+/// system prompt, with the tools and parameters.
+pub(super) fn chat_request(
+    model: &str,
+    messages: &[Message],
+    tools: Option<Vec<ToolDefinition>>,
+    options: &ChatOptions,
+) -> ChatRequest {
+    let mut final_messages = Vec::new();
+    final_messages.push(Message::system(SYSTEM_PROMPT));
+    final_messages.extend_from_slice(messages);
+
+    ChatRequest {
+        model: model.to_string(),
+        messages: final_messages,
+        stream: false,
+        tools,
+        tool_choice: options.tool_choice.as_ref().map(ToolChoice::body),
+        response_format: options.format.as_ref().map(ResponseFormat::body),
+        generation: options.generation.clone(),
+        logprobs: options.logprobs.then_some(true),
+        top_logprobs: options.logprobs.then_some(TOP_LOGPROBS),
+    }
+}
+
+/// A wait as `45s`, `12m` or `3h 5m`.
+fn format_wait(wait: Duration) -> String {
+    let secs = wait.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs.max(1)),
+        60..=3599 => format!("{}m", secs.div_ceil(60)),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// The `error.message` of an error response, or the body as it came.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.trim().to_string())
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+
+use crate::message::{FunctionCall, Message, TokenLogprob, ToolCall, ToolChoice, Usage};
+use crate::notice;
+
+/// One scripted reply for the mock provider, read from a JSON array file.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MockReply {
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Pieces delivered one by one in streaming mode; defaults to splitting `content` on words.
+    #[serde(default)]
+    pub chunks: Option<Vec<String>>,
+    #[serde(default)]
+    pub tool_calls: Vec<MockToolCall>,
+    /// Reasoning returned apart from the answer, as reasoning models do.
+    #[serde(default)]
+    pub reasoning: Option<String>,
+    /// Further answers, as when several are asked for with `n`.
+    #[serde(default)]
+    pub alternatives: Vec<String>,
+    /// Token log probabilities, as returned when they are asked for.
+    #[serde(default)]
+    pub logprobs: Vec<TokenLogprob>,
+    /// Token usage, in the API's shape, cached tokens included.
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MockToolCall {
+    pub name: String,
+    /// The arguments as JSON, or a string sent as it is, which lets a
+    /// script send arguments that are not valid JSON.
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+pub struct MockProvider {
+    script: Mutex<VecDeque<MockReply>>,
+    calls: Mutex<usize>,
+    debug: bool,
+}
+
+impl MockProvider {
+    pub fn new(script: Vec<MockReply>) -> Self {
+        Self {
+            script: Mutex::new(script.into()),
+            calls: Mutex::new(0),
+            debug: false,
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read mock script {}: {}", path, e))?;
+        let script: Vec<MockReply> = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid mock script {}: {}", path, e))?;
+        Ok(Self::new(script))
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
+
+    /// Pops the next scripted reply, or improvises one when the script is exhausted:
+    /// `search <query>` triggers a brave_search call, as does a prompt when a tool
+    /// call is forced, tool results get summarized, and anything else is echoed back.
+    fn next_reply(&self, messages: &[Message], tool_choice: Option<&ToolChoice>) -> MockReply {
+        if let Some(reply) = self.script.lock().unwrap().pop_front() {
+            return reply;
+        }
+
+        // Instructions added for the request, such as the one for JSON
+        // answers, are not what is being answered.
+        let last = messages.iter().rev().find(|m| m.role != "system");
+        let last_content = last.and_then(|m| m.content.clone()).unwrap_or_default();
+        match last.map(|m| m.role.as_str()) {
+            Some("tool") => MockReply {
+                content: Some(format!(
+                    "Mock answer based on the tool result:\n\n{}",
+                    last_content.lines().take(4).collect::<Vec<_>>().join("\n")
+                )),
+                ..Default::default()
+            },
+            _ => match last_content.strip_prefix("search ").or(match tool_choice {
+                Some(choice) if choice.forces_call() => Some(last_content.as_str()),
+                _ => None,
+            }) {
+                Some(query) => MockReply {
+                    tool_calls: vec![MockToolCall {
+                        name: "brave_search".to_string(),
+                        arguments: serde_json::json!({ "query": query }),
+                    }],
+                    ..Default::default()
+                },
+                None => MockReply {
+                    content: Some(format!("This is a mock response to: {}", last_content)),
+                    ..Default::default()
+                },
+            },
+        }
+    }
+
+    fn to_message(&self, reply: &MockReply) -> Message {
+        if reply.tool_calls.is_empty() {
+            let content = reply
+                .content
+                .clone()
+                .or_else(|| reply.chunks.as_ref().map(|c| c.concat()))
+                .unwrap_or_default();
+            let mut message = Message::assistant(&content);
+            message.reasoning = reply.reasoning.clone();
+            message.alternatives = reply.alternatives.clone();
+            message.logprobs = reply.logprobs.clone();
+            message.usage = reply.usage;
+            return message;
+        }
+
+        let mut calls = self.calls.lock().unwrap();
+        let tool_calls = reply
+            .tool_calls
+            .iter()
+            .map(|call| {
+                *calls += 1;
+                ToolCall {
+                    id: format!("mock_call_{}", calls),
+                    r#type: "function".to_string(),
+                    function: FunctionCall {
+                        name: call.name.clone(),
+                        arguments: match &call.arguments {
+                            serde_json::Value::String(raw) => raw.clone(),
+                            arguments => arguments.to_string(),
+                        },
+                    },
+                }
+            })
+            .collect();
+        Message {
+            role: "assistant".to_string(),
+            content: reply.content.clone(),
+            tool_calls: Some(tool_calls),
+            tool_call_id: None,
+            reasoning: reply.reasoning.clone(),
+            alternatives: Vec::new(),
+            logprobs: Vec::new(),
+            usage: reply.usage,
+        }
+    }
+
+    fn print_request(&self, model: &str, messages: &[Message]) {
+        if self.debug {
+            let body = serde_json::json!({ "model": model, "messages": messages });
+            notice::debug(
+                "mock request",
+                &serde_json::to_string_pretty(&body).unwrap_or_default(),
+            );
+        }
+    }
+
+    pub async fn chat_completion(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tool_choice: Option<&ToolChoice>,
+    ) -> Message {
+        self.print_request(model, messages);
+        let reply = self.next_reply(messages, tool_choice);
+        self.to_message(&reply)
+    }
+
+    pub async fn chat_completion_stream(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tool_choice: Option<&ToolChoice>,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Message {
+        self.print_request(model, messages);
+        let reply = self.next_reply(messages, tool_choice);
+        if reply.tool_calls.is_empty() {
+            let chunks = reply.chunks.clone().unwrap_or_else(|| {
+                reply
+                    .content
+                    .as_deref()
+                    .unwrap_or("")
+                    .split_inclusive(' ')
+                    .map(|s| s.to_string())
+                    .collect()
+            });
+            for chunk in &chunks {
+                on_delta(chunk);
+                tokio::time::sleep(tokio::time::Duration::from_millis(30)).await;
+            }
+        }
+        self.to_message(&reply)
+    }
+}
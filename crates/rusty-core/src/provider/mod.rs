@@ -0,0 +1,289 @@
+//! Chat completion and embedding backends.
+
+mod embeddings;
+mod groq;
+mod mock;
+mod recorder;
+mod scheduler;
+
+use std::fmt;
+
+pub use embeddings::{Embedder, EmbeddingsClient};
+pub use groq::{GroqApiClient, GROQ_API_URL, SYSTEM_PROMPT};
+pub use mock::{MockProvider, MockReply, MockToolCall};
+pub use recorder::Recorder;
+pub use scheduler::Scheduler;
+
+use crate::conversation;
+use crate::diagnostics;
+use crate::message::{ChatRequest, Generation, Message, ToolChoice, ToolDefinition};
+use crate::structured::ResponseFormat;
+
+/// Why a chat completion failed, kept apart so callers can tell a bad key
+/// from a busy or unreachable service.
+#[derive(Debug)]
+pub enum ProviderError {
+    /// The API refused the key.
+    Auth(String),
+    /// The API kept refusing requests for going over the rate limit.
+    RateLimited(String),
+    /// The request did not get through, or the answer did not come back.
+    Network(reqwest::Error),
+    /// The API answered with another error, or with something unreadable.
+    Api(String),
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::Auth(message) => write!(f, "The API key was refused: {}", message),
+            ProviderError::RateLimited(message) => {
+                write!(f, "Rate limit exceeded after retries: {}", message)
+            }
+            ProviderError::Network(e) => write!(f, "Network error: {}", e),
+            ProviderError::Api(message) => write!(f, "API error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        ProviderError::Network(e)
+    }
+}
+
+/// What a request asks of the model beyond answering the conversation
+/// with the tools offered.
+#[derive(Debug, Default, Clone)]
+pub struct ChatOptions {
+    pub generation: Generation,
+    /// Asks for the answer as JSON.
+    pub format: Option<ResponseFormat>,
+    /// Asks how sure the model was of each token.
+    pub logprobs: bool,
+    /// Whether the model must, or must not, call a tool; unset to let it
+    /// decide.
+    pub tool_choice: Option<ToolChoice>,
+}
+
+pub enum Provider {
+    Groq(GroqApiClient),
+    Mock(MockProvider),
+    /// Groq recorded to a cassette, or a cassette played back.
+    Recorded(Recorder),
+}
+
+impl Provider {
+    pub async fn chat_completion(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<Vec<ToolDefinition>>,
+    ) -> Result<Message, ProviderError> {
+        self.chat_completion_with(model, messages, tools, &ChatOptions::default())
+            .await
+    }
+
+    /// Like `chat_completion`, with `options`. A JSON answer is also asked
+    /// for in a system message.
+    pub async fn chat_completion_with(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<Vec<ToolDefinition>>,
+        options: &ChatOptions,
+    ) -> Result<Message, ProviderError> {
+        let mut messages = messages.to_vec();
+        if let Some(format) = &options.format {
+            messages.push(Message::system(&format.instruction()));
+        }
+        diagnostics::request(&describe(
+            model,
+            &messages,
+            tools.as_deref(),
+            options,
+            false,
+        ));
+        let reply = match self {
+            Provider::Groq(client) => {
+                client
+                    .chat_completion(model, &messages, tools, options)
+                    .await
+            }
+            Provider::Mock(mock) => Ok(mock
+                .chat_completion(model, &messages, options.tool_choice.as_ref())
+                .await),
+            Provider::Recorded(recorder) => {
+                recorder
+                    .chat_completion(model, &messages, tools, options)
+                    .await
+            }
+        };
+        let mut reply = log_outcome(reply)?;
+        reply.split_reasoning();
+        Ok(reply)
+    }
+
+    /// Like `chat_completion_with`, but hands text to `on_delta` as it
+    /// arrives.
+    pub async fn chat_completion_stream(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<Vec<ToolDefinition>>,
+        options: &ChatOptions,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Message, ProviderError> {
+        diagnostics::request(&describe(model, messages, tools.as_deref(), options, true));
+        let reply = match self {
+            Provider::Groq(client) => {
+                client
+                    .chat_completion(model, messages, tools, options)
+                    .await
+            }
+            Provider::Mock(mock) => Ok(mock
+                .chat_completion_stream(model, messages, options.tool_choice.as_ref(), on_delta)
+                .await),
+            Provider::Recorded(recorder) => {
+                recorder
+                    .chat_completion(model, messages, tools, options)
+                    .await
+            }
+        };
+        let mut reply = log_outcome(reply)?;
+        reply.split_reasoning();
+        Ok(reply)
+    }
+
+    /// The request `chat_completion_with` would send to the API, without
+    /// sending it.
+    pub fn preview(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<Vec<ToolDefinition>>,
+        options: &ChatOptions,
+    ) -> ChatRequest {
+        let mut messages = messages.to_vec();
+        if let Some(format) = &options.format {
+            messages.push(Message::system(&format.instruction()));
+        }
+        groq::chat_request(model, &messages, tools, options)
+    }
+
+    pub fn supports_streaming(&self) -> bool {
+        matches!(self, Provider::Mock(_))
+    }
+
+    /// The host requests go to, for messages about it; `None` for
+    /// providers that need no network.
+    pub fn host(&self) -> Option<&'static str> {
+        self.probe().map(|_| groq::GROQ_HOST)
+    }
+
+    /// What tells whether the API can be reached; `None` for providers that
+    /// need no network.
+    pub fn probe(&self) -> Option<Probe> {
+        match self {
+            Provider::Groq(client) => Some(client.probe()),
+            Provider::Recorded(recorder) => recorder.probe(),
+            Provider::Mock(_) => None,
+        }
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        match self {
+            Provider::Groq(client) => client.set_debug(debug),
+            Provider::Mock(mock) => mock.set_debug(debug),
+            Provider::Recorded(recorder) => recorder.set_debug(debug),
+        }
+    }
+
+    pub fn is_debug(&self) -> bool {
+        match self {
+            Provider::Groq(client) => client.is_debug(),
+            Provider::Mock(mock) => mock.is_debug(),
+            Provider::Recorded(recorder) => recorder.is_debug(),
+        }
+    }
+}
+
+/// Asks the API whether it can be reached, through the client requests
+/// use, so the same proxies apply.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    client: reqwest::Client,
+    url: &'static str,
+}
+
+impl Probe {
+    /// Whether the API answers a request without a key within a few
+    /// seconds. Any answer will do, a refusal too, so the probe costs no
+    /// quota.
+    pub async fn reachable(&self) -> bool {
+        let request = self.client.head(self.url).send();
+        matches!(
+            tokio::time::timeout(std::time::Duration::from_secs(3), request).await,
+            Ok(Ok(_))
+        )
+    }
+}
+
+/// A request as the diagnostic log keeps it: what it asked for and how
+/// large it was, without the messages themselves.
+fn describe(
+    model: &str,
+    messages: &[Message],
+    tools: Option<&[ToolDefinition]>,
+    options: &ChatOptions,
+    stream: bool,
+) -> String {
+    let mut what = format!(
+        "model={} messages={} (~{} tokens) tools={}",
+        model,
+        messages.len(),
+        conversation::estimate_tokens(messages),
+        tools.map_or(0, <[ToolDefinition]>::len)
+    );
+    if stream {
+        what.push_str(" stream");
+    }
+    if options.format.is_some() {
+        what.push_str(" json");
+    }
+    if options.logprobs {
+        what.push_str(" logprobs");
+    }
+    if let Some(choice) = &options.tool_choice {
+        what.push_str(&format!(" tool_choice={:?}", choice));
+    }
+    what
+}
+
+/// Logs how a request ended, for diagnostic reports.
+fn log_outcome(reply: Result<Message, ProviderError>) -> Result<Message, ProviderError> {
+    match &reply {
+        Ok(message) => diagnostics::log(&match &message.usage {
+            Some(usage) => format!(
+                "response {} tokens in, {} out",
+                usage.prompt, usage.completion
+            ),
+            None => "response".to_string(),
+        }),
+        Err(e) => diagnostics::log(&format!("failed: {}", e)),
+    }
+    reply
+}
+
+/// Keeps the first and last four characters of a secret so it stays recognizable without leaking.
+pub fn redact_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}****{}", head, tail)
+}
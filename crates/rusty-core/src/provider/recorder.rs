@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use super::{ChatOptions, GroqApiClient, Probe, ProviderError};
+use crate::cassette::Cassette;
+use crate::message::{Message, ToolDefinition};
+use crate::notice;
+
+/// Groq behind a cassette: its answers are recorded as they come, or,
+/// when playing back, read from the cassette without Groq being asked.
+pub struct Recorder {
+    /// `None` when playing back.
+    client: Option<GroqApiClient>,
+    cassette: Arc<Cassette>,
+    debug: bool,
+}
+
+impl Recorder {
+    pub fn record(client: GroqApiClient, cassette: Arc<Cassette>) -> Self {
+        Self {
+            client: Some(client),
+            cassette,
+            debug: false,
+        }
+    }
+
+    pub fn replay(cassette: Arc<Cassette>) -> Self {
+        Self {
+            client: None,
+            cassette,
+            debug: false,
+        }
+    }
+
+    pub async fn chat_completion(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<Vec<ToolDefinition>>,
+        options: &ChatOptions,
+    ) -> Result<Message, ProviderError> {
+        let key = key(model, messages);
+        let Some(client) = &self.client else {
+            let response = self
+                .cassette
+                .play("chat", &key)
+                .map_err(ProviderError::Api)?;
+            return serde_json::from_value(response)
+                .map_err(|e| ProviderError::Api(format!("Invalid recorded answer: {}", e)));
+        };
+
+        let request = json!({
+            "model": model,
+            "messages": messages,
+            "tools": tools.iter().flatten().map(|tool| &tool.function.name).collect::<Vec<_>>(),
+        });
+        let reply = client
+            .chat_completion(model, messages, tools, options)
+            .await?;
+        if let Err(e) = self.cassette.add("chat", &key, request, response(&reply)) {
+            notice::warn(&e);
+        }
+        Ok(reply)
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+        if let Some(client) = &mut self.client {
+            client.set_debug(debug);
+        }
+    }
+
+    /// Playing back needs no network, so there is nothing to probe.
+    pub fn probe(&self) -> Option<Probe> {
+        self.client.as_ref().map(GroqApiClient::probe)
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.client.is_none()
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
+}
+
+/// What a chat request is matched on: the model and the last prompt.
+fn key(model: &str, messages: &[Message]) -> String {
+    let prompt = messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .and_then(|message| message.content.as_deref())
+        .unwrap_or_default();
+    format!("{} {}", model, prompt)
+}
+
+/// An answer as the cassette keeps it: as the API sent it, reasoning
+/// included.
+fn response(reply: &Message) -> Value {
+    let mut response = serde_json::to_value(reply).unwrap_or_default();
+    if let (Some(reasoning), Some(object)) = (&reply.reasoning, response.as_object_mut()) {
+        object.insert("reasoning".to_string(), json!(reasoning));
+    }
+    response
+}
@@ -0,0 +1,67 @@
+//! Limits on a provider's calls, shared by everything that makes them: at
+//! most so many requests in flight at once, and optionally at most so many
+//! started per minute. Callers wait their turn rather than being refused.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+pub struct Scheduler {
+    slots: Semaphore,
+    requests_per_minute: Option<usize>,
+    /// When each request of the last minute was started, oldest first.
+    started: Mutex<VecDeque<Instant>>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(4, None)
+    }
+}
+
+impl Scheduler {
+    pub fn new(max_concurrent: usize, requests_per_minute: Option<usize>) -> Self {
+        Self {
+            slots: Semaphore::new(max_concurrent.max(1)),
+            requests_per_minute: requests_per_minute.filter(|&rpm| rpm > 0),
+            started: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Waits for a free slot, held until the permit is dropped.
+    pub async fn slot(&self) -> SemaphorePermit<'_> {
+        // The semaphore is never closed, so this cannot fail.
+        self.slots
+            .acquire()
+            .await
+            .expect("scheduler semaphore closed")
+    }
+
+    /// Waits until another request fits in the per-minute limit, then
+    /// counts it. Retries count too, since the provider counts them.
+    pub async fn pace(&self) {
+        let Some(limit) = self.requests_per_minute else {
+            return;
+        };
+        let mut started = self.started.lock().await;
+        loop {
+            let now = Instant::now();
+            while started
+                .front()
+                .is_some_and(|&time| now.duration_since(time) >= MINUTE)
+            {
+                started.pop_front();
+            }
+            match started.front() {
+                Some(&oldest) if started.len() >= limit => {
+                    tokio::time::sleep(MINUTE.saturating_sub(now.duration_since(oldest))).await;
+                }
+                _ => break,
+            }
+        }
+        started.push_back(Instant::now());
+    }
+}
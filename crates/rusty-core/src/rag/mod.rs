@@ -0,0 +1,267 @@
+//! Local document retrieval: files under a directory are split into chunks,
+//! embedded, and saved to a [`VectorStore`] that the `search_docs` tool
+//! queries. Embeddings come from an [`Embedder`], local by default.
+
+mod store;
+
+pub use store::{open_store, IndexInfo, JsonStore, SqliteStore, VectorStore};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::provider::Embedder;
+
+/// File extensions read when indexing; everything else is skipped.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "md", "markdown", "txt", "rst", "org", "adoc", "tex", "html", "csv", "json", "toml", "yaml",
+    "yml", "rs", "py", "js", "ts", "go", "java", "c", "h", "cpp", "rb", "sh",
+];
+
+/// Files larger than this are skipped as likely generated or data files.
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+
+/// Target size of a chunk in characters.
+const CHUNK_CHARS: usize = 1500;
+
+/// Dimensions of the local hashed embedding.
+const LOCAL_DIMENSIONS: usize = 512;
+
+/// Name recorded in the index for vectors made by [`embed_local`].
+pub const LOCAL_EMBEDDER: &str = "local-hash-512";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Path of the file, relative to the indexed directory.
+    pub path: String,
+    /// First line of the chunk, from 1.
+    pub line: usize,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+/// The result of indexing a directory, before it goes into a store.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentIndex {
+    pub root: PathBuf,
+    /// Name of the embedder that made the vectors.
+    pub embedder: String,
+    pub chunks: Vec<Chunk>,
+}
+
+impl DocumentIndex {
+    /// Chunks and embeds every text file under `root`, honoring `.gitignore`
+    /// and skipping hidden files. Unreadable files are reported as warnings.
+    pub async fn build(root: &Path, embedder: &Embedder) -> Result<(Self, Vec<String>), String> {
+        let root = root
+            .canonicalize()
+            .map_err(|e| format!("Failed to read {}: {}", root.display(), e))?;
+        if !root.is_dir() {
+            return Err(format!("{} is not a directory", root.display()));
+        }
+
+        let mut warnings = Vec::new();
+        let mut chunks = Vec::new();
+        for entry in ignore::WalkBuilder::new(&root).build() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warnings.push(e.to_string());
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if !is_indexable(path) {
+                continue;
+            }
+            let text = match fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) => {
+                    warnings.push(format!("Skipped {}: {}", path.display(), e));
+                    continue;
+                }
+            };
+            let relative = path
+                .strip_prefix(&root)
+                .unwrap_or(path)
+                .display()
+                .to_string();
+            for (line, text) in chunk_text(&text) {
+                chunks.push(Chunk {
+                    path: relative.clone(),
+                    line,
+                    text,
+                    vector: Vec::new(),
+                });
+            }
+        }
+
+        let texts: Vec<String> = chunks.iter().map(|c| c.text.clone()).collect();
+        let vectors = embedder.embed(&texts).await?;
+        for (chunk, vector) in chunks.iter_mut().zip(vectors) {
+            chunk.vector = vector;
+        }
+
+        let index = Self {
+            root,
+            embedder: embedder.name(),
+            chunks,
+        };
+        Ok((index, warnings))
+    }
+
+    /// Number of distinct files with at least one chunk.
+    pub fn file_count(&self) -> usize {
+        let mut paths: Vec<&str> = self.chunks.iter().map(|c| c.path.as_str()).collect();
+        paths.dedup();
+        paths.len()
+    }
+}
+
+/// A passage found by a search, with its similarity to the query.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub score: f32,
+    pub path: String,
+    pub line: usize,
+    pub text: String,
+}
+
+/// A vector store paired with the embedder its vectors came from, so
+/// queries are embedded the same way.
+pub struct Retriever {
+    store: Box<dyn VectorStore>,
+    embedder: Embedder,
+    info: IndexInfo,
+}
+
+impl Retriever {
+    /// `None` when nothing has been indexed yet. Fails when the index was
+    /// built with a different embedder than the one configured now.
+    pub fn open(store: Box<dyn VectorStore>, embedder: Embedder) -> Result<Option<Self>, String> {
+        let Some(info) = store.info()? else {
+            return Ok(None);
+        };
+        if info.embedder != embedder.name() {
+            return Err(format!(
+                "The document index was built with {} but {} is configured; run `rusty index` again",
+                info.embedder,
+                embedder.name()
+            ));
+        }
+        Ok(Some(Self {
+            store,
+            embedder,
+            info,
+        }))
+    }
+
+    pub fn info(&self) -> &IndexInfo {
+        &self.info
+    }
+
+    /// The passages closest to `query`, formatted for the model with their
+    /// sources.
+    pub async fn search(&self, query: &str, limit: usize) -> Result<String, String> {
+        let vectors = self.embedder.embed(&[query.to_string()]).await?;
+        let query = vectors.first().ok_or("No embedding for the query")?;
+        let hits = self.store.search(query, limit)?;
+        if hits.is_empty() {
+            return Ok("No matching passages in the indexed documents.".to_string());
+        }
+        let mut output = String::from("### Passages from the user's documents\n\n");
+        for hit in hits {
+            output.push_str(&format!(
+                "Source: {}:{}\n```\n{}\n```\n\n",
+                hit.path,
+                hit.line,
+                hit.text.trim_end()
+            ));
+        }
+        Ok(output)
+    }
+}
+
+fn is_indexable(path: &Path) -> bool {
+    let text_file = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| TEXT_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+    text_file && fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() <= MAX_FILE_BYTES)
+}
+
+/// Splits `text` into chunks of whole paragraphs of about [`CHUNK_CHARS`],
+/// each with the line it starts on.
+pub(crate) fn chunk_text(text: &str) -> Vec<(usize, String)> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut start = 1;
+    for (i, line) in text.lines().enumerate() {
+        let blank = line.trim().is_empty();
+        let len = current.chars().count();
+        if blank && len >= CHUNK_CHARS || len >= CHUNK_CHARS * 2 {
+            chunks.push((start, std::mem::take(&mut current)));
+        }
+        if current.is_empty() {
+            if blank {
+                continue;
+            }
+            start = i + 1;
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.is_empty() {
+        chunks.push((start, current));
+    }
+    chunks
+}
+
+/// An embedding that needs no model: words and word pairs hashed into a
+/// fixed number of buckets, then normalized. It matches on shared vocabulary
+/// rather than meaning, which is enough to find the right passages in most
+/// personal notes and docs.
+pub fn embed_local(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; LOCAL_DIMENSIONS];
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 1)
+        .map(str::to_lowercase)
+        .collect();
+    let mut add = |feature: &str, weight: f32| {
+        let hash = fnv1a(feature);
+        let sign = if hash & 1 == 0 { 1.0 } else { -1.0 };
+        vector[(hash >> 1) as usize % LOCAL_DIMENSIONS] += sign * weight;
+    };
+    for word in &words {
+        add(word, 1.0);
+    }
+    for pair in words.windows(2) {
+        add(&format!("{} {}", pair[0], pair[1]), 0.5);
+    }
+    for value in &mut vector {
+        *value = value.signum() * value.abs().ln_1p();
+    }
+    normalize(&mut vector);
+    vector
+}
+
+pub(crate) fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for value in vector {
+            *value /= norm;
+        }
+    }
+}
+
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
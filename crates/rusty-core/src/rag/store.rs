@@ -0,0 +1,270 @@
+//! Where chunk vectors are kept and searched. Retrieval only talks to the
+//! [`VectorStore`] trait, so another backend can be added here and picked in
+//! `[index] store` without touching it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{dot, DocumentIndex, Hit};
+use crate::config::{IndexConfig, StoreKind};
+use crate::paths;
+
+/// What a store holds.
+#[derive(Debug, Clone)]
+pub struct IndexInfo {
+    pub root: PathBuf,
+    /// Name of the embedder that made the vectors.
+    pub embedder: String,
+    pub chunks: usize,
+    pub files: usize,
+}
+
+pub trait VectorStore: Send + Sync {
+    /// Replaces everything stored with `index`.
+    fn replace(&mut self, index: &DocumentIndex) -> Result<(), String>;
+
+    /// `None` when nothing has been indexed yet.
+    fn info(&self) -> Result<Option<IndexInfo>, String>;
+
+    /// The `limit` chunks closest to the `query` vector, best first. Scores
+    /// are cosine similarities; unrelated chunks are left out.
+    fn search(&self, query: &[f32], limit: usize) -> Result<Vec<Hit>, String>;
+}
+
+/// The store chosen in `[index] store`, in the configuration directory.
+pub fn open_store(config: &IndexConfig) -> Result<Box<dyn VectorStore>, String> {
+    match config.store {
+        StoreKind::Sqlite => Ok(Box::new(SqliteStore::open(&paths::index_db())?)),
+        StoreKind::Json => Ok(Box::new(JsonStore::open(&paths::index_file())?)),
+    }
+}
+
+/// The whole index in one JSON file, loaded into memory and searched by
+/// comparing the query with every chunk.
+pub struct JsonStore {
+    path: PathBuf,
+    index: Option<DocumentIndex>,
+}
+
+impl JsonStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let index = if path.exists() {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let index = serde_json::from_str(&text)
+                .map_err(|e| format!("Invalid document index {}: {}", path.display(), e))?;
+            Some(index)
+        } else {
+            None
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            index,
+        })
+    }
+}
+
+impl VectorStore for JsonStore {
+    fn replace(&mut self, index: &DocumentIndex) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let json = serde_json::to_string(index).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        self.index = Some(DocumentIndex {
+            root: index.root.clone(),
+            embedder: index.embedder.clone(),
+            chunks: index.chunks.clone(),
+        });
+        Ok(())
+    }
+
+    fn info(&self) -> Result<Option<IndexInfo>, String> {
+        Ok(self.index.as_ref().map(|index| IndexInfo {
+            root: index.root.clone(),
+            embedder: index.embedder.clone(),
+            chunks: index.chunks.len(),
+            files: index.file_count(),
+        }))
+    }
+
+    fn search(&self, query: &[f32], limit: usize) -> Result<Vec<Hit>, String> {
+        let Some(index) = &self.index else {
+            return Ok(Vec::new());
+        };
+        let mut hits: Vec<Hit> = index
+            .chunks
+            .iter()
+            .map(|chunk| Hit {
+                score: dot(query, &chunk.vector),
+                path: chunk.path.clone(),
+                line: chunk.line,
+                text: chunk.text.clone(),
+            })
+            .filter(|hit| hit.score > 0.0)
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+}
+
+/// A SQLite database with the sqlite-vec extension, which keeps vectors on
+/// disk and finds nearest neighbours without loading the index into memory.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| unsafe {
+            type EntryPoint = unsafe extern "C" fn(
+                *mut rusqlite::ffi::sqlite3,
+                *mut *mut std::os::raw::c_char,
+                *const rusqlite::ffi::sqlite3_api_routines,
+            ) -> std::os::raw::c_int;
+            let init = sqlite_vec::sqlite3_vec_init as *const ();
+            rusqlite::ffi::sqlite3_auto_extension(Some(
+                std::mem::transmute::<*const (), EntryPoint>(init),
+            ));
+        });
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| sql_error(path, e))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             CREATE TABLE IF NOT EXISTS chunks (
+                 id INTEGER PRIMARY KEY,
+                 path TEXT NOT NULL,
+                 line INTEGER NOT NULL,
+                 text TEXT NOT NULL
+             );",
+        )
+        .map_err(|e| sql_error(path, e))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl VectorStore for SqliteStore {
+    fn replace(&mut self, index: &DocumentIndex) -> Result<(), String> {
+        let conn = self.conn.get_mut().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute_batch("DROP TABLE IF EXISTS vec_chunks; DELETE FROM chunks; DELETE FROM meta;")
+            .map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO meta (key, value) VALUES ('root', ?1), ('embedder', ?2)",
+            params![index.root.display().to_string(), index.embedder],
+        )
+        .map_err(|e| e.to_string())?;
+
+        if let Some(first) = index.chunks.first() {
+            tx.execute_batch(&format!(
+                "CREATE VIRTUAL TABLE vec_chunks USING vec0(embedding float[{}] distance_metric=cosine)",
+                first.vector.len()
+            ))
+            .map_err(|e| e.to_string())?;
+            let mut insert_chunk = tx
+                .prepare("INSERT INTO chunks (id, path, line, text) VALUES (?1, ?2, ?3, ?4)")
+                .map_err(|e| e.to_string())?;
+            let mut insert_vector = tx
+                .prepare("INSERT INTO vec_chunks (rowid, embedding) VALUES (?1, ?2)")
+                .map_err(|e| e.to_string())?;
+            for (id, chunk) in index.chunks.iter().enumerate() {
+                let id = id as i64 + 1;
+                insert_chunk
+                    .execute(params![id, chunk.path, chunk.line as i64, chunk.text])
+                    .map_err(|e| e.to_string())?;
+                insert_vector
+                    .execute(params![id, vector_bytes(&chunk.vector)])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn info(&self) -> Result<Option<IndexInfo>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let meta = |key: &str| {
+            conn.query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()
+            .map_err(|e| e.to_string())
+        };
+        let (Some(root), Some(embedder)) = (meta("root")?, meta("embedder")?) else {
+            return Ok(None);
+        };
+        let (chunks, files): (i64, i64) = conn
+            .query_row(
+                "SELECT COUNT(*), COUNT(DISTINCT path) FROM chunks",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(Some(IndexInfo {
+            root: PathBuf::from(root),
+            embedder,
+            chunks: chunks as usize,
+            files: files as usize,
+        }))
+    }
+
+    fn search(&self, query: &[f32], limit: usize) -> Result<Vec<Hit>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let has_vectors = conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE name = 'vec_chunks'",
+                [],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .is_some();
+        if !has_vectors {
+            return Ok(Vec::new());
+        }
+
+        let mut statement = conn
+            .prepare(
+                "WITH knn AS (
+                     SELECT rowid, distance FROM vec_chunks WHERE embedding MATCH ?1 AND k = ?2
+                 )
+                 SELECT chunks.path, chunks.line, chunks.text, knn.distance
+                 FROM knn JOIN chunks ON chunks.id = knn.rowid
+                 ORDER BY knn.distance",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map(params![vector_bytes(query), limit as i64], |row| {
+                Ok(Hit {
+                    path: row.get(0)?,
+                    line: row.get::<_, i64>(1)? as usize,
+                    text: row.get(2)?,
+                    score: 1.0 - row.get::<_, f64>(3)? as f32,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        let hits: Vec<Hit> = rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        Ok(hits.into_iter().filter(|hit| hit.score > 0.0).collect())
+    }
+}
+
+/// Vectors are passed to sqlite-vec as little-endian `f32` blobs.
+fn vector_bytes(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn sql_error(path: &Path, e: rusqlite::Error) -> String {
+    format!("Failed to open {}: {}", path.display(), e)
+}
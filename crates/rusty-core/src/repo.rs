@@ -0,0 +1,99 @@
+//! The Git repository of the working directory, for the dashboard and the
+//! prompt: its name, the branch checked out, and whether anything is
+//! changed.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone)]
+pub struct Repo {
+    /// The name of the repository's top directory.
+    pub name: String,
+    /// The branch checked out, or the short commit id when detached.
+    pub branch: String,
+    /// Files changed, staged or untracked.
+    pub changed: usize,
+    /// Commits not yet pushed to the upstream branch, and commits there not
+    /// yet pulled; both 0 without an upstream.
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+impl Repo {
+    /// The repository the working directory is in; `None` outside one, or
+    /// when Git is not installed.
+    pub fn current() -> Option<Self> {
+        let top = top_level()?;
+        let name = top.file_name()?.to_string_lossy().to_string();
+        let status = git(&["status", "--porcelain=v2", "--branch"])?;
+        let mut head = None;
+        let mut commit = None;
+        let (mut changed, mut ahead, mut behind) = (0, 0, 0);
+        for line in status.lines() {
+            if let Some(branch) = line.strip_prefix("# branch.head ") {
+                head = Some(branch).filter(|branch| *branch != "(detached)");
+            } else if let Some(oid) = line.strip_prefix("# branch.oid ") {
+                commit = Some(oid.get(..7).unwrap_or(oid));
+            } else if let Some(counts) = line.strip_prefix("# branch.ab ") {
+                // "+1 -2": one commit ahead, two behind.
+                let mut counts = counts
+                    .split_whitespace()
+                    .map(|count| count[1..].parse().unwrap_or(0));
+                ahead = counts.next().unwrap_or(0);
+                behind = counts.next().unwrap_or(0);
+            } else if !line.starts_with('#') {
+                changed += 1;
+            }
+        }
+        let branch = head.or(commit)?.to_string();
+        Some(Self {
+            name,
+            branch,
+            changed,
+            ahead,
+            behind,
+        })
+    }
+}
+
+/// `rusty (main*)`, the star saying something is changed.
+impl fmt::Display for Repo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let star = if self.changed > 0 { "*" } else { "" };
+        write!(f, "{} ({}{})", self.name, self.branch, star)
+    }
+}
+
+/// The top directory of the repository the working directory is in.
+pub fn top_level() -> Option<PathBuf> {
+    let top = git(&["rev-parse", "--show-toplevel"])?;
+    Some(PathBuf::from(top.trim()))
+}
+
+/// The working directory, with the home directory written as `~`.
+pub fn working_dir() -> String {
+    let Ok(dir) = std::env::current_dir() else {
+        return String::new();
+    };
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    match home.and_then(|home| dir.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~{}{}", std::path::MAIN_SEPARATOR, rest.display()),
+        None => dir.display().to_string(),
+    }
+}
+
+/// The output of a Git command, or `None` when it fails.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -0,0 +1,96 @@
+//! Cron schedules for `rusty schedule`, and the log scheduled answers are
+//! appended to.
+//!
+//! Patterns have the usual five fields (minute, hour, day of month, month,
+//! day of week), read in local time, or a nickname such as `@daily`.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use croner::Cron;
+
+/// The longest single sleep while waiting for a run. The clock is read
+/// again after each, so a run is not missed after the computer slept or
+/// the clock was changed.
+const MAX_NAP: Duration = Duration::from_secs(60);
+
+pub struct Schedule {
+    pattern: String,
+    cron: Cron,
+}
+
+impl Schedule {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let cron = Cron::new(pattern)
+            .parse()
+            .map_err(|e| format!("Invalid schedule '{}': {}", pattern, e))?;
+        Ok(Self {
+            pattern: pattern.to_string(),
+            cron,
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The first time after `time` the schedule matches, or `None` if it
+    /// never does again, as with February 30th.
+    pub fn next_after(&self, time: &DateTime<Local>) -> Option<DateTime<Local>> {
+        self.cron.find_next_occurrence(time, false).ok()
+    }
+}
+
+/// Waits until the next time any of `schedules` matches, and returns that
+/// time with the positions of the schedules due then. Returns `None` at
+/// once if none of them will run again.
+pub async fn next_due(schedules: &[Schedule]) -> Option<(DateTime<Local>, Vec<usize>)> {
+    let now = Local::now();
+    let times: Vec<Option<DateTime<Local>>> =
+        schedules.iter().map(|s| s.next_after(&now)).collect();
+    let next = times.iter().flatten().min().copied()?;
+    loop {
+        let left = (next - Local::now()).to_std().unwrap_or_default();
+        if left.is_zero() {
+            break;
+        }
+        tokio::time::sleep(left.min(MAX_NAP)).await;
+    }
+    let due = (0..times.len())
+        .filter(|&i| times[i] == Some(next))
+        .collect();
+    Some((next, due))
+}
+
+/// Appends one run of `prompt`, started at `time`, to the log at `path` as
+/// a Markdown section.
+pub fn append_log(
+    path: &Path,
+    time: &DateTime<Local>,
+    prompt: &str,
+    result: &Result<String, String>,
+) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let body = match result {
+        Ok(answer) => answer.trim().to_string(),
+        Err(e) => format!("**Failed:** {}", e),
+    };
+    let entry = format!(
+        "## {} · {}\n\n{}\n\n",
+        time.format("%Y-%m-%d %H:%M"),
+        prompt.lines().next().unwrap_or_default(),
+        body
+    );
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(entry.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,204 @@
+//! User hooks written in Rhai.
+//!
+//! Every `*.rhai` file in the scripts directory is compiled at startup and may
+//! define any of these functions:
+//!
+//! - `on_user_message(text)` runs before a prompt is added to the conversation.
+//! - `on_response(text)` runs on the final assistant answer before it is stored.
+//! - `on_tool_call(name, args)` runs before a tool executes; `args` is a map.
+//!
+//! A hook returns a new value to replace the input, returns nothing to leave it
+//! unchanged, or calls `throw "reason"` to block the action. Scripts run in
+//! file-name order, each seeing the previous script's output. Besides the Rhai
+//! standard library, scripts can call `env(name)` and `cwd()`.
+
+use std::path::{Path, PathBuf};
+
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+
+use crate::message::ToolCall;
+
+/// Keeps a runaway script from hanging the REPL.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What a hook decided to do with its input.
+#[derive(Debug, PartialEq)]
+pub enum HookOutcome<T> {
+    Keep,
+    Replace(T),
+    Block(String),
+}
+
+struct Script {
+    path: PathBuf,
+    ast: AST,
+}
+
+impl Script {
+    fn defines(&self, hook: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == hook)
+    }
+}
+
+pub struct ScriptHooks {
+    engine: Engine,
+    scripts: Vec<Script>,
+}
+
+impl ScriptHooks {
+    pub fn new() -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.register_fn("env", |name: &str| std::env::var(name).unwrap_or_default());
+        engine.register_fn("cwd", || {
+            std::env::current_dir()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+        });
+        Self {
+            engine,
+            scripts: Vec::new(),
+        }
+    }
+
+    /// Compiles every `*.rhai` file in `dir`, returning a warning for each
+    /// script that fails to compile.
+    pub fn load(dir: &Path) -> (Self, Vec<String>) {
+        let mut hooks = Self::new();
+        let mut warnings = Vec::new();
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return (hooks, warnings);
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "rhai"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match hooks.engine.compile_file(path.clone()) {
+                Ok(ast) => hooks.scripts.push(Script { path, ast }),
+                Err(e) => warnings.push(format!(
+                    "Script {} failed to compile: {}",
+                    path.display(),
+                    e
+                )),
+            }
+        }
+
+        (hooks, warnings)
+    }
+
+    pub fn on_user_message(&self, text: &str) -> Result<HookOutcome<String>, String> {
+        self.run_text_hook("on_user_message", text)
+    }
+
+    pub fn on_response(&self, text: &str) -> Result<HookOutcome<String>, String> {
+        self.run_text_hook("on_response", text)
+    }
+
+    /// Runs `on_tool_call` hooks, returning the call with rewritten arguments
+    /// when a hook replaced them. Calls whose arguments are not valid JSON are
+    /// left for the tool parser to reject.
+    pub fn on_tool_call(&self, call: &ToolCall) -> Result<HookOutcome<ToolCall>, String> {
+        let hook = "on_tool_call";
+        if !self.scripts.iter().any(|s| s.defines(hook)) {
+            return Ok(HookOutcome::Keep);
+        }
+        let Ok(mut current) = serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+        else {
+            return Ok(HookOutcome::Keep);
+        };
+        let mut changed = false;
+
+        for script in self.scripts.iter().filter(|s| s.defines(hook)) {
+            let args = rhai::serde::to_dynamic(&current).map_err(|e| e.to_string())?;
+            match self.call(script, hook, (call.function.name.clone(), args))? {
+                HookOutcome::Keep => {}
+                HookOutcome::Replace(value) => {
+                    current = rhai::serde::from_dynamic(&value).map_err(|e| {
+                        format!(
+                            "{}: {} returned invalid arguments: {}",
+                            script.path.display(),
+                            hook,
+                            e
+                        )
+                    })?;
+                    changed = true;
+                }
+                HookOutcome::Block(reason) => return Ok(HookOutcome::Block(reason)),
+            }
+        }
+
+        if !changed {
+            return Ok(HookOutcome::Keep);
+        }
+        let mut rewritten = call.clone();
+        rewritten.function.arguments = current.to_string();
+        Ok(HookOutcome::Replace(rewritten))
+    }
+
+    fn run_text_hook(&self, hook: &str, text: &str) -> Result<HookOutcome<String>, String> {
+        let mut current = text.to_string();
+        let mut changed = false;
+
+        for script in self.scripts.iter().filter(|s| s.defines(hook)) {
+            match self.call(script, hook, (current.clone(),))? {
+                HookOutcome::Keep => {}
+                HookOutcome::Replace(value) => {
+                    current = value.into_string().map_err(|ty| {
+                        format!(
+                            "{}: {} must return a string, not {}",
+                            script.path.display(),
+                            hook,
+                            ty
+                        )
+                    })?;
+                    changed = true;
+                }
+                HookOutcome::Block(reason) => return Ok(HookOutcome::Block(reason)),
+            }
+        }
+
+        Ok(if changed {
+            HookOutcome::Replace(current)
+        } else {
+            HookOutcome::Keep
+        })
+    }
+
+    fn call(
+        &self,
+        script: &Script,
+        hook: &str,
+        args: impl rhai::FuncArgs,
+    ) -> Result<HookOutcome<Dynamic>, String> {
+        let mut scope = Scope::new();
+        match self
+            .engine
+            .call_fn::<Dynamic>(&mut scope, &script.ast, hook, args)
+        {
+            Ok(value) if value.is_unit() => Ok(HookOutcome::Keep),
+            Ok(value) => Ok(HookOutcome::Replace(value)),
+            Err(e) => match *e {
+                EvalAltResult::ErrorRuntime(reason, _) => {
+                    Ok(HookOutcome::Block(reason.to_string()))
+                }
+                other => Err(format!(
+                    "{}: {} failed: {}",
+                    script.path.display(),
+                    hook,
+                    other
+                )),
+            },
+        }
+    }
+}
+
+impl Default for ScriptHooks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
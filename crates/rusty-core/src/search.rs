@@ -0,0 +1,439 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use futures_util::future::join_all;
+
+use crate::cassette::Cassette;
+use crate::notice;
+use crate::settings::DEFAULT_SEARCH_COUNT;
+
+const SUMMARIZER_URL: &str = "https://api.search.brave.com/res/v1/summarizer/search";
+
+/// The most results Brave gives for one search.
+const MAX_COUNT: u32 = 20;
+
+/// Pages of the same site kept from one search.
+const MAX_PER_SITE: usize = 2;
+
+/// The share of words in common above which two titles are taken to be
+/// the same page.
+const SAME_TITLE: f64 = 0.8;
+
+/// Where the user is, sent to Brave with each search so that questions like
+/// "coffee near me" find local results.
+#[derive(Debug, Clone, Default)]
+pub struct Location {
+    pub city: Option<String>,
+    pub state: Option<String>,
+    /// A two-letter country code, such as `FR`.
+    pub country: Option<String>,
+    pub postal_code: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// An IANA time zone, such as `Europe/Paris`.
+    pub timezone: Option<String>,
+}
+
+impl Location {
+    /// The headers Brave reads the location from.
+    fn headers(&self) -> Vec<(&'static str, String)> {
+        [
+            ("X-Loc-City", self.city.clone()),
+            ("X-Loc-State", self.state.clone()),
+            ("X-Loc-Country", self.country.clone()),
+            ("X-Loc-Postal-Code", self.postal_code.clone()),
+            ("X-Loc-Lat", self.latitude.map(|lat| lat.to_string())),
+            ("X-Loc-Long", self.longitude.map(|long| long.to_string())),
+            ("X-Loc-Timezone", self.timezone.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+    }
+}
+
+pub struct BraveSearchClient {
+    api_key: String,
+    client: reqwest::Client,
+    offline: bool,
+    /// Results of each search.
+    count: u32,
+    /// The Brave Goggle reranking the results, as a URL or a definition.
+    goggle: Option<String>,
+    /// Whether searches also ask Brave's summarizer for an answer.
+    summarizer: bool,
+    /// Sent only when the user turned it on.
+    location: Option<Location>,
+    /// Where searches are recorded to, or played back from.
+    cassette: Option<Arc<Cassette>>,
+}
+
+impl BraveSearchClient {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            client: reqwest::Client::new(),
+            offline: false,
+            count: DEFAULT_SEARCH_COUNT,
+            goggle: None,
+            summarizer: false,
+            location: None,
+            cassette: None,
+        }
+    }
+
+    /// A client that never touches the network and answers with canned results.
+    pub fn offline() -> Self {
+        Self {
+            offline: true,
+            ..Self::new(String::new())
+        }
+    }
+
+    pub fn set_count(&mut self, count: u32) {
+        self.count = count;
+    }
+
+    pub fn set_goggle(&mut self, goggle: Option<String>) {
+        self.goggle = goggle;
+    }
+
+    pub fn set_summarizer(&mut self, summarizer: bool) {
+        self.summarizer = summarizer;
+    }
+
+    pub fn set_location(&mut self, location: Option<Location>) {
+        self.location = location;
+    }
+
+    pub fn set_cassette(&mut self, cassette: Option<Arc<Cassette>>) {
+        self.cassette = cassette;
+    }
+
+    /// Whether Brave accepts the key, found with a one-result search. An
+    /// error means Brave could not be asked.
+    pub async fn check_key(&self) -> Result<bool, String> {
+        let response = self
+            .client
+            .get("https://api.search.brave.com/res/v1/web/search")
+            .header("X-Subscription-Token", &self.api_key)
+            .header("Accept", "application/json")
+            .query(&[("q", "rust"), ("count", "1")])
+            .send()
+            .await
+            .map_err(|e| format!("Brave Search could not be reached: {}", e))?;
+        Ok(!matches!(response.status().as_u16(), 401 | 403 | 422))
+    }
+
+    /// Searches the web. A `goggle` given here is used instead of the one
+    /// set for the client.
+    pub async fn search(
+        &self,
+        query: &str,
+        goggle: Option<&str>,
+    ) -> Result<String, reqwest::Error> {
+        self.search_all(&[query], goggle).await
+    }
+
+    /// Searches the web for a question asked several ways at once, and
+    /// merges the results into one list: taken from each search in turn,
+    /// with duplicates dropped, and ranked by the words of all the queries.
+    /// A summary is only asked for the first query. Fails only when every
+    /// search does.
+    pub async fn search_all(
+        &self,
+        queries: &[&str],
+        goggle: Option<&str>,
+    ) -> Result<String, reqwest::Error> {
+        let searches = join_all(queries.iter().map(|query| self.results(query, goggle))).await;
+        let mut error = None;
+        let mut responses = Vec::new();
+        for search in searches {
+            match search {
+                Ok(json) => responses.push(json),
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+        if responses.is_empty() {
+            if let Some(e) = error {
+                return Err(e);
+            }
+        }
+
+        let key = responses
+            .first()
+            .and_then(|json| json["summarizer"]["key"].as_str())
+            .map(str::to_string);
+        let query = queries.join(" ");
+        let results = match responses.len() {
+            1 => self.format_results(responses.remove(0), &query),
+            _ => self.format_results(interleave(&responses), &query),
+        };
+        let summary = match key {
+            Some(key) => self.summary(&key).await,
+            None => None,
+        };
+        Ok(match summary {
+            Some(summary) => format!("{}\n{}", summary, results),
+            None => results,
+        })
+    }
+
+    /// The response to one search.
+    async fn results(
+        &self,
+        query: &str,
+        goggle: Option<&str>,
+    ) -> Result<serde_json::Value, reqwest::Error> {
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replay()) {
+            // A search the cassette does not have finds nothing, as a
+            // request error cannot be made up.
+            return Ok(cassette.play("search", query).unwrap_or_else(|e| {
+                notice::warn(&e);
+                serde_json::json!({})
+            }));
+        }
+        if self.offline {
+            return Ok(Self::canned_results(query));
+        }
+
+        let url = "https://api.search.brave.com/res/v1/web/search";
+        // Twice as many as are shown, so that dropping duplicates still
+        // leaves enough.
+        let count = (self.count * 2).min(MAX_COUNT).to_string();
+        let mut params = vec![("q", query), ("count", &count)];
+        if let Some(goggle) = goggle.or(self.goggle.as_deref()) {
+            params.push(("goggles", goggle));
+        }
+        if self.summarizer {
+            params.push(("summary", "1"));
+        }
+        let mut request = self
+            .client
+            .get(url)
+            .header("X-Subscription-Token", &self.api_key)
+            .header("Accept", "application/json")
+            .query(&params);
+        for (name, value) in self.location.iter().flat_map(Location::headers) {
+            request = request.header(name, value);
+        }
+        let json: serde_json::Value = request.send().await?.json().await?;
+        if let Some(cassette) = &self.cassette {
+            let request = serde_json::json!({ "query": params });
+            if let Err(e) = cassette.add("search", query, request, json.clone()) {
+                notice::warn(&e);
+            }
+        }
+        Ok(json)
+    }
+
+    /// Brave's summary of a search, fetched with the key its results gave,
+    /// with the sources it cites numbered at the end. A summary that cannot
+    /// be had, as with plans without the summarizer, is left out quietly.
+    async fn summary(&self, key: &str) -> Option<String> {
+        let json = self.summary_response(key).await?;
+        if json["status"].as_str() != Some("complete") {
+            return None;
+        }
+
+        let mut text = String::new();
+        let mut sources: Vec<&str> = Vec::new();
+        for part in json["summary"].as_array()? {
+            match part["type"].as_str() {
+                Some("token") => text.push_str(part["data"].as_str().unwrap_or_default()),
+                Some("inline_reference") => {
+                    let Some(url) = part["data"]["url"].as_str() else {
+                        continue;
+                    };
+                    let number = match sources.iter().position(|source| *source == url) {
+                        Some(i) => i + 1,
+                        None => {
+                            sources.push(url);
+                            sources.len()
+                        }
+                    };
+                    text.push_str(&format!(" [{}]", number));
+                }
+                _ => {}
+            }
+        }
+        if text.trim().is_empty() {
+            return None;
+        }
+
+        let mut output = format!("### Brave Summary\n\n{}\n", text.trim());
+        if !sources.is_empty() {
+            output.push('\n');
+            for (i, url) in sources.iter().enumerate() {
+                output.push_str(&format!("[{}] {}\n", i + 1, url));
+            }
+        }
+        Some(output)
+    }
+
+    /// The summarizer's response for `key`.
+    async fn summary_response(&self, key: &str) -> Option<serde_json::Value> {
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replay()) {
+            return cassette.play("summary", key).ok();
+        }
+        let json: serde_json::Value = self
+            .client
+            .get(SUMMARIZER_URL)
+            .header("X-Subscription-Token", &self.api_key)
+            .header("Accept", "application/json")
+            .query(&[("key", key)])
+            .send()
+            .await
+            .ok()?
+            .json()
+            .await
+            .ok()?;
+        if let Some(cassette) = &self.cassette {
+            let request = serde_json::json!({ "key": key });
+            if let Err(e) = cassette.add("summary", key, request, json.clone()) {
+                notice::warn(&e);
+            }
+        }
+        Some(json)
+    }
+
+    fn canned_results(query: &str) -> serde_json::Value {
+        serde_json::json!({
+            "web": {
+                "results": [
+                    {
+                        "title": format!("Mock result for {}", query),
+                        "description": format!("Offline placeholder snippet about {}.", query),
+                        "url": "https://example.com/mock"
+                    }
+                ]
+            }
+        })
+    }
+
+    /// The results as Markdown for the model: duplicates dropped, the ones
+    /// whose title and snippet mention more of the query's words first, and
+    /// at most `count` of them.
+    pub fn format_results(&self, json: serde_json::Value, query: &str) -> String {
+        let mut output = String::from("### Brave Search Results\n\n");
+
+        let Some(results) = json
+            .get("web")
+            .and_then(|w| w.get("results"))
+            .and_then(|r| r.as_array())
+        else {
+            output.push_str("Failed to parse search results.\n");
+            return output;
+        };
+        let results = rank(dedupe(results), query);
+        if results.is_empty() {
+            output.push_str("No results found.\n");
+        }
+        for (i, result) in results.iter().enumerate().take(self.count as usize) {
+            output.push_str(&format!("{}. **{}**\n", i + 1, result.title));
+            output.push_str(&format!("   - Snippet: {}\n", result.description));
+            output.push_str(&format!("   - URL: {}\n\n", result.url));
+        }
+
+        output
+    }
+}
+
+/// The web results of several searches as those of one: the first of
+/// each, then the second of each, and so on.
+fn interleave(responses: &[serde_json::Value]) -> serde_json::Value {
+    let lists: Vec<&Vec<serde_json::Value>> = responses
+        .iter()
+        .filter_map(|json| json["web"]["results"].as_array())
+        .collect();
+    let longest = lists
+        .iter()
+        .map(|list| list.len())
+        .max()
+        .unwrap_or_default();
+    let results: Vec<&serde_json::Value> = (0..longest)
+        .flat_map(|i| lists.iter().filter_map(move |list| list.get(i)))
+        .collect();
+    serde_json::json!({ "web": { "results": results } })
+}
+
+struct SearchResult<'a> {
+    title: &'a str,
+    description: &'a str,
+    url: &'a str,
+}
+
+/// The results without repeats: the same page under another URL, more than
+/// `MAX_PER_SITE` pages of one site, or a title all but equal to one
+/// already kept, as with mirrors and syndicated copies.
+fn dedupe(results: &[serde_json::Value]) -> Vec<SearchResult<'_>> {
+    let mut kept: Vec<SearchResult> = Vec::new();
+    let mut pages = HashSet::new();
+    let mut sites: HashMap<String, usize> = HashMap::new();
+    for result in results {
+        let result = SearchResult {
+            title: result["title"].as_str().unwrap_or("No Title"),
+            description: result["description"].as_str().unwrap_or(""),
+            url: result["url"].as_str().unwrap_or(""),
+        };
+        let (site, page) = normalize(result.url);
+        if !pages.insert(page) {
+            continue;
+        }
+        let on_site = sites.entry(site).or_default();
+        if *on_site >= MAX_PER_SITE {
+            continue;
+        }
+        let title = words(result.title);
+        if !title.is_empty()
+            && kept
+                .iter()
+                .any(|other| similarity(&title, &words(other.title)) >= SAME_TITLE)
+        {
+            continue;
+        }
+        *on_site += 1;
+        kept.push(result);
+    }
+    kept
+}
+
+/// Sorts the results by how many of the query's words their title and
+/// snippet contain, keeping Brave's order between equals.
+fn rank<'a>(mut results: Vec<SearchResult<'a>>, query: &str) -> Vec<SearchResult<'a>> {
+    let terms = words(query);
+    results.sort_by_cached_key(|result| {
+        let text = words(&format!("{} {}", result.title, result.description));
+        Reverse(terms.intersection(&text).count())
+    });
+    results
+}
+
+/// A URL's site, without `www.`, and the page, without the scheme, the
+/// fragment or a trailing slash, both in lowercase.
+fn normalize(url: &str) -> (String, String) {
+    let url = url.to_lowercase();
+    let rest = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let rest = rest.split('#').next().unwrap_or_default();
+    let rest = rest.strip_prefix("www.").unwrap_or(rest);
+    let site = rest.split(['/', '?']).next().unwrap_or_default();
+    (site.to_string(), rest.trim_end_matches('/').to_string())
+}
+
+/// The lowercase words of a text, leaving out single letters.
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The share of words two texts have in common, from 0 to 1.
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
@@ -0,0 +1,597 @@
+//! The long-running services behind `rusty discord`, `slack`, `telegram`,
+//! `webhook`, `schedule` and `watch`: each takes what arrives, has the
+//! [`Agent`] answer it, and sends the answer back, until interrupted.
+//!
+//! The front end resolves the keys and arguments, and shows what a service
+//! is doing through a [`Reporter`].
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::agent::Agent;
+use crate::config::{Config, ScheduledPrompt};
+use crate::conversation::ConversationManager;
+use crate::discord::{self, DiscordBot, Event};
+use crate::encryption::Cipher;
+use crate::models::{context_window, MODELS};
+use crate::paths;
+use crate::prompts::{Attachment, PromptLibrary};
+use crate::schedule::{self, Schedule};
+use crate::sessions::{Session, SessionStore};
+use crate::slack::{self, SlackBot};
+use crate::telegram::{self, TelegramBot};
+use crate::tools::summarize_arguments;
+use crate::watch::Watcher;
+use crate::webhook;
+
+/// What `rusty watch` asks about each saved file when no prompt is given.
+pub const WATCH_PROMPT: &str = "Review the file I just saved. Point out bugs, unclear code and \
+anything risky, most important first; if it looks fine, say so in a sentence.";
+
+/// How a service tells the person running it what it is doing.
+pub trait Reporter {
+    /// A line about the service itself, such as where it listens.
+    fn status(&self, line: &str);
+    /// A step of the work, such as whom it answers or a tool it called.
+    fn step(&self, step: &str);
+    /// An answer that has nowhere else to go.
+    fn answer(&self, answer: &str);
+    /// Something that went wrong without stopping the service.
+    fn error(&self, error: &str);
+}
+
+/// Sends each file saved under `target`, a directory or a single file, to
+/// the model with `prompt`, or the configured one, and reports the answer.
+/// The reviews are one conversation, so later ones can refer to earlier
+/// ones.
+pub async fn watch(
+    config: &Config,
+    mut agent: Agent,
+    target: &str,
+    prompt: Option<String>,
+    reporter: &dyn Reporter,
+) -> Result<(), String> {
+    let prompt = prompt
+        .or_else(|| config.watch.prompt.clone())
+        .unwrap_or_else(|| WATCH_PROMPT.to_string());
+    let model = config
+        .watch
+        .model
+        .clone()
+        .unwrap_or_else(|| MODELS[0].to_string());
+
+    let mut watcher = Watcher::new(Path::new(target))?;
+    let mut conversation = ConversationManager::new();
+    conversation.set_context(config.context.clone());
+    // What each file held when it was last reviewed, so saving without
+    // changes does not ask again.
+    let mut reviewed: HashMap<PathBuf, String> = HashMap::new();
+    reporter.status(&format!(
+        "Watching {} with {}. Save a file to have it reviewed; Ctrl+C stops it.",
+        target, model
+    ));
+    while let Some(paths) = watcher.changed().await {
+        for path in paths {
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            if reviewed.get(&path) == Some(&content) {
+                continue;
+            }
+            let shown = std::env::current_dir()
+                .ok()
+                .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+                .unwrap_or_else(|| path.clone());
+            reporter.step(&format!("Reviewing {}", shown.display()));
+            let attachment = Attachment {
+                path: shown.display().to_string(),
+                content: content.clone(),
+            };
+            let result = agent
+                .reply(
+                    &mut conversation,
+                    &model,
+                    context_window(&model),
+                    &format!("{}\n\n{}", prompt, attachment.to_markdown()),
+                    &mut |call| {
+                        reporter.step(&format!(
+                            "  ⎿ {}({})",
+                            call.function.name,
+                            summarize_arguments(&call.function.arguments)
+                        ))
+                    },
+                )
+                .await;
+            match result {
+                Ok(answer) => {
+                    reporter.answer(&answer);
+                    reviewed.insert(path, content);
+                }
+                Err(e) => reporter.error(&e),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `jobs` on their cron schedules. Each answer is added to the job's
+/// session, kept with `cipher` when storage is encrypted, or appended to the
+/// schedule log.
+pub async fn schedule(
+    config: &Config,
+    mut agent: Agent,
+    jobs: Vec<ScheduledPrompt>,
+    cipher: Option<Cipher>,
+    reporter: &dyn Reporter,
+) -> Result<(), String> {
+    let schedules = jobs
+        .iter()
+        .map(|job| Schedule::parse(&job.cron))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sessions = jobs
+        .iter()
+        .any(|job| job.session.is_some())
+        .then(|| SessionStore::new(&paths::sessions_dir(), cipher));
+    let model = config
+        .schedule
+        .model
+        .clone()
+        .unwrap_or_else(|| MODELS[0].to_string());
+
+    reporter.status(&format!(
+        "Scheduled {} prompt{}, answering with {}. Ctrl+C stops it.",
+        jobs.len(),
+        if jobs.len() == 1 { "" } else { "s" },
+        model
+    ));
+    for (job, schedule) in jobs.iter().zip(&schedules) {
+        reporter.status(&format!("  {}  {}", schedule.pattern(), job.prompt));
+    }
+    while let Some((time, due)) = schedule::next_due(&schedules).await {
+        for job in due.iter().map(|&i| &jobs[i]) {
+            reporter.step(&format!("Running \"{}\"", job.prompt));
+            let mut session = match (&job.session, &sessions) {
+                (Some(title), Some(store)) => Some(
+                    store
+                        .list()
+                        .into_iter()
+                        .find(|s| s.title.as_deref() == Some(title))
+                        .unwrap_or_else(|| {
+                            let mut session = Session::new(&model);
+                            session.title = Some(title.clone());
+                            session
+                        }),
+                ),
+                _ => None,
+            };
+
+            // A session's earlier runs are part of the conversation, so a
+            // briefing can tell what is new since the last one.
+            let mut conversation = ConversationManager::new();
+            conversation.set_context(config.context.clone());
+            for message in session.iter().flat_map(|s| &s.messages) {
+                conversation.add_message(message.clone());
+            }
+            let result = agent
+                .reply(
+                    &mut conversation,
+                    &model,
+                    context_window(&model),
+                    &job.prompt,
+                    &mut |call| {
+                        reporter.step(&format!(
+                            "  ⎿ {}({})",
+                            call.function.name,
+                            summarize_arguments(&call.function.arguments)
+                        ))
+                    },
+                )
+                .await;
+
+            let saved = match (&mut session, &sessions) {
+                (Some(session), Some(store)) if result.is_ok() => {
+                    session.messages = conversation.get_messages().to_vec();
+                    session.model = model.clone();
+                    store.save(session)
+                }
+                _ => schedule::append_log(&paths::schedule_log(), &time, &job.prompt, &result),
+            };
+            if let Err(e) = result.and(saved) {
+                reporter.error(&e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Listens for POSTed JSON payloads, gives each to its route's prompt
+/// template in a conversation of its own, and POSTs the answer to the
+/// route's callback, or reports it when there is none. With a `secret`,
+/// requests must carry it. Payloads are answered one at a time, in the
+/// order they arrive.
+pub async fn webhook(
+    config: &Config,
+    mut agent: Agent,
+    secret: Option<String>,
+    reporter: &dyn Reporter,
+) -> Result<(), String> {
+    let settings = &config.webhook;
+    if settings.routes.is_empty() {
+        return Err(format!(
+            "No webhook routes are set up; add a [webhook.routes.<name>] table to {}",
+            Config::path().display()
+        ));
+    }
+    let library = PromptLibrary::load(&paths::prompts_dir());
+    for (name, route) in &settings.routes {
+        if library.get(&route.prompt).is_none() {
+            return Err(format!(
+                "The route '{}' uses the prompt '{}', which is not in {}",
+                name,
+                route.prompt,
+                paths::prompts_dir().display()
+            ));
+        }
+    }
+    if secret.is_none() {
+        reporter.error(
+            "RUSTY_WEBHOOK_SECRET is not set, so anyone who can reach the listener can run prompts",
+        );
+    }
+    let model = settings
+        .model
+        .clone()
+        .unwrap_or_else(|| MODELS[0].to_string());
+
+    let mut jobs = webhook::listen(
+        &settings.listen,
+        secret,
+        settings.routes.keys().cloned().collect(),
+    )
+    .await?;
+    reporter.status(&format!(
+        "Listening on http://{} for {}, answering with {}. Ctrl+C stops it.",
+        settings.listen,
+        settings
+            .routes
+            .keys()
+            .map(|name| format!("/{}", name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        model
+    ));
+    while let Some(job) = jobs.recv().await {
+        let route = &settings.routes[&job.route];
+        let Some(template) = library.get(&route.prompt) else {
+            continue;
+        };
+        reporter.step(&format!("Running /{} #{}", job.route, job.id));
+
+        let mut conversation = ConversationManager::new();
+        conversation.set_context(config.context.clone());
+        let payload = serde_json::to_string_pretty(&job.payload).unwrap_or_default();
+        let result = agent
+            .reply(
+                &mut conversation,
+                &model,
+                context_window(&model),
+                &template.expand(&payload, &[]),
+                &mut |call| {
+                    reporter.step(&format!(
+                        "  ⎿ {}({})",
+                        call.function.name,
+                        summarize_arguments(&call.function.arguments)
+                    ))
+                },
+            )
+            .await;
+        let body = match &result {
+            Ok(answer) => serde_json::json!({ "id": job.id, "route": job.route, "result": answer }),
+            Err(e) => {
+                reporter.error(e);
+                serde_json::json!({ "id": job.id, "route": job.route, "error": e })
+            }
+        };
+        match &route.callback {
+            Some(callback) => {
+                if let Err(e) = webhook::deliver(callback, &body).await {
+                    reporter.error(&e);
+                }
+            }
+            None => {
+                if let Ok(answer) = &result {
+                    reporter.answer(answer);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Answers mentions and direct messages on Discord as the bot with `token`,
+/// keeping a conversation per channel.
+pub async fn discord(
+    config: &Config,
+    mut agent: Agent,
+    token: String,
+    reporter: &dyn Reporter,
+) -> Result<(), String> {
+    let model = config
+        .discord
+        .model
+        .clone()
+        .unwrap_or_else(|| MODELS[0].to_string());
+
+    let bot = DiscordBot::new(token);
+    let mut events = bot.listen();
+    let mut conversations: HashMap<String, ConversationManager> = HashMap::new();
+    reporter.status("Connecting to Discord...");
+    while let Some(event) = events.recv().await {
+        let message = match event? {
+            Event::Ready(name) => {
+                reporter.status(&format!(
+                    "Connected as {}, answering with {}. Mention the bot or send it a direct message; Ctrl+C stops it.",
+                    name, model
+                ));
+                continue;
+            }
+            Event::Message(message) => message,
+        };
+
+        let conversation = conversations
+            .entry(message.channel_id.clone())
+            .or_insert_with(|| {
+                let mut conversation = ConversationManager::new();
+                conversation.set_context(config.context.clone());
+                conversation.set_system_prompt(Some(discord::SYSTEM_PROMPT.to_string()));
+                conversation
+            });
+        reporter.step(&format!("Answering {}", message.author));
+        let typing = bot.keep_typing(&message.channel_id);
+        let result = agent
+            .reply(
+                conversation,
+                &model,
+                context_window(&model),
+                &format!("{}: {}", message.author, message.content),
+                &mut |_| {},
+            )
+            .await;
+        typing.abort();
+
+        let text = result.unwrap_or_else(|e| {
+            reporter.error(&e);
+            format!("Sorry, I could not answer that: {}", e)
+        });
+        if let Err(e) = bot.reply(&message, &text).await {
+            reporter.error(&e);
+        }
+    }
+    Ok(())
+}
+
+/// Answers in Slack threads over Socket Mode with the app and bot tokens,
+/// keeping a conversation per thread. Tool calls are posted to the thread
+/// as they run.
+pub async fn slack(
+    config: &Config,
+    mut agent: Agent,
+    app_token: String,
+    bot_token: String,
+    reporter: &dyn Reporter,
+) -> Result<(), String> {
+    let model = config
+        .slack
+        .model
+        .clone()
+        .unwrap_or_else(|| MODELS[0].to_string());
+    let window = config
+        .slack
+        .context_tokens
+        .map_or(context_window(&model), |tokens| {
+            tokens.min(context_window(&model))
+        });
+
+    let bot = SlackBot::connect(app_token, bot_token).await?;
+    let mut events = bot.listen();
+    let mut threads: HashMap<(String, String), ConversationManager> = HashMap::new();
+    reporter.status(&format!(
+        "Connected to Slack, answering with {}. Mention the app or send it a direct message; Ctrl+C stops it.",
+        model
+    ));
+    while let Some(message) = events.recv().await {
+        let message = message?;
+        let key = (message.channel.clone(), message.thread_ts.clone());
+        // Follow-ups in a thread the bot already answers in need no mention.
+        if message.text.is_empty()
+            || !(message.mentioned || message.direct || threads.contains_key(&key))
+        {
+            continue;
+        }
+        let conversation = threads.entry(key).or_insert_with(|| {
+            let mut conversation = ConversationManager::new();
+            conversation.set_context(config.context.clone());
+            conversation.set_system_prompt(Some(slack::SYSTEM_PROMPT.to_string()));
+            conversation
+        });
+        reporter.step(&format!("Answering {}", message.user));
+
+        // Steps are posted from a task of their own, in order, while the
+        // turn goes on; what could not be posted is reported after it.
+        let (steps, mut pending) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let poster = tokio::spawn({
+            let bot = bot.clone();
+            let (channel, thread) = (message.channel.clone(), message.thread_ts.clone());
+            async move {
+                let mut errors = Vec::new();
+                while let Some(step) = pending.recv().await {
+                    if let Err(e) = bot.post(&channel, &thread, &step).await {
+                        errors.push(e);
+                    }
+                }
+                errors
+            }
+        });
+        let result = agent
+            .reply(
+                conversation,
+                &model,
+                window,
+                &format!("<@{}>: {}", message.user, message.text),
+                &mut |call| {
+                    let _ = steps.send(format!(
+                        "⎿ `{}({})`",
+                        call.function.name,
+                        summarize_arguments(&call.function.arguments)
+                    ));
+                },
+            )
+            .await;
+        drop(steps);
+        for e in poster.await.unwrap_or_default() {
+            reporter.error(&e);
+        }
+
+        let text = result.unwrap_or_else(|e| {
+            reporter.error(&e);
+            format!("Sorry, I could not answer that: {}", e)
+        });
+        if let Err(e) = bot.post(&message.channel, &message.thread_ts, &text).await {
+            reporter.error(&e);
+        }
+    }
+    Ok(())
+}
+
+/// A Telegram chat's history and the model answering in it.
+struct TelegramChat {
+    conversation: ConversationManager,
+    model: String,
+}
+
+/// Answers Telegram messages by long polling as the bot with `token`,
+/// keeping a conversation and a model per chat.
+pub async fn telegram(
+    config: &Config,
+    mut agent: Agent,
+    token: String,
+    reporter: &dyn Reporter,
+) -> Result<(), String> {
+    let default_model = config
+        .telegram
+        .model
+        .clone()
+        .unwrap_or_else(|| MODELS[0].to_string());
+
+    let mut bot = TelegramBot::connect(token).await?;
+    if let Err(e) = bot
+        .set_commands(&[
+            ("model", "Show or change the model"),
+            ("clear", "Start a new conversation"),
+        ])
+        .await
+    {
+        reporter.error(&e);
+    }
+    reporter.status(&format!(
+        "Connected as @{}. Message the bot, or mention it in a group; Ctrl+C stops it.",
+        bot.username()
+    ));
+    if config.telegram.users.is_empty() {
+        reporter.error("Anyone can use this bot; list who may in [telegram] users in config.toml");
+    }
+
+    let mut chats: HashMap<i64, TelegramChat> = HashMap::new();
+    loop {
+        let messages = match bot.poll().await {
+            Ok(messages) => messages,
+            Err(e) => {
+                reporter.error(&e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        for message in messages {
+            let allowed = config.telegram.users.is_empty()
+                || config.telegram.users.iter().any(|user| {
+                    let user = user.trim_start_matches('@');
+                    user == message.user_id.to_string()
+                        || message
+                            .username
+                            .as_deref()
+                            .is_some_and(|name| name.eq_ignore_ascii_case(user))
+                });
+            if !allowed {
+                continue;
+            }
+
+            let chat = chats.entry(message.chat_id).or_insert_with(|| {
+                let mut conversation = ConversationManager::new();
+                conversation.set_context(config.context.clone());
+                conversation.set_system_prompt(Some(telegram::SYSTEM_PROMPT.to_string()));
+                TelegramChat {
+                    conversation,
+                    model: default_model.clone(),
+                }
+            });
+            let (command, args) = message
+                .text
+                .split_once(' ')
+                .unwrap_or((message.text.as_str(), ""));
+            let text = match command {
+                "/start" | "/help" => "Send me a message and I will answer, searching the web when it helps. `/model` shows or changes the model, and `/clear` starts a new conversation.".to_string(),
+                "/clear" => {
+                    chat.conversation.clear();
+                    "Started a new conversation.".to_string()
+                }
+                "/model" if args.trim().is_empty() => {
+                    let mut text = String::from("Models (`/model <number>` to switch):\n");
+                    for (i, model) in MODELS.iter().enumerate() {
+                        let current = if *model == chat.model { " ← current" } else { "" };
+                        text.push_str(&format!("{}. `{}`{}\n", i + 1, model, current));
+                    }
+                    text
+                }
+                "/model" => {
+                    let choice = args.trim();
+                    let model = choice
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| n.checked_sub(1))
+                        .and_then(|i| MODELS.get(i))
+                        .or_else(|| MODELS.iter().find(|model| **model == choice));
+                    match model {
+                        Some(model) => {
+                            chat.model = model.to_string();
+                            format!("Now answering with `{}`.", model)
+                        }
+                        None => format!("No model `{}`; send `/model` for the list.", choice),
+                    }
+                }
+                _ => {
+                    reporter.step(&format!("Answering {}", message.author));
+                    let typing = bot.keep_typing(message.chat_id);
+                    let result = agent
+                        .reply(
+                            &mut chat.conversation,
+                            &chat.model,
+                            context_window(&chat.model),
+                            &format!("{}: {}", message.author, message.text),
+                            &mut |_| {},
+                        )
+                        .await;
+                    typing.abort();
+                    result.unwrap_or_else(|e| {
+                        reporter.error(&e);
+                        format!("Sorry, I could not answer that: {}", e)
+                    })
+                }
+            };
+            if let Err(e) = bot.reply(&message, &text).await {
+                reporter.error(&e);
+            }
+        }
+    }
+}
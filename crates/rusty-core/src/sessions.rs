@@ -0,0 +1,344 @@
+//! Saved conversations, one JSON file per session in the sessions directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::{self, Cipher};
+use crate::message::Message;
+use crate::provider::Provider;
+use crate::settings::Settings;
+
+/// Longest title kept, in characters.
+const MAX_TITLE_CHARS: usize = 60;
+
+const TITLE_PROMPT: &str = "Write a short title of at most six words for the conversation below. \
+Reply with the title only, without quotes or trailing punctuation.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Unix timestamps in seconds.
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub model: String,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Lowercase labels such as `work` or `research`, kept sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// What `/set` changed, when it differs from the configuration, and
+    /// always the seed, so a run can be repeated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Settings>,
+    pub messages: Vec<Message>,
+}
+
+impl Session {
+    /// A new, unsaved session. The id is derived from the current time.
+    pub fn new(model: &str) -> Self {
+        let now = now();
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        Self {
+            id: format!("{:x}", millis),
+            title: None,
+            created_at: now,
+            updated_at: now,
+            model: model.to_string(),
+            system_prompt: None,
+            tags: Vec::new(),
+            settings: None,
+            messages: Vec::new(),
+        }
+    }
+
+    /// The title, or the start of the first prompt for untitled sessions.
+    pub fn display_title(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
+        self.messages
+            .iter()
+            .find(|m| m.role == "user")
+            .and_then(|m| m.content.as_deref())
+            .map(|text| truncate(text.lines().next().unwrap_or(""), MAX_TITLE_CHARS))
+            .unwrap_or_else(|| "Untitled session".to_string())
+    }
+
+    /// Adds a tag, ignoring a leading `#` and letter case. Returns false if
+    /// the session already had it.
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() || self.tags.contains(&tag) {
+            return false;
+        }
+        self.tags.push(tag);
+        self.tags.sort();
+        true
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        let before = self.tags.len();
+        self.tags.retain(|t| *t != tag);
+        self.tags.len() != before
+    }
+
+    /// Whether the session carries every tag in `tags`.
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter()
+            .all(|tag| self.tags.contains(&normalize_tag(tag)))
+    }
+
+    /// Looks for `query` (case-insensitive) in the title and messages,
+    /// returning a snippet around the first hit.
+    pub fn search(&self, query: &str) -> Option<String> {
+        let query = query.to_lowercase();
+        let title = self.display_title();
+        if title.to_lowercase().contains(&query) {
+            return Some(title);
+        }
+        self.messages
+            .iter()
+            .filter_map(|m| m.content.as_deref())
+            .find_map(|text| snippet(text, &query))
+    }
+
+    /// Number of prompts the user sent.
+    pub fn turns(&self) -> usize {
+        self.messages.iter().filter(|m| m.role == "user").count()
+    }
+}
+
+pub struct SessionStore {
+    dir: PathBuf,
+    /// Encrypts sessions on save when set; plain sessions still load.
+    cipher: Option<Cipher>,
+}
+
+impl SessionStore {
+    pub fn new(dir: &Path, cipher: Option<Cipher>) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            cipher,
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Writes `session`, stamping its update time.
+    pub fn save(&self, session: &mut Session) -> Result<(), String> {
+        session.updated_at = now();
+        self.write(session)
+    }
+
+    /// Writes `session` with the times it has, as for sessions imported
+    /// from another service.
+    pub fn write(&self, session: &Session) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        let path = self.path(&session.id);
+        let json = serde_json::to_string_pretty(session).map_err(|e| e.to_string())?;
+        encryption::write_text(&path, &json, self.cipher.as_ref())
+    }
+
+    pub fn load(&self, id: &str) -> Result<Session, String> {
+        let path = self.path(id);
+        let text = encryption::read_text(&path, self.cipher.as_ref())?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid session {}: {}", path.display(), e))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        let path = self.path(id);
+        fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+    }
+
+    /// Where the in-progress conversation is kept between turns. It has no
+    /// `.json` extension, so it never shows up as a saved session.
+    fn recovery_path(&self) -> PathBuf {
+        self.dir.join(".recovery")
+    }
+
+    /// Records the in-progress conversation so it survives a crash.
+    pub fn write_recovery(&self, session: &Session) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        let json = serde_json::to_string(session).map_err(|e| e.to_string())?;
+        encryption::write_text(&self.recovery_path(), &json, self.cipher.as_ref())
+    }
+
+    /// The conversation left behind by a session that did not exit cleanly.
+    pub fn recovery(&self) -> Option<Session> {
+        let path = self.recovery_path();
+        if !path.exists() {
+            return None;
+        }
+        let text = encryption::read_text(&path, self.cipher.as_ref()).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub fn clear_recovery(&self) {
+        let _ = fs::remove_file(self.recovery_path());
+    }
+
+    /// Where the prompt being typed is kept, next to the recovery file.
+    fn draft_path(&self) -> PathBuf {
+        self.dir.join(".draft")
+    }
+
+    /// Records the prompt being typed so that it survives Ctrl+C, a closed
+    /// terminal or a crash; an empty one removes it.
+    pub fn write_draft(&self, text: &str) -> Result<(), String> {
+        if text.trim().is_empty() {
+            self.clear_draft();
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        encryption::write_text(&self.draft_path(), text, self.cipher.as_ref())
+    }
+
+    /// The prompt left unsent last time, if any.
+    pub fn draft(&self) -> Option<String> {
+        let path = self.draft_path();
+        if !path.exists() {
+            return None;
+        }
+        let text = encryption::read_text(&path, self.cipher.as_ref()).ok()?;
+        (!text.trim().is_empty()).then_some(text)
+    }
+
+    pub fn clear_draft(&self) {
+        let _ = fs::remove_file(self.draft_path());
+    }
+
+    /// Every readable session, most recently updated first.
+    pub fn list(&self) -> Vec<Session> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut sessions: Vec<Session> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .filter_map(|p| encryption::read_text(&p, self.cipher.as_ref()).ok())
+            .filter_map(|text| serde_json::from_str(&text).ok())
+            .collect();
+        // Ids grow with creation time, which breaks ties within a second.
+        sessions.sort_by(|a, b| {
+            (b.updated_at, b.id.len(), &b.id).cmp(&(a.updated_at, a.id.len(), &a.id))
+        });
+        sessions
+    }
+}
+
+/// Asks the model for a short title based on the first exchanges of
+/// `messages`. Returns `None` if the request fails or the reply is empty.
+pub async fn generate_title(
+    provider: &Provider,
+    model: &str,
+    messages: &[Message],
+) -> Option<String> {
+    let mut transcript = String::new();
+    let exchanges = messages
+        .iter()
+        .filter(|m| m.role == "user" || (m.role == "assistant" && m.tool_calls.is_none()))
+        .take(4);
+    for message in exchanges {
+        let content = message.content.as_deref().unwrap_or("");
+        transcript.push_str(&format!("{}: {}\n\n", message.role, truncate(content, 500)));
+    }
+    if transcript.is_empty() {
+        return None;
+    }
+
+    let request = [Message::system(TITLE_PROMPT), Message::user(&transcript)];
+    let reply = provider.chat_completion(model, &request, None).await.ok()?;
+    let title = reply.content?;
+    let title = title
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?
+        .trim_matches(|c: char| c == '"' || c == '\'' || c == '*' || c == '#' || c == '.')
+        .trim();
+    (!title.is_empty()).then(|| truncate(title, MAX_TITLE_CHARS))
+}
+
+/// "just now", "5m ago", "3h ago", or "2d ago" for a Unix timestamp.
+pub fn time_ago(timestamp: u64) -> String {
+    let elapsed = now().saturating_sub(timestamp);
+    match elapsed {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", elapsed / 60),
+        3600..86400 => format!("{}h ago", elapsed / 3600),
+        _ => format!("{}d ago", elapsed / 86400),
+    }
+}
+
+/// Splits a filter such as `"#work rust lifetimes"` into its tags and the
+/// remaining search text.
+pub fn parse_filter(filter: &str) -> (Vec<String>, String) {
+    let mut tags = Vec::new();
+    let mut words = Vec::new();
+    for word in filter.split_whitespace() {
+        match word.strip_prefix('#') {
+            Some(tag) if !tag.is_empty() => tags.push(normalize_tag(tag)),
+            _ => words.push(word),
+        }
+    }
+    (tags, words.join(" "))
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+/// About 30 characters either side of the first match of `query`, which must
+/// already be lowercase, on one line.
+fn snippet(text: &str, query: &str) -> Option<String> {
+    let lower: Vec<char> = text.to_lowercase().chars().collect();
+    let needle: Vec<char> = query.chars().collect();
+    let start = lower
+        .windows(needle.len().max(1))
+        .position(|w| w == needle)?;
+    let chars: Vec<char> = text.chars().collect();
+    // Lowercasing can change lengths; fall back to the start of the text.
+    let start = if lower.len() == chars.len() { start } else { 0 };
+    let from = start.saturating_sub(30);
+    let to = (start + needle.len() + 30).min(chars.len());
+    let mut out: String = chars[from..to].iter().collect();
+    out = out.split_whitespace().collect::<Vec<_>>().join(" ");
+    if from > 0 {
+        out.insert(0, '…');
+    }
+    if to < chars.len() {
+        out.push('…');
+    }
+    Some(out)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(max - 1).collect();
+    out.push('…');
+    out
+}
@@ -0,0 +1,240 @@
+//! The settings `/set` and `/get` work on: how the model writes its
+//! answers, how many search results it gets and how they are ranked, and
+//! which tools it is offered.
+//! They come from `[settings]` in the configuration, and a session keeps the
+//! ones it was changed to.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::Generation;
+
+/// Search results given to the model when `search_count` is not set.
+pub const DEFAULT_SEARCH_COUNT: u32 = 5;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    #[serde(flatten)]
+    pub generation: Generation,
+    /// Results of each Brave search, from 1 to 20.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_count: Option<u32>,
+    /// The Brave Goggle reranking search results: the URL of one, or its
+    /// definition.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goggle: Option<String>,
+    /// Whether searches also ask Brave's summarizer for an answer with
+    /// citations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summarizer: Option<bool>,
+    /// Whether answers that drew on the web get numbered citations and a
+    /// list of sources.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub citations: Option<bool>,
+    /// Whether the model must call a tool on the first request of a turn:
+    /// `auto`, `none`, `required`, or the name of the tool.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<String>,
+    /// Tools turned off, or on for those offered only on request, by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tools: BTreeMap<String, bool>,
+}
+
+impl Settings {
+    /// The settings there are, `tools` standing for `tools.<name>`.
+    pub const KEYS: &'static [&'static str] = &[
+        "temperature",
+        "top_p",
+        "max_tokens",
+        "seed",
+        "stop",
+        "n",
+        "logit_bias",
+        "search_count",
+        "goggle",
+        "summarizer",
+        "citations",
+        "tool_choice",
+        "tools",
+    ];
+
+    /// Sets `key` to `value`, written as in the configuration file, such as
+    /// `["END", "\n\n"]` for `stop` or `{ "1734" = -100 }` for `logit_bias`.
+    /// Strings may be given bare, a single one for `stop`, and tools,
+    /// `summarizer` and `citations` take `on` and `off`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let path = Self::path(key)?;
+        let value = match toml::from_str::<toml::Table>(&format!("value = {}", value)) {
+            Ok(mut table) => table.remove("value").unwrap_or(toml::Value::Boolean(false)),
+            Err(_) => toml::Value::String(value.to_string()),
+        };
+        let value = match (path[0], value) {
+            ("stop", toml::Value::String(stop)) => {
+                toml::Value::Array(vec![toml::Value::String(stop)])
+            }
+            ("tools" | "summarizer" | "citations", toml::Value::String(state))
+                if state == "on" || state == "off" =>
+            {
+                toml::Value::Boolean(state == "on")
+            }
+            (_, value) => value,
+        };
+        if path[0] == "search_count" && !matches!(value.as_integer(), Some(1..=20)) {
+            return Err("search_count goes from 1 to 20".to_string());
+        }
+        let mut table = self.table();
+        match path[..] {
+            [name] => {
+                table.insert(name.to_string(), value);
+            }
+            [group, name] => {
+                table
+                    .entry(group)
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    .as_table_mut()
+                    .map(|group| group.insert(name.to_string(), value));
+            }
+            _ => {}
+        }
+        self.replace(table, key)
+    }
+
+    /// These settings with those set in `over` in their place, tool by
+    /// tool for `tools`.
+    pub fn overlay(&self, over: &Settings) -> Settings {
+        let mut table = self.table();
+        for (key, value) in over.table() {
+            match (table.get_mut(&key), value) {
+                (Some(toml::Value::Table(group)), toml::Value::Table(over)) => group.extend(over),
+                (_, value) => {
+                    table.insert(key, value);
+                }
+            }
+        }
+        table.try_into().unwrap_or_else(|_| over.clone())
+    }
+
+    /// Takes `key` back to its value in `defaults`.
+    pub fn reset(&mut self, key: &str, defaults: &Settings) -> Result<(), String> {
+        let path = Self::path(key)?;
+        let mut table = self.table();
+        let default = defaults.value(key);
+        match (&path[..], default) {
+            ([name], Some(value)) => {
+                table.insert(name.to_string(), value);
+            }
+            ([name], None) => {
+                table.remove(*name);
+            }
+            ([group, name], default) => {
+                if let Some(group) = table.get_mut(*group).and_then(|g| g.as_table_mut()) {
+                    match default {
+                        Some(value) => group.insert(name.to_string(), value),
+                        None => group.remove(*name),
+                    };
+                } else if let Some(value) = default {
+                    table.insert(
+                        group.to_string(),
+                        toml::Value::Table([(name.to_string(), value)].into_iter().collect()),
+                    );
+                }
+            }
+            _ => {}
+        }
+        self.replace(table, key)
+    }
+
+    /// The value of `key` as it would be written in the configuration file,
+    /// or `None` when it is left to the default.
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Self::path(key)?;
+        Ok(self.value(key).map(|value| value.to_string()))
+    }
+
+    fn value(&self, key: &str) -> Option<toml::Value> {
+        let table = self.table();
+        let mut path = key.split('.');
+        let mut value = table.get(path.next()?)?;
+        for part in path {
+            value = value.get(part)?;
+        }
+        Some(value.clone())
+    }
+
+    /// Each setting that is set, as it would be written in the
+    /// configuration file, one per line.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (key, value) in self.table() {
+            match value {
+                toml::Value::Table(group) if key == "tools" => {
+                    for (name, value) in group {
+                        lines.push(format!("tools.{} = {}", name, value));
+                    }
+                }
+                value => lines.push(format!("{} = {}", key, value)),
+            }
+        }
+        lines
+    }
+
+    pub fn search_count(&self) -> u32 {
+        self.search_count.unwrap_or(DEFAULT_SEARCH_COUNT)
+    }
+
+    pub fn summarizer(&self) -> bool {
+        self.summarizer.unwrap_or(false)
+    }
+
+    pub fn citations(&self) -> bool {
+        self.citations.unwrap_or(true)
+    }
+
+    /// Whether the tool called `name` is offered, `offered` telling whether
+    /// it would be without a setting.
+    pub fn tool_enabled(&self, name: &str, offered: bool) -> bool {
+        self.tools.get(name).copied().unwrap_or(offered)
+    }
+
+    /// `key` split into the setting and, for tools, the tool's name.
+    fn path(key: &str) -> Result<Vec<&str>, String> {
+        let path: Vec<&str> = key.split('.').collect();
+        match path[..] {
+            ["tools", name] if !name.is_empty() => Ok(path),
+            ["tools"] => Err("Name the tool, as in tools.brave_search".to_string()),
+            [name] if Self::KEYS.contains(&name) => Ok(path),
+            _ => Err(format!(
+                "Unknown setting '{}'; known ones are {}",
+                key,
+                Self::KEYS
+                    .iter()
+                    .map(|key| match *key {
+                        "tools" => "tools.<name>",
+                        key => key,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    fn table(&self) -> toml::Table {
+        toml::Table::try_from(self).unwrap_or_default()
+    }
+
+    /// Replaces the settings with `table`, unless it does not hold valid
+    /// settings because `key` was given a wrong value.
+    fn replace(&mut self, table: toml::Table, key: &str) -> Result<(), String> {
+        *self = table.try_into().map_err(|e: toml::de::Error| {
+            let reason = e.to_string();
+            format!(
+                "Invalid value for {}: {}",
+                key,
+                reason.lines().next().unwrap_or_default()
+            )
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+//! Shell integration. `rusty shell-init` prints a hook for the shell's
+//! startup file that, before each prompt, exports the command just run and
+//! its exit status, so that `rusty explain-last`, started next, can find
+//! them without anything being pasted. `rusty cmd` goes the other way,
+//! from a description to a command.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+/// Where the hook leaves the command and its exit status.
+pub const COMMAND_VAR: &str = "RUSTY_LAST_COMMAND";
+pub const STATUS_VAR: &str = "RUSTY_LAST_STATUS";
+
+const BASH_HOOK: &str = r#"# Rusty: remember the last command for `rusty explain-last`.
+# An empty line leaves the history as it was, and the command with it.
+__rusty_entry=$(HISTTIMEFORMAT= history 1)
+__rusty_precmd() {
+    local last_status=$? entry
+    entry=$(HISTTIMEFORMAT= history 1)
+    if [[ $entry != "$__rusty_entry" ]]; then
+        __rusty_entry=$entry
+        export RUSTY_LAST_STATUS=$last_status
+        export RUSTY_LAST_COMMAND="$(sed 's/^ *[0-9]*[* ] *//' <<<"$entry")"
+    fi
+    return $last_status
+}
+case ";$PROMPT_COMMAND;" in
+    *";__rusty_precmd;"*) ;;
+    *) PROMPT_COMMAND="__rusty_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+"#;
+
+const ZSH_HOOK: &str = r#"# Rusty: remember the last command for `rusty explain-last`.
+__rusty_preexec() {
+    __rusty_command=$1
+}
+__rusty_precmd() {
+    local last_status=$?
+    [[ -n $__rusty_command ]] || return
+    export RUSTY_LAST_STATUS=$last_status
+    export RUSTY_LAST_COMMAND=$__rusty_command
+    __rusty_command=
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook preexec __rusty_preexec
+add-zsh-hook precmd __rusty_precmd
+"#;
+
+const FISH_HOOK: &str = r#"# Rusty: remember the last command for `rusty explain-last`.
+function __rusty_postexec --on-event fish_postexec
+    set -l last_status $status
+    test -n "$argv[1]"; or return
+    set -gx RUSTY_LAST_STATUS $last_status
+    set -gx RUSTY_LAST_COMMAND $argv[1]
+end
+"#;
+
+const COMMAND_PROMPT: &str = "Turn the request into a single command for the shell and system \
+named below. Reply with the command alone in a ```sh code block, on one line where it can be, \
+chaining steps with && or pipes when one program is not enough. Then explain in one to three \
+sentences what it does and what any unusual options are for, and warn when it deletes, \
+overwrites or changes files, needs root or may take long. Prefer tools that come with the \
+system over ones that must be installed.";
+
+/// The command run last in the shell Rusty was started from.
+#[derive(Debug, Clone)]
+pub struct LastCommand {
+    pub command: String,
+    pub status: i32,
+}
+
+/// The hook for `shell`, such as `bash` or `/usr/bin/zsh`, or for the
+/// shell in `$SHELL` when none is given.
+pub fn init_script(shell: Option<&str>) -> Result<&'static str, String> {
+    let shell = match shell {
+        Some(shell) => shell.to_string(),
+        None => std::env::var("SHELL")
+            .map_err(|_| "Name the shell: rusty shell-init bash, zsh or fish".to_string())?,
+    };
+    let name = Path::new(&shell)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    match name {
+        "bash" => Ok(BASH_HOOK),
+        "zsh" => Ok(ZSH_HOOK),
+        "fish" => Ok(FISH_HOOK),
+        _ => Err(format!(
+            "No hook for {}; rusty shell-init knows bash, zsh and fish",
+            shell
+        )),
+    }
+}
+
+/// A command suggested by `rusty cmd`, with what it does.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub command: String,
+    pub explanation: String,
+}
+
+/// What the hook exported, or `None` when it is not installed in this
+/// shell or no command has been run yet.
+pub fn last_command() -> Option<LastCommand> {
+    let command = std::env::var(COMMAND_VAR).ok()?;
+    let status = std::env::var(STATUS_VAR).ok()?.trim().parse().ok()?;
+    let command = command.trim().to_string();
+    (!command.is_empty()).then_some(LastCommand { command, status })
+}
+
+/// The prompt asking why `last` failed, or what it did when it did not,
+/// with `question` added when one is given. `with_output` says the output
+/// of the command follows the prompt.
+pub fn explain_prompt(last: &LastCommand, question: &str, with_output: bool) -> String {
+    let mut prompt = format!(
+        "I ran this command {}:\n\n```\n{}\n```\n\n",
+        environment(),
+        last.command
+    );
+    if last.status == 0 {
+        prompt.push_str(
+            "It exited with status 0. Explain briefly what it did, and point out anything \
+             surprising or risky about it.",
+        );
+    } else {
+        prompt.push_str(&format!(
+            "It failed with exit status {}{}. {} Explain the most likely reasons, most \
+             likely first, what the status means for this program, and how to fix or find out \
+             more, with the commands to run.",
+            last.status,
+            signal(last.status)
+                .map(|name| format!(", which means it was killed by {}", name))
+                .unwrap_or_default(),
+            if with_output {
+                "Its output is below."
+            } else {
+                "I have not got its output."
+            }
+        ));
+    }
+    if !question.trim().is_empty() {
+        prompt.push_str(&format!("\n\n{}", question.trim()));
+    }
+    prompt
+}
+
+/// The system prompt of `rusty cmd`.
+pub fn command_prompt() -> String {
+    format!(
+        "{}\n\nThe command will run {}.",
+        COMMAND_PROMPT,
+        environment()
+    )
+}
+
+/// Reads the answer to `command_prompt`: the command is the first code
+/// block, or the first line when there is none, and the rest explains it.
+pub fn parse_suggestion(answer: &str) -> Result<Suggestion, String> {
+    let answer = answer.trim();
+    let (command, explanation) = match answer.split_once("```") {
+        Some((before, rest)) => {
+            // The language tag, if any, is on the opening line.
+            let rest = rest.split_once('\n').map_or("", |(_, code)| code);
+            let (code, after) = rest.split_once("```").unwrap_or((rest, ""));
+            (
+                code.to_string(),
+                format!("{}\n{}", before.trim(), after.trim()),
+            )
+        }
+        None => {
+            let (first, rest) = answer.split_once('\n').unwrap_or((answer, ""));
+            (first.trim_matches('`').to_string(), rest.to_string())
+        }
+    };
+    let command = command.trim().to_string();
+    if command.is_empty() {
+        return Err("The model did not suggest a command".to_string());
+    }
+    Ok(Suggestion {
+        command,
+        explanation: explanation.trim().to_string(),
+    })
+}
+
+/// Runs `command` with the user's shell, `$SHELL` or else `sh`, or with
+/// `cmd` on Windows, in the terminal Rusty runs in.
+pub fn run(command: &str) -> io::Result<ExitStatus> {
+    if cfg!(windows) {
+        return Command::new("cmd").arg("/C").arg(command).status();
+    }
+    let shell = std::env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| "sh".to_string());
+    Command::new(shell).arg("-c").arg(command).status()
+}
+
+/// Where commands run: "in <dir> (shell <shell>, <os>)".
+fn environment() -> String {
+    let cwd = std::env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default();
+    let shell = if cfg!(windows) {
+        "cmd".to_string()
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string())
+    };
+    format!("in {} (shell {}, {})", cwd, shell, std::env::consts::OS)
+}
+
+/// The signal that shells report as exit status `status`, 128 and above.
+fn signal(status: i32) -> Option<&'static str> {
+    Some(match status - 128 {
+        1 => "SIGHUP",
+        2 => "SIGINT (Ctrl+C)",
+        6 => "SIGABRT",
+        9 => "SIGKILL, often the out-of-memory killer",
+        11 => "SIGSEGV, a segmentation fault",
+        13 => "SIGPIPE",
+        15 => "SIGTERM",
+        _ => return None,
+    })
+}
@@ -0,0 +1,242 @@
+//! A Slack app in Socket Mode: events arrive over a WebSocket opened with
+//! the app-level token, so no public address is needed, and replies go out
+//! through the Web API with the bot token.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use reqwest::header::RETRY_AFTER;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message as Frame;
+
+use crate::agent::split_message;
+
+const API_URL: &str = "https://slack.com/api";
+
+/// The longest `markdown_text` Slack accepts, in characters.
+const MESSAGE_LIMIT: usize = 12_000;
+
+/// Failed connection attempts in a row before giving up.
+const MAX_RECONNECTS: u32 = 5;
+
+/// Messages remembered to drop the second event for the same message;
+/// a mention arrives both as `app_mention` and as `message`.
+const SEEN_MESSAGES: usize = 200;
+
+/// Sets the scene for the model.
+pub const SYSTEM_PROMPT: &str = "You are Rusty, an assistant answering in a Slack thread. Each \
+message starts with the Slack mention of the person who wrote it, like <@U123>; use it to address \
+them. Keep answers short and use Markdown.";
+
+/// A message from a person in a channel or direct message the bot can see.
+#[derive(Debug, Clone)]
+pub struct IncomingMessage {
+    pub channel: String,
+    /// The thread the message belongs to, or starts.
+    pub thread_ts: String,
+    /// The sender's user id.
+    pub user: String,
+    /// The text, without the mention of the bot.
+    pub text: String,
+    /// Whether the message mentions the bot.
+    pub mentioned: bool,
+    /// Whether it was sent in a direct message to the bot.
+    pub direct: bool,
+}
+
+#[derive(Clone)]
+pub struct SlackBot {
+    app_token: String,
+    bot_token: String,
+    client: reqwest::Client,
+    user_id: String,
+}
+
+impl SlackBot {
+    /// Checks the bot token and learns the bot's user id.
+    pub async fn connect(app_token: String, bot_token: String) -> Result<Self, String> {
+        let mut bot = Self {
+            app_token,
+            bot_token,
+            client: reqwest::Client::new(),
+            user_id: String::new(),
+        };
+        let identity = bot.call("auth.test", &bot.bot_token, &json!({})).await?;
+        bot.user_id = identity["user_id"].as_str().unwrap_or_default().to_string();
+        Ok(bot)
+    }
+
+    /// Opens the Socket Mode connection in the background, reconnecting
+    /// when Slack asks to. Messages arrive on the returned channel, which
+    /// ends with an `Err` once Slack cannot be reached.
+    pub fn listen(&self) -> mpsc::UnboundedReceiver<Result<IncomingMessage, String>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run_socket(self.clone(), sender));
+        receiver
+    }
+
+    /// Posts `markdown` in the thread `thread_ts` of `channel`, in several
+    /// messages when it is long.
+    pub async fn post(&self, channel: &str, thread_ts: &str, markdown: &str) -> Result<(), String> {
+        for piece in split_message(markdown, MESSAGE_LIMIT) {
+            let body = json!({
+                "channel": channel,
+                "thread_ts": thread_ts,
+                "markdown_text": piece,
+                "unfurl_links": false,
+            });
+            self.call("chat.postMessage", &self.bot_token, &body)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Calls a Web API method and returns its response, waiting out rate
+    /// limits a few times.
+    async fn call(&self, method: &str, token: &str, body: &Value) -> Result<Value, String> {
+        for _ in 0..3 {
+            let response = self
+                .client
+                .post(format!("{}/{}", API_URL, method))
+                .bearer_auth(token)
+                .json(body)
+                .send()
+                .await
+                .map_err(|e| format!("Slack request failed: {}", e))?;
+            if response.status().as_u16() == 429 {
+                let secs = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(1);
+                tokio::time::sleep(Duration::from_secs(secs)).await;
+                continue;
+            }
+            let json: Value = response
+                .json()
+                .await
+                .map_err(|e| format!("Invalid Slack response: {}", e))?;
+            if json["ok"].as_bool() == Some(true) {
+                return Ok(json);
+            }
+            let error = json["error"].as_str().unwrap_or("unknown error");
+            return Err(format!("Slack refused {}: {}", method, error));
+        }
+        Err("Slack kept rate-limiting the bot".to_string())
+    }
+
+    /// The message in an event, if a person sent it. Edits, joins and
+    /// messages from bots, this one included, are skipped.
+    fn message(&self, event: &Value) -> Option<IncomingMessage> {
+        if !matches!(event["type"].as_str(), Some("message" | "app_mention"))
+            || !event["subtype"].is_null()
+            || !event["bot_id"].is_null()
+        {
+            return None;
+        }
+        let user = event["user"].as_str()?;
+        if user == self.user_id {
+            return None;
+        }
+        let mention = format!("<@{}>", self.user_id);
+        let text = event["text"].as_str().unwrap_or_default();
+        let ts = event["ts"].as_str()?;
+        Some(IncomingMessage {
+            channel: event["channel"].as_str()?.to_string(),
+            thread_ts: event["thread_ts"].as_str().unwrap_or(ts).to_string(),
+            user: user.to_string(),
+            text: text.replace(&mention, "").trim().to_string(),
+            mentioned: event["type"] == "app_mention" || text.contains(&mention),
+            direct: event["channel_type"] == "im",
+        })
+    }
+}
+
+async fn run_socket(bot: SlackBot, events: mpsc::UnboundedSender<Result<IncomingMessage, String>>) {
+    let mut seen = VecDeque::new();
+    let mut failures = 0;
+    loop {
+        match connect(&bot, &events, &mut seen).await {
+            Ok(true) => failures = 0,
+            // Nobody is listening any more.
+            Ok(false) => return,
+            Err(reason) => {
+                failures += 1;
+                if failures > MAX_RECONNECTS {
+                    let _ = events.send(Err(reason));
+                    return;
+                }
+                tokio::time::sleep(Duration::from_secs(1 << failures)).await;
+            }
+        }
+    }
+}
+
+/// One Socket Mode connection. Returns `Ok(true)` when a connection that
+/// worked ends, so a new one should be opened, and `Ok(false)` once nobody
+/// listens for events.
+async fn connect(
+    bot: &SlackBot,
+    events: &mpsc::UnboundedSender<Result<IncomingMessage, String>>,
+    seen: &mut VecDeque<String>,
+) -> Result<bool, String> {
+    let opened = bot
+        .call("apps.connections.open", &bot.app_token, &json!({}))
+        .await?;
+    let url = opened["url"]
+        .as_str()
+        .ok_or("Slack gave no Socket Mode address")?;
+    let (mut socket, _) = tokio_tungstenite::connect_async(url)
+        .await
+        .map_err(|e| format!("Could not connect to Slack: {}", e))?;
+
+    let mut hello = false;
+    while let Some(frame) = socket.next().await {
+        let text = match frame {
+            Ok(Frame::Text(text)) => text,
+            Ok(Frame::Close(_)) | Err(_) if hello => return Ok(true),
+            Ok(Frame::Close(_)) => break,
+            Err(e) => return Err(format!("Slack connection failed: {}", e)),
+            Ok(_) => continue,
+        };
+        let Ok(envelope) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+        // Every envelope must be acknowledged, or Slack sends it again.
+        if let Some(id) = envelope["envelope_id"].as_str() {
+            let ack = json!({ "envelope_id": id });
+            socket
+                .send(Frame::text(ack.to_string()))
+                .await
+                .map_err(|e| format!("Slack connection failed: {}", e))?;
+        }
+        match envelope["type"].as_str() {
+            Some("hello") => hello = true,
+            Some("disconnect") => return Ok(true),
+            Some("events_api") => {
+                let Some(message) = bot.message(&envelope["payload"]["event"]) else {
+                    continue;
+                };
+                let key = format!("{}/{}", message.channel, envelope["payload"]["event"]["ts"]);
+                if seen.contains(&key) {
+                    continue;
+                }
+                seen.push_back(key);
+                if seen.len() > SEEN_MESSAGES {
+                    seen.pop_front();
+                }
+                if events.send(Ok(message)).is_err() {
+                    return Ok(false);
+                }
+            }
+            _ => {}
+        }
+    }
+    if hello {
+        return Ok(true);
+    }
+    Err("Slack closed the connection".to_string())
+}
@@ -0,0 +1,251 @@
+//! What requests have used and cost, kept by day and model in a SQLite
+//! database, so spending caps hold across runs. The chat's tool calls are
+//! counted there too.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::Local;
+use rusqlite::{params, Connection};
+
+use crate::config::BudgetConfig;
+use crate::message::Usage;
+
+pub struct Ledger {
+    conn: Connection,
+}
+
+/// What a model was used for on a day.
+#[derive(Debug, Clone)]
+pub struct DayUsage {
+    /// The local date, as `2025-01-31`.
+    pub day: String,
+    pub model: String,
+    pub requests: u64,
+    pub usage: Usage,
+    /// In dollars, at the model's price; 0 for models without one.
+    pub cost: f64,
+}
+
+/// The calls of a tool: how many, how many failed and how long they took.
+#[derive(Debug, Clone, Default)]
+pub struct ToolStats {
+    pub calls: u64,
+    pub failures: u64,
+    pub time: Duration,
+}
+
+impl ToolStats {
+    pub fn record(&mut self, ok: bool, elapsed: Duration) {
+        self.calls += 1;
+        self.failures += u64::from(!ok);
+        self.time += elapsed;
+    }
+
+    pub fn average(&self) -> Duration {
+        self.time / self.calls.max(1) as u32
+    }
+}
+
+impl Ledger {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| sql_error(path, e))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS usage (
+                 day TEXT NOT NULL,
+                 model TEXT NOT NULL,
+                 requests INTEGER NOT NULL,
+                 prompt INTEGER NOT NULL,
+                 completion INTEGER NOT NULL,
+                 cached INTEGER NOT NULL,
+                 cost REAL NOT NULL,
+                 PRIMARY KEY (day, model)
+             );
+             CREATE TABLE IF NOT EXISTS tools (
+                 tool TEXT PRIMARY KEY,
+                 calls INTEGER NOT NULL,
+                 failures INTEGER NOT NULL,
+                 millis INTEGER NOT NULL
+             );",
+        )
+        .map_err(|e| sql_error(path, e))?;
+        Ok(Self { conn })
+    }
+
+    /// Adds a request to `model` that used `usage` and cost `cost` dollars
+    /// to today's totals.
+    pub fn record(&self, model: &str, usage: &Usage, cost: f64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO usage (day, model, requests, prompt, completion, cached, cost)
+                 VALUES (?1, ?2, 1, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (day, model) DO UPDATE SET
+                     requests = requests + 1,
+                     prompt = prompt + excluded.prompt,
+                     completion = completion + excluded.completion,
+                     cached = cached + excluded.cached,
+                     cost = cost + excluded.cost",
+                params![
+                    today(),
+                    model,
+                    usage.prompt as i64,
+                    usage.completion as i64,
+                    usage.cached as i64,
+                    cost
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Adds a call of `tool` that took `elapsed` to its totals.
+    pub fn record_tool(&self, tool: &str, ok: bool, elapsed: Duration) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO tools (tool, calls, failures, millis) VALUES (?1, 1, ?2, ?3)
+                 ON CONFLICT (tool) DO UPDATE SET
+                     calls = calls + 1,
+                     failures = failures + excluded.failures,
+                     millis = millis + excluded.millis",
+                params![tool, i64::from(!ok), elapsed.as_millis() as i64],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Every tool called so far, with its totals.
+    pub fn tool_stats(&self) -> Result<Vec<(String, ToolStats)>, String> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT tool, calls, failures, millis FROM tools")
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    ToolStats {
+                        calls: row.get::<_, i64>(1)? as u64,
+                        failures: row.get::<_, i64>(2)? as u64,
+                        time: Duration::from_millis(row.get::<_, i64>(3)? as u64),
+                    },
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Every day's use of each model, oldest first.
+    pub fn days(&self) -> Result<Vec<DayUsage>, String> {
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT day, model, requests, prompt, completion, cached, cost
+                 FROM usage ORDER BY day, model",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok(DayUsage {
+                    day: row.get(0)?,
+                    model: row.get(1)?,
+                    requests: row.get::<_, i64>(2)? as u64,
+                    usage: Usage {
+                        prompt: row.get::<_, i64>(3)? as u64,
+                        completion: row.get::<_, i64>(4)? as u64,
+                        cached: row.get::<_, i64>(5)? as u64,
+                    },
+                    cost: row.get(6)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Dollars spent on days starting with `prefix`, a day or a month.
+    fn spent(&self, prefix: &str) -> Result<f64, String> {
+        self.conn
+            .query_row(
+                "SELECT COALESCE(SUM(cost), 0) FROM usage WHERE day LIKE ?1 || '%'",
+                [prefix],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())
+    }
+
+    /// Which cap in `budget` today's or this month's spending has reached,
+    /// said in a sentence, if any.
+    pub fn cap_reached(&self, budget: &BudgetConfig) -> Result<Option<String>, String> {
+        let day = today();
+        let caps = [
+            ("Today's", "daily", budget.daily, day.clone()),
+            (
+                "This month's",
+                "monthly",
+                budget.monthly,
+                day[..7].to_string(),
+            ),
+        ];
+        for (when, name, cap, prefix) in caps {
+            let Some(cap) = cap else {
+                continue;
+            };
+            let spent = self.spent(&prefix)?;
+            if spent >= cap {
+                return Ok(Some(format!(
+                    "{} spending, about ${:.2}, has reached the {} cap of ${:.2}",
+                    when, spent, name, cap
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// What `usage` costs at `price`, dollars per million input and output
+/// tokens.
+pub fn cost(usage: &Usage, price: (f64, f64)) -> f64 {
+    (usage.prompt as f64 * price.0 + usage.completion as f64 * price.1) / 1_000_000.0
+}
+
+/// `days` as CSV with a header row, for spreadsheets and expense tools.
+pub fn to_csv(days: &[DayUsage]) -> String {
+    let mut csv = String::from(
+        "date,model,requests,prompt_tokens,completion_tokens,cached_tokens,cost_usd\n",
+    );
+    for day in days {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{:.6}\n",
+            day.day,
+            csv_field(&day.model),
+            day.requests,
+            day.usage.prompt,
+            day.usage.completion,
+            day.usage.cached,
+            day.cost
+        ));
+    }
+    csv
+}
+
+/// `text` quoted when it holds a comma, a quote or a line break.
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// The local date, as `2025-01-31`.
+pub fn today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn sql_error(path: &Path, e: rusqlite::Error) -> String {
+    format!("Failed to open {}: {}", path.display(), e)
+}
@@ -0,0 +1,186 @@
+//! Question search on Stack Overflow and the other Stack Exchange sites,
+//! for the `stack_exchange` tool.
+
+use regex::Regex;
+use serde_json::Value;
+
+const API_URL: &str = "https://api.stackexchange.com/2.3";
+
+/// Questions listed per search.
+const RESULTS: usize = 5;
+
+/// Characters of each question's best answer shown.
+const ANSWER_CHARS: usize = 1_500;
+
+/// The questions on `site` (default `stackoverflow`) best matching `query`,
+/// each with its accepted or else highest-voted answer.
+pub async fn search(
+    query: &str,
+    site: Option<&str>,
+    tagged: Option<&str>,
+) -> Result<String, String> {
+    let site = site.unwrap_or("stackoverflow");
+    let client = reqwest::Client::new();
+    let pagesize = RESULTS.to_string();
+    let mut params = vec![
+        ("q", query),
+        ("site", site),
+        ("order", "desc"),
+        ("sort", "relevance"),
+        ("pagesize", &pagesize),
+    ];
+    if let Some(tagged) = tagged {
+        params.push(("tagged", tagged));
+    }
+    let questions = get(&client, &format!("{}/search/advanced", API_URL), &params).await?;
+
+    let ids: Vec<String> = questions["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|q| q["question_id"].as_u64())
+        .map(|id| id.to_string())
+        .collect();
+    let answers = if ids.is_empty() {
+        Value::Null
+    } else {
+        get(
+            &client,
+            &format!("{}/questions/{}/answers", API_URL, ids.join(";")),
+            &[
+                ("site", site),
+                ("order", "desc"),
+                ("sort", "votes"),
+                ("pagesize", "100"),
+                ("filter", "withbody"),
+            ],
+        )
+        .await?
+    };
+    Ok(format_results(site, questions, answers))
+}
+
+async fn get(
+    client: &reqwest::Client,
+    url: &str,
+    params: &[(&str, &str)],
+) -> Result<Value, String> {
+    let response = client
+        .get(url)
+        .query(params)
+        .send()
+        .await
+        .map_err(|e| format!("Stack Exchange request failed: {}", e))?;
+    let json: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Stack Exchange response: {}", e))?;
+    if let Some(message) = json["error_message"].as_str() {
+        return Err(format!("Stack Exchange returned an error: {}", message));
+    }
+    Ok(json)
+}
+
+/// Renders questions and their answers the way Brave results are shown, so
+/// the model reads every search tool's output the same way.
+pub fn format_results(site: &str, questions: Value, answers: Value) -> String {
+    let mut output = format!("### Stack Exchange Results ({})\n\n", site);
+
+    let Some(questions) = questions["items"].as_array() else {
+        output.push_str("Failed to parse search results.\n");
+        return output;
+    };
+    if questions.is_empty() {
+        output.push_str("No results found.\n");
+        return output;
+    }
+    let answers = answers["items"].as_array().cloned().unwrap_or_default();
+
+    for (i, question) in questions.iter().enumerate().take(RESULTS) {
+        let id = question["question_id"].as_u64();
+        let title = unescape(question["title"].as_str().unwrap_or("No Title"));
+        let tags: Vec<&str> = question["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        let accepted = question["accepted_answer_id"].is_u64();
+
+        output.push_str(&format!("{}. **{}**\n", i + 1, title));
+        let answer_count = question["answer_count"].as_u64().unwrap_or(0);
+        output.push_str(&format!(
+            "   - Score: {}, {} answer{}{}\n",
+            question["score"].as_i64().unwrap_or(0),
+            answer_count,
+            if answer_count == 1 { "" } else { "s" },
+            if accepted { ", accepted answer" } else { "" }
+        ));
+        if !tags.is_empty() {
+            output.push_str(&format!("   - Tags: {}\n", tags.join(", ")));
+        }
+        output.push_str(&format!(
+            "   - URL: {}\n",
+            question["link"].as_str().unwrap_or("")
+        ));
+
+        // Answers arrive sorted by votes; the accepted one wins regardless.
+        let for_question = || answers.iter().filter(|a| a["question_id"].as_u64() == id);
+        let best = for_question()
+            .find(|a| a["is_accepted"].as_bool() == Some(true))
+            .or_else(|| for_question().next());
+        if let Some(answer) = best {
+            let body = html_to_text(answer["body"].as_str().unwrap_or(""));
+            let mut snippet: String = body.chars().take(ANSWER_CHARS).collect();
+            if snippet.len() < body.len() {
+                snippet.push('…');
+                if snippet.matches("```").count() % 2 == 1 {
+                    snippet.push_str("\n```");
+                }
+            }
+            output.push_str(&format!(
+                "   - {} answer (score {}):\n\n{}\n",
+                if answer["is_accepted"].as_bool() == Some(true) {
+                    "Accepted"
+                } else {
+                    "Top"
+                },
+                answer["score"].as_i64().unwrap_or(0),
+                indent(&snippet)
+            ));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Plain text from an answer body, keeping code as Markdown.
+fn html_to_text(html: &str) -> String {
+    let block = Regex::new(r"(?s)<pre[^>]*>\s*<code[^>]*>(.*?)\n?</code>\s*</pre>").unwrap();
+    let inline = Regex::new(r"(?s)<code>(.*?)</code>").unwrap();
+    let breaks = Regex::new(r"(?i)<br\s*/?>|</p>|</li>|</h\d>|</blockquote>").unwrap();
+    let tags = Regex::new(r"<[^>]+>").unwrap();
+    let blank_lines = Regex::new(r"\n{3,}").unwrap();
+
+    let text = block.replace_all(html, "\n```\n$1\n```\n");
+    let text = inline.replace_all(&text, "`$1`");
+    let text = breaks.replace_all(&text, "\n");
+    let text = tags.replace_all(&text, "");
+    let text = unescape(&text);
+    blank_lines.replace_all(text.trim(), "\n\n").into_owned()
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("     {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
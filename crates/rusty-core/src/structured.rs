@@ -0,0 +1,92 @@
+//! JSON answers for `/json`: the `response_format` that asks the model for
+//! them, and the check that an answer is the JSON that was asked for.
+
+use serde_json::{json, Value};
+
+/// Validation errors quoted back to the model when it is asked again.
+const MAX_ERRORS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub enum ResponseFormat {
+    /// Any JSON object.
+    Object,
+    /// JSON matching `schema`, sent to the API under `name`.
+    Schema { name: String, schema: Value },
+}
+
+impl ResponseFormat {
+    /// Answers matching a JSON schema, which is checked to be one first.
+    pub fn schema(name: &str, schema: Value) -> Result<Self, String> {
+        jsonschema::validator_for(&schema).map_err(|e| format!("Invalid schema: {}", e))?;
+        Ok(Self::Schema {
+            name: name.to_string(),
+            schema,
+        })
+    }
+
+    /// The `response_format` field of a chat request.
+    pub fn body(&self) -> Value {
+        match self {
+            Self::Object => json!({ "type": "json_object" }),
+            Self::Schema { name, schema } => json!({
+                "type": "json_schema",
+                "json_schema": { "name": name, "schema": schema },
+            }),
+        }
+    }
+
+    /// The system message sent along, since the API only accepts JSON mode
+    /// when the conversation asks for JSON, and not every model reads the
+    /// schema from `response_format`.
+    pub fn instruction(&self) -> String {
+        let rule =
+            "Answer with a single JSON value only, without code fences or any text around it.";
+        match self {
+            Self::Object => format!("{} The value must be a JSON object.", rule),
+            Self::Schema { schema, .. } => format!(
+                "{} The value must match this JSON schema:\n{}",
+                rule,
+                serde_json::to_string_pretty(schema).unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Reads an answer as JSON and checks it against the schema, returning
+    /// the value, or why it does not fit.
+    pub fn check(&self, answer: &str) -> Result<Value, String> {
+        let text = answer.trim();
+        let text = text
+            .strip_prefix("```json")
+            .or_else(|| text.strip_prefix("```"))
+            .and_then(|rest| rest.trim_end().strip_suffix("```"))
+            .unwrap_or(text);
+        let value: Value = serde_json::from_str(text.trim())
+            .map_err(|e| format!("The answer is not JSON: {}", e))?;
+        match self {
+            Self::Object if !value.is_object() => {
+                Err("The answer is JSON, but not an object".to_string())
+            }
+            Self::Object => Ok(value),
+            Self::Schema { schema, .. } => {
+                let validator = jsonschema::validator_for(schema)
+                    .map_err(|e| format!("Invalid schema: {}", e))?;
+                let errors: Vec<String> = validator
+                    .iter_errors(&value)
+                    .take(MAX_ERRORS)
+                    .map(|e| match e.instance_path.to_string().as_str() {
+                        "" => e.to_string(),
+                        path => format!("{}: {}", path, e),
+                    })
+                    .collect();
+                if errors.is_empty() {
+                    Ok(value)
+                } else {
+                    Err(format!(
+                        "The answer does not match the schema: {}",
+                        errors.join("; ")
+                    ))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+//! A Telegram bot: long polling for the messages addressed to the bot, and
+//! replies converted from Markdown to the HTML subset Telegram formats.
+
+use std::time::Duration;
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+
+use crate::agent::split_message;
+use crate::export::escape_html;
+
+const API_URL: &str = "https://api.telegram.org";
+
+/// Seconds a poll waits for messages before coming back empty.
+const POLL_TIMEOUT_SECS: u64 = 50;
+
+/// Characters of Markdown per message. Telegram accepts 4096 after the
+/// markup is parsed, so this leaves room for entities that grow, like
+/// `&amp;`.
+const MESSAGE_LIMIT: usize = 3500;
+
+/// How often the typing indicator is renewed; Telegram shows it for five
+/// seconds.
+const TYPING_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Sets the scene for the model.
+pub const SYSTEM_PROMPT: &str = "You are Rusty, an assistant chatting on Telegram. Keep answers \
+short and readable on a phone. Markdown is converted for Telegram, but tables are not shown as \
+tables, so use lists instead.";
+
+/// A message to the bot: any message in a private chat, and in groups a
+/// command, a mention of the bot, or a reply to one of its messages.
+#[derive(Debug, Clone)]
+pub struct IncomingMessage {
+    pub id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    /// The sender's username, without the `@`, if they have one.
+    pub username: Option<String>,
+    /// The sender's name.
+    pub author: String,
+    /// The text, without the mention of the bot. Commands keep their `/`
+    /// but lose any `@botname`.
+    pub text: String,
+}
+
+#[derive(Clone)]
+pub struct TelegramBot {
+    token: String,
+    client: reqwest::Client,
+    id: i64,
+    username: String,
+    /// The update to poll from; earlier ones are confirmed as handled.
+    offset: i64,
+}
+
+impl TelegramBot {
+    /// Checks the token and learns the bot's name.
+    pub async fn connect(token: String) -> Result<Self, String> {
+        let mut bot = Self {
+            token,
+            client: reqwest::Client::new(),
+            id: 0,
+            username: String::new(),
+            offset: 0,
+        };
+        let me = bot.call("getMe", &json!({})).await?;
+        bot.id = me["id"].as_i64().unwrap_or_default();
+        bot.username = me["username"].as_str().unwrap_or_default().to_string();
+        Ok(bot)
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Sets the commands Telegram suggests when `/` is typed.
+    pub async fn set_commands(&self, commands: &[(&str, &str)]) -> Result<(), String> {
+        let commands: Vec<Value> = commands
+            .iter()
+            .map(|(command, description)| json!({ "command": command, "description": description }))
+            .collect();
+        self.call("setMyCommands", &json!({ "commands": commands }))
+            .await
+            .map(|_| ())
+    }
+
+    /// Waits for new messages and returns those addressed to the bot.
+    pub async fn poll(&mut self) -> Result<Vec<IncomingMessage>, String> {
+        let updates = self
+            .call(
+                "getUpdates",
+                &json!({
+                    "offset": self.offset,
+                    "timeout": POLL_TIMEOUT_SECS,
+                    "allowed_updates": ["message"],
+                }),
+            )
+            .await?;
+        let mut messages = Vec::new();
+        for update in updates.as_array().into_iter().flatten() {
+            if let Some(id) = update["update_id"].as_i64() {
+                self.offset = self.offset.max(id + 1);
+            }
+            if let Some(message) = self.addressed(&update["message"]) {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Shows the bot typing in `chat_id` until the task is aborted.
+    pub fn keep_typing(&self, chat_id: i64) -> JoinHandle<()> {
+        let bot = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let _ = bot
+                    .call(
+                        "sendChatAction",
+                        &json!({ "chat_id": chat_id, "action": "typing" }),
+                    )
+                    .await;
+                tokio::time::sleep(TYPING_INTERVAL).await;
+            }
+        })
+    }
+
+    /// Answers `message` with `markdown`, formatted for Telegram, in
+    /// several messages when it is long. A piece Telegram cannot parse is
+    /// sent again as plain text.
+    pub async fn reply(&self, message: &IncomingMessage, markdown: &str) -> Result<(), String> {
+        for (i, piece) in split_message(markdown, MESSAGE_LIMIT).iter().enumerate() {
+            let mut body = json!({
+                "chat_id": message.chat_id,
+                "text": to_html(piece),
+                "parse_mode": "HTML",
+            });
+            if i == 0 {
+                body["reply_parameters"] = json!({
+                    "message_id": message.id,
+                    "allow_sending_without_reply": true,
+                });
+            }
+            if self.call("sendMessage", &body).await.is_err() {
+                body["text"] = json!(piece);
+                if let Some(body) = body.as_object_mut() {
+                    body.remove("parse_mode");
+                }
+                self.call("sendMessage", &body).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls a Bot API method and returns its result, waiting out rate
+    /// limits a few times.
+    async fn call(&self, method: &str, body: &Value) -> Result<Value, String> {
+        for _ in 0..3 {
+            let response = self
+                .client
+                .post(format!("{}/bot{}/{}", API_URL, self.token, method))
+                .json(body)
+                .send()
+                .await
+                // The URL holds the token.
+                .map_err(|e| format!("Telegram request failed: {}", e.without_url()))?;
+            let json: Value = response
+                .json()
+                .await
+                .map_err(|e| format!("Invalid Telegram response: {}", e.without_url()))?;
+            if json["ok"].as_bool() == Some(true) {
+                return Ok(json["result"].clone());
+            }
+            if let Some(secs) = json["parameters"]["retry_after"].as_u64() {
+                tokio::time::sleep(Duration::from_secs(secs)).await;
+                continue;
+            }
+            let description = json["description"].as_str().unwrap_or("unknown error");
+            return Err(format!("Telegram refused {}: {}", method, description));
+        }
+        Err("Telegram kept rate-limiting the bot".to_string())
+    }
+
+    fn addressed(&self, message: &Value) -> Option<IncomingMessage> {
+        let text = message["text"].as_str()?;
+        let from = &message["from"];
+        if from["is_bot"].as_bool() == Some(true) {
+            return None;
+        }
+        let private = message["chat"]["type"] == "private";
+        let mention = format!("@{}", self.username);
+
+        let text = if let Some(command) = text.strip_prefix('/') {
+            // In groups, `/clear@otherbot` is for another bot.
+            let (word, rest) = command
+                .split_once(char::is_whitespace)
+                .unwrap_or((command, ""));
+            let word = match word.split_once('@') {
+                Some((word, bot)) if bot.eq_ignore_ascii_case(&self.username) => word,
+                Some(_) => return None,
+                None => word,
+            };
+            format!("/{} {}", word, rest)
+        } else if private
+            || text.contains(&mention)
+            || message["reply_to_message"]["from"]["id"] == self.id
+        {
+            text.replace(&mention, "")
+        } else {
+            return None;
+        };
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        let author = match (from["first_name"].as_str(), from["last_name"].as_str()) {
+            (Some(first), Some(last)) => format!("{} {}", first, last),
+            (Some(first), None) => first.to_string(),
+            _ => from["username"].as_str().unwrap_or("someone").to_string(),
+        };
+        Some(IncomingMessage {
+            id: message["message_id"].as_i64()?,
+            chat_id: message["chat"]["id"].as_i64()?,
+            user_id: from["id"].as_i64()?,
+            username: from["username"].as_str().map(str::to_string),
+            author,
+            text: text.to_string(),
+        })
+    }
+}
+
+/// Converts Markdown to the HTML Telegram understands: bold, italics,
+/// strikethrough, code, links and quotes. Headings become bold lines,
+/// lists get bullets or numbers, and table cells are separated by `|`.
+pub fn to_html(markdown: &str) -> String {
+    let mut out = String::new();
+    // The next number of each list being written, `None` when unordered.
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let options =
+        Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS;
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Heading { .. } | Tag::Strong => out.push_str("<b>"),
+                Tag::Emphasis => out.push_str("<i>"),
+                Tag::Strikethrough => out.push_str("<s>"),
+                Tag::BlockQuote(_) => out.push_str("<blockquote>"),
+                Tag::CodeBlock(CodeBlockKind::Fenced(language)) if !language.is_empty() => {
+                    let language = language.split_whitespace().next().unwrap_or_default();
+                    out.push_str(&format!(
+                        "<pre><code class=\"language-{}\">",
+                        escape_html(language)
+                    ));
+                }
+                Tag::CodeBlock(_) => out.push_str("<pre><code>"),
+                Tag::Link { dest_url, .. } => {
+                    out.push_str(&format!("<a href=\"{}\">", escape_html(&dest_url)))
+                }
+                Tag::List(start) => {
+                    if !lists.is_empty() {
+                        out.push('\n');
+                    }
+                    lists.push(start);
+                }
+                Tag::Item => {
+                    out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                    match lists.last_mut() {
+                        Some(Some(number)) => {
+                            out.push_str(&format!("{}. ", number));
+                            *number += 1;
+                        }
+                        _ => out.push_str("• "),
+                    }
+                }
+                _ => {}
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Heading(_) => out.push_str("</b>\n\n"),
+                TagEnd::Strong => out.push_str("</b>"),
+                TagEnd::Emphasis => out.push_str("</i>"),
+                TagEnd::Strikethrough => out.push_str("</s>"),
+                TagEnd::BlockQuote(_) => {
+                    trim_end(&mut out);
+                    out.push_str("</blockquote>\n\n");
+                }
+                TagEnd::CodeBlock => {
+                    trim_end(&mut out);
+                    out.push_str("</code></pre>\n\n");
+                }
+                TagEnd::Link => out.push_str("</a>"),
+                // Paragraphs in list items are not spaced apart.
+                TagEnd::Paragraph if lists.is_empty() => out.push_str("\n\n"),
+                TagEnd::Paragraph => out.push('\n'),
+                TagEnd::Item => {
+                    trim_end(&mut out);
+                    out.push('\n');
+                }
+                TagEnd::List(_) => {
+                    lists.pop();
+                    if lists.is_empty() {
+                        out.push('\n');
+                    }
+                }
+                TagEnd::TableCell => out.push_str(" | "),
+                TagEnd::TableHead | TagEnd::TableRow => {
+                    let trimmed = out.trim_end_matches(" | ").len();
+                    out.truncate(trimmed);
+                    out.push('\n');
+                }
+                TagEnd::Table => out.push('\n'),
+                _ => {}
+            },
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                out.push_str(&escape_html(&text))
+            }
+            Event::Code(code) => out.push_str(&format!("<code>{}</code>", escape_html(&code))),
+            Event::SoftBreak | Event::HardBreak => out.push('\n'),
+            Event::Rule => out.push_str("──────────\n\n"),
+            Event::TaskListMarker(done) => out.push_str(if done { "☑ " } else { "☐ " }),
+            _ => {}
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Drops trailing whitespace before a closing tag or line end.
+fn trim_end(out: &mut String) {
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+}
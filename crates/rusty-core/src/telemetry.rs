@@ -0,0 +1,167 @@
+//! Anonymous usage counts, collected only after `/telemetry on`: how often
+//! each command is used and each model chosen, and which kinds of error
+//! happen. Nothing typed or answered is counted, and there is no id, so
+//! reports from one machine cannot be told apart from another's.
+//!
+//! The counts are kept in a file until Rusty exits, then sent to the
+//! configured endpoint and cleared; without an endpoint they stay local,
+//! for `/telemetry status`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// How long sending the counts may hold up exiting.
+const SEND_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Counts {
+    pub commands: BTreeMap<String, u64>,
+    pub models: BTreeMap<String, u64>,
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl Counts {
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.models.is_empty() && self.errors.is_empty()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct State {
+    enabled: bool,
+    counts: Counts,
+}
+
+pub struct Telemetry {
+    state: State,
+    path: PathBuf,
+    endpoint: Option<String>,
+}
+
+impl Telemetry {
+    /// Telemetry that stays off and cannot be turned on, for commands that
+    /// do not count anything.
+    pub fn disabled() -> Self {
+        Self {
+            state: State::default(),
+            path: PathBuf::new(),
+            endpoint: None,
+        }
+    }
+
+    /// Reads the state at `path`, which need not exist yet: off, with
+    /// nothing counted.
+    pub fn load(path: &Path, endpoint: Option<String>) -> Self {
+        let state = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            state,
+            path: path.to_path_buf(),
+            endpoint: endpoint.filter(|endpoint| !endpoint.is_empty()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.enabled
+    }
+
+    /// Where the counts are sent, if anywhere.
+    pub fn endpoint(&self) -> Option<&str> {
+        self.endpoint.as_deref()
+    }
+
+    /// What has been counted and not sent yet.
+    pub fn counts(&self) -> &Counts {
+        &self.state.counts
+    }
+
+    /// Turns collecting on or off. Turning it off forgets what was counted.
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<(), String> {
+        self.state.enabled = enabled;
+        if !enabled {
+            self.state.counts = Counts::default();
+        }
+        self.save()
+    }
+
+    /// Counts a use of the command `name`, such as `/model`.
+    pub fn command(&mut self, name: &str) {
+        self.count(|counts| &mut counts.commands, name);
+    }
+
+    /// Counts a request to `model`.
+    pub fn model(&mut self, model: &str) {
+        self.count(|counts| &mut counts.models, model);
+    }
+
+    /// Counts an error of the kind `class`, such as `rate_limit`.
+    pub fn error(&mut self, class: &str) {
+        self.count(|counts| &mut counts.errors, class);
+    }
+
+    fn count(&mut self, map: fn(&mut Counts) -> &mut BTreeMap<String, u64>, key: &str) {
+        if !self.state.enabled {
+            return;
+        }
+        *map(&mut self.state.counts)
+            .entry(key.to_string())
+            .or_default() += 1;
+        // Counts are only statistics; losing some is no reason to complain.
+        let _ = self.save();
+    }
+
+    /// Sends the counts to the endpoint and clears them. Nothing is sent
+    /// while off, without an endpoint, or when nothing was counted.
+    pub async fn send(&mut self) -> Result<(), String> {
+        let Some(endpoint) = self.endpoint.as_deref().filter(|_| self.state.enabled) else {
+            return Ok(());
+        };
+        if self.state.counts.is_empty() {
+            return Ok(());
+        }
+        let body = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "os": std::env::consts::OS,
+            "commands": self.state.counts.commands,
+            "models": self.state.counts.models,
+            "errors": self.state.counts.errors,
+        });
+        let response = reqwest::Client::new()
+            .post(endpoint)
+            .timeout(Duration::from_secs(SEND_TIMEOUT_SECS))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Sending usage statistics failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Sending usage statistics failed: {}",
+                response.status()
+            ));
+        }
+        self.state.counts = Counts::default();
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if self.path.as_os_str().is_empty() {
+            return Err("Usage statistics are not available here".to_string());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(&self.state).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
@@ -0,0 +1,1154 @@
+//! The tools built into Rusty.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use super::{Mutation, Tool, ToolContext, ToolFuture, ToolSteps};
+use crate::message::{ToolDefinition, ToolFunction};
+use crate::{arxiv, calc, clipboard, datetime, feed, github, http, market, pdf};
+use crate::{stackexchange, wikipedia, youtube};
+
+/// Other ways of asking `brave_search` runs along with the query.
+const MAX_REPHRASINGS: usize = 2;
+
+/// Passages `search_docs` returns.
+const DOCUMENT_HITS: usize = 5;
+
+/// Every built-in tool, in the order they are offered.
+pub(super) fn tools() -> Vec<Arc<dyn Tool>> {
+    vec![
+        Arc::new(BraveSearch),
+        Arc::new(Open),
+        Arc::new(Remember),
+        Arc::new(SearchDocs),
+        Arc::new(ReadPdf),
+        Arc::new(YoutubeTranscript),
+        Arc::new(Wikipedia),
+        Arc::new(Arxiv),
+        Arc::new(GitHub),
+        Arc::new(StackExchange),
+        Arc::new(Calculate),
+        Arc::new(CryptoPrice),
+        Arc::new(StockQuote),
+        Arc::new(ReadFeed),
+        Arc::new(HttpRequest),
+        Arc::new(DateTime),
+        Arc::new(ReadImage),
+        Arc::new(ReadClipboard),
+    ]
+}
+
+struct BraveSearch;
+
+impl Tool for BraveSearch {
+    fn name(&self) -> &str {
+        "brave_search"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Search the web for up-to-date information, news, current events, and general knowledge. Use this for questions that require real-time data or when you need to verify facts.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search query to look up on the web."
+                    },
+                    "rephrasings": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "maxItems": MAX_REPHRASINGS,
+                        "description": "For complex or niche questions, up to two other queries for the same need, worded differently or narrower, searched along with the query and merged with its results."
+                    },
+                    "goggle": {
+                        "type": "string",
+                        "description": "The URL of a Brave Goggle to rerank the results with, only when the user gives one for this search."
+                    }
+                },
+                "required": ["query"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        let queries: Vec<String> = queries(arguments)
+            .iter()
+            .map(|query| format!("'{}'", query))
+            .collect();
+        steps(
+            format!("Searching Brave for {}", queries.join(", ")),
+            "Reasoning with search results",
+            "Search failed",
+            "Error: Search failed. Please answer without search.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            context
+                .brave
+                .search_all(&queries(arguments), optional(arguments, "goggle"))
+                .await
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+struct Open;
+
+impl Tool for Open {
+    fn name(&self) -> &str {
+        "open"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Open a web page to read its content. Long pages are cut to an outline and the parts most relevant to the question.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The URL of the page to open."
+                    },
+                    "question": {
+                        "type": "string",
+                        "description": "What you are looking for on the page, to pick the relevant parts of a long one."
+                    }
+                },
+                "required": ["id"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        // Why a page could not be read, such as its robots.txt refusing,
+        // tells the model what to do instead.
+        ToolSteps {
+            step: format!("Reading '{}'", text(arguments, "id")),
+            done: Some("Analyzing page content"),
+            failure: "Failed to read content",
+            fallback: None,
+        }
+    }
+
+    /// Only web addresses; anything else is searched for.
+    fn destination<'a>(&self, arguments: &'a Value) -> Option<&'a str> {
+        let id = text(arguments, "id");
+        http::host(id).is_ok().then_some(id)
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let id = text(arguments, "id");
+            // Anything but a web address is looked up instead.
+            if http::host(id).is_err() {
+                return context
+                    .brave
+                    .search(id, None)
+                    .await
+                    .map_err(|e| e.to_string());
+            }
+            context
+                .fetcher
+                .open(id, optional(arguments, "question"))
+                .await
+        })
+    }
+}
+
+struct Remember;
+
+impl Tool for Remember {
+    fn name(&self) -> &str {
+        "remember"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Save a lasting fact or preference about the user to long-term memory, so it is known in future conversations. Use this when the user states a preference, asks you to remember something, or shares details about themselves or their projects that will matter later. Do not save passing or sensitive details.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "fact": {
+                        "type": "string",
+                        "description": "The fact to remember, as one short self-contained sentence."
+                    }
+                },
+                "required": ["fact"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!("Remembering '{}'", text(arguments, "fact")),
+            "Saved to memory",
+            "Failed to save memory",
+            "Error: The fact could not be saved.",
+        )
+    }
+
+    fn mutates(&self, _arguments: &Value) -> bool {
+        true
+    }
+
+    fn mutation(&self) -> Mutation {
+        Mutation::Always
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            match context.memory.add(text(arguments, "fact"))? {
+                true => Ok("Saved to long-term memory.".to_string()),
+                false => Ok("This is already in long-term memory.".to_string()),
+            }
+        })
+    }
+}
+
+/// Offered only once documents have been indexed with `rusty index`.
+struct SearchDocs;
+
+impl Tool for SearchDocs {
+    fn name(&self) -> &str {
+        "search_docs"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Search the user's own indexed local documents and notes. Use this when the user asks about their files, notes, projects, or anything that may be written down in their documents, before searching the web.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "What to look for, in the words the documents would likely use."
+                    }
+                },
+                "required": ["query"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!(
+                "Searching your documents for '{}'",
+                text(arguments, "query")
+            ),
+            "Reasoning with your documents",
+            "Document search failed",
+            "Error: Document search failed. Please answer without it.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            match context.documents {
+                Some(retriever) => {
+                    retriever
+                        .search(text(arguments, "query"), DOCUMENT_HITS)
+                        .await
+                }
+                None => Err("No documents are indexed".to_string()),
+            }
+        })
+    }
+}
+
+struct ReadPdf;
+
+impl Tool for ReadPdf {
+    fn name(&self) -> &str {
+        "read_pdf"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Extract the text of a local PDF file, such as a paper or an invoice, page by page. Long documents are cut off with a note saying which pages to request next.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the PDF file, absolute or relative to the current directory."
+                    },
+                    "pages": {
+                        "type": "string",
+                        "description": "Optional page range, such as \"3-5\", \"7\" or \"10-\". Defaults to the whole document."
+                    }
+                },
+                "required": ["path"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!("Reading PDF '{}'", text(arguments, "path")),
+            "Reasoning with the document",
+            "Failed to read PDF",
+            "Error: The PDF could not be read.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            pdf::extract_text(
+                Path::new(text(arguments, "path")),
+                optional(arguments, "pages"),
+            )
+        })
+    }
+}
+
+struct YoutubeTranscript;
+
+impl Tool for YoutubeTranscript {
+    fn name(&self) -> &str {
+        "youtube_transcript"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Fetch the transcript of a YouTube video from its captions, with timestamps. Use this to summarize, quote or answer questions about a talk or video. Long transcripts are split into parts; the result says which part to request next.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The video URL (youtube.com/watch, youtu.be, shorts) or its 11-character ID."
+                    },
+                    "language": {
+                        "type": "string",
+                        "description": "Optional caption language code, such as \"en\" or \"de\". Defaults to English when available."
+                    },
+                    "part": {
+                        "type": "integer",
+                        "description": "Which part of a long transcript to return, starting at 1. Defaults to 1."
+                    }
+                },
+                "required": ["url"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!("Fetching the transcript of '{}'", text(arguments, "url")),
+            "Reasoning with the transcript",
+            "Failed to fetch transcript",
+            "Error: The transcript could not be fetched.",
+        )
+    }
+
+    /// YouTube, whatever the video is given as.
+    fn destination<'a>(&self, _arguments: &'a Value) -> Option<&'a str> {
+        Some("https://www.youtube.com/")
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let part = arguments["part"].as_u64().unwrap_or(1) as usize;
+            youtube::transcript(
+                text(arguments, "url"),
+                optional(arguments, "language"),
+                part,
+            )
+            .await
+        })
+    }
+}
+
+struct Wikipedia;
+
+impl Tool for Wikipedia {
+    fn name(&self) -> &str {
+        "wikipedia"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Look up an encyclopedia article on Wikipedia. Returns the article's summary and its list of sections; call again with a section name to read that section in full. Prefer this over web search for people, places, history, science and other encyclopedic topics.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "title": {
+                        "type": "string",
+                        "description": "The article title, such as \"Alan Turing\". Other phrasings are resolved with Wikipedia's search."
+                    },
+                    "section": {
+                        "type": "string",
+                        "description": "Optional section to read in full, such as \"Early life\"."
+                    },
+                    "language": {
+                        "type": "string",
+                        "description": "Optional Wikipedia language code, such as \"de\". Defaults to \"en\"."
+                    }
+                },
+                "required": ["title"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        let title = text(arguments, "title");
+        steps(
+            match optional(arguments, "section") {
+                Some(section) => format!("Reading Wikipedia '{}' § {}", title, section),
+                None => format!("Looking up '{}' on Wikipedia", title),
+            },
+            "Reasoning with the article",
+            "Wikipedia lookup failed",
+            "Error: The Wikipedia lookup failed. Please try searching instead.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            wikipedia::lookup(
+                text(arguments, "title"),
+                optional(arguments, "section"),
+                optional(arguments, "language"),
+            )
+            .await
+        })
+    }
+}
+
+struct Arxiv;
+
+impl Tool for Arxiv {
+    fn name(&self) -> &str {
+        "arxiv"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Search arXiv for research papers, or fetch one paper's metadata and full abstract by its ID. Use this for questions about scientific research, papers and preprints instead of web search, and cite papers by their arXiv ID.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Words to search for. Every word must match."
+                    },
+                    "field": {
+                        "type": "string",
+                        "enum": ["all", "title", "abstract", "author"],
+                        "description": "Where the query words must appear. Defaults to \"all\"."
+                    },
+                    "sort": {
+                        "type": "string",
+                        "enum": ["relevance", "newest"],
+                        "description": "Result order. Defaults to \"relevance\"."
+                    },
+                    "id": {
+                        "type": "string",
+                        "description": "An arXiv ID such as \"2401.12345\" or an arXiv URL, to fetch that paper's details instead of searching."
+                    }
+                }
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            match optional(arguments, "id") {
+                Some(id) => format!("Fetching arXiv paper {}", id),
+                None => format!("Searching arXiv for '{}'", text(arguments, "query")),
+            },
+            "Reasoning with the papers",
+            "arXiv lookup failed",
+            "Error: The arXiv lookup failed. Please answer without it.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let newest = optional(arguments, "sort") == Some("newest");
+            match (optional(arguments, "id"), optional(arguments, "query")) {
+                (Some(id), _) => arxiv::paper(id).await,
+                (None, Some(query)) => {
+                    arxiv::search(query, optional(arguments, "field"), newest).await
+                }
+                (None, None) => Err("Give either a query or a paper ID".to_string()),
+            }
+        })
+    }
+}
+
+struct GitHub;
+
+impl Tool for GitHub {
+    fn name(&self) -> &str {
+        "github"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Query GitHub directly for exact, current facts about repositories: metadata such as stars and license, issue and pull request searches with total counts, a single issue with its description, file contents or directory listings, and code search. Prefer this over web search for questions about a GitHub project.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["repo", "issues", "issue", "file", "search_code"],
+                        "description": "repo: repository metadata. issues: search issues (add is:pr to the query for pull requests). issue: one issue or pull request by number. file: a file's content or a directory listing. search_code: search code."
+                    },
+                    "repo": {
+                        "type": "string",
+                        "description": "The repository as owner/name, such as \"tokio-rs/tokio\". Optional for search_code."
+                    },
+                    "query": {
+                        "type": "string",
+                        "description": "Search words for issues or search_code; GitHub qualifiers such as label:bug or language:rust are allowed."
+                    },
+                    "state": {
+                        "type": "string",
+                        "enum": ["open", "closed", "all"],
+                        "description": "Issue state for the issues action. Defaults to \"open\"."
+                    },
+                    "number": {
+                        "type": "integer",
+                        "description": "Issue or pull request number for the issue action."
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File or directory path for the file action; empty for the repository root."
+                    },
+                    "ref": {
+                        "type": "string",
+                        "description": "Optional branch, tag or commit for the file action."
+                    }
+                },
+                "required": ["action"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        let repo = optional(arguments, "repo");
+        let query = optional(arguments, "query");
+        let number = arguments["number"].as_u64();
+        steps(
+            match (text(arguments, "action"), repo, query) {
+                ("issues", Some(repo), Some(query)) => {
+                    format!("Searching {} issues for '{}' on GitHub", repo, query)
+                }
+                ("issues", Some(repo), None) => format!("Listing {} issues on GitHub", repo),
+                ("issue", Some(repo), _) if number.is_some() => {
+                    format!("Reading {}#{} on GitHub", repo, number.unwrap_or_default())
+                }
+                ("file", Some(repo), _) => {
+                    format!("Reading {}/{} on GitHub", repo, text(arguments, "path"))
+                }
+                ("search_code", _, query) => {
+                    format!("Searching GitHub code for '{}'", query.unwrap_or(""))
+                }
+                (_, Some(repo), _) => format!("Looking up {} on GitHub", repo),
+                _ => "Querying GitHub".to_string(),
+            },
+            "Reasoning with GitHub data",
+            "GitHub query failed",
+            "Error: The GitHub query failed. Please answer without it.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let action = text(arguments, "action");
+            let repo = optional(arguments, "repo");
+            let needs_repo = || repo.ok_or(format!("The {} action needs a repo", action));
+            let query = match action {
+                "repo" => github::Query::Repo {
+                    repo: needs_repo()?,
+                },
+                "issues" => github::Query::Issues {
+                    repo: needs_repo()?,
+                    query: optional(arguments, "query"),
+                    state: optional(arguments, "state"),
+                },
+                "issue" => github::Query::Issue {
+                    repo: needs_repo()?,
+                    number: arguments["number"]
+                        .as_u64()
+                        .ok_or("The issue action needs a number")?,
+                },
+                "file" => github::Query::File {
+                    repo: needs_repo()?,
+                    path: text(arguments, "path"),
+                    reference: optional(arguments, "ref"),
+                },
+                "search_code" => github::Query::SearchCode {
+                    query: optional(arguments, "query")
+                        .ok_or("The search_code action needs a query")?,
+                    repo,
+                },
+                other => return Err(format!("Unknown GitHub action '{}'", other)),
+            };
+            github::query(query).await
+        })
+    }
+}
+
+struct StackExchange;
+
+impl Tool for StackExchange {
+    fn name(&self) -> &str {
+        "stack_exchange"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Search Stack Overflow (or another Stack Exchange site) for questions matching a programming problem, with their scores and the accepted or top-voted answer. Use this for error messages, debugging and how-to questions about code.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The problem or error message to look for."
+                    },
+                    "tagged": {
+                        "type": "string",
+                        "description": "Optional tags the questions must have, separated by semicolons, such as \"rust;tokio\"."
+                    },
+                    "site": {
+                        "type": "string",
+                        "description": "Optional Stack Exchange site, such as \"superuser\" or \"unix\". Defaults to \"stackoverflow\"."
+                    }
+                },
+                "required": ["query"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!(
+                "Searching {} for '{}'",
+                match optional(arguments, "site") {
+                    None | Some("stackoverflow") => "Stack Overflow",
+                    Some(site) => site,
+                },
+                text(arguments, "query")
+            ),
+            "Reasoning with the answers",
+            "Stack Exchange search failed",
+            "Error: The Stack Exchange search failed. Please answer without it.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            stackexchange::search(
+                text(arguments, "query"),
+                optional(arguments, "site"),
+                optional(arguments, "tagged"),
+            )
+            .await
+        })
+    }
+}
+
+struct Calculate;
+
+impl Tool for Calculate {
+    fn name(&self) -> &str {
+        "calculate"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Evaluate a math expression exactly, with arbitrary precision and unit conversion. Always use this instead of doing arithmetic yourself. Supports + - * / ^ ! %, mod, parentheses, sqrt, ln, log, exp, sin, cos, tan, abs, round, pi and e, and units such as km, mi, ft, in, kg, lb, L, gal, h, min, °C, °F, J, kWh, W, Pa, psi, GB, MiB and Mbit. Convert with \"to\", as in \"5 ft 2 in to cm\" or \"100 °F to °C\".",
+            json!({
+                "type": "object",
+                "properties": {
+                    "expression": {
+                        "type": "string",
+                        "description": "The expression, such as \"2^64 - 1\", \"17.5% * 2300\" or \"60 mi/h to km/h\"."
+                    }
+                },
+                "required": ["expression"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        // The result is all there is to it, and it is already shown under the
+        // step; a failed expression is for the model to fix.
+        ToolSteps {
+            step: format!("Calculating {}", text(arguments, "expression")),
+            done: None,
+            failure: "Calculation failed",
+            fallback: None,
+        }
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move { calc::evaluate(text(arguments, "expression")) })
+    }
+}
+
+struct CryptoPrice;
+
+impl Tool for CryptoPrice {
+    fn name(&self) -> &str {
+        "crypto_price"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Get the live spot price of a cryptocurrency with its 24-hour change, high, low and volume, and market cap. Use this instead of web search for any question about current crypto prices.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "coin": {
+                        "type": "string",
+                        "description": "The coin's ticker or name, such as \"BTC\", \"ETH\" or \"solana\"."
+                    },
+                    "currency": {
+                        "type": "string",
+                        "description": "Currency to quote in, such as \"usd\", \"eur\" or \"btc\". Defaults to \"usd\"."
+                    }
+                },
+                "required": ["coin"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!(
+                "Checking the price of {} in {}",
+                text(arguments, "coin").to_uppercase(),
+                optional(arguments, "currency")
+                    .unwrap_or("usd")
+                    .to_uppercase()
+            ),
+            "Reasoning with market data",
+            "Price lookup failed",
+            "Error: The price could not be fetched. Please answer without it.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            market::price(text(arguments, "coin"), optional(arguments, "currency")).await
+        })
+    }
+}
+
+struct StockQuote;
+
+impl Tool for StockQuote {
+    fn name(&self) -> &str {
+        "stock_quote"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Get the latest quote for a stock or ETF: price, change since the previous close, day range and volume. Use this instead of web search for current share prices.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "symbol": {
+                        "type": "string",
+                        "description": "The ticker symbol, such as \"AAPL\" or \"MSFT\"."
+                    }
+                },
+                "required": ["symbol"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!(
+                "Getting a quote for {}",
+                text(arguments, "symbol").to_uppercase()
+            ),
+            "Reasoning with market data",
+            "Quote lookup failed",
+            "Error: The stock quote could not be fetched. Please answer without it.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move { market::stock_quote(text(arguments, "symbol")).await })
+    }
+}
+
+struct ReadFeed;
+
+impl Tool for ReadFeed {
+    fn name(&self) -> &str {
+        "read_feed"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Read the recent entries of an RSS or Atom feed, with their dates, summaries and links. Use this for what is new on a blog, news site or project. The URL may be the feed itself or a page that links to one.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The feed URL, or the URL of the site whose feed to read."
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "How many of the newest entries to list, up to 30. Defaults to 10."
+                    }
+                },
+                "required": ["url"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!("Reading the feed at {}", text(arguments, "url")),
+            "Reasoning with feed entries",
+            "Feed read failed",
+            "Error: The feed could not be read. Please answer without it.",
+        )
+    }
+
+    fn destination<'a>(&self, arguments: &'a Value) -> Option<&'a str> {
+        Some(text(arguments, "url"))
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let limit = arguments["limit"].as_u64().map(|limit| limit as usize);
+            feed::read(text(arguments, "url"), limit).await
+        })
+    }
+}
+
+struct HttpRequest;
+
+impl Tool for HttpRequest {
+    fn name(&self) -> &str {
+        "http_request"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Call an HTTP API with GET or POST and read the status, main headers and body. Use this for JSON APIs and endpoints the user names. Requests to domains the user has not allowed need their approval first, and may be refused.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "method": {
+                        "type": "string",
+                        "enum": ["GET", "POST"],
+                        "description": "The HTTP method. Defaults to GET."
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "The full http or https URL, including any query string."
+                    },
+                    "headers": {
+                        "type": "object",
+                        "description": "Request headers as name-value pairs, such as {\"Content-Type\": \"application/json\"}.",
+                        "additionalProperties": { "type": "string" }
+                    },
+                    "body": {
+                        "type": "string",
+                        "description": "The request body, for POST only."
+                    }
+                },
+                "required": ["url"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        // A failed request is for the model to fix.
+        ToolSteps {
+            step: format!("Calling {} {}", method(arguments), text(arguments, "url")),
+            done: Some("Reasoning with the response"),
+            failure: "HTTP request failed",
+            fallback: None,
+        }
+    }
+
+    fn url<'a>(&self, arguments: &'a Value) -> Option<&'a str> {
+        Some(text(arguments, "url"))
+    }
+
+    /// A GET only reads.
+    fn mutates(&self, arguments: &Value) -> bool {
+        method(arguments) != "GET"
+    }
+
+    fn mutation(&self) -> Mutation {
+        Mutation::Sometimes("for non-GET methods")
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let headers: Vec<(String, String)> = arguments["headers"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(name, value)| {
+                    let value = match value.as_str() {
+                        Some(text) => text.to_string(),
+                        None => value.to_string(),
+                    };
+                    (name.clone(), value)
+                })
+                .collect();
+            http::send(
+                &method(arguments),
+                text(arguments, "url"),
+                &headers,
+                optional(arguments, "body"),
+            )
+            .await
+        })
+    }
+}
+
+struct DateTime;
+
+impl Tool for DateTime {
+    fn name(&self) -> &str {
+        "datetime"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Exact dates and times: the current time in any time zone, converting a time between zones, adding or subtracting a duration, and the time between two dates. Use this rather than working out dates yourself.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["now", "convert", "add", "between"],
+                        "description": "now: current time in `timezone` (several may be comma-separated). convert: `time` in `timezone` shown in `to_timezone`. add: `time` plus `duration`. between: the span from `time` to `end`."
+                    },
+                    "time": {
+                        "type": "string",
+                        "description": "A date or time such as \"2025-03-14\", \"2025-03-14 09:30\", \"17:00\" (today) or an RFC 3339 timestamp. Defaults to now."
+                    },
+                    "timezone": {
+                        "type": "string",
+                        "description": "IANA time zone the time is in, such as \"America/New_York\" or \"Asia/Tokyo\". Defaults to the user's local zone."
+                    },
+                    "to_timezone": {
+                        "type": "string",
+                        "description": "For convert: the IANA time zone to show the time in."
+                    },
+                    "duration": {
+                        "type": "string",
+                        "description": "For add: terms such as \"90 days\", \"-2 weeks\" or \"1 year 3 months 4 hours\"."
+                    },
+                    "end": {
+                        "type": "string",
+                        "description": "For between: the second date or time."
+                    }
+                },
+                "required": ["action"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        // A date the model got wrong is for it to fix.
+        ToolSteps {
+            step: match text(arguments, "action") {
+                "convert" => "Converting between time zones",
+                "add" | "between" => "Working out dates",
+                _ => "Checking the time",
+            }
+            .to_string(),
+            done: Some("Reasoning with the time"),
+            failure: "Date calculation failed",
+            fallback: None,
+        }
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let time = optional(arguments, "time");
+            let zone = optional(arguments, "timezone");
+            datetime::query(match optional(arguments, "action").unwrap_or("now") {
+                "now" => datetime::Query::Now { zones: zone },
+                "convert" => datetime::Query::Convert {
+                    time,
+                    from: zone,
+                    to: optional(arguments, "to_timezone"),
+                },
+                "add" => datetime::Query::Add {
+                    time,
+                    zone,
+                    duration: optional(arguments, "duration").ok_or("add needs a duration")?,
+                },
+                "between" => datetime::Query::Between {
+                    time,
+                    end: optional(arguments, "end").ok_or("between needs an end time")?,
+                    zone,
+                },
+                other => {
+                    return Err(format!(
+                        "Unknown datetime action '{}'; use now, convert, add or between",
+                        other
+                    ))
+                }
+            })
+        })
+    }
+}
+
+struct ReadImage;
+
+impl Tool for ReadImage {
+    fn name(&self) -> &str {
+        "read_image"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Read the text in a local image file, such as a screenshot, photo of a document or scanned page, using OCR.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to a PNG, JPEG, GIF, BMP, TIFF or WebP file."
+                    }
+                },
+                "required": ["path"]
+            }),
+        )
+    }
+
+    fn steps(&self, arguments: &Value) -> ToolSteps {
+        steps(
+            format!("Reading the text in '{}'", text(arguments, "path")),
+            "Reasoning with the image text",
+            "Failed to read image",
+            "Error: No text could be read from the image.",
+        )
+    }
+
+    fn execute<'a>(&'a self, arguments: &'a Value, context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            context
+                .ocr
+                .image_text(Path::new(text(arguments, "path")))
+                .await
+        })
+    }
+}
+
+/// Offered only when `[clipboard] tool` is on.
+struct ReadClipboard;
+
+impl Tool for ReadClipboard {
+    fn name(&self) -> &str {
+        "read_clipboard"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        function(
+            self.name(),
+            "Read the text currently on the user's clipboard. Use this when the user refers to something they copied.",
+            json!({
+                "type": "object",
+                "properties": {}
+            }),
+        )
+    }
+
+    fn steps(&self, _arguments: &Value) -> ToolSteps {
+        steps(
+            "Reading your clipboard".to_string(),
+            "Reasoning with the clipboard",
+            "Failed to read the clipboard",
+            "Error: The clipboard could not be read.",
+        )
+    }
+
+    fn execute<'a>(&'a self, _arguments: &'a Value, _context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(async move { clipboard::read() })
+    }
+}
+
+fn function(name: &str, description: &str, parameters: Value) -> ToolDefinition {
+    ToolDefinition {
+        r#type: "function".to_string(),
+        function: ToolFunction {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters,
+        },
+    }
+}
+
+fn steps(
+    step: String,
+    done: &'static str,
+    failure: &'static str,
+    fallback: &'static str,
+) -> ToolSteps {
+    ToolSteps {
+        step,
+        done: Some(done),
+        failure,
+        fallback: Some(fallback),
+    }
+}
+
+/// A text argument, empty when it is missing.
+fn text<'a>(arguments: &'a Value, key: &str) -> &'a str {
+    arguments[key].as_str().unwrap_or("")
+}
+
+fn optional<'a>(arguments: &'a Value, key: &str) -> Option<&'a str> {
+    arguments[key].as_str()
+}
+
+/// The query of a `brave_search` call and its rephrasings, without
+/// repeats.
+fn queries(arguments: &Value) -> Vec<&str> {
+    let mut queries = vec![text(arguments, "query")];
+    let rephrasings = arguments["rephrasings"].as_array().into_iter().flatten();
+    for query in rephrasings.filter_map(Value::as_str).take(MAX_REPHRASINGS) {
+        let query = query.trim();
+        if !query.is_empty() && !queries.iter().any(|q| q.eq_ignore_ascii_case(query)) {
+            queries.push(query);
+        }
+    }
+    queries
+}
+
+fn method(arguments: &Value) -> String {
+    optional(arguments, "method")
+        .unwrap_or("GET")
+        .to_uppercase()
+}
@@ -0,0 +1,438 @@
+//! Tools the model may call, and their execution.
+//!
+//! Each tool is a [`Tool`]: its definition, how a call is described while it
+//! runs, and the call itself. A [`ToolRegistry`] holds the built-in tools and
+//! the plugin tools, so a new tool is registered in one place and the chat,
+//! the bots and the MCP server run calls the same way.
+
+mod builtin;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::fetch::Fetcher;
+use crate::http::{self, Allowlist, Egress};
+use crate::memory::MemoryStore;
+use crate::message::ToolDefinition;
+use crate::ocr::Ocr;
+use crate::plugins::PluginHost;
+use crate::rag::Retriever;
+use crate::search::BraveSearchClient;
+
+/// Names of the tools built into Rusty, which plugins may not redefine.
+pub const BUILTIN_TOOL_NAMES: &[&str] = &[
+    "brave_search",
+    "open",
+    "remember",
+    "search_docs",
+    "read_pdf",
+    "youtube_transcript",
+    "wikipedia",
+    "arxiv",
+    "github",
+    "stack_exchange",
+    "calculate",
+    "crypto_price",
+    "stock_quote",
+    "read_feed",
+    "http_request",
+    "datetime",
+    "read_image",
+    "read_clipboard",
+];
+
+/// Tools that need the internet, left out while it can't be reached.
+pub const NETWORK_TOOLS: &[&str] = &[
+    "brave_search",
+    "open",
+    "youtube_transcript",
+    "wikipedia",
+    "arxiv",
+    "github",
+    "stack_exchange",
+    "crypto_price",
+    "stock_quote",
+    "read_feed",
+    "http_request",
+];
+
+/// Tools offered only in some cases: `search_docs` once documents are
+/// indexed, and `read_clipboard` when `[clipboard] tool` is on.
+pub const OPTIONAL_TOOLS: &[&str] = &["search_docs", "read_clipboard"];
+
+/// What the model is told of a call read-only mode refused.
+pub const READ_ONLY_REFUSAL: &str = "Error: Rusty is in read-only mode, which refuses tool calls that may change something. Answer without this one.";
+
+/// What the model is told of a call to a tool that runs programs in a
+/// workspace that is not trusted.
+pub const UNTRUSTED_REFUSAL: &str = "Error: Tools that run programs are off here, as the user does not trust this workspace. Answer without them.";
+
+/// Schema violations quoted back to the model about one tool call.
+const MAX_ARGUMENT_ERRORS: usize = 5;
+
+/// Repeated calls in one turn after which the model must answer without
+/// calling more tools.
+pub const MAX_REPEATED_CALLS: usize = 2;
+
+/// What running a tool call eventually returns: the text handed back to the
+/// model, or why the call failed.
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>>;
+
+/// What a tool may use while it runs.
+pub struct ToolContext<'a> {
+    pub brave: &'a BraveSearchClient,
+    pub memory: &'a mut MemoryStore,
+    /// The indexed documents, if there are any.
+    pub documents: Option<&'a Retriever>,
+    pub ocr: &'a Ocr,
+    pub fetcher: &'a Fetcher,
+}
+
+/// How a tool call is shown while it runs and once it is done.
+pub struct ToolSteps {
+    /// Says what the call is doing, such as "Searching Brave for 'rust'".
+    pub step: String,
+    /// Shown when it worked, or `None` when its output says it all.
+    pub done: Option<&'static str>,
+    /// Put before the error in the record of the run.
+    pub failure: &'static str,
+    /// What the model is told when it failed, or `None` to tell it the
+    /// error itself, for calls it can fix.
+    pub fallback: Option<&'static str>,
+}
+
+/// Which calls of a tool may change something, for listings such as
+/// `/tools`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    Never,
+    /// Only some calls, as the phrase says, such as "for non-GET methods".
+    Sometimes(&'static str),
+    Always,
+}
+
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The definition offered to the model, with its parameters schema.
+    fn definition(&self) -> ToolDefinition;
+
+    /// How a call with `arguments` is shown.
+    fn steps(&self, arguments: &Value) -> ToolSteps;
+
+    /// The URL a call with `arguments` reaches, which the allowlist or the
+    /// user must approve before it runs.
+    fn url<'a>(&self, _arguments: &'a Value) -> Option<&'a str> {
+        None
+    }
+
+    /// The URL a call with `arguments` contacts, which the egress allowlist
+    /// must allow.
+    fn destination<'a>(&self, arguments: &'a Value) -> Option<&'a str> {
+        self.url(arguments)
+    }
+
+    /// Whether the tool runs programs of its own on this machine, which is
+    /// only allowed in trusted workspaces.
+    fn runs_programs(&self) -> bool {
+        false
+    }
+
+    /// Whether a call with `arguments` may change something, on this
+    /// machine or elsewhere, which read-only mode refuses. Tools that run
+    /// programs are taken to.
+    fn mutates(&self, _arguments: &Value) -> bool {
+        self.runs_programs()
+    }
+
+    /// Which of its calls `mutates` says change something. Tools that
+    /// override `mutates` say so here as well.
+    fn mutation(&self) -> Mutation {
+        if self.runs_programs() {
+            Mutation::Always
+        } else {
+            Mutation::Never
+        }
+    }
+
+    /// Runs a call with `arguments`, already checked against the schema.
+    fn execute<'a>(&'a self, arguments: &'a Value, context: ToolContext<'a>) -> ToolFuture<'a>;
+}
+
+/// What decides whether a tool call may run, the same for the chat, the
+/// bots and the MCP server.
+pub struct Policy<'a> {
+    /// Calls that may change something are refused.
+    pub read_only: bool,
+    /// Whether the workspace is trusted to run programs in, or `None`
+    /// while that was not decided.
+    pub trusted: Option<bool>,
+    /// Hosts any tool may contact at all.
+    pub egress: &'a Egress,
+    /// Hosts `http_request` may call without asking.
+    pub allowlist: &'a Allowlist,
+}
+
+/// What [`Policy::check`] decided about a call.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Gate {
+    Run,
+    /// The tool runs programs, and whether the workspace is trusted has to
+    /// be decided first.
+    AskTrust,
+    /// The call reaches this host, which is not on the allowlist.
+    AskHost(String),
+    /// `reason` is for the user, and `message` what the model is told.
+    Refused {
+        reason: String,
+        message: String,
+    },
+}
+
+impl Gate {
+    /// The decision where nobody can be asked: an undecided workspace is
+    /// not trusted and hosts off the allowlist are refused. The error is
+    /// what the model is told.
+    pub fn unattended(self) -> Result<(), String> {
+        match self {
+            Gate::Run => Ok(()),
+            Gate::AskTrust => Err(UNTRUSTED_REFUSAL.to_string()),
+            Gate::AskHost(host) => Err(format!(
+                "Error: {} is not on the [http] allowlist in Rusty's config.toml",
+                host
+            )),
+            Gate::Refused { message, .. } => Err(message),
+        }
+    }
+}
+
+impl Policy<'_> {
+    /// Whether `tool` is offered at all: not when it runs programs in a
+    /// workspace that is not trusted, nor in read-only mode when every
+    /// call of it changes something.
+    pub fn offers(&self, tool: &dyn Tool) -> bool {
+        !(self.trusted == Some(false) && tool.runs_programs()
+            || self.read_only && tool.mutation() == Mutation::Always)
+    }
+
+    /// Whether a call of `tool` with `arguments` may run: the egress
+    /// allowlist, read-only mode and the workspace's trust are checked in
+    /// that order, and then whether the host it reaches, if any, is on the
+    /// allowlist.
+    pub fn check(&self, tool: &dyn Tool, arguments: &Value) -> Gate {
+        if let Some(url) = tool.destination(arguments) {
+            if let Err(message) = self.egress.check(tool.name(), url) {
+                let host = http::host(url).unwrap_or_default();
+                return Gate::Refused {
+                    reason: format!("{} is not on the egress allowlist", host),
+                    message,
+                };
+            }
+        }
+        if self.read_only && tool.mutates(arguments) {
+            return Gate::Refused {
+                reason: "read-only mode".to_string(),
+                message: READ_ONLY_REFUSAL.to_string(),
+            };
+        }
+        if tool.runs_programs() {
+            match self.trusted {
+                None => return Gate::AskTrust,
+                Some(false) => {
+                    return Gate::Refused {
+                        reason: "the workspace is not trusted".to_string(),
+                        message: UNTRUSTED_REFUSAL.to_string(),
+                    }
+                }
+                Some(true) => {}
+            }
+        }
+        if let Some(url) = tool.url(arguments) {
+            match http::host(url) {
+                Ok(host) if !self.allowlist.allows(&host) => return Gate::AskHost(host),
+                Ok(_) => {}
+                Err(e) => {
+                    return Gate::Refused {
+                        message: format!("Error: {}", e),
+                        reason: e,
+                    }
+                }
+            }
+        }
+        Gate::Run
+    }
+}
+
+/// Every tool that can be offered, built-in ones first.
+#[derive(Clone)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    /// The built-in tools and those of `plugins`.
+    pub fn new(plugins: &PluginHost) -> Self {
+        let mut registry = Self { tools: Vec::new() };
+        for tool in builtin::tools() {
+            registry.register(tool);
+        }
+        for tool in plugins.tools() {
+            registry.register(Arc::new(tool.clone()));
+        }
+        registry
+    }
+
+    /// Adds a tool, in place of any other of the same name.
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        match self.tools.iter().position(|t| t.name() == tool.name()) {
+            Some(i) => self.tools[i] = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.iter().find(|t| t.name() == name).cloned()
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|t| t.definition()).collect()
+    }
+}
+
+/// Notices the model calling a tool again with the same arguments in one
+/// turn, which would only bring back what it already has and, in a loop,
+/// use up the quota of search APIs.
+#[derive(Default)]
+pub struct RepeatGuard {
+    seen: HashSet<(String, String)>,
+    repeats: usize,
+}
+
+impl RepeatGuard {
+    /// Records a call and returns whether the same one was made before.
+    pub fn is_repeat(&mut self, name: &str, arguments: &Value) -> bool {
+        let repeat = !self.seen.insert((name.to_string(), arguments.to_string()));
+        if repeat {
+            self.repeats += 1;
+        }
+        repeat
+    }
+
+    /// Whether the model has repeated itself often enough that it must
+    /// answer now.
+    pub fn exhausted(&self) -> bool {
+        self.repeats >= MAX_REPEATED_CALLS
+    }
+
+    /// What the model is told instead of running a repeated call to `name`.
+    pub fn tool_message(&self, name: &str) -> String {
+        if self.exhausted() {
+            format!(
+                "Error: {} was already called with these arguments, and its result is above. \
+Stop calling tools and answer with what you have.",
+                name
+            )
+        } else {
+            format!(
+                "Error: {} was already called with these arguments, and its result is above. \
+Use that result, or call a tool with different arguments.",
+                name
+            )
+        }
+    }
+}
+
+/// Why the arguments of a tool call cannot be used.
+#[derive(Debug)]
+pub enum ArgumentError {
+    /// They are not valid JSON.
+    Malformed(serde_json::Error),
+    /// They are JSON, but break the tool's parameters schema in these ways.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(e) => write!(f, "not valid JSON: {}", e),
+            Self::Invalid(errors) => write!(f, "do not match the schema: {}", errors.join("; ")),
+        }
+    }
+}
+
+impl ArgumentError {
+    /// What the model is told about its call to `name`, so it can make the
+    /// call again.
+    pub fn tool_message(&self, name: &str) -> String {
+        match self {
+            Self::Malformed(e) => format!(
+                "Error: The arguments of this {} call are not valid JSON ({}). Call {} again \
+with its arguments as a single valid JSON object.",
+                name, e, name
+            ),
+            Self::Invalid(errors) => format!(
+                "Error: The arguments of this {} call do not match its parameters ({}). Call {} \
+again with arguments that follow its schema.",
+                name,
+                errors.join("; "),
+                name
+            ),
+        }
+    }
+}
+
+/// A call's JSON arguments as `key: value` pairs, for one line, with each
+/// value cut short.
+pub fn summarize_arguments(arguments: &str) -> String {
+    let shorten = |text: &str| {
+        if text.chars().count() <= 60 {
+            return text.to_string();
+        }
+        let mut cut: String = text.chars().take(59).collect();
+        cut.push('…');
+        cut
+    };
+    match serde_json::from_str::<Value>(arguments) {
+        Ok(Value::Object(map)) => map
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| format!("{}: {}", key, shorten(&value.to_string())))
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => shorten(arguments.trim()),
+    }
+}
+
+/// Reads the arguments of a call to `tool` and checks them against its
+/// parameters schema, so nothing missing or mistyped is quietly read as
+/// empty.
+pub fn read_arguments(tool: &dyn Tool, arguments: &str) -> Result<Value, ArgumentError> {
+    // Models leave the arguments of a tool without parameters empty.
+    let arguments: Value = match arguments.trim() {
+        "" => serde_json::json!({}),
+        text => serde_json::from_str(text).map_err(ArgumentError::Malformed)?,
+    };
+    // A plugin's schema that is itself broken is its author's problem, not
+    // the model's.
+    let Ok(validator) = jsonschema::validator_for(&tool.definition().function.parameters) else {
+        return Ok(arguments);
+    };
+    let errors: Vec<String> = validator
+        .iter_errors(&arguments)
+        .take(MAX_ARGUMENT_ERRORS)
+        .map(|e| match e.instance_path.to_string().as_str() {
+            "" => e.to_string(),
+            path => format!("{}: {}", path, e),
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(arguments)
+    } else {
+        Err(ArgumentError::Invalid(errors))
+    }
+}
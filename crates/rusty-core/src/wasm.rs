@@ -0,0 +1,149 @@
+//! Sandboxed tool plugins compiled to WebAssembly.
+//!
+//! A WASM plugin is a core module (no WASI) that exports:
+//!
+//! - `memory`
+//! - `rusty_alloc(len: i32) -> i32`, returning a buffer the host writes requests into
+//! - `rusty_describe() -> i64`
+//! - `rusty_call(ptr: i32, len: i32) -> i64`, receiving the call request JSON
+//!
+//! Both `rusty_describe` and `rusty_call` return the response JSON as a packed
+//! `(ptr << 32) | len`. Requests and responses use the same shapes as the
+//! stdio protocol in [`crate::plugins`].
+//!
+//! The only host function is `rusty.log(ptr: i32, len: i32)`, which hands a
+//! line to the front end to show. Plugins get no filesystem, network, or clock access, each
+//! invocation runs in a fresh instance, and execution is bounded by fuel and
+//! a memory cap.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use wasmtime::{Caller, Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::notice::{self, Notice};
+
+/// Roughly the number of wasm instructions a single invocation may execute.
+const FUEL_PER_INVOCATION: u64 = 2_000_000_000;
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+struct HostState {
+    limits: StoreLimits,
+    plugin: String,
+}
+
+pub struct WasmPlugin {
+    engine: Engine,
+    module: Module,
+    path: PathBuf,
+}
+
+impl fmt::Debug for WasmPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmPlugin")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl WasmPlugin {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| e.to_string())?;
+        let module = Module::from_file(&engine, path).map_err(|e| e.to_string())?;
+        Ok(Self {
+            engine,
+            module,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Returns the plugin's describe response JSON.
+    pub fn describe(&self) -> Result<String, String> {
+        self.invoke(None)
+    }
+
+    /// Hands `request` JSON to the plugin and returns its response JSON.
+    pub fn call(&self, request: &str) -> Result<String, String> {
+        self.invoke(Some(request))
+    }
+
+    fn invoke(&self, request: Option<&str>) -> Result<String, String> {
+        let plugin = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut store = Store::new(
+            &self.engine,
+            HostState {
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_BYTES)
+                    .build(),
+                plugin,
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store
+            .set_fuel(FUEL_PER_INVOCATION)
+            .map_err(|e| e.to_string())?;
+
+        let mut linker = Linker::new(&self.engine);
+        linker
+            .func_wrap(
+                "rusty",
+                "log",
+                |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                    let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory())
+                    else {
+                        return;
+                    };
+                    let mut buf = vec![0u8; len.max(0) as usize];
+                    if memory.read(&caller, ptr as usize, &mut buf).is_ok() {
+                        notice::send(Notice::PluginLog {
+                            plugin: &caller.data().plugin,
+                            line: &String::from_utf8_lossy(&buf),
+                        });
+                    }
+                },
+            )
+            .map_err(|e| e.to_string())?;
+
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .map_err(|e| e.to_string())?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or("plugin does not export `memory`")?;
+
+        let packed = match request {
+            None => instance
+                .get_typed_func::<(), i64>(&mut store, "rusty_describe")
+                .and_then(|f| f.call(&mut store, ()))
+                .map_err(|e| e.to_string())?,
+            Some(request) => {
+                let len = request.len() as i32;
+                let ptr = instance
+                    .get_typed_func::<i32, i32>(&mut store, "rusty_alloc")
+                    .and_then(|f| f.call(&mut store, len))
+                    .map_err(|e| e.to_string())?;
+                memory
+                    .write(&mut store, ptr as usize, request.as_bytes())
+                    .map_err(|e| e.to_string())?;
+                instance
+                    .get_typed_func::<(i32, i32), i64>(&mut store, "rusty_call")
+                    .and_then(|f| f.call(&mut store, (ptr, len)))
+                    .map_err(|e| e.to_string())?
+            }
+        };
+
+        let ptr = (packed >> 32) as u32 as usize;
+        let len = (packed & 0xffff_ffff) as u32 as usize;
+        let mut buf = vec![0u8; len];
+        memory
+            .read(&store, ptr, &mut buf)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(buf).map_err(|e| e.to_string())
+    }
+}
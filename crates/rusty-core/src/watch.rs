@@ -0,0 +1,102 @@
+//! Noticing saved files for `rusty watch`. Hidden files, editor backups and
+//! whatever the top `.gitignore` ignores are left out, and the events of one
+//! save, which editors often spread over several writes and renames, are
+//! reported once.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use ignore::gitignore::Gitignore;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use tokio::sync::mpsc;
+
+/// How long things must stay quiet after a change before it is reported.
+const SETTLE: Duration = Duration::from_millis(300);
+
+pub struct Watcher {
+    /// Stops watching when dropped.
+    _watcher: RecommendedWatcher,
+    changes: mpsc::UnboundedReceiver<PathBuf>,
+}
+
+impl Watcher {
+    /// Watches a directory and everything below it, or a single file.
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let target = path
+            .canonicalize()
+            .map_err(|e| format!("Cannot watch {}: {}", path.display(), e))?;
+        // Editors often save by renaming a new file over the old one, which
+        // a watch on the file itself would not survive.
+        let (root, only) = if target.is_dir() {
+            (target.clone(), None)
+        } else {
+            let parent = target.parent().unwrap_or(&target).to_path_buf();
+            (parent, Some(target.clone()))
+        };
+        let (gitignore, _) = Gitignore::new(root.join(".gitignore"));
+
+        let (sender, changes) = mpsc::unbounded_channel();
+        let filter_root = root.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    return;
+                }
+                for path in event.paths {
+                    let wanted = match &only {
+                        Some(file) => path == *file,
+                        None => is_source(&filter_root, &gitignore, &path),
+                    };
+                    if wanted && path.is_file() {
+                        let _ = sender.send(path);
+                    }
+                }
+            })
+            .map_err(|e| format!("Cannot watch {}: {}", path.display(), e))?;
+        let mode = if target.is_dir() {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher
+            .watch(&root, mode)
+            .map_err(|e| format!("Cannot watch {}: {}", path.display(), e))?;
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Waits for files to be saved and returns them, each once, in order,
+    /// once no more changes have come for a moment.
+    pub async fn changed(&mut self) -> Option<Vec<PathBuf>> {
+        let mut paths = vec![self.changes.recv().await?];
+        while let Ok(Some(path)) = tokio::time::timeout(SETTLE, self.changes.recv()).await {
+            paths.push(path);
+        }
+        paths.sort();
+        paths.dedup();
+        Some(paths)
+    }
+}
+
+/// Whether a changed path under `root` is worth a look: not hidden, not an
+/// editor's backup, and not ignored by Git.
+fn is_source(root: &Path, gitignore: &Gitignore, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+    let hidden = relative
+        .components()
+        .any(|part| part.as_os_str().to_string_lossy().starts_with('.'));
+    let name = relative.to_string_lossy();
+    if hidden || name.ends_with('~') || name.ends_with(".swp") {
+        return false;
+    }
+    !gitignore
+        .matched_path_or_any_parents(relative, false)
+        .is_ignore()
+}
@@ -0,0 +1,204 @@
+//! A small HTTP listener for `rusty webhook`: a JSON payload POSTed to
+//! `/<route>` becomes a job, answered at once with `202 Accepted` and its
+//! id, and the result is delivered later to the route's callback URL.
+//!
+//! Only what webhook senders need is understood: one request per
+//! connection, with a `Content-Length` body.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// The largest payload accepted, in bytes.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// How long a sender has to send its whole request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a callback has to answer.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A payload waiting for its prompt to run.
+#[derive(Debug)]
+pub struct Job {
+    /// Counts up from 1 while the listener runs; sent back in the response
+    /// and with the result.
+    pub id: u64,
+    pub route: String,
+    pub payload: Value,
+}
+
+/// Listens on `address`, which must be free, and sends a job for every
+/// accepted request. With a `secret`, requests must carry it as
+/// `Authorization: Bearer <secret>`. Each connection is read on its own, so
+/// a slow sender holds up nobody else.
+pub async fn listen(
+    address: &str,
+    secret: Option<String>,
+    routes: Vec<String>,
+) -> Result<mpsc::UnboundedReceiver<Job>, String> {
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let secret = Arc::new(secret);
+    let routes = Arc::new(routes);
+    let next_id = Arc::new(AtomicU64::new(1));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            if sender.is_closed() {
+                return;
+            }
+            let (sender, secret, routes, next_id) = (
+                sender.clone(),
+                secret.clone(),
+                routes.clone(),
+                next_id.clone(),
+            );
+            tokio::spawn(async move {
+                if let Some(job) = accept(stream, secret.as_deref(), &routes, &next_id).await {
+                    let _ = sender.send(job);
+                }
+            });
+        }
+    });
+    Ok(receiver)
+}
+
+/// POSTs `body` as JSON to a callback URL.
+pub async fn deliver(callback: &str, body: &Value) -> Result<(), String> {
+    let response = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?
+        .post(callback)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| format!("Callback to {} failed: {}", callback, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Callback to {} returned {}",
+            callback,
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+/// Reads one request and answers it, returning the job it carried, which
+/// takes the next id.
+async fn accept(
+    stream: TcpStream,
+    secret: Option<&str>,
+    routes: &[String],
+    next_id: &AtomicU64,
+) -> Option<Job> {
+    let mut stream = BufReader::new(stream);
+    let result = tokio::time::timeout(READ_TIMEOUT, read_job(&mut stream, secret, routes))
+        .await
+        .unwrap_or_else(|_| {
+            Err((
+                "408 Request Timeout",
+                "The request took too long".to_string(),
+            ))
+        })
+        .map(|(route, payload)| Job {
+            id: next_id.fetch_add(1, Ordering::Relaxed),
+            route,
+            payload,
+        });
+    let (status, body) = match &result {
+        Ok(job) => ("202 Accepted", json!({ "id": job.id })),
+        Err((status, message)) => (*status, json!({ "error": message })),
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.get_mut().write_all(response.as_bytes()).await;
+    let _ = stream.get_mut().shutdown().await;
+    result.ok()
+}
+
+/// The route and payload of a request.
+async fn read_job(
+    stream: &mut BufReader<TcpStream>,
+    secret: Option<&str>,
+    routes: &[String],
+) -> Result<(String, Value), (&'static str, String)> {
+    let bad_request = |message: &str| ("400 Bad Request", message.to_string());
+
+    let mut request_line = String::new();
+    stream
+        .read_line(&mut request_line)
+        .await
+        .map_err(|_| bad_request("Unreadable request"))?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+
+    let mut length = 0;
+    let mut authorization = String::new();
+    loop {
+        let mut line = String::new();
+        stream
+            .read_line(&mut line)
+            .await
+            .map_err(|_| bad_request("Unreadable headers"))?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.trim().parse().unwrap_or(0),
+            "authorization" => authorization = value.trim().to_string(),
+            _ => {}
+        }
+    }
+
+    if let Some(secret) = secret {
+        if authorization.strip_prefix("Bearer ") != Some(secret) {
+            return Err(("401 Unauthorized", "Wrong or missing secret".to_string()));
+        }
+    }
+    let route = target
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .trim_matches('/');
+    if !routes.iter().any(|r| r == route) {
+        return Err(("404 Not Found", format!("No route '{}'", route)));
+    }
+    if method != "POST" {
+        return Err(("405 Method Not Allowed", "Send a POST".to_string()));
+    }
+    if length > MAX_BODY {
+        return Err((
+            "413 Payload Too Large",
+            "The payload is too large".to_string(),
+        ));
+    }
+
+    let mut body = vec![0; length];
+    stream
+        .read_exact(&mut body)
+        .await
+        .map_err(|_| bad_request("The body ended early"))?;
+    let payload = serde_json::from_slice(&body)
+        .map_err(|e| bad_request(&format!("The body is not JSON: {}", e)))?;
+    Ok((route.to_string(), payload))
+}
@@ -0,0 +1,262 @@
+//! Encyclopedia lookups for the `wikipedia` tool: an article's summary from
+//! the REST API, and its sections in full on request.
+
+use reqwest::{StatusCode, Url};
+use serde_json::Value;
+
+/// Characters of section text returned at once.
+const MAX_CHARS: usize = 20_000;
+
+/// Matches offered when the title is not an article.
+const SEARCH_LIMIT: usize = 5;
+
+/// A heading and the plain text under it, up to the next heading.
+struct Section {
+    level: usize,
+    name: String,
+    body: String,
+}
+
+/// The summary and section list of the article `title`, or the text of
+/// one of its sections. Titles that are not articles are looked up with
+/// Wikipedia's search and the best match is used.
+pub async fn lookup(
+    title: &str,
+    section: Option<&str>,
+    language: Option<&str>,
+) -> Result<String, String> {
+    let language = language.unwrap_or("en");
+    if language.is_empty() || !language.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+        return Err(format!("Invalid Wikipedia language code: {}", language));
+    }
+    let wiki = Wiki {
+        base: format!("https://{}.wikipedia.org", language),
+        client: reqwest::Client::new(),
+    };
+
+    let mut others = Vec::new();
+    let summary = match wiki.summary(title).await? {
+        Some(summary) => summary,
+        None => {
+            let mut matches = wiki.search(title).await?.into_iter();
+            let best = matches
+                .next()
+                .ok_or_else(|| format!("No Wikipedia article matches '{}'", title))?;
+            others = matches.collect();
+            let key = best["key"].as_str().unwrap_or_default();
+            wiki.summary(key)
+                .await?
+                .ok_or_else(|| format!("No Wikipedia article matches '{}'", title))?
+        }
+    };
+
+    let name = summary["title"].as_str().unwrap_or(title);
+    let canonical = summary["titles"]["canonical"].as_str().unwrap_or(name);
+    let url = summary["content_urls"]["desktop"]["page"]
+        .as_str()
+        .unwrap_or_default();
+    let sections = parse_sections(&wiki.plain_text(canonical).await?);
+
+    match section {
+        Some(wanted) => read_section(name, url, &sections, wanted),
+        None => {
+            let mut output = format!("# {}\n", name);
+            if let Some(description) = summary["description"].as_str() {
+                output.push_str(&format!("{}\n", description));
+            }
+            output.push_str(&format!("{}\n\n", url));
+            if summary["type"].as_str() == Some("disambiguation") {
+                output.push_str(
+                    "(This is a disambiguation page; look up a more specific title.)\n\n",
+                );
+            }
+            output.push_str(summary["extract"].as_str().unwrap_or_default());
+            output.push_str("\n\n");
+
+            let headings: Vec<&Section> = sections.iter().filter(|s| s.level > 1).collect();
+            if !headings.is_empty() {
+                output.push_str("Sections:\n");
+                for section in headings {
+                    output.push_str(&format!(
+                        "{}- {}\n",
+                        "  ".repeat(section.level - 2),
+                        section.name
+                    ));
+                }
+                output.push_str("[Request a section by name to read it in full.]\n");
+            }
+            if !others.is_empty() {
+                output.push_str("\nOther matching articles:\n");
+                for page in others {
+                    output.push_str(&format!("- {}", page["title"].as_str().unwrap_or_default()));
+                    if let Some(description) = page["description"].as_str() {
+                        output.push_str(&format!(": {}", description));
+                    }
+                    output.push('\n');
+                }
+            }
+            Ok(output)
+        }
+    }
+}
+
+struct Wiki {
+    base: String,
+    client: reqwest::Client,
+}
+
+impl Wiki {
+    /// The REST summary of `title`, following redirects; `None` when there
+    /// is no such article.
+    async fn summary(&self, title: &str) -> Result<Option<Value>, String> {
+        let mut url = Url::parse(&format!("{}/api/rest_v1/page/summary", self.base))
+            .map_err(|e| e.to_string())?;
+        url.path_segments_mut()
+            .map_err(|_| "Invalid Wikipedia URL".to_string())?
+            .push(&title.replace(' ', "_"));
+        let response = self.get(url.as_str(), &[]).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        json(response).await.map(Some)
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Value>, String> {
+        let limit = SEARCH_LIMIT.to_string();
+        let url = format!("{}/w/rest.php/v1/search/page", self.base);
+        let response = self.get(&url, &[("q", query), ("limit", &limit)]).await?;
+        let json = json(response).await?;
+        Ok(json["pages"].as_array().cloned().unwrap_or_default())
+    }
+
+    /// The whole article as plain text, with `== Heading ==` lines.
+    async fn plain_text(&self, title: &str) -> Result<String, String> {
+        let url = format!("{}/w/api.php", self.base);
+        let response = self
+            .get(
+                &url,
+                &[
+                    ("action", "query"),
+                    ("prop", "extracts"),
+                    ("explaintext", "1"),
+                    ("exsectionformat", "wiki"),
+                    ("redirects", "1"),
+                    ("format", "json"),
+                    ("formatversion", "2"),
+                    ("titles", title),
+                ],
+            )
+            .await?;
+        let json = json(response).await?;
+        Ok(json["query"]["pages"][0]["extract"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    async fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<reqwest::Response, String> {
+        self.client
+            .get(url)
+            .query(query)
+            .header("User-Agent", "rusty-cli")
+            .send()
+            .await
+            .map_err(|e| format!("Wikipedia request failed: {}", e))
+    }
+}
+
+async fn json(response: reqwest::Response) -> Result<Value, String> {
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Wikipedia returned {}", status));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Wikipedia response: {}", e))
+}
+
+/// Splits a plain-text article at its `== Heading ==` lines. The text
+/// before the first heading is a level 1 section with no name.
+fn parse_sections(text: &str) -> Vec<Section> {
+    let mut sections = vec![Section {
+        level: 1,
+        name: String::new(),
+        body: String::new(),
+    }];
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let level = trimmed.chars().take_while(|&c| c == '=').count();
+        let is_heading =
+            level >= 2 && trimmed.len() > level * 2 && trimmed.ends_with(&"=".repeat(level));
+        if is_heading {
+            sections.push(Section {
+                level,
+                name: trimmed[level..trimmed.len() - level].trim().to_string(),
+                body: String::new(),
+            });
+        } else {
+            let body = &mut sections.last_mut().unwrap().body;
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    sections
+}
+
+/// The section named `wanted` with its subsections, matched exactly and
+/// then by substring, ignoring case.
+fn read_section(
+    title: &str,
+    url: &str,
+    sections: &[Section],
+    wanted: &str,
+) -> Result<String, String> {
+    let lowered = wanted.trim().to_lowercase();
+    let start = sections
+        .iter()
+        .position(|s| s.level > 1 && s.name.to_lowercase() == lowered)
+        .or_else(|| {
+            sections
+                .iter()
+                .position(|s| s.level > 1 && s.name.to_lowercase().contains(&lowered))
+        })
+        .ok_or_else(|| {
+            let names: Vec<&str> = sections
+                .iter()
+                .filter(|s| s.level > 1)
+                .map(|s| s.name.as_str())
+                .collect();
+            format!(
+                "'{}' has no section '{}'; its sections are: {}",
+                title,
+                wanted.trim(),
+                names.join(", ")
+            )
+        })?;
+
+    let level = sections[start].level;
+    let mut output = format!(
+        "# {} § {}\n{}#{}\n\n",
+        title,
+        sections[start].name,
+        url,
+        sections[start].name.replace(' ', "_")
+    );
+    for (i, section) in sections[start..].iter().enumerate() {
+        if i > 0 {
+            if section.level <= level {
+                break;
+            }
+            output.push_str(&format!("{} {}\n", "#".repeat(section.level), section.name));
+        }
+        output.push_str(section.body.trim());
+        output.push_str("\n\n");
+    }
+
+    if output.chars().count() > MAX_CHARS {
+        output = output.chars().take(MAX_CHARS).collect();
+        output.push_str("\n[Truncated. Request a subsection to read the rest.]\n");
+    }
+    Ok(output)
+}
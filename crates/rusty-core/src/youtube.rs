@@ -0,0 +1,296 @@
+//! Transcripts of YouTube videos, for the `youtube_transcript` tool.
+
+use regex::Regex;
+use reqwest::Url;
+use serde_json::{json, Value};
+
+const WATCH_URL: &str = "https://www.youtube.com/watch";
+const PLAYER_URL: &str = "https://www.youtube.com/youtubei/v1/player";
+
+/// Characters of transcript returned per part; longer transcripts are split
+/// and read on with the `part` argument.
+const PART_CHARS: usize = 40_000;
+
+/// Seconds of speech gathered under one timestamp.
+const PARAGRAPH_SECONDS: f64 = 30.0;
+
+/// Part `part` (1-based) of the transcript of `video`, a YouTube URL or
+/// video ID. Captions in `language` are preferred, then English, and
+/// uploaded captions are preferred over auto-generated ones.
+pub async fn transcript(
+    video: &str,
+    language: Option<&str>,
+    part: usize,
+) -> Result<String, String> {
+    let id = video_id(video).ok_or_else(|| format!("Not a YouTube video URL or ID: {}", video))?;
+    let client = reqwest::Client::new();
+
+    // The player endpoint wants the key embedded in the watch page.
+    let page = client
+        .get(WATCH_URL)
+        .query(&[("v", id.as_str())])
+        .header("Accept-Language", "en-US")
+        .send()
+        .await
+        .map_err(request_error)?
+        .text()
+        .await
+        .map_err(request_error)?;
+    let api_key = Regex::new(r#""INNERTUBE_API_KEY":\s*"([\w-]+)""#)
+        .unwrap()
+        .captures(&page)
+        .map(|c| c[1].to_string())
+        .ok_or("YouTube did not return a video page")?;
+
+    let player: Value = client
+        .post(PLAYER_URL)
+        .query(&[("key", api_key.as_str())])
+        .json(&json!({
+            "context": { "client": { "clientName": "ANDROID", "clientVersion": "20.10.38" } },
+            "videoId": id,
+        }))
+        .send()
+        .await
+        .map_err(request_error)?
+        .json()
+        .await
+        .map_err(request_error)?;
+    let status = player["playabilityStatus"]["status"].as_str().unwrap_or("");
+    if status != "OK" {
+        let reason = player["playabilityStatus"]["reason"]
+            .as_str()
+            .unwrap_or("the video is unavailable");
+        return Err(format!("YouTube cannot play {}: {}", id, reason));
+    }
+
+    let tracks = player["captions"]["playerCaptionsTracklistRenderer"]["captionTracks"]
+        .as_array()
+        .filter(|tracks| !tracks.is_empty())
+        .ok_or("This video has no captions")?;
+    let track = pick_track(tracks, language)?;
+    let base_url = track["baseUrl"]
+        .as_str()
+        .ok_or("Caption track has no URL")?;
+    let xml = client
+        .get(base_url.replace("&fmt=srv3", ""))
+        .send()
+        .await
+        .map_err(request_error)?
+        .text()
+        .await
+        .map_err(request_error)?;
+
+    let paragraphs = paragraphs(&parse_captions(&xml));
+    if paragraphs.is_empty() {
+        return Err("The caption track is empty".to_string());
+    }
+    let parts = split_parts(&paragraphs);
+    if part == 0 || part > parts.len() {
+        return Err(format!(
+            "Invalid part {}; the transcript has {} parts",
+            part,
+            parts.len()
+        ));
+    }
+
+    let title = player["videoDetails"]["title"].as_str().unwrap_or(&id);
+    let mut output = format!(
+        "Transcript of \"{}\" ({})\n{}?v={}\n",
+        title,
+        track_label(track),
+        WATCH_URL,
+        id
+    );
+    if parts.len() > 1 {
+        output.push_str(&format!("Part {} of {}\n", part, parts.len()));
+    }
+    output.push('\n');
+    output.push_str(&parts[part - 1]);
+    if part < parts.len() {
+        output.push_str(&format!(
+            "[Part {} of {}. Request part {} to read on.]\n",
+            part,
+            parts.len(),
+            part + 1
+        ));
+    }
+    Ok(output)
+}
+
+/// The 11-character video ID in a watch, short, embed or `youtu.be` URL, or
+/// `input` itself when it already is one.
+pub fn video_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let is_id = |s: &str| {
+        s.len() == 11
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    if is_id(input) {
+        return Some(input.to_string());
+    }
+
+    let url = if input.contains("://") {
+        Url::parse(input).ok()?
+    } else {
+        Url::parse(&format!("https://{}", input)).ok()?
+    };
+    let host = url
+        .host_str()?
+        .trim_start_matches("www.")
+        .trim_start_matches("m.");
+    let mut segments = url.path_segments()?;
+    let id = match host {
+        "youtu.be" => segments.next()?.to_string(),
+        "youtube.com" | "music.youtube.com" | "youtube-nocookie.com" => match segments.next()? {
+            "watch" => url
+                .query_pairs()
+                .find(|(key, _)| key == "v")
+                .map(|(_, value)| value.into_owned())?,
+            "shorts" | "embed" | "live" | "v" => segments.next()?.to_string(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    is_id(&id).then_some(id)
+}
+
+fn pick_track<'a>(tracks: &'a [Value], language: Option<&str>) -> Result<&'a Value, String> {
+    let code = |track: &Value| track["languageCode"].as_str().unwrap_or("").to_string();
+    let uploaded = |track: &&Value| track["kind"].as_str() != Some("asr");
+    let in_language = |wanted: &str| {
+        let matching = || {
+            tracks.iter().filter(move |track| {
+                let code = code(track);
+                code == wanted || code.split('-').next() == Some(wanted)
+            })
+        };
+        matching().find(uploaded).or_else(|| matching().next())
+    };
+
+    if let Some(language) = language {
+        return in_language(language).ok_or_else(|| {
+            let available: Vec<String> = tracks
+                .iter()
+                .map(|track| match track["kind"].as_str() {
+                    Some("asr") => format!("{} (auto-generated)", code(track)),
+                    _ => code(track),
+                })
+                .collect();
+            format!(
+                "No {} captions; available: {}",
+                language,
+                available.join(", ")
+            )
+        });
+    }
+    Ok(in_language("en")
+        .or_else(|| tracks.iter().find(uploaded))
+        .unwrap_or(&tracks[0]))
+}
+
+/// The language of a caption track, e.g. `English, auto-generated`.
+fn track_label(track: &Value) -> String {
+    let name = track["name"]["runs"][0]["text"]
+        .as_str()
+        .or(track["name"]["simpleText"].as_str())
+        .or(track["languageCode"].as_str())
+        .unwrap_or("unknown language");
+    let name = name.trim_end_matches(" (auto-generated)");
+    if track["kind"].as_str() == Some("asr") {
+        format!("{}, auto-generated", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// `(start seconds, text)` for each caption in a timedtext XML document.
+fn parse_captions(xml: &str) -> Vec<(f64, String)> {
+    let caption = Regex::new(r#"(?s)<text start="([\d.]+)"[^>]*>(.*?)</text>"#).unwrap();
+    let tags = Regex::new(r"<[^>]+>").unwrap();
+    caption
+        .captures_iter(xml)
+        .filter_map(|c| {
+            let start = c[1].parse().ok()?;
+            // Caption text is escaped once for XML and often once more as HTML.
+            let text = unescape(&unescape(&c[2]));
+            let text = tags.replace_all(&text, "");
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            (!text.is_empty()).then_some((start, text))
+        })
+        .collect()
+}
+
+fn unescape(text: &str) -> String {
+    let entity = Regex::new(r"&(#x?[0-9a-fA-F]+|amp|lt|gt|quot|apos);").unwrap();
+    entity
+        .replace_all(text, |c: &regex::Captures| {
+            let name = &c[1];
+            let decoded = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ if name.starts_with("#x") => u32::from_str_radix(&name[2..], 16)
+                    .ok()
+                    .and_then(char::from_u32),
+                _ => name[1..].parse().ok().and_then(char::from_u32),
+            };
+            decoded.map_or_else(|| c[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+/// Captions joined into paragraphs of about [`PARAGRAPH_SECONDS`], each
+/// starting with its timestamp.
+fn paragraphs(captions: &[(f64, String)]) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current: Option<(f64, String)> = None;
+    for (start, text) in captions {
+        match &mut current {
+            Some((began, paragraph)) if start - *began < PARAGRAPH_SECONDS => {
+                paragraph.push(' ');
+                paragraph.push_str(text);
+            }
+            _ => {
+                if let Some((began, paragraph)) = current.take() {
+                    paragraphs.push(format!("[{}] {}", timestamp(began), paragraph));
+                }
+                current = Some((*start, text.clone()));
+            }
+        }
+    }
+    if let Some((began, paragraph)) = current {
+        paragraphs.push(format!("[{}] {}", timestamp(began), paragraph));
+    }
+    paragraphs
+}
+
+fn split_parts(paragraphs: &[String]) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    for paragraph in paragraphs {
+        let last = parts.last_mut().unwrap();
+        if !last.is_empty() && last.len() + paragraph.len() > PART_CHARS {
+            parts.push(String::new());
+        }
+        let last = parts.last_mut().unwrap();
+        last.push_str(paragraph);
+        last.push_str("\n\n");
+    }
+    parts
+}
+
+fn timestamp(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+fn request_error(e: reqwest::Error) -> String {
+    format!("YouTube request failed: {}", e)
+}
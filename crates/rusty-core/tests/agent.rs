@@ -0,0 +1,207 @@
+//! The bots' agent loop, driven by the mock provider: tool calls run and
+//! their results go back to the model, read-only mode refuses calls that
+//! change something, and secrets are masked before anything is sent. A
+//! played-back cassette drives it through a search as a recorded session
+//! would.
+
+use std::sync::Arc;
+
+use rusty_core::agent::Agent;
+use rusty_core::cassette::Cassette;
+use rusty_core::config::{HooksConfig, RedactConfig};
+use rusty_core::conversation::ConversationManager;
+use rusty_core::hooks::CommandHooks;
+use rusty_core::http::Allowlist;
+use rusty_core::ocr::Ocr;
+use rusty_core::plugins::PluginHost;
+use rusty_core::provider::{MockProvider, MockReply, MockToolCall, Provider, Recorder};
+use rusty_core::redact::Redactor;
+use rusty_core::scripting::ScriptHooks;
+use rusty_core::search::BraveSearchClient;
+use rusty_core::tools::READ_ONLY_REFUSAL;
+use serde_json::json;
+
+fn agent(script: Vec<MockReply>) -> Agent {
+    Agent::new(
+        Provider::Mock(MockProvider::new(script)),
+        BraveSearchClient::new(String::new()),
+        PluginHost::default(),
+        ScriptHooks::new(),
+        CommandHooks::new(HooksConfig::default()),
+        Ocr::default(),
+        Allowlist::default(),
+    )
+}
+
+fn tool_call(name: &str, arguments: serde_json::Value) -> MockReply {
+    MockReply {
+        tool_calls: vec![MockToolCall {
+            name: name.to_string(),
+            arguments,
+        }],
+        ..Default::default()
+    }
+}
+
+fn answer(text: &str) -> MockReply {
+    MockReply {
+        content: Some(text.to_string()),
+        ..Default::default()
+    }
+}
+
+/// What the tools returned, in the order they ran.
+fn tool_results(conversation: &ConversationManager) -> Vec<String> {
+    conversation
+        .get_messages()
+        .iter()
+        .filter(|message| message.role == "tool")
+        .filter_map(|message| message.content.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn runs_a_tool_and_answers_with_its_result() {
+    let mut agent = agent(vec![
+        tool_call("calculate", json!({ "expression": "6 * 7" })),
+        answer("It is 42."),
+    ]);
+    let mut conversation = ConversationManager::new();
+    let mut called = Vec::new();
+    let reply = agent
+        .reply(
+            &mut conversation,
+            "mock",
+            128_000,
+            "What is 6 times 7?",
+            &mut |call| called.push(call.function.name.clone()),
+        )
+        .await;
+
+    assert_eq!(reply.as_deref(), Ok("It is 42."));
+    assert_eq!(called, ["calculate"]);
+    let results = tool_results(&conversation);
+    assert_eq!(results.len(), 1);
+    assert!(results[0].contains("42"), "{}", results[0]);
+}
+
+#[tokio::test]
+async fn read_only_mode_refuses_calls_that_change_something() {
+    let mut agent = agent(vec![
+        tool_call(
+            "http_request",
+            json!({ "method": "POST", "url": "https://example.com/orders" }),
+        ),
+        answer("I could not place the order."),
+    ]);
+    agent.set_read_only(true);
+    let mut conversation = ConversationManager::new();
+    let reply = agent
+        .reply(&mut conversation, "mock", 128_000, "Order it", &mut |_| {})
+        .await;
+
+    assert_eq!(reply.as_deref(), Ok("I could not place the order."));
+    assert_eq!(tool_results(&conversation), [READ_ONLY_REFUSAL]);
+}
+
+#[tokio::test]
+async fn masks_secrets_before_the_model_sees_them() {
+    // With its script used up, the mock answers with the message it got.
+    let mut agent = agent(Vec::new());
+    agent.set_redactor(Redactor::new(&RedactConfig::default()).0);
+    let mut conversation = ConversationManager::new();
+    let reply = agent
+        .reply(
+            &mut conversation,
+            "mock",
+            128_000,
+            "Why is gsk_abcdefghijklmnopqrstuvwxyz0123 refused?",
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+
+    assert!(!reply.contains("gsk_"), "{}", reply);
+    assert!(reply.contains("[REDACTED Groq API key]"), "{}", reply);
+}
+
+#[tokio::test]
+async fn plays_back_a_recorded_search() {
+    let question = "What is new in Rust 1.90?";
+    let key = format!("llama-3.3-70b-versatile {}", question);
+    let tape = json!({ "interactions": [
+        {
+            "kind": "chat",
+            "key": key,
+            "response": {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {
+                        "name": "brave_search",
+                        "arguments": "{\"query\": \"Rust 1.90 release\"}"
+                    }
+                }]
+            }
+        },
+        {
+            "kind": "search",
+            "key": "Rust 1.90 release",
+            "response": { "web": { "results": [{
+                "title": "Announcing Rust 1.90.0",
+                "url": "https://blog.rust-lang.org/2025/09/18/Rust-1.90.0/",
+                "description": "Rust 1.90 makes LLD the default linker on x86_64 Linux."
+            }] } }
+        },
+        {
+            "kind": "chat",
+            "key": key,
+            "response": {
+                "role": "assistant",
+                "content": "LLD is now the default linker on x86_64 Linux."
+            }
+        }
+    ] });
+    let path = std::env::temp_dir().join(format!("rusty-cassette-{}.json", std::process::id()));
+    std::fs::write(&path, tape.to_string()).unwrap();
+    let cassette = Arc::new(Cassette::replay(&path).unwrap());
+    let _ = std::fs::remove_file(&path);
+
+    let mut brave = BraveSearchClient::new(String::new());
+    brave.set_cassette(Some(cassette.clone()));
+    let mut agent = Agent::new(
+        Provider::Recorded(Recorder::replay(cassette)),
+        brave,
+        PluginHost::default(),
+        ScriptHooks::new(),
+        CommandHooks::new(HooksConfig::default()),
+        Ocr::default(),
+        Allowlist::default(),
+    );
+    let mut conversation = ConversationManager::new();
+    let mut called = Vec::new();
+    let reply = agent
+        .reply(
+            &mut conversation,
+            "llama-3.3-70b-versatile",
+            128_000,
+            question,
+            &mut |call| called.push(call.function.name.clone()),
+        )
+        .await;
+
+    assert_eq!(
+        reply.as_deref(),
+        Ok("LLD is now the default linker on x86_64 Linux.")
+    );
+    assert_eq!(called, ["brave_search"]);
+    let results = tool_results(&conversation);
+    assert_eq!(results.len(), 1);
+    assert!(
+        results[0].contains("Announcing Rust 1.90.0"),
+        "{}",
+        results[0]
+    );
+}
@@ -0,0 +1,103 @@
+//! The fetcher against a local server: redirects are followed only to
+//! hosts the egress allowlist lets through, and the bots' allowlist keeps
+//! tools off local addresses.
+
+use rusty_core::config::FetchConfig;
+use rusty_core::fetch::Fetcher;
+use rusty_core::http::Egress;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Serves `/page` as a page, and redirects every other path to
+/// `http://<path>/page`, so `/localhost:8080` leads to that host.
+async fn serve() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..read]).to_string();
+            let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+            let response = if path == "/page" {
+                let body = "<html><body><p>Arrived</p></body></html>";
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else {
+                format!(
+                    "HTTP/1.1 302 Found\r\nLocation: http:/{}/page\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    path
+                )
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+    port
+}
+
+/// The configuration directory for the tests, where refusals are logged.
+fn config_dir() -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("rusty-fetch-test-{}", std::process::id()));
+    std::env::set_var("RUSTY_CONFIG_DIR", &dir);
+    dir
+}
+
+fn fetcher(egress: &[&str]) -> Fetcher {
+    let config = FetchConfig {
+        robots: false,
+        delay_ms: 0,
+        ..FetchConfig::default()
+    };
+    let egress: Vec<String> = egress.iter().map(|host| host.to_string()).collect();
+    Fetcher::from_config(&config, &Egress::new(&egress))
+}
+
+#[tokio::test]
+async fn follows_redirects_only_to_allowed_hosts() {
+    let dir = config_dir();
+    let port = serve().await;
+
+    let page = fetcher(&["127.0.0.1"])
+        .page(&format!("http://127.0.0.1:{}/127.0.0.1:{}", port, port))
+        .await
+        .unwrap();
+    assert_eq!(page.url.path(), "/page");
+    assert_eq!(page.text, "Arrived");
+
+    let error = fetcher(&["127.0.0.1"])
+        .page(&format!("http://127.0.0.1:{}/localhost:{}", port, port))
+        .await
+        .err()
+        .unwrap();
+    assert!(error.contains("Could not fetch"), "{}", error);
+    let log = std::fs::read_to_string(dir.join("egress.log")).unwrap();
+    assert!(log.contains(&format!("redirect http://localhost:{}/page", port)));
+}
+
+#[test]
+fn keeps_unattended_tools_off_local_addresses() {
+    config_dir();
+    let egress = Egress::default().unattended();
+    let error = egress
+        .check("open", "http://169.254.169.254/latest/meta-data/")
+        .unwrap_err();
+    assert!(
+        error.contains("169.254.169.254 is a local or private address"),
+        "{}",
+        error
+    );
+    assert!(egress
+        .check("read_feed", "http://localhost:8080/feed.xml")
+        .is_err());
+    assert!(egress.check("open", "https://example.com/").is_ok());
+
+    // Listing the address lets it through; a bare `*` does not.
+    let listed = Egress::new(&["localhost".to_string()]).unattended();
+    assert!(listed.check("open", "http://localhost:8080/").is_ok());
+    let anything = Egress::new(&["*".to_string()]).unattended();
+    assert!(anything.check("open", "http://127.0.0.1/").is_err());
+    assert!(Egress::default().check("open", "http://127.0.0.1/").is_ok());
+}
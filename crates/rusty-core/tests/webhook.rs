@@ -0,0 +1,53 @@
+//! The webhook listener over real connections: a sender that never
+//! finishes its request does not hold up the others.
+
+use std::time::Duration;
+
+use rusty_core::webhook;
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn free_address() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn a_stalled_sender_holds_up_nobody() {
+    let address = free_address().await;
+    let mut jobs = webhook::listen(&address, None, vec!["build".to_string()])
+        .await
+        .unwrap();
+
+    // Sends part of a request line and stops there.
+    let mut stalled = TcpStream::connect(&address).await.unwrap();
+    stalled.write_all(b"POST /bu").await.unwrap();
+
+    let body = json!({ "status": "failed" }).to_string();
+    let mut sender = TcpStream::connect(&address).await.unwrap();
+    let request = format!(
+        "POST /build HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    sender.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(5), sender.read_to_string(&mut response))
+        .await
+        .expect("the request is answered")
+        .unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 202 Accepted"),
+        "{}",
+        response
+    );
+
+    let job = tokio::time::timeout(Duration::from_secs(5), jobs.recv())
+        .await
+        .expect("the job is not held up")
+        .unwrap();
+    assert_eq!(job.id, 1);
+    assert_eq!(job.route, "build");
+    assert_eq!(job.payload["status"], "failed");
+}
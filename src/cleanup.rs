@@ -0,0 +1,93 @@
+//! Puts the terminal back the way it was found when Rusty is stopped by a
+//! signal or a panic: raw mode, the type-ahead's echo settings, colors and
+//! the cursor would otherwise stay as the prompt box left them.
+
+use std::io::{self, IsTerminal, Write};
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+use termimad::crossterm::terminal;
+
+/// The terminal settings at startup, which the type-ahead changes while a
+/// turn runs.
+#[cfg(unix)]
+static ORIGINAL: OnceLock<libc::termios> = OnceLock::new();
+
+/// Remembers the terminal settings and restores them on panics and on
+/// SIGINT, SIGTERM and SIGHUP (Ctrl+C elsewhere), except for a SIGINT that
+/// cancels a tool call. Must be called from the
+/// runtime, once, before anything changes the terminal.
+pub fn install() {
+    #[cfg(unix)]
+    if io::stdin().is_terminal() {
+        // SAFETY: termios is plain data, filled in by tcgetattr before use.
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(0, &mut original) } == 0 {
+            let _ = ORIGINAL.set(original);
+        }
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        default_hook(info);
+    }));
+
+    tokio::spawn(async {
+        let code = wait_for_signal().await;
+        restore();
+        std::process::exit(code);
+    });
+}
+
+/// Leaves raw mode and the type-ahead's settings, resets colors and the
+/// cursor shape, shows the cursor, and moves to a fresh line.
+pub fn restore() {
+    let _ = terminal::disable_raw_mode();
+    #[cfg(unix)]
+    if let Some(original) = ORIGINAL.get() {
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, original) };
+    }
+    if io::stdout().is_terminal() && crate::console::escapes() {
+        let mut out = io::stdout();
+        let _ = write!(out, "\x1b[0m\x1b[0 q\x1b[?25h\r\n");
+        let _ = out.flush();
+    }
+}
+
+/// Waits for a signal that ends the program and returns the exit code the
+/// shell expects for it, 128 plus its number.
+#[cfg(unix)]
+async fn wait_for_signal() -> i32 {
+    use tokio::signal::unix::{signal, SignalKind};
+    let (Ok(mut interrupt), Ok(mut terminate), Ok(mut hangup)) = (
+        signal(SignalKind::interrupt()),
+        signal(SignalKind::terminate()),
+        signal(SignalKind::hangup()),
+    ) else {
+        return std::future::pending().await;
+    };
+    loop {
+        tokio::select! {
+            _ = interrupt.recv() => {
+                if !crate::interrupt::cancel() {
+                    return 130;
+                }
+            }
+            _ = terminate.recv() => return 143,
+            _ = hangup.recv() => return 129,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> i32 {
+    loop {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return std::future::pending().await;
+        }
+        if !crate::interrupt::cancel() {
+            return 130;
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! What the console can show. Windows consoles before Windows 10 ignore
+//! escape sequences, newer ones only follow them once virtual terminal
+//! processing is on, and many terminals have no 24-bit color. Where
+//! escapes do not work, the prompt box, the spinner and the styled output
+//! give way to plain lines. Terminals that follow OSC 8 also make links
+//! clickable.
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use base64::Engine;
+use regex::{Captures, Regex};
+
+static ESCAPES: AtomicBool = AtomicBool::new(true);
+
+static HYPERLINKS: AtomicBool = AtomicBool::new(false);
+
+/// Marks the start and end of a link in Markdown handed to the skin, which
+/// keeps them where they are, to be replaced with OSC 8 sequences after.
+const LINK_START: char = '\u{E000}';
+const LINK_END: char = '\u{E001}';
+
+/// Turns on escape sequences where they must be asked for, and notes
+/// whether the console follows them. Without them, colors are turned off.
+pub fn init() {
+    let term = std::env::var("TERM").ok();
+    let supported = if cfg!(windows) {
+        // Git Bash and other terminals that are not consoles set TERM.
+        enable_virtual_terminal() || term.is_some_and(|term| term != "dumb")
+    } else {
+        term.as_deref() != Some("dumb")
+    };
+    ESCAPES.store(supported, Ordering::Relaxed);
+    if !supported {
+        colored::control::set_override(false);
+    }
+    HYPERLINKS.store(
+        supported && std::io::stdout().is_terminal() && detect_hyperlinks(),
+        Ordering::Relaxed,
+    );
+}
+
+/// Whether the terminal is one known to follow OSC 8; `FORCE_HYPERLINK`
+/// says so either way.
+fn detect_hyperlinks() -> bool {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+    if let Ok(force) = std::env::var("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+    let term = var("TERM");
+    matches!(
+        var("TERM_PROGRAM").as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper" | "Tabby"
+    ) || std::env::var_os("WT_SESSION").is_some()
+        || std::env::var_os("KONSOLE_VERSION").is_some()
+        || std::env::var_os("DOMTERM").is_some()
+        // GNOME Terminal, Tilix and the other VTE terminals since 0.50.
+        || var("VTE_VERSION").parse::<u32>().is_ok_and(|version| version >= 5000)
+        || ["kitty", "alacritty", "foot", "ghostty"]
+            .iter()
+            .any(|name| term.contains(name))
+}
+
+/// How the terminal shows images inline, if it can.
+#[derive(Clone, Copy)]
+pub enum Graphics {
+    /// The kitty graphics protocol, also followed by Ghostty and WezTerm.
+    Kitty,
+    /// iTerm2's inline images.
+    Iterm,
+}
+
+/// The image protocol of the terminal, when output goes to one that has it.
+pub fn graphics() -> Option<Graphics> {
+    if !escapes() || !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    match std::env::var("TERM_PROGRAM").unwrap_or_default().as_str() {
+        "iTerm.app" => Some(Graphics::Iterm),
+        "ghostty" | "WezTerm" => Some(Graphics::Kitty),
+        _ if term.contains("kitty") || term.contains("ghostty") => Some(Graphics::Kitty),
+        _ => None,
+    }
+}
+
+/// The escape sequences that show a PNG image inline.
+pub fn image(graphics: Graphics, png: &[u8]) -> String {
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    match graphics {
+        Graphics::Iterm => format!(
+            "\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07\n",
+            png.len(),
+            data
+        ),
+        Graphics::Kitty => {
+            // Sent in chunks of at most 4096 bytes, each saying whether
+            // more follow.
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+            let mut out = String::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = u8::from(i + 1 < chunks.len());
+                let keys = if i == 0 {
+                    format!("a=T,f=100,m={}", more)
+                } else {
+                    format!("m={}", more)
+                };
+                out.push_str(&format!(
+                    "\x1b_G{};{}\x1b\\",
+                    keys,
+                    String::from_utf8_lossy(chunk)
+                ));
+            }
+            out.push('\n');
+            out
+        }
+    }
+}
+
+/// Whether links are written as OSC 8 hyperlinks.
+pub fn hyperlinks() -> bool {
+    HYPERLINKS.load(Ordering::Relaxed)
+}
+
+/// `text` as a link to `url` that can be clicked, or as it is where that
+/// does not work.
+pub fn hyperlink(text: &str, url: &str) -> String {
+    if hyperlinks() {
+        format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn url_pattern() -> Regex {
+    Regex::new(r"https?://[^\s<>()\[\]\x1b]*[^\s<>()\[\].,;:!?'\x1b]").unwrap()
+}
+
+/// `text` with the URLs in it made clickable.
+pub fn linkify(text: &str) -> String {
+    if !hyperlinks() {
+        return text.to_string();
+    }
+    url_pattern()
+        .replace_all(text, |c: &Captures| hyperlink(&c[0], &c[0]))
+        .into_owned()
+}
+
+/// Markdown with its links, `[text](url)` and bare URLs, marked so that
+/// they can be made clickable once rendered with [`unmark_links`]; a link
+/// shows as its text. The URLs are returned in order. Left as it is where
+/// links cannot be clicked.
+pub fn mark_links(markdown: &str) -> (String, Vec<String>) {
+    let mut urls = Vec::new();
+    if !hyperlinks() {
+        return (markdown.to_string(), urls);
+    }
+    let link = Regex::new(&format!(
+        r"\[([^\]\n]+)\]\(({})\)|({})",
+        url_pattern().as_str(),
+        url_pattern().as_str()
+    ))
+    .unwrap();
+    let marked = link.replace_all(markdown, |c: &Captures| {
+        let (text, url) = match c.get(1) {
+            Some(text) => (text.as_str(), &c[2]),
+            None => (&c[3], &c[3]),
+        };
+        urls.push(url.to_string());
+        format!("{}{}{}", LINK_START, text, LINK_END)
+    });
+    (marked.into_owned(), urls)
+}
+
+/// Rendered text with the links [`mark_links`] marked turned into OSC 8
+/// hyperlinks. Styles the renderer put inside a link are kept.
+pub fn unmark_links(rendered: &str, urls: &[String]) -> String {
+    let mut urls = urls.iter();
+    let mut out = String::with_capacity(rendered.len());
+    for c in rendered.chars() {
+        match c {
+            LINK_START => {
+                if let Some(url) = urls.next() {
+                    out.push_str(&format!("\x1b]8;;{}\x1b\\", url));
+                }
+            }
+            LINK_END => out.push_str("\x1b]8;;\x1b\\"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Whether cursor movement, line clearing and colors work.
+pub fn escapes() -> bool {
+    ESCAPES.load(Ordering::Relaxed)
+}
+
+/// Whether 24-bit colors show as such rather than as the nearest of the
+/// basic colors: `COLORTERM` says so, or this is Windows Terminal.
+pub fn truecolor() -> bool {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    escapes()
+        && (colorterm == "truecolor"
+            || colorterm == "24bit"
+            || std::env::var_os("WT_SESSION").is_some())
+}
+
+#[cfg(windows)]
+fn enable_virtual_terminal() -> bool {
+    colored::control::set_virtual_terminal(true).is_ok()
+}
+
+#[cfg(not(windows))]
+fn enable_virtual_terminal() -> bool {
+    true
+}
+
+/// The escape sequence for a foreground color, in 24 bits where that
+/// works, or as the nearest of the 256 colors most terminals have.
+pub fn fg(r: u8, g: u8, b: u8) -> String {
+    color(38, r, g, b)
+}
+
+/// The escape sequence for a background color, as for `fg`.
+pub fn bg(r: u8, g: u8, b: u8) -> String {
+    color(48, r, g, b)
+}
+
+fn color(layer: u8, r: u8, g: u8, b: u8) -> String {
+    if truecolor() {
+        return format!("\x1b[{};2;{};{};{}m", layer, r, g, b);
+    }
+    // The 6×6×6 color cube, or the gray ramp when the three are close.
+    let level = |c: u8| (c as u16 * 5 + 127) / 255;
+    let index = if r.abs_diff(g) < 16 && g.abs_diff(b) < 16 {
+        let gray = (r as u16 + g as u16 + b as u16) / 3;
+        match gray {
+            0..=7 => 16,
+            248.. => 231,
+            _ => 232 + (gray - 8) / 10,
+        }
+    } else {
+        16 + 36 * level(r) + 6 * level(g) + level(b)
+    };
+    format!("\x1b[{};5;{}m", layer, index)
+}
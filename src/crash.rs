@@ -0,0 +1,41 @@
+//! Offers a diagnostic report after a panic or a fatal error, written to a
+//! file that can be attached to a bug report.
+
+use std::io::{self, IsTerminal, Write};
+
+use colored::*;
+use rusty_core::diagnostics;
+
+/// Offers a report after the message of any panic. Must be called after
+/// `cleanup::install`, so the terminal is put back first.
+pub fn install() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        offer_report(&format!("panic: {}", info));
+    }));
+}
+
+/// Asks whether to write a report on `failure`, and writes it. Only on a
+/// terminal; piped runs have nobody to ask.
+pub fn offer_report(failure: &str) {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return;
+    }
+    eprint!(
+        "\nWrite a diagnostic report to attach to a bug report? Secrets and conversations are left out. [y/N] "
+    );
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+        return;
+    }
+    match diagnostics::write_report(failure) {
+        Ok(path) => eprintln!(
+            "Wrote {}\nPlease look it over, then attach it to an issue at {}",
+            path.display().to_string().bold(),
+            diagnostics::ISSUES_URL
+        ),
+        Err(e) => eprintln!("{}", e),
+    }
+}
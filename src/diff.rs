@@ -0,0 +1,493 @@
+//! Patches in answers drawn for reading. The unified view colors each
+//! line by what it does; the side-by-side view puts the old lines on the
+//! left and the new on the right, numbered from the hunk headers, with the
+//! part of a changed line that differs picked out.
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::console;
+
+const RESET: &str = "\x1b[0m";
+
+/// Whether a code block is a patch: tagged `diff` or `patch`, or untagged
+/// and starting the way unified diffs do.
+pub fn is_diff(lang: &str, code: &str) -> bool {
+    match lang {
+        "diff" | "patch" | "udiff" => true,
+        "" => {
+            let mut lines = code.lines();
+            match lines.next() {
+                Some(first) if first.starts_with("diff --git ") => true,
+                Some(first) if first.starts_with("--- ") => lines
+                    .next()
+                    .is_some_and(|second| second.starts_with("+++ ")),
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// The patch line by line, additions green, deletions red, hunk headers
+/// dim and the file headers bold.
+pub fn unified(code: &str) -> Vec<String> {
+    code.lines()
+        .map(|line| {
+            let style = if ["diff ", "index ", "--- ", "+++ "]
+                .iter()
+                .any(|header| line.starts_with(header))
+            {
+                "\x1b[1m".to_string()
+            } else if line.starts_with("@@") {
+                hunk_style()
+            } else if line.starts_with('+') {
+                added()
+            } else if line.starts_with('-') {
+                removed()
+            } else {
+                String::new()
+            };
+            format!("{}{}{}", style, line, RESET)
+        })
+        .collect()
+}
+
+/// The patch side by side in `width` columns: each removed line next to
+/// the line added in its place, and unchanged lines on both sides.
+pub fn split(code: &str, width: usize) -> Vec<String> {
+    let rows = rows(code);
+    let digits = rows
+        .iter()
+        .flat_map(|row| match row {
+            Row::Pair(old, new) => [old.as_ref(), new.as_ref()],
+            _ => [None, None],
+        })
+        .flatten()
+        .filter_map(|cell| cell.number)
+        .max()
+        .map(|n| n.to_string().len());
+    let side = width.saturating_sub(3) / 2;
+    let text_width = side.saturating_sub(digits.map_or(0, |d| d + 1)).max(1);
+    let bar = format!("{} │{} ", console::fg(100, 100, 100), RESET);
+
+    let mut out = Vec::new();
+    for row in &rows {
+        match row {
+            Row::Header(line) => out.push(format!("\x1b[1m{}{}", truncate(line, width), RESET)),
+            Row::Note(line) => out.push(format!(
+                "{}{}{}",
+                hunk_style(),
+                truncate(line, width),
+                RESET
+            )),
+            Row::Pair(old, new) => {
+                let (old_changed, new_changed) = match (old, new) {
+                    (Some(old), Some(new)) if old.change != new.change => {
+                        changed(&old.text, &new.text).unzip()
+                    }
+                    _ => (None, None),
+                };
+                let left = cell_lines(old.as_ref(), old_changed, digits, text_width);
+                let right = cell_lines(new.as_ref(), new_changed, digits, text_width);
+                for i in 0..left.len().max(right.len()) {
+                    let blank = " ".repeat(side);
+                    let left = left.get(i).unwrap_or(&blank);
+                    let right = right.get(i).map_or("", String::as_str);
+                    out.push(format!("{}{}{}", left, bar, right).trim_end().to_string());
+                }
+            }
+        }
+    }
+    out
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+struct Cell {
+    /// The line's number in its file, when the hunk header gives one.
+    number: Option<usize>,
+    text: String,
+    change: Change,
+}
+
+enum Row<'a> {
+    /// A file header, across both sides.
+    Header(&'a str),
+    /// A hunk header or `\ No newline at end of file`, across both sides.
+    Note(&'a str),
+    Pair(Option<Cell>, Option<Cell>),
+}
+
+/// The patch as rows of the side-by-side view. Lines are read as part of
+/// a hunk after an `@@` header, or throughout when the patch has none, as
+/// patches written by hand often do not.
+fn rows(code: &str) -> Vec<Row<'_>> {
+    let marked = code.lines().any(|line| line.starts_with("@@"));
+    let mut rows = Vec::new();
+    let mut removed: Vec<Cell> = Vec::new();
+    let mut added: Vec<Cell> = Vec::new();
+    let (mut old, mut new) = (None, None);
+    let mut in_hunk = !marked;
+    let mut lines = code.lines().peekable();
+    while let Some(line) = lines.next() {
+        let file_header = line.starts_with("diff ")
+            || line.starts_with("index ")
+            || line.starts_with("--- ")
+                && lines.peek().is_some_and(|next| next.starts_with("+++ "));
+        let body =
+            in_hunk && !file_header && matches!(line.chars().next(), Some('+' | '-' | ' ') | None);
+        // A removal after additions starts another change.
+        if !body
+            || line.starts_with('-') && !added.is_empty()
+            || line.starts_with(' ')
+            || line.is_empty()
+        {
+            pair(&mut rows, &mut removed, &mut added);
+        }
+        if file_header {
+            rows.push(Row::Header(line));
+            if line.starts_with("--- ") {
+                rows.extend(lines.next().map(Row::Header));
+            }
+            in_hunk = !marked;
+            continue;
+        }
+        if line.starts_with("@@") {
+            (old, new) = hunk_start(line);
+            in_hunk = true;
+            rows.push(Row::Note(line));
+            continue;
+        }
+        if !body {
+            rows.push(if in_hunk {
+                Row::Note(line)
+            } else {
+                Row::Header(line)
+            });
+            continue;
+        }
+        let text = line.get(1..).unwrap_or_default().replace('\t', "    ");
+        match line.chars().next() {
+            Some('-') => removed.push(Cell {
+                number: next(&mut old),
+                text,
+                change: Change::Removed,
+            }),
+            Some('+') => added.push(Cell {
+                number: next(&mut new),
+                text,
+                change: Change::Added,
+            }),
+            _ => rows.push(Row::Pair(
+                Some(Cell {
+                    number: next(&mut old),
+                    text: text.clone(),
+                    change: Change::Same,
+                }),
+                Some(Cell {
+                    number: next(&mut new),
+                    text,
+                    change: Change::Same,
+                }),
+            )),
+        }
+    }
+    pair(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+/// Puts the removed lines of a change next to the added ones, in order.
+fn pair(rows: &mut Vec<Row>, removed: &mut Vec<Cell>, added: &mut Vec<Cell>) {
+    let mut removed = removed.drain(..);
+    let mut added = added.drain(..);
+    loop {
+        match (removed.next(), added.next()) {
+            (None, None) => break,
+            (old, new) => rows.push(Row::Pair(old, new)),
+        }
+    }
+}
+
+/// The first old and new line numbers of a hunk header such as
+/// `@@ -12,7 +12,8 @@`.
+fn hunk_start(line: &str) -> (Option<usize>, Option<usize>) {
+    let mut ranges = line.split_whitespace().skip(1);
+    let start = |range: Option<&str>, sign: char| {
+        range?.strip_prefix(sign)?.split(',').next()?.parse().ok()
+    };
+    let old = start(ranges.next(), '-');
+    let new = start(ranges.next(), '+');
+    (old, new)
+}
+
+/// The current line number, moving on to the next.
+fn next(number: &mut Option<usize>) -> Option<usize> {
+    let current = *number;
+    if let Some(n) = number {
+        *n += 1;
+    }
+    current
+}
+
+/// Which characters of two versions of a line differ, word by word: the
+/// words and symbols outside their longest common sequence. `None` when
+/// they have no word in common, as the whole line is then new.
+fn changed(old: &str, new: &str) -> Option<(Vec<bool>, Vec<bool>)> {
+    let old = tokens(old);
+    let new = tokens(new);
+    // lengths[i][j]: the longest common sequence of old[i..] and new[j..].
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut old_kept = vec![false; old.len()];
+    let mut new_kept = vec![false; new.len()];
+    let (mut i, mut j) = (0, 0);
+    let mut shared = false;
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            shared |= !old[i].trim().is_empty();
+            (old_kept[i], new_kept[j]) = (true, true);
+            (i, j) = (i + 1, j + 1);
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    if !shared {
+        return None;
+    }
+    let marks = |tokens: &[&str], kept: &[bool]| -> Vec<bool> {
+        tokens
+            .iter()
+            .zip(kept)
+            .flat_map(|(token, &kept)| std::iter::repeat_n(!kept, token.chars().count()))
+            .collect()
+    };
+    Some((marks(&old, &old_kept), marks(&new, &new_kept)))
+}
+
+/// A line split into words, runs of spaces, and single other characters.
+fn tokens(line: &str) -> Vec<&str> {
+    let kind = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut previous = None;
+    for (i, c) in line.char_indices() {
+        let k = kind(c);
+        if i > start && (previous != Some(k) || k == 2) {
+            tokens.push(&line[start..i]);
+            start = i;
+        }
+        previous = Some(k);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+/// A side of a row, wrapped to `width` and padded to fill it, after the
+/// line number when the patch has them.
+fn cell_lines(
+    cell: Option<&Cell>,
+    changed: Option<Vec<bool>>,
+    digits: Option<usize>,
+    width: usize,
+) -> Vec<String> {
+    let Some(cell) = cell else {
+        return Vec::new();
+    };
+    let (color, highlight) = match cell.change {
+        Change::Same => (String::new(), String::new()),
+        Change::Removed => (removed(), console::bg(90, 30, 35)),
+        Change::Added => (added(), console::bg(25, 75, 40)),
+    };
+    let chars: Vec<char> = cell.text.chars().collect();
+    let mut chunks = Vec::new();
+    let (mut start, mut used) = (0, 0);
+    for (i, c) in chars.iter().enumerate() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width && i > start {
+            chunks.push(start..i);
+            (start, used) = (i, 0);
+        }
+        used += w;
+    }
+    chunks.push(start..chars.len());
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(n, chunk)| {
+            let gutter = match digits {
+                Some(digits) => {
+                    let number = cell.number.filter(|_| n == 0);
+                    format!(
+                        "{}{:>digits$}{} ",
+                        console::fg(100, 100, 100),
+                        number.map(|n| n.to_string()).unwrap_or_default(),
+                        RESET
+                    )
+                }
+                None => String::new(),
+            };
+            let mut text = color.clone();
+            let mut lit = false;
+            for i in chunk.clone() {
+                let inside = changed.as_ref().is_some_and(|changed| changed[i]);
+                if inside != lit {
+                    text.push_str(if inside { &highlight } else { "\x1b[49m" });
+                    lit = inside;
+                }
+                text.push(chars[i]);
+            }
+            let used: usize = chars[chunk].iter().map(|c| c.width().unwrap_or(0)).sum();
+            format!(
+                "{}{}{}{}",
+                gutter,
+                text,
+                RESET,
+                " ".repeat(width.saturating_sub(used))
+            )
+        })
+        .collect()
+}
+
+/// `line` cut to `width` columns, ending in `…` when it was longer.
+fn truncate(line: &str, width: usize) -> String {
+    let widths = line.chars().map(|c| c.width().unwrap_or(0));
+    if widths.sum::<usize>() <= width {
+        return line.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in line.chars() {
+        used += c.width().unwrap_or(0);
+        if used >= width {
+            break;
+        }
+        out.push(c);
+    }
+    out.push('…');
+    out
+}
+
+fn added() -> String {
+    console::fg(80, 200, 120)
+}
+
+fn removed() -> String {
+    console::fg(230, 90, 90)
+}
+
+fn hunk_style() -> String {
+    format!("\x1b[2m{}", console::fg(100, 160, 200))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+--- a/greet.rs
++++ b/greet.rs
+@@ -1,3 +1,3 @@
+ fn greet() {
+-    println!(\"hello\");
++    println!(\"hello, world\");
+ }";
+
+    /// `line` without its escape sequences, and the trailing spaces that
+    /// pad the columns.
+    fn plain(line: &str) -> String {
+        let mut out = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                out.push(c);
+            }
+        }
+        out.trim_end().to_string()
+    }
+
+    #[test]
+    fn tells_patches_from_other_code() {
+        assert!(is_diff("diff", "anything"));
+        assert!(is_diff("", PATCH));
+        assert!(is_diff("", "diff --git a/x b/x\n"));
+        assert!(!is_diff("", "--- a heading in prose\nand more"));
+        assert!(!is_diff("rust", PATCH));
+    }
+
+    #[test]
+    fn colors_lines_by_what_they_do() {
+        let lines = unified(PATCH);
+        assert_eq!(lines.len(), PATCH.lines().count());
+        assert!(lines[0].starts_with("\x1b[1m"));
+        assert!(lines[4].starts_with(&removed()));
+        assert!(lines[5].starts_with(&added()));
+        assert!(lines.iter().map(|line| plain(line)).eq(PATCH.lines()));
+    }
+
+    #[test]
+    fn puts_a_changed_line_next_to_its_replacement() {
+        let lines: Vec<String> = split(PATCH, 80).iter().map(|line| plain(line)).collect();
+        assert_eq!(lines[0], "--- a/greet.rs");
+        assert_eq!(lines[1], "+++ b/greet.rs");
+        assert_eq!(lines[2], "@@ -1,3 +1,3 @@");
+        let numbered = |line: &str| {
+            let (old, new) = line.split_once(" │ ").unwrap();
+            (old.trim().to_string(), new.trim().to_string())
+        };
+        assert_eq!(
+            numbered(&lines[3]),
+            ("1 fn greet() {".to_string(), "1 fn greet() {".to_string())
+        );
+        assert_eq!(
+            numbered(&lines[4]),
+            (
+                "2     println!(\"hello\");".to_string(),
+                "2     println!(\"hello, world\");".to_string()
+            )
+        );
+        assert_eq!(numbered(&lines[5]), ("3 }".to_string(), "3 }".to_string()));
+    }
+
+    #[test]
+    fn picks_out_the_words_that_changed() {
+        let (old, new) = changed("let x = 1;", "let x = 2;").unwrap();
+        let marked = |text: &str, marks: &[bool]| -> String {
+            text.chars()
+                .zip(marks)
+                .filter(|(_, &marked)| marked)
+                .map(|(c, _)| c)
+                .collect()
+        };
+        assert_eq!(marked("let x = 1;", &old), "1");
+        assert_eq!(marked("let x = 2;", &new), "2");
+        assert!(changed("alpha", "beta").is_none());
+    }
+}
@@ -0,0 +1,423 @@
+//! Raw-mode line editing for the prompt box.
+
+mod typeahead;
+mod vi;
+
+use std::fs;
+use std::io::{self, Write};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use colored::*;
+use termimad::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use termimad::crossterm::terminal;
+use unicode_width::UnicodeWidthChar;
+
+use crate::palette::{self, PaletteAction, PaletteItem};
+use rusty_core::History;
+use vi::{Mode, ViState};
+
+pub use typeahead::{TypeAhead, Typed};
+
+/// How a call to [`LineEditor::read_line`] ended.
+pub enum ReadOutcome {
+    Line(String),
+    /// Ctrl+C on an empty line.
+    Interrupted,
+    /// Ctrl+D on an empty line.
+    Eof,
+    /// Stopped because the wake flag was set, with the line as it stood.
+    Woken(String),
+}
+
+/// How often the wake flag is checked while waiting for a key.
+const WAKE_CHECK: Duration = Duration::from_millis(250);
+
+/// Disables raw mode when dropped, so early returns and panics restore the terminal.
+struct RawModeGuard {
+    /// Whether the cursor shape was changed and must be reset.
+    reset_cursor: bool,
+}
+
+impl RawModeGuard {
+    fn enable(reset_cursor: bool) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self { reset_cursor })
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        if self.reset_cursor {
+            print!("\x1b[0 q");
+            let _ = io::stdout().flush();
+        }
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The text being edited and the cursor position within it, in characters.
+struct Line {
+    buffer: Vec<char>,
+    cursor: usize,
+}
+
+impl Line {
+    fn new(text: &str) -> Self {
+        let buffer: Vec<char> = text.chars().collect();
+        let cursor = buffer.len();
+        Self { buffer, cursor }
+    }
+
+    fn text(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    fn set(&mut self, text: &str) {
+        *self = Self::new(text);
+    }
+
+    fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// Removes `start..end` and leaves the cursor at `start`, returning the removed text.
+    fn delete(&mut self, start: usize, end: usize) -> String {
+        let end = end.min(self.buffer.len());
+        let start = start.min(end);
+        self.cursor = start;
+        self.buffer.drain(start..end).collect()
+    }
+}
+
+/// Emacs-style editing, which is also what insert mode uses under vi keys.
+fn edit_key(line: &mut Line, key: KeyEvent) {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Char('a') if ctrl => line.cursor = 0,
+        KeyCode::Char('e') if ctrl => line.cursor = line.buffer.len(),
+        KeyCode::Char('u') if ctrl => {
+            line.delete(0, line.cursor);
+        }
+        KeyCode::Char('w') if ctrl => {
+            let start = word_start(&line.buffer, line.cursor);
+            line.delete(start, line.cursor);
+        }
+        KeyCode::Char(c) if !ctrl => line.insert(c),
+        KeyCode::Backspace if line.cursor > 0 => {
+            line.delete(line.cursor - 1, line.cursor);
+        }
+        KeyCode::Delete if line.cursor < line.buffer.len() => {
+            line.delete(line.cursor, line.cursor + 1);
+        }
+        KeyCode::Left => line.cursor = line.cursor.saturating_sub(1),
+        KeyCode::Right => line.cursor = (line.cursor + 1).min(line.buffer.len()),
+        KeyCode::Home => line.cursor = 0,
+        KeyCode::End => line.cursor = line.buffer.len(),
+        _ => {}
+    }
+}
+
+pub struct LineEditor {
+    history: History,
+    vi_mode: bool,
+}
+
+impl LineEditor {
+    /// `vi_mode` switches the prompt to vi keybindings, starting each line in insert mode.
+    pub fn new(history: History, vi_mode: bool) -> Self {
+        Self { history, vi_mode }
+    }
+
+    /// Prompts submitted so far, oldest first, including earlier sessions.
+    pub fn history(&self) -> &[String] {
+        self.history.entries()
+    }
+
+    pub fn add_history(&mut self, line: &str) -> Result<(), String> {
+        self.history.add(line)
+    }
+
+    /// Edits a line on the current terminal row, which must already show the
+    /// prompt box. `palette_items` are offered when Ctrl+K opens the palette,
+    /// `search` finds the entries for what is typed after Ctrl+R, and
+    /// `files` the files for what is typed after an `@` that starts a word.
+    /// `save_draft` is given the line whenever it changes, to keep it should
+    /// Rusty be closed before it is sent; clearing it with Ctrl+C keeps what
+    /// was saved.
+    /// Editing stops early when `wake` is set, so something else can use
+    /// the terminal.
+    pub fn read_line(
+        &mut self,
+        initial: &str,
+        palette_items: &[PaletteItem],
+        search: &dyn Fn(&str) -> Vec<PaletteItem>,
+        files: &dyn Fn(&str) -> Vec<PaletteItem>,
+        save_draft: &dyn Fn(&str),
+        wake: Option<&AtomicBool>,
+    ) -> io::Result<ReadOutcome> {
+        let _raw = RawModeGuard::enable(self.vi_mode)?;
+        let mut line = Line::new(initial);
+        let mut vi = self.vi_mode.then(ViState::new);
+        let mut recall = Recall::new(self.history.entries().len());
+        // Ctrl+X starts a two-key binding, as in shells.
+        let mut after_ctrl_x = false;
+        let mut saved = String::new();
+
+        loop {
+            if line.text() != saved {
+                saved = line.text();
+                save_draft(&saved);
+            }
+            render(&line, vi.as_ref().map(ViState::mode))?;
+
+            if let Some(wake) = wake {
+                while !event::poll(WAKE_CHECK)? {
+                    if wake.load(Ordering::Relaxed) {
+                        print!("\r\n");
+                        io::stdout().flush()?;
+                        return Ok(ReadOutcome::Woken(line.text()));
+                    }
+                }
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            let ctrl_x = std::mem::take(&mut after_ctrl_x);
+
+            match key.code {
+                KeyCode::Char('x') if ctrl => after_ctrl_x = true,
+                KeyCode::Char('e') if ctrl && ctrl_x => {
+                    // The editor gets the terminal as it expects to find it.
+                    terminal::disable_raw_mode()?;
+                    let edited = edit_externally(&line.text());
+                    terminal::enable_raw_mode()?;
+                    match edited {
+                        Ok(text) if !text.trim().is_empty() => {
+                            // Only the first line fits in the prompt box.
+                            let shown = match text.trim().split_once('\n') {
+                                Some((first, _)) => format!("{} …", first),
+                                None => text.trim().to_string(),
+                            };
+                            render(&Line::new(&shown), None)?;
+                            print!("\r\n");
+                            io::stdout().flush()?;
+                            return Ok(ReadOutcome::Line(text));
+                        }
+                        // Saved empty: back to the line as it was.
+                        Ok(_) => {}
+                        // The editor could not be run; the line is kept.
+                        Err(_) => print!("\x07"),
+                    }
+                }
+                KeyCode::Enter => {
+                    // Raw mode does not echo the newline; move onto the bottom border.
+                    print!("\r\n");
+                    io::stdout().flush()?;
+                    return Ok(ReadOutcome::Line(line.text()));
+                }
+                KeyCode::Char('c') if ctrl => {
+                    if line.buffer.is_empty() {
+                        print!("\r\n");
+                        return Ok(ReadOutcome::Interrupted);
+                    }
+                    line.set("");
+                    // Likely pressed by mistake, so the draft stays.
+                    saved.clear();
+                }
+                KeyCode::Char('d') if ctrl => {
+                    if line.buffer.is_empty() {
+                        print!("\r\n");
+                        return Ok(ReadOutcome::Eof);
+                    }
+                    if line.cursor < line.buffer.len() {
+                        line.delete(line.cursor, line.cursor + 1);
+                    }
+                }
+                KeyCode::Char(c @ ('k' | 'r')) if ctrl => {
+                    let chosen = if c == 'k' {
+                        palette::pick(palette_items)?
+                    } else {
+                        palette::search(search)?
+                    };
+                    match chosen {
+                        Some(PaletteAction::Submit(text)) => {
+                            render(&Line::new(&text), None)?;
+                            print!("\r\n");
+                            io::stdout().flush()?;
+                            return Ok(ReadOutcome::Line(text));
+                        }
+                        Some(PaletteAction::Insert(text)) => line.set(&text),
+                        None => {}
+                    }
+                }
+                KeyCode::Char('@')
+                    if !ctrl
+                        && vi.as_ref().is_none_or(|vi| vi.mode() == Mode::Insert)
+                        && (line.cursor == 0 || line.buffer[line.cursor - 1] == ' ') =>
+                {
+                    line.insert('@');
+                    render(&line, vi.as_ref().map(ViState::mode))?;
+                    // Esc leaves the `@` to go on typing the path by hand.
+                    if let Some(PaletteAction::Insert(path)) = palette::files(files)? {
+                        for c in path.chars().chain([' ']) {
+                            line.insert(c);
+                        }
+                    }
+                }
+                KeyCode::Up => recall.older(&mut line, self.history.entries()),
+                KeyCode::Down => recall.newer(&mut line, self.history.entries()),
+                KeyCode::Char('p') if ctrl => recall.older(&mut line, self.history.entries()),
+                KeyCode::Char('n') if ctrl => recall.newer(&mut line, self.history.entries()),
+                KeyCode::Char('k') if vi.as_ref().is_some_and(ViState::is_idle) => {
+                    recall.older(&mut line, self.history.entries())
+                }
+                KeyCode::Char('j') if vi.as_ref().is_some_and(ViState::is_idle) => {
+                    recall.newer(&mut line, self.history.entries())
+                }
+                _ => match vi.as_mut() {
+                    Some(vi) => vi.handle_key(&mut line, key),
+                    None => edit_key(&mut line, key),
+                },
+            }
+        }
+    }
+}
+
+/// Opens `text` in `$VISUAL` or `$EDITOR` (vi, or Notepad on Windows, if
+/// neither is set) and returns the saved contents without the trailing newline.
+pub fn edit_externally(text: &str) -> io::Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    let path = std::env::temp_dir().join(format!("rusty-edit-{}.md", std::process::id()));
+    fs::write(&path, text)?;
+
+    // Run through the shell so settings like `code --wait` work.
+    let status = if cfg!(windows) {
+        Command::new("cmd")
+            .arg("/C")
+            .arg(format!("{} \"{}\"", editor, path.display()))
+            .status()
+    } else {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&path)
+            .status()
+    };
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    let status = status?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}",
+            editor, status
+        )));
+    }
+    Ok(edited?.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Walks through history with Up and Down, keeping the unsent line so that
+/// coming back down past the newest entry restores it.
+struct Recall {
+    position: usize,
+    draft: String,
+}
+
+impl Recall {
+    fn new(len: usize) -> Self {
+        Self {
+            position: len,
+            draft: String::new(),
+        }
+    }
+
+    fn older(&mut self, line: &mut Line, entries: &[String]) {
+        if self.position == 0 {
+            return;
+        }
+        if self.position == entries.len() {
+            self.draft = line.text();
+        }
+        self.position -= 1;
+        line.set(&entries[self.position]);
+    }
+
+    fn newer(&mut self, line: &mut Line, entries: &[String]) {
+        if self.position >= entries.len() {
+            return;
+        }
+        self.position += 1;
+        match entries.get(self.position) {
+            Some(entry) => line.set(entry),
+            None => line.set(&self.draft),
+        }
+    }
+}
+
+/// Index where the word before `cursor` starts, skipping trailing spaces first.
+fn word_start(buffer: &[char], cursor: usize) -> usize {
+    let mut i = cursor;
+    while i > 0 && buffer[i - 1] == ' ' {
+        i -= 1;
+    }
+    while i > 0 && buffer[i - 1] != ' ' {
+        i -= 1;
+    }
+    i
+}
+
+/// Redraws the prompt row, scrolling horizontally to keep the cursor visible.
+/// Under vi keys the cursor shape follows the mode, and the prompt glyph is
+/// dimmed in normal mode.
+fn render(line: &Line, vi_mode: Option<Mode>) -> io::Result<()> {
+    let orange = Color::TrueColor {
+        r: 255,
+        g: 140,
+        b: 0,
+    };
+    let gray = Color::TrueColor {
+        r: 100,
+        g: 100,
+        b: 100,
+    };
+    let width = match terminal::size() {
+        Ok((w, _)) if w > 0 => w as usize,
+        _ => 80,
+    };
+    // Measured in columns, since wide glyphs such as CJK and emoji take two.
+    let columns = |chars: &[char]| -> usize { chars.iter().map(|c| c.width().unwrap_or(0)).sum() };
+    let room = width.saturating_sub(3).max(1);
+    let mut start = 0;
+    while start < line.cursor && columns(&line.buffer[start..line.cursor]) > room {
+        start += 1;
+    }
+    let mut end = line.cursor;
+    while end < line.buffer.len() && columns(&line.buffer[start..=end]) <= room {
+        end += 1;
+    }
+    let visible: String = line.buffer[start..end].iter().collect();
+    let (glyph, shape) = match vi_mode {
+        Some(Mode::Normal) => ("❯".color(gray).bold(), "\x1b[2 q"),
+        Some(Mode::Insert) => ("❯".color(orange).bold(), "\x1b[6 q"),
+        None => ("❯".color(orange).bold(), ""),
+    };
+
+    let mut out = io::stdout();
+    write!(out, "{}\r\x1b[2K{} {}\r", shape, glyph, visible)?;
+    write!(
+        out,
+        "\x1b[{}C",
+        2 + columns(&line.buffer[start..line.cursor])
+    )?;
+    out.flush()
+}
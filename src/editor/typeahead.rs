@@ -0,0 +1,204 @@
+//! Typing ahead while a turn runs. Keystrokes are collected without echo,
+//! since the response is being printed; lines finished with Enter are
+//! queued to be sent after the turn, and an unfinished line becomes the
+//! start of the next prompt. Esc cancels the tool call that is running.
+//!
+//! The terminal keeps its output processing, so the rest of the program can
+//! print as usual. Only Unix terminals are supported; elsewhere typing
+//! during a turn behaves as before.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// What was typed during a turn.
+#[derive(Debug, Default, Clone)]
+pub struct Typed {
+    /// Lines finished with Enter, oldest first.
+    pub queued: Vec<String>,
+    /// The line still being typed.
+    pub draft: String,
+}
+
+impl Typed {
+    /// A short description for the spinner line, empty when nothing was
+    /// typed.
+    pub fn preview(&self) -> String {
+        let mut preview = String::new();
+        if !self.queued.is_empty() {
+            preview.push_str(&format!("{} queued", self.queued.len()));
+        }
+        if !self.draft.is_empty() {
+            if !preview.is_empty() {
+                preview.push_str(" · ");
+            }
+            let chars: Vec<char> = self.draft.chars().collect();
+            // The end of the line is what is being typed, so keep that.
+            let shown: String = chars[chars.len().saturating_sub(40)..].iter().collect();
+            preview.push_str(&format!("❯ {}", shown));
+        }
+        preview
+    }
+
+    fn push(&mut self, input: &str) {
+        let mut chars = input.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' | '\n' => {
+                    let line = std::mem::take(&mut self.draft);
+                    if !line.trim().is_empty() {
+                        self.queued.push(line);
+                    }
+                }
+                // Backspace.
+                '\x7f' | '\x08' => {
+                    self.draft.pop();
+                }
+                // Ctrl+U clears the line.
+                '\x15' => self.draft.clear(),
+                // Ctrl+W deletes the word before the cursor.
+                '\x17' => {
+                    let kept = self.draft.trim_end().len();
+                    self.draft.truncate(kept);
+                    let start = self.draft.rfind(' ').map_or(0, |i| i + 1);
+                    self.draft.truncate(start);
+                }
+                // Esc alone; a key pressed with Alt comes with it.
+                '\x1b' if chars.peek().is_none() => {
+                    crate::interrupt::cancel();
+                }
+                // Arrow keys and the like; there is no cursor to move.
+                '\x1b' => {
+                    if matches!(chars.peek(), Some('[' | 'O')) {
+                        chars.next();
+                        for c in chars.by_ref() {
+                            if ('\x40'..='\x7e').contains(&c) {
+                                break;
+                            }
+                        }
+                    }
+                }
+                c if c.is_control() => {}
+                c => self.draft.push(c),
+            }
+        }
+    }
+}
+
+/// Collects keystrokes in the background until paused or finished.
+pub struct TypeAhead {
+    typed: Arc<Mutex<Typed>>,
+    running: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl TypeAhead {
+    /// Starts collecting keystrokes, continuing `draft`, or `None` when
+    /// input is not a terminal this works on.
+    pub fn start(draft: &str) -> Option<Self> {
+        use std::io::IsTerminal;
+        if !cfg!(unix) || !std::io::stdin().is_terminal() {
+            return None;
+        }
+        let mut type_ahead = Self {
+            typed: Arc::new(Mutex::new(Typed {
+                queued: Vec::new(),
+                draft: draft.to_string(),
+            })),
+            running: None,
+        };
+        type_ahead.resume();
+        Some(type_ahead)
+    }
+
+    /// What has been typed so far, shared for display.
+    pub fn typed(&self) -> Arc<Mutex<Typed>> {
+        Arc::clone(&self.typed)
+    }
+
+    /// Hands the terminal back, for a prompt that reads input itself.
+    pub fn pause(&mut self) {
+        if let Some((stop, handle)) = self.running.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.running.is_some() {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::spawn({
+            let stop = Arc::clone(&stop);
+            let typed = Arc::clone(&self.typed);
+            move || collect(&stop, &typed)
+        });
+        self.running = Some((stop, handle));
+    }
+
+    /// Stops collecting and returns what was typed.
+    pub fn finish(mut self) -> Typed {
+        self.pause();
+        self.typed.lock().map(|t| t.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for TypeAhead {
+    fn drop(&mut self) {
+        self.pause();
+    }
+}
+
+/// Reads keystrokes into `typed` until `stop` is set, with echo and line
+/// buffering off for the duration.
+#[cfg(unix)]
+fn collect(stop: &AtomicBool, typed: &Mutex<Typed>) {
+    const STDIN: libc::c_int = 0;
+    // SAFETY: termios is plain data, filled in by tcgetattr before use.
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(STDIN, &mut original) } != 0 {
+        return;
+    }
+    let mut quiet = original;
+    quiet.c_lflag &= !(libc::ICANON | libc::ECHO);
+    quiet.c_cc[libc::VMIN] = 1;
+    quiet.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(STDIN, libc::TCSANOW, &quiet) } != 0 {
+        return;
+    }
+
+    let mut buffer = [0u8; 256];
+    // Bytes of a character split across reads.
+    let mut partial = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        let mut poll = libc::pollfd {
+            fd: STDIN,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // A short timeout, so a stop request is noticed quickly.
+        if unsafe { libc::poll(&mut poll, 1, 50) } <= 0 {
+            continue;
+        }
+        let read = unsafe { libc::read(STDIN, buffer.as_mut_ptr().cast(), buffer.len()) };
+        if read <= 0 {
+            break;
+        }
+        partial.extend_from_slice(&buffer[..read as usize]);
+        let valid = match std::str::from_utf8(&partial) {
+            Ok(text) => text.len(),
+            // Not UTF-8 at all; keep what can be made of it.
+            Err(e) if e.error_len().is_some() => partial.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        if let Ok(mut typed) = typed.lock() {
+            typed.push(&String::from_utf8_lossy(&partial[..valid]));
+        }
+        partial.drain(..valid);
+    }
+
+    unsafe { libc::tcsetattr(STDIN, libc::TCSANOW, &original) };
+}
+
+#[cfg(not(unix))]
+fn collect(_stop: &AtomicBool, _typed: &Mutex<Typed>) {}
@@ -0,0 +1,337 @@
+//! Vi keybindings for the prompt line: an insert mode that edits like the
+//! default bindings, and a normal mode with motions, operators, and text objects.
+
+use termimad::crossterm::event::{KeyCode, KeyEvent};
+
+use super::{edit_key, Line};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Insert,
+    Normal,
+}
+
+pub struct ViState {
+    mode: Mode,
+    /// Keys of an unfinished normal-mode command, such as `d` or `ci`.
+    pending: String,
+    /// Text from the last delete or yank, for `p` and `P`.
+    register: String,
+    /// Earlier states of the line, restored by `u`.
+    undo: Vec<(Vec<char>, usize)>,
+    /// The line as it was when insert mode began, so one insert undoes as a unit.
+    insert_start: Option<(Vec<char>, usize)>,
+}
+
+impl ViState {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Insert,
+            pending: String::new(),
+            register: String::new(),
+            undo: Vec::new(),
+            insert_start: None,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// In normal mode with no command pending, where `j` and `k` move through history.
+    pub fn is_idle(&self) -> bool {
+        self.mode == Mode::Normal && self.pending.is_empty()
+    }
+
+    pub fn handle_key(&mut self, line: &mut Line, key: KeyEvent) {
+        match self.mode {
+            Mode::Insert => self.insert_key(line, key),
+            Mode::Normal => self.normal_key(line, key),
+        }
+        if self.mode == Mode::Normal {
+            // Normal mode sits on a character, never past the end.
+            line.cursor = line.cursor.min(line.buffer.len().saturating_sub(1));
+        }
+    }
+
+    fn insert_key(&mut self, line: &mut Line, key: KeyEvent) {
+        let start = self
+            .insert_start
+            .get_or_insert_with(|| (line.buffer.clone(), line.cursor));
+        if key.code != KeyCode::Esc {
+            edit_key(line, key);
+            return;
+        }
+
+        if start.0 != line.buffer {
+            self.undo.push(start.clone());
+        }
+        self.insert_start = None;
+        self.mode = Mode::Normal;
+        line.cursor = line.cursor.saturating_sub(1);
+    }
+
+    fn normal_key(&mut self, line: &mut Line, key: KeyEvent) {
+        if self.pending == "r" && !matches!(key.code, KeyCode::Char(_)) {
+            self.pending.clear();
+            return;
+        }
+        let c = match key.code {
+            KeyCode::Char(c) => c,
+            KeyCode::Left | KeyCode::Backspace => 'h',
+            KeyCode::Right => 'l',
+            KeyCode::Home => '0',
+            KeyCode::End => '$',
+            KeyCode::Esc => {
+                self.pending.clear();
+                return;
+            }
+            _ => return,
+        };
+        let mut keys = std::mem::take(&mut self.pending);
+        keys.push(c);
+
+        if keys == "u" {
+            if let Some((buffer, cursor)) = self.undo.pop() {
+                line.buffer = buffer;
+                line.cursor = cursor;
+            }
+            return;
+        }
+
+        let before = (line.buffer.clone(), line.cursor);
+        self.command(line, &keys);
+        match self.mode {
+            Mode::Insert => self.insert_start = Some(before),
+            Mode::Normal if line.buffer != before.0 => self.undo.push(before),
+            Mode::Normal => {}
+        }
+    }
+
+    fn command(&mut self, line: &mut Line, keys: &str) {
+        let len = line.buffer.len();
+        match keys {
+            "i" => self.mode = Mode::Insert,
+            "a" => {
+                line.cursor = (line.cursor + 1).min(len);
+                self.mode = Mode::Insert;
+            }
+            "I" => {
+                line.cursor = first_non_blank(&line.buffer);
+                self.mode = Mode::Insert;
+            }
+            "A" => {
+                line.cursor = len;
+                self.mode = Mode::Insert;
+            }
+            "x" if len > 0 => self.register = line.delete(line.cursor, line.cursor + 1),
+            "X" if line.cursor > 0 => self.register = line.delete(line.cursor - 1, line.cursor),
+            "D" => self.register = line.delete(line.cursor, len),
+            "C" => {
+                self.register = line.delete(line.cursor, len);
+                self.mode = Mode::Insert;
+            }
+            "s" => {
+                self.register = line.delete(line.cursor, line.cursor + 1);
+                self.mode = Mode::Insert;
+            }
+            "S" | "cc" => {
+                self.register = line.delete(0, len);
+                self.mode = Mode::Insert;
+            }
+            "dd" => self.register = line.delete(0, len),
+            "yy" => self.register = line.text(),
+            "p" | "P" => {
+                if keys == "p" && len > 0 {
+                    line.cursor += 1;
+                }
+                for c in self.register.chars() {
+                    line.insert(c);
+                }
+                line.cursor = line.cursor.saturating_sub(1);
+            }
+            "~" if len > 0 => {
+                let c = line.buffer[line.cursor];
+                let toggled = if c.is_uppercase() {
+                    c.to_lowercase().next()
+                } else {
+                    c.to_uppercase().next()
+                };
+                line.buffer[line.cursor] = toggled.unwrap_or(c);
+                line.cursor += 1;
+            }
+            "d" | "c" | "y" | "r" | "di" | "da" | "ci" | "ca" | "yi" | "ya" => {
+                self.pending = keys.to_string();
+            }
+            _ => {
+                let mut chars = keys.chars();
+                let first = chars.next().unwrap_or(' ');
+                let rest = chars.as_str();
+                match first {
+                    'r' if len > 0 => {
+                        if let Some(c) = rest.chars().next() {
+                            line.buffer[line.cursor] = c;
+                        }
+                    }
+                    'd' | 'c' | 'y' if keys.len() > 1 => {
+                        if let Some((start, end)) = range(line, rest, first == 'c') {
+                            self.operate(line, first, start, end);
+                        }
+                    }
+                    _ => {
+                        if let Some(position) = motion(line, keys) {
+                            line.cursor = position;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn operate(&mut self, line: &mut Line, operator: char, start: usize, end: usize) {
+        match operator {
+            'y' => {
+                self.register = line.buffer[start..end].iter().collect();
+                line.cursor = start;
+            }
+            _ => {
+                self.register = line.delete(start, end);
+                if operator == 'c' {
+                    self.mode = Mode::Insert;
+                }
+            }
+        }
+    }
+}
+
+/// Where a cursor motion lands, or `None` if `keys` is not a motion.
+fn motion(line: &Line, keys: &str) -> Option<usize> {
+    let buffer = &line.buffer;
+    let cursor = line.cursor;
+    Some(match keys {
+        "h" => cursor.saturating_sub(1),
+        "l" => (cursor + 1).min(buffer.len()),
+        "0" => 0,
+        "^" => first_non_blank(buffer),
+        "$" => buffer.len(),
+        "w" => next_word_start(buffer, cursor),
+        "b" => previous_word_start(buffer, cursor),
+        "e" => word_end(buffer, cursor),
+        _ => return None,
+    })
+}
+
+/// The span an operator acts on for a motion or text object, end exclusive.
+fn range(line: &Line, target: &str, change: bool) -> Option<(usize, usize)> {
+    let buffer = &line.buffer;
+    let cursor = line.cursor;
+    let len = buffer.len();
+    match target {
+        "iw" => inner_word(buffer, cursor),
+        "aw" => inner_word(buffer, cursor).map(|(mut start, mut end)| {
+            if end < len && is_blank(buffer[end]) {
+                while end < len && is_blank(buffer[end]) {
+                    end += 1;
+                }
+            } else {
+                while start > 0 && is_blank(buffer[start - 1]) {
+                    start -= 1;
+                }
+            }
+            (start, end)
+        }),
+        // Like vi, `cw` changes to the end of the word rather than the next one.
+        "e" => Some((cursor, (word_end(buffer, cursor) + 1).min(len))),
+        "w" if change => Some((cursor, (word_end(buffer, cursor) + 1).min(len))),
+        _ => {
+            let position = motion(line, target)?;
+            Some((cursor.min(position), cursor.max(position)))
+        }
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c.is_whitespace()
+}
+
+/// Characters of the same class form a word: blanks, word characters, or punctuation.
+fn class(c: char) -> u8 {
+    if is_blank(c) {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn first_non_blank(buffer: &[char]) -> usize {
+    buffer
+        .iter()
+        .position(|c| !is_blank(*c))
+        .unwrap_or(buffer.len())
+}
+
+fn next_word_start(buffer: &[char], cursor: usize) -> usize {
+    let len = buffer.len();
+    let mut i = cursor;
+    if i >= len {
+        return len;
+    }
+    let current = class(buffer[i]);
+    while i < len && class(buffer[i]) == current {
+        i += 1;
+    }
+    while i < len && is_blank(buffer[i]) {
+        i += 1;
+    }
+    i
+}
+
+fn previous_word_start(buffer: &[char], cursor: usize) -> usize {
+    let mut i = cursor.min(buffer.len());
+    while i > 0 && is_blank(buffer[i - 1]) {
+        i -= 1;
+    }
+    if i == 0 {
+        return 0;
+    }
+    let current = class(buffer[i - 1]);
+    while i > 0 && class(buffer[i - 1]) == current {
+        i -= 1;
+    }
+    i
+}
+
+fn word_end(buffer: &[char], cursor: usize) -> usize {
+    let len = buffer.len();
+    let mut i = cursor + 1;
+    while i < len && is_blank(buffer[i]) {
+        i += 1;
+    }
+    if i >= len {
+        return len.saturating_sub(1);
+    }
+    let current = class(buffer[i]);
+    while i + 1 < len && class(buffer[i + 1]) == current {
+        i += 1;
+    }
+    i
+}
+
+fn inner_word(buffer: &[char], cursor: usize) -> Option<(usize, usize)> {
+    if buffer.is_empty() {
+        return None;
+    }
+    let i = cursor.min(buffer.len() - 1);
+    let current = class(buffer[i]);
+    let mut start = i;
+    while start > 0 && class(buffer[start - 1]) == current {
+        start -= 1;
+    }
+    let mut end = i + 1;
+    while end < buffer.len() && class(buffer[end]) == current {
+        end += 1;
+    }
+    Some((start, end))
+}
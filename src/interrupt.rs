@@ -0,0 +1,39 @@
+//! Cancelling the tool call that is running, with Esc or Ctrl+C, without
+//! ending the turn: the model is told the call was cancelled and goes on
+//! without it. At any other time Ctrl+C ends Rusty, as before.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::sync::Notify;
+
+/// Set while a call that can be cancelled runs.
+static ARMED: AtomicBool = AtomicBool::new(false);
+
+static CANCEL: Notify = Notify::const_new();
+
+/// Runs `future` to the end, or until it is cancelled, which drops it and
+/// returns `None`.
+pub async fn cancellable<F: Future>(future: F) -> Option<F::Output> {
+    let cancelled = CANCEL.notified();
+    tokio::pin!(cancelled);
+    // Listening before arming, so no cancel falls in between.
+    cancelled.as_mut().enable();
+    ARMED.store(true, Ordering::SeqCst);
+    let output = tokio::select! {
+        output = future => Some(output),
+        _ = cancelled => None,
+    };
+    ARMED.store(false, Ordering::SeqCst);
+    output
+}
+
+/// Cancels the call that is running. Returns `false` when there is none,
+/// for the key to do what it otherwise does.
+pub fn cancel() -> bool {
+    if !ARMED.load(Ordering::SeqCst) {
+        return false;
+    }
+    CANCEL.notify_waiters();
+    true
+}
@@ -0,0 +1,67 @@
+//! The interface text in the chosen language. English, Spanish and German
+//! are built in; a catalog in the `locales` configuration directory adds a
+//! language or overrides entries of a built-in one.
+
+use std::fs;
+use std::sync::OnceLock;
+
+use rusty_core::locale::{self, Catalog};
+use rusty_core::paths;
+
+const BUILT_IN: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.toml")),
+    ("es", include_str!("../locales/es.toml")),
+    ("de", include_str!("../locales/de.toml")),
+];
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Picks the language, `configured` or the system's, for the rest of the
+/// run. Without a call, the text is English.
+pub fn init(configured: Option<&str>) -> Result<(), String> {
+    let language = locale::language(configured);
+    let mut catalog = english();
+    if let Some((_, source)) = BUILT_IN.iter().find(|(code, _)| *code == language) {
+        catalog.merge(source)?;
+    }
+    let path = paths::locales_dir().join(format!("{}.toml", language));
+    let result = match fs::read_to_string(&path) {
+        Ok(source) => catalog
+            .merge(&source)
+            .map_err(|e| format!("Invalid catalog {}: {}", path.display(), e)),
+        Err(_) => Ok(()),
+    };
+    let _ = CATALOG.set(catalog);
+    result
+}
+
+fn english() -> Catalog {
+    let mut catalog = Catalog::default();
+    catalog
+        .merge(BUILT_IN[0].1)
+        .expect("the English catalog is valid");
+    catalog
+}
+
+fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(english)
+}
+
+/// The text for `key`, or the key itself if no catalog has it.
+pub fn t(key: &'static str) -> &'static str {
+    catalog().text(key).unwrap_or(key)
+}
+
+/// The text for `key` with each `{name}` replaced by its value.
+pub fn tf(key: &'static str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(t(key).to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// The list for `key`, empty if no catalog has it.
+pub fn list(key: &str) -> &'static [String] {
+    catalog().list(key).unwrap_or_default()
+}
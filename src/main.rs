@@ -1,120 +1,214 @@
 use rand::prelude::IndexedRandom;
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fs;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
+use clap::{Parser, Subcommand};
 use colored::*;
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
-use serde::{Deserialize, Serialize};
+use rusty_core::browser;
+use rusty_core::cassette::Cassette;
+use rusty_core::citations::{self, Sources};
+use rusty_core::clipboard;
+use rusty_core::config::Alias;
+use rusty_core::config::IndexConfig;
+use rusty_core::config::Persona;
+use rusty_core::config::ScheduledPrompt;
+use rusty_core::config::{BudgetConfig, DiffView, EnvironmentConfig, StylesConfig, TrustConfig};
+use rusty_core::conversation;
+use rusty_core::diagnostics;
+use rusty_core::diagram;
+use rusty_core::encryption::{self, Cipher};
+use rusty_core::environment;
+use rusty_core::feedback::{self, FeedbackLog, Rating};
+use rusty_core::fetch::{Fetcher, Page};
+use rusty_core::githooks;
+use rusty_core::http::{Allowlist, Egress};
+use rusty_core::instructions::{self, InstructionFile};
+use rusty_core::mcp::McpServer;
+use rusty_core::models::{self, context_window, model_info, MODELS};
+use rusty_core::moderation::{Direction, Moderator, Verdict};
+use rusty_core::notice::{self, Notice};
+use rusty_core::notify;
+use rusty_core::ocr::{self, Ocr};
+use rusty_core::pii::Scrubber;
+use rusty_core::projects::Projects;
+use rusty_core::prompts;
+use rusty_core::rag::{self, DocumentIndex, Retriever};
+use rusty_core::redact::Redactor;
+use rusty_core::repo::{self, Repo};
+use rusty_core::services;
+use rusty_core::sessions;
+use rusty_core::shell;
+use rusty_core::spending::{self, DayUsage, Ledger, ToolStats};
+use rusty_core::telemetry::Telemetry;
+use rusty_core::tools::{
+    self, summarize_arguments, Gate, Mutation, Policy, RepeatGuard, ToolContext, ToolRegistry,
+    ToolSteps,
+};
+use rusty_core::{export, gist};
+use rusty_core::{
+    paths, Agent, BraveSearchClient, ChatOptions, CommandHooks, Config, ConversationManager,
+    Embedder, GroqApiClient, History, HookOutcome, MemoryStore, Message, MockProvider, PluginHost,
+    PromptLibrary, Provider, ProviderError, Recorder, ResponseFormat, Scheduler, ScriptHooks,
+    Session, SessionStore, Settings, TokenLogprob, ToolChoice, ToolDefinition, Usage,
+};
 use syntect::easy::HighlightLines;
 use syntect::highlighting::{Style, ThemeSet};
 use syntect::parsing::SyntaxSet;
 use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 use termimad::crossterm::style::Color as CrosstermColor;
+use termimad::crossterm::terminal;
 use termimad::MadSkin;
-use tokio::io::AsyncBufReadExt;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use editor::{LineEditor, ReadOutcome, TypeAhead};
+use palette::{PaletteAction, PaletteItem};
+use spinner::Spinner;
+
+mod cleanup;
+mod console;
+mod crash;
+mod diff;
+mod editor;
+mod interrupt;
+mod locale;
+mod man;
+mod math;
+mod palette;
+mod picker;
+mod setup;
+mod spinner;
+mod table;
+
+/// Prints a status line under the step above it, like `println!`, marked
+/// with a tree branch except in accessibility mode. Nothing is printed in
+/// quiet mode.
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !UserInterface::quiet() {
+            println!("{}{}", UserInterface::branch(), format_args!($($arg)*))
+        }
+    };
+}
 
 // Constants
 
-const GROQ_API_URL: &str = "https://api.groq.com/openai/v1/chat/completions";
-const MODELS: &[&str] = &[
-    "openai/gpt-oss-120b",
-    "meta-llama/llama-4-maverick-17b-128e-instruct",
-    "moonshotai/kimi-k2-instruct-0905",
+/// Built-in commands with their usage and the catalog key of a one-line
+/// description, shared by `/help` and the command palette.
+const COMMANDS: &[(&str, &str)] = &[
+    ("/exit", "commands.exit"),
+    ("/model [name|n]", "commands.model"),
+    ("/clear", "commands.clear"),
+    ("/stream", "commands.stream"),
+    ("/debug", "commands.debug"),
+    ("/dryrun", "commands.dryrun"),
+    ("/redact [on|off]", "commands.redact"),
+    ("/prompts [name]", "commands.prompts"),
+    ("/use <name> [text]", "commands.use"),
+    ("/system [prompt|reset]", "commands.system"),
+    ("/persona [name|off]", "commands.persona"),
+    ("/edit [--editor]", "commands.edit"),
+    ("/editor [text]", "commands.editor"),
+    ("/checkpoint [name]", "commands.checkpoint"),
+    ("/rewind <name>", "commands.rewind"),
+    ("/save [title]", "commands.save"),
+    ("/sessions [#tag]", "commands.sessions"),
+    ("/session [new|switch|close <name>]", "commands.session"),
+    ("/resume <n|id>", "commands.resume"),
+    ("/tag [name|-name]", "commands.tag"),
+    ("/search <text> [#tag]", "commands.search"),
+    ("/share [--public]", "commands.share"),
+    ("/export [html|md|jsonl] [path]", "commands.export"),
+    ("/history [n]", "commands.history"),
+    ("/messages [show <n>]", "commands.messages"),
+    ("/dump [path]", "commands.dump"),
+    ("/memory [add|rm|clear|files]", "commands.memory"),
+    ("/index [dir]", "commands.index"),
+    ("/crawl <url> [--depth n] [--max n]", "commands.crawl"),
+    ("/paste [text]", "commands.paste"),
+    ("/copy [code]", "commands.copy"),
+    ("/last [edit]", "commands.last"),
+    ("/open <n>", "commands.open"),
+    ("/diagram [n] [text]", "commands.diagram"),
+    ("/expand [n]", "commands.expand"),
+    ("/thinking [show|hide]", "commands.thinking"),
+    ("/json [on|off|schema <file>]", "commands.json"),
+    ("/set [--save] <key> [value]", "commands.set"),
+    ("/get [key]", "commands.get"),
+    ("/tools [stats|enable|disable <tool>]", "commands.tools"),
+    ("/logprobs [on|off]", "commands.logprobs"),
+    ("/usage [on|off]", "commands.usage"),
+    ("/followups [on|off]", "commands.followups"),
+    ("/good [note]", "commands.good"),
+    ("/bad [note]", "commands.bad"),
+    ("/telemetry [status|on|off]", "commands.telemetry"),
+    ("/search-first <text>", "commands.search_first"),
+    ("/add-dir [path]", "commands.add_dir"),
+    ("/translate <lang|off> [text]", "commands.translate"),
+    ("/compare <model|off>", "commands.compare"),
+    ("/help", "commands.help"),
 ];
 
-// Data Models
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Message {
-    role: String,
-    #[serde(default)]
-    content: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tool_calls: Option<Vec<ToolCall>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tool_call_id: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct ToolCall {
-    id: String,
-    #[serde(rename = "type")]
-    r#type: String,
-    function: FunctionCall,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct FunctionCall {
-    name: String,
-    arguments: String,
-}
+/// Key bindings of the prompt line, listed under `/help`.
+const SHORTCUTS: &[(&str, &str)] = &[
+    ("Ctrl+K", "shortcuts.palette"),
+    ("Ctrl+R", "shortcuts.history_search"),
+    ("@", "shortcuts.mention"),
+    ("Ctrl+X Ctrl+E", "shortcuts.compose"),
+    ("Up / Down", "shortcuts.history"),
+    ("Ctrl+C", "shortcuts.clear"),
+    ("Esc", "shortcuts.cancel_tool"),
+    ("Ctrl+D", "shortcuts.exit"),
+];
 
-impl Message {
-    fn new(role: &str, content: &str) -> Self {
-        Self {
-            role: role.to_string(),
-            content: Some(content.to_string()),
-            tool_calls: None,
-            tool_call_id: None,
-        }
-    }
+/// How many times in a turn the model is asked to repeat a tool call whose
+/// arguments are not valid JSON.
+const TOOL_CALL_REPAIRS: usize = 2;
 
-    fn user(content: &str) -> Self {
-        Self::new("user", content)
-    }
+/// What the model is told of a tool call cancelled with Esc or Ctrl+C.
+const TOOL_CANCELLED: &str = "Error: The user cancelled this tool call. Do not call it again \
+with the same arguments; answer with what you have, or say what is missing.";
 
-    fn assistant(content: &str) -> Self {
-        Self::new("assistant", content)
-    }
+/// How many links away from the first page `/crawl` goes, and how many
+/// pages it reads, unless told otherwise.
+const CRAWL_DEPTH: usize = 2;
+const CRAWL_MAX: usize = 20;
 
-    fn system(content: &str) -> Self {
-        Self::new("system", content)
-    }
+/// The id of the session `rusty quick` keeps going.
+const SCRATCH_SESSION: &str = "scratch";
 
-    fn tool(content: &str, id: &str) -> Self {
-        Self {
-            role: "tool".to_string(),
-            content: Some(content.to_string()),
-            tool_calls: None,
-            tool_call_id: Some(id.to_string()),
-        }
-    }
-}
+/// How much piped input a one-shot prompt keeps, in characters. Past
+/// this, the middle of it is left out, as the start and the end of a log
+/// are usually what matters.
+const MAX_PIPED_CHARS: usize = 60_000;
 
-#[derive(Debug, Serialize)]
-struct ToolDefinition {
-    r#type: String,
-    function: ToolFunction,
-}
+/// The name of the conversation Rusty starts with, for `/session`.
+const MAIN_THREAD: &str = "main";
 
-#[derive(Debug, Serialize)]
-struct ToolFunction {
-    name: String,
-    description: String,
-    parameters: serde_json::Value,
-}
+/// How many files the `@` finder lists for what has been typed.
+const FILE_MATCHES: usize = 50;
 
-#[derive(Debug, Serialize)]
-struct ChatRequest {
-    model: String,
-    messages: Vec<Message>,
-    stream: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tools: Option<Vec<ToolDefinition>>,
-}
+/// How many prompts `/followups` offers after each answer.
+const FOLLOW_UPS: usize = 3;
 
-#[derive(Debug, Deserialize)]
-struct ChatResponse {
-    choices: Vec<Choice>,
-}
+/// How many times a `/json` answer that is not the JSON asked for is sent
+/// back to the model with what is wrong before it is shown anyway.
+const JSON_RETRIES: usize = 2;
 
-#[derive(Debug, Deserialize)]
-struct Choice {
-    message: Message,
-}
+/// The pace of `rusty replay` at speed 1, in milliseconds: per character
+/// typed, before each answer and tool call, and per word streamed.
+const REPLAY_TYPING_MS: u64 = 45;
+const REPLAY_THINKING_MS: u64 = 900;
+const REPLAY_STREAMING_MS: u64 = 25;
 
-#[derive(Debug, Deserialize)]
+/// How often the network is probed while prompts wait for it.
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
 
 // Configuration Manager
 
@@ -134,10 +228,10 @@ impl ConfigManager {
         Err(format!("{} not found in .env file.", key_name))
     }
 
-    fn save_key(key_name: &str, key_value: &str) -> Result<(), String> {
-        let path = Self::get_config_path();
+    /// Sets `key_name` in the `.env` file at `path`, keeping its other lines.
+    fn save_key(path: &Path, key_name: &str, key_value: &str) -> Result<(), String> {
         let mut content = if path.exists() {
-            fs::read_to_string(&path).unwrap_or_default()
+            fs::read_to_string(path).unwrap_or_default()
         } else {
             String::new()
         };
@@ -162,7 +256,7 @@ impl ConfigManager {
             content.push('\n');
         }
 
-        fs::write(&path, content).map_err(|e| format!("Failed to write API key: {}", e))
+        fs::write(path, content).map_err(|e| format!("Failed to write API key: {}", e))
     }
 
     fn prompt_for_key(key_name: &str, display_name: &str) -> Result<String, String> {
@@ -171,274 +265,216 @@ impl ConfigManager {
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
-        io::stdin()
+        let read = io::stdin()
             .read_line(&mut input)
             .expect("Failed to read input");
+        // With nothing left to read, asking again would never end.
+        if read == 0 {
+            UserInterface::print_error(&format!("No {} given", display_name));
+            Failure::Usage.exit();
+        }
 
         let key = input.trim().to_string();
         if key.is_empty() {
             return Err("Empty API key".to_string());
         }
+        // A command typed here, such as `/exit`, is not saved as the key.
+        if key.starts_with('/') {
+            return Err(format!("'{}' is a command, not an API key", key));
+        }
 
-        Self::save_key(key_name, &key)?;
+        Self::save_key(&Self::get_config_path(), key_name, &key)?;
         Ok(key)
     }
 
-    fn get_or_prompt_api_keys() -> (String, String) {
-        let groq_key = loop {
-            match Self::load_key("GROQ_API_KEY") {
-                Ok(key) => break key,
-                Err(_) => {
-                    if let Ok(key) = Self::prompt_for_key("GROQ_API_KEY", "GroqCloud API key") {
-                        break key;
-                    }
-                }
-            }
-        };
+    /// Unlocks encrypted storage with `RUSTY_PASSPHRASE`, or by asking for the
+    /// passphrase (twice when it is being chosen) on a terminal.
+    fn unlock_storage() -> Result<Cipher, String> {
+        let key_file = paths::key_file();
+        if let Ok(passphrase) = std::env::var("RUSTY_PASSPHRASE") {
+            return Cipher::unlock(&key_file, &passphrase);
+        }
+        if !io::stdin().is_terminal() {
+            return Err("Encrypted storage needs RUSTY_PASSPHRASE when input is piped".to_string());
+        }
 
-        let brave_key = loop {
-            match Self::load_key("BRAVE_API_KEY") {
-                Ok(key) => break key,
-                Err(_) => {
-                    if let Ok(key) = Self::prompt_for_key("BRAVE_API_KEY", "Brave Search API key") {
-                        break key;
-                    }
+        if !key_file.exists() {
+            println!("Encrypted storage is on. Choose a passphrase for sessions and history.");
+            loop {
+                let passphrase =
+                    rpassword::prompt_password("New passphrase: ").map_err(|e| e.to_string())?;
+                let confirm =
+                    rpassword::prompt_password("Repeat passphrase: ").map_err(|e| e.to_string())?;
+                if passphrase.is_empty() || passphrase != confirm {
+                    println!("Passphrases were empty or did not match. Try again.");
+                    continue;
                 }
+                return Cipher::unlock(&key_file, &passphrase);
             }
-        };
-
-        (groq_key, brave_key)
-    }
-}
-
-// Brave Search Client
-
-struct BraveSearchClient {
-    api_key: String,
-    client: reqwest::Client,
-}
-
-impl BraveSearchClient {
-    fn new(api_key: String) -> Self {
-        Self {
-            api_key,
-            client: reqwest::Client::new(),
         }
-    }
-
-    async fn search(&self, query: &str) -> Result<String, reqwest::Error> {
-        let url = "https://api.search.brave.com/res/v1/web/search";
-        let response = self
-            .client
-            .get(url)
-            .header("X-Subscription-Token", &self.api_key)
-            .header("Accept", "application/json")
-            .query(&[("q", query), ("count", "5")])
-            .send()
-            .await?;
 
-        let json: serde_json::Value = response.json().await?;
-        Ok(self.format_results(json))
-    }
-
-    fn format_results(&self, json: serde_json::Value) -> String {
-        let mut output = String::from("### Brave Search Results\n\n");
-
-        if let Some(web) = json
-            .get("web")
-            .and_then(|w| w.get("results"))
-            .and_then(|r| r.as_array())
-        {
-            if web.is_empty() {
-                output.push_str("No results found.\n");
-            } else {
-                for (i, result) in web.iter().enumerate().take(5) {
-                    let title = result
-                        .get("title")
-                        .and_then(|t| t.as_str())
-                        .unwrap_or("No Title");
-                    let description = result
-                        .get("description")
-                        .and_then(|d| d.as_str())
-                        .unwrap_or("");
-                    let url = result.get("url").and_then(|u| u.as_str()).unwrap_or("");
-
-                    output.push_str(&format!("{}. **{}**\n", i + 1, title));
-                    output.push_str(&format!("   - Snippet: {}\n", description));
-                    output.push_str(&format!("   - URL: {}\n\n", url));
+        let mut attempts = 0;
+        loop {
+            let passphrase =
+                rpassword::prompt_password("Passphrase: ").map_err(|e| e.to_string())?;
+            match Cipher::unlock(&key_file, &passphrase) {
+                Ok(cipher) => return Ok(cipher),
+                Err(e) if attempts < 2 => {
+                    println!("{}", e);
+                    attempts += 1;
                 }
+                Err(e) => return Err(e),
             }
-        } else {
-            output.push_str("Failed to parse search results.\n");
-        }
-
-        output
-    }
-}
-
-// API Client
-
-struct GroqApiClient {
-    api_key: String,
-    client: reqwest::Client,
-}
-
-impl GroqApiClient {
-    fn new(api_key: String) -> Self {
-        Self {
-            api_key,
-            client: reqwest::Client::new(),
         }
     }
 
-    async fn chat_completion(
-        &self,
-        model: &str,
-        messages: &[Message],
-        tools: Option<Vec<ToolDefinition>>,
-    ) -> Result<Message, reqwest::Error> {
-        self.chat_completion_non_stream(model, messages, tools)
-            .await
-    }
-
-    async fn chat_completion_non_stream(
-        &self,
-        model: &str,
-        messages: &[Message],
-        tools: Option<Vec<ToolDefinition>>,
-    ) -> Result<Message, reqwest::Error> {
-        let mut final_messages = Vec::new();
-        final_messages.push(Message::system("You are a helpful AI assistant with access to real-time information via the `brave_search` tool. You can use it to find up-to-date information. Do not attempt to use any tools that are not listed here. Specifically, do NOT use a tool named `open` or `read_file`; they do not exist."));
-        final_messages.extend_from_slice(messages);
-
-        let request = ChatRequest {
-            model: model.to_string(),
-            messages: final_messages,
-            stream: false,
-            tools,
+    fn get_or_prompt_api_keys() -> (String, String) {
+        let groq_key = loop {
+            match Self::load_key("GROQ_API_KEY") {
+                Ok(key) => break key,
+                Err(_) => match Self::prompt_for_key("GROQ_API_KEY", "GroqCloud API key") {
+                    Ok(key) => break key,
+                    Err(e) => UserInterface::print_error(&e),
+                },
+            }
         };
 
-        let mut retries = 0;
-        loop {
-            let response = self
-                .client
-                .post(GROQ_API_URL)
-                .header(AUTHORIZATION, format!("Bearer {}", self.api_key))
-                .header(CONTENT_TYPE, "application/json")
-                .json(&request)
-                .send()
-                .await?;
-
-            if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-                if retries >= 3 {
-                    let body_text = response.text().await?;
-                    eprintln!("Rate limit exceeded after retries. Body: {}", body_text);
-                    panic!("Groq API Rate Limit Exceeded");
-                }
-                retries += 1;
-                eprintln!(
-                    "Rate limit hit, retrying in 2 seconds... (Attempt {}/3)",
-                    retries
-                );
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                continue;
+        let brave_key = loop {
+            match Self::load_key("BRAVE_API_KEY") {
+                Ok(key) => break key,
+                Err(_) => match Self::prompt_for_key("BRAVE_API_KEY", "Brave Search API key") {
+                    Ok(key) => break key,
+                    Err(e) => UserInterface::print_error(&e),
+                },
             }
+        };
 
-            let body_text = response.text().await?;
-            let chat_response: ChatResponse = match serde_json::from_str(&body_text) {
-                Ok(r) => r,
-                Err(e) => {
-                    eprintln!("Failed to parse API response: {}", e);
-                    eprintln!("Response body: {}", body_text);
-                    panic!("Groq API Error: {}", e);
-                }
-            };
-            return Ok(chat_response
-                .choices
-                .first()
-                .map(|c| c.message.clone())
-                .unwrap_or_else(|| Message::assistant("")));
-        }
+        (groq_key, brave_key)
     }
-
-    // Stream mode is trickier with tool calls, for now let's focus on non-stream for search
-    // or handle it by disabling stream when tool calls are expected.
 }
 
 // Model Manager
 
 struct ModelManager {
     selected_model: String,
+    /// What an empty or unknown choice selects.
+    default_model: String,
 }
 
 impl ModelManager {
     fn new() -> Self {
         Self {
             selected_model: MODELS[0].to_string(),
+            default_model: MODELS[0].to_string(),
         }
     }
 
+    /// Makes `model` the one selected until another is chosen, and the one
+    /// Enter picks at startup.
+    fn set_default(&mut self, model: &str) {
+        self.selected_model = model.to_string();
+        self.default_model = model.to_string();
+    }
+
     fn list_models() {
         let orange = Color::TrueColor {
             r: 255,
             g: 140,
             b: 0,
         };
-        println!("{}", "\nAvailable models:".color(orange).bold());
+        println!("\n{}", locale::t("models.available").color(orange).bold());
+        let width = MODELS.iter().map(|model| model.len()).max().unwrap_or(0) + 2;
         for (i, model) in MODELS.iter().enumerate() {
-            println!("  [{}] {}", (i + 1).to_string().color(orange), model);
+            println!(
+                "  [{}] {:<width$}{}",
+                (i + 1).to_string().color(orange),
+                model,
+                Self::details(model).dimmed(),
+                width = width
+            );
         }
         println!();
     }
 
+    /// The context window of `model`, what it can do, and its price.
+    fn details(model: &str) -> String {
+        let info = model_info(model);
+        let context = format!("{}K", info.context_window / 1024);
+        let mut details = vec![locale::tf("models.context", &[("context", &context)])];
+        if info.vision {
+            details.push(locale::t("models.vision").to_string());
+        }
+        if !info.tools {
+            details.push(locale::t("models.no_tools").to_string());
+        }
+        if let Some((input, output)) = info.price {
+            details.push(locale::tf(
+                "models.price",
+                &[
+                    ("input", &format!("{:.2}", input)),
+                    ("output", &format!("{:.2}", output)),
+                ],
+            ));
+        }
+        details.join(" · ")
+    }
+
+    /// Whether models are chosen from a list with the arrow keys, rather
+    /// than by typing a number.
+    fn can_pick() -> bool {
+        !UserInterface::plain() && io::stdin().is_terminal() && io::stdout().is_terminal()
+    }
+
+    /// Picks a model from the list, starting at `current`. `Ok(None)` when
+    /// the list is dismissed.
+    fn pick(current: &str) -> io::Result<Option<String>> {
+        let choices: Vec<picker::Choice> = MODELS
+            .iter()
+            .map(|model| picker::Choice {
+                label: model.to_string(),
+                detail: Self::details(model),
+            })
+            .collect();
+        let selected = MODELS
+            .iter()
+            .position(|model| *model == current)
+            .unwrap_or(0);
+        println!();
+        let chosen = picker::pick(
+            locale::t("models.available"),
+            locale::t("models.pick_hint"),
+            &choices,
+            selected,
+        )?;
+        Ok(chosen.map(|i| MODELS[i].to_string()))
+    }
+
     async fn select_model_interactive(
         &mut self,
         reader: &mut tokio::io::BufReader<tokio::io::Stdin>,
     ) -> Result<bool, Box<dyn std::error::Error>> {
-        let orange = Color::TrueColor {
-            r: 255,
-            g: 140,
-            b: 0,
-        };
-        let gray = Color::TrueColor {
-            r: 100,
-            g: 100,
-            b: 100,
+        if Self::can_pick() {
+            self.selected_model =
+                Self::pick(&self.default_model)?.unwrap_or_else(|| self.default_model.clone());
+            return Ok(true);
+        }
+        Self::list_models();
+        let default = match MODELS.iter().position(|m| *m == self.default_model) {
+            Some(i) => (i + 1).to_string(),
+            None => self.default_model.clone(),
         };
-        println!("Select a model (1-3) or press Enter for default [1]: ");
-        println!("{}", "─".repeat(110).color(gray));
-        println!(" ");
-        println!("{}", "─".repeat(110).color(gray));
-        print!("  {} {}", "?".color(gray), "for shortcuts".color(gray));
-        io::stdout().flush().unwrap();
-
-        print!("\x1b[2A\r");
-        print!("{} ", "❯".color(orange).bold());
-        io::stdout().flush().unwrap();
-
+        println!(
+            "{} ",
+            locale::tf("models.select_default", &[("default", &default)])
+        );
+        UserInterface::print_prompt();
         let mut input = String::new();
         tokio::io::AsyncBufReadExt::read_line(reader, &mut input).await?;
         let input = input.trim();
-
-        // Move to hint line and clear it
-        println!();
-        print!("\x1b[2K\r");
-        io::stdout().flush().unwrap();
+        UserInterface::print_prompt_closure();
 
         if input == "/exit" || input == "/quit" {
-            let goodbyes = [
-                "Catch you on the flip side!",
-                "Keep it 100!",
-                "Stay classy!",
-                "Later, alligator!",
-                "See ya!",
-                "Cheers!",
-                "Bye!",
-                "Until next time!",
-            ];
-            let mut rng = rand::rng();
-            let goodbye = goodbyes.choose(&mut rng).unwrap_or(&"Goodbye!");
-            println!("  ⎿  {}\n", goodbye);
+            UserInterface::print_goodbye();
             return Ok(false);
         }
 
@@ -450,51 +486,22 @@ impl ModelManager {
         &mut self,
         reader: &mut tokio::io::BufReader<tokio::io::Stdin>,
     ) -> Result<bool, Box<dyn std::error::Error>> {
-        let orange = Color::TrueColor {
-            r: 255,
-            g: 140,
-            b: 0,
-        };
-        let gray = Color::TrueColor {
-            r: 100,
-            g: 100,
-            b: 100,
-        };
+        if Self::can_pick() {
+            if let Some(model) = Self::pick(&self.selected_model)? {
+                self.selected_model = model;
+            }
+            return Ok(true);
+        }
         Self::list_models();
-        println!("Select a model (1-3): ");
-        println!("{}", "─".repeat(110).color(gray));
-        println!(" ");
-        println!("{}", "─".repeat(110).color(gray));
-        print!("  {} {}", "?".color(gray), "for shortcuts".color(gray));
-        io::stdout().flush().unwrap();
-
-        print!("\x1b[2A\r");
-        print!("{} ", "❯".color(orange).bold());
-        io::stdout().flush().unwrap();
-
+        println!("{} ", locale::t("models.select"));
+        UserInterface::print_prompt();
         let mut input = String::new();
         tokio::io::AsyncBufReadExt::read_line(reader, &mut input).await?;
         let input = input.trim();
-
-        // Move to hint line and clear it
-        println!();
-        print!("\x1b[2K\r");
-        io::stdout().flush().unwrap();
+        UserInterface::print_prompt_closure();
 
         if input == "/exit" || input == "/quit" {
-            let goodbyes = [
-                "Catch you on the flip side!",
-                "Keep it 100!",
-                "Stay classy!",
-                "Later, alligator!",
-                "See ya!",
-                "Cheers!",
-                "Bye!",
-                "Until next time!",
-            ];
-            let mut rng = rand::rng();
-            let goodbye = goodbyes.choose(&mut rng).unwrap_or(&"Goodbye!");
-            println!("  ⎿  {}\n", goodbye);
+            UserInterface::print_goodbye();
             return Ok(false);
         }
 
@@ -503,83 +510,223 @@ impl ModelManager {
     }
 
     fn parse_model_choice(&self, choice: &str) -> String {
-        match choice {
-            "1" | "" => MODELS[0].to_string(),
-            "2" => MODELS[1].to_string(),
-            "3" => MODELS[2].to_string(),
+        if choice.is_empty() {
+            return self.default_model.clone();
+        }
+        match Self::find_models(choice).as_slice() {
+            [model] => model.to_string(),
             _ => {
-                println!("Invalid choice. Using default model.");
-                MODELS[0].to_string()
+                println!("{}", locale::t("models.invalid"));
+                self.default_model.clone()
             }
         }
     }
 
+    /// The models `choice` names: the one at that number in the list, the
+    /// one of that name, or else those whose names contain it, ignoring
+    /// case.
+    fn find_models(choice: &str) -> Vec<&'static str> {
+        if let Some(model) = choice
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| MODELS.get(i))
+        {
+            return vec![model];
+        }
+        if let Some(model) = MODELS
+            .iter()
+            .find(|model| model.eq_ignore_ascii_case(choice))
+        {
+            return vec![model];
+        }
+        let choice = choice.to_lowercase();
+        MODELS
+            .iter()
+            .copied()
+            .filter(|model| model.to_lowercase().contains(&choice))
+            .collect()
+    }
+
     fn get_current_model(&self) -> &str {
         &self.selected_model
     }
 }
 
-// Conversation Manager
+// User Interface
 
-struct ConversationManager {
-    messages: Vec<Message>,
-    stream_mode: bool,
-}
+struct UserInterface;
 
-impl ConversationManager {
-    fn new() -> Self {
-        Self {
-            messages: Vec::new(),
-            stream_mode: false,
+/// Set by `--accessible` or `accessible` under `[chat]`: linear, labeled
+/// output for screen readers, without boxes, colors or cursor movement.
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+/// Set by `-q`/`--quiet`: only the final answer is printed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Set by `raw_math` under `[chat]`: LaTeX is shown as written.
+static RAW_MATH: AtomicBool = AtomicBool::new(false);
+
+/// Set by `git_prompt` under `[chat]`: the prompt's hint line names the
+/// Git branch.
+static GIT_PROMPT: AtomicBool = AtomicBool::new(false);
+
+/// `rusty quick`: the prompt fits a small popup and says how to close it.
+static COMPACT: AtomicBool = AtomicBool::new(false);
+
+/// Set by `keep_title` under `[chat]`.
+static KEEP_TITLE: AtomicBool = AtomicBool::new(false);
+
+/// The window title last set, to set it only when it changes and to know
+/// whether the original is to be put back.
+static TITLE: Mutex<Option<String>> = Mutex::new(None);
+
+/// The output of the `statusline` hook, shown on the prompt's hint line.
+static STATUS_LINE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Built from `[styles]` at startup.
+static SKIN: OnceLock<MadSkin> = OnceLock::new();
+
+/// Set by `diff_view` under `[chat]`.
+static DIFF_VIEW: Mutex<DiffView> = Mutex::new(DiffView::Auto);
+
+/// The narrowest terminal patches are shown side by side in when
+/// `diff_view` is `auto`.
+const SIDE_BY_SIDE_WIDTH: usize = 120;
+
+impl UserInterface {
+    fn set_accessible(on: bool) {
+        ACCESSIBLE.store(on, Ordering::Relaxed);
+        if on {
+            colored::control::set_override(false);
         }
     }
 
-    fn add_user_message(&mut self, content: &str) {
-        self.messages.push(Message::user(content));
+    fn accessible() -> bool {
+        ACCESSIBLE.load(Ordering::Relaxed)
     }
 
-    fn remove_last_message(&mut self) {
-        self.messages.pop();
+    fn set_quiet(on: bool) {
+        QUIET.store(on, Ordering::Relaxed);
     }
 
-    fn clear(&mut self) {
-        self.messages.clear();
+    fn quiet() -> bool {
+        QUIET.load(Ordering::Relaxed)
     }
 
-    fn get_messages(&self) -> &[Message] {
-        &self.messages
+    fn set_raw_math(on: bool) {
+        RAW_MATH.store(on, Ordering::Relaxed);
     }
 
-    fn toggle_stream_mode(&mut self) {
-        self.stream_mode = !self.stream_mode;
+    fn set_status_line(status: Option<String>) {
+        *STATUS_LINE.lock().unwrap() = status;
     }
 
-    fn is_stream_mode(&self) -> bool {
-        self.stream_mode
+    fn set_compact(on: bool) {
+        COMPACT.store(on, Ordering::Relaxed);
     }
-}
 
-// User Interface
+    fn set_keep_title(on: bool) {
+        KEEP_TITLE.store(on, Ordering::Relaxed);
+    }
 
-struct UserInterface;
+    /// Names the terminal window or tab `title` (OSC 2), saving the title
+    /// it had on the terminal's title stack the first time.
+    fn set_title(title: &str) {
+        if KEEP_TITLE.load(Ordering::Relaxed) || Self::quiet() || !io::stdout().is_terminal() {
+            return;
+        }
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        let mut current = TITLE.lock().unwrap();
+        if current.as_deref() == Some(title.as_str()) {
+            return;
+        }
+        if current.is_none() {
+            print!("\x1b[22;0t");
+        }
+        print!("\x1b]2;{}\x07", title);
+        let _ = io::stdout().flush();
+        *current = Some(title);
+    }
 
-impl UserInterface {
-    fn print_welcome(model: &str) {
+    /// Puts back the title the terminal had before `set_title`.
+    fn restore_title() {
+        if TITLE.lock().unwrap().take().is_some() {
+            print!("\x1b[23;0t");
+            let _ = io::stdout().flush();
+        }
+    }
+
+    fn set_git_prompt(on: bool) {
+        GIT_PROMPT.store(on, Ordering::Relaxed);
+    }
+
+    fn set_diff_view(view: DiffView) {
+        *DIFF_VIEW.lock().unwrap() = view;
+    }
+
+    /// Line by line, without the prompt box, the spinner or styled
+    /// answers: in accessibility mode, or where the console does not follow
+    /// escape sequences.
+    fn plain() -> bool {
+        Self::accessible() || !console::escapes()
+    }
+
+    /// What status lines start with, see [`note!`].
+    fn branch() -> &'static str {
+        if Self::accessible() {
+            ""
+        } else {
+            "  ⎿  "
+        }
+    }
+
+    /// What the assistant's answers start with.
+    fn assistant_label() -> String {
+        if Self::accessible() {
+            format!("{} ", locale::t("labels.assistant"))
+        } else {
+            "● ".to_string()
+        }
+    }
+
+    fn print_welcome(model: &str, recent: &[Session]) {
         let orange = Color::TrueColor {
             r: 255,
             g: 140,
             b: 0,
         };
+        let app = "Rusty".color(orange).bold().to_string();
+        let model_name = model.white().bold().to_string();
         println!(
-            "\nLaunching {} with {}...\n",
-            "Rusty".color(orange).bold(),
-            model.white().bold()
+            "\n{}\n",
+            locale::tf("launching", &[("app", &app), ("model", &model_name)])
         );
-        // Simulate a small delay or just clear for the dashboard
-        Self::draw_dashboard(model);
+        if Self::accessible() {
+            Self::print_recent(recent);
+        } else {
+            Self::draw_dashboard(model, recent);
+        }
+    }
+
+    /// The dashboard's recent activity as plain lines.
+    fn print_recent(recent: &[Session]) {
+        if recent.is_empty() {
+            println!("{}\n", locale::t("dashboard.no_recent"));
+            return;
+        }
+        println!("{}", locale::t("dashboard.recent"));
+        for session in recent.iter().take(4) {
+            println!(
+                "{}, {}",
+                session.display_title(),
+                sessions::time_ago(session.updated_at)
+            );
+        }
+        println!();
     }
 
-    fn draw_dashboard(model: &str) {
+    fn draw_dashboard(model: &str, recent: &[Session]) {
         let orange = Color::TrueColor {
             r: 255,
             g: 140,
@@ -595,7 +742,7 @@ impl UserInterface {
             g: 116,
             b: 81,
         };
-        let path = "~/Claude Code/Rusty";
+        let path = Repo::current().map_or_else(repo::working_dir, |repo| repo.to_string());
         let version = "v1.0.0";
         let b = "│".color(orange);
         let s = "│".color(gray);
@@ -603,11 +750,27 @@ impl UserInterface {
         let col1_w = 40;
         let col2_w = inner_w - col1_w - 1; // 69
 
-        // helper for padding
+        // Up to four saved sessions fill the rows under "Recent activity".
+        let mut activity: Vec<String> = recent
+            .iter()
+            .take(4)
+            .map(|session| {
+                let ago = sessions::time_ago(session.updated_at);
+                let room = col2_w - ago.width() - 4;
+                let title = fit_width(&session.display_title(), room);
+                format!(" {}{}  {}", title, " ".repeat(room - title.width()), ago)
+            })
+            .collect();
+        activity.resize(4, String::new());
+
+        // Pads or cuts to `w` columns; wide glyphs such as CJK and emoji
+        // take two.
         let pad = |s: &str, w: usize, center: bool| -> String {
-            let s_len = s.chars().count();
+            let s_len = s.width();
             if s_len >= w {
-                return s.chars().take(w).collect();
+                let cut = fit_width(s, w);
+                let short = w - cut.width();
+                return format!("{}{}", cut, " ".repeat(short));
             }
             if center {
                 let left = (w - s_len) / 2;
@@ -643,9 +806,11 @@ impl UserInterface {
         println!(
             "{}{}{}{}{}",
             b,
-            pad("Welcome back!", col1_w, true).white().bold(),
+            pad(locale::t("dashboard.welcome"), col1_w, true)
+                .white()
+                .bold(),
             s,
-            pad(" Tips for getting started", col2_w, false)
+            pad(&format!(" {}", locale::t("dashboard.tips")), col2_w, false)
                 .color(orange)
                 .bold(),
             b
@@ -657,12 +822,7 @@ impl UserInterface {
             b,
             pad("", col1_w, true),
             s,
-            pad(
-                " Run /init to create a RUSTY.md file with instructions for Rusty",
-                col2_w,
-                false
-            )
-            .white(),
+            pad(&format!(" {}", locale::t("dashboard.init")), col2_w, false).white(),
             b
         );
 
@@ -682,7 +842,13 @@ impl UserInterface {
             b,
             pad("▐▛███▜▌", col1_w, true).color(logo_orange).bold(),
             s,
-            pad(" Recent activity", col2_w, false).color(orange).bold(),
+            pad(
+                &format!(" {}", locale::t("dashboard.recent")),
+                col2_w,
+                false
+            )
+            .color(orange)
+            .bold(),
             b
         );
 
@@ -692,7 +858,16 @@ impl UserInterface {
             b,
             pad("▝▜█████▛▘", col1_w, true).color(logo_orange).bold(),
             s,
-            pad(" No recent activity", col2_w, false).color(gray),
+            if recent.is_empty() {
+                pad(
+                    &format!(" {}", locale::t("dashboard.no_recent")),
+                    col2_w,
+                    false,
+                )
+                .color(gray)
+            } else {
+                pad(&activity[0], col2_w, false).white()
+            },
             b
         );
 
@@ -702,18 +877,18 @@ impl UserInterface {
             b,
             pad("▘▘ ▝▝ ", col1_w, true).color(logo_orange).bold(),
             s,
-            pad("", col2_w, false),
+            pad(&activity[1], col2_w, false).white(),
             b
         );
 
         // Line 7: Model info
-        let model_info = format!("{} • API Usage Billing", model);
+        let model_info = locale::tf("dashboard.billing", &[("model", model)]);
         println!(
             "{}{}{}{}{}",
             b,
             pad(&model_info, col1_w, true).color(gray),
             s,
-            pad("", col2_w, false),
+            pad(&activity[2], col2_w, false).white(),
             b
         );
 
@@ -721,9 +896,9 @@ impl UserInterface {
         println!(
             "{}{}{}{}{}",
             b,
-            pad(path, col1_w, true).color(gray),
+            pad(&path, col1_w, true).color(gray),
             s,
-            pad("", col2_w, false),
+            pad(&activity[3], col2_w, false).white(),
             b
         );
 
@@ -742,20 +917,55 @@ impl UserInterface {
     }
 
     fn print_instructions() {
-        println!("Type your message and press Enter.");
-        println!("Commands: /exit, /stream, /clear, /model\n");
+        println!("{}", locale::t("prompt.instructions"));
+        println!("{}\n", locale::t("prompt.commands"));
+    }
+
+    fn print_goodbye() {
+        let goodbye = locale::list("goodbyes")
+            .choose(&mut rand::rng())
+            .map_or(locale::t("goodbye"), String::as_str);
+        note!("{}\n", goodbye);
+    }
+
+    fn print_aliases(aliases: &BTreeMap<String, Alias>) {
+        if aliases.is_empty() {
+            return;
+        }
+        println!("  {}", locale::t("help.aliases"));
+        for (name, alias) in aliases {
+            let expansion = match alias {
+                Alias::Line(line) => line.clone(),
+                Alias::Steps(steps) => steps.join(" → "),
+            };
+            println!("  {:<26}{}", format!("/{}", name), expansion.dimmed());
+        }
+        println!();
     }
 
     fn print_help() {
-        println!("  /exit                   Exit the REPL");
-        println!("  /model                  Change the AI model");
-        println!("  /clear                  Clear conversation history and free up context");
-        println!("  /stream                 Toggle streaming mode");
-        println!("  /help                   Show this help message");
+        for (usage, description) in COMMANDS {
+            println!("  {:<26}{}", usage, locale::t(description));
+        }
+        println!();
+        println!("  {}", locale::t("help.shortcuts"));
+        for (keys, description) in SHORTCUTS {
+            println!("  {:<26}{}", keys, locale::t(description));
+        }
         println!();
     }
 
     fn print_prompt() {
+        if Self::accessible() {
+            print!("{} ", locale::t("labels.you"));
+            io::stdout().flush().unwrap();
+            return;
+        }
+        if Self::plain() {
+            print!("> ");
+            io::stdout().flush().unwrap();
+            return;
+        }
         let orange = Color::TrueColor {
             r: 255,
             g: 140,
@@ -767,12 +977,41 @@ impl UserInterface {
             b: 100,
         };
 
+        let compact = COMPACT.load(Ordering::Relaxed);
+        let width = if compact {
+            terminal::size().map_or(80, |(width, _)| (width as usize).min(110))
+        } else {
+            110
+        };
+
         // Sandwich Layout: Pre-render the full box and move cursor back up.
         // This makes it look like you are typing INSIDE the box.
-        println!("{}", "─".repeat(110).color(gray));
+        println!("{}", "─".repeat(width).color(gray));
         println!(" "); // Placeholder for prompt
-        println!("{}", "─".repeat(110).color(gray));
-        print!("  {} {}", "?".color(gray), "for shortcuts".color(gray));
+        println!("{}", "─".repeat(width).color(gray));
+        if compact {
+            print!("  {}", locale::t("prompt.quick_hint").color(gray));
+        } else {
+            print!(
+                "  {} {}",
+                "?".color(gray),
+                locale::t("prompt.hint").color(gray)
+            );
+        }
+        if GIT_PROMPT.load(Ordering::Relaxed) {
+            if let Some(repo) = Repo::current() {
+                print!("{}", format!(" · {}", repo).color(gray));
+            }
+        }
+        if let Some(status) = STATUS_LINE.lock().unwrap().as_deref() {
+            // Gray unless the command colors its output itself.
+            print!(
+                "{}{}{}\x1b[0m",
+                " · ".color(gray),
+                console::fg(100, 100, 100),
+                status
+            );
+        }
         io::stdout().flush().unwrap();
 
         // Move cursor up 2 lines (From Hint -> Bottom -> Prompt Space)
@@ -784,6 +1023,9 @@ impl UserInterface {
     }
 
     fn print_prompt_closure() {
+        if Self::plain() {
+            return;
+        }
         // After input, we are on the Bottom Separator line.
         // Move down to Hint Line and clear it so next output starts fresh.
         println!();
@@ -791,7 +1033,11 @@ impl UserInterface {
         io::stdout().flush().unwrap();
     }
 
-    fn print_thinking() {
+    /// Starts the spinner with a random word, waiting for the model.
+    fn print_thinking(animate: bool) -> Spinner {
+        if Self::quiet() {
+            return Spinner::hidden();
+        }
         let colors = [
             Color::TrueColor {
                 r: 242,
@@ -865,33 +1111,118 @@ impl UserInterface {
             }, // Kanagawa Autumn (New)
         ];
 
-        let words = [
-            "Simmering",
-            "Sparkling",
-            "Zesting",
-            "Julienning",
-            "Marinating",
-            "Cerebrating",
-            "Cogitating",
-            "Ruminating",
-            "Pondering",
-            "Clauding",
-            "Razzmatazzing",
-        ];
+        let words = locale::list("thinking");
 
         let mut rng = rand::rng();
-        let word = words.choose(&mut rng).unwrap_or(&"Thinking");
+        let word = words.choose(&mut rng).map_or("Thinking", String::as_str);
         let color = colors.choose(&mut rng).unwrap_or(&colors[0]);
 
-        println!("{} {}...", "\n*".color(*color), word.color(*color));
+        Spinner::start(word, *color, "waiting for the model", animate)
     }
 
-    fn print_assistant_response(response: &str) {
-        let skin = Self::get_skin();
+    /// Two texts in columns with their labels, or one under the other when
+    /// the terminal is too narrow for columns.
+    fn print_side_by_side(left: (&str, &str), right: (&str, &str)) {
+        let orange = Color::TrueColor {
+            r: 255,
+            g: 140,
+            b: 0,
+        };
+        let gray = Color::TrueColor {
+            r: 100,
+            g: 100,
+            b: 100,
+        };
+        let width = match terminal::size() {
+            Ok((w, _)) if w > 0 => w as usize,
+            _ => 80,
+        };
+        println!();
+        if width < 60 || Self::accessible() {
+            for (label, text) in [left, right] {
+                println!("  {}", label.color(orange).bold());
+                for line in wrap(text, width.saturating_sub(4).max(10)) {
+                    println!("  {}", line);
+                }
+                println!();
+            }
+            return;
+        }
+
+        let column = (width - 7) / 2;
+        let left_lines = wrap(left.1, column);
+        let right_lines = wrap(right.1, column);
+        let header = |label: &str| {
+            let label = fit_width(label, column);
+            format!("{}{}", label, " ".repeat(column - label.width()))
+        };
+        println!(
+            "  {} {} {}",
+            header(left.0).color(orange).bold(),
+            "│".color(gray),
+            header(right.0).color(orange).bold()
+        );
+        for i in 0..left_lines.len().max(right_lines.len()) {
+            let left = left_lines.get(i).map(String::as_str).unwrap_or("");
+            let right = right_lines.get(i).map(String::as_str).unwrap_or("");
+            let padding = column.saturating_sub(left.width());
+            println!(
+                "  {}{} {} {}",
+                left,
+                " ".repeat(padding),
+                "│".color(gray),
+                right
+            );
+        }
+        println!();
+    }
+
+    fn print_assistant_response(response: &str, theme: Option<&str>) {
+        if Self::quiet() {
+            println!("{}", response.trim_end());
+            return;
+        }
         println!(); // Spacing before response
-        print!("● ");
-        use std::io::Write;
-        let _ = std::io::stdout().flush();
+        if Self::plain() {
+            println!("{}{}", Self::assistant_label(), response.trim_end());
+        } else {
+            print!("● {}", Self::render_markdown(response, theme));
+        }
+        println!(); // Spacing after response
+    }
+
+    /// Points out the diagrams in an answer, which are shown as code.
+    fn offer_diagrams(answer: &str) {
+        let diagrams = diagram::find(answer);
+        match diagrams.as_slice() {
+            [] => {}
+            [diagram] => note!(
+                "This answer has a {} diagram · /diagram to draw it\n",
+                diagram.kind.name()
+            ),
+            diagrams => note!(
+                "This answer has {} diagrams · /diagram <n> to draw one\n",
+                diagrams.len()
+            ),
+        }
+    }
+
+    /// The sources of a streamed answer, below it.
+    fn print_sources(footer: &str, theme: Option<&str>) {
+        if Self::quiet() || Self::plain() {
+            println!("{}", footer.trim_end());
+        } else {
+            print!("{}", Self::render_markdown(footer, theme));
+        }
+        println!();
+    }
+
+    /// Renders a response for the terminal: Markdown through the skin and
+    /// fenced code blocks highlighted inside a box, with the syntect theme
+    /// named `theme` when there is one by that name.
+    fn render_markdown(response: &str, theme: Option<&str>) -> String {
+        let skin = Self::get_skin();
+        let mut out = String::new();
 
         // Initialize syntect
         let ps = SyntaxSet::load_defaults_newlines();
@@ -899,7 +1230,9 @@ impl UserInterface {
 
         // Try to load custom Monokai theme
         let theme_path = "Monokai.tmTheme";
-        let theme = if let Ok(t) = ThemeSet::get_theme(theme_path) {
+        let theme = if let Some(t) = theme.and_then(|name| ts.themes.get(name)) {
+            t
+        } else if let Ok(t) = ThemeSet::get_theme(theme_path) {
             ts.themes.insert("monokai-custom".to_string(), t);
             &ts.themes["monokai-custom"]
         } else {
@@ -912,9 +1245,31 @@ impl UserInterface {
         for (i, part) in parts.iter().enumerate() {
             if i % 2 == 0 {
                 // Text part
-                if !part.trim().is_empty() {
-                    skin.print_text(part);
+                if part.trim().is_empty() {
+                    continue;
+                }
+                let part = if RAW_MATH.load(Ordering::Relaxed) {
+                    part.to_string()
+                } else {
+                    math::render(part)
+                };
+                // Links are marked first, so that tables leave room for
+                // their text only.
+                let (part, urls) = console::mark_links(&part);
+                let mut text = String::new();
+                for block in table::blocks(&part) {
+                    match block {
+                        table::Block::Text(markdown) => {
+                            text.push_str(&skin.term_text(&markdown).to_string());
+                        }
+                        table::Block::Table(table) => {
+                            let width = terminal::size().map_or(80, |(width, _)| width as usize);
+                            let gray = console::fg(100, 100, 100);
+                            text.push_str(&table.render(width, &skin, &gray));
+                        }
+                    }
                 }
+                out.push_str(&console::unmark_links(&text, &urls));
             } else {
                 // Code block part
                 let mut lines = part.lines();
@@ -930,36 +1285,146 @@ impl UserInterface {
                     .unwrap_or_else(|| ps.find_syntax_plain_text());
 
                 let mut h = HighlightLines::new(syntax, theme);
+                let diff = diff::is_diff(lang, &code);
 
-                println!();
-                let gray = "\x1b[38;2;100;100;100m";
+                out.push('\n');
+                let gray = console::fg(100, 100, 100);
                 let reset = "\x1b[0m";
-                println!(
-                    "   {}╭───────────────────────────────────────────────────╮{}",
+                out.push_str(&format!(
+                    "   {}╭───────────────────────────────────────────────────╮{}\n",
                     gray, reset
-                );
-
-                for line in LinesWithEndings::from(&code) {
-                    let ranges: Vec<(Style, &str)> = h.highlight_line(line, &ps).unwrap();
-                    let escaped = as_24_bit_terminal_escaped(&ranges[..], false);
-                    print!("   {}│{} {}", gray, reset, escaped); // Indent + Vertical bar
-                }
+                ));
+
+                if diff {
+                    let width = terminal::size().map_or(80, |(width, _)| width as usize);
+                    let side_by_side = match *DIFF_VIEW.lock().unwrap() {
+                        DiffView::Auto => width >= SIDE_BY_SIDE_WIDTH,
+                        DiffView::Unified => false,
+                        DiffView::SideBySide => true,
+                    };
+                    let lines = if side_by_side {
+                        // Past the box's indent, bar and space.
+                        diff::split(&code, width.saturating_sub(5))
+                    } else {
+                        diff::unified(&code)
+                    };
+                    for line in lines {
+                        out.push_str(&format!("   {}│{} {}\n", gray, reset, line));
+                    }
+                } else {
+                    for line in LinesWithEndings::from(&code) {
+                        let ranges: Vec<(Style, &str)> = h.highlight_line(line, &ps).unwrap();
+                        let escaped = if console::truecolor() {
+                            as_24_bit_terminal_escaped(&ranges[..], false)
+                        } else {
+                            ranges
+                                .iter()
+                                .map(|(style, text)| {
+                                    let c = style.foreground;
+                                    format!("{}{}", console::fg(c.r, c.g, c.b), text)
+                                })
+                                .collect::<String>()
+                                + "\x1b[0m"
+                        };
+                        out.push_str(&format!("   {}│{} {}", gray, reset, escaped));
+                        // Indent + Vertical bar
+                    }
 
-                if !code.ends_with('\n') {
-                    println!();
+                    if !code.ends_with('\n') {
+                        out.push('\n');
+                    }
                 }
-                println!(
-                    "   {}╰───────────────────────────────────────────────────╯{}",
+                out.push_str(&format!(
+                    "   {}╰───────────────────────────────────────────────────╯{}\n\n",
                     gray, reset
-                );
-                println!();
+                ));
             }
         }
+        out
+    }
 
-        println!(); // Spacing after response
+    /// Shows long output through `$PAGER` (`less -R` by default) when stdout
+    /// is a terminal, and prints it directly otherwise or in accessibility
+    /// mode.
+    fn page(text: &str) {
+        if io::stdout().is_terminal() && !Self::accessible() {
+            let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+            let (shell, flag) = if cfg!(windows) {
+                ("cmd", "/C")
+            } else {
+                ("sh", "-c")
+            };
+            let child = std::process::Command::new(shell)
+                .arg(flag)
+                .arg(&pager)
+                .stdin(std::process::Stdio::piped())
+                .spawn();
+            if let Ok(mut child) = child {
+                if let Some(mut stdin) = child.stdin.take() {
+                    // The pager may quit before reading everything; that is fine.
+                    let _ = stdin.write_all(text.as_bytes());
+                }
+                if child.wait().is_ok_and(|status| status.success()) {
+                    return;
+                }
+            }
+        }
+        print!("{}", text);
+        let _ = io::stdout().flush();
     }
 
     fn get_skin() -> MadSkin {
+        SKIN.get().cloned().unwrap_or_else(Self::default_skin)
+    }
+
+    /// Builds the skin with the styles of `[styles]` over the defaults.
+    /// One that cannot be read is reported and left at its default.
+    fn set_styles(styles: &StylesConfig) {
+        let mut skin = Self::default_skin();
+        let compound = |key: &str, value: &Option<String>| {
+            let value = value.as_deref()?;
+            termimad::parse_compound_style(value)
+                .map_err(|e| {
+                    Self::print_error(&format!("Invalid style for {} in [styles]: {}", key, e))
+                })
+                .ok()
+        };
+        let styled_char = |key: &str, value: &Option<String>, default: char| {
+            let value = value.as_deref()?;
+            termimad::parse_styled_char(value, default)
+                .map_err(|e| {
+                    Self::print_error(&format!("Invalid style for {} in [styles]: {}", key, e))
+                })
+                .ok()
+        };
+
+        if let Some(style) = compound("headers", &styles.headers) {
+            for header in &mut skin.headers {
+                header.compound_style = style.clone();
+            }
+        }
+        if let Some(style) = compound("bold", &styles.bold) {
+            skin.bold = style;
+        }
+        if let Some(style) = compound("italic", &styles.italic) {
+            skin.italic = style;
+        }
+        if let Some(style) = compound("inline_code", &styles.inline_code) {
+            skin.inline_code = style;
+        }
+        if let Some(style) = compound("code_block", &styles.code_block) {
+            skin.code_block.compound_style = style;
+        }
+        if let Some(quote) = styled_char("quote", &styles.quote, skin.quote_mark.nude_char()) {
+            skin.quote_mark = quote;
+        }
+        if let Some(bullet) = styled_char("bullet", &styles.bullet, skin.bullet.nude_char()) {
+            skin.bullet = bullet;
+        }
+        let _ = SKIN.set(skin);
+    }
+
+    fn default_skin() -> MadSkin {
         let mut skin = MadSkin::default();
 
         let orange = CrosstermColor::AnsiValue(208);
@@ -983,12 +1448,306 @@ impl UserInterface {
     }
 
     fn print_step(step: &str, color: Color) {
+        if Self::quiet() {
+            return;
+        }
         println!("{} {}...", "\n*".color(color), step.color(color));
     }
 
+    /// A finished tool call as a tree item under its step: the call, how
+    /// long it took, and the start of its output, with the number `/expand`
+    /// shows it by when there is more.
+    fn print_tool_run(number: usize, run: &ToolRun, elapsed: Duration, failed: bool) {
+        const SHOWN_LINES: usize = 3;
+        if Self::quiet() {
+            return;
+        }
+        let width = terminal::size()
+            .map(|(width, _)| width as usize)
+            .unwrap_or(80)
+            .saturating_sub(6)
+            .max(20);
+        let header = format!(
+            "{}({}) · {:.1}s",
+            run.name,
+            run.arguments,
+            elapsed.as_secs_f64()
+        );
+        if Self::accessible() {
+            println!("{} {}", locale::t("labels.tool"), header);
+            let lines = run.output.lines().filter(|l| !l.trim().is_empty());
+            for line in lines.take(SHOWN_LINES) {
+                println!("{}", line.trim_end());
+            }
+            return;
+        }
+        note!("{}", truncate(&header, width).dimmed());
+
+        let lines: Vec<&str> = run
+            .output
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+        for line in lines.iter().take(SHOWN_LINES) {
+            let shown = truncate(line.trim_end(), width);
+            if failed {
+                println!("     {}", shown.red());
+            } else if shown == line.trim_end() {
+                println!("     {}", console::linkify(&shown.dimmed().to_string()));
+            } else {
+                // A URL cut short would lead nowhere.
+                println!("     {}", shown.dimmed());
+            }
+        }
+        if lines.len() > SHOWN_LINES {
+            println!(
+                "     {}",
+                format!(
+                    "… {} more lines · /expand {}",
+                    lines.len() - SHOWN_LINES,
+                    number
+                )
+                .dimmed()
+                .italic()
+            );
+        }
+    }
+
+    /// One line on how sure the model was of its answer: the mean token
+    /// probability and the least likely token.
+    fn print_confidence(tokens: &[TokenLogprob]) {
+        let Some(least) = tokens.iter().min_by(|a, b| a.logprob.total_cmp(&b.logprob)) else {
+            note!("{}", "The provider sent no token probabilities\n".dimmed());
+            return;
+        };
+        let mean = tokens.iter().map(TokenLogprob::probability).sum::<f64>() / tokens.len() as f64;
+        note!(
+            "{}",
+            format!(
+                "Confidence {:.0}% on average, least sure of {:?} at {:.0}% · /logprobs\n",
+                mean * 100.0,
+                least.token,
+                least.probability() * 100.0
+            )
+            .dimmed()
+        );
+    }
+
+    /// One line on the tokens a turn took, and how many of the prompt's
+    /// came from the provider's cache.
+    fn print_usage(usage: &Usage) {
+        note!(
+            "{}",
+            format!(
+                "{} in · {} cached ({:.0}%) · {} out · /usage\n",
+                usage.prompt,
+                usage.cached,
+                usage.hit_rate() * 100.0,
+                usage.completion
+            )
+            .dimmed()
+        );
+    }
+
+    /// Prompts to send next, numbered: typing the number sends one.
+    fn print_follow_ups(follow_ups: &[String]) {
+        note!("{}", "Follow up with:".dimmed());
+        for (i, follow_up) in follow_ups.iter().enumerate() {
+            println!("     [{}] {}", i + 1, follow_up);
+        }
+        println!();
+    }
+
+    /// The last answer token by token, colored by how sure the model was
+    /// of each: green above 90%, yellow above 50%, red below. Without
+    /// colors, tokens under 90% are followed by their probability. The
+    /// unsure tokens are then listed with what the model could have written
+    /// instead.
+    fn print_logprobs(tokens: &[TokenLogprob]) {
+        let mut text = String::new();
+        for token in tokens {
+            let probability = token.probability();
+            if Self::plain() {
+                text.push_str(&token.token);
+                if probability < 0.9 {
+                    text.push_str(&format!("[{:.0}%]", probability * 100.0));
+                }
+                continue;
+            }
+            let color = match probability {
+                p if p >= 0.9 => Color::Green,
+                p if p >= 0.5 => Color::Yellow,
+                _ => Color::Red,
+            };
+            text.push_str(&token.token.color(color).to_string());
+        }
+        println!("\n{}\n", text.trim_end());
+
+        let mut unsure: Vec<&TokenLogprob> =
+            tokens.iter().filter(|t| t.probability() < 0.5).collect();
+        unsure.sort_by(|a, b| a.logprob.total_cmp(&b.logprob));
+        for token in unsure.iter().take(10) {
+            let others: Vec<String> = token
+                .top_logprobs
+                .iter()
+                .filter(|other| other.token != token.token)
+                .map(|other| format!("{:?} {:.0}%", other.token, other.logprob.exp() * 100.0))
+                .collect();
+            note!(
+                "{:?} {:.0}%{}",
+                token.token,
+                token.probability() * 100.0,
+                if others.is_empty() {
+                    String::new()
+                } else {
+                    format!(" · instead {}", others.join(", "))
+                        .dimmed()
+                        .to_string()
+                }
+            );
+        }
+        if !unsure.is_empty() {
+            println!();
+        }
+    }
+
+    /// A reasoning model's thinking, dimmed: in full, or folded into one
+    /// line that says how to read it.
+    fn print_reasoning(reasoning: &str, expanded: bool) {
+        if Self::quiet() {
+            return;
+        }
+        if !expanded {
+            let words = reasoning.split_whitespace().count();
+            note!(
+                "{}",
+                format!("Thought for {} words · /thinking", words)
+                    .dimmed()
+                    .italic()
+            );
+            return;
+        }
+        note!("{}", "Thinking".dimmed().italic());
+        for line in reasoning.trim().lines() {
+            println!("     {}", line.dimmed());
+        }
+    }
+
+    fn print_hook_output(output: &[String]) {
+        if Self::quiet() {
+            return;
+        }
+        for block in output {
+            let mut lines = block.lines();
+            if let Some(first) = lines.next() {
+                note!("{}", first);
+            }
+            for line in lines {
+                println!("     {}", line);
+            }
+        }
+        if !output.is_empty() {
+            println!();
+        }
+    }
+
     fn print_error(error: &str) {
         eprintln!("\nError: {}", error);
     }
+
+    /// Shows what the engine reports while it works: warnings on a line of
+    /// their own, clear of any status line being drawn, and debug output
+    /// and plugin logs dimmed.
+    fn print_notice(notice: Notice<'_>) {
+        match notice {
+            Notice::Warning(message) => eprintln!("\r\x1b[2K{}", message.yellow()),
+            Notice::PluginLog { plugin, line } => eprintln!("[{}] {}", plugin, line),
+            Notice::Debug { label, body } => Self::print_debug(label, body),
+        }
+    }
+
+    /// Prints a labelled block of debug output to stderr in dim gray.
+    fn print_debug(label: &str, body: &str) {
+        let gray = Color::TrueColor {
+            r: 100,
+            g: 100,
+            b: 100,
+        };
+        eprintln!("\n{} {}", "[debug]".color(gray).bold(), label.color(gray));
+        for line in body.lines() {
+            eprintln!("  {}", line.color(gray));
+        }
+    }
+
+    /// The banner shown when the API can't be reached, in place of the
+    /// error the request failed with.
+    fn print_offline(host: &str) {
+        eprintln!(
+            "\n{} {}\n",
+            format!(" Offline: {} can't be reached ", host)
+                .black()
+                .on_yellow()
+                .bold(),
+            "Prompts are queued until the connection is back, and web tools are off meanwhile."
+                .yellow()
+        );
+    }
+}
+
+/// Why a one-shot or piped run failed, told to scripts by the exit code.
+/// Interactive sessions always exit with 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// Anything not listed below.
+    Other = 1,
+    /// The command line made no sense, as with `-q` and no prompt.
+    Usage = 2,
+    /// The API key was refused.
+    Auth = 3,
+    /// Requests were still rate-limited after retrying.
+    RateLimit = 4,
+    /// The API could not be reached.
+    Network = 5,
+    /// A tool call was refused, so the answer was made without it.
+    ToolDenied = 6,
+}
+
+impl Failure {
+    /// The kind of failure, as counted by telemetry.
+    fn name(self) -> &'static str {
+        match self {
+            Failure::Other => "other",
+            Failure::Usage => "usage",
+            Failure::Auth => "auth",
+            Failure::RateLimit => "rate_limit",
+            Failure::Network => "network",
+            Failure::ToolDenied => "tool_denied",
+        }
+    }
+
+    fn of(error: &ProviderError) -> Self {
+        match error {
+            ProviderError::Auth(_) => Failure::Auth,
+            ProviderError::RateLimited(_) => Failure::RateLimit,
+            ProviderError::Network(_) => Failure::Network,
+            ProviderError::Api(_) => Failure::Other,
+        }
+    }
+
+    /// Ends the process with this failure's code.
+    fn exit(self) -> ! {
+        let _ = io::stdout().flush();
+        std::process::exit(self as i32)
+    }
+}
+
+/// A finished tool call, kept so `/expand` can show all of its output.
+struct ToolRun {
+    name: String,
+    /// The arguments, summarized for one line.
+    arguments: String,
+    /// The output, or the error when the call failed.
+    output: String,
 }
 
 // Command Handler
@@ -996,180 +1755,4778 @@ impl UserInterface {
 enum Command {
     Quit,
     Stream,
+    Debug,
+    /// Show the request each prompt would send instead of sending it.
+    DryRun,
+    /// Show whether secrets are masked in messages, or turn masking on or
+    /// off.
+    Redact(String),
     Clear,
-    Model,
+    /// Switch to the model named, or choose one from the list when empty.
+    Model(String),
     Help,
+    Prompts(Option<String>),
+    Use(String),
+    System(Option<String>),
+    /// List the personas, or switch to one, or with `off` back to none.
+    Persona(String),
+    /// Revise and resend the last prompt; `true` opens it in `$EDITOR`.
+    Edit(bool),
+    /// Write a prompt in `$EDITOR`, starting from the text given, and send it.
+    Editor(String),
+    /// Name the conversation as it is now, or list the names given.
+    Checkpoint(String),
+    /// Go back to a checkpoint, dropping the messages after it.
+    Rewind(String),
+    Save(Option<String>),
+    Sessions(String),
+    /// List the conversations of this run, or start, switch to or close one.
+    Session(String),
+    Resume(String),
+    Tag(String),
+    Search(String),
+    /// Publish the conversation as a gist; `true` makes it public.
+    Share(bool),
+    Export(String),
+    /// Re-render earlier turns, or only the given one.
+    History(Option<usize>),
+    /// List the messages as kept, or show one in full.
+    Messages(String),
+    /// Write the messages of the next request as JSON, to stdout or a file.
+    Dump(Option<String>),
+    Memory(String),
+    /// Show the document index, or rebuild it from the given directory.
+    Index(Option<String>),
+    /// Read a site from the given page and index it.
+    Crawl(String),
+    /// Translate the text after the language, or with no text, every
+    /// message until `off`.
+    Translate(String),
+    /// Send every prompt to another model as well and keep the answer
+    /// picked, until `off`.
+    Compare(String),
+    /// Send the clipboard with the given text, or with the next message.
+    Paste(String),
+    /// Copy the last answer; `true` copies only its code blocks.
+    Copy(bool),
+    /// Show the last answer as raw Markdown; `true` opens it in `$EDITOR`.
+    Last(bool),
+    /// Open the source the last answer cites under this number.
+    Open(usize),
+    /// Draw a diagram of the last answer.
+    Diagram(String),
+    /// Show the full output of a tool call, the latest by default.
+    Expand(Option<usize>),
+    /// Show the last answer's reasoning, or with `Some`, whether reasoning
+    /// is shown in full from now on.
+    Thinking(Option<bool>),
+    Json(String),
+    Set(String),
+    Get(String),
+    /// List the tools or how they have done, or switch one on or off for
+    /// this session.
+    Tools(String),
+    /// Show how sure the model was of the last answer, or with `Some`,
+    /// whether to ask for it.
+    Logprobs(Option<bool>),
+    /// Show the tokens used since Rusty started, or with `Some`, whether to
+    /// follow each answer with what it took.
+    Usage(Option<bool>),
+    /// Show the follow-ups offered last, or with `Some`, whether to offer
+    /// them after each answer.
+    FollowUps(Option<bool>),
+    /// Rate the last answer good (`true`) or bad, with an optional note.
+    Rate(bool, String),
+    /// Show whether anonymous usage statistics are collected and what was
+    /// counted, or turn collecting on or off.
+    Telemetry(String),
+    /// Send a message, making the model search the web before answering.
+    SearchFirst(String),
+    /// Add a directory to the workspace, or list the workspace when empty.
+    AddDir(String),
     Message(String),
 }
 
-struct CommandHandler;
+struct CommandHandler {
+    aliases: BTreeMap<String, Alias>,
+}
 
 impl CommandHandler {
+    fn new(aliases: BTreeMap<String, Alias>) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let aliases = aliases
+            .into_iter()
+            .map(|(name, alias)| (name.trim_start_matches('/').to_string(), alias))
+            .filter(|(name, _)| {
+                let builtin = Self::is_builtin(name);
+                if builtin {
+                    warnings.push(format!(
+                        "Alias /{} shadows a built-in command; ignoring it.",
+                        name
+                    ));
+                }
+                !builtin
+            })
+            .collect();
+        (Self { aliases }, warnings)
+    }
+
+    /// Expands a user-defined alias into the lines it stands for. Anything
+    /// else, including built-ins, comes back unchanged as a single line.
+    fn expand(&self, input: &str) -> Vec<String> {
+        let (head, args) = match input.split_once(char::is_whitespace) {
+            Some((head, args)) => (head, args.trim()),
+            None => (input, ""),
+        };
+        let alias = head
+            .strip_prefix('/')
+            .and_then(|name| self.aliases.get(name));
+
+        match alias {
+            Some(alias) => alias.expand(args),
+            None => vec![input.to_string()],
+        }
+    }
+
+    /// Built-ins cannot be shadowed. `/use` only parses with arguments, so it is listed explicitly.
+    fn is_builtin(name: &str) -> bool {
+        let command = format!("/{}", name);
+        COMMANDS
+            .iter()
+            .any(|(usage, _)| usage.split(' ').next() == Some(command.as_str()))
+            || !matches!(Self::parse(&command), Command::Message(_))
+    }
+
     fn parse(input: &str) -> Command {
-        match input {
-            "/quit" | "/exit" => Command::Quit,
-            "/stream" => Command::Stream,
-            "/clear" => Command::Clear,
-            "/model" => Command::Model,
-            "/help" | "/" | "?" => Command::Help,
+        let (head, args) = match input.split_once(char::is_whitespace) {
+            Some((head, args)) => (head, args.trim()),
+            None => (input, ""),
+        };
+
+        match (head, args) {
+            ("/quit" | "/exit", "") => Command::Quit,
+            ("/stream", "") => Command::Stream,
+            ("/debug", "") => Command::Debug,
+            ("/dryrun", "") => Command::DryRun,
+            ("/redact", arg) => Command::Redact(arg.to_string()),
+            ("/clear", "") => Command::Clear,
+            ("/model", choice) => Command::Model(choice.to_string()),
+            ("/help" | "/" | "?", "") => Command::Help,
+            ("/prompts", "") => Command::Prompts(None),
+            ("/prompts", name) => Command::Prompts(Some(name.to_string())),
+            ("/use", args) if !args.is_empty() => Command::Use(args.to_string()),
+            ("/system", "") => Command::System(None),
+            ("/system", prompt) => Command::System(Some(prompt.to_string())),
+            ("/persona", name) => Command::Persona(name.to_string()),
+            ("/edit", "") => Command::Edit(false),
+            ("/edit", "-e" | "--editor") => Command::Edit(true),
+            ("/editor", text) => Command::Editor(text.to_string()),
+            ("/save", "") => Command::Save(None),
+            ("/save", title) => Command::Save(Some(title.to_string())),
+            ("/sessions", filter) => Command::Sessions(filter.to_string()),
+            ("/session", args) => Command::Session(args.to_string()),
+            ("/tag", tags) => Command::Tag(tags.to_string()),
+            ("/search", query) if !query.is_empty() => Command::Search(query.to_string()),
+            ("/share", "") => Command::Share(false),
+            ("/share", "--public") => Command::Share(true),
+            ("/export", args) => Command::Export(args.to_string()),
+            ("/history", "") => Command::History(None),
+            ("/history", turn) if turn.parse::<usize>().is_ok() => {
+                Command::History(turn.parse().ok())
+            }
+            ("/messages", args) => Command::Messages(args.to_string()),
+            ("/dump", "") => Command::Dump(None),
+            ("/dump", path) => Command::Dump(Some(path.to_string())),
+            ("/memory", args) => Command::Memory(args.to_string()),
+            ("/index", "") => Command::Index(None),
+            ("/index", dir) => Command::Index(Some(dir.to_string())),
+            ("/crawl", args) => Command::Crawl(args.to_string()),
+            ("/translate", args) => Command::Translate(args.to_string()),
+            ("/compare", args) => Command::Compare(args.to_string()),
+            ("/paste", text) => Command::Paste(text.to_string()),
+            ("/copy", "") => Command::Copy(false),
+            ("/copy", "code") => Command::Copy(true),
+            ("/last", "") => Command::Last(false),
+            ("/last", "edit") => Command::Last(true),
+            ("/diagram", args) => Command::Diagram(args.to_string()),
+            ("/open", n) if n.parse::<usize>().is_ok_and(|n| n > 0) => {
+                Command::Open(n.parse().unwrap_or(1))
+            }
+            ("/expand", "") => Command::Expand(None),
+            ("/expand", n) if n.parse::<usize>().is_ok() => Command::Expand(n.parse().ok()),
+            ("/thinking", "") => Command::Thinking(None),
+            ("/thinking", "show") => Command::Thinking(Some(true)),
+            ("/thinking", "hide") => Command::Thinking(Some(false)),
+            ("/json", args) => Command::Json(args.to_string()),
+            ("/set", args) => Command::Set(args.to_string()),
+            ("/get", key) => Command::Get(key.to_string()),
+            ("/tools", args) => Command::Tools(args.to_string()),
+            ("/logprobs", "") => Command::Logprobs(None),
+            ("/logprobs", "on") => Command::Logprobs(Some(true)),
+            ("/logprobs", "off") => Command::Logprobs(Some(false)),
+            ("/usage", "") => Command::Usage(None),
+            ("/usage", "on") => Command::Usage(Some(true)),
+            ("/usage", "off") => Command::Usage(Some(false)),
+            ("/followups", "") => Command::FollowUps(None),
+            ("/followups", "on") => Command::FollowUps(Some(true)),
+            ("/followups", "off") => Command::FollowUps(Some(false)),
+            ("/good", note) => Command::Rate(true, note.to_string()),
+            ("/bad", note) => Command::Rate(false, note.to_string()),
+            ("/telemetry", arg) => Command::Telemetry(arg.to_string()),
+            ("/search-first", text) if !text.is_empty() => Command::SearchFirst(text.to_string()),
+            ("/resume", session) if !session.is_empty() => Command::Resume(session.to_string()),
+            ("/add-dir", path) => Command::AddDir(path.to_string()),
+            ("/checkpoint", name) => Command::Checkpoint(name.to_string()),
+            ("/rewind", name) => Command::Rewind(name.to_string()),
             _ => Command::Message(input.to_string()),
         }
     }
 }
 
-// Chat Application
+/// `text` cut to `width` characters, with an ellipsis when it was longer.
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut cut = fit_width(text, width.saturating_sub(1));
+    cut.push('…');
+    cut
+}
 
-struct ChatApplication {
-    api_client: GroqApiClient,
-    brave_client: BraveSearchClient,
-    model_manager: ModelManager,
-    conversation_manager: ConversationManager,
-    reader: tokio::io::BufReader<tokio::io::Stdin>,
+/// Piped `input` cut down to `MAX_PIPED_CHARS` by leaving out its middle,
+/// at line breaks where there are any, with a line saying so in its place,
+/// and a note on what was left out.
+fn trim_piped(input: &str) -> (String, Option<String>) {
+    let total = input.chars().count();
+    if total <= MAX_PIPED_CHARS {
+        return (input.to_string(), None);
+    }
+    let half = MAX_PIPED_CHARS / 2;
+    let byte = |chars: usize| {
+        input
+            .char_indices()
+            .nth(chars)
+            .map_or(input.len(), |(i, _)| i)
+    };
+    let (head, tail) = (byte(half), byte(total - half));
+    let head = input[..head].rfind('\n').unwrap_or(head);
+    let tail = input[tail..].find('\n').map_or(tail, |i| tail + i + 1);
+    let left_out = &input[head..tail];
+    let what = format!(
+        "{} lines ({} characters)",
+        left_out.lines().count(),
+        left_out.chars().count()
+    );
+    let note = format!(
+        "the input was {} characters; {} in the middle were left out",
+        total, what
+    );
+    let text = format!(
+        "{}\n[… {} left out …]\n{}",
+        &input[..head],
+        what,
+        &input[tail..]
+    );
+    (text, Some(note))
 }
 
-impl ChatApplication {
-    fn new(groq_key: String, brave_key: String) -> Self {
-        Self {
-            api_client: GroqApiClient::new(groq_key),
-            brave_client: BraveSearchClient::new(brave_key),
-            model_manager: ModelManager::new(),
-            conversation_manager: ConversationManager::new(),
-            reader: tokio::io::BufReader::new(tokio::io::stdin()),
-        }
+/// What Ctrl+R finds for `query`, case-insensitively: earlier prompts
+/// containing it, the newest first, to edit again, then saved sessions
+/// whose title or messages do, with where, to resume.
+fn history_matches(
+    query: &str,
+    history: &[String],
+    sessions: &[sessions::Session],
+) -> Vec<PaletteItem> {
+    let needle = query.to_lowercase();
+    let mut seen = HashSet::new();
+    let mut items: Vec<PaletteItem> = history
+        .iter()
+        .rev()
+        .filter(|line| line.to_lowercase().contains(&needle) && seen.insert(line.as_str()))
+        .map(|line| PaletteItem::new(line, "prompt", PaletteAction::Insert(line.clone())))
+        .collect();
+    for session in sessions {
+        let Some(snippet) = session.search(query) else {
+            continue;
+        };
+        let title = session.display_title();
+        let detail = if snippet == title {
+            format!("session · {}", sessions::time_ago(session.updated_at))
+        } else {
+            format!("session · {}", snippet.replace('\n', " "))
+        };
+        items.push(PaletteItem::new(
+            &title,
+            &detail,
+            PaletteAction::Submit(format!("/resume {}", session.id)),
+        ));
     }
+    items
+}
 
-    async fn initialize(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
-        UserInterface::print_welcome(self.model_manager.get_current_model());
-        ModelManager::list_models();
-        if !self
-            .model_manager
-            .select_model_interactive(&mut self.reader)
-            .await?
-        {
-            return Ok(false);
-        }
+/// The files of the workspace that `query` matches, best first, for the
+/// `@` finder.
+fn file_matches(query: &str, files: &[String]) -> Vec<PaletteItem> {
+    let mut scored: Vec<(i64, &String)> = files
+        .iter()
+        .filter_map(|path| palette::fuzzy_score(query, path).map(|score| (score, path)))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored
+        .into_iter()
+        .take(FILE_MATCHES)
+        .map(|(_, path)| PaletteItem::new(path, "", PaletteAction::Insert(path.clone())))
+        .collect()
+}
 
-        let orange = Color::TrueColor {
-            r: 255,
-            g: 140,
-            b: 0,
-        };
-        println!(
-            "\n{} {}\n",
-            "Active Model:".color(orange).bold(),
-            self.model_manager.get_current_model().white()
-        );
-        UserInterface::print_instructions();
+/// The longest start of `text` that takes at most `width` terminal
+/// columns, counting wide glyphs such as CJK and emoji as two.
+fn fit_width(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.chars()
+        .take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= width
+        })
+        .collect()
+}
 
-        Ok(true)
+/// Splits `text` into lines of at most `width` columns, breaking at
+/// spaces where it can and keeping its own line breaks.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.to_string();
+            // Words longer than a line are cut wherever the line ends.
+            while word.width() > width {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                // At least one character, in case it is wider than a line.
+                let cut = fit_width(&word, width)
+                    .len()
+                    .max(word.chars().next().map_or(0, char::len_utf8));
+                lines.push(word[..cut].to_string());
+                word.drain(..cut);
+            }
+            if !line.is_empty() && line.width() + 1 + word.width() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        lines.push(line);
     }
+    lines
+}
 
-    async fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if !self.initialize().await? {
-            return Ok(());
-        }
+/// `rusty mcp-serve`: offers the tools to an MCP client over stdin and
+/// stdout. Nothing is asked for, since stdin carries the protocol: without
+/// a Brave key, searches fail, and encrypted memory needs
+/// `RUSTY_PASSPHRASE`. Warnings go to stderr, which clients log.
+async fn serve_mcp(config: &Config, read_only: bool, embedder: Embedder) -> Result<(), String> {
+    let mut brave_client =
+        BraveSearchClient::new(ConfigManager::load_key("BRAVE_API_KEY").unwrap_or_default());
+    brave_client.set_location(config.search.location());
+
+    let trusted = unattended_trust(config);
+    let (plugins, warnings) =
+        PluginHost::load(&paths::plugins_dir(), tools::BUILTIN_TOOL_NAMES, trusted).await;
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
 
-        // Initial hint
-        // Initial hint handled by print_prompt
+    let cipher = if config.storage.encrypt {
+        Some(ConfigManager::unlock_storage()?)
+    } else {
+        None
+    };
+    let (memory, warnings) = MemoryStore::load(&paths::memory_file(), cipher);
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
 
-        loop {
-            UserInterface::print_prompt();
+    let documents = rag::open_store(&config.index)
+        .and_then(|store| Retriever::open(store, embedder))
+        .unwrap_or_else(|e| {
+            UserInterface::print_error(&e);
+            None
+        });
+
+    let mut server = McpServer::new(
+        brave_client,
+        plugins,
+        memory,
+        documents,
+        Ocr::from_config(&config.ocr),
+        Allowlist::new(&config.http.allow),
+        config.clipboard.tool,
+    );
+    server.set_fetcher(Fetcher::from_config(
+        &config.fetch,
+        &Egress::new(&config.http.egress),
+    ));
+    server.set_egress(Egress::new(&config.http.egress));
+    server.set_read_only(read_only || config.trust.read_only);
+    server.set_trusted(trusted);
+    server
+        .serve(
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+        )
+        .await
+}
 
-            let input = self.read_user_input().await?;
-            UserInterface::print_prompt_closure();
+/// Whether the workspace is trusted, for runs where nobody can be asked,
+/// such as the bots and `rusty mcp-serve`: one never asked about is not.
+fn unattended_trust(config: &Config) -> bool {
+    repo::top_level()
+        .or_else(|| std::env::current_dir().ok())
+        .and_then(|workspace| config.trust.decision(&workspace))
+        .unwrap_or(false)
+}
 
-            if input.is_empty() {
-                continue;
-            }
+/// `rusty import <file>`: the conversations of a ChatGPT or Claude export,
+/// saved as sessions.
+fn import_sessions(config: &Config, path: &str) -> Result<(), String> {
+    if path.is_empty() {
+        return Err("Usage: rusty import <conversations.json>".to_string());
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let import = rusty_core::import::read_export(&text)?;
+    let cipher = if config.storage.encrypt {
+        Some(ConfigManager::unlock_storage()?)
+    } else {
+        None
+    };
+    let store = SessionStore::new(&paths::sessions_dir(), cipher);
+    for session in &import.sessions {
+        store.write(session)?;
+    }
+    println!(
+        "Imported {} conversations from {} into {}",
+        import.sessions.len(),
+        import.source.name(),
+        paths::sessions_dir().display()
+    );
+    if import.skipped > 0 {
+        println!("Skipped {} without any text", import.skipped);
+    }
+    println!(
+        "Find them with /sessions #{} or /search, and continue one with /resume",
+        import.source.tag()
+    );
+    Ok(())
+}
 
-            let command = CommandHandler::parse(&input);
-            if !self.handle_command(command, &input).await? {
-                break;
-            }
+/// `rusty sessions [list [#tag...] | show <session> | delete <session>]`:
+/// the saved sessions, listed as `/sessions` lists them, one printed as
+/// Markdown, or one deleted.
+fn sessions_command(config: &Config, action: &Option<SessionsAction>) -> Result<(), String> {
+    let cipher = if config.storage.encrypt {
+        Some(ConfigManager::unlock_storage()?)
+    } else {
+        None
+    };
+    let store = SessionStore::new(&paths::sessions_dir(), cipher);
+    let saved = store.list();
+    match action {
+        None => list_sessions(&saved, &[]),
+        Some(SessionsAction::List { tags }) => list_sessions(&saved, tags),
+        Some(SessionsAction::Show { session }) => {
+            let session = find_session(saved, session)
+                .ok_or_else(|| format!("No session '{}'. See rusty sessions list", session))?;
+            print!("{}", export::to_markdown(&session));
+        }
+        Some(SessionsAction::Delete { session }) => {
+            let session = find_session(saved, session)
+                .ok_or_else(|| format!("No session '{}'. See rusty sessions list", session))?;
+            store.delete(&session.id)?;
+            println!("Deleted \"{}\"", session.display_title());
         }
-
-        Ok(())
     }
+    Ok(())
+}
 
-    async fn read_user_input(&mut self) -> Result<String, Box<dyn std::error::Error>> {
-        let mut line = String::new();
-        self.reader.read_line(&mut line).await?;
-        Ok(line.trim().to_string())
+/// Prints the sessions with every tag in `tags`, numbered as in the full
+/// list so the numbers can be given to `show`, `delete` and `/resume`.
+fn list_sessions(saved: &[Session], tags: &[String]) {
+    if !saved.iter().any(|session| session.has_tags(tags)) {
+        eprintln!("No saved sessions");
+        return;
+    }
+    for (i, session) in saved.iter().enumerate() {
+        if !session.has_tags(tags) {
+            continue;
+        }
+        let tag_list: String = session.tags.iter().map(|t| format!(" #{}", t)).collect();
+        let title: String = session.display_title().chars().take(50).collect();
+        println!(
+            "{:>3}  {:<14} {:<50}  {}{}",
+            i + 1,
+            session.id,
+            title,
+            format!(
+                "{} · {} prompt{}",
+                sessions::time_ago(session.updated_at),
+                session.turns(),
+                if session.turns() == 1 { "" } else { "s" }
+            )
+            .dimmed(),
+            tag_list.cyan()
+        );
+    }
+}
+
+/// The session numbered `target` in `saved`, counting from 1, or the one
+/// whose id it is.
+fn find_session(saved: Vec<Session>, target: &str) -> Option<Session> {
+    match target.parse::<usize>() {
+        Ok(n) if n >= 1 => saved.into_iter().nth(n - 1),
+        _ => saved.into_iter().find(|s| s.id == target),
+    }
+}
+
+/// `rusty config get [key] | set <key> <value> | path`: the configuration
+/// file, read and changed from scripts without an editor.
+fn config_command(action: &ConfigAction) -> Result<(), String> {
+    let path = Config::path();
+    match action {
+        ConfigAction::Get { key: None } => match fs::read_to_string(&path) {
+            Ok(text) => print!("{}", text),
+            Err(_) => eprintln!("{} does not exist; the defaults apply", path.display()),
+        },
+        ConfigAction::Get { key: Some(key) } => match Config::get_key(key)? {
+            Some(value) => println!("{}", value),
+            None => eprintln!("{} is not set; the default applies", key),
+        },
+        ConfigAction::Set { key, value } => {
+            Config::set_key(key, value)?;
+            println!("Set {} in {}", key, path.display());
+        }
+        ConfigAction::Path => println!("{}", path.display()),
+    }
+    Ok(())
+}
+
+/// `rusty export-jsonl [#tag...] [--flatten-tools] [file]`: the saved
+/// sessions with every given tag, or all of them, as chat JSONL for
+/// fine-tuning or evaluation datasets, written to `file` or stdout.
+fn export_jsonl(config: &Config, args: &[String]) -> Result<(), String> {
+    let mut tags = Vec::new();
+    let mut flatten_tools = false;
+    let mut output = None;
+    for arg in args {
+        match arg.as_str() {
+            "--flatten-tools" => flatten_tools = true,
+            tag if tag.starts_with('#') => tags.push(tag.to_string()),
+            flag if flag.starts_with('-') => {
+                return Err(format!(
+                "Unknown option {}. Usage: rusty export-jsonl [#tag...] [--flatten-tools] [file]",
+                flag
+            ))
+            }
+            path => output = Some(path),
+        }
+    }
+    let cipher = if config.storage.encrypt {
+        Some(ConfigManager::unlock_storage()?)
+    } else {
+        None
+    };
+    let mut sessions: Vec<Session> = SessionStore::new(&paths::sessions_dir(), cipher)
+        .list()
+        .into_iter()
+        .filter(|session| session.has_tags(&tags))
+        .collect();
+    // Oldest first, as they happened.
+    sessions.reverse();
+    let jsonl = export::to_jsonl(&sessions, flatten_tools);
+    let lines = jsonl.lines().count();
+    match output {
+        Some(path) => {
+            fs::write(path, &jsonl).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            println!("Exported {} sessions to {}", lines, path);
+        }
+        None => print!("{}", jsonl),
+    }
+    Ok(())
+}
+
+/// `rusty replay <session> [--speed n]`: shows a saved conversation again
+/// as if it were happening, prompts typed and answers streamed, without
+/// sending anything. `<session>` is a session file or the id of a saved
+/// session.
+async fn replay(config: &Config, args: &[String]) -> Result<(), String> {
+    let usage = "Usage: rusty replay <session file or id> [--speed n]";
+    let mut source = None;
+    let mut speed = 1.0;
+    let mut words = args.iter();
+    while let Some(arg) = words.next() {
+        match arg.as_str() {
+            "--speed" => {
+                speed = words
+                    .next()
+                    .and_then(|n| n.parse::<f64>().ok())
+                    .filter(|n| *n > 0.0)
+                    .ok_or_else(|| format!("--speed takes a number above 0. {}", usage))?;
+            }
+            flag if flag.starts_with('-') => {
+                return Err(format!("Unknown option {}. {}", flag, usage))
+            }
+            arg => source = Some(arg),
+        }
+    }
+    let source = source.ok_or_else(|| usage.to_string())?;
+    let cipher = if config.storage.encrypt {
+        Some(ConfigManager::unlock_storage()?)
+    } else {
+        None
+    };
+    let session: Session = if Path::new(source).is_file() {
+        let text = encryption::read_text(Path::new(source), cipher.as_ref())?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid session {}: {}", source, e))?
+    } else {
+        SessionStore::new(&paths::sessions_dir(), cipher).load(source)?
+    };
+
+    let pause =
+        |base: u64| tokio::time::sleep(Duration::from_secs_f64(base as f64 / 1000.0 / speed));
+    let theme = config.chat.theme.as_deref();
+    note!(
+        "Replaying \"{}\" · {}\n",
+        session.display_title(),
+        session.model
+    );
+    let mut number = 0;
+    for (i, message) in session.messages.iter().enumerate() {
+        match message.role.as_str() {
+            "user" => {
+                let Some(prompt) = message.content.as_deref() else {
+                    continue;
+                };
+                UserInterface::print_prompt();
+                for c in prompt.chars() {
+                    print!("{}", c);
+                    let _ = io::stdout().flush();
+                    pause(REPLAY_TYPING_MS).await;
+                }
+                println!();
+                UserInterface::print_prompt_closure();
+                let animate = io::stdout().is_terminal() && !UserInterface::plain();
+                let mut spinner = UserInterface::print_thinking(animate);
+                pause(REPLAY_THINKING_MS).await;
+                spinner.pause();
+            }
+            "assistant" => {
+                for call in message.tool_calls.iter().flatten() {
+                    let output = session.messages[i + 1..]
+                        .iter()
+                        .find(|m| m.tool_call_id.as_deref() == Some(call.id.as_str()))
+                        .and_then(|m| m.content.clone())
+                        .unwrap_or_default();
+                    number += 1;
+                    pause(REPLAY_THINKING_MS).await;
+                    let run = ToolRun {
+                        name: call.function.name.clone(),
+                        arguments: summarize_arguments(&call.function.arguments),
+                        output,
+                    };
+                    UserInterface::print_tool_run(
+                        number,
+                        &run,
+                        Duration::from_secs_f64(REPLAY_THINKING_MS as f64 / 1000.0 / speed),
+                        false,
+                    );
+                }
+                let Some(answer) = message.content.as_deref().filter(|a| !a.trim().is_empty())
+                else {
+                    continue;
+                };
+                let shown = if UserInterface::plain() {
+                    format!("{}{}", UserInterface::assistant_label(), answer.trim_end())
+                } else {
+                    format!("● {}", UserInterface::render_markdown(answer, theme))
+                };
+                println!();
+                // Word by word, so escape sequences are never cut.
+                for word in shown.split_inclusive([' ', '\n']) {
+                    print!("{}", word);
+                    let _ = io::stdout().flush();
+                    pause(REPLAY_STREAMING_MS).await;
+                }
+                println!();
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `rusty usage [--csv] [file]`: the tokens and dollars spent each day on
+/// each model, as a table, or as CSV for expense tracking.
+fn export_usage(args: &[String]) -> Result<(), String> {
+    let mut csv = false;
+    let mut output = None;
+    for arg in args {
+        match arg.as_str() {
+            "--csv" => csv = true,
+            flag if flag.starts_with('-') => {
+                return Err(format!(
+                    "Unknown option {}. Usage: rusty usage [--csv] [file]",
+                    flag
+                ))
+            }
+            path => output = Some(path),
+        }
+    }
+    let days = Ledger::open(&paths::usage_db())?.days()?;
+    let text = if csv {
+        spending::to_csv(&days)
+    } else if days.is_empty() {
+        "No usage recorded yet\n".to_string()
+    } else {
+        format!("{}\n", usage_table(&days))
+    };
+    match output {
+        Some(path) => {
+            fs::write(path, &text).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            println!("Exported {} days of usage to {}", days.len(), path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// `rusty feedback [--csv] [file]`: how often each model's answers were
+/// rated good and bad, or every rating as CSV.
+fn export_feedback(args: &[String]) -> Result<(), String> {
+    let mut csv = false;
+    let mut output = None;
+    for arg in args {
+        match arg.as_str() {
+            "--csv" => csv = true,
+            flag if flag.starts_with('-') => {
+                return Err(format!(
+                    "Unknown option {}. Usage: rusty feedback [--csv] [file]",
+                    flag
+                ))
+            }
+            path => output = Some(path),
+        }
+    }
+    let ratings = FeedbackLog::open(&paths::feedback_db())?.all()?;
+    let text = if csv {
+        feedback::to_csv(&ratings)
+    } else if ratings.is_empty() {
+        "No answers rated yet; rate one with /good or /bad\n".to_string()
+    } else {
+        let mut models: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for rating in &ratings {
+            let counts = models.entry(&rating.model).or_default();
+            if rating.good {
+                counts.0 += 1;
+            } else {
+                counts.1 += 1;
+            }
+        }
+        let width = models.keys().map(|model| model.len()).max().unwrap_or(0);
+        let mut lines = vec![format!(
+            "  {:<width$}  {:>5}  {:>5}",
+            "Model", "Good", "Bad"
+        )];
+        for (model, (good, bad)) in models {
+            lines.push(format!("  {:<width$}  {:>5}  {:>5}", model, good, bad));
+        }
+        format!("{}\n", lines.join("\n"))
+    };
+    match output {
+        Some(path) => {
+            fs::write(path, &text).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            println!("Exported {} ratings to {}", ratings.len(), path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// `days` as a table, a row per day and model, with the totals under it.
+fn usage_table(days: &[DayUsage]) -> String {
+    let model_width = days.iter().map(|day| day.model.len()).max().unwrap_or(0);
+    let row =
+        |date: &str, model: &str, requests: String, input: String, output: String, cost: String| {
+            format!(
+                "  {:<10}  {:<model_width$}  {:>8}  {:>12}  {:>12}  {:>9}",
+                date, model, requests, input, output, cost
+            )
+        };
+    let mut lines = vec![row(
+        "Date",
+        "Model",
+        "Requests".to_string(),
+        "Tokens in".to_string(),
+        "Tokens out".to_string(),
+        "Cost".to_string(),
+    )
+    .bold()
+    .to_string()];
+    let mut total = (0, Usage::default(), 0.0);
+    for day in days {
+        lines.push(row(
+            &day.day,
+            &day.model,
+            day.requests.to_string(),
+            day.usage.prompt.to_string(),
+            day.usage.completion.to_string(),
+            format!("${:.4}", day.cost),
+        ));
+        total.0 += day.requests;
+        total.1.add(&day.usage);
+        total.2 += day.cost;
+    }
+    lines.push(
+        row(
+            "Total",
+            "",
+            total.0.to_string(),
+            total.1.prompt.to_string(),
+            total.1.completion.to_string(),
+            format!("${:.4}", total.2),
+        )
+        .bold()
+        .to_string(),
+    );
+    lines.join("\n")
+}
+
+/// The calls of each tool, the one that took longest in all first, as
+/// `/tools stats` shows them.
+fn tool_stats_table(stats: &[(String, ToolStats)]) -> String {
+    let mut stats = stats.to_vec();
+    stats.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.time));
+    let tool_width = stats
+        .iter()
+        .map(|(tool, _)| tool.len())
+        .chain(Some(4))
+        .max()
+        .unwrap_or(0);
+    let row = |tool: &str, calls: String, failed: String, average: String, time: String| {
+        format!(
+            "  {:<tool_width$}  {:>6}  {:>7}  {:>8}  {:>8}",
+            tool, calls, failed, average, time
+        )
+    };
+    let mut lines = vec![row(
+        "Tool",
+        "Calls".to_string(),
+        "Failed".to_string(),
+        "Average".to_string(),
+        "In all".to_string(),
+    )
+    .bold()
+    .to_string()];
+    for (tool, stats) in &stats {
+        lines.push(row(
+            tool,
+            stats.calls.to_string(),
+            format!(
+                "{:.0}%",
+                stats.failures as f64 / stats.calls.max(1) as f64 * 100.0
+            ),
+            format!("{:.2}s", stats.average().as_secs_f64()),
+            format!("{:.1}s", stats.time.as_secs_f64()),
+        ));
+    }
+    lines.join("\n")
+}
+
+/// `rusty hook <name> [args...]`: the helper for the Git hook `name`, given
+/// the arguments Git passes to the hook. Nothing here may stop Git, so
+/// failures are only reported; a missing API key skips the helper.
+async fn run_git_hook(config: &Config, args: &CliArgs, hook: &[String]) -> Result<(), String> {
+    const USAGE: &str = "Usage: rusty hook prepare-commit-msg <file> [source] [commit], or rusty hook pre-push-review <remote> [url]";
+    let provider = if args.provider == "mock" {
+        Provider::Mock(match &args.mock_script {
+            Some(path) => MockProvider::from_file(path)?,
+            None => MockProvider::new(Vec::new()),
+        })
+    } else {
+        // Git hooks cannot be answered, so the key is never asked for.
+        let Ok(key) = ConfigManager::load_key("GROQ_API_KEY") else {
+            eprintln!("rusty: GROQ_API_KEY is not set, skipping the hook");
+            return Ok(());
+        };
+        Provider::Groq(GroqApiClient::new(key))
+    };
+    let model = config
+        .git
+        .model
+        .clone()
+        .unwrap_or_else(|| MODELS[0].to_string());
+    let (redactor, warnings) = Redactor::new(&config.redact);
+    let (moderator, more) = Moderator::new(&config.moderation);
+    for warning in warnings.iter().chain(&more) {
+        eprintln!("rusty: {}", warning);
+    }
+    let mut checks = githooks::Checks {
+        redactor,
+        pii: Scrubber::new(&config.pii),
+        moderator,
+        ledger: Ledger::open(&paths::usage_db())
+            .map_err(|e| eprintln!("rusty: {}", e))
+            .ok(),
+        budget: (!args.ignore_caps).then(|| config.budget.clone()),
+        price: model_info(&model).price,
+    };
+
+    let result = match hook {
+        [name, file, rest @ ..] if name == "prepare-commit-msg" => {
+            githooks::prepare_commit_msg(
+                &provider,
+                &model,
+                &mut checks,
+                Path::new(file),
+                rest.first().map(String::as_str),
+            )
+            .await
+        }
+        [name, remote, ..] if name == "pre-push-review" => {
+            let updates = io::read_to_string(io::stdin())
+                .map_err(|e| format!("Cannot read the pushed refs: {}", e))?;
+            githooks::pre_push_review(&provider, &model, &mut checks, remote, &updates)
+                .await
+                .map(|review| {
+                    if let Some((review, path)) = review {
+                        eprintln!("{}\n\n(Saved to {})", review.trim(), path.display());
+                    }
+                })
+        }
+        _ => return Err(USAGE.to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("rusty: {}", e);
+    }
+    Ok(())
+}
+
+/// Shows what a service is doing in the terminal.
+struct TerminalReporter<'a> {
+    theme: Option<&'a str>,
+}
+
+impl services::Reporter for TerminalReporter<'_> {
+    fn status(&self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn step(&self, step: &str) {
+        let blue = Color::TrueColor {
+            r: 122,
+            g: 162,
+            b: 247,
+        };
+        UserInterface::print_step(step, blue);
+    }
+
+    fn answer(&self, answer: &str) {
+        UserInterface::print_assistant_response(answer, self.theme);
+    }
+
+    fn error(&self, error: &str) {
+        UserInterface::print_error(error);
+    }
+}
+
+/// `rusty watch`: sends each file saved under a directory, or a single file,
+/// to the model with a prompt and prints the answer, until interrupted.
+async fn run_watch(config: &Config, agent: Agent, args: Vec<String>) -> Result<(), String> {
+    let mut target = None;
+    let mut prompt = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--prompt" => prompt = args.next(),
+            _ if target.is_none() => target = Some(arg),
+            _ => return Err("Usage: rusty watch [path] [--prompt <prompt>]".to_string()),
+        }
+    }
+    let target = target.unwrap_or_else(|| ".".to_string());
+    let reporter = TerminalReporter {
+        theme: config.chat.theme.as_deref(),
+    };
+    services::watch(config, agent, &target, prompt, &reporter).await
+}
+
+/// `rusty schedule`: runs prompts on cron schedules until interrupted. With
+/// a pattern and a prompt on the command line, runs that one; otherwise the
+/// jobs in the configuration. Each answer is added to the job's session, or
+/// appended to the schedule log.
+async fn run_schedule(config: &Config, agent: Agent, args: Vec<String>) -> Result<(), String> {
+    let jobs = match args.as_slice() {
+        [] if config.schedule.jobs.is_empty() => {
+            return Err(format!(
+                "Nothing is scheduled. Run rusty schedule \"<cron>\" \"<prompt>\", or add [[schedule.jobs]] to {}",
+                Config::path().display()
+            ))
+        }
+        [] => config.schedule.jobs.clone(),
+        [cron, prompt] => vec![ScheduledPrompt {
+            cron: cron.clone(),
+            prompt: prompt.clone(),
+            session: None,
+        }],
+        [cron, prompt, flag, title] if flag == "--session" => vec![ScheduledPrompt {
+            cron: cron.clone(),
+            prompt: prompt.clone(),
+            session: Some(title.clone()),
+        }],
+        _ => {
+            return Err(
+                "Usage: rusty schedule [\"<cron>\" \"<prompt>\" [--session <title>]]".to_string(),
+            )
+        }
+    };
+    let cipher = if config.storage.encrypt && jobs.iter().any(|job| job.session.is_some()) {
+        Some(ConfigManager::unlock_storage()?)
+    } else {
+        None
+    };
+    let reporter = TerminalReporter {
+        theme: config.chat.theme.as_deref(),
+    };
+    services::schedule(config, agent, jobs, cipher, &reporter).await
+}
+
+/// `rusty webhook`: listens for POSTed JSON payloads and answers each with
+/// its route's prompt template, until interrupted.
+async fn run_webhook(config: &Config, agent: Agent) -> Result<(), String> {
+    let secret = ConfigManager::load_key("RUSTY_WEBHOOK_SECRET").ok();
+    let reporter = TerminalReporter {
+        theme: config.chat.theme.as_deref(),
+    };
+    services::webhook(config, agent, secret, &reporter).await
+}
+
+/// `rusty discord`: answers mentions and direct messages on Discord, keeping
+/// a conversation per channel, until interrupted.
+async fn run_discord(config: &Config, agent: Agent) -> Result<(), String> {
+    let token = ConfigManager::load_key("DISCORD_BOT_TOKEN")
+        .map_err(|_| "Add DISCORD_BOT_TOKEN, your bot's token, to .env".to_string())?;
+    let reporter = TerminalReporter {
+        theme: config.chat.theme.as_deref(),
+    };
+    services::discord(config, agent, token, &reporter).await
+}
+
+/// `rusty slack`: answers in Slack threads over Socket Mode, keeping a
+/// conversation per thread, until interrupted.
+async fn run_slack(config: &Config, agent: Agent) -> Result<(), String> {
+    let (app_token, bot_token) = ConfigManager::load_key("SLACK_APP_TOKEN")
+        .and_then(|app| Ok((app, ConfigManager::load_key("SLACK_BOT_TOKEN")?)))
+        .map_err(|_| {
+            "Add SLACK_APP_TOKEN (xapp-...) and SLACK_BOT_TOKEN (xoxb-...) to .env".to_string()
+        })?;
+    let reporter = TerminalReporter {
+        theme: config.chat.theme.as_deref(),
+    };
+    services::slack(config, agent, app_token, bot_token, &reporter).await
+}
+
+/// `rusty telegram`: answers Telegram messages by long polling, keeping a
+/// conversation and a model per chat, until interrupted.
+async fn run_telegram(config: &Config, agent: Agent) -> Result<(), String> {
+    let token = ConfigManager::load_key("TELEGRAM_BOT_TOKEN")
+        .map_err(|_| "Add TELEGRAM_BOT_TOKEN, your bot's token, to .env".to_string())?;
+    let reporter = TerminalReporter {
+        theme: config.chat.theme.as_deref(),
+    };
+    services::telegram(config, agent, token, &reporter).await
+}
+
+/// Builds the document index from `dir` and stores it, reporting progress.
+async fn index_documents(
+    dir: &Path,
+    embedder: &Embedder,
+    config: &IndexConfig,
+) -> Result<Retriever, String> {
+    note!("Indexing {} with {}...", dir.display(), embedder.name());
+    let (index, warnings) = DocumentIndex::build(dir, embedder).await?;
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
+    let mut store = rag::open_store(config)?;
+    store.replace(&index)?;
+    note!(
+        "Indexed {} chunks from {} files\n",
+        index.chunks.len(),
+        index.file_count()
+    );
+    Retriever::open(store, embedder.clone())?.ok_or_else(|| "The index is empty".to_string())
+}
+
+/// Writes the pages of a crawl to `dir` as Markdown files, one per page,
+/// replacing those of an earlier crawl of the site.
+fn save_crawl(dir: &Path, pages: &[Page]) -> Result<(), String> {
+    if dir.exists() {
+        fs::remove_dir_all(dir).map_err(|e| format!("Failed to clear {}: {}", dir.display(), e))?;
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let mut names = HashSet::new();
+    for page in pages {
+        let base = match slug(page.url.path()) {
+            name if name.is_empty() => "index".to_string(),
+            name => name,
+        };
+        let mut name = base.clone();
+        let mut n = 1;
+        while !names.insert(name.clone()) {
+            n += 1;
+            name = format!("{}-{}", base, n);
+        }
+        let title = page.title.as_deref().unwrap_or(page.url.as_str());
+        let text = format!(
+            "# {}\n\nSource: {}\n\n{}\n",
+            title,
+            page.url,
+            page.text.trim()
+        );
+        let path = dir.join(format!("{}.md", name));
+        fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// `rusty embed`: prints the embedding of each argument, or of each line of
+/// stdin when there are none, as a JSON array.
+async fn print_embeddings(inputs: Vec<String>, embedder: &Embedder) -> Result<(), String> {
+    let inputs = if inputs.is_empty() {
+        io::stdin()
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    } else {
+        inputs
+    };
+    let vectors = embedder.embed(&inputs).await?;
+    let output: Vec<serde_json::Value> = inputs
+        .iter()
+        .zip(vectors)
+        .map(|(input, embedding)| serde_json::json!({ "input": input, "embedding": embedding }))
+        .collect();
+    let json = serde_json::to_string(&output).map_err(|e| e.to_string())?;
+    match writeln!(io::stdout(), "{}", json) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// A file-name-friendly version of a title: lowercase words joined by dashes.
+fn slug(title: &str) -> String {
+    let words: Vec<String> = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let slug: String = words.join("-").chars().take(50).collect();
+    if slug.is_empty() {
+        "conversation".to_string()
+    } else {
+        slug
+    }
+}
+
+// Chat Application
+
+/// A conversation set aside by `/session new` or `/session switch`, to be
+/// taken up again as it was left.
+struct Thread {
+    session: Session,
+    persona: Option<String>,
+}
+
+struct ChatApplication {
+    provider: Provider,
+    brave_client: BraveSearchClient,
+    tools: ToolRegistry,
+    hooks: ScriptHooks,
+    command_hooks: CommandHooks,
+    prompts: PromptLibrary,
+    command_handler: CommandHandler,
+    model_manager: ModelManager,
+    conversation_manager: ConversationManager,
+    reader: tokio::io::BufReader<tokio::io::Stdin>,
+    editor: LineEditor,
+    sessions: SessionStore,
+    session: Session,
+    /// The model last chosen in each project.
+    projects: Projects,
+    /// Anonymous counts of commands, models and errors, once allowed.
+    telemetry: Telemetry,
+    memory: MemoryStore,
+    /// The `RUSTY.md` files whose instructions are sent, the nearest last.
+    instruction_files: Vec<InstructionFile>,
+    /// Directories added to the workspace with `/add-dir`, beyond the one
+    /// Rusty was started in.
+    extra_dirs: Vec<PathBuf>,
+    /// The Git repository Rusty was started in, or else the directory.
+    workspace: Option<PathBuf>,
+    /// Whether the user trusts the workspace to run programs in, once
+    /// known.
+    workspace_trusted: Option<bool>,
+    /// `--read-only` or `[trust] read_only`: tool calls that may change
+    /// something are refused.
+    read_only: bool,
+    documents: Option<Retriever>,
+    embedder: Embedder,
+    index_config: IndexConfig,
+    /// Hosts `http_request` may call without asking, from the configuration
+    /// and from "always" answers this session.
+    http_allowlist: Allowlist,
+    /// `[http] egress`: the hosts tools may contact at all.
+    egress: Egress,
+    /// The language every message is translated into, while `/translate`
+    /// mode is on.
+    translation: Option<String>,
+    /// The model each prompt is also sent to, while `/compare` mode is on.
+    compare: Option<String>,
+    ocr: Ocr,
+    fetcher: Fetcher,
+    /// Clipboard text from `/paste`, sent with the next message.
+    pending_paste: Option<String>,
+    /// Whether the model is offered `read_clipboard`.
+    clipboard_tool: bool,
+    /// Every tool call this session, numbered from 1 for `/expand`.
+    tool_runs: Vec<ToolRun>,
+    /// Print reasoning models' thinking in full rather than as one line.
+    show_thinking: bool,
+    /// The reasoning behind the last answer, for `/thinking`.
+    last_reasoning: Option<String>,
+    /// What `/set` changes, starting from the configuration.
+    settings: Settings,
+    /// The settings from the configuration, which `/set <key>` goes back to.
+    default_settings: Settings,
+    /// `[personas]`, by name.
+    personas: BTreeMap<String, Persona>,
+    /// The persona switched to last, if it is still on.
+    persona: Option<String>,
+    /// The name of the conversation under way, as `/session` lists it.
+    thread: String,
+    /// The other conversations of this run, by name.
+    threads: BTreeMap<String, Thread>,
+    /// The JSON every answer must be while `/json` is on.
+    json_format: Option<ResponseFormat>,
+    /// Ask for token log probabilities, while `/logprobs` is on.
+    logprobs: bool,
+    /// The log probabilities of the last answer's tokens.
+    last_logprobs: Vec<TokenLogprob>,
+    /// The tokens used since Rusty started, as the provider reported them.
+    usage: Usage,
+    /// The tool calls since Rusty started, by tool.
+    tool_stats: BTreeMap<String, ToolStats>,
+    /// Follow each answer with the tokens it took, while `/usage` is on.
+    usage_footer: bool,
+    /// Follow each answer with prompts to send next, while `/followups` is
+    /// on.
+    suggest_follow_ups: bool,
+    /// The prompts offered after the last answer, and the length of the
+    /// conversation then: they can be picked only while it has not changed.
+    follow_ups: Vec<String>,
+    follow_ups_after: usize,
+    /// The session's token budget and the daily and monthly spending caps.
+    budget: BudgetConfig,
+    /// Requests over the token budget were allowed for the rest of the
+    /// session.
+    over_budget: bool,
+    /// Requests over `[budget] request_tokens` were allowed for the rest of
+    /// the session.
+    large_requests_allowed: bool,
+    /// What requests have cost, by day and model, for the spending caps.
+    ledger: Option<Ledger>,
+    /// The ratings given with `/good` and `/bad`.
+    feedback: Option<FeedbackLog>,
+    /// `--ignore-caps`: priced models may be used past the spending caps.
+    ignore_caps: bool,
+    /// `--dry-run` or `/dryrun`: prompts show the request they would send
+    /// and are not sent.
+    dry_run: bool,
+    /// Masks API keys, credentials and private keys in messages before
+    /// they are sent, until `/redact off`.
+    redactor: Redactor,
+    /// `[pii]`: personal data in prompts and tool outputs is replaced by
+    /// placeholders, which answers are shown with the values of again.
+    pii: Scrubber,
+    /// `[moderation]`: prompts and answers are checked and may be blocked.
+    moderator: Moderator,
+    /// `[environment]`: what the model is told about this machine.
+    environment: EnvironmentConfig,
+    /// The tool choice of the next turn only, as set by `/search-first`.
+    next_tool_choice: Option<ToolChoice>,
+    /// Messages typed ahead during a turn, sent one per turn after it.
+    queued: VecDeque<String>,
+    /// A line typed ahead but not finished, to start the next prompt with.
+    draft: String,
+    /// Prompts written while the network was down, sent in order once it
+    /// is back.
+    offline: VecDeque<String>,
+    /// Set by the connectivity probe when the API can be reached again.
+    online: Arc<AtomicBool>,
+    /// The API could not be reached, at startup or by the last request, and
+    /// the probe has not found it back yet.
+    network_down: bool,
+    /// How long a turn may take before its end is announced with a desktop
+    /// notification; `None` turns notifications off.
+    notify_after: Option<Duration>,
+    /// `"bell"` or a sound file, played at the end of every turn.
+    sound: Option<String>,
+    /// The syntect theme code blocks are highlighted with.
+    code_theme: Option<String>,
+    /// The first thing that went wrong in a turn, for the exit code of a
+    /// one-shot or piped run.
+    failure: Option<Failure>,
+}
+
+impl ChatApplication {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        provider: Provider,
+        brave_client: BraveSearchClient,
+        hooks: ScriptHooks,
+        command_hooks: CommandHooks,
+        command_handler: CommandHandler,
+        editor: LineEditor,
+        sessions: SessionStore,
+        memory: MemoryStore,
+        documents: Option<Retriever>,
+        embedder: Embedder,
+        index_config: IndexConfig,
+    ) -> Self {
+        let mut conversation_manager = ConversationManager::new();
+        conversation_manager.set_memory(memory.system_prompt());
+        let instruction_files = instructions::load();
+        conversation_manager.set_instructions(instructions::system_prompt(&instruction_files));
+        Self {
+            provider,
+            brave_client,
+            tools: ToolRegistry::new(&PluginHost::default()),
+            hooks,
+            command_hooks,
+            prompts: PromptLibrary::load(&paths::prompts_dir()),
+            command_handler,
+            model_manager: ModelManager::new(),
+            conversation_manager,
+            reader: tokio::io::BufReader::new(tokio::io::stdin()),
+            editor,
+            sessions,
+            session: Session::new(MODELS[0]),
+            projects: Projects::load(&paths::projects_file()),
+            telemetry: Telemetry::disabled(),
+            memory,
+            instruction_files,
+            extra_dirs: Vec::new(),
+            workspace: None,
+            workspace_trusted: None,
+            read_only: false,
+            documents,
+            embedder,
+            index_config,
+            http_allowlist: Allowlist::default(),
+            egress: Egress::default(),
+            translation: None,
+            compare: None,
+            ocr: Ocr::default(),
+            fetcher: Fetcher::default(),
+            pending_paste: None,
+            clipboard_tool: false,
+            tool_runs: Vec::new(),
+            show_thinking: false,
+            last_reasoning: None,
+            settings: Settings::default(),
+            default_settings: Settings::default(),
+            personas: BTreeMap::new(),
+            persona: None,
+            thread: MAIN_THREAD.to_string(),
+            threads: BTreeMap::new(),
+            json_format: None,
+            logprobs: false,
+            last_logprobs: Vec::new(),
+            usage: Usage::default(),
+            tool_stats: BTreeMap::new(),
+            usage_footer: false,
+            suggest_follow_ups: false,
+            follow_ups: Vec::new(),
+            follow_ups_after: 0,
+            budget: BudgetConfig::default(),
+            over_budget: false,
+            large_requests_allowed: false,
+            ledger: None,
+            feedback: None,
+            ignore_caps: false,
+            dry_run: false,
+            redactor: Redactor::default(),
+            pii: Scrubber::new(&Default::default()),
+            moderator: Moderator::default(),
+            environment: EnvironmentConfig::default(),
+            next_tool_choice: None,
+            queued: VecDeque::new(),
+            draft: String::new(),
+            offline: VecDeque::new(),
+            online: Arc::new(AtomicBool::new(true)),
+            network_down: false,
+            notify_after: None,
+            sound: None,
+            code_theme: None,
+            failure: None,
+        }
+    }
+
+    async fn initialize(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        UserInterface::print_welcome(
+            self.model_manager.get_current_model(),
+            &self.sessions.list(),
+        );
+        if !self
+            .model_manager
+            .select_model_interactive(&mut self.reader)
+            .await?
+        {
+            return Ok(false);
+        }
+        self.remember_model();
+
+        let orange = Color::TrueColor {
+            r: 255,
+            g: 140,
+            b: 0,
+        };
+        println!(
+            "\n{} {}\n",
+            locale::t("models.active").color(orange).bold(),
+            self.model_manager.get_current_model().white()
+        );
+        self.offer_recovery().await?;
+        self.restore_draft();
+        if let Some(probe) = self.provider.probe() {
+            if !probe.reachable().await {
+                self.go_offline();
+            }
+        }
+        UserInterface::print_instructions();
+
+        Ok(true)
+    }
+
+    /// Quiet mode: sends `words` as one prompt, followed by whatever is
+    /// piped in, or just the piped text without words, and prints only the
+    /// answer.
+    async fn run_once(&mut self, words: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut prompt = words.join(" ");
+        if !io::stdin().is_terminal() {
+            let input = self.read_piped().await?;
+            // With a prompt to go with it, the input is what it is about,
+            // so it follows as a block of its own.
+            if prompt.trim().is_empty() {
+                prompt = input;
+            } else if !input.trim().is_empty() {
+                prompt = format!("{}\n\n{}", prompt, clipboard::fenced(&input));
+            }
+        }
+        let prompt = prompt.trim();
+        if prompt.is_empty() {
+            UserInterface::print_error("Usage: rusty -q <prompt>, or pipe the prompt in");
+            Failure::Usage.exit();
+        }
+        self.process_message(prompt).await?;
+        if let Some(failure) = self.failure {
+            failure.exit();
+        }
+        Ok(())
+    }
+
+    /// `rusty explain-last`: asks why the command last run in this shell
+    /// failed, as the hook of `rusty shell-init` left it, with its output
+    /// when that is piped in.
+    async fn explain_last(
+        &mut self,
+        question: &[String],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(last) = shell::last_command() else {
+            UserInterface::print_error(
+                "No last command to explain. Add the hook to your shell's startup file: \
+                 eval \"$(rusty shell-init bash)\" for bash, eval \"$(rusty shell-init zsh)\" \
+                 for zsh or rusty shell-init fish | source for fish",
+            );
+            Failure::Usage.exit();
+        };
+        let output = if io::stdin().is_terminal() {
+            String::new()
+        } else {
+            self.read_piped().await?
+        };
+        let mut prompt =
+            shell::explain_prompt(&last, &question.join(" "), !output.trim().is_empty());
+        if !output.trim().is_empty() {
+            prompt = format!("{}\n\n{}", prompt, clipboard::fenced(&output));
+        }
+        self.process_message(&prompt).await?;
+        if let Some(failure) = self.failure {
+            failure.exit();
+        }
+        Ok(())
+    }
+
+    /// `rusty cmd`: asks for one shell command that does what `words`
+    /// describe, shows it with what it does, and runs it once confirmed.
+    /// It is only printed where nobody can confirm it, and then alone when
+    /// stdout is not a terminal, as in `$(rusty cmd ...)`.
+    async fn run_cmd(&mut self, words: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let request = words.join(" ");
+        if request.trim().is_empty() {
+            UserInterface::print_error("Usage: rusty cmd <what the command should do>");
+            Failure::Usage.exit();
+        }
+        // Masked and checked as any prompt; a blocked one has no command.
+        let Some(request) = self.prepare_user_message(request.trim()).await else {
+            Failure::Other.exit();
+        };
+        let model = self.model_manager.get_current_model().to_string();
+        let messages = [
+            Message::system(&shell::command_prompt()),
+            Message::user(&request),
+        ];
+        if self.dry_run {
+            self.show_dry_run(&model, &messages, None, &ChatOptions::default());
+            return Ok(());
+        }
+        if let Some(cap) = self.spending_cap_reached(&model) {
+            UserInterface::print_error(&format!(
+                "{}; start Rusty with --ignore-caps to go on",
+                cap
+            ));
+            Failure::Other.exit();
+        }
+        // Printed alone, the command is all that may reach stdout.
+        let mut spinner = if io::stdout().is_terminal() {
+            UserInterface::print_thinking(self.animate_spinner())
+        } else {
+            Spinner::hidden()
+        };
+        let reply = self.provider.chat_completion(&model, &messages, None).await;
+        spinner.pause();
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                UserInterface::print_error(&e.to_string());
+                Failure::of(&e).exit();
+            }
+        };
+        if let Some(usage) = &reply.usage {
+            self.usage.add(usage);
+            self.record_spending(&model, usage);
+        }
+        let reply = self.pii.restore(&reply.content.unwrap_or_default());
+        let mut suggestion = shell::parse_suggestion(&reply)?;
+        if !io::stdout().is_terminal() {
+            println!("{}", suggestion.command);
+            return Ok(());
+        }
+        UserInterface::print_assistant_response(
+            &format!(
+                "```sh\n{}\n```\n\n{}",
+                suggestion.command, suggestion.explanation
+            ),
+            self.code_theme.as_deref(),
+        );
+        if self.read_only {
+            note!("Read-only mode: the command was not run\n");
+            return Ok(());
+        }
+        if !io::stdin().is_terminal() {
+            note!("Not run, as there is nobody to confirm it\n");
+            return Ok(());
+        }
+        loop {
+            print!("  Run it? [y/N/e to edit] ");
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            self.reader.read_line(&mut answer).await?;
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => break,
+                "e" | "edit" => match editor::edit_externally(&suggestion.command) {
+                    Ok(edited) if !edited.trim().is_empty() => {
+                        suggestion.command = edited.trim().to_string();
+                        println!("  {}", suggestion.command.bold());
+                    }
+                    Ok(_) => {}
+                    Err(e) => UserInterface::print_error(&format!("Cannot edit it: {}", e)),
+                },
+                _ => return Ok(()),
+            }
+        }
+        let status = shell::run(&suggestion.command)
+            .map_err(|e| format!("Cannot run the command: {}", e))?;
+        if !status.success() {
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        Ok(())
+    }
+
+    /// What is piped in, trimmed to `MAX_PIPED_CHARS`.
+    async fn read_piped(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut input = String::new();
+        self.reader.read_to_string(&mut input).await?;
+        let (input, trimmed) = trim_piped(input.trim_end());
+        if let Some(trimmed) = &trimmed {
+            eprintln!("rusty: {}", trimmed);
+        }
+        Ok(input)
+    }
+
+    /// `rusty quick`, made for a tmux popup: straight to the prompt with
+    /// the default model, in the scratch session kept from the last time,
+    /// without the welcome screen. An empty line closes it, so one key
+    /// after the answer closes the popup. Inside tmux, each answer is also
+    /// put in tmux's paste buffer, to paste once the popup is gone.
+    async fn run_quick(&mut self, words: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        UserInterface::set_compact(true);
+        match self.sessions.load(SCRATCH_SESSION) {
+            Ok(session) => {
+                for message in &session.messages {
+                    self.conversation_manager.add_message(message.clone());
+                }
+                self.conversation_manager
+                    .set_system_prompt(session.system_prompt.clone());
+                self.load_settings(&session);
+                self.session = session;
+            }
+            Err(_) => {
+                self.session.id = SCRATCH_SESSION.to_string();
+                self.session.title = Some("Scratch".to_string());
+            }
+        }
+        self.session.model = self.model_manager.get_current_model().to_string();
+
+        let mut input = words.join(" ");
+        if !input.is_empty() {
+            UserInterface::print_prompt();
+            println!("{}", input);
+            UserInterface::print_prompt_closure();
+        }
+        loop {
+            if input.is_empty() {
+                UserInterface::set_title(&self.window_title());
+                UserInterface::print_prompt();
+                let line = self.read_user_input("").await?;
+                UserInterface::print_prompt_closure();
+                match line.filter(|line| !line.trim().is_empty()) {
+                    Some(line) => input = line,
+                    None => break,
+                }
+            }
+            let before = self.conversation_manager.get_messages().len();
+            for line in self.command_handler.expand(&input) {
+                let command = CommandHandler::parse(&line);
+                if !self.handle_command(command, &line).await? {
+                    return Ok(());
+                }
+            }
+            input.clear();
+            if clipboard::in_tmux() {
+                let answer = self
+                    .conversation_manager
+                    .get_messages()
+                    .iter()
+                    .skip(before)
+                    .rev()
+                    .find(|m| m.role == "assistant")
+                    .and_then(|m| m.content.as_deref())
+                    .filter(|answer| !answer.trim().is_empty())
+                    .map(|answer| self.pii.restore(answer));
+                if let Some(answer) = answer {
+                    if let Err(e) = clipboard::write_tmux_buffer(&answer) {
+                        UserInterface::print_error(&e);
+                    }
+                }
+            }
+            self.write_recovery();
+        }
+        self.handle_command(Command::Quit, "/exit").await?;
+        Ok(())
+    }
+
+    async fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.initialize().await? {
+            return Ok(());
+        }
+
+        // Initial hint handled by print_prompt
+
+        'repl: loop {
+            if !UserInterface::plain() {
+                let model = self.model_manager.get_current_model();
+                UserInterface::set_status_line(self.command_hooks.statusline(model).await);
+            }
+            self.check_network().await?;
+            UserInterface::set_title(&self.window_title());
+            UserInterface::print_prompt();
+
+            let input = match self.queued.pop_front() {
+                Some(line) => {
+                    // Shown in the prompt box as if typed just now.
+                    println!("{}", line);
+                    if !line.starts_with(' ') {
+                        if let Err(e) = self.editor.add_history(line.trim()) {
+                            UserInterface::print_error(&e);
+                        }
+                    }
+                    Some(line.trim().to_string())
+                }
+                None => {
+                    let draft = std::mem::take(&mut self.draft);
+                    self.read_user_input(&draft).await?
+                }
+            };
+            UserInterface::print_prompt_closure();
+            let Some(input) = input else {
+                self.handle_command(Command::Quit, "/exit").await?;
+                break;
+            };
+
+            if input.is_empty() {
+                continue;
+            }
+            self.check_network().await?;
+
+            for line in self.command_handler.expand(&input) {
+                let command = CommandHandler::parse(&line);
+                if !self.handle_command(command, &line).await? {
+                    break 'repl;
+                }
+            }
+            self.write_recovery();
+        }
+
+        // Prompts piped in are a batch; a script wants to know if one failed.
+        if let Some(failure) = self.failure.filter(|_| !io::stdin().is_terminal()) {
+            failure.exit();
+        }
+        Ok(())
+    }
+
+    /// The session's title, or the start of its first prompt, and the
+    /// model, to tell Rusty's tabs apart by.
+    fn window_title(&self) -> String {
+        let model = self.model_manager.get_current_model();
+        let title = self.session.title.clone().or_else(|| {
+            let prompt = self
+                .conversation_manager
+                .get_messages()
+                .iter()
+                .find(|m| m.role == "user")?;
+            let line = prompt.content.as_deref()?.lines().next()?;
+            Some(truncate(line.trim(), 40))
+        });
+        match title.filter(|title| !title.is_empty()) {
+            Some(title) => format!("{} · {}", title, model),
+            None => format!("Rusty · {}", model),
+        }
+    }
+
+    /// Reads the next line, starting from `initial` on a terminal. `None` means
+    /// the user asked to leave: Ctrl+C or Ctrl+D on an empty line, or the end
+    /// of piped input.
+    async fn read_user_input(
+        &mut self,
+        initial: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if !io::stdin().is_terminal() {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(line.trim().to_string()));
+        }
+
+        // In accessibility mode the terminal edits and echoes the line
+        // itself, which screen readers follow best; consoles without escape
+        // sequences cannot show the line editor anyway.
+        let line = if UserInterface::plain() {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+            line.trim_end_matches(['\r', '\n']).to_string()
+        } else {
+            let items = self.palette_items();
+            // Sessions are only read once Ctrl+R is pressed.
+            let sessions = OnceCell::new();
+            let history = self.editor.history().to_vec();
+            let search = |query: &str| {
+                history_matches(
+                    query,
+                    &history,
+                    sessions.get_or_init(|| self.sessions.list()),
+                )
+            };
+            // The workspace is only walked once `@` is typed.
+            let workspace = OnceCell::new();
+            let files = |query: &str| {
+                file_matches(
+                    query,
+                    workspace.get_or_init(|| prompts::workspace_files(&self.extra_dirs)),
+                )
+            };
+            // Waiting on queued prompts, the prompt gives way when the
+            // network comes back so they can be sent.
+            let wake = self.network_down.then_some(&*self.online);
+            let save_draft = |text: &str| {
+                if let Err(e) = self.sessions.write_draft(text) {
+                    diagnostics::log(&e);
+                }
+            };
+            let outcome = tokio::task::block_in_place(|| {
+                self.editor
+                    .read_line(initial, &items, &search, &files, &save_draft, wake)
+            })?;
+            match outcome {
+                ReadOutcome::Line(line) => {
+                    if !line.trim().is_empty() {
+                        self.sessions.clear_draft();
+                    }
+                    line
+                }
+                ReadOutcome::Interrupted | ReadOutcome::Eof => return Ok(None),
+                ReadOutcome::Woken(line) => {
+                    self.draft = line;
+                    return Ok(Some(String::new()));
+                }
+            }
+        };
+        // As in shells, a leading space keeps a line out of history.
+        if !line.starts_with(' ') {
+            if let Err(e) = self.editor.add_history(line.trim()) {
+                UserInterface::print_error(&e);
+            }
+        }
+        Ok(Some(line.trim().to_string()))
+    }
+
+    /// Everything the Ctrl+K palette offers: built-in commands, aliases,
+    /// prompt templates, saved sessions, and recent prompts (newest first).
+    fn palette_items(&self) -> Vec<PaletteItem> {
+        let mut items = Vec::new();
+        for (usage, description) in COMMANDS {
+            // Commands that need an argument are inserted for editing.
+            let action = match usage.split_once(' ') {
+                Some((name, args)) if args.starts_with('<') => {
+                    PaletteAction::Insert(format!("{} ", name))
+                }
+                Some((name, _)) => PaletteAction::Submit(name.to_string()),
+                None => PaletteAction::Submit(usage.to_string()),
+            };
+            items.push(PaletteItem::new(usage, locale::t(description), action));
+        }
+        for (name, alias) in &self.command_handler.aliases {
+            let expansion = match alias {
+                Alias::Line(line) => line.clone(),
+                Alias::Steps(steps) => steps.join(" → "),
+            };
+            let label = format!("/{}", name);
+            items.push(PaletteItem::new(
+                &label,
+                &expansion,
+                PaletteAction::Submit(label.clone()),
+            ));
+        }
+        for template in self.prompts.templates() {
+            let label = format!("/use {}", template.name);
+            items.push(PaletteItem::new(
+                &label,
+                template.description(),
+                PaletteAction::Insert(format!("{} ", label)),
+            ));
+        }
+        for session in self.sessions.list() {
+            items.push(PaletteItem::new(
+                &session.display_title(),
+                &format!(
+                    "session · {}{}",
+                    sessions::time_ago(session.updated_at),
+                    session
+                        .tags
+                        .iter()
+                        .map(|t| format!(" #{}", t))
+                        .collect::<String>()
+                ),
+                PaletteAction::Submit(format!("/resume {}", session.id)),
+            ));
+        }
+        let recent = self.editor.history().iter().rev();
+        for prompt in recent.filter(|line| !line.starts_with('/')) {
+            items.push(PaletteItem::new(
+                prompt,
+                "recent prompt",
+                PaletteAction::Insert(prompt.clone()),
+            ));
+        }
+        items
+    }
+
+    async fn handle_command(
+        &mut self,
+        command: Command,
+        input: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        // Only the names of built-in commands are counted, never what
+        // follows them.
+        if !matches!(command, Command::Message(_)) {
+            if let Some(name) = input.split_whitespace().next() {
+                self.telemetry.command(name);
+            }
+        }
+        match command {
+            Command::Quit => {
+                if let Err(e) = self.save_session().await {
+                    UserInterface::print_error(&e);
+                }
+                self.sessions.clear_recovery();
+                if let Err(e) = self.telemetry.send().await {
+                    UserInterface::print_error(&e);
+                }
+                if !self.offline.is_empty() {
+                    note!(
+                        "{} queued prompt{} never sent\n",
+                        self.offline.len(),
+                        if self.offline.len() == 1 {
+                            " was"
+                        } else {
+                            "s were"
+                        }
+                    );
+                }
+
+                UserInterface::restore_title();
+                UserInterface::print_goodbye();
+                Ok(false)
+            }
+            Command::Stream => {
+                self.conversation_manager.toggle_stream_mode();
+                let status = if self.conversation_manager.is_stream_mode() {
+                    "ON"
+                } else {
+                    "OFF"
+                };
+                note!("Streaming mode: {}\n", status);
+                Ok(true)
+            }
+            Command::Debug => {
+                let debug = !self.provider.is_debug();
+                self.provider.set_debug(debug);
+                let status = if debug { "ON" } else { "OFF" };
+                note!("Debug mode: {}\n", status);
+                Ok(true)
+            }
+            Command::DryRun => {
+                self.dry_run = !self.dry_run;
+                let status = if self.dry_run { "ON" } else { "OFF" };
+                note!("Dry run: {}\n", status);
+                Ok(true)
+            }
+            Command::Redact(arg) => {
+                match arg.as_str() {
+                    "" => {
+                        let status = if self.redactor.enabled() { "ON" } else { "OFF" };
+                        note!("Secret masking: {}\n", status);
+                    }
+                    "on" => {
+                        self.redactor.set_enabled(true);
+                        note!("Secret masking: ON\n");
+                    }
+                    "off" => {
+                        self.redactor.set_enabled(false);
+                        note!("Secret masking: OFF; messages are sent as they are\n");
+                    }
+                    _ => UserInterface::print_error("Usage: /redact [on|off]"),
+                }
+                Ok(true)
+            }
+            Command::Clear => {
+                // The cleared conversation stays available through /sessions.
+                if let Err(e) = self.save_session().await {
+                    UserInterface::print_error(&e);
+                }
+                self.session = Session::new(self.model_manager.get_current_model());
+                self.conversation_manager.clear();
+                note!("(no content)\n");
+                Ok(true)
+            }
+            Command::Model(choice) => {
+                let models = if choice.is_empty() {
+                    Vec::new()
+                } else {
+                    ModelManager::find_models(&choice)
+                };
+                match models.as_slice() {
+                    [model] => self.model_manager.selected_model = model.to_string(),
+                    [] if !choice.is_empty() => {
+                        UserInterface::print_error(&format!(
+                            "No model matches '{}'; /model lists them",
+                            choice
+                        ));
+                        return Ok(true);
+                    }
+                    matches => {
+                        if !matches.is_empty() {
+                            note!("'{}' matches {} models\n", choice, matches.len());
+                        }
+                        if !self
+                            .model_manager
+                            .change_model_interactive(&mut self.reader)
+                            .await?
+                        {
+                            return Ok(false);
+                        }
+                    }
+                }
+                self.remember_model();
+
+                let orange = Color::TrueColor {
+                    r: 255,
+                    g: 140,
+                    b: 0,
+                };
+                println!(
+                    "\n{} {}\n",
+                    locale::t("models.active").color(orange).bold(),
+                    self.model_manager.get_current_model().white()
+                );
+
+                Ok(true)
+            }
+            Command::Help => {
+                UserInterface::print_help();
+                UserInterface::print_aliases(&self.command_handler.aliases);
+                Ok(true)
+            }
+            Command::Prompts(name) => {
+                self.show_prompts(name.as_deref());
+                Ok(true)
+            }
+            Command::Use(args) => {
+                let (name, rest) = args.split_once(char::is_whitespace).unwrap_or((&args, ""));
+                let Some(template) = self.prompts.get(name) else {
+                    note!("Unknown template '{}'. Run /prompts to list them.\n", name);
+                    return Ok(true);
+                };
+                match prompts::extract_attachments(rest, &self.ocr, &self.extra_dirs).await {
+                    Ok((input, attachments)) => {
+                        let model = self.model_manager.get_current_model();
+                        if !model_info(model).vision {
+                            for attachment in &attachments {
+                                if ocr::is_image(Path::new(&attachment.path)) {
+                                    note!(
+                                        "{} cannot see images; {} is sent as the text read from it",
+                                        model,
+                                        attachment.path
+                                    );
+                                }
+                            }
+                        }
+                        let message = template.expand(&input, &attachments);
+                        note!(
+                            "Using template '{}' ({} file(s) attached)",
+                            template.name,
+                            attachments.len()
+                        );
+                        self.process_message(&message).await?;
+                    }
+                    Err(e) => note!("{}\n", e),
+                }
+                Ok(true)
+            }
+            Command::Persona(name) => {
+                match name.as_str() {
+                    "" => self.list_personas(),
+                    "off" => self.leave_persona(),
+                    name => {
+                        if let Err(e) = self.use_persona(name) {
+                            UserInterface::print_error(&e);
+                        }
+                    }
+                }
+                Ok(true)
+            }
+            Command::System(None) => {
+                match self.conversation_manager.system_prompt() {
+                    Some(prompt) => note!("{}\n", prompt),
+                    None => note!("No custom system prompt\n"),
+                }
+                Ok(true)
+            }
+            Command::System(Some(prompt)) => {
+                if prompt == "reset" {
+                    self.conversation_manager.set_system_prompt(None);
+                    note!("System prompt reset\n");
+                } else {
+                    self.conversation_manager.set_system_prompt(Some(prompt));
+                    note!("System prompt set\n");
+                }
+                Ok(true)
+            }
+            Command::Edit(external) => {
+                let Some(last) = self
+                    .conversation_manager
+                    .last_user_message()
+                    .map(str::to_string)
+                else {
+                    note!("No previous prompt to edit\n");
+                    return Ok(true);
+                };
+
+                let edited = if external {
+                    match tokio::task::block_in_place(|| editor::edit_externally(&last)) {
+                        Ok(text) => {
+                            note!("{}\n", text.trim().dimmed());
+                            Some(text.trim().to_string())
+                        }
+                        Err(e) => {
+                            UserInterface::print_error(&format!("Editor failed: {}", e));
+                            return Ok(true);
+                        }
+                    }
+                } else {
+                    UserInterface::print_prompt();
+                    let line = self.read_user_input(&last).await?;
+                    UserInterface::print_prompt_closure();
+                    line
+                };
+
+                match edited.filter(|text| !text.is_empty()) {
+                    Some(text) => {
+                        self.conversation_manager.rewind_last_user_message();
+                        self.process_message(&text).await?;
+                    }
+                    None => note!("Edit cancelled\n"),
+                }
+                Ok(true)
+            }
+            Command::Editor(text) => {
+                match tokio::task::block_in_place(|| editor::edit_externally(&text)) {
+                    Ok(text) if !text.trim().is_empty() => {
+                        note!("{}\n", text.trim().dimmed());
+                        self.process_message(text.trim()).await?;
+                    }
+                    Ok(_) => note!("Nothing written, nothing sent\n"),
+                    Err(e) => UserInterface::print_error(&format!("Editor failed: {}", e)),
+                }
+                Ok(true)
+            }
+            Command::Save(title) => {
+                if title.is_some() {
+                    self.session.title = title;
+                }
+                if self.conversation_manager.get_messages().is_empty() {
+                    note!("Nothing to save yet\n");
+                    return Ok(true);
+                }
+                match self.save_session().await {
+                    Ok(()) => note!("Saved \"{}\"\n", self.session.display_title()),
+                    Err(e) => UserInterface::print_error(&e),
+                }
+                Ok(true)
+            }
+            Command::Sessions(filter) => {
+                let (tags, _) = sessions::parse_filter(&filter);
+                self.show_sessions(&tags);
+                Ok(true)
+            }
+            Command::Tag(args) => {
+                self.tag_session(&args).await;
+                Ok(true)
+            }
+            Command::Search(query) => {
+                self.search_sessions(&query);
+                Ok(true)
+            }
+            Command::Session(args) => {
+                self.session_command(&args).await;
+                Ok(true)
+            }
+            Command::Resume(target) => {
+                self.resume_session(&target).await;
+                Ok(true)
+            }
+            Command::Share(public) => {
+                self.share_session(public).await;
+                Ok(true)
+            }
+            Command::Export(args) => {
+                self.export_session(&args);
+                Ok(true)
+            }
+            Command::History(turn) => {
+                self.show_history(turn);
+                Ok(true)
+            }
+            Command::Messages(args) => {
+                self.messages_command(&args);
+                Ok(true)
+            }
+            Command::Dump(path) => {
+                self.refresh_environment();
+                self.dump_messages(path.as_deref());
+                Ok(true)
+            }
+            Command::Memory(args) => {
+                self.manage_memory(&args);
+                Ok(true)
+            }
+            Command::Index(dir) => {
+                self.index_command(dir.as_deref()).await;
+                Ok(true)
+            }
+            Command::Crawl(args) => {
+                self.crawl_command(&args).await;
+                Ok(true)
+            }
+            Command::Translate(args) => {
+                self.translate_command(&args).await;
+                Ok(true)
+            }
+            Command::Compare(args) => {
+                self.compare_command(&args);
+                Ok(true)
+            }
+            Command::Json(args) => {
+                self.json_command(&args);
+                Ok(true)
+            }
+            Command::Set(args) => {
+                self.set_command(&args);
+                Ok(true)
+            }
+            Command::Get(key) => {
+                self.get_command(&key);
+                Ok(true)
+            }
+            Command::Tools(args) => {
+                self.tools_command(&args);
+                Ok(true)
+            }
+            Command::Logprobs(None) => {
+                if self.last_logprobs.is_empty() {
+                    note!(
+                        "No token probabilities for the last answer. /logprobs on asks for them\n"
+                    );
+                } else {
+                    UserInterface::print_logprobs(&self.last_logprobs);
+                }
+                Ok(true)
+            }
+            Command::Logprobs(Some(on)) => {
+                self.logprobs = on;
+                if on {
+                    note!("Asking how sure the model is of each token; /logprobs shows it\n");
+                } else {
+                    note!("No longer asking for token probabilities\n");
+                }
+                Ok(true)
+            }
+            Command::Rate(good, note) => {
+                self.rate_last_answer(good, &note);
+                Ok(true)
+            }
+            Command::Usage(None) => {
+                if self.usage.prompt == 0 {
+                    note!("The provider has reported no token usage yet\n");
+                } else {
+                    note!(
+                        "{} prompt tokens, {} of them from the provider's cache ({:.0}%), and {} completion tokens since Rusty started\n",
+                        self.usage.prompt,
+                        self.usage.cached,
+                        self.usage.hit_rate() * 100.0,
+                        self.usage.completion
+                    );
+                }
+                let month = &spending::today()[..7];
+                match self.ledger.as_ref().map(Ledger::days) {
+                    Some(Ok(days)) => {
+                        let days: Vec<DayUsage> = days
+                            .into_iter()
+                            .filter(|day| day.day.starts_with(month))
+                            .collect();
+                        if !days.is_empty() {
+                            println!("{}", usage_table(&days));
+                            note!(
+                                "{}\n",
+                                "All of it: rusty usage, or rusty usage --csv".dimmed()
+                            );
+                        }
+                    }
+                    Some(Err(e)) => UserInterface::print_error(&e),
+                    None => {}
+                }
+                Ok(true)
+            }
+            Command::Telemetry(arg) => {
+                match arg.as_str() {
+                    "" | "status" => self.show_telemetry(),
+                    "on" | "off" => match self.telemetry.set_enabled(arg == "on") {
+                        Ok(()) if arg == "on" => note!(
+                            "Counting commands, models and errors, without anything you type; /telemetry off stops it\n"
+                        ),
+                        Ok(()) => note!("No longer counting; what was counted is forgotten\n"),
+                        Err(e) => UserInterface::print_error(&e),
+                    },
+                    _ => UserInterface::print_error("Usage: /telemetry [status|on|off]"),
+                }
+                Ok(true)
+            }
+            Command::Usage(Some(on)) => {
+                self.usage_footer = on;
+                if on {
+                    note!("Each answer is followed by the tokens it took\n");
+                } else {
+                    note!("No longer showing the tokens each answer took\n");
+                }
+                Ok(true)
+            }
+            Command::Paste(text) => {
+                match clipboard::read() {
+                    Ok(pasted) if pasted.trim().is_empty() => {
+                        note!("The clipboard is empty\n")
+                    }
+                    Ok(pasted) => {
+                        let lines = pasted.trim_end().lines().count();
+                        let block = clipboard::fenced(&pasted);
+                        if text.is_empty() {
+                            note!(
+                                "Pasted {} line{}; they will be sent with your next message\n",
+                                lines,
+                                if lines == 1 { "" } else { "s" }
+                            );
+                            self.pending_paste = Some(block);
+                        } else {
+                            note!("Pasted {} line{}", lines, if lines == 1 { "" } else { "s" });
+                            self.send(&format!("{}\n\n{}", text, block)).await?;
+                        }
+                    }
+                    Err(e) => UserInterface::print_error(&e),
+                }
+                Ok(true)
+            }
+            Command::Copy(code) => {
+                self.copy_last_answer(code);
+                Ok(true)
+            }
+            Command::Last(edit) => {
+                let Some(answer) = self.last_answer() else {
+                    note!("No answer yet\n");
+                    return Ok(true);
+                };
+                let answer = self.pii.restore(answer);
+                if edit {
+                    // What is saved is not read back; the editor is only
+                    // for copying from or saving elsewhere.
+                    if let Err(e) = tokio::task::block_in_place(|| editor::edit_externally(&answer))
+                    {
+                        UserInterface::print_error(&format!("Editor failed: {}", e));
+                    }
+                } else {
+                    UserInterface::page(&format!("\n{}\n\n", answer.trim_end()));
+                }
+                Ok(true)
+            }
+            Command::Open(n) => {
+                self.open_source(n);
+                Ok(true)
+            }
+            Command::Diagram(args) => {
+                self.draw_diagram(&args);
+                Ok(true)
+            }
+            Command::Expand(n) => {
+                match n.unwrap_or(self.tool_runs.len()) {
+                    0 if self.tool_runs.is_empty() => note!("No tool calls yet\n"),
+                    n if n == 0 || n > self.tool_runs.len() => note!(
+                        "No tool call {}; there have been {}\n",
+                        n,
+                        self.tool_runs.len()
+                    ),
+                    n => {
+                        let run = &self.tool_runs[n - 1];
+                        UserInterface::page(&format!(
+                            "\n{}\n\n{}\n\n",
+                            format!("[{}] {}({})", n, run.name, run.arguments).bold(),
+                            run.output.trim_end()
+                        ));
+                    }
+                }
+                Ok(true)
+            }
+            Command::Thinking(None) => {
+                match &self.last_reasoning {
+                    Some(reasoning) => UserInterface::page(&format!(
+                        "\n{}\n\n{}\n\n",
+                        "Thinking".bold(),
+                        reasoning.trim_end()
+                    )),
+                    None => note!("The last answer came without reasoning\n"),
+                }
+                Ok(true)
+            }
+            Command::Thinking(Some(show)) => {
+                self.show_thinking = show;
+                if show {
+                    note!("Reasoning is shown in full\n");
+                } else {
+                    note!("Reasoning is folded into one line; /thinking shows it\n");
+                }
+                Ok(true)
+            }
+            Command::SearchFirst(content) => {
+                self.next_tool_choice = Some(ToolChoice::Tool("brave_search".to_string()));
+                self.send(&content).await?;
+                self.next_tool_choice = None;
+                Ok(true)
+            }
+            Command::AddDir(path) => {
+                self.add_dir(&path);
+                Ok(true)
+            }
+            Command::Checkpoint(name) if name.is_empty() => {
+                self.show_checkpoints();
+                Ok(true)
+            }
+            Command::Checkpoint(name) => {
+                let name = name.split_whitespace().collect::<Vec<_>>().join("-");
+                let moved = self.conversation_manager.checkpoint(&name);
+                let turn = self.turns_before(self.conversation_manager.get_messages().len());
+                let verb = if moved { "moved" } else { "set" };
+                note!(
+                    "Checkpoint '{}' {} after turn {}; /rewind {} comes back here\n",
+                    name,
+                    verb,
+                    turn,
+                    name
+                );
+                Ok(true)
+            }
+            Command::Rewind(name) if name.is_empty() => {
+                self.show_checkpoints();
+                Ok(true)
+            }
+            Command::Rewind(name) => {
+                match self.conversation_manager.rewind_to(&name) {
+                    Ok(0) => note!("Already at checkpoint '{}'\n", name),
+                    Ok(dropped) => note!(
+                        "Rewound to checkpoint '{}', dropping {} message(s)\n",
+                        name,
+                        dropped
+                    ),
+                    Err(e) => {
+                        UserInterface::print_error(&format!("{}. Run /checkpoint to list them.", e))
+                    }
+                }
+                Ok(true)
+            }
+            Command::FollowUps(None) => {
+                match self.current_follow_ups() {
+                    Some(follow_ups) => UserInterface::print_follow_ups(follow_ups),
+                    None if self.suggest_follow_ups => {
+                        note!("No follow-ups for the last answer\n")
+                    }
+                    None => note!("Follow-ups are off; /followups on offers them\n"),
+                }
+                Ok(true)
+            }
+            Command::FollowUps(Some(on)) => {
+                self.suggest_follow_ups = on;
+                if on {
+                    note!("Each answer is followed by prompts to send next; type a number to send one\n");
+                } else {
+                    self.follow_ups.clear();
+                    note!("No longer suggesting follow-ups\n");
+                }
+                Ok(true)
+            }
+            Command::Message(content) => {
+                // A number alone sends that follow-up of the last answer.
+                let picked = content
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| self.current_follow_ups()?.get(n.checked_sub(1)?))
+                    .cloned();
+                let content = match picked {
+                    Some(follow_up) => {
+                        note!("{}\n", follow_up.dimmed());
+                        follow_up
+                    }
+                    None => content,
+                };
+                self.follow_ups.clear();
+                self.send(&content).await?;
+                if self.suggest_follow_ups && self.translation.is_none() {
+                    self.offer_follow_ups().await;
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    /// The text of the latest answer that has any, as the model wrote it.
+    fn last_answer(&self) -> Option<&str> {
+        self.conversation_manager
+            .get_messages()
+            .iter()
+            .rev()
+            .filter(|m| m.role == "assistant")
+            .find_map(|m| m.content.as_deref().filter(|c| !c.trim().is_empty()))
+    }
+
+    /// `/copy` puts the last answer's Markdown on the clipboard; `/copy
+    /// code` only its code blocks, separated by blank lines.
+    fn copy_last_answer(&self, code: bool) {
+        let Some(answer) = self.last_answer() else {
+            note!("No answer to copy yet\n");
+            return;
+        };
+        let (text, what) = if code {
+            let blocks = clipboard::code_blocks(answer);
+            if blocks.is_empty() {
+                note!("The last answer has no code blocks\n");
+                return;
+            }
+            let what = format!(
+                "{} code block{}",
+                blocks.len(),
+                if blocks.len() == 1 { "" } else { "s" }
+            );
+            (blocks.join("\n\n"), what)
+        } else {
+            (answer.to_string(), "the last answer".to_string())
+        };
+        match clipboard::write(&text) {
+            Ok(()) => note!("Copied {} to the clipboard\n", what),
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// `/open <n>` opens the `n`th source the last answer cites in the
+    /// browser.
+    fn open_source(&self, n: usize) {
+        let answer = self.last_answer();
+        let urls = answer.map(citations::cited_urls).unwrap_or_default();
+        let Some(url) = urls.get(n - 1) else {
+            match urls.len() {
+                0 => note!("The last answer cites no sources\n"),
+                1 => note!("The last answer cites only one source\n"),
+                count => note!("The last answer cites {} sources\n", count),
+            }
+            return;
+        };
+        match browser::open(url) {
+            Ok(()) => note!("Opened {}\n", url),
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// `/diagram [n] [text]` draws the `n`th Mermaid or Graphviz diagram of
+    /// the last answer: inline on terminals that show images, in the image
+    /// viewer elsewhere, or as text with `text` or when it cannot be drawn.
+    fn draw_diagram(&self, args: &str) {
+        let mut n = 1;
+        let mut text = false;
+        for word in args.split_whitespace() {
+            match word {
+                "text" => text = true,
+                _ => match word.parse::<usize>() {
+                    Ok(number) if number > 0 => n = number,
+                    _ => return UserInterface::print_error("Usage: /diagram [n] [text]"),
+                },
+            }
+        }
+        let answer = self
+            .conversation_manager
+            .get_messages()
+            .iter()
+            .rev()
+            .filter(|m| m.role == "assistant")
+            .find_map(|m| m.content.as_deref().filter(|c| !c.trim().is_empty()));
+        let diagrams = answer.map(diagram::find).unwrap_or_default();
+        let Some(diagram) = diagrams.get(n - 1) else {
+            match diagrams.len() {
+                0 => note!("The last answer has no Mermaid or Graphviz diagram\n"),
+                count => note!("The last answer has {} diagrams\n", count),
+            }
+            return;
+        };
+
+        if !text {
+            let path = std::env::temp_dir().join(format!("rusty-diagram-{}.png", n));
+            let drawn = diagram.render(&path).and_then(|()| {
+                match console::graphics() {
+                    Some(graphics) => {
+                        let png = fs::read(&path)
+                            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                        println!();
+                        print!("{}", console::image(graphics, &png));
+                        println!();
+                    }
+                    None => {
+                        browser::open(&path.to_string_lossy())?;
+                        note!("Opened {}\n", path.display());
+                    }
+                }
+                Ok(())
+            });
+            match drawn {
+                Ok(()) => return,
+                Err(e) => UserInterface::print_error(&format!("{}; showing it as text", e)),
+            }
+        }
+        println!();
+        println!("{}", diagram.text());
+        println!();
+    }
+
+    /// Sends a typed message, with any clipboard waiting from `/paste` and
+    /// the files it mentions with `@path` appended, to the model, or to the
+    /// translator when `/translate` mode is on.
+    async fn send(&mut self, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let content = match self.pending_paste.take() {
+            Some(block) => format!("{}\n\n{}", content, block),
+            None => content.to_string(),
+        };
+        let content = match prompts::attach_mentions(&content, &self.ocr, &self.extra_dirs).await {
+            Ok((content, paths)) => {
+                if !paths.is_empty() {
+                    note!("Attached {}", paths.join(", "));
+                }
+                content
+            }
+            Err(e) => {
+                UserInterface::print_error(&e);
+                return Ok(());
+            }
+        };
+        match (self.translation.clone(), self.compare.clone()) {
+            (Some(language), _) => self.translate(&language, &content).await,
+            (None, Some(model)) => self.compare_turn(&model, &content).await?,
+            (None, None) => self.process_message(&content).await?,
+        }
+        Ok(())
+    }
+
+    /// The follow-ups offered after the last answer, unless the
+    /// conversation has changed since.
+    fn current_follow_ups(&self) -> Option<&[String]> {
+        let len = self.conversation_manager.get_messages().len();
+        (!self.follow_ups.is_empty() && self.follow_ups_after == len)
+            .then_some(self.follow_ups.as_slice())
+    }
+
+    /// Asks for prompts to send after the answer just given and lists
+    /// them, numbered. Nothing is shown when the turn failed or the
+    /// request does; they are a help, not part of the answer.
+    async fn offer_follow_ups(&mut self) {
+        let messages = self.conversation_manager.get_messages();
+        let answered = messages
+            .last()
+            .is_some_and(|m| m.role == "assistant" && m.content.is_some());
+        if !answered {
+            return;
+        }
+        let mut spinner = UserInterface::print_thinking(self.animate_spinner());
+        let result = conversation::follow_ups(
+            &self.provider,
+            self.model_manager.get_current_model(),
+            messages,
+            FOLLOW_UPS,
+        )
+        .await;
+        spinner.pause();
+        match result {
+            Ok(follow_ups) if !follow_ups.is_empty() => {
+                UserInterface::print_follow_ups(&follow_ups);
+                self.follow_ups = follow_ups;
+                self.follow_ups_after = self.conversation_manager.get_messages().len();
+            }
+            Ok(_) => {}
+            Err(e) => diagnostics::log(&e),
+        }
+    }
+
+    /// `/checkpoint` or `/rewind` alone: the checkpoints, with the turn each
+    /// was set after.
+    fn show_checkpoints(&self) {
+        let checkpoints = self.conversation_manager.checkpoints();
+        if checkpoints.is_empty() {
+            note!("No checkpoints. /checkpoint <name> sets one\n");
+            return;
+        }
+        note!("Checkpoints:");
+        for checkpoint in checkpoints {
+            println!(
+                "     {}  after turn {}",
+                checkpoint.name,
+                self.turns_before(checkpoint.len)
+            );
+        }
+        println!();
+    }
+
+    /// How many prompts there are among the first `len` messages.
+    fn turns_before(&self, len: usize) -> usize {
+        self.conversation_manager.get_messages()[..len]
+            .iter()
+            .filter(|message| message.role == "user")
+            .count()
+    }
+
+    /// `/add-dir <path>` adds a directory to the workspace, where `@path`
+    /// mentions are also looked for; `/add-dir` alone lists the workspace.
+    fn add_dir(&mut self, path: &str) {
+        if path.is_empty() {
+            let cwd = std::env::current_dir().unwrap_or_default();
+            note!("Workspace:");
+            println!("     {} (started in)", cwd.display());
+            for dir in &self.extra_dirs {
+                println!("     {}", dir.display());
+            }
+            println!();
+            return;
+        }
+        let dir = match (path.strip_prefix("~/"), std::env::var("HOME")) {
+            (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+            _ => PathBuf::from(path),
+        };
+        let dir = match dir.canonicalize() {
+            Ok(dir) if dir.is_dir() => dir,
+            Ok(_) => {
+                UserInterface::print_error(&format!("{} is not a directory", path));
+                return;
+            }
+            Err(e) => {
+                UserInterface::print_error(&format!("Cannot add {}: {}", path, e));
+                return;
+            }
+        };
+        if self.extra_dirs.contains(&dir)
+            || std::env::current_dir()
+                .is_ok_and(|cwd| cwd.canonicalize().is_ok_and(|cwd| cwd == dir))
+        {
+            note!("{} is already in the workspace\n", dir.display());
+            return;
+        }
+        note!("Added {} to the workspace\n", dir.display());
+        self.extra_dirs.push(dir);
+    }
+
+    /// `/compare <model>` sends every prompt to `<model>` as well as the
+    /// current model, until `/compare off`.
+    fn compare_command(&mut self, args: &str) {
+        let current = self.model_manager.get_current_model().to_string();
+        match args {
+            "" => match &self.compare {
+                Some(model) => note!(
+                    "Comparing {} with {}; /compare off to stop\n",
+                    current,
+                    model
+                ),
+                None => note!("Usage: /compare <model>, or /compare off\n"),
+            },
+            "off" => match self.compare.take() {
+                Some(_) => note!("Compare mode off\n"),
+                None => note!("Compare mode is not on\n"),
+            },
+            choice => match ModelManager::find_models(choice).as_slice() {
+                [model] if *model == current => {
+                    UserInterface::print_error(&format!("{} is already the current model", model))
+                }
+                [model] => {
+                    self.compare = Some(model.to_string());
+                    note!(
+                        "Compare mode on: each prompt goes to {} and {}, and you pick the answer to keep\n",
+                        current,
+                        model
+                    );
+                }
+                [] => UserInterface::print_error(&format!(
+                    "No model matches '{}'; /model lists them",
+                    choice
+                )),
+                matches => UserInterface::print_error(&format!(
+                    "'{}' matches {} models; name one of them",
+                    choice,
+                    matches.len()
+                )),
+            },
+        }
+    }
+
+    /// Describes the machine to the model afresh, as the working directory
+    /// and the repository may have changed since the last turn.
+    fn refresh_environment(&mut self) {
+        self.conversation_manager
+            .set_environment(environment::note(&self.environment), self.environment.date);
+    }
+
+    /// A turn in compare mode: the prompt goes to the current model and to
+    /// `other` at once, without tools, and their answers are shown side by
+    /// side. The one picked becomes the answer in the conversation. The
+    /// prompt goes through the same checks as any other, for both models.
+    async fn compare_turn(
+        &mut self,
+        other: &str,
+        content: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(content) = self.prepare_user_message(content).await else {
+            return Ok(());
+        };
+        let current = self.model_manager.get_current_model().to_string();
+        self.refresh_environment();
+        self.conversation_manager.add_user_message(&content);
+        let window = context_window(&current).min(context_window(other));
+        let messages = match self.conversation_manager.fit(window, 0) {
+            Ok(messages) => messages,
+            Err(e) => {
+                UserInterface::print_error(&e);
+                self.conversation_manager.rewind_last_user_message();
+                return Ok(());
+            }
+        };
+        if self.dry_run {
+            for model in [current.as_str(), other] {
+                self.show_dry_run(model, &messages, None, &ChatOptions::default());
+            }
+            self.conversation_manager.rewind_last_user_message();
+            return Ok(());
+        }
+        for model in [current.as_str(), other] {
+            if let Some(cap) = self.spending_cap_reached(model) {
+                UserInterface::print_error(&format!(
+                    "{}: {}; start Rusty with --ignore-caps to go on",
+                    model, cap
+                ));
+                self.fail(Failure::Other);
+                self.conversation_manager.rewind_last_user_message();
+                return Ok(());
+            }
+        }
+        let estimate = conversation::estimate_tokens(&messages) as u64;
+        // Both requests count towards the session's budget.
+        let approved = match self.budget_overrun(2 * estimate) {
+            Some(overrun) => self.approve_over_budget(&overrun).await?,
+            None => true,
+        };
+        let approved = approved
+            && match self.large_request(estimate) {
+                Some(warning) => self.approve_large_request(&warning).await?,
+                None => true,
+            };
+        if !approved {
+            note!("Not sent\n");
+            self.fail(Failure::Other);
+            self.conversation_manager.rewind_last_user_message();
+            return Ok(());
+        }
+
+        let mut spinner = UserInterface::print_thinking(self.animate_spinner());
+        let type_ahead = self.start_type_ahead(&mut spinner);
+        let (first, second) = tokio::join!(
+            self.provider.chat_completion(&current, &messages, None),
+            self.provider.chat_completion(other, &messages, None),
+        );
+        spinner.pause();
+        self.keep_typed(type_ahead);
+
+        let mut answers = Vec::new();
+        for (model, result) in [(current.as_str(), first), (other, second)] {
+            match result {
+                Ok(reply) => {
+                    if let Some(usage) = &reply.usage {
+                        self.usage.add(usage);
+                        self.record_spending(model, usage);
+                    }
+                    let answer = reply.content.unwrap_or_default();
+                    if let Some(answer) = self.checked_answer(model, answer).await {
+                        answers.push((model.to_string(), answer));
+                    }
+                }
+                Err(e) => {
+                    UserInterface::print_error(&format!("{}: {}", model, e));
+                    self.fail(Failure::of(&e));
+                }
+            }
+        }
+        let winner = match answers.as_slice() {
+            [] => None,
+            [(model, answer)] => {
+                UserInterface::print_assistant_response(
+                    &self.pii.restore(answer),
+                    self.code_theme.as_deref(),
+                );
+                note!("Only {} answered, so its answer is kept\n", model);
+                Some(0)
+            }
+            [(first_model, first), (second_model, second), ..] => {
+                UserInterface::print_side_by_side(
+                    (&format!("[1] {}", first_model), &self.pii.restore(first)),
+                    (&format!("[2] {}", second_model), &self.pii.restore(second)),
+                );
+                self.pick_winner(&answers).await?
+            }
+        };
+        match winner {
+            Some(i) => {
+                let (model, answer) = &answers[i];
+                self.conversation_manager
+                    .add_message(Message::assistant(answer));
+                if answers.len() > 1 {
+                    note!("Kept the answer from {}\n", model);
+                }
+                let report = self.command_hooks.post_response(answer, model).await;
+                UserInterface::print_hook_output(&report.output);
+            }
+            None => {
+                self.conversation_manager.rewind_last_user_message();
+                note!("Neither answer kept\n");
+            }
+        }
+        Ok(())
+    }
+
+    /// A compared answer after the response hooks and moderation, as any
+    /// answer goes through them, or `None` when one of them blocks it.
+    async fn checked_answer(&mut self, model: &str, answer: String) -> Option<String> {
+        let answer = match self.hooks.on_response(&answer) {
+            Ok(HookOutcome::Keep) => answer,
+            Ok(HookOutcome::Replace(answer)) => answer,
+            Ok(HookOutcome::Block(reason)) => {
+                note!("{}: response blocked by hook: {}\n", model, reason);
+                return None;
+            }
+            Err(e) => {
+                UserInterface::print_error(&e);
+                answer
+            }
+        };
+        match self.moderator.check(&answer, Direction::Output).await {
+            Verdict::Block(reason) => {
+                note!("{}: answer blocked by moderation: {}\n", model, reason);
+                return None;
+            }
+            Verdict::Warn(reason) => note!("{}: moderation warning: {}\n", model, reason),
+            Verdict::Allow => {}
+        }
+        Some(answer)
+    }
+
+    /// Which of the compared answers to keep: picked from a list on a
+    /// terminal, or typed as its number otherwise. `None` keeps neither.
+    async fn pick_winner(
+        &mut self,
+        answers: &[(String, String)],
+    ) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+        if ModelManager::can_pick() {
+            let choices: Vec<picker::Choice> = answers
+                .iter()
+                .map(|(model, answer)| picker::Choice {
+                    label: model.clone(),
+                    detail: format!("{} words", answer.split_whitespace().count()),
+                })
+                .collect();
+            return Ok(picker::pick(
+                "Which answer wins?",
+                "↑/↓ to move · Enter to keep it · Esc to keep neither",
+                &choices,
+                0,
+            )?);
+        }
+        print!("  Keep which answer? [1/2, or Enter for neither] ");
+        io::stdout().flush()?;
+        let mut choice = String::new();
+        self.reader.read_line(&mut choice).await?;
+        Ok(choice
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=answers.len()).contains(n))
+            .map(|n| n - 1))
+    }
+
+    /// `/translate <lang> <text>` translates once; `/translate <lang>` alone
+    /// translates every message until `/translate off`.
+    async fn translate_command(&mut self, args: &str) {
+        let (language, text) = match args.split_once(char::is_whitespace) {
+            Some((language, text)) => (language, text.trim()),
+            None => (args, ""),
+        };
+        match (language, text) {
+            ("", _) => match &self.translation {
+                Some(language) => note!(
+                    "Translating every message into {}; /translate off to stop\n",
+                    language
+                ),
+                None => note!("Usage: /translate <language> [text], or /translate off\n"),
+            },
+            ("off", "") => match self.translation.take() {
+                Some(_) => note!("Translation mode off\n"),
+                None => note!("Translation mode is not on\n"),
+            },
+            (language, "") => {
+                note!(
+                    "Translating every message into {} until /translate off\n",
+                    language
+                );
+                self.translation = Some(language.to_string());
+            }
+            (language, text) => self.translate(language, text).await,
+        }
+    }
+
+    /// `/json on` asks for every answer as a JSON object, `/json schema
+    /// <file>` for JSON matching the schema in the file (or given inline),
+    /// until `/json off`.
+    fn json_command(&mut self, args: &str) {
+        let (action, rest) = match args.split_once(char::is_whitespace) {
+            Some((action, rest)) => (action, rest.trim()),
+            None => (args, ""),
+        };
+        match (action, rest) {
+            ("", _) => match &self.json_format {
+                Some(ResponseFormat::Object) => {
+                    note!("Answers are JSON objects; /json off to stop\n")
+                }
+                Some(ResponseFormat::Schema { name, .. }) => {
+                    note!("Answers follow the schema '{}'; /json off to stop\n", name)
+                }
+                None => note!("Usage: /json on, /json schema <file>, or /json off\n"),
+            },
+            ("on", "") => {
+                note!("Answers are JSON objects until /json off\n");
+                self.json_format = Some(ResponseFormat::Object);
+            }
+            ("off", "") => match self.json_format.take() {
+                Some(_) => note!("JSON mode off\n"),
+                None => note!("JSON mode is not on\n"),
+            },
+            ("schema", source) if !source.is_empty() => {
+                let (name, text) = if source.starts_with('{') {
+                    ("schema".to_string(), source.to_string())
+                } else {
+                    let path = Path::new(source);
+                    let name: String = path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default()
+                        .chars()
+                        .map(|c| {
+                            if c.is_ascii_alphanumeric() || c == '-' {
+                                c
+                            } else {
+                                '_'
+                            }
+                        })
+                        .collect();
+                    match fs::read_to_string(path) {
+                        Ok(text) => (name, text),
+                        Err(e) => {
+                            UserInterface::print_error(&format!(
+                                "Failed to read {}: {}",
+                                source, e
+                            ));
+                            return;
+                        }
+                    }
+                };
+                let format = serde_json::from_str(&text)
+                    .map_err(|e| format!("Invalid schema: {}", e))
+                    .and_then(|schema| ResponseFormat::schema(&name, schema));
+                match format {
+                    Ok(format) => {
+                        note!("Answers follow the schema '{}' until /json off\n", name);
+                        self.json_format = Some(format);
+                    }
+                    Err(e) => UserInterface::print_error(&e),
+                }
+            }
+            _ => note!("Usage: /json on, /json schema <file>, or /json off\n"),
+        }
+    }
+
+    /// `/set <key> <value>` changes a setting for this session, and with
+    /// `--save` in the configuration too; `/set <key>` takes it back to the
+    /// configured value.
+    fn set_command(&mut self, args: &str) {
+        let (save, args) = match args.strip_prefix("--save") {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, args),
+        };
+        let (key, value) = match args.split_once(char::is_whitespace) {
+            Some((key, value)) => (key, value.trim()),
+            None => (args, ""),
+        };
+        let tool = match key {
+            "tool_choice" if !["", "auto", "none", "required"].contains(&value) => Some(value),
+            key => key.strip_prefix("tools."),
+        };
+        if let Some(tool) = tool {
+            if !self.tool_names().iter().any(|name| name == tool) {
+                note!("No tool '{}'. /get tools lists them\n", tool);
+                return;
+            }
+        }
+        let result = match (key, value, save) {
+            ("", ..) => {
+                note!("Usage: /set [--save] <key> [value]; /get lists the settings\n");
+                return;
+            }
+            (key, "", false) => self
+                .settings
+                .reset(key, &self.default_settings)
+                .map(|()| format!("{} is back to its configured value", key)),
+            (_, "", true) => Err("Give the value to save".to_string()),
+            (key, value, false) => self
+                .settings
+                .set(key, value)
+                .map(|()| format!("Set {} for this session", key)),
+            (key, value, true) => self.settings.set(key, value).and_then(|()| {
+                let value = self.settings.get(key)?.unwrap_or_default();
+                Config::set_toml("settings", key, &value)?;
+                self.default_settings.set(key, &value)?;
+                Ok(format!(
+                    "Set {} and saved it to {}",
+                    key,
+                    Config::path().display()
+                ))
+            }),
+        };
+        match result {
+            Ok(message) => {
+                self.apply_settings();
+                note!("{}\n", message);
+            }
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// `/get` lists the settings that are set, `/get <key>` shows one, and
+    /// `/get tools` which tools are offered.
+    fn get_command(&self, key: &str) {
+        match key {
+            "" => {
+                let lines = self.settings.describe();
+                if lines.is_empty() {
+                    note!("Nothing set; the defaults apply\n");
+                    return;
+                }
+                note!("{}", lines.join("\n     "));
+                println!();
+            }
+            "tools" => {
+                for name in self.tool_names() {
+                    let offered = self
+                        .settings
+                        .tool_enabled(&name, self.offered_by_default(&name));
+                    println!("     {} {}", if offered { "on " } else { "off" }, name);
+                }
+                println!();
+            }
+            key => match self.settings.get(key) {
+                Ok(Some(value)) => note!("{} = {}\n", key, value),
+                Ok(None) if key == "search_count" => {
+                    note!(
+                        "search_count = {} (default)\n",
+                        self.settings.search_count()
+                    )
+                }
+                Ok(None) => note!("{} is not set; the API's default applies\n", key),
+                Err(e) => UserInterface::print_error(&e),
+            },
+        }
+    }
+
+    /// `/tools` lists every tool, whether it is offered now and what limits
+    /// its calls; `/tools enable <tool>` and `/tools disable <tool>` switch
+    /// one for this session, as `/set tools.<tool>` does.
+    fn tools_command(&mut self, args: &str) {
+        let args: Vec<&str> = args.split_whitespace().collect();
+        let (on, name) = match args[..] {
+            [] => return self.list_tools(),
+            ["stats"] => return self.show_tool_stats(),
+            ["enable" | "on", name] => (true, name),
+            ["disable" | "off", name] => (false, name),
+            _ => {
+                UserInterface::print_error("Usage: /tools [stats|enable <tool>|disable <tool>]");
+                return;
+            }
+        };
+        if self.tools.get(name).is_none() {
+            UserInterface::print_error(&format!("No tool is called '{}'; /tools lists them", name));
+            return;
+        }
+        let state = if on { "on" } else { "off" };
+        match self.settings.set(&format!("tools.{}", name), state) {
+            Ok(()) => {
+                self.apply_settings();
+                note!("{} is {} for this session\n", name, state);
+            }
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// How often each tool was called, how often it failed and how long it
+    /// took, since Rusty started and in all.
+    fn show_tool_stats(&self) {
+        if self.tool_stats.is_empty() {
+            note!("No tools called since Rusty started\n");
+        } else {
+            note!("Since Rusty started:\n");
+            let stats: Vec<(String, ToolStats)> = self
+                .tool_stats
+                .iter()
+                .map(|(tool, stats)| (tool.clone(), stats.clone()))
+                .collect();
+            println!("{}\n", tool_stats_table(&stats));
+        }
+        match self.ledger.as_ref().map(Ledger::tool_stats) {
+            Some(Ok(stats)) if !stats.is_empty() => {
+                note!("In all:\n");
+                println!("{}\n", tool_stats_table(&stats));
+            }
+            Some(Err(e)) => UserInterface::print_error(&e),
+            _ => {}
+        }
+    }
+
+    fn list_tools(&self) {
+        let mut rows = Vec::new();
+        for name in self.tool_names() {
+            let Some(tool) = self.tools.get(&name) else {
+                continue;
+            };
+            // Why a tool that is switched on is not offered right now.
+            let held_back = if self.network_down && tools::NETWORK_TOOLS.contains(&name.as_str()) {
+                Some("offline")
+            } else if self.workspace_trusted == Some(false) && tool.runs_programs() {
+                Some("workspace not trusted")
+            } else if self.read_only && tool.mutation() == Mutation::Always {
+                Some("read-only mode")
+            } else {
+                None
+            };
+            let state = match held_back {
+                _ if !self
+                    .settings
+                    .tool_enabled(&name, self.offered_by_default(&name)) =>
+                {
+                    "off".to_string()
+                }
+                Some(reason) => format!("off ({})", reason),
+                None => "on".to_string(),
+            };
+            let mut policy = Vec::new();
+            if tools::NETWORK_TOOLS.contains(&name.as_str()) {
+                policy.push("network");
+            }
+            // Tools that reach a URL given in the call have it approved.
+            if tool.url(&serde_json::Value::Null).is_some() {
+                policy.push("asks before new hosts");
+            }
+            let changes;
+            if tool.runs_programs() {
+                policy.push("runs programs, trusted workspaces only");
+            } else {
+                match tool.mutation() {
+                    Mutation::Always => policy.push("makes changes, not in read-only mode"),
+                    Mutation::Sometimes(when) => {
+                        changes = format!("changes things {}, refused in read-only mode", when);
+                        policy.push(&changes);
+                    }
+                    Mutation::Never => {}
+                }
+            }
+            if policy.is_empty() {
+                policy.push("read-only");
+            }
+            rows.push((name, state, policy.join(", ")));
+        }
+        let name_width = rows.iter().map(|(name, ..)| name.len()).max().unwrap_or(0);
+        let state_width = rows
+            .iter()
+            .map(|(_, state, _)| state.len())
+            .max()
+            .unwrap_or(0);
+        for (name, state, policy) in rows {
+            println!(
+                "     {:<name_width$}  {:<state_width$}  {}",
+                name,
+                state,
+                policy.dimmed()
+            );
+        }
+        if !model_info(self.model_manager.get_current_model()).tools {
+            note!(
+                "{} cannot call tools, so none are offered",
+                self.model_manager.get_current_model()
+            );
+        }
+        println!();
+    }
+
+    /// Every tool the model can be offered, whether or not it is now.
+    fn every_tool(&self) -> Vec<ToolDefinition> {
+        self.tools.definitions()
+    }
+
+    fn tool_names(&self) -> Vec<String> {
+        self.every_tool()
+            .into_iter()
+            .map(|tool| tool.function.name)
+            .collect()
+    }
+
+    /// Whether the tool called `name` is offered without a setting:
+    /// `search_docs` once there is an index, `read_clipboard` when the
+    /// configuration allows it, and everything else always.
+    fn offered_by_default(&self, name: &str) -> bool {
+        match name {
+            "search_docs" => self.documents.is_some(),
+            "read_clipboard" => self.clipboard_tool,
+            _ => true,
+        }
+    }
+
+    /// Translates `text` and shows it beside the original. Both are kept in
+    /// the conversation, so they can be discussed, saved and exported. The
+    /// text goes through the same checks as any prompt.
+    async fn translate(&mut self, language: &str, text: &str) {
+        let Some(masked) = self.prepare_user_message(text).await else {
+            return;
+        };
+        let model = self.model_manager.get_current_model().to_string();
+        if self.dry_run {
+            let request = conversation::translation_request(language, &masked);
+            self.show_dry_run(&model, &request, None, &ChatOptions::default());
+            return;
+        }
+        if let Some(cap) = self.spending_cap_reached(&model) {
+            UserInterface::print_error(&format!(
+                "{}; start Rusty with --ignore-caps to go on",
+                cap
+            ));
+            self.fail(Failure::Other);
+            return;
+        }
+        let mut spinner = UserInterface::print_thinking(self.animate_spinner());
+        let type_ahead = self.start_type_ahead(&mut spinner);
+        let result = conversation::translate(&self.provider, &model, language, &masked).await;
+        spinner.pause();
+        self.keep_typed(type_ahead);
+        match result {
+            Ok((translation, usage)) => {
+                if let Some(usage) = &usage {
+                    self.usage.add(usage);
+                    self.record_spending(&model, usage);
+                }
+                UserInterface::print_side_by_side(
+                    ("Original", text),
+                    (language, &self.pii.restore(&translation)),
+                );
+                self.conversation_manager.add_user_message(&masked);
+                self.conversation_manager
+                    .add_message(Message::assistant(&translation));
+            }
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// `/index` describes the document index; `/index <dir>` rebuilds it.
+    async fn index_command(&mut self, dir: Option<&str>) {
+        match dir {
+            Some(dir) => {
+                let result =
+                    index_documents(Path::new(dir), &self.embedder, &self.index_config).await;
+                match result {
+                    Ok(retriever) => self.documents = Some(retriever),
+                    Err(e) => UserInterface::print_error(&e),
+                }
+            }
+            None => match self.documents.as_ref().map(Retriever::info) {
+                Some(info) => note!(
+                    "{} chunks from {} files in {}, embedded with {}\n",
+                    info.chunks,
+                    info.files,
+                    info.root.display(),
+                    info.embedder
+                ),
+                None => note!("No documents indexed yet; run /index <dir>\n"),
+            },
+        }
+    }
+
+    /// `/crawl <url> [--depth n] [--max n]` reads the pages a site links to
+    /// from `url`, saves their text under the crawls directory and indexes
+    /// it, replacing the document index.
+    async fn crawl_command(&mut self, args: &str) {
+        let usage = "Usage: /crawl <url> [--depth n] [--max n]";
+        let mut url = None;
+        let mut depth = CRAWL_DEPTH;
+        let mut max = CRAWL_MAX;
+        let mut words = args.split_whitespace();
+        while let Some(word) = words.next() {
+            let limit = match word {
+                "--depth" => &mut depth,
+                "--max" => &mut max,
+                _ if url.is_none() => {
+                    url = Some(word);
+                    continue;
+                }
+                _ => return UserInterface::print_error(usage),
+            };
+            match words.next().and_then(|n| n.parse().ok()) {
+                Some(n) => *limit = n,
+                None => return UserInterface::print_error(usage),
+            }
+        }
+        let Some(url) = url else {
+            return UserInterface::print_error(usage);
+        };
+        if max == 0 {
+            return UserInterface::print_error("--max must be at least 1");
+        }
+
+        note!("Crawling {} (depth {}, up to {} pages)...", url, depth, max);
+        let pages = self
+            .fetcher
+            .crawl(url, depth, max, |url, page| match page {
+                Ok(_) => println!("     {}", url),
+                Err(e) => println!("     {} {}", url, format!("({})", e).dimmed()),
+            })
+            .await;
+        let pages = match pages {
+            Ok(pages) => pages,
+            Err(e) => return UserInterface::print_error(&e),
+        };
+
+        let site = pages[0].url.host_str().unwrap_or("site").to_string();
+        let dir = paths::crawls_dir().join(&site);
+        if let Err(e) = save_crawl(&dir, &pages) {
+            return UserInterface::print_error(&e);
+        }
+        note!("Read {} pages into {}\n", pages.len(), dir.display());
+        match index_documents(&dir, &self.embedder, &self.index_config).await {
+            Ok(retriever) => self.documents = Some(retriever),
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// `/memory files`: the `RUSTY.md` files whose instructions are sent,
+    /// in the order they are, with their size.
+    fn show_instruction_files(&self) {
+        if self.instruction_files.is_empty() {
+            note!(
+                "No {} files found; write one in {} or in the project\n",
+                instructions::FILE_NAME,
+                paths::config_dir().display()
+            );
+            return;
+        }
+        let gray = Color::TrueColor {
+            r: 100,
+            g: 100,
+            b: 100,
+        };
+        note!("Instructions read from, the later taking precedence:");
+        for (i, file) in self.instruction_files.iter().enumerate() {
+            let lines = file.text.lines().count();
+            println!(
+                "     [{}] {} {}",
+                i + 1,
+                file.path.display(),
+                format!(
+                    "({} line{}, ~{} tokens)",
+                    lines,
+                    if lines == 1 { "" } else { "s" },
+                    file.text.chars().count() / 4
+                )
+                .color(gray)
+            );
+        }
+        println!();
+    }
+
+    /// `/memory` lists remembered facts; `add <fact>`, `rm <n>` and `clear`
+    /// change them, and `files` lists the `RUSTY.md` files read.
+    fn manage_memory(&mut self, args: &str) {
+        let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+        let rest = rest.trim();
+        let result = match action {
+            "" => {
+                if self.memory.facts().is_empty() {
+                    note!("No memories yet\n");
+                } else {
+                    let orange = Color::TrueColor {
+                        r: 255,
+                        g: 140,
+                        b: 0,
+                    };
+                    note!("Remembered across sessions:");
+                    for (i, fact) in self.memory.facts().iter().enumerate() {
+                        println!("     {} {}", format!("[{}]", i + 1).color(orange), fact);
+                    }
+                    println!();
+                }
+                return;
+            }
+            "add" => self.memory.add(rest).map(|added| {
+                if added {
+                    let fact = self.memory.facts().last().map_or("", String::as_str);
+                    note!("Remembered: {}\n", fact);
+                } else {
+                    note!("Already remembered\n");
+                }
+            }),
+            "rm" | "forget" => match rest.parse::<usize>() {
+                Ok(n) if n > 0 => self
+                    .memory
+                    .remove(n - 1)
+                    .map(|fact| note!("Forgot: {}\n", fact)),
+                _ => Err("Usage: /memory rm <n>".to_string()),
+            },
+            "clear" => self.memory.clear().map(|_| note!("Forgot everything\n")),
+            "files" => {
+                self.show_instruction_files();
+                return;
+            }
+            _ => Err("Usage: /memory [add <fact>|rm <n>|clear|files]".to_string()),
+        };
+        match result {
+            Ok(()) => self
+                .conversation_manager
+                .set_memory(self.memory.system_prompt()),
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// Renders the conversation turn by turn, numbered from 1, through the
+    /// pager. With `only`, just that turn is shown.
+    fn show_history(&self, only: Option<usize>) {
+        let blue = Color::TrueColor {
+            r: 122,
+            g: 162,
+            b: 247,
+        };
+        let mut out = String::new();
+        let mut turn = 0;
+        for message in self.conversation_manager.get_messages() {
+            if message.role == "user" {
+                turn += 1;
+            }
+            if only.is_some_and(|n| n != turn) {
+                continue;
+            }
+            let content = self
+                .pii
+                .restore(message.content.as_deref().unwrap_or("").trim());
+            let content = content.as_str();
+            let accessible = UserInterface::accessible();
+            match message.role.as_str() {
+                "user" if accessible => out.push_str(&format!(
+                    "\n[{}] {} {}\n",
+                    turn,
+                    locale::t("labels.you"),
+                    content
+                )),
+                "user" => out.push_str(&format!(
+                    "\n{} {}\n",
+                    format!("[{}] ❯", turn).color(blue).bold(),
+                    content
+                )),
+                "assistant" => {
+                    for call in message.tool_calls.iter().flatten() {
+                        let call = format!("{} {}", call.function.name, call.function.arguments);
+                        if accessible {
+                            out.push_str(&format!("{} {}\n", locale::t("labels.tool"), call));
+                        } else {
+                            out.push_str(&format!("  ⎿  {}\n", call.dimmed()));
+                        }
+                    }
+                    if UserInterface::plain() && !content.is_empty() {
+                        out.push_str(&format!(
+                            "\n{}{}\n",
+                            UserInterface::assistant_label(),
+                            content
+                        ));
+                    } else if !content.is_empty() {
+                        out.push_str(&format!(
+                            "\n● {}",
+                            UserInterface::render_markdown(content, self.code_theme.as_deref())
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match (turn, only) {
+            (0, _) => note!("No turns yet\n"),
+            (total, Some(n)) if n == 0 || n > total => {
+                note!("No turn {}; this conversation has {}\n", n, total)
+            }
+            _ => UserInterface::page(&out),
+        }
+    }
+
+    /// `/messages` lists the messages of the conversation as they are kept,
+    /// tool messages included, and `/messages show <n>` prints one in full.
+    fn messages_command(&self, args: &str) {
+        let messages = self.conversation_manager.get_messages();
+        let orange = Color::TrueColor {
+            r: 255,
+            g: 140,
+            b: 0,
+        };
+        let tokens =
+            |message: &Message| conversation::estimate_tokens(std::slice::from_ref(message));
+        match args.split_whitespace().collect::<Vec<_>>()[..] {
+            [] if messages.is_empty() => note!("No messages yet\n"),
+            [] => {
+                let width = terminal::size()
+                    .map(|(width, _)| width as usize)
+                    .unwrap_or(80)
+                    .saturating_sub(28)
+                    .max(20);
+                for (i, message) in messages.iter().enumerate() {
+                    let calls = message.tool_calls.iter().flatten().map(|call| {
+                        format!("→ {}({})", call.function.name, call.function.arguments)
+                    });
+                    let first_line = message
+                        .content
+                        .as_deref()
+                        .and_then(|content| content.trim().lines().next())
+                        .map(str::to_string)
+                        .into_iter()
+                        .chain(calls)
+                        .next()
+                        .unwrap_or_default();
+                    println!(
+                        "  {} {:<9} {:>6}  {}",
+                        format!("{:>4}", format!("[{}]", i + 1)).color(orange),
+                        message.role,
+                        format!("~{}", tokens(message)),
+                        truncate(&first_line, width)
+                    );
+                }
+                let window = context_window(self.model_manager.get_current_model());
+                let request = self.conversation_manager.request_messages(window, 0);
+                let omitted = self.conversation_manager.omitted(&request);
+                note!(
+                    "{} messages, about {} tokens{}\n",
+                    messages.len(),
+                    conversation::estimate_tokens(messages),
+                    match omitted {
+                        0 => String::new(),
+                        n => format!("; {} left out of the next request to fit the window", n),
+                    }
+                );
+            }
+            ["show", n] => match n.parse::<usize>() {
+                Ok(n) if (1..=messages.len()).contains(&n) => {
+                    let message = &messages[n - 1];
+                    let mut out = format!(
+                        "{} {} · ~{} tokens\n",
+                        format!("[{}]", n).color(orange),
+                        message.role.bold(),
+                        tokens(message)
+                    );
+                    if let Some(id) = &message.tool_call_id {
+                        out.push_str(&format!("{}\n", format!("answers {}", id).dimmed()));
+                    }
+                    if let Some(content) = &message.content {
+                        out.push_str(&format!("\n{}\n", content));
+                    }
+                    for call in message.tool_calls.iter().flatten() {
+                        let arguments =
+                            serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+                                .and_then(|value| serde_json::to_string_pretty(&value))
+                                .unwrap_or_else(|_| call.function.arguments.clone());
+                        out.push_str(&format!(
+                            "\n→ {} {}\n{}\n",
+                            call.function.name,
+                            format!("({})", call.id).dimmed(),
+                            arguments
+                        ));
+                    }
+                    UserInterface::page(&out);
+                }
+                _ => note!(
+                    "No message {}; this conversation has {}\n",
+                    n,
+                    messages.len()
+                ),
+            },
+            _ => UserInterface::print_error("Usage: /messages [show <n>]"),
+        }
     }
 
-    async fn handle_command(
+    /// `/dump [path]` writes the messages array of the next request, system
+    /// prompt, date and memory notes included and pruned as it would be, as
+    /// JSON: to `path`, or to stdout so it can be copied into a bug report.
+    fn dump_messages(&self, path: Option<&str>) {
+        let window = context_window(self.model_manager.get_current_model());
+        let messages = self.conversation_manager.request_messages(window, 0);
+        let json = match serde_json::to_string_pretty(&messages) {
+            Ok(json) => json,
+            Err(e) => {
+                UserInterface::print_error(&format!("Cannot write the messages as JSON: {}", e));
+                return;
+            }
+        };
+        match path {
+            None => println!("{}\n", json),
+            Some(path) => match fs::write(path, format!("{}\n", json)) {
+                Ok(()) => note!("Wrote {} messages to {}\n", messages.len(), path),
+                Err(e) => UserInterface::print_error(&format!("Failed to write {}: {}", path, e)),
+            },
+        }
+    }
+
+    /// `/export [html|md|jsonl] [path]`; HTML by default, named after the session
+    /// title in the current directory unless a path is given.
+    fn export_session(&mut self, args: &str) {
+        if self.conversation_manager.get_messages().is_empty() {
+            note!("Nothing to export yet\n");
+            return;
+        }
+        let (format, path) = match args.split_once(char::is_whitespace) {
+            Some((format, path)) => (format, Some(path.trim())),
+            None if args.is_empty() => ("html", None),
+            None if !args.contains(['.', '/', '\\']) => (args, None),
+            None => ("", Some(args)),
+        };
+        // A bare path picks the format from its extension.
+        let format = match (format, path) {
+            ("", Some(path)) if path.ends_with(".md") => "md",
+            ("", Some(path)) if path.ends_with(".jsonl") => "jsonl",
+            ("", _) => "html",
+            (format, _) => format,
+        };
+        let extension = match format {
+            "html" => "html",
+            "md" | "markdown" => "md",
+            "jsonl" => "jsonl",
+            other => {
+                note!("Unknown export format '{}'. Use html, md or jsonl\n", other);
+                return;
+            }
+        };
+
+        self.sync_session();
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => PathBuf::from(format!(
+                "{}.{}",
+                slug(&self.session.display_title()),
+                extension
+            )),
+        };
+        let content = match extension {
+            "html" => export::to_html(&self.session),
+            "jsonl" => export::to_jsonl(std::slice::from_ref(&self.session), false),
+            _ => export::to_markdown(&self.session),
+        };
+        match fs::write(&path, content) {
+            Ok(()) => note!("Exported to {}\n", path.display()),
+            Err(e) => {
+                UserInterface::print_error(&format!("Failed to write {}: {}", path.display(), e))
+            }
+        }
+    }
+
+    /// Folds the oldest part of the conversation into a summary note so the
+    /// next request fits; on failure, pruning takes over.
+    async fn summarize_overflow(&mut self) {
+        let Some(range) = self.conversation_manager.oldest_chunk() else {
+            return;
+        };
+        let chunk = &self.conversation_manager.get_messages()[range.clone()];
+        let result = conversation::summarize(
+            &self.provider,
+            self.model_manager.get_current_model(),
+            chunk,
+        )
+        .await;
+        match result {
+            Ok(summary) => {
+                let gray = Color::TrueColor {
+                    r: 100,
+                    g: 100,
+                    b: 100,
+                };
+                note!(
+                    "{}",
+                    format!(
+                        "Summarized {} earlier messages to stay within the context window",
+                        range.len()
+                    )
+                    .color(gray)
+                );
+                self.conversation_manager
+                    .replace_with_summary(range, &summary);
+            }
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// Keeps a copy of the conversation after every turn, so a crash or a
+    /// closed terminal loses nothing. A clean exit removes it.
+    fn write_recovery(&mut self) {
+        if self.conversation_manager.get_messages().is_empty() {
+            self.sessions.clear_recovery();
+            return;
+        }
+        self.sync_session();
+        if let Err(e) = self.sessions.write_recovery(&self.session) {
+            UserInterface::print_error(&e);
+        }
+    }
+
+    /// Whether the workspace is trusted to run programs in. The first time
+    /// a tool would there, the user is asked, and the answer is written to
+    /// `[trust]` in the configuration file. Where nobody can be asked, the
+    /// workspace is not trusted, for this run only.
+    async fn trust_workspace(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(trusted) = self.workspace_trusted {
+            return Ok(trusted);
+        }
+        let Some(workspace) = self.workspace.clone() else {
+            self.workspace_trusted = Some(false);
+            return Ok(false);
+        };
+        if UserInterface::quiet() || !io::stdin().is_terminal() {
+            self.workspace_trusted = Some(false);
+            return Ok(false);
+        }
+        let orange = Color::TrueColor {
+            r: 255,
+            g: 140,
+            b: 0,
+        };
+        println!(
+            "  {}",
+            format!("Rusty has not run tools in {} before.", workspace.display())
+                .color(orange)
+                .bold()
+        );
+        print!(
+            "  Trust it? Plugins that run programs are only allowed in trusted workspaces. [y/N] "
+        );
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        self.reader.read_line(&mut answer).await?;
+        let trusted = matches!(answer.trim().to_lowercase().as_str(), "y" | "yes");
+        self.workspace_trusted = Some(trusted);
+        if let Err(e) = TrustConfig::remember(&workspace, trusted) {
+            UserInterface::print_error(&e);
+        }
+        Ok(trusted)
+    }
+
+    /// Adds the plugin tools. Describing an executable plugin runs it, so
+    /// those are only loaded once the workspace is trusted, which is asked
+    /// here when it was not decided yet.
+    async fn load_plugins(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = paths::plugins_dir();
+        let trusted = PluginHost::has_executables(&dir) && self.trust_workspace().await?;
+        let (plugins, warnings) = PluginHost::load(&dir, tools::BUILTIN_TOOL_NAMES, trusted).await;
+        for warning in &warnings {
+            UserInterface::print_error(warning);
+        }
+        for tool in plugins.tools() {
+            self.tools.register(Arc::new(tool.clone()));
+        }
+        Ok(())
+    }
+
+    /// What decides whether a tool call may run, as for the bots and the
+    /// MCP server.
+    fn policy(&self) -> Policy<'_> {
+        Policy {
+            read_only: self.read_only,
+            trusted: self.workspace_trusted,
+            egress: &self.egress,
+            allowlist: &self.http_allowlist,
+        }
+    }
+
+    /// Whether a tool may call `host`, which is not on the allowlist, as
+    /// the user is asked. "Always" allows the host until Rusty exits.
+    async fn approve_host(&mut self, host: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        // Nobody is there to ask in quiet mode.
+        if UserInterface::quiet() {
+            return Ok(false);
+        }
+        let orange = Color::TrueColor {
+            r: 255,
+            g: 140,
+            b: 0,
+        };
+        print!(
+            "  {} is not on your allowlist. Allow this request? [y/N/always] ",
+            host.color(orange).bold()
+        );
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        self.reader.read_line(&mut answer).await?;
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "a" | "always" => {
+                self.http_allowlist.add(host);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// What a request of about `estimate` tokens would bring the session
+    /// to, when that is over its token budget and past requests were not
+    /// allowed for the rest of it.
+    fn budget_overrun(&self, estimate: u64) -> Option<String> {
+        let budget = self.budget.session_tokens.filter(|_| !self.over_budget)?;
+        let used = self.usage.prompt + self.usage.completion;
+        (used + estimate > budget).then(|| {
+            format!(
+                "This request would bring the session to about {} tokens ({} used, about {} for this request), over the budget of {}",
+                used + estimate,
+                used,
+                estimate,
+                budget
+            )
+        })
+    }
+
+    /// The size and cost of a request of about `estimate` prompt tokens,
+    /// when that is over `[budget] request_tokens` and such requests were
+    /// not allowed for the rest of the session.
+    fn large_request(&self, estimate: u64) -> Option<String> {
+        let limit = self
+            .budget
+            .request_tokens
+            .filter(|_| !self.large_requests_allowed)?;
+        if estimate <= limit {
+            return None;
+        }
+        let model = self.model_manager.get_current_model();
+        let prompt = Usage {
+            prompt: estimate,
+            ..Usage::default()
+        };
+        Some(match model_info(model).price {
+            Some(price) => format!(
+                "This request is about {} prompt tokens, about ${:.4} on {} before the answer",
+                estimate,
+                spending::cost(&prompt, price),
+                model
+            ),
+            None => format!(
+                "This request is about {} prompt tokens, over the {} set to confirm",
+                estimate, limit
+            ),
+        })
+    }
+
+    /// `/telemetry status`: whether usage statistics are collected, where
+    /// they go, and what was counted and not sent yet.
+    fn show_telemetry(&self) {
+        if !self.telemetry.is_enabled() {
+            note!("Usage statistics are off; /telemetry on counts the commands and models you use and the errors you meet, never what you type\n");
+            return;
+        }
+        match self.telemetry.endpoint() {
+            Some(endpoint) => note!("Usage statistics are on, sent to {} when Rusty exits", endpoint),
+            None => note!("Usage statistics are on, kept on this machine; set endpoint under [telemetry] to send them"),
+        }
+        let counts = self.telemetry.counts();
+        if counts.is_empty() {
+            note!("Nothing counted yet\n");
+            return;
+        }
+        let width = [&counts.commands, &counts.models, &counts.errors]
+            .iter()
+            .flat_map(|map| map.keys())
+            .map(String::len)
+            .max()
+            .unwrap_or(0);
+        for (title, map) in [
+            ("Commands", &counts.commands),
+            ("Models", &counts.models),
+            ("Errors", &counts.errors),
+        ] {
+            if map.is_empty() {
+                continue;
+            }
+            println!("\n  {}", title.bold());
+            for (name, count) in map {
+                println!("    {:<width$}  {:>5}", name, count);
+            }
+        }
+        println!();
+    }
+
+    /// Notes that the turn failed with `failure`, for the exit code of a
+    /// piped run, unless something failed already, and counts it.
+    fn fail(&mut self, failure: Failure) {
+        self.failure.get_or_insert(failure);
+        self.telemetry.error(failure.name());
+    }
+
+    /// Which spending cap stops requests to `model`, which must have a
+    /// price, unless `--ignore-caps` was given.
+    fn spending_cap_reached(&self, model: &str) -> Option<String> {
+        let ledger = self.ledger.as_ref().filter(|_| !self.ignore_caps)?;
+        model_info(model).price?;
+        match ledger.cap_reached(&self.budget) {
+            Ok(cap) => cap,
+            Err(e) => {
+                UserInterface::print_error(&format!("Cannot check the spending caps: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Counts a tool call for `/tools stats`, for this run and in the
+    /// usage database.
+    fn record_tool_call(&mut self, name: &str, ok: bool, elapsed: Duration) {
+        self.tool_stats
+            .entry(name.to_string())
+            .or_default()
+            .record(ok, elapsed);
+        if let Some(ledger) = &self.ledger {
+            if let Err(e) = ledger.record_tool(name, ok, elapsed) {
+                UserInterface::print_error(&format!("Cannot record the tool call: {}", e));
+            }
+        }
+    }
+
+    /// Adds a request's usage, and its cost at the model's price, to the
+    /// ledger.
+    fn record_spending(&self, model: &str, usage: &Usage) {
+        let Some(ledger) = &self.ledger else {
+            return;
+        };
+        let cost = model_info(model)
+            .price
+            .map_or(0.0, |price| spending::cost(usage, price));
+        if let Err(e) = ledger.record(model, usage, cost) {
+            UserInterface::print_error(&format!("Cannot record usage: {}", e));
+        }
+    }
+
+    /// `/good` and `/bad` record what was thought of the last answer, with
+    /// the prompt it answered and the model in use.
+    fn rate_last_answer(&self, good: bool, note: &str) {
+        let Some(feedback) = &self.feedback else {
+            UserInterface::print_error("The feedback database could not be opened");
+            return;
+        };
+        let messages = self.conversation_manager.get_messages();
+        let Some(answer_at) = messages.iter().rposition(|m| {
+            m.role == "assistant" && m.content.as_deref().is_some_and(|c| !c.trim().is_empty())
+        }) else {
+            note!("No answer to rate yet\n");
+            return;
+        };
+        let prompt = messages[..answer_at]
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .and_then(|m| m.content.as_deref())
+            .unwrap_or_default();
+        let model = self.model_manager.get_current_model();
+        let rating = Rating::new(
+            model,
+            good,
+            prompt,
+            messages[answer_at].content.as_deref().unwrap_or_default(),
+            Some(note).filter(|note| !note.is_empty()),
+        );
+        match feedback.record(&rating) {
+            Ok(()) => note!(
+                "Rated the last answer from {} as {}\n",
+                model,
+                if good { "good" } else { "bad" }
+            ),
+            Err(e) => UserInterface::print_error(&format!("Cannot record the rating: {}", e)),
+        }
+    }
+
+    /// Asks whether to send a request over the token budget, `overrun`
+    /// saying by how much: this once, or with "always" every request after
+    /// it.
+    async fn approve_over_budget(
         &mut self,
-        command: Command,
-        _input: &str,
+        overrun: &str,
     ) -> Result<bool, Box<dyn std::error::Error>> {
-        match command {
-            Command::Quit => {
-                let goodbyes = [
-                    "Catch you on the flip side!",
-                    "Keep it 100!",
-                    "Stay classy!",
-                    "Later, alligator!",
-                    "See ya!",
-                    "Cheers!",
-                    "Bye!",
-                    "Until next time!",
-                ];
-                let mut rng = rand::rng();
-                let goodbye = goodbyes.choose(&mut rng).unwrap_or(&"Goodbye!");
-
-                println!("  ⎿  {}\n", goodbye);
-                Ok(false)
+        // Nobody is there to ask in quiet mode.
+        if UserInterface::quiet() {
+            UserInterface::print_error(overrun);
+            return Ok(false);
+        }
+        let orange = Color::TrueColor {
+            r: 255,
+            g: 140,
+            b: 0,
+        };
+        print!(
+            "  {}. Send it anyway? [y/N/always] ",
+            overrun.color(orange).bold()
+        );
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        self.reader.read_line(&mut answer).await?;
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "a" | "always" => {
+                self.over_budget = true;
+                Ok(true)
             }
-            Command::Stream => {
-                self.conversation_manager.toggle_stream_mode();
-                let status = if self.conversation_manager.is_stream_mode() {
-                    "ON"
-                } else {
-                    "OFF"
-                };
-                println!("  ⎿  Streaming mode: {}\n", status);
+            _ => Ok(false),
+        }
+    }
+
+    async fn approve_large_request(
+        &mut self,
+        warning: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        // Nobody is there to ask in quiet mode.
+        if UserInterface::quiet() {
+            UserInterface::print_error(warning);
+            return Ok(false);
+        }
+        let orange = Color::TrueColor {
+            r: 255,
+            g: 140,
+            b: 0,
+        };
+        print!("  {}. Send it? [y/N/always] ", warning.color(orange).bold());
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        self.reader.read_line(&mut answer).await?;
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "a" | "always" => {
+                self.large_requests_allowed = true;
                 Ok(true)
             }
-            Command::Clear => {
+            _ => Ok(false),
+        }
+    }
+
+    /// Puts the prompt left unsent last time back on the line.
+    fn restore_draft(&mut self) {
+        if UserInterface::plain() || !io::stdin().is_terminal() {
+            return;
+        }
+        if let Some(draft) = self.sessions.draft() {
+            note!("The prompt you had not sent is back on the line; Ctrl+U clears it\n");
+            self.draft = draft;
+        }
+    }
+
+    /// Offers to restore a conversation left behind by a crash.
+    async fn offer_recovery(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(session) = self.sessions.recovery() else {
+            return Ok(());
+        };
+        let orange = Color::TrueColor {
+            r: 255,
+            g: 140,
+            b: 0,
+        };
+        print!(
+            "{} \"{}\" ({} prompt{}, {}). Restore it? [Y/n] ",
+            "Rusty did not exit cleanly last time:".color(orange).bold(),
+            session.display_title(),
+            session.turns(),
+            if session.turns() == 1 { "" } else { "s" },
+            sessions::time_ago(session.updated_at)
+        );
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        self.reader.read_line(&mut answer).await?;
+        if answer.trim().eq_ignore_ascii_case("n") {
+            self.sessions.clear_recovery();
+            note!("Discarded\n");
+            return Ok(());
+        }
+
+        for message in &session.messages {
+            self.conversation_manager.add_message(message.clone());
+        }
+        self.conversation_manager
+            .set_system_prompt(session.system_prompt.clone());
+        self.load_settings(&session);
+        note!("Restored \"{}\"\n", session.display_title());
+        self.session = session;
+        Ok(())
+    }
+
+    /// Copies the live conversation into the session record.
+    fn sync_session(&mut self) {
+        self.session.messages = self.conversation_manager.get_messages().to_vec();
+        self.session.system_prompt = self
+            .conversation_manager
+            .system_prompt()
+            .map(str::to_string);
+        self.session.model = self.model_manager.get_current_model().to_string();
+        self.session.settings = (self.settings != self.default_settings
+            || self.settings.generation.seed.is_some())
+        .then(|| self.settings.clone());
+    }
+
+    /// Switches to the persona called `name`: its system prompt in place of
+    /// the one set, its settings over the configured ones, only its tools
+    /// when it names them, and its model.
+    fn use_persona(&mut self, name: &str) -> Result<(), String> {
+        let Some(persona) = self.personas.get(name).cloned() else {
+            return Err(if self.personas.is_empty() {
+                "No personas; add them under [personas] in config.toml".to_string()
+            } else {
+                format!("No persona is called '{}'; /persona lists them", name)
+            });
+        };
+        let mut settings = self.default_settings.overlay(&persona.settings);
+        // A seed given with --seed holds until a persona brings its own.
+        settings.generation.seed = settings.generation.seed.or(self.settings.generation.seed);
+        if let Some(tools) = &persona.tools {
+            let names = self.tool_names();
+            for tool in tools.iter().filter(|tool| !names.contains(tool)) {
+                note!("The persona names a tool there is not: {}\n", tool);
+            }
+            settings.tools = names
+                .into_iter()
+                .map(|tool| {
+                    let on = tools.contains(&tool);
+                    (tool, on)
+                })
+                .collect();
+        }
+        if let Some(model) = &persona.model {
+            match ModelManager::find_models(model).as_slice() {
+                [model] => self.model_manager.selected_model = model.to_string(),
+                // Models Rusty does not list are taken as they are.
+                _ => self.model_manager.selected_model = model.clone(),
+            }
+        }
+        self.conversation_manager
+            .set_system_prompt(persona.system.clone());
+        self.settings = settings;
+        self.apply_settings();
+        self.persona = Some(name.to_string());
+        note!("Persona: {}\n", name);
+        Ok(())
+    }
+
+    /// `/persona off`: the system prompt cleared and the configured
+    /// settings back, the model left as it is.
+    fn leave_persona(&mut self) {
+        let Some(name) = self.persona.take() else {
+            note!("No persona is on\n");
+            return;
+        };
+        let seed = self.settings.generation.seed;
+        self.settings = self.default_settings.clone();
+        self.settings.generation.seed = self.settings.generation.seed.or(seed);
+        self.apply_settings();
+        self.conversation_manager.set_system_prompt(None);
+        note!("Persona {} is off\n", name);
+    }
+
+    fn list_personas(&self) {
+        if self.personas.is_empty() {
+            note!("No personas; add them under [personas] in config.toml\n");
+            return;
+        }
+        for (name, persona) in &self.personas {
+            let mark = if self.persona.as_deref() == Some(name) {
+                "●"
+            } else {
+                " "
+            };
+            println!(
+                "     {} {}  {}",
+                mark,
+                name,
+                persona.description.as_deref().unwrap_or_default().dimmed()
+            );
+        }
+        println!();
+    }
+
+    /// Takes on the settings a saved session was changed to, or the
+    /// configured ones.
+    fn load_settings(&mut self, session: &Session) {
+        self.settings = session
+            .settings
+            .clone()
+            .unwrap_or_else(|| self.default_settings.clone());
+        self.apply_settings();
+    }
+
+    /// Passes the settings on to what does not read them for each request.
+    fn apply_settings(&mut self) {
+        self.brave_client.set_count(self.settings.search_count());
+        self.brave_client.set_goggle(self.settings.goggle.clone());
+        self.brave_client.set_summarizer(self.settings.summarizer());
+    }
+
+    /// Renders the conversation to Markdown and posts it as a gist using
+    /// `GITHUB_TOKEN`, printing the link.
+    async fn share_session(&mut self, public: bool) {
+        if self.conversation_manager.get_messages().is_empty() {
+            note!("Nothing to share yet\n");
+            return;
+        }
+        let token = match ConfigManager::load_key("GITHUB_TOKEN") {
+            Ok(token) => token,
+            Err(_) => {
+                UserInterface::print_error(
+                    "Set GITHUB_TOKEN in .env to a token with the gist scope to use /share",
+                );
+                return;
+            }
+        };
+
+        self.sync_session();
+        let markdown = export::to_markdown(&self.session);
+        let title = self.session.display_title();
+        match gist::create_gist(&token, &title, "conversation.md", &markdown, public).await {
+            Ok(url) => note!("Shared: {}\n", url),
+            Err(e) => UserInterface::print_error(&e),
+        }
+    }
+
+    /// Writes the conversation to its session file, asking the model for a
+    /// title the first time. Empty conversations are not saved.
+    async fn save_session(&mut self) -> Result<(), String> {
+        if self.conversation_manager.get_messages().is_empty() {
+            return Ok(());
+        }
+        self.sync_session();
+        if self.session.title.is_none() {
+            self.session.title = sessions::generate_title(
+                &self.provider,
+                &self.session.model,
+                &self.session.messages,
+            )
+            .await;
+        }
+        self.sessions.save(&mut self.session)
+    }
+
+    fn show_sessions(&self, tags: &[String]) {
+        let saved = self.sessions.list();
+        if saved.is_empty() {
+            note!("No saved sessions\n");
+            return;
+        }
+        // Numbers stay those of the full list so /resume <n> works after filtering.
+        let mut shown = 0;
+        for (i, session) in saved.iter().enumerate() {
+            if !session.has_tags(tags) {
+                continue;
+            }
+            shown += 1;
+            let tag_list: String = session.tags.iter().map(|t| format!(" #{}", t)).collect();
+            let title: String = session.display_title().chars().take(50).collect();
+            println!(
+                "  {:>3}  {:<50}  {}{}",
+                i + 1,
+                title,
+                format!(
+                    "{} · {} prompt{}",
+                    sessions::time_ago(session.updated_at),
+                    session.turns(),
+                    if session.turns() == 1 { "" } else { "s" }
+                )
+                .dimmed(),
+                tag_list.cyan()
+            );
+        }
+        if shown == 0 {
+            note!("No sessions tagged {}", tags.join(", "));
+        }
+        println!();
+    }
+
+    /// `/tag` lists the current session's tags; `/tag work` adds one and
+    /// `/tag -work` removes it. Changes are saved right away.
+    async fn tag_session(&mut self, args: &str) {
+        for tag in args.split_whitespace() {
+            match tag.strip_prefix('-') {
+                Some(tag) => self.session.remove_tag(tag),
+                None => self.session.add_tag(tag),
+            };
+        }
+        if !args.is_empty() {
+            if let Err(e) = self.save_session().await {
+                UserInterface::print_error(&e);
+            }
+        }
+        if self.session.tags.is_empty() {
+            note!("No tags\n");
+        } else {
+            let tags: Vec<String> = self
+                .session
+                .tags
+                .iter()
+                .map(|t| format!("#{}", t))
+                .collect();
+            note!("Tags: {}\n", tags.join(" "));
+        }
+    }
+
+    /// Lists saved sessions whose title or messages contain the text, limited
+    /// to sessions carrying every `#tag` in the query.
+    fn search_sessions(&self, query: &str) {
+        let (tags, text) = sessions::parse_filter(query);
+        let mut found = 0;
+        for (i, session) in self.sessions.list().iter().enumerate() {
+            if !session.has_tags(&tags) {
+                continue;
+            }
+            let hit = if text.is_empty() {
+                Some(String::new())
+            } else {
+                session.search(&text)
+            };
+            let Some(snippet) = hit else {
+                continue;
+            };
+            found += 1;
+            println!("  {:>3}  {}", i + 1, session.display_title().bold());
+            if !snippet.is_empty() {
+                println!("       {}", snippet.dimmed());
+            }
+        }
+        if found == 0 {
+            note!("No matching sessions");
+        }
+        println!();
+    }
+
+    /// Loads a saved session by its number in `/sessions` or by id, saving
+    /// the current conversation first.
+    async fn resume_session(&mut self, target: &str) {
+        let Some(session) = find_session(self.sessions.list(), target) else {
+            note!("No session '{}'. See /sessions\n", target);
+            return;
+        };
+
+        if session.id != self.session.id {
+            if let Err(e) = self.save_session().await {
+                UserInterface::print_error(&e);
+            }
+        }
+        note!("Resumed \"{}\"\n", session.display_title());
+        if session.model != self.model_manager.get_current_model() {
+            note!("{} {}\n", locale::t("models.active"), session.model);
+        }
+        self.load_session(session);
+    }
+
+    /// Makes `session` the conversation under way, with its system prompt,
+    /// settings and model.
+    fn load_session(&mut self, session: Session) {
+        self.conversation_manager.clear();
+        for message in &session.messages {
+            self.conversation_manager.add_message(message.clone());
+        }
+        self.conversation_manager
+            .set_system_prompt(session.system_prompt.clone());
+        self.load_settings(&session);
+        self.model_manager.selected_model = session.model.clone();
+        self.follow_ups.clear();
+        self.session = session;
+    }
+
+    /// `/session`: lists the conversations of this run; `new <name>` sets
+    /// the one under way aside for a new one, `switch <name>` goes back to
+    /// one set aside, and `close <name>` forgets one, which stays saved.
+    async fn session_command(&mut self, args: &str) {
+        let (action, name) = args.split_once(' ').unwrap_or((args, ""));
+        let name = name.trim();
+        match (action, name) {
+            ("" | "list", "") => self.list_threads(),
+            ("new", name) if !name.is_empty() => {
+                if name == self.thread || self.threads.contains_key(name) {
+                    note!(
+                        "There is a session called '{}' already; /session switch {} goes to it\n",
+                        name,
+                        name
+                    );
+                    return;
+                }
+                self.park_thread().await;
+                // As after /clear, the model, settings, system prompt and
+                // persona stay; only the conversation starts over.
                 self.conversation_manager.clear();
-                println!("  ⎿  (no content)\n");
-                Ok(true)
+                self.session = Session::new(self.model_manager.get_current_model());
+                self.session.title = Some(name.to_string());
+                let previous = std::mem::replace(&mut self.thread, name.to_string());
+                note!(
+                    "Started session {}; /session switch {} goes back\n",
+                    name,
+                    previous
+                );
             }
-            Command::Model => {
-                if !self
-                    .model_manager
-                    .change_model_interactive(&mut self.reader)
-                    .await?
-                {
-                    return Ok(false);
+            ("switch", name) if !name.is_empty() => {
+                if name == self.thread {
+                    note!("Session {} is the one under way\n", name);
+                    return;
+                }
+                if !self.threads.contains_key(name) {
+                    note!("No session called '{}'; /session lists them\n", name);
+                    return;
+                }
+                self.park_thread().await;
+                let Some(thread) = self.threads.remove(name) else {
+                    return;
+                };
+                self.load_session(thread.session);
+                self.persona = thread.persona;
+                self.thread = name.to_string();
+                note!(
+                    "Switched to session {} ({}, {} prompt{})\n",
+                    name,
+                    self.model_manager.get_current_model(),
+                    self.session.turns(),
+                    if self.session.turns() == 1 { "" } else { "s" }
+                );
+            }
+            ("close", name) if !name.is_empty() => {
+                if name == self.thread {
+                    note!("Switch to another session before closing {}\n", name);
+                } else {
+                    match self.threads.remove(name) {
+                        Some(thread) if thread.session.messages.is_empty() => {
+                            note!("Closed session {}\n", name)
+                        }
+                        Some(_) => note!(
+                            "Closed session {}; it is saved, and /sessions lists it\n",
+                            name
+                        ),
+                        None => note!("No session called '{}'; /session lists them\n", name),
+                    }
+                }
+            }
+            _ => UserInterface::print_error("Usage: /session [new|switch|close <name>]"),
+        }
+    }
+
+    /// Sets the conversation under way aside under its name, saving it
+    /// first so that it is not lost should Rusty not exit cleanly.
+    async fn park_thread(&mut self) {
+        if let Err(e) = self.save_session().await {
+            UserInterface::print_error(&e);
+        }
+        self.sync_session();
+        let thread = Thread {
+            session: self.session.clone(),
+            persona: self.persona.clone(),
+        };
+        self.threads.insert(self.thread.clone(), thread);
+    }
+
+    fn list_threads(&self) {
+        let current = (
+            &self.thread,
+            self.model_manager.get_current_model(),
+            self.turns_before(self.conversation_manager.get_messages().len()),
+        );
+        let parked = self
+            .threads
+            .iter()
+            .map(|(name, thread)| (name, thread.session.model.as_str(), thread.session.turns()));
+        let mut threads: Vec<_> = std::iter::once(current).chain(parked).collect();
+        threads.sort_by_key(|(name, _, _)| *name);
+        let width = threads
+            .iter()
+            .map(|(name, _, _)| name.width())
+            .max()
+            .unwrap_or(0);
+        for (name, model, turns) in threads {
+            let mark = if *name == self.thread { "●" } else { " " };
+            println!(
+                "     {} {}{}  {}",
+                mark,
+                name,
+                " ".repeat(width - name.width()),
+                format!(
+                    "{} · {} prompt{}",
+                    model,
+                    turns,
+                    if turns == 1 { "" } else { "s" }
+                )
+                .dimmed()
+            );
+        }
+        println!();
+    }
+
+    /// Keeps the current model as the session's and as the one this
+    /// project starts with next time.
+    fn remember_model(&mut self) {
+        let model = self.model_manager.get_current_model().to_string();
+        self.telemetry.model(&model);
+        self.session.model = model.clone();
+        if let Err(e) = self.projects.set_model(&model) {
+            UserInterface::print_error(&e);
+        }
+    }
+
+    fn show_prompts(&self, name: Option<&str>) {
+        match name {
+            None if self.prompts.templates().is_empty() => note!(
+                "No prompt templates. Add *.md files to {}\n",
+                paths::prompts_dir().display()
+            ),
+            None => {
+                for template in self.prompts.templates() {
+                    println!(
+                        "  {:<22}  {}",
+                        template.name,
+                        template.description().dimmed()
+                    );
                 }
+                println!();
+            }
+            Some(name) => match self.prompts.get(name) {
+                Some(template) => println!("{}\n", template.body.trim_end()),
+                None => note!("Unknown template '{}'\n", name),
+            },
+        }
+    }
 
-                let orange = Color::TrueColor {
-                    r: 255,
-                    g: 140,
-                    b: 0,
-                };
-                println!(
-                    "\n{} {}\n",
-                    "Active Model:".color(orange).bold(),
-                    self.model_manager.get_current_model().white()
-                );
+    /// The request a prompt would send, as `--dry-run` and `/dryrun` show
+    /// it in place of sending it: the body as the API would get it, system
+    /// prompt, history, tools and parameters included, and its size.
+    fn show_dry_run(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<Vec<ToolDefinition>>,
+        options: &ChatOptions,
+    ) {
+        let tool_count = tools.as_ref().map_or(0, Vec::len);
+        let tool_tokens = tools
+            .as_deref()
+            .map_or(0, conversation::estimate_tool_tokens);
+        let request = self.provider.preview(model, messages, tools, options);
+        let body = serde_json::to_string_pretty(&request).unwrap_or_default();
+        if UserInterface::quiet() {
+            println!("{}", body);
+            return;
+        }
+        print!(
+            "\n{}",
+            UserInterface::render_markdown(
+                &format!("```json\n{}\n```\n", body),
+                self.code_theme.as_deref()
+            )
+        );
+        let message_tokens = conversation::estimate_tokens(&request.messages);
+        note!(
+            "Dry run, not sent: ~{} tokens, {} in {} messages and {} in {} tools\n",
+            message_tokens + tool_tokens,
+            message_tokens,
+            request.messages.len(),
+            tool_tokens,
+            tool_count
+        );
+    }
 
-                Ok(true)
+    /// What every prompt goes through before it is sent, whichever way:
+    /// the offline queue, the hooks, the masking of secrets and personal
+    /// data, and moderation. `None` when it is not to be sent now, as it was
+    /// queued or blocked.
+    async fn prepare_user_message(&mut self, content: &str) -> Option<String> {
+        // Prompts keep their order: while earlier ones wait for the network,
+        // later ones wait behind them.
+        if self.network_down && self.can_queue() && !self.dry_run {
+            self.offline.push_back(content.to_string());
+            note!("Offline; queued ({} waiting)\n", self.offline.len());
+            return None;
+        }
+
+        let content = match self.hooks.on_user_message(content) {
+            Ok(HookOutcome::Keep) => content.to_string(),
+            Ok(HookOutcome::Replace(text)) => text,
+            Ok(HookOutcome::Block(reason)) => {
+                note!("Blocked by hook: {}\n", reason);
+                return None;
             }
-            Command::Help => {
-                UserInterface::print_help();
-                Ok(true)
+            Err(e) => {
+                UserInterface::print_error(&e);
+                content.to_string()
             }
-            Command::Message(content) => {
-                self.process_message(&content).await?;
-                Ok(true)
+        };
+
+        let report = self
+            .command_hooks
+            .pre_submit(&content, self.model_manager.get_current_model())
+            .await;
+        UserInterface::print_hook_output(&report.output);
+        if let Some(reason) = report.blocked {
+            note!("Blocked by hook: {}\n", reason);
+            return None;
+        }
+
+        let (content, secrets) = self.redactor.redact(&content);
+        if !secrets.is_empty() {
+            note!(
+                "Masked before sending: {}. /redact off sends messages as they are\n",
+                secrets.join(", ")
+            );
+        }
+        let content = self.pii.scrub(&content);
+        // Moderation sees the masked text, as the model does.
+        match self.moderator.check(&content, Direction::Input).await {
+            Verdict::Block(reason) => {
+                note!("Blocked by moderation: {}\n", reason);
+                return None;
             }
+            Verdict::Warn(reason) => note!("Moderation warning: {}\n", reason),
+            Verdict::Allow => {}
         }
+        Some(content)
     }
 
     async fn process_message(&mut self, content: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.conversation_manager.add_user_message(content);
+        let prompt = content;
+        let Some(content) = self.prepare_user_message(content).await else {
+            return Ok(());
+        };
+        self.refresh_environment();
+        self.conversation_manager.add_user_message(&content);
+        let started = Instant::now();
+        let mut turn_usage: Option<Usage> = None;
+        // The pages the tools returned, for citations.
+        let mut sources = Sources::default();
+        let mut sources_footer = None;
 
         let blue = Color::TrueColor {
             r: 122,
@@ -1182,165 +6539,1231 @@ impl ChatApplication {
             b: 161,
         };
 
-        UserInterface::print_thinking();
+        let mut spinner = UserInterface::print_thinking(self.animate_spinner());
+        let mut type_ahead = self.start_type_ahead(&mut spinner);
+        let mut json_retries = 0;
+        let mut repairs = 0;
+        let mut repeats = RepeatGuard::default();
+        let mut large_confirmed = false;
+        let mut tool_choice = self
+            .next_tool_choice
+            .take()
+            .or_else(|| self.settings.tool_choice.as_deref().map(ToolChoice::parse));
 
         loop {
-            let tools = vec![self.get_brave_search_tool(), self.get_open_tool()];
-
-            let result = self
-                .api_client
-                .chat_completion(
+            let tools: Vec<ToolDefinition> = self
+                .every_tool()
+                .into_iter()
+                .filter(|tool| {
+                    let name = &tool.function.name;
+                    self.settings
+                        .tool_enabled(name, self.offered_by_default(name))
+                        && !(self.network_down && tools::NETWORK_TOOLS.contains(&name.as_str()))
+                        && self
+                            .tools
+                            .get(name)
+                            .is_some_and(|tool| self.policy().offers(tool.as_ref()))
+                })
+                .collect();
+            // Models that cannot call tools are sent none.
+            let tools = model_info(self.model_manager.get_current_model())
+                .tools
+                .then_some(tools);
+            let window = context_window(self.model_manager.get_current_model());
+            if self.conversation_manager.should_summarize(window) && !self.dry_run {
+                spinner.pause();
+                self.summarize_overflow().await;
+            }
+            let overhead = tools
+                .as_deref()
+                .map_or(0, conversation::estimate_tool_tokens);
+            let messages = match self.conversation_manager.fit(window, overhead) {
+                Ok(messages) => messages,
+                // Refused here rather than by the API with an opaque error.
+                Err(e) => {
+                    spinner.pause();
+                    UserInterface::print_error(&e);
+                    self.conversation_manager.rewind_last_user_message();
+                    self.announce_finish(started, "Request failed", &e);
+                    break;
+                }
+            };
+            // A JSON answer is checked before it is shown.
+            let options = ChatOptions {
+                generation: self.settings.generation.clone(),
+                format: self.json_format.clone(),
+                logprobs: self.logprobs,
+                tool_choice: tool_choice.clone(),
+            };
+            // A forced tool call holds for the first request only, so the
+            // model can answer with what the tool returned.
+            if tool_choice.as_ref().is_some_and(ToolChoice::forces_call) {
+                tool_choice = None;
+            }
+            if self.dry_run {
+                spinner.pause();
+                self.show_dry_run(
                     self.model_manager.get_current_model(),
-                    self.conversation_manager.get_messages(),
-                    Some(tools),
-                )
-                .await;
+                    &messages,
+                    tools,
+                    &options,
+                );
+                self.conversation_manager.rewind_last_user_message();
+                break;
+            }
+            if let Some(cap) = self.spending_cap_reached(self.model_manager.get_current_model()) {
+                spinner.pause();
+                UserInterface::print_error(&format!(
+                    "{}; start Rusty with --ignore-caps to go on",
+                    cap
+                ));
+                self.fail(Failure::Other);
+                self.conversation_manager.rewind_last_user_message();
+                break;
+            }
+            let estimate = (conversation::estimate_tokens(&messages) + overhead) as u64;
+            if let Some(overrun) = self.budget_overrun(estimate) {
+                spinner.pause();
+                if let Some(type_ahead) = type_ahead.as_mut() {
+                    type_ahead.pause();
+                }
+                let approved = self.approve_over_budget(&overrun).await?;
+                if let Some(type_ahead) = type_ahead.as_mut() {
+                    type_ahead.resume();
+                }
+                if !approved {
+                    note!("Not sent\n");
+                    self.fail(Failure::Other);
+                    self.conversation_manager.rewind_last_user_message();
+                    break;
+                }
+            }
+            // Asked once a turn: the requests after tool calls carry the
+            // same prompt.
+            if let Some(warning) = self.large_request(estimate).filter(|_| !large_confirmed) {
+                spinner.pause();
+                if let Some(type_ahead) = type_ahead.as_mut() {
+                    type_ahead.pause();
+                }
+                let approved = self.approve_large_request(&warning).await?;
+                if let Some(type_ahead) = type_ahead.as_mut() {
+                    type_ahead.resume();
+                }
+                if !approved {
+                    note!("Not sent\n");
+                    self.fail(Failure::Other);
+                    self.conversation_manager.rewind_last_user_message();
+                    break;
+                }
+                large_confirmed = true;
+            }
+            let pruned = self.conversation_manager.omitted(&messages);
+            if pruned > 0 && self.provider.is_debug() {
+                UserInterface::print_debug(
+                    "context",
+                    &format!("pruned {} older messages to fit the window", pruned),
+                );
+            }
+            spinner.resume("waiting for the model");
+
+            let streaming = self.conversation_manager.is_stream_mode()
+                && self.provider.supports_streaming()
+                && self.json_format.is_none()
+                && !self.moderator.checks(Direction::Output);
+            let mut streamed_text = false;
+            // The end of a placeholder cut off in a streamed piece.
+            let mut pending = String::new();
+            let result = if streaming {
+                self.provider
+                    .chat_completion_stream(
+                        self.model_manager.get_current_model(),
+                        &messages,
+                        tools,
+                        &options,
+                        &mut |delta| {
+                            if !streamed_text {
+                                spinner.pause();
+                                if !UserInterface::quiet() {
+                                    print!("\n{}", UserInterface::assistant_label());
+                                }
+                                streamed_text = true;
+                            }
+                            print!("{}", self.pii.restore_streamed(&mut pending, delta));
+                            let _ = io::stdout().flush();
+                        },
+                    )
+                    .await
+            } else {
+                self.provider
+                    .chat_completion_with(
+                        self.model_manager.get_current_model(),
+                        &messages,
+                        tools,
+                        &options,
+                    )
+                    .await
+            };
+            spinner.pause();
+            if !pending.is_empty() {
+                print!("{}", self.pii.restore(&pending));
+            }
 
             match result {
-                Ok(response_msg) => {
-                    self.conversation_manager
-                        .messages
-                        .push(response_msg.clone());
+                Ok(mut response_msg) => {
+                    if let Some(usage) = &response_msg.usage {
+                        self.usage.add(usage);
+                        self.record_spending(self.model_manager.get_current_model(), usage);
+                        turn_usage.get_or_insert_with(Usage::default).add(usage);
+                    }
+                    self.last_reasoning = response_msg
+                        .reasoning
+                        .take()
+                        .filter(|reasoning| !reasoning.trim().is_empty());
+                    if let Some(reasoning) = &self.last_reasoning {
+                        UserInterface::print_reasoning(reasoning, self.show_thinking);
+                    }
+                    if response_msg.tool_calls.is_none() {
+                        if let Some(text) = &response_msg.content {
+                            match self.hooks.on_response(text) {
+                                Ok(HookOutcome::Keep) => {}
+                                Ok(HookOutcome::Replace(text)) => response_msg.content = Some(text),
+                                Ok(HookOutcome::Block(reason)) => {
+                                    note!("Response blocked by hook: {}\n", reason);
+                                    break;
+                                }
+                                Err(e) => UserInterface::print_error(&e),
+                            }
+                            let text = response_msg.content.as_deref().unwrap_or_default();
+                            match self.moderator.check(text, Direction::Output).await {
+                                Verdict::Block(reason) => {
+                                    note!("Answer blocked by moderation: {}\n", reason);
+                                    break;
+                                }
+                                Verdict::Warn(reason) => {
+                                    note!("Moderation warning: {}\n", reason)
+                                }
+                                Verdict::Allow => {}
+                            }
+                        }
+                        // Links to the pages the tools returned become
+                        // numbered citations.
+                        if self.settings.citations() && self.json_format.is_none() {
+                            let answer = response_msg.content.as_deref().unwrap_or_default();
+                            if let Some(cited) = sources.cite(answer) {
+                                response_msg.content =
+                                    Some(format!("{}\n\n{}", cited.text.trim_end(), cited.footer));
+                                sources_footer = Some(cited.footer);
+                            }
+                        }
+                    }
+
+                    self.conversation_manager.add_message(response_msg.clone());
 
                     if let Some(tool_calls) = &response_msg.tool_calls {
                         for tool_call in tool_calls {
-                            if tool_call.function.name == "brave_search" {
-                                let args: serde_json::Value =
-                                    serde_json::from_str(&tool_call.function.arguments)?;
-                                let query = args["query"].as_str().unwrap_or("");
-
-                                UserInterface::print_step(
-                                    &format!("Searching Brave for '{}'", query),
-                                    blue,
-                                );
+                            let tool_call = match self.hooks.on_tool_call(tool_call) {
+                                Ok(HookOutcome::Keep) => tool_call.clone(),
+                                Ok(HookOutcome::Replace(call)) => call,
+                                Ok(HookOutcome::Block(reason)) => {
+                                    UserInterface::print_error(&format!(
+                                        "Tool call '{}' blocked by hook: {}",
+                                        tool_call.function.name, reason
+                                    ));
+                                    self.conversation_manager.add_message(Message::tool(
+                                        &format!("Error: This tool call was blocked: {}", reason),
+                                        &tool_call.id,
+                                    ));
+                                    continue;
+                                }
+                                Err(e) => {
+                                    UserInterface::print_error(&e);
+                                    tool_call.clone()
+                                }
+                            };
+
+                            let report = self.command_hooks.pre_tool_exec(&tool_call).await;
+                            UserInterface::print_hook_output(&report.output);
+                            if let Some(reason) = report.blocked {
+                                UserInterface::print_error(&format!(
+                                    "Tool call '{}' blocked by hook: {}",
+                                    tool_call.function.name, reason
+                                ));
+                                self.conversation_manager.add_message(Message::tool(
+                                    &format!("Error: This tool call was blocked: {}", reason),
+                                    &tool_call.id,
+                                ));
+                                continue;
+                            }
 
-                                match self.brave_client.search(query).await {
-                                    Ok(search_results) => {
+                            let name = tool_call.function.name.clone();
+                            let Some(tool) = self.tools.get(&name) else {
+                                self.conversation_manager.add_message(Message::tool(
+                                    &format!("Error: Unknown tool `{}`.", name),
+                                    &tool_call.id,
+                                ));
+                                continue;
+                            };
+                            let arguments = match tools::read_arguments(
+                                tool.as_ref(),
+                                &tool_call.function.arguments,
+                            ) {
+                                Ok(arguments) => arguments,
+                                Err(e) => {
+                                    let message = if repairs < TOOL_CALL_REPAIRS {
+                                        repairs += 1;
                                         UserInterface::print_step(
-                                            "Reasoning with search results",
-                                            green,
+                                            &format!(
+                                                "Invalid arguments for {}; asking again",
+                                                name
+                                            ),
+                                            blue,
                                         );
-                                        self.conversation_manager
-                                            .messages
-                                            .push(Message::tool(&search_results, &tool_call.id));
-                                    }
-                                    Err(e) => {
+                                        e.tool_message(&name)
+                                    } else {
                                         UserInterface::print_error(&format!(
-                                            "Search failed: {}",
-                                            e
-                                        ));
-                                        self.conversation_manager.messages.push(Message::tool(
-                                            "Error: Search failed. Please answer without search.",
-                                            &tool_call.id,
+                                            "The arguments for {} still {}",
+                                            name, e
                                         ));
-                                    }
+                                        format!(
+                                            "Error: The arguments are still invalid. Answer \
+without {}.",
+                                            name
+                                        )
+                                    };
+                                    self.conversation_manager
+                                        .add_message(Message::tool(&message, &tool_call.id));
+                                    continue;
                                 }
-                            } else if tool_call.function.name == "open" {
-                                let args: serde_json::Value =
-                                    serde_json::from_str(&tool_call.function.arguments)?;
-                                let url = args
-                                    .get("id")
-                                    .or_else(|| args.get("url"))
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("");
-
+                            };
+                            if repeats.is_repeat(&name, &arguments) {
                                 UserInterface::print_step(
-                                    &format!("Checking content from '{}'", url),
+                                    &format!("Skipping a repeated {} call", name),
                                     blue,
                                 );
+                                if repeats.exhausted() {
+                                    // The model is going round in circles.
+                                    tool_choice = Some(ToolChoice::None);
+                                }
+                                self.conversation_manager.add_message(Message::tool(
+                                    &repeats.tool_message(&name),
+                                    &tool_call.id,
+                                ));
+                                continue;
+                            }
+                            let ToolSteps {
+                                step,
+                                done,
+                                failure,
+                                fallback,
+                            } = tool.steps(&arguments);
+
+                            let gate = loop {
+                                match self.policy().check(tool.as_ref(), &arguments) {
+                                    Gate::AskTrust => {
+                                        if let Some(type_ahead) = type_ahead.as_mut() {
+                                            type_ahead.pause();
+                                        }
+                                        self.trust_workspace().await?;
+                                        if let Some(type_ahead) = type_ahead.as_mut() {
+                                            type_ahead.resume();
+                                        }
+                                    }
+                                    gate => break gate,
+                                }
+                            };
+                            if let Gate::Refused { reason, message } = &gate {
+                                note!("{} not run: {}\n", name, reason);
+                                self.fail(Failure::ToolDenied);
+                                self.conversation_manager
+                                    .add_message(Message::tool(message, &tool_call.id));
+                                continue;
+                            }
+
+                            // The step is printed once it is done, except for requests
+                            // that need approval, which must say what they are first.
+                            let asked = matches!(gate, Gate::AskHost(_));
+                            if let Gate::AskHost(host) = &gate {
+                                UserInterface::print_step(&step, blue);
+                                if let Some(type_ahead) = type_ahead.as_mut() {
+                                    type_ahead.pause();
+                                }
+                                let approved = self.approve_host(host).await?;
+                                if let Some(type_ahead) = type_ahead.as_mut() {
+                                    type_ahead.resume();
+                                }
+                                if !approved {
+                                    note!("Request denied\n");
+                                    self.fail(Failure::ToolDenied);
+                                    self.conversation_manager.add_message(Message::tool(
+                                        "Error: The user did not approve this request.",
+                                        &tool_call.id,
+                                    ));
+                                    continue;
+                                }
+                            }
 
-                                // Redirect to brave search as a fallback for now
-                                match self.brave_client.search(url).await {
-                                    Ok(search_results) => {
-                                        UserInterface::print_step("Analyzing page content", green);
-                                        self.conversation_manager
-                                            .messages
-                                            .push(Message::tool(&search_results, &tool_call.id));
+                            spinner.resume(&step);
+                            diagnostics::log(&format!("tool {}", name));
+                            let tool_started = Instant::now();
+                            let context = ToolContext {
+                                brave: &self.brave_client,
+                                memory: &mut self.memory,
+                                documents: self.documents.as_ref(),
+                                ocr: &self.ocr,
+                                fetcher: &self.fetcher,
+                            };
+                            let result =
+                                interrupt::cancellable(tool.execute(&arguments, context)).await;
+                            let cancelled = result.is_none();
+                            let result =
+                                result.unwrap_or_else(|| Err("cancelled by the user".to_string()));
+                            let elapsed = tool_started.elapsed();
+                            spinner.pause();
+                            self.record_tool_call(&name, result.is_ok(), elapsed);
+                            if !asked {
+                                UserInterface::print_step(&step, blue);
+                            }
+                            let run = ToolRun {
+                                name,
+                                arguments: summarize_arguments(&tool_call.function.arguments),
+                                output: match &result {
+                                    Ok(output) => output.clone(),
+                                    Err(e) => format!("{}: {}", failure, e),
+                                },
+                            };
+                            UserInterface::print_tool_run(
+                                self.tool_runs.len() + 1,
+                                &run,
+                                elapsed,
+                                result.is_err(),
+                            );
+                            self.tool_runs.push(run);
+                            // The tool may have added to long-term memory.
+                            self.conversation_manager
+                                .set_memory(self.memory.system_prompt());
+                            match result {
+                                Ok(output) => {
+                                    if let Some(done) = done {
+                                        UserInterface::print_step(done, green);
                                     }
-                                    Err(e) => {
-                                        UserInterface::print_error(&format!(
-                                            "Failed to read content: {}",
-                                            e
-                                        ));
-                                        self.conversation_manager.messages.push(Message::tool(
-                                            "Error: Failed to read page content. Please try searching instead.",
-                                            &tool_call.id,
-                                        ));
+                                    sources.add(&output);
+                                    // Pages, files and the clipboard may hold secrets too.
+                                    let (output, secrets) = self.redactor.redact(&output);
+                                    if !secrets.is_empty() {
+                                        note!(
+                                            "Masked in the tool's output: {}\n",
+                                            secrets.join(", ")
+                                        );
                                     }
+                                    let output = self.pii.scrub(&output);
+                                    self.conversation_manager
+                                        .add_message(Message::tool(&output, &tool_call.id));
+                                }
+                                Err(e) => {
+                                    // Some calls the model can fix if it sees why they failed.
+                                    let message = match fallback {
+                                        _ if cancelled => TOOL_CANCELLED.to_string(),
+                                        Some(fallback) => fallback.to_string(),
+                                        None => self.redactor.redact(&format!("Error: {}", e)).0,
+                                    };
+                                    self.conversation_manager
+                                        .add_message(Message::tool(&message, &tool_call.id));
                                 }
                             }
                         }
                         // Continue loop to let AI process results
                         continue;
                     } else {
+                        if let Some(format) = &self.json_format {
+                            let answer = response_msg.content.as_deref().unwrap_or_default();
+                            if let Err(e) = format.check(answer) {
+                                if json_retries < JSON_RETRIES {
+                                    json_retries += 1;
+                                    UserInterface::print_step(
+                                        &format!("{}; asking again", e),
+                                        blue,
+                                    );
+                                    self.conversation_manager.add_user_message(&format!(
+                                        "{}. Reply again with only the JSON that was asked for.",
+                                        e
+                                    ));
+                                    continue;
+                                }
+                                UserInterface::print_error(&e);
+                            }
+                        }
+
                         // No more tool calls, we have final response
+                        if streamed_text && UserInterface::quiet() {
+                            println!();
+                        } else if streamed_text {
+                            println!("\n");
+                        } else if let Some(final_content) = &response_msg.content {
+                            UserInterface::print_assistant_response(
+                                &self.pii.restore(final_content),
+                                self.code_theme.as_deref(),
+                            );
+                        }
+                        // The streamed answer was shown with its links as
+                        // they were; only the sources are left.
+                        if let Some(footer) = sources_footer.as_deref().filter(|_| streamed_text) {
+                            UserInterface::print_sources(footer, self.code_theme.as_deref());
+                        }
+                        if let Some(answer) = &response_msg.content {
+                            UserInterface::offer_diagrams(answer);
+                        }
+                        self.print_alternatives(&response_msg.alternatives);
+                        self.last_logprobs = response_msg.logprobs.clone();
+                        if self.logprobs {
+                            UserInterface::print_confidence(&self.last_logprobs);
+                        }
+                        if let Some(usage) = turn_usage.filter(|_| self.usage_footer) {
+                            UserInterface::print_usage(&usage);
+                        }
+
                         if let Some(final_content) = &response_msg.content {
-                            UserInterface::print_assistant_response(final_content);
+                            let report = self
+                                .command_hooks
+                                .post_response(
+                                    final_content,
+                                    self.model_manager.get_current_model(),
+                                )
+                                .await;
+                            UserInterface::print_hook_output(&report.output);
                         }
+                        self.announce_finish(
+                            started,
+                            "Reply ready",
+                            response_msg.content.as_deref().unwrap_or_default(),
+                        );
                         break;
                     }
                 }
+                Err(e) if Failure::of(&e) == Failure::Network && self.can_queue() => {
+                    spinner.pause();
+                    if self.provider.is_debug() {
+                        UserInterface::print_error(&e.to_string());
+                    }
+                    // The prompt is sent again from the queue, so it and
+                    // any tool calls it led to are taken back.
+                    self.conversation_manager.rewind_last_user_message();
+                    self.go_offline();
+                    self.offline.push_back(prompt.to_string());
+                    note!("The prompt is queued and sent when the connection is back\n");
+                    break;
+                }
                 Err(e) => {
-                    UserInterface::print_error(&e.to_string());
+                    let error = match self.provider.host() {
+                        Some(host) if Failure::of(&e) == Failure::Network => {
+                            format!("Offline: {} can't be reached ({})", host, e)
+                        }
+                        _ => e.to_string(),
+                    };
+                    UserInterface::print_error(&error);
+                    self.fail(Failure::of(&e));
                     self.conversation_manager.remove_last_message();
+                    self.announce_finish(started, "Request failed", &e.to_string());
                     break;
                 }
             }
         }
 
+        self.keep_typed(type_ahead);
         Ok(())
     }
 
-    fn get_brave_search_tool(&self) -> ToolDefinition {
-        ToolDefinition {
-            r#type: "function".to_string(),
-            function: ToolFunction {
-                name: "brave_search".to_string(),
-                description: "Search the web for up-to-date information, news, current events, and general knowledge. Use this for questions that require real-time data or when you need to verify facts.".to_string(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "query": {
-                            "type": "string",
-                            "description": "The search query to look up on the web."
-                        }
-                    },
-                    "required": ["query"]
-                }),
-            },
+    /// Whether a prompt that could not reach the API can wait for the
+    /// network: only at an interactive prompt, not in a one-shot or piped
+    /// run, which ends first.
+    fn can_queue(&self) -> bool {
+        io::stdin().is_terminal() && !UserInterface::quiet()
+    }
+
+    /// Shows the offline banner and works offline, queueing prompts and
+    /// leaving out the web tools, while probing every few seconds for the
+    /// API to be back.
+    fn go_offline(&mut self) {
+        let (Some(host), Some(probe)) = (self.provider.host(), self.provider.probe()) else {
+            return;
+        };
+        if self.network_down {
+            return;
         }
+        self.network_down = true;
+        UserInterface::print_offline(host);
+        self.online.store(false, Ordering::Relaxed);
+        let online = Arc::clone(&self.online);
+        tokio::spawn(async move {
+            while !probe.reachable().await {
+                tokio::time::sleep(PROBE_INTERVAL).await;
+            }
+            online.store(true, Ordering::Relaxed);
+        });
     }
 
-    fn get_open_tool(&self) -> ToolDefinition {
-        ToolDefinition {
-            r#type: "function".to_string(),
-            function: ToolFunction {
-                name: "open".to_string(),
-                description: "Open a URL to read its content.".to_string(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "id": {
-                            "type": "string",
-                            "description": "The URL or ID of the resource to open."
-                        }
-                    },
-                    "required": ["id"]
-                }),
+    /// Once the probe has found the network back, says so and sends the
+    /// prompts queued while offline, in order. Any that fail again are
+    /// queued anew.
+    async fn check_network(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.network_down || !self.online.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.network_down = false;
+        let prompts = std::mem::take(&mut self.offline);
+        match prompts.len() {
+            0 => note!("Back online\n"),
+            1 => note!("Back online; sending the queued prompt\n"),
+            n => note!("Back online; sending {} queued prompts\n", n),
+        }
+        for prompt in prompts {
+            if self.network_down {
+                self.offline.push_back(prompt);
+                continue;
+            }
+            UserInterface::print_prompt();
+            println!("{}", prompt);
+            UserInterface::print_prompt_closure();
+            match self.compare.clone() {
+                Some(model) => self.compare_turn(&model, &prompt).await?,
+                None => self.process_message(&prompt).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// The other answers, when `n` asked for several, each under its
+    /// number. With `--quiet` they are separated by a line of `---`.
+    fn print_alternatives(&self, alternatives: &[String]) {
+        let count = alternatives.len() + 1;
+        for (i, answer) in alternatives.iter().enumerate() {
+            if UserInterface::quiet() {
+                println!("---");
+            } else {
+                note!("{}", format!("Choice {} of {}", i + 2, count).dimmed());
+            }
+            UserInterface::print_assistant_response(
+                &self.pii.restore(answer),
+                self.code_theme.as_deref(),
+            );
+        }
+        if !alternatives.is_empty() {
+            note!(
+                "{}",
+                "Only the first choice is kept in the conversation\n".dimmed()
+            );
+        }
+    }
+
+    /// Starts collecting what is typed during a turn, continuing any
+    /// unfinished line, and shows it on the spinner line. Not in the plain
+    /// layout, where the terminal keeps what is typed, nor in quiet mode,
+    /// which takes no input.
+    fn start_type_ahead(&mut self, spinner: &mut Spinner) -> Option<TypeAhead> {
+        if UserInterface::plain() || UserInterface::quiet() {
+            return None;
+        }
+        let type_ahead = TypeAhead::start(&std::mem::take(&mut self.draft))?;
+        spinner.show_typed(type_ahead.typed());
+        Some(type_ahead)
+    }
+
+    /// Queues the lines typed during a turn and keeps the unfinished one
+    /// for the next prompt.
+    fn keep_typed(&mut self, type_ahead: Option<TypeAhead>) {
+        if let Some(type_ahead) = type_ahead {
+            let typed = type_ahead.finish();
+            self.queued.extend(typed.queued);
+            self.draft = typed.draft;
+        }
+    }
+
+    /// Whether the spinner may redraw its line: only on a terminal, not
+    /// when debug output would be interleaved with it, and not in the plain
+    /// layout.
+    fn animate_spinner(&self) -> bool {
+        io::stdout().is_terminal() && !self.provider.is_debug() && !UserInterface::plain()
+    }
+
+    /// Plays the configured sound, then sends a desktop notification when
+    /// the turn begun at `started` ran past the configured threshold and
+    /// the terminal is not focused. When focus cannot be told, the
+    /// notification is sent anyway.
+    fn announce_finish(&self, started: Instant, title: &str, text: &str) {
+        match self.sound.as_deref() {
+            Some("bell") => {
+                // Terminals and tmux flag the window, even in the background.
+                print!("\x07");
+                let _ = io::stdout().flush();
+            }
+            Some(path) => {
+                if let Err(e) = notify::play_sound(path) {
+                    if self.provider.is_debug() {
+                        UserInterface::print_debug("notify", &e);
+                    }
+                }
+            }
+            None => {}
+        }
+
+        let elapsed = started.elapsed();
+        if self.notify_after.is_none_or(|after| elapsed < after)
+            || notify::terminal_focused() == Some(true)
+        {
+            return;
+        }
+        let line = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or_default();
+        let mut body: String = line.chars().take(120).collect();
+        if body.len() < line.len() {
+            body.push('…');
+        }
+        let title = format!("Rusty: {} after {}s", title, elapsed.as_secs());
+        if let Err(e) = notify::send(&title, &body) {
+            if self.provider.is_debug() {
+                UserInterface::print_debug("notify", &e);
+            }
+        }
+    }
+}
+
+// Command-Line Arguments
+
+/// What Rusty can run as instead of the chat: a bot on a chat service, or
+/// the webhook listener.
+enum Service {
+    Discord,
+    Telegram,
+    Slack,
+    Webhook,
+    /// With the arguments after `schedule`.
+    Schedule(Vec<String>),
+    /// With the arguments after `watch`.
+    Watch(Vec<String>),
+}
+
+/// The command line. Without a command, Rusty starts the chat.
+#[derive(Parser)]
+#[command(
+    name = "rusty",
+    version,
+    propagate_version = true,
+    about = "An agentic chatbot for the terminal, with web search and tools"
+)]
+struct CliArgs {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+    /// The prompt of a one-shot run with `-q`.
+    prompt: Vec<String>,
+    /// Answer the prompt once, printing only the answer, as `rusty ask` does.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Print the requests and responses of the API.
+    #[arg(long, global = true)]
+    debug: bool,
+    /// Plain, labeled output for screen readers.
+    #[arg(long, global = true)]
+    accessible: bool,
+    /// The seed of every request, for repeatable runs.
+    #[arg(long, global = true)]
+    seed: Option<String>,
+    /// Go on past the daily and monthly spending caps.
+    #[arg(long, global = true)]
+    ignore_caps: bool,
+    /// Show the requests prompts would send, without sending them.
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Refuse tool calls that may change something.
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// Start as a persona from `[personas]` in the configuration.
+    #[arg(long, global = true, value_name = "NAME")]
+    persona: Option<String>,
+    /// Who answers: `groq`, or `mock` for canned answers.
+    #[arg(long, global = true, env = "RUSTY_PROVIDER", default_value = "groq")]
+    provider: String,
+    /// The answers of the mock provider, one per line.
+    #[arg(long, global = true, env = "RUSTY_MOCK_SCRIPT")]
+    mock_script: Option<String>,
+    /// Record the answers of Groq and the searches to a cassette.
+    #[arg(long, global = true, value_name = "FILE")]
+    record: Option<String>,
+    /// Play back a recorded cassette instead of asking Groq or searching.
+    #[arg(long, global = true, value_name = "FILE")]
+    cassette: Option<String>,
+}
+
+#[derive(Subcommand, Clone)]
+enum CliCommand {
+    /// Chat in the terminal, as `rusty` alone does.
+    Chat,
+    /// Answer one prompt and exit, printing only the answer.
+    Ask {
+        /// Piped input follows it.
+        prompt: Vec<String>,
+    },
+    /// List the models to choose from.
+    Models,
+    /// Read or change the configuration file.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// List, show or delete saved sessions.
+    Sessions {
+        #[command(subcommand)]
+        action: Option<SessionsAction>,
+    },
+    /// The chat made for a tmux popup, on a scratch session.
+    Quick { prompt: Vec<String> },
+    /// Choose the keys, model and theme again.
+    Setup,
+    /// Print the manual page, as for `rusty man > rusty.1`, or write it
+    /// with a page for each command to a directory.
+    Man { dir: Option<PathBuf> },
+    /// Print the hook for the shell's startup file that lets explain-last
+    /// know the last command, as in `eval "$(rusty shell-init bash)"`.
+    ShellInit {
+        /// bash, zsh or fish; $SHELL when left out.
+        shell: Option<String>,
+    },
+    /// Suggest a shell command for what is described, and run it once
+    /// confirmed.
+    Cmd {
+        /// What the command should do, as in "find files over 1GB changed this week".
+        request: Vec<String>,
+    },
+    /// Explain why the last command in this shell failed. Pipe its output
+    /// in for a closer look.
+    ExplainLast {
+        /// Anything to ask about it as well.
+        question: Vec<String>,
+    },
+    /// Build the document index of a directory.
+    Index {
+        #[arg(default_value = ".")]
+        dir: String,
+    },
+    /// Print embeddings as JSON.
+    Embed { text: Vec<String> },
+    /// Offer the tools to an MCP client over stdio.
+    McpServe,
+    /// Run a Git hook helper: prepare-commit-msg or pre-push-review.
+    Hook {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Read a ChatGPT or Claude export into sessions.
+    Import { file: String },
+    /// Write saved sessions as fine-tuning JSONL: [#tag...] [--flatten-tools] [file].
+    ExportJsonl {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Print or export the usage ledger: [--csv] [file].
+    Usage {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Print or export the ratings: [--csv] [file].
+    Feedback {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Show a saved conversation again: <session> [--speed n].
+    Replay {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Run as a Discord bot.
+    Discord,
+    /// Run as a Telegram bot.
+    Telegram,
+    /// Run as a Slack app.
+    Slack,
+    /// Listen for webhooks.
+    Webhook,
+    /// Run the scheduled prompts: ["<cron>" "<prompt>" [--session <title>]].
+    Schedule {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Review files as they are saved: [path] [--prompt <prompt>].
+    Watch {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum ConfigAction {
+    /// Print what the file sets for a key such as `chat.model`, or the
+    /// whole file.
+    Get { key: Option<String> },
+    /// Set a key such as `chat.model` in the file.
+    Set { key: String, value: String },
+    /// Print where the file is.
+    Path,
+}
+
+#[derive(Subcommand, Clone)]
+enum SessionsAction {
+    /// List the saved sessions, most recent first.
+    List {
+        /// Only those with every tag given, such as `#work`.
+        tags: Vec<String>,
+    },
+    /// Print a session as Markdown.
+    Show {
+        /// Its number in the list, or its id.
+        session: String,
+    },
+    /// Delete a session.
+    Delete {
+        /// Its number in the list, or its id.
+        session: String,
+    },
+}
+
+impl CliArgs {
+    /// The command to run, with `-q` standing for `ask` and no command for
+    /// `chat`.
+    fn command(&self) -> CliCommand {
+        match &self.command {
+            Some(command) => command.clone(),
+            None if self.quiet => CliCommand::Ask {
+                prompt: self.prompt.clone(),
             },
+            None => CliCommand::Chat,
         }
     }
 }
 
+impl CliCommand {
+    /// Whether Rusty starts the chat, rather than a command or a service.
+    fn is_chat(&self) -> bool {
+        matches!(
+            self,
+            CliCommand::Chat
+                | CliCommand::Ask { .. }
+                | CliCommand::Quick { .. }
+                | CliCommand::ExplainLast { .. }
+                | CliCommand::Cmd { .. }
+        )
+    }
+
+    /// The service Rusty runs as instead of the chat, if any.
+    fn service(&self) -> Option<Service> {
+        Some(match self {
+            CliCommand::Discord => Service::Discord,
+            CliCommand::Telegram => Service::Telegram,
+            CliCommand::Slack => Service::Slack,
+            CliCommand::Webhook => Service::Webhook,
+            CliCommand::Schedule { args } => Service::Schedule(args.clone()),
+            CliCommand::Watch { args } => Service::Watch(args.clone()),
+            _ => return None,
+        })
+    }
+}
+
 // Main Entry Point
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    console::init();
+    cleanup::install();
+    crash::install();
+    notice::set_handler(UserInterface::print_notice);
     dotenvy::dotenv().ok();
+    dotenvy::from_path(paths::env_file()).ok();
 
-    let (groq_key, brave_key) = ConfigManager::get_or_prompt_api_keys();
-    let mut app = ChatApplication::new(groq_key, brave_key);
-    app.run().await?;
+    let args = CliArgs::parse();
+    let command = args.command();
+    let quiet = matches!(command, CliCommand::Ask { .. });
 
-    Ok(())
+    if let CliCommand::Setup = command {
+        setup::run().await?;
+        return Ok(());
+    }
+    // The first start, before there is a key or a configuration file.
+    let mut setup_keys = None;
+    if command.is_chat()
+        && !quiet
+        && args.provider != "mock"
+        && args.cassette.is_none()
+        && io::stdin().is_terminal()
+        && !Config::path().exists()
+        && ConfigManager::load_key("GROQ_API_KEY").is_err()
+    {
+        setup_keys = Some(setup::run().await?);
+    }
+
+    let config = Config::load().unwrap_or_else(|e| {
+        UserInterface::print_error(&e);
+        Config::default()
+    });
+    UserInterface::set_accessible(args.accessible || config.chat.accessible);
+    UserInterface::set_quiet(quiet);
+    UserInterface::set_raw_math(config.chat.raw_math);
+    UserInterface::set_diff_view(config.chat.diff_view);
+    UserInterface::set_git_prompt(config.chat.git_prompt);
+    UserInterface::set_keep_title(config.chat.keep_title);
+    UserInterface::set_styles(&config.styles);
+    models::set_overrides(config.models.clone());
+    if let Err(e) = locale::init(config.chat.locale.as_deref()) {
+        UserInterface::print_error(&e);
+    }
+
+    let mut embedder = Embedder::from_config(&config.embeddings);
+    embedder.set_debug(args.debug);
+
+    match &command {
+        CliCommand::Index { dir } => {
+            index_documents(Path::new(dir), &embedder, &config.index).await?;
+            return Ok(());
+        }
+        CliCommand::Embed { text } => return Ok(print_embeddings(text.clone(), &embedder).await?),
+        CliCommand::McpServe => return Ok(serve_mcp(&config, args.read_only, embedder).await?),
+        CliCommand::Hook { args: hook } => return Ok(run_git_hook(&config, &args, hook).await?),
+        CliCommand::Import { file } => return Ok(import_sessions(&config, file)?),
+        CliCommand::ExportJsonl { args } => return Ok(export_jsonl(&config, args)?),
+        CliCommand::Usage { args } => return Ok(export_usage(args)?),
+        CliCommand::Feedback { args } => return Ok(export_feedback(args)?),
+        CliCommand::Replay { args } => return Ok(replay(&config, args).await?),
+        CliCommand::Models => {
+            ModelManager::list_models();
+            return Ok(());
+        }
+        CliCommand::Man { dir } => return Ok(man::generate(dir.as_deref())?),
+        CliCommand::ShellInit { shell } => {
+            print!("{}", shell::init_script(shell.as_deref())?);
+            return Ok(());
+        }
+        CliCommand::Config { action } => return Ok(config_command(action)?),
+        CliCommand::Sessions { action } => return Ok(sessions_command(&config, action)?),
+        _ => {}
+    }
+
+    let (mut provider, mut brave_client) = if let Some(path) = &args.cassette {
+        let cassette = Arc::new(Cassette::replay(Path::new(path))?);
+        let mut brave = BraveSearchClient::offline();
+        brave.set_cassette(Some(cassette.clone()));
+        (Provider::Recorded(Recorder::replay(cassette)), brave)
+    } else if args.provider == "mock" {
+        let mock = match &args.mock_script {
+            Some(path) => MockProvider::from_file(path)?,
+            None => MockProvider::new(Vec::new()),
+        };
+        (Provider::Mock(mock), BraveSearchClient::offline())
+    } else {
+        let (groq_key, brave_key) = match setup_keys {
+            Some(keys) => (keys.groq, keys.brave),
+            None => ConfigManager::get_or_prompt_api_keys(),
+        };
+        let mut groq = GroqApiClient::new(groq_key);
+        groq.set_round_robin(config.groq.round_robin);
+        groq.set_retry(config.groq.retry.clone());
+        groq.set_scheduler(Scheduler::new(
+            config.groq.max_concurrent,
+            config.groq.requests_per_minute,
+        ));
+        let mut brave = BraveSearchClient::new(brave_key);
+        match &args.record {
+            Some(path) => {
+                let cassette = Arc::new(Cassette::record(Path::new(path)));
+                brave.set_cassette(Some(cassette.clone()));
+                (Provider::Recorded(Recorder::record(groq, cassette)), brave)
+            }
+            None => (Provider::Groq(groq), brave),
+        }
+    };
+    provider.set_debug(args.debug);
+    brave_client.set_location(config.search.location());
+
+    let (hooks, warnings) = ScriptHooks::load(&paths::scripts_dir());
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
+
+    if let Some(service) = command.service() {
+        let trusted = unattended_trust(&config);
+        let (plugins, warnings) =
+            PluginHost::load(&paths::plugins_dir(), tools::BUILTIN_TOOL_NAMES, trusted).await;
+        for warning in &warnings {
+            UserInterface::print_error(warning);
+        }
+        let mut agent = Agent::new(
+            provider,
+            brave_client,
+            plugins,
+            hooks,
+            CommandHooks::new(config.hooks.clone()),
+            Ocr::from_config(&config.ocr),
+            Allowlist::new(&config.http.allow),
+        );
+        let egress = Egress::new(&config.http.egress).unattended();
+        agent.set_fetcher(Fetcher::from_config(&config.fetch, &egress));
+        agent.set_egress(egress);
+        let (redactor, warnings) = Redactor::new(&config.redact);
+        for warning in &warnings {
+            UserInterface::print_error(warning);
+        }
+        agent.set_redactor(redactor);
+        if config.pii.enabled {
+            agent.set_pii(Scrubber::new(&config.pii));
+        }
+        let (moderator, warnings) = Moderator::new(&config.moderation);
+        for warning in &warnings {
+            UserInterface::print_error(warning);
+        }
+        agent.set_moderator(moderator);
+        agent.set_read_only(args.read_only || config.trust.read_only);
+        agent.set_trusted(trusted);
+        return Ok(match service {
+            Service::Discord => run_discord(&config, agent).await,
+            Service::Telegram => run_telegram(&config, agent).await,
+            Service::Slack => run_slack(&config, agent).await,
+            Service::Webhook => run_webhook(&config, agent).await,
+            Service::Schedule(args) => run_schedule(&config, agent, args).await,
+            Service::Watch(args) => run_watch(&config, agent, args).await,
+        }?);
+    }
+
+    let cipher = if config.storage.encrypt {
+        Some(ConfigManager::unlock_storage()?)
+    } else {
+        None
+    };
+
+    let (history, warnings) =
+        History::load(&paths::history_file(), &config.history, cipher.clone());
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
+
+    let (moderator, warnings) = Moderator::new(&config.moderation);
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
+
+    let (pii, warnings) = Scrubber::load(&paths::pii_file(), &config.pii, cipher.clone());
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
+
+    let (memory, warnings) = MemoryStore::load(&paths::memory_file(), cipher.clone());
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
+
+    let documents = rag::open_store(&config.index)
+        .and_then(|store| Retriever::open(store, embedder.clone()))
+        .unwrap_or_else(|e| {
+            UserInterface::print_error(&e);
+            None
+        });
+
+    let (redactor, warnings) = Redactor::new(&config.redact);
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
+
+    let (command_handler, warnings) = CommandHandler::new(config.aliases);
+    for warning in &warnings {
+        UserInterface::print_error(warning);
+    }
+
+    let mut app = ChatApplication::new(
+        provider,
+        brave_client,
+        hooks,
+        CommandHooks::new(config.hooks),
+        command_handler,
+        LineEditor::new(history, config.editor.vi_mode),
+        SessionStore::new(&paths::sessions_dir(), cipher.clone()),
+        memory,
+        documents,
+        embedder,
+        config.index,
+    );
+    app.conversation_manager.set_context(config.context);
+    app.http_allowlist = Allowlist::new(&config.http.allow);
+    app.egress = Egress::new(&config.http.egress);
+    app.workspace = repo::top_level().or_else(|| std::env::current_dir().ok());
+    app.read_only = args.read_only || config.trust.read_only;
+    app.workspace_trusted = app
+        .workspace
+        .as_deref()
+        .and_then(|workspace| config.trust.decision(workspace));
+    app.ocr = Ocr::from_config(&config.ocr);
+    app.fetcher = Fetcher::from_config(&config.fetch, &app.egress);
+    app.clipboard_tool = config.clipboard.tool;
+    app.notify_after = config
+        .notifications
+        .enabled
+        .then(|| Duration::from_secs(config.notifications.after_secs));
+    app.sound = config.notifications.sound.filter(|sound| !sound.is_empty());
+    app.code_theme = config.chat.theme;
+    app.suggest_follow_ups = config.chat.follow_ups;
+    app.budget = config.budget.clone();
+    app.telemetry = Telemetry::load(&paths::telemetry_file(), config.telemetry.endpoint);
+    app.ignore_caps = args.ignore_caps;
+    app.dry_run = args.dry_run;
+    app.redactor = redactor;
+    app.pii = pii;
+    app.moderator = moderator;
+    app.environment = config.environment.clone();
+    match Ledger::open(&paths::usage_db()) {
+        Ok(ledger) => app.ledger = Some(ledger),
+        Err(e) => UserInterface::print_error(&e),
+    }
+    match FeedbackLog::open(&paths::feedback_db()) {
+        Ok(feedback) => app.feedback = Some(feedback),
+        Err(e) => UserInterface::print_error(&e),
+    }
+    app.settings = config.settings.clone();
+    app.default_settings = config.settings;
+    app.personas = config.personas;
+    if let Some(seed) = &args.seed {
+        match seed.parse() {
+            Ok(seed) => app.settings.generation.seed = Some(seed),
+            Err(_) => {
+                UserInterface::print_error(&format!("--seed takes a whole number, not '{}'", seed));
+                Failure::Usage.exit();
+            }
+        }
+    }
+    app.apply_settings();
+    if let Some(model) = &config.chat.model {
+        app.model_manager.set_default(model);
+    }
+    // The model last chosen in this project comes before the configured one.
+    if let Some(model) = app.projects.model() {
+        app.model_manager.set_default(model);
+    }
+    if let Some(name) = &args.persona {
+        if let Err(e) = app.use_persona(name) {
+            UserInterface::print_error(&e);
+            Failure::Usage.exit();
+        }
+        // Enter picks the persona's model at startup.
+        let model = app.model_manager.get_current_model().to_string();
+        app.model_manager.set_default(&model);
+    }
+    if let Err(e) = app.load_plugins().await {
+        UserInterface::print_error(&e.to_string());
+    }
+    let result = match &command {
+        CliCommand::Ask { prompt } => app.run_once(prompt).await,
+        CliCommand::Quick { prompt } => app.run_quick(prompt).await,
+        CliCommand::ExplainLast { question } => app.explain_last(question).await,
+        CliCommand::Cmd { request } => app.run_cmd(request).await,
+        _ => app.run().await,
+    };
+    if let Err(e) = &result {
+        crash::offer_report(&format!("error: {}", e));
+    }
+    result
 }
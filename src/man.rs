@@ -0,0 +1,265 @@
+//! `rusty man`: the manual page, made from the command line's own
+//! definition and the tables behind `/help`, so that it cannot fall
+//! behind them. Packages install it with `rusty man > rusty.1`, or with
+//! a page for each subcommand too, which the main one refers to, with
+//! `rusty man <dir>`.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use clap::CommandFactory;
+use clap_mangen::Man;
+use roff::{bold, italic, roman, Roff};
+
+use crate::{locale, CliArgs, COMMANDS, SHORTCUTS};
+
+/// The tables of the configuration file and the keys each takes. Keep in
+/// step with `rusty_core::config`.
+const CONFIG_SECTIONS: &[(&str, &str, &str)] = &[
+    (
+        "chat",
+        "The chat's defaults.",
+        "model, theme, locale, accessible, raw_math, diff_view, git_prompt, keep_title, follow_ups",
+    ),
+    (
+        "styles",
+        "Colors of rendered Markdown.",
+        "headers, bold, italic, inline_code, code_block, quote, bullet",
+    ),
+    ("editor", "The prompt line.", "vi_mode"),
+    ("history", "Prompt history.", "max_entries, dedupe, ignore"),
+    (
+        "storage",
+        "Encryption of sessions, history and memory.",
+        "encrypt",
+    ),
+    (
+        "context",
+        "What is kept of long conversations.",
+        "strategy, keep_last, max_tokens, reserve_tokens, summarize",
+    ),
+    (
+        "environment",
+        "What the model is told about this machine.",
+        "os, shell, cwd, git, date",
+    ),
+    (
+        "budget",
+        "Token limits and spending caps.",
+        "session_tokens, request_tokens, daily, monthly",
+    ),
+    (
+        "settings",
+        "The defaults of what /set changes.",
+        "temperature, top_p, max_tokens, seed, stop, n, logit_bias, search_count, goggle, summarizer, citations, tool_choice, tools.<name>",
+    ),
+    (
+        "groq",
+        "Requests to Groq.",
+        "round_robin, max_concurrent, requests_per_minute, retry",
+    ),
+    (
+        "embeddings",
+        "The embedding model of the document index.",
+        "model, base_url, api_key_env, retry",
+    ),
+    ("index", "Where the document index is kept.", "store"),
+    (
+        "search",
+        "The location searches are made from.",
+        "location, city, state, country, postal_code, latitude, longitude",
+    ),
+    ("fetch", "How web pages are fetched.", "user_agent, robots, delay_ms"),
+    ("http", "Hosts that tools may reach.", "allow, egress"),
+    ("trust", "Trusted workspaces and read-only mode.", "trusted, untrusted, read_only"),
+    ("redact", "Masking of secrets before they are sent.", "enabled, patterns"),
+    ("pii", "Pseudonyms for personal data.", "enabled, emails, phones, names"),
+    ("moderation", "Checks on prompts and answers.", "rules, endpoint, fail_closed"),
+    (
+        "hooks",
+        "Shell commands run around each turn.",
+        "pre_submit, post_response, pre_tool_exec, timeout_secs, statusline",
+    ),
+    ("ocr", "Reading text from images.", "languages, vision_model, base_url, api_key_env"),
+    ("clipboard", "The clipboard tool.", "tool"),
+    ("notifications", "Desktop notifications for long answers.", "enabled, after_secs, sound"),
+    ("telemetry", "Where usage counts are sent, if anywhere.", "endpoint"),
+    ("git", "Git hook helpers.", "model"),
+    ("discord", "The Discord bot.", "model"),
+    ("telegram", "The Telegram bot.", "model, users"),
+    ("slack", "The Slack app.", "model, context_tokens"),
+    ("webhook", "The webhook listener.", "listen, model, routes"),
+    ("schedule", "Scheduled prompts.", "model, jobs"),
+    ("watch", "rusty watch.", "model, prompt"),
+    ("aliases", "Slash commands of your own, by name.", "<name>"),
+    (
+        "personas",
+        "Named system prompts, settings and tools for /persona.",
+        "<name>.description, system, model, settings, tools",
+    ),
+    (
+        "models",
+        "What models can do and cost, by model id.",
+        "<model>.context_window, tools, vision, input_price, output_price",
+    ),
+];
+
+const ENVIRONMENT: &[(&str, &str)] = &[
+    ("GROQ_API_KEY", "The key of the Groq API."),
+    ("BRAVE_API_KEY", "The key of Brave Search."),
+    (
+        "GITHUB_TOKEN",
+        "A token for the github tool, for private repositories and higher limits.",
+    ),
+    ("DISCORD_BOT_TOKEN", "The token of the Discord bot."),
+    ("TELEGRAM_BOT_TOKEN", "The token of the Telegram bot."),
+    (
+        "SLACK_BOT_TOKEN, SLACK_APP_TOKEN",
+        "The tokens of the Slack app.",
+    ),
+    (
+        "RUSTY_WEBHOOK_SECRET",
+        "The secret webhook requests are signed with.",
+    ),
+    (
+        "RUSTY_PASSPHRASE",
+        "Unlocks encrypted storage without asking.",
+    ),
+    (
+        "RUSTY_CONFIG_DIR",
+        "The configuration directory, instead of $XDG_CONFIG_HOME/rusty or ~/.config/rusty.",
+    ),
+    (
+        "RUSTY_PROVIDER, RUSTY_MOCK_SCRIPT",
+        "As --provider and --mock-script.",
+    ),
+    (
+        "VISUAL, EDITOR",
+        "The editor of /editor, /edit --editor and Ctrl+X Ctrl+E.",
+    ),
+    ("PAGER", "The pager of long output such as /history."),
+    (
+        "RUSTY_LAST_COMMAND, RUSTY_LAST_STATUS",
+        "The last command and its exit status, as the hook of rusty shell-init sets them for rusty explain-last.",
+    ),
+];
+
+const FILES: &[(&str, &str)] = &[
+    ("config.toml", "The configuration file."),
+    (".env", "API keys, read as environment variables."),
+    (
+        "RUSTY.md",
+        "Standing instructions; also read from the working directory and its parents.",
+    ),
+    ("sessions/", "Saved sessions."),
+    ("history", "Prompt history."),
+    ("memory", "What the model was asked to remember."),
+    ("prompts/", "Prompt templates for /use."),
+    ("plugins/, scripts/", "Tool plugins and scripting hooks."),
+    ("locales/", "Translations of the interface of your own."),
+    (
+        "usage.sqlite, feedback.sqlite",
+        "The usage ledger and the ratings.",
+    ),
+];
+
+const EXIT_STATUS: &[(&str, &str)] = &[
+    ("0", "Success."),
+    ("1", "Any other error."),
+    ("2", "Wrong usage, such as -q without a prompt."),
+    ("3", "The API key was refused."),
+    ("4", "Still rate-limited after retrying."),
+    ("5", "The API could not be reached."),
+    (
+        "6",
+        "A tool call was refused; the answer was made without it.",
+    ),
+];
+
+/// Writes the manual page to stdout, or, given `dir`, writes it there
+/// with one for each subcommand as `rusty-<command>.1`.
+pub fn generate(dir: Option<&Path>) -> io::Result<()> {
+    let Some(dir) = dir else {
+        let mut out = io::stdout().lock();
+        return render(&mut out);
+    };
+    fs::create_dir_all(dir)?;
+    let mut command = <CliArgs as CommandFactory>::command().disable_help_subcommand(true);
+    // Built, subcommands are named `rusty-<command>`, as their pages are.
+    command.build();
+    for subcommand in command.get_subcommands() {
+        clap_mangen::generate_to(subcommand.clone(), dir)?;
+    }
+    let path = dir.join("rusty.1");
+    render(&mut File::create(&path)?)?;
+    println!("Wrote the manual pages to {}", dir.display());
+    Ok(())
+}
+
+/// The page of `rusty` itself: what clap knows of the command line, and
+/// the chat's commands, keys, configuration and files.
+fn render(out: &mut dyn Write) -> io::Result<()> {
+    let man = Man::new(<CliArgs as CommandFactory>::command()).manual("Rusty Manual");
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    man.render_options_section(out)?;
+    man.render_subcommands_section(out)?;
+
+    let mut page = Roff::new();
+    page.control("SH", ["CHAT COMMANDS"]);
+    page.text([roman(
+        "Lines starting with a slash are commands; anything else is sent to the model.",
+    )]);
+    for (usage, description) in COMMANDS {
+        entry(&mut page, usage, locale::t(description));
+    }
+    page.control("SH", ["KEYS"]);
+    for (keys, description) in SHORTCUTS {
+        entry(&mut page, keys, locale::t(description));
+    }
+
+    page.control("SH", ["CONFIGURATION"]);
+    page.text([
+        roman("Settings are read from "),
+        italic("config.toml"),
+        roman(" in the configuration directory; "),
+        bold("rusty config"),
+        roman(" reads and changes them. Its tables and their keys:"),
+    ]);
+    for (section, description, keys) in CONFIG_SECTIONS {
+        page.control("TP", []);
+        page.text([bold(format!("[{}]", section))]);
+        page.text([roman(format!("{} Keys: {}.", description, keys))]);
+    }
+
+    table(&mut page, "ENVIRONMENT", ENVIRONMENT);
+    page.control("SH", ["FILES"]);
+    page.text([roman(
+        "In $RUSTY_CONFIG_DIR, else $XDG_CONFIG_HOME/rusty, ~/.config/rusty or %APPDATA%\\rusty:",
+    )]);
+    for (file, description) in FILES {
+        entry(&mut page, file, description);
+    }
+    table(&mut page, "EXIT STATUS", EXIT_STATUS);
+    page.to_writer(out)?;
+
+    man.render_version_section(out)?;
+    out.flush()
+}
+
+/// A tagged paragraph: `term` in bold, then what it is.
+fn entry(page: &mut Roff, term: &str, description: &str) {
+    page.control("TP", []);
+    page.text([bold(term)]);
+    page.text([roman(description)]);
+}
+
+fn table(page: &mut Roff, title: &str, rows: &[(&str, &str)]) {
+    page.control("SH", [title]);
+    for (term, description) in rows {
+        entry(page, term, description);
+    }
+}
@@ -0,0 +1,474 @@
+//! LaTeX math in answers made readable in a terminal: `$…$`, `\(…\)`,
+//! `$$…$$` and `\[…\]` spans are turned into Unicode, with Greek letters
+//! and symbols, superscripts and subscripts where Unicode has them,
+//! fractions as `a/b` and roots as `√x`. What has no Unicode form is
+//! written out plainly, such as `e^(iπ)` for a superscript with no
+//! raised form.
+
+/// `markdown` with the math in it converted, leaving code spans alone.
+pub fn render(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+    while let Some(start) = rest.find(['$', '\\', '`']) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with('`') {
+            // A code span runs to the next run of as many backticks.
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let fence = &rest[..ticks];
+            let end = rest[ticks..]
+                .find(fence)
+                .map_or(rest.len(), |i| i + 2 * ticks);
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let span = [("$$", "$$"), ("\\[", "\\]"), ("\\(", "\\)")]
+            .iter()
+            .find_map(|(open, close)| {
+                let inner = rest.strip_prefix(open)?;
+                let end = inner.find(close)?;
+                Some((&inner[..end], open.len() + end + close.len()))
+            })
+            .or_else(|| inline_dollars(rest));
+        match span {
+            Some((latex, length)) if !latex.trim().is_empty() => {
+                out.push_str(&to_unicode(latex.trim()));
+                rest = &rest[length..];
+            }
+            _ => {
+                // An escaped dollar, or a backslash that starts no math.
+                let length = if rest.starts_with("\\$") { 2 } else { 1 };
+                out.push_str(&rest[..length]);
+                rest = &rest[length..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The math of a `$…$` span at the start of `text`, and its length. Like
+/// Pandoc, the opening `$` must not be followed by a space, and the closing
+/// one neither preceded by a space nor followed by a digit, so that prices
+/// such as "$5 and $10" are left alone.
+fn inline_dollars(text: &str) -> Option<(&str, usize)> {
+    let inner = text.strip_prefix('$')?;
+    if inner.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let line = inner.split('\n').next().unwrap_or_default();
+    let mut from = 0;
+    while let Some(i) = line[from..].find('$').map(|i| from + i) {
+        let before = line[..i].chars().next_back();
+        let after = line[i + 1..].chars().next();
+        if i > 0
+            && before.is_some_and(|c| !c.is_whitespace() && c != '\\')
+            && !after.is_some_and(|c| c.is_ascii_digit())
+        {
+            return Some((&line[..i], i + 2));
+        }
+        from = i + 1;
+    }
+    None
+}
+
+/// A LaTeX formula in Unicode.
+pub fn to_unicode(latex: &str) -> String {
+    let mut parser = Parser {
+        chars: latex.chars().collect(),
+        position: 0,
+    };
+    let text = parser.sequence(false);
+    // Spacing commands and dropped delimiters leave runs of spaces.
+    let mut out = String::new();
+    for c in text.trim().chars() {
+        if c == ' ' && out.ends_with(' ') {
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    /// Everything up to the end, or to the `}` closing a group.
+    fn sequence(&mut self, group: bool) -> String {
+        let mut out = String::new();
+        while let Some(c) = self.next() {
+            match c {
+                '}' if group => break,
+                '}' => {}
+                '{' => out.push_str(&self.sequence(true)),
+                '^' | '_' => {
+                    let argument = self.argument();
+                    out.push_str(&script(&argument, c == '^'));
+                }
+                '\\' => out.push_str(&self.command()),
+                '&' | '~' => out.push(' '),
+                '\'' => out.push('′'),
+                '-' => out.push('−'),
+                c if c.is_whitespace() => out.push(' '),
+                c => out.push(c),
+            }
+        }
+        out
+    }
+
+    /// What a command or script applies to: a group, a command, or a
+    /// single character.
+    fn argument(&mut self) -> String {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+        match self.next() {
+            Some('{') => self.sequence(true),
+            Some('\\') => self.command(),
+            Some('-') => "−".to_string(),
+            Some(c) => c.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// The argument of `\text` and the like, as it is written.
+    fn text(&mut self) -> String {
+        if self.peek() != Some('{') {
+            return self.argument();
+        }
+        self.position += 1;
+        let mut depth = 0;
+        let mut text = String::new();
+        while let Some(c) = self.next() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => text.push(c),
+            }
+        }
+        text
+    }
+
+    /// The `[n]` of `\sqrt[n]{x}`.
+    fn optional(&mut self) -> Option<String> {
+        if self.peek() != Some('[') {
+            return None;
+        }
+        self.position += 1;
+        let mut text = String::new();
+        while let Some(c) = self.next() {
+            if c == ']' {
+                break;
+            }
+            text.push(c);
+        }
+        Some(text)
+    }
+
+    /// A command, its backslash already read.
+    fn command(&mut self) -> String {
+        let mut name = String::new();
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            name.extend(self.next());
+        }
+        if name.is_empty() {
+            name.extend(self.next());
+        }
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.argument();
+                let denominator = self.argument();
+                fraction(&numerator, &denominator)
+            }
+            "sqrt" => {
+                let index = self.optional();
+                let radicand = self.argument();
+                let sign = match index.as_deref().map(str::trim) {
+                    Some("3") => "∛",
+                    Some("4") => "∜",
+                    Some(index) if !index.is_empty() => {
+                        return format!("{}{}", script(index, true), root("√", &radicand));
+                    }
+                    _ => "√",
+                };
+                root(sign, &radicand)
+            }
+            "text" | "textrm" | "textbf" | "textit" | "mbox" => self.text(),
+            "mathrm" | "mathbf" | "mathit" | "mathsf" | "mathtt" | "boldsymbol"
+            | "operatorname" => self.argument(),
+            "mathbb" => self.argument().chars().map(double_struck).collect(),
+            "hat" | "widehat" => combine(&self.argument(), '\u{302}'),
+            "bar" | "overline" => combine(&self.argument(), '\u{304}'),
+            "vec" => combine(&self.argument(), '\u{20D7}'),
+            "dot" => combine(&self.argument(), '\u{307}'),
+            "ddot" => combine(&self.argument(), '\u{308}'),
+            "tilde" | "widetilde" => combine(&self.argument(), '\u{303}'),
+            "left" | "right" | "bigl" | "bigr" | "Bigl" | "Bigr" | "big" | "Big" => {
+                // `\left.` stands for no delimiter.
+                if self.peek() == Some('.') {
+                    self.position += 1;
+                }
+                String::new()
+            }
+            "begin" | "end" => {
+                let environment = self.argument();
+                // The column spec of an array.
+                if name == "begin" && environment == "array" {
+                    self.argument();
+                }
+                String::new()
+            }
+            "\\" => "\n".to_string(),
+            "," | ":" | ";" | " " | "quad" => " ".to_string(),
+            "qquad" => "  ".to_string(),
+            "!" => String::new(),
+            "|" => "‖".to_string(),
+            _ => match symbol(&name) {
+                Some(symbol) => symbol.to_string(),
+                // `\sin`, `\log` and the like, and anything unknown.
+                None => name,
+            },
+        }
+    }
+}
+
+/// `a/b`, or the fraction's own character, such as `½`.
+fn fraction(numerator: &str, denominator: &str) -> String {
+    let vulgar = match (numerator.trim(), denominator.trim()) {
+        ("1", "2") => Some("½"),
+        ("1", "3") => Some("⅓"),
+        ("2", "3") => Some("⅔"),
+        ("1", "4") => Some("¼"),
+        ("3", "4") => Some("¾"),
+        ("1", "5") => Some("⅕"),
+        ("1", "6") => Some("⅙"),
+        ("1", "8") => Some("⅛"),
+        ("1", "10") => Some("⅒"),
+        _ => None,
+    };
+    match vulgar {
+        Some(vulgar) => vulgar.to_string(),
+        None => format!("{}/{}", group(numerator), group(denominator)),
+    }
+}
+
+fn root(sign: &str, radicand: &str) -> String {
+    format!("{}{}", sign, group(radicand))
+}
+
+/// `text` in parentheses unless it is a single term.
+fn group(text: &str) -> String {
+    let text = text.trim();
+    let single = text
+        .chars()
+        .all(|c| !c.is_whitespace() && !"+−-=·×/,<>≤≥".contains(c));
+    if single {
+        text.to_string()
+    } else {
+        format!("({})", text)
+    }
+}
+
+/// `text` raised or lowered: in superscript or subscript characters when
+/// all of them have one, or else after `^` or `_`.
+fn script(text: &str, raised: bool) -> String {
+    let text = text.trim();
+    let convert = if raised { superscript } else { subscript };
+    let converted: Option<String> = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(convert)
+        .collect();
+    match converted {
+        Some(converted) if !converted.is_empty() => converted,
+        _ if text.chars().count() == 1 => format!("{}{}", if raised { '^' } else { '_' }, text),
+        _ => format!("{}({})", if raised { '^' } else { '_' }, text),
+    }
+}
+
+fn superscript(c: char) -> Option<char> {
+    let from = "0123456789+−-=()niabcdefghjklmoprstuvwxyzT′";
+    let index = from.find(c)?;
+    "⁰¹²³⁴⁵⁶⁷⁸⁹⁺⁻⁻⁼⁽⁾ⁿⁱᵃᵇᶜᵈᵉᶠᵍʰʲᵏˡᵐᵒᵖʳˢᵗᵘᵛʷˣʸᶻᵀ′"
+        .chars()
+        .nth(from[..index].chars().count())
+}
+
+fn subscript(c: char) -> Option<char> {
+    let from = "0123456789+−-=()aehijklmnoprstuvxβγρφχ";
+    let index = from.find(c)?;
+    "₀₁₂₃₄₅₆₇₈₉₊₋₋₌₍₎ₐₑₕᵢⱼₖₗₘₙₒₚᵣₛₜᵤᵥₓᵦᵧᵨᵩᵪ"
+        .chars()
+        .nth(from[..index].chars().count())
+}
+
+fn double_struck(c: char) -> char {
+    match c {
+        'N' => 'ℕ',
+        'Z' => 'ℤ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'C' => 'ℂ',
+        'P' => 'ℙ',
+        'H' => 'ℍ',
+        c => c,
+    }
+}
+
+/// `text` with a combining accent after its first character, as in `x̂`.
+fn combine(text: &str, accent: char) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) if text.chars().count() == 1 => format!("{}{}", first, accent),
+        Some(_) => format!("{}{}", group(text), accent),
+        None => String::new(),
+    }
+}
+
+/// The character a symbol command stands for.
+fn symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" | "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" | "vartheta" => "θ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" | "varpi" => "π",
+        "rho" | "varrho" => "ρ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" | "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "times" => "×",
+        "cdot" => "·",
+        "div" => "÷",
+        "pm" => "±",
+        "mp" => "∓",
+        "ast" => "∗",
+        "star" => "⋆",
+        "circ" => "∘",
+        "bullet" => "•",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "neq" | "ne" => "≠",
+        "ll" => "≪",
+        "gg" => "≫",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "cong" => "≅",
+        "propto" => "∝",
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "int" => "∫",
+        "iint" => "∬",
+        "iiint" => "∭",
+        "oint" => "∮",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        "uparrow" => "↑",
+        "downarrow" => "↓",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "subset" => "⊂",
+        "subseteq" => "⊆",
+        "supset" => "⊃",
+        "supseteq" => "⊇",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "emptyset" | "varnothing" => "∅",
+        "forall" => "∀",
+        "exists" => "∃",
+        "nexists" => "∄",
+        "neg" | "lnot" => "¬",
+        "land" | "wedge" => "∧",
+        "lor" | "vee" => "∨",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "angle" => "∠",
+        "perp" => "⊥",
+        "parallel" => "∥",
+        "mid" | "vert" => "|",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "prime" => "′",
+        "degree" => "°",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        "aleph" => "ℵ",
+        "therefore" => "∴",
+        "because" => "∵",
+        "checkmark" => "✓",
+        "{" | "lbrace" => "{",
+        "}" | "rbrace" => "}",
+        "%" => "%",
+        "$" => "$",
+        "&" => "&",
+        "#" => "#",
+        "_" => "_",
+        _ => return None,
+    })
+}
@@ -0,0 +1,217 @@
+//! The Ctrl+K command palette, a fuzzy-filtered list drawn below the prompt
+//! box, and the Ctrl+R history search and the `@` file finder drawn the
+//! same way.
+
+use std::io::{self, Write};
+
+use colored::*;
+use termimad::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use termimad::crossterm::terminal;
+use unicode_width::UnicodeWidthStr;
+
+use crate::fit_width;
+
+const MAX_VISIBLE: usize = 10;
+
+/// What choosing a palette entry does.
+#[derive(Debug, Clone)]
+pub enum PaletteAction {
+    /// Run the line immediately, as if typed and submitted.
+    Submit(String),
+    /// Put the text into the input line for editing.
+    Insert(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PaletteItem {
+    pub label: String,
+    pub detail: String,
+    pub action: PaletteAction,
+}
+
+impl PaletteItem {
+    pub fn new(label: &str, detail: &str, action: PaletteAction) -> Self {
+        Self {
+            label: label.to_string(),
+            detail: detail.to_string(),
+            action,
+        }
+    }
+}
+
+/// Scores `text` against `query` as a case-insensitive subsequence match,
+/// favoring consecutive characters and word starts. `None` means no match.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let mut score = 0i64;
+    let mut qi = 0;
+    let mut previous: Option<usize> = None;
+    for (ti, c) in text.iter().enumerate() {
+        if qi == query.len() {
+            break;
+        }
+        if *c != query[qi] {
+            continue;
+        }
+        score += 10;
+        if previous.is_some_and(|p| p + 1 == ti) {
+            score += 15;
+        }
+        if ti == 0 || !text[ti - 1].is_alphanumeric() {
+            score += 10;
+        }
+        previous = Some(ti);
+        qi += 1;
+    }
+
+    (qi == query.len()).then(|| score - text.len() as i64)
+}
+
+/// Runs the palette until an entry is chosen or it is dismissed. Expects raw
+/// mode and the cursor on the prompt line; leaves the cursor back there.
+pub fn pick(items: &[PaletteItem]) -> io::Result<Option<PaletteAction>> {
+    run("Command palette:", 'k', |query| {
+        filter(items, query).into_iter().cloned().collect()
+    })
+}
+
+/// Runs an incremental search, as Ctrl+R does in shells: `find` gives the
+/// entries matching what has been typed so far, the most recent first.
+/// Ctrl+R again moves on to the next older match.
+pub fn search(find: impl Fn(&str) -> Vec<PaletteItem>) -> io::Result<Option<PaletteAction>> {
+    run("History search:", 'r', find)
+}
+
+/// Runs the file finder `@` opens: `find` gives the files matching what has
+/// been typed so far, best first.
+pub fn files(find: impl Fn(&str) -> Vec<PaletteItem>) -> io::Result<Option<PaletteAction>> {
+    run("Attach file:", '@', find)
+}
+
+/// The list under the prompt box, with `title` before the query. `opened_by`
+/// is the key that opened it: Ctrl+K closes the palette again, while
+/// Ctrl+R in the search moves on to the next match.
+fn run(
+    title: &str,
+    opened_by: char,
+    find: impl Fn(&str) -> Vec<PaletteItem>,
+) -> io::Result<Option<PaletteAction>> {
+    let mut query = String::new();
+    let mut selected = 0usize;
+
+    // Skip past the bottom border and the hint line of the prompt box.
+    print!("\r\n\r\n\r\n");
+
+    let chosen = loop {
+        let matches = find(&query);
+        selected = selected.min(matches.len().saturating_sub(1));
+        draw(title, &query, &matches, selected)?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => break None,
+            KeyCode::Char('r') if ctrl && opened_by == 'r' => selected += 1,
+            KeyCode::Char('c' | 'k') if ctrl => break None,
+            KeyCode::Enter => break matches.get(selected).map(|item| item.action.clone()),
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab => selected += 1,
+            KeyCode::Char('n') if ctrl => selected += 1,
+            KeyCode::Backspace => {
+                query.pop();
+                selected = 0;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                query.push(c);
+                selected = 0;
+            }
+            _ => {}
+        }
+    };
+
+    print!("\r\x1b[J\x1b[3A");
+    io::stdout().flush()?;
+    Ok(chosen)
+}
+
+fn filter<'a>(items: &'a [PaletteItem], query: &str) -> Vec<&'a PaletteItem> {
+    let mut scored: Vec<(i64, usize, &PaletteItem)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| {
+            let haystack = format!("{} {}", item.label, item.detail);
+            fuzzy_score(query, &haystack).map(|score| (score, i, item))
+        })
+        .collect();
+    // Best score first; ties keep the caller's order.
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, _, item)| item).collect()
+}
+
+fn draw(title: &str, query: &str, matches: &[PaletteItem], selected: usize) -> io::Result<()> {
+    let orange = Color::TrueColor {
+        r: 255,
+        g: 140,
+        b: 0,
+    };
+    let gray = Color::TrueColor {
+        r: 100,
+        g: 100,
+        b: 100,
+    };
+    let width = match terminal::size() {
+        Ok((w, _)) if w > 0 => w as usize,
+        _ => 80,
+    };
+
+    // Keep the selection in view when it moves past the first page.
+    let offset = selected.saturating_sub(MAX_VISIBLE - 1);
+    let visible = &matches[offset..matches.len().min(offset + MAX_VISIBLE)];
+
+    let mut out = io::stdout();
+    write!(out, "\r\x1b[J  {} {}", title.color(orange).bold(), query)?;
+    if matches.is_empty() {
+        write!(out, "\r\n    {}", "No matches".color(gray))?;
+    }
+    for (i, item) in visible.iter().enumerate() {
+        let label = fit_width(&item.label, width.saturating_sub(6));
+        let label = format!(
+            "{}{}",
+            label,
+            " ".repeat(26usize.saturating_sub(label.width()).max(1))
+        );
+        let room = width.saturating_sub(label.width() + 8);
+        let detail = fit_width(&item.detail, room);
+        if offset + i == selected {
+            write!(
+                out,
+                "\r\n  {} {}{}",
+                "❯".color(orange).bold(),
+                label.color(orange).bold(),
+                detail.color(gray)
+            )?;
+        } else {
+            write!(out, "\r\n    {}{}", label, detail.color(gray))?;
+        }
+    }
+
+    // Return to the end of the query line.
+    let drawn = visible.len().max(usize::from(matches.is_empty()));
+    if drawn > 0 {
+        write!(out, "\x1b[{}A", drawn)?;
+    }
+    let column = 2 + title.width() + 1 + query.chars().count();
+    write!(out, "\r\x1b[{}C", column)?;
+    out.flush()
+}
@@ -0,0 +1,131 @@
+//! A list to choose from with the arrow keys and Enter, drawn below the
+//! cursor, as for the model.
+
+use std::io::{self, Write};
+
+use colored::*;
+use termimad::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use termimad::crossterm::terminal;
+use unicode_width::UnicodeWidthStr;
+
+use crate::fit_width;
+
+pub struct Choice {
+    pub label: String,
+    /// Shown dimmed after the label.
+    pub detail: String,
+}
+
+/// Disables raw mode and shows the cursor again when dropped.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        print!("\x1b[?25l");
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?25h");
+        let _ = io::stdout().flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Shows `choices` between `title` and `hint` with `selected` highlighted
+/// until one is chosen, with Enter or by its number, or the list is
+/// dismissed with Esc or Ctrl+C, which gives `None`. What was drawn is
+/// cleared afterwards.
+pub fn pick(
+    title: &str,
+    hint: &str,
+    choices: &[Choice],
+    selected: usize,
+) -> io::Result<Option<usize>> {
+    let _raw = RawMode::enable()?;
+    let mut selected = selected.min(choices.len().saturating_sub(1));
+    let chosen = loop {
+        draw(title, hint, choices, selected)?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => break None,
+            KeyCode::Char('c') if ctrl => break None,
+            KeyCode::Enter => break Some(selected),
+            KeyCode::Up | KeyCode::BackTab => selected = selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => selected = selected.saturating_sub(1),
+            KeyCode::Char('k') => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab | KeyCode::Char('j') => selected += 1,
+            KeyCode::Char('n') if ctrl => selected += 1,
+            KeyCode::Home => selected = 0,
+            KeyCode::End => selected = choices.len(),
+            KeyCode::Char(c) => {
+                let number = c.to_digit(10).unwrap_or(0) as usize;
+                if (1..=choices.len()).contains(&number) {
+                    break Some(number - 1);
+                }
+            }
+            _ => {}
+        }
+        selected = selected.min(choices.len().saturating_sub(1));
+    };
+    print!("\r\x1b[J");
+    io::stdout().flush()?;
+    Ok(chosen)
+}
+
+/// Draws the list from the cursor down and puts the cursor back.
+fn draw(title: &str, hint: &str, choices: &[Choice], selected: usize) -> io::Result<()> {
+    let orange = Color::TrueColor {
+        r: 255,
+        g: 140,
+        b: 0,
+    };
+    let gray = Color::TrueColor {
+        r: 100,
+        g: 100,
+        b: 100,
+    };
+    let width = match terminal::size() {
+        Ok((w, _)) if w > 0 => w as usize,
+        _ => 80,
+    };
+    let label_width = choices.iter().map(|c| c.label.width()).max().unwrap_or(0) + 2;
+
+    let mut out = io::stdout();
+    write!(out, "\r\x1b[J{}", title.color(orange).bold())?;
+    for (i, choice) in choices.iter().enumerate() {
+        let label = fit_width(&choice.label, width.saturating_sub(10));
+        let label = format!(
+            "{}{}",
+            label,
+            " ".repeat(label_width.saturating_sub(label.width()))
+        );
+        let room = width.saturating_sub(label.width() + 10);
+        let detail = fit_width(&choice.detail, room);
+        let number = format!("[{}]", i + 1);
+        if i == selected {
+            write!(
+                out,
+                "\r\n  {} {} {}{}",
+                "❯".color(orange).bold(),
+                number.color(orange),
+                label.color(orange).bold(),
+                detail.color(gray)
+            )?;
+        } else {
+            write!(out, "\r\n    {} {}{}", number, label, detail.color(gray))?;
+        }
+    }
+    write!(out, "\r\n  {}", hint.color(gray))?;
+    write!(out, "\x1b[{}A\r", choices.len() + 1)?;
+    out.flush()
+}
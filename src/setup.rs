@@ -0,0 +1,214 @@
+//! `rusty setup`, which also runs on the first start: picks the provider,
+//! takes the API keys and checks them before keeping them, and chooses the
+//! default model, the code theme and where the keys are stored. The choices
+//! go to `.env` and the configuration file.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use colored::*;
+use rusty_core::models::MODELS;
+use rusty_core::{paths, BraveSearchClient, Config, GroqApiClient};
+
+use crate::ConfigManager;
+
+/// The providers Rusty can talk to: name, key variable, and where to get a key.
+const PROVIDERS: &[(&str, &str, &str)] =
+    &[("GroqCloud", "GROQ_API_KEY", "https://console.groq.com/keys")];
+
+/// The highlighting themes that come with syntect, the default first.
+const THEMES: &[&str] = &[
+    "base16-mocha.dark",
+    "base16-ocean.dark",
+    "base16-eighties.dark",
+    "base16-ocean.light",
+    "InspiredGitHub",
+    "Solarized (dark)",
+    "Solarized (light)",
+];
+
+const BRAVE_KEY_URL: &str = "https://brave.com/search/api/";
+
+/// The keys chosen, for the session that follows the setup, since they are
+/// not necessarily saved anywhere it would find them.
+pub struct Keys {
+    pub groq: String,
+    pub brave: String,
+}
+
+pub async fn run() -> Result<Keys, String> {
+    let orange = Color::TrueColor {
+        r: 255,
+        g: 140,
+        b: 0,
+    };
+    let heading = |number: usize, title: &str| {
+        println!(
+            "\n{} {}",
+            format!("{}/5", number).color(orange).bold(),
+            title.bold()
+        );
+    };
+    println!(
+        "\n{} Answer a few questions to get started; Enter takes the default.",
+        "Welcome to Rusty!".color(orange).bold()
+    );
+
+    heading(1, "Provider");
+    for (i, (name, ..)) in PROVIDERS.iter().enumerate() {
+        println!("  [{}] {}", i + 1, name);
+    }
+    let (provider, key_name, key_url) = PROVIDERS[choose(PROVIDERS.len(), 1)?];
+
+    heading(2, "API keys");
+    let groq = loop {
+        let key = read_key(&format!("{} API key", provider), key_name, key_url)?;
+        match GroqApiClient::new(key.clone()).check_keys().await {
+            Ok(true) => {
+                println!("  ⎿  Key accepted");
+                break key;
+            }
+            Ok(false) => println!("  ⎿  {} refused this key. Try again.", provider),
+            Err(e) => {
+                println!("  ⎿  {}. Keeping the key unchecked.", e);
+                break key;
+            }
+        }
+    };
+    let brave = loop {
+        let key = read_key("Brave Search API key", "BRAVE_API_KEY", BRAVE_KEY_URL)?;
+        match BraveSearchClient::new(key.clone()).check_key().await {
+            Ok(true) => {
+                println!("  ⎿  Key accepted");
+                break key;
+            }
+            Ok(false) => println!("  ⎿  Brave Search refused this key. Try again."),
+            Err(e) => {
+                println!("  ⎿  {}. Keeping the key unchecked.", e);
+                break key;
+            }
+        }
+    };
+
+    heading(3, "Default model");
+    for (i, model) in MODELS.iter().enumerate() {
+        println!("  [{}] {}", i + 1, model);
+    }
+    let model = MODELS[choose(MODELS.len(), 1)?];
+
+    heading(4, "Code theme");
+    for (i, theme) in THEMES.iter().enumerate() {
+        println!("  [{}] {}", i + 1, theme);
+    }
+    let theme = THEMES[choose(THEMES.len(), 1)?];
+
+    heading(5, "Where to keep the keys");
+    let global = paths::env_file();
+    let local = ConfigManager::get_config_path();
+    println!("  [1] {}, read wherever you start Rusty", global.display());
+    println!(
+        "  [2] {}, read when Rusty starts in this directory",
+        local.display()
+    );
+    println!("  [3] Nowhere; I set GROQ_API_KEY and BRAVE_API_KEY in my environment");
+    let env_file = match choose(3, 1)? {
+        0 => Some(global),
+        1 => Some(local),
+        _ => None,
+    };
+
+    if let Some(path) = &env_file {
+        save_keys(path, &[(key_name, &groq), ("BRAVE_API_KEY", &brave)])?;
+    }
+    let config_path = Config::path();
+    if !config_path.exists() {
+        if let Some(dir) = config_path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        fs::write(
+            &config_path,
+            "# Rusty's configuration. The README describes every setting.\n\n[chat]\n",
+        )
+        .map_err(|e| format!("Failed to write {}: {}", config_path.display(), e))?;
+    }
+    Config::set_value("chat", "model", model)?;
+    Config::set_value("chat", "theme", theme)?;
+
+    println!();
+    if let Some(path) = &env_file {
+        println!("  ⎿  Saved the keys to {}", path.display());
+    }
+    println!(
+        "  ⎿  Saved your choices to {}. Run rusty setup to change them.\n",
+        config_path.display()
+    );
+    Ok(Keys { groq, brave })
+}
+
+/// Asks for a choice between 1 and `count` until one is given, and returns
+/// its position in the list.
+fn choose(count: usize, default: usize) -> Result<usize, String> {
+    loop {
+        print!("  Choice [{}]: ", default);
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        let mut input = String::new();
+        if io::stdin()
+            .read_line(&mut input)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            return Err("Setup was cancelled".to_string());
+        }
+        match input.trim() {
+            "" => return Ok(default - 1),
+            choice => match choice.parse::<usize>() {
+                Ok(n) if (1..=count).contains(&n) => return Ok(n - 1),
+                _ => println!("  Enter a number from 1 to {}.", count),
+            },
+        }
+    }
+}
+
+/// Asks for a key without showing it. When one is already set, Enter keeps
+/// it.
+fn read_key(label: &str, name: &str, url: &str) -> Result<String, String> {
+    let current = ConfigManager::load_key(name).ok();
+    println!("  Get one at {}", url.underline());
+    let hint = if current.is_some() {
+        " (Enter keeps the current one)"
+    } else {
+        ""
+    };
+    loop {
+        let key = rpassword::prompt_password(format!("  {}{}: ", label, hint))
+            .map_err(|e| format!("Setup was cancelled: {}", e))?;
+        match (key.trim(), &current) {
+            ("", Some(current)) => return Ok(current.clone()),
+            ("", None) => println!("  A key is needed to continue."),
+            (key, _) if key.starts_with('/') => {
+                println!("  That looks like a command, not a key.")
+            }
+            (key, _) => return Ok(key.to_string()),
+        }
+    }
+}
+
+/// Writes the keys to the `.env` file at `path`, readable only by you where
+/// the system allows.
+fn save_keys(path: &Path, keys: &[(&str, &str)]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    for (name, value) in keys {
+        ConfigManager::save_key(path, name, value)?;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+    }
+    Ok(())
+}
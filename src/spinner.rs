@@ -0,0 +1,138 @@
+//! The animated status line shown while the model thinks and tools run.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use colored::*;
+
+use crate::editor::Typed;
+
+const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A one-line spinner with the time since the turn began and what is
+/// happening now. It redraws from a thread of its own, so it must be paused
+/// before anything else is printed; pausing clears the line.
+///
+/// When not animated (output is not a terminal, or debug output would
+/// interleave with it) the word is printed once, as a plain line.
+pub struct Spinner {
+    word: String,
+    color: Color,
+    started: Instant,
+    animate: bool,
+    phase: Arc<Mutex<String>>,
+    /// Text typed ahead during the turn, shown at the end of the line.
+    typed: Option<Arc<Mutex<Typed>>>,
+    running: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl Spinner {
+    pub fn start(word: &str, color: Color, phase: &str, animate: bool) -> Self {
+        let mut spinner = Self {
+            word: word.to_string(),
+            color,
+            started: Instant::now(),
+            animate,
+            phase: Arc::new(Mutex::new(phase.to_string())),
+            typed: None,
+            running: None,
+        };
+        if animate {
+            spinner.resume(phase);
+        } else {
+            println!("{} {}...", "\n*".color(color), word.color(color));
+        }
+        spinner
+    }
+
+    /// A spinner that never shows, for quiet mode.
+    pub fn hidden() -> Self {
+        Self {
+            word: String::new(),
+            color: Color::White,
+            started: Instant::now(),
+            animate: false,
+            phase: Arc::new(Mutex::new(String::new())),
+            typed: None,
+            running: None,
+        }
+    }
+
+    /// Shows what is typed ahead at the end of the line from now on.
+    pub fn show_typed(&mut self, typed: Arc<Mutex<Typed>>) {
+        let was_running = self.running.is_some();
+        self.pause();
+        self.typed = Some(typed);
+        if was_running {
+            let phase = self.phase.lock().map(|p| p.clone()).unwrap_or_default();
+            self.resume(&phase);
+        }
+    }
+
+    /// Shows the spinner again, now describing `phase`.
+    pub fn resume(&mut self, phase: &str) {
+        if let Ok(mut current) = self.phase.lock() {
+            *current = phase.to_string();
+        }
+        if !self.animate || self.running.is_some() {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::spawn({
+            let stop = Arc::clone(&stop);
+            let phase = Arc::clone(&self.phase);
+            let word = self.word.clone();
+            let color = self.color;
+            let started = self.started;
+            let typed = self.typed.clone();
+            move || {
+                for frame in FRAMES.iter().cycle() {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let phase = phase.lock().map(|p| p.clone()).unwrap_or_default();
+                    let status = format!("({}s · {})", started.elapsed().as_secs(), phase);
+                    let preview = typed
+                        .as_ref()
+                        .and_then(|typed| typed.lock().ok().map(|t| t.preview()))
+                        .filter(|preview| !preview.is_empty())
+                        .map(|preview| format!("  {}", preview))
+                        .unwrap_or_default();
+                    print!(
+                        "\r\x1b[2K{} {} {}{}",
+                        frame.color(color),
+                        format!("{}...", word).color(color),
+                        status.dimmed(),
+                        preview
+                    );
+                    let _ = io::stdout().flush();
+                    thread::park_timeout(FRAME_INTERVAL);
+                }
+            }
+        });
+        self.running = Some((stop, handle));
+    }
+
+    /// Stops the animation and clears its line, leaving the cursor at the
+    /// start of it.
+    pub fn pause(&mut self) {
+        if let Some((stop, handle)) = self.running.take() {
+            stop.store(true, Ordering::Relaxed);
+            handle.thread().unpark();
+            let _ = handle.join();
+            print!("\r\x1b[2K");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.pause();
+    }
+}
@@ -0,0 +1,311 @@
+//! Markdown tables drawn to fit the terminal. Columns get the width their
+//! longest cell needs when there is room, and otherwise share what there
+//! is by how much they need, cells wrapping at word boundaries; each
+//! column keeps the alignment its delimiter row gives.
+
+use termimad::MadSkin;
+use unicode_width::UnicodeWidthChar;
+
+/// The narrowest a column is made, however little room there is.
+const MIN_COLUMN: usize = 3;
+
+/// A run of Markdown text, or a table found in it.
+pub enum Block {
+    Text(String),
+    Table(Table),
+}
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+pub struct Table {
+    header: Vec<String>,
+    align: Vec<Align>,
+    rows: Vec<Vec<String>>,
+}
+
+/// `markdown` split into its tables and the text around them.
+pub fn blocks(markdown: &str) -> Vec<Block> {
+    let lines: Vec<&str> = markdown.split_inclusive('\n').collect();
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let align = lines.get(i + 1).and_then(|line| delimiter(line));
+        let header = cells(lines[i]);
+        match align {
+            Some(align) if lines[i].contains('|') && header.len() == align.len() => {
+                if !text.is_empty() {
+                    blocks.push(Block::Text(std::mem::take(&mut text)));
+                }
+                let mut rows = Vec::new();
+                i += 2;
+                while i < lines.len() && lines[i].contains('|') && !lines[i].trim().is_empty() {
+                    let mut row = cells(lines[i]);
+                    row.resize(align.len(), String::new());
+                    rows.push(row);
+                    i += 1;
+                }
+                blocks.push(Block::Table(Table {
+                    header,
+                    align,
+                    rows,
+                }));
+            }
+            _ => {
+                text.push_str(lines[i]);
+                i += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        blocks.push(Block::Text(text));
+    }
+    blocks
+}
+
+/// The alignments a delimiter row such as `|:---|---:|` gives, or `None`
+/// when the line is not one.
+fn delimiter(line: &str) -> Option<Vec<Align>> {
+    if !line.contains('-') {
+        return None;
+    }
+    cells(line)
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Align::Center,
+                (false, true) => Align::Right,
+                _ => Align::Left,
+            })
+        })
+        .collect()
+}
+
+/// The cells of a row, split at pipes outside code spans and not escaped.
+fn cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => line,
+    };
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut code = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        match c {
+            '|' if escaped => {
+                cell.pop();
+                cell.push('|');
+            }
+            '|' if !code => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            '`' => {
+                code = !code;
+                cell.push(c);
+            }
+            _ => cell.push(c),
+        }
+        escaped = c == '\\';
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+impl Table {
+    /// The table drawn in `width` columns or fewer, its cells styled by
+    /// `skin`, with the borders in `border` color.
+    pub fn render(&self, width: usize, skin: &MadSkin, border: &str) -> String {
+        let widths = self.widths(width);
+        let reset = "\x1b[0m";
+        let rule = |left: &str, middle: &str, right: &str| {
+            let bars: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+            format!(
+                "{}{}{}{}{}\n",
+                border,
+                left,
+                bars.join(middle),
+                right,
+                reset
+            )
+        };
+
+        let mut out = rule("╭", "┬", "╮");
+        out.push_str(&self.row(&self.header, &widths, skin, border, true));
+        out.push_str(&rule("├", "┼", "┤"));
+        for row in &self.rows {
+            out.push_str(&self.row(row, &widths, skin, border, false));
+        }
+        out.push_str(&rule("╰", "┴", "╯"));
+        out
+    }
+
+    fn row(
+        &self,
+        cells: &[String],
+        widths: &[usize],
+        skin: &MadSkin,
+        border: &str,
+        header: bool,
+    ) -> String {
+        let wrapped: Vec<Vec<String>> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, &width)| wrap(cell, width))
+            .collect();
+        let height = wrapped.iter().map(Vec::len).max().unwrap_or(1);
+        let bar = format!("{}│\x1b[0m", border);
+        let mut out = String::new();
+        for line in 0..height {
+            out.push_str(&bar);
+            for (column, lines) in wrapped.iter().enumerate() {
+                let text = lines.get(line).map_or("", String::as_str);
+                let gap = widths[column].saturating_sub(visible_width(text));
+                let (before, after) = match self.align[column] {
+                    Align::Left => (0, gap),
+                    Align::Center => (gap / 2, gap - gap / 2),
+                    Align::Right => (gap, 0),
+                };
+                let styled = if header && !text.is_empty() {
+                    skin.inline(&format!("**{}**", text.replace("**", "")))
+                        .to_string()
+                } else {
+                    skin.inline(text).to_string()
+                };
+                out.push_str(&format!(
+                    " {}{}{} {}",
+                    " ".repeat(before),
+                    styled,
+                    " ".repeat(after),
+                    bar
+                ));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// The width of each column's text: what its longest cell needs if all
+    /// fit, or else a share of what is left once each has room for its
+    /// longest word, in proportion to how much more it needs.
+    fn widths(&self, width: usize) -> Vec<usize> {
+        let columns = self.align.len();
+        let all_rows = || std::iter::once(&self.header).chain(&self.rows);
+        let measure = |f: fn(&str) -> usize| -> Vec<usize> {
+            (0..columns)
+                .map(|c| {
+                    all_rows()
+                        .map(|row| f(&row[c]))
+                        .max()
+                        .unwrap_or(0)
+                        .max(MIN_COLUMN)
+                })
+                .collect()
+        };
+        let natural = measure(visible_width);
+        let longest_word = measure(|cell| {
+            cell.split_whitespace()
+                .map(visible_width)
+                .max()
+                .unwrap_or(0)
+        });
+
+        // Each column has a space either side and a bar after it.
+        let room = width.saturating_sub(columns * 3 + 1);
+        if natural.iter().sum::<usize>() <= room {
+            return natural;
+        }
+        let least: usize = longest_word.iter().sum();
+        if least >= room {
+            // Even words must break: share the room by the words' lengths.
+            return longest_word
+                .iter()
+                .map(|w| (w * room / least.max(1)).max(MIN_COLUMN))
+                .collect();
+        }
+        let spare = room - least;
+        let wanted: usize = natural.iter().zip(&longest_word).map(|(n, w)| n - w).sum();
+        natural
+            .iter()
+            .zip(&longest_word)
+            .map(|(n, w)| w + (n - w) * spare / wanted.max(1))
+            .collect()
+    }
+}
+
+/// The width a cell's text takes once its emphasis and code markers are
+/// styles rather than characters.
+fn visible_width(text: &str) -> usize {
+    text.replace("**", "")
+        .replace("~~", "")
+        .chars()
+        // Private-use characters mark links, see `console::mark_links`.
+        .filter(|c| !matches!(c, '`' | '*' | '\u{E000}' | '\u{E001}'))
+        .map(|c| c.width().unwrap_or(0))
+        .sum()
+}
+
+/// A cell's text in lines of at most `width`, broken between words, and
+/// within words longer than a line. Bold and code spans cut by a break are
+/// closed at the end of the line and opened again on the next.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let needed = visible_width(&line) + usize::from(!line.is_empty()) + visible_width(word);
+        if needed <= width {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        let mut rest = word;
+        while visible_width(rest) > width {
+            let mut taken = 0;
+            let end = rest
+                .char_indices()
+                .find(|(_, c)| {
+                    taken += c.width().unwrap_or(0);
+                    taken > width
+                })
+                .map_or(rest.len(), |(i, _)| i);
+            lines.push(rest[..end].to_string());
+            rest = &rest[end..];
+        }
+        line = rest.to_string();
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    let mut open: Vec<&str> = Vec::new();
+    for line in &mut lines {
+        let reopened: String = open.concat();
+        for marker in ["**", "`"] {
+            let was_open = open.contains(&marker);
+            if line.matches(marker).count() % 2 == 1 {
+                if was_open {
+                    open.retain(|m| *m != marker);
+                } else {
+                    open.push(marker);
+                }
+            }
+        }
+        let closing: String = open.iter().rev().copied().collect();
+        *line = format!("{}{}{}", reopened, line, closing);
+    }
+    lines
+}